@@ -0,0 +1,295 @@
+use blockchain_rpc_lib::config::ServiceConfig;
+use blockchain_rpc_lib::jobs::allow_access::AccessTarget;
+use clap::{Args, Parser, Subcommand};
+use eyre::{Result, WrapErr, eyre};
+use reqwest::{Method, RequestBuilder};
+use serde_json::{Value, json};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "secure-rpc-gateway",
+    version,
+    about = "Secure RPC Gateway Blueprint"
+)]
+pub struct Cli {
+    /// Defaults to `run`.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Boot the gateway from its config, check its listeners, backends, signing key and
+    /// webhooks, print a report and exit non-zero if any check failed.
+    #[arg(long)]
+    pub self_test: bool,
+    /// Load and validate the gateway's config without starting it, print every problem found
+    /// and exit non-zero if there are any.
+    #[arg(long)]
+    pub check_config: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the gateway and the Tangle job runner.
+    Run {
+        /// Arguments passed through to the Blueprint environment.
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, hide = true)]
+        args: Vec<String>,
+    },
+    /// Parse and validate a config file without starting anything.
+    CheckConfig {
+        #[arg(long, default_value = "config.toml")]
+        config: PathBuf,
+    },
+    /// List and edit firewall rules of a running gateway through its admin API.
+    Rules {
+        #[command(subcommand)]
+        action: RulesCommand,
+        #[command(flatten)]
+        admin: AdminArgs,
+    },
+    /// Grant temporary access through the admin API of a running gateway.
+    Grant {
+        #[command(flatten)]
+        target: TargetArgs,
+        /// How long access lasts, in seconds.
+        #[arg(long)]
+        duration: u64,
+        /// Grant on a `[plans]` tier, limited by its rate limit and methods.
+        #[arg(long)]
+        plan: Option<String>,
+        #[command(flatten)]
+        admin: AdminArgs,
+    },
+    /// Export per-account usage or grant history for invoicing through the admin API of a
+    /// running gateway.
+    Billing {
+        #[command(subcommand)]
+        export: BillingCommand,
+        #[command(flatten)]
+        admin: AdminArgs,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RulesCommand {
+    /// Print config and dynamic allow/deny rules.
+    List,
+    /// Add a dynamic allow rule, or a ban with `--deny`.
+    Add {
+        #[command(flatten)]
+        target: TargetArgs,
+        #[arg(long)]
+        deny: bool,
+    },
+    /// Remove a dynamic allow rule.
+    Remove {
+        #[command(flatten)]
+        target: TargetArgs,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BillingCommand {
+    /// Print each account's usage per metering period (`[metering]`).
+    Usage {
+        #[command(flatten)]
+        range: BillingArgs,
+    },
+    /// Print grants, extensions and revocations of access from the audit log (`[audit]`).
+    Grants {
+        #[command(flatten)]
+        range: BillingArgs,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct BillingArgs {
+    /// Start of the range, RFC 3339 (e.g. `2026-09-01T00:00:00Z`). Defaults to the earliest
+    /// record kept.
+    #[arg(long)]
+    from: Option<String>,
+    /// End of the range, RFC 3339. Defaults to now.
+    #[arg(long)]
+    to: Option<String>,
+    /// Only this AccountId32 (SS58).
+    #[arg(long)]
+    account: Option<String>,
+    /// Print CSV rather than JSON.
+    #[arg(long)]
+    csv: bool,
+}
+
+impl BillingArgs {
+    fn query(&self) -> Vec<(&'static str, &str)> {
+        let mut query = vec![("format", if self.csv { "csv" } else { "json" })];
+        for (name, value) in [
+            ("from", &self.from),
+            ("to", &self.to),
+            ("account", &self.account),
+        ] {
+            if let Some(value) = value {
+                query.push((name, value));
+            }
+        }
+        query
+    }
+}
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+pub struct TargetArgs {
+    /// IP address or CIDR range.
+    #[arg(long)]
+    ip: Option<String>,
+    /// AccountId32 (SS58).
+    #[arg(long)]
+    account: Option<String>,
+}
+
+impl TargetArgs {
+    fn access_target(self) -> AccessTarget {
+        match (self.ip, self.account) {
+            (Some(ip), _) => AccessTarget::Ip(ip),
+            (None, Some(account)) => AccessTarget::Account(account),
+            // clap's argument group requires exactly one of them.
+            (None, None) => unreachable!("either --ip or --account is required"),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct AdminArgs {
+    /// Base URL of the gateway's admin API (`[admin] listen_addr`).
+    #[arg(
+        long,
+        env = "SECURE_RPC_ADMIN_URL",
+        default_value = "http://127.0.0.1:9615"
+    )]
+    admin_url: String,
+    /// Admin bearer token (`[admin] token`).
+    #[arg(long, env = "SECURE_RPC_ADMIN_TOKEN", hide_env_values = true)]
+    admin_token: Option<String>,
+}
+
+impl AdminArgs {
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let url = format!("{}{}", self.admin_url.trim_end_matches('/'), path);
+        let request = reqwest::Client::new().request(method, url);
+        match &self.admin_token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+}
+
+/// Runs an operator subcommand. `run` is handled by the caller.
+pub async fn execute(command: Command) -> Result<()> {
+    match command {
+        Command::Run { .. } => unreachable!("`run` is handled by main"),
+        Command::CheckConfig { config } => check_config(config),
+        Command::Rules { action, admin } => match action {
+            RulesCommand::List => {
+                print_response(admin.request(Method::GET, "/firewall/rules")).await
+            }
+            RulesCommand::Add { target, deny } => {
+                let path = if deny {
+                    "/firewall/deny"
+                } else {
+                    "/firewall/allow"
+                };
+                print_response(
+                    admin
+                        .request(Method::POST, path)
+                        .json(&target.access_target()),
+                )
+                .await
+            }
+            RulesCommand::Remove { target } => {
+                print_response(
+                    admin
+                        .request(Method::DELETE, "/firewall/allow")
+                        .json(&target.access_target()),
+                )
+                .await
+            }
+        },
+        Command::Grant {
+            target,
+            duration,
+            plan,
+            admin,
+        } => {
+            print_response(
+                admin
+                    .request(Method::POST, "/firewall/temporary")
+                    .json(&json!({
+                        "target": target.access_target(),
+                        "duration_secs": duration,
+                        "plan": plan,
+                    })),
+            )
+            .await
+        }
+        Command::Billing { export, admin } => {
+            let (path, range) = match &export {
+                BillingCommand::Usage { range } => ("/billing/usage", range),
+                BillingCommand::Grants { range } => ("/billing/grants", range),
+            };
+            print_response(admin.request(Method::GET, path).query(&range.query())).await
+        }
+    }
+}
+
+pub fn check_config(path: PathBuf) -> Result<()> {
+    let config = match ServiceConfig::load(&path) {
+        Ok(config) => config,
+        Err(blockchain_rpc_lib::Error::InvalidConfig(problems)) => {
+            println!("{} has {} problem(s):", path.display(), problems.len());
+            for problem in &problems {
+                println!("  {problem}");
+            }
+            return Err(eyre!("Invalid config file {}", path.display()));
+        }
+        Err(e) => {
+            return Err(e).wrap_err_with(|| format!("Invalid config file {}", path.display()));
+        }
+    };
+    println!("{} is valid", path.display());
+    println!("  listen_addr:  {}", config.rpc.listen_addr);
+    println!("  proxy_to_url: {}", config.rpc.proxy_to_url);
+    println!(
+        "  firewall:     {} allowed IPs, {} allowed accounts, {} denied IPs, {} denied accounts{}",
+        config.firewall.allow_ips.len(),
+        config.firewall.allow_accounts.len(),
+        config.firewall.deny_ips.len(),
+        config.firewall.deny_accounts.len(),
+        if config.firewall.allow_unrestricted_access {
+            " (unrestricted access enabled)"
+        } else {
+            ""
+        },
+    );
+    match config.admin.listen_addr {
+        Some(listen_addr) => println!("  admin API:    {listen_addr}"),
+        None => println!("  admin API:    disabled"),
+    }
+    Ok(())
+}
+
+/// Sends an admin API request and prints the response body, pretty-printed if it is JSON.
+async fn print_response(request: RequestBuilder) -> Result<()> {
+    let response = request
+        .send()
+        .await
+        .wrap_err("Failed to reach the admin API")?;
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        return Err(eyre!("Admin API returned {status}: {body}"));
+    }
+    match serde_json::from_str::<Value>(&body) {
+        Ok(value) => println!("{}", serde_json::to_string_pretty(&value)?),
+        Err(_) if body.is_empty() => println!("OK"),
+        Err(_) => println!("{body}"),
+    }
+    Ok(())
+}
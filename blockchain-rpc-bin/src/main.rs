@@ -1,8 +1,14 @@
+mod cli;
+
 use blockchain_rpc_blueprint_lib::{MyContext, SAY_HELLO_JOB_ID, say_hello};
-use blockchain_rpc_lib::config::ServiceConfig;
+use blockchain_rpc_lib::admin::start_admin_server;
+use blockchain_rpc_lib::config::{ServiceConfig, TelemetryConfig};
+use blockchain_rpc_lib::config_reload::spawn_config_reloader;
 use blockchain_rpc_lib::context::SecureRpcContext;
 use blockchain_rpc_lib::jobs;
-use blockchain_rpc_lib::rpc::start_rpc_gateway;
+use blockchain_rpc_lib::listener::start_rpc_gateway;
+use blockchain_rpc_lib::self_test::{self, CheckStatus};
+use blockchain_rpc_lib::telemetry::{self, TelemetryGuard};
 use blueprint_sdk::Job;
 use blueprint_sdk::Router;
 use blueprint_sdk::contexts::tangle::TangleClientContext;
@@ -16,6 +22,8 @@ use blueprint_sdk::tangle::consumer::TangleConsumer;
 use blueprint_sdk::tangle::filters::MatchesServiceId;
 use blueprint_sdk::tangle::layers::TangleLayer;
 use blueprint_sdk::tangle::producer::TangleProducer;
+use clap::Parser;
+use cli::{Cli, Command};
 use sp_core::sr25519::Pair as Sr25519Pair;
 use std::sync::Arc;
 use tower::filter::FilterLayer;
@@ -26,15 +34,41 @@ use tracing::level_filters::LevelFilter;
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     color_eyre::install().expect("Failed to install color_eyre");
-    configure_tracing("secure_rpc_gateway=debug,blueprint_sdk=info")?;
 
-    info!("Loading Blueprint environment...");
-    let env = BlueprintEnvironment::load()?;
+    // Operator subcommands run and exit; without one (or with `run`) the gateway starts.
+    let cli = Cli::parse();
+    match cli.command {
+        None | Some(Command::Run { .. }) => {}
+        Some(command) => {
+            if let Err(e) = cli::execute(command).await {
+                eprintln!("Error: {e:?}");
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+    }
 
-    info!("Loading service configuration...");
-    let service_config = ServiceConfig::load(env.config_dir().join("config.toml"))?;
+    // Tracing is set up once the service configuration (and its `[telemetry]` section) is known.
+    let env = BlueprintEnvironment::load()?;
+    let config_path = env.config_dir().join("config.toml");
+    // A dry run: report every problem with the config the gateway would start with, then exit.
+    if cli.check_config {
+        if let Err(e) = cli::check_config(config_path) {
+            eprintln!("Error: {e:?}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    let service_config = ServiceConfig::load(&config_path)?;
+    let _telemetry = setup_log(&service_config.telemetry)?;
+    let _metrics = telemetry::otlp_metrics(&service_config.telemetry)?;
     info!(?service_config, "Service configuration loaded");
 
+    if cli.self_test {
+        let passed = run_self_test(env, service_config).await;
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+
     info!("Setting up Tangle signer...");
     let signer_key = env
         .keystore()
@@ -58,9 +92,11 @@ async fn main() -> Result<(), Error> {
 
     info!("Creating service context...");
     let context = Arc::new(SecureRpcContext::new(env.clone(), service_config).await?);
+    spawn_config_reloader(&context, config_path)?;
 
     info!("Starting RPC gateway in background...");
     let gateway_handle = tokio::spawn(start_rpc_gateway(context.clone()));
+    let admin_handle = tokio::spawn(start_admin_server(context.clone()));
 
     info!("Building job router...");
     let router = Router::new()
@@ -76,6 +112,82 @@ async fn main() -> Result<(), Error> {
             jobs::REGISTER_WEBHOOK_JOB_ID,
             jobs::register_webhook::handler.layer(TangleLayer),
         )
+        .route(
+            jobs::ISSUE_API_KEY_JOB_ID,
+            jobs::issue_api_key::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::ACCESS_HISTORY_JOB_ID,
+            jobs::access_history::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::REVOKE_ACCESS_JOB_ID,
+            jobs::revoke_access::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::BAN_TARGET_JOB_ID,
+            jobs::ban_target::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::REPORT_USAGE_JOB_ID,
+            jobs::report_usage::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::USAGE_SUMMARY_JOB_ID,
+            jobs::usage_summary::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::QUERY_RULES_JOB_ID,
+            jobs::query_rules::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::UPDATE_CONFIG_JOB_ID,
+            jobs::update_config::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::SET_ACCOUNT_LABELS_JOB_ID,
+            jobs::set_account_labels::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::PATCH_CONFIG_JOB_ID,
+            jobs::patch_config::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::SET_ACCOUNT_ROLES_JOB_ID,
+            jobs::set_account_roles::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::SET_READ_ONLY_JOB_ID,
+            jobs::set_read_only::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::EXTEND_ACCESS_JOB_ID,
+            jobs::extend_access::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::SYNC_RULES_JOB_ID,
+            jobs::sync_rules::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::QUERY_USAGE_JOB_ID,
+            jobs::query_usage::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::PRICE_SCHEDULE_JOB_ID,
+            jobs::price_schedule::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::SWITCH_UPSTREAM_JOB_ID,
+            jobs::switch_upstream::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::REMOVE_WEBHOOK_JOB_ID,
+            jobs::remove_webhook::handler.layer(TangleLayer),
+        )
+        .route(
+            jobs::LIST_WEBHOOKS_JOB_ID,
+            jobs::list_webhooks::handler.layer(TangleLayer),
+        )
         .with_context(context.clone());
 
     info!("Starting Blueprint runner...");
@@ -86,25 +198,67 @@ async fn main() -> Result<(), Error> {
         .run()
         .await?;
 
-    info!("Blueprint runner finished. Waiting for gateway to shutdown...");
-    // If the runner exits (e.g., due to error or signal), wait for the gateway to finish.
+    info!("Blueprint runner finished. Shutting down background tasks and gateway...");
+    // If the runner exits (e.g., due to error or signal), stop background tasks and the
+    // gateway, then wait for the gateway to finish.
+    context.shutdown().await;
     let _ = gateway_handle.await;
+    let _ = admin_handle.await;
 
     info!("Secure RPC Gateway finished.");
     Ok(())
 }
 
-pub fn setup_log() {
+/// Boots the service context, runs the self-test against it and prints the report. Returns
+/// whether every check passed.
+async fn run_self_test(env: BlueprintEnvironment, service_config: ServiceConfig) -> bool {
+    let context = match SecureRpcContext::new(env, service_config).await {
+        Ok(context) => context,
+        Err(e) => {
+            println!("[FAIL] {:<24} {e}", "service context");
+            return false;
+        }
+    };
+    let report = self_test::run(&context).await;
+    context.shutdown().await;
+
+    for check in &report.checks {
+        let status = match check.status {
+            CheckStatus::Passed => "PASS",
+            CheckStatus::Failed => "FAIL",
+            CheckStatus::Skipped => "SKIP",
+        };
+        println!("[{status}] {:<24} {}", check.name, check.detail);
+    }
+    let passed = report.passed();
+    println!("Self-test {}", if passed { "passed" } else { "failed" });
+    passed
+}
+
+pub fn setup_log(
+    telemetry_config: &TelemetryConfig,
+) -> Result<Option<TelemetryGuard>, blockchain_rpc_lib::Error> {
+    use tracing_subscriber::layer::SubscriberExt;
     use tracing_subscriber::util::SubscriberInitExt;
 
-    let _ = tracing_subscriber::fmt::SubscriberBuilder::default()
-        .without_time()
-        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::NONE)
-        .with_env_filter(
+    let (otlp_layer, guard) = match telemetry::otlp_layer(telemetry_config)? {
+        Some((layer, guard)) => (Some(layer), Some(guard)),
+        None => (None, None),
+    };
+
+    let _ = tracing_subscriber::registry()
+        .with(
             tracing_subscriber::EnvFilter::builder()
                 .with_default_directive(LevelFilter::INFO.into())
                 .from_env_lossy(),
         )
-        .finish()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .without_time()
+                .with_span_events(tracing_subscriber::fmt::format::FmtSpan::NONE),
+        )
+        .with(otlp_layer)
         .try_init();
+
+    Ok(guard)
 }
@@ -0,0 +1,117 @@
+use crate::config::AccessEventConfig;
+use crate::firewall::{Firewall, WebhookEvent};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+/// Queues the events access checks raise, so requests never wait on (or pay for) webhook
+/// payloads, audit lines or event history. [`AccessEventDispatcher`] delivers them.
+#[derive(Debug, Clone)]
+pub struct AccessEvents {
+    sender: mpsc::Sender<WebhookEvent>,
+    counters: Arc<AccessEventCounters>,
+}
+
+#[derive(Debug, Default)]
+struct AccessEventCounters {
+    dropped: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+impl AccessEvents {
+    /// The queue, and the dispatcher delivering what is queued.
+    pub fn new(config: &AccessEventConfig) -> (Self, AccessEventDispatcher) {
+        let (sender, receiver) = mpsc::channel(config.queue_size.max(1));
+        let counters = Arc::new(AccessEventCounters::default());
+        let dispatcher = AccessEventDispatcher {
+            receiver,
+            window: Duration::from_secs(config.coalesce_window_secs),
+            last_delivered: HashMap::new(),
+            counters: counters.clone(),
+        };
+        (AccessEvents { sender, counters }, dispatcher)
+    }
+
+    /// Queues `event`, dropping it if the queue is full.
+    pub fn queue(&self, event: WebhookEvent) {
+        if self.sender.try_send(event).is_err() {
+            let dropped = self.counters.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if dropped.is_power_of_two() {
+                warn!(dropped, "Access event queue full, dropping events");
+            }
+        }
+    }
+
+    /// Events dropped because the queue was full, since startup.
+    pub fn dropped(&self) -> u64 {
+        self.counters.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Events not delivered because they repeated one delivered within the coalescing window,
+    /// since startup.
+    pub fn coalesced(&self) -> u64 {
+        self.counters.coalesced.load(Ordering::Relaxed)
+    }
+}
+
+/// Delivers queued access events through the firewall's webhooks, audit log and event history,
+/// skipping repeats of an event delivered within the coalescing window.
+#[derive(Debug)]
+pub struct AccessEventDispatcher {
+    receiver: mpsc::Receiver<WebhookEvent>,
+    window: Duration,
+    /// When each distinct event was last delivered.
+    last_delivered: HashMap<String, Instant>,
+    counters: Arc<AccessEventCounters>,
+}
+
+impl AccessEventDispatcher {
+    /// Delivers events until `shutdown`.
+    pub async fn run(mut self, firewall: Arc<Firewall>, shutdown: CancellationToken) {
+        let mut prune_interval = tokio::time::interval(self.window.max(Duration::from_secs(1)));
+        loop {
+            let event = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = prune_interval.tick() => {
+                    let window = self.window;
+                    self.last_delivered.retain(|_, at| at.elapsed() < window);
+                    continue;
+                }
+                event = self.receiver.recv() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+            };
+            if self.is_repeat(&event) {
+                self.counters.coalesced.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            firewall.notify_webhook(event).await;
+        }
+        debug!("Access event dispatcher stopped");
+    }
+
+    /// Whether an identical event was delivered within the window; records it if not.
+    fn is_repeat(&mut self, event: &WebhookEvent) -> bool {
+        if self.window.is_zero() {
+            return false;
+        }
+        // The event's fields identify it: type, source and how access was decided
+        let key = match serde_json::to_string(event) {
+            Ok(key) => key,
+            Err(_) => return false,
+        };
+        let now = Instant::now();
+        match self.last_delivered.get(&key) {
+            Some(at) if now.duration_since(*at) < self.window => true,
+            _ => {
+                self.last_delivered.insert(key, now);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,427 @@
+use crate::Result;
+use crate::config::{AccessLogConfig, AccessLogFormat};
+use crate::context::SecureRpcContext;
+use crate::request_id::RequestId;
+use axum::body::Body;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value;
+use sp_runtime::AccountId32;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+const ACCESS_DIR: &str = "access";
+const CURRENT_FILE: &str = "access.log";
+/// Entries waiting to be written; entries recorded while it is full are dropped.
+const QUEUE_CAPACITY: usize = 16 * 1024;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// How long entries still arriving at shutdown, e.g. closing WebSocket sessions, are waited for.
+const SHUTDOWN_GRACE: Duration = Duration::from_millis(500);
+
+/// What an access log entry records.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind")]
+pub enum AccessRecord {
+    /// An HTTP request and its response, once the response body has been sent or dropped.
+    Http {
+        client_ip: Option<IpAddr>,
+        account: Option<String>,
+        route: Option<String>,
+        /// The HTTP method.
+        method: String,
+        /// The JSON-RPC methods called, in request order.
+        rpc_methods: Vec<String>,
+        status: u16,
+        request_bytes: u64,
+        response_bytes: u64,
+        duration_ms: u64,
+        /// The backend the request was sent to, if it got that far.
+        upstream: Option<String>,
+        /// Also returned to the client and forwarded to the backend in `X-Request-Id`.
+        request_id: Option<String>,
+    },
+    /// A WebSocket session connected to its backend.
+    WebSocketOpen {
+        session_id: u64,
+        /// The upgrade request's ID.
+        request_id: Option<String>,
+        client_ip: IpAddr,
+        account: Option<String>,
+        route: String,
+        upstream: String,
+    },
+    /// A WebSocket session ended, with the frames it carried.
+    WebSocketClose {
+        session_id: u64,
+        client_ip: IpAddr,
+        account: Option<String>,
+        route: String,
+        duration_ms: u64,
+        /// Frames received from the client.
+        messages_in: u64,
+        /// Frames sent to the client.
+        messages_out: u64,
+        /// Who closed the session, or why the gateway did.
+        reason: String,
+    },
+}
+
+/// A line of the access log.
+#[derive(Debug, Clone, Serialize)]
+pub struct AccessEntry {
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub record: AccessRecord,
+}
+
+/// One line per request and per WebSocket session open and close, under
+/// `<data_dir>/access`, kept apart from tracing output. Entries are queued and written by an
+/// [`AccessLogWriter`], so a slow disk never holds up requests; when the queue is full entries
+/// are dropped and counted instead.
+#[derive(Debug)]
+pub struct AccessLog {
+    sender: mpsc::Sender<AccessEntry>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Writes queued access log entries to `access.log`, which is rotated to
+/// `access-<timestamp>.log` once it grows past `max_file_bytes`. Only the newest `max_files`
+/// rotated files are kept.
+#[derive(Debug)]
+pub struct AccessLogWriter {
+    receiver: mpsc::Receiver<AccessEntry>,
+    dropped: Arc<AtomicU64>,
+    dir: PathBuf,
+    format: AccessLogFormat,
+    max_file_bytes: u64,
+    max_files: usize,
+    file: BufWriter<File>,
+    size: u64,
+}
+
+impl AccessLog {
+    /// Opens the access log under `data_dir`, appending to its current file. The returned
+    /// writer must be run for entries to be written.
+    pub fn open(data_dir: &Path, config: &AccessLogConfig) -> Result<(Self, AccessLogWriter)> {
+        let dir = data_dir.join(ACCESS_DIR);
+        std::fs::create_dir_all(&dir)?;
+        let file = open_append(&dir.join(CURRENT_FILE))?;
+        let size = file.metadata()?.len();
+        info!(dir = %dir.display(), format = ?config.format, "Opened access log");
+
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let writer = AccessLogWriter {
+            receiver,
+            dropped: dropped.clone(),
+            dir,
+            format: config.format,
+            max_file_bytes: config.max_file_bytes.max(1),
+            max_files: config.max_files,
+            file: BufWriter::new(file),
+            size,
+        };
+        Ok((AccessLog { sender, dropped }, writer))
+    }
+
+    /// Queues an entry, timestamped now.
+    pub fn record(&self, record: AccessRecord) {
+        self.record_at(Utc::now(), record);
+    }
+
+    fn record_at(&self, timestamp: DateTime<Utc>, record: AccessRecord) {
+        if self
+            .sender
+            .try_send(AccessEntry { timestamp, record })
+            .is_err()
+        {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl AccessLogWriter {
+    /// Writes entries as they are queued, flushing every second, until `shutdown`.
+    pub async fn run(mut self, shutdown: CancellationToken) {
+        let mut flush_interval = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                entry = self.receiver.recv() => match entry {
+                    Some(entry) => self.write(&entry),
+                    None => break,
+                },
+                _ = flush_interval.tick() => self.flush(),
+            }
+        }
+        while let Ok(Some(entry)) = tokio::time::timeout(SHUTDOWN_GRACE, self.receiver.recv()).await
+        {
+            self.write(&entry);
+        }
+        self.flush();
+    }
+
+    fn write(&mut self, entry: &AccessEntry) {
+        if let Err(e) = self.append(entry) {
+            warn!(error = %e, "Failed to write access log entry");
+        }
+    }
+
+    fn append(&mut self, entry: &AccessEntry) -> Result<()> {
+        let mut line = match self.format {
+            AccessLogFormat::Json => serde_json::to_vec(entry)?,
+            AccessLogFormat::Text => format!(
+                "timestamp={} {}",
+                entry.timestamp.to_rfc3339(),
+                logfmt(&serde_json::to_value(&entry.record)?)
+            )
+            .into_bytes(),
+        };
+        line.push(b'\n');
+
+        if self.size > 0 && self.size + line.len() as u64 > self.max_file_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(&line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        self.file.flush()?;
+        let rotated = self.dir.join(format!(
+            "access-{}.log",
+            Utc::now().format("%Y%m%dT%H%M%S%.3fZ")
+        ));
+        std::fs::rename(self.dir.join(CURRENT_FILE), &rotated)?;
+        self.file = BufWriter::new(open_append(&self.dir.join(CURRENT_FILE))?);
+        self.size = 0;
+        info!(rotated = %rotated.display(), "Rotated access log");
+        if self.max_files > 0 {
+            self.remove_oldest()?;
+        }
+        Ok(())
+    }
+
+    /// Deletes rotated files beyond the newest `max_files`.
+    fn remove_oldest(&self) -> Result<()> {
+        let mut rotated: Vec<PathBuf> = std::fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("access-") && name.ends_with(".log"))
+            })
+            .collect();
+        // Timestamps are fixed-width, so names sort oldest first.
+        rotated.sort();
+        let excess = rotated.len().saturating_sub(self.max_files);
+        for path in &rotated[..excess] {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) {
+        if let Err(e) = self.file.flush() {
+            warn!(error = %e, "Failed to flush access log");
+        }
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            warn!(
+                dropped,
+                "Dropped access log entries while the writer was behind"
+            );
+        }
+    }
+}
+
+/// What the handler learned about a request, attached to it so it can be filled in as the
+/// request is handled and read back when the access log entry is written.
+#[derive(Debug, Clone, Default)]
+pub struct AccessDetails(Arc<Mutex<RequestDetails>>);
+
+#[derive(Debug, Default)]
+struct RequestDetails {
+    client_ip: Option<IpAddr>,
+    account: Option<String>,
+    route: Option<String>,
+    rpc_methods: Vec<String>,
+    upstream: Option<String>,
+}
+
+impl AccessDetails {
+    /// The client the request is from, after trusted proxies, and the route it matched.
+    pub fn set_client(&self, client_ip: IpAddr, route: &str) {
+        let mut details = self.0.lock();
+        details.client_ip = Some(client_ip);
+        details.route = Some(route.to_string());
+    }
+
+    pub fn set_account(&self, account: &AccountId32) {
+        self.0.lock().account = Some(account.to_string());
+    }
+
+    pub fn set_rpc_methods(&self, methods: Vec<String>) {
+        self.0.lock().rpc_methods = methods;
+    }
+
+    pub fn set_upstream(&self, upstream: String) {
+        self.0.lock().upstream = Some(upstream);
+    }
+
+    pub(crate) fn route(&self) -> Option<String> {
+        self.0.lock().route.clone()
+    }
+
+    pub(crate) fn account(&self) -> Option<String> {
+        self.0.lock().account.clone()
+    }
+
+    pub(crate) fn rpc_methods(&self) -> Vec<String> {
+        self.0.lock().rpc_methods.clone()
+    }
+}
+
+/// An HTTP request's entry, written when dropped, i.e. once its response body has been sent,
+/// or abandoned by a client that went away.
+struct PendingEntry {
+    log: Arc<AccessLog>,
+    timestamp: DateTime<Utc>,
+    started: Instant,
+    method: String,
+    request_id: Option<String>,
+    status: StatusCode,
+    details: AccessDetails,
+    request_bytes: Arc<AtomicU64>,
+    response_bytes: u64,
+}
+
+impl Drop for PendingEntry {
+    fn drop(&mut self) {
+        let details = std::mem::take(&mut *self.details.0.lock());
+        self.log.record_at(
+            self.timestamp,
+            AccessRecord::Http {
+                client_ip: details.client_ip,
+                account: details.account,
+                route: details.route,
+                method: std::mem::take(&mut self.method),
+                rpc_methods: details.rpc_methods,
+                status: self.status.as_u16(),
+                request_bytes: self.request_bytes.load(Ordering::Relaxed),
+                response_bytes: self.response_bytes,
+                duration_ms: elapsed_ms(self.started),
+                upstream: details.upstream,
+                request_id: self.request_id.take(),
+            },
+        );
+    }
+}
+
+/// Logs every request to the access log, if `[access_log]` is configured. Request and
+/// response bytes are counted as the bodies stream; WebSocket upgrades are logged by their
+/// session instead.
+pub(crate) async fn log_request(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(log) = ctx.access_log.clone() else {
+        return next.run(req).await;
+    };
+    let timestamp = Utc::now();
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let request_id = req.extensions().get::<RequestId>().map(ToString::to_string);
+    let details = AccessDetails::default();
+    details.0.lock().client_ip = Some(peer.ip());
+    let request_bytes = Arc::new(AtomicU64::new(0));
+
+    let counted = request_bytes.clone();
+    let mut req = req.map(|body| {
+        Body::from_stream(body.into_data_stream().inspect_ok(move |chunk| {
+            counted.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        }))
+    });
+    req.extensions_mut().insert(details.clone());
+
+    let response = next.run(req).await;
+    if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+        return response;
+    }
+    let mut pending = PendingEntry {
+        log,
+        timestamp,
+        started,
+        method,
+        request_id,
+        status: response.status(),
+        details,
+        request_bytes,
+        response_bytes: 0,
+    };
+    response.map(|body| {
+        Body::from_stream(body.into_data_stream().inspect_ok(move |chunk| {
+            pending.response_bytes += chunk.len() as u64;
+        }))
+    })
+}
+
+pub(crate) fn elapsed_ms(started: Instant) -> u64 {
+    u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX)
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    Ok(OpenOptions::new().create(true).append(true).open(path)?)
+}
+
+/// Renders a record as `key=value` pairs, quoting values with spaces, quotes or `=` and
+/// joining lists with commas. Absent values are left out.
+fn logfmt(record: &Value) -> String {
+    let Value::Object(fields) = record else {
+        return record.to_string();
+    };
+    let mut line = String::new();
+    for (key, value) in fields {
+        let value = match value {
+            Value::Null => continue,
+            Value::String(s) => s.clone(),
+            Value::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+            other => other.to_string(),
+        };
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(key);
+        line.push('=');
+        if value.is_empty() || value.contains([' ', '"', '=']) {
+            line.push_str(&Value::String(value).to_string());
+        } else {
+            line.push_str(&value);
+        }
+    }
+    line
+}
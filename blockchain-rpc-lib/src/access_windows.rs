@@ -0,0 +1,113 @@
+use crate::Result;
+use crate::error::Error;
+use chrono::{DateTime, Datelike, FixedOffset, NaiveTime, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// A recurring period during which an allow rule grants access, such as business hours or a
+/// maintenance window, e.g. weekdays from 09:00 to 17:00 at UTC+02:00.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessWindow {
+    /// Days the window opens on, e.g. `["Mon", "Fri"]`. Empty means every day.
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// Local time the window opens, e.g. `09:00`.
+    pub start: NaiveTime,
+    /// Local time the window closes. Earlier than `start` for a window spanning midnight, which
+    /// then ends the following day.
+    pub end: NaiveTime,
+    /// The offset from UTC `start` and `end` are in, e.g. `+02:00`. Defaults to UTC.
+    #[serde(default = "utc", with = "utc_offset")]
+    pub utc_offset: FixedOffset,
+}
+
+impl AccessWindow {
+    /// Rejects a window that is never open.
+    pub fn validate(&self) -> Result<()> {
+        if self.start == self.end {
+            return Err(Error::InvalidJobInput(format!(
+                "Access window opens and closes at the same time ({})",
+                self.start
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns true if the window is open at `now`.
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        let local = now.with_timezone(&self.utc_offset);
+        let time = local.time();
+        let opens_on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        if self.start < self.end {
+            opens_on(local.weekday()) && time >= self.start && time < self.end
+        } else {
+            (opens_on(local.weekday()) && time >= self.start)
+                || (opens_on(local.weekday().pred()) && time < self.end)
+        }
+    }
+
+    /// The first time after `now` the window opens or closes.
+    pub fn next_change(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let today = now.with_timezone(&self.utc_offset).date_naive();
+        (-1..=7)
+            .filter_map(|days| today.checked_add_signed(chrono::Duration::days(days)))
+            .filter(|day| self.days.is_empty() || self.days.contains(&day.weekday()))
+            .flat_map(|day| {
+                // A window spanning midnight closes the day after it opens
+                let closes_on = if self.end < self.start {
+                    day.succ_opt()
+                } else {
+                    Some(day)
+                };
+                [
+                    Some(day.and_time(self.start)),
+                    closes_on.map(|day| day.and_time(self.end)),
+                ]
+            })
+            .flatten()
+            .filter_map(|local| self.utc_offset.from_local_datetime(&local).single())
+            .map(|time| time.with_timezone(&Utc))
+            .filter(|time| *time > now)
+            .min()
+    }
+}
+
+/// Returns true if a rule restricted to `windows` applies at `now`. A rule without windows
+/// always applies.
+pub fn is_open(windows: &[AccessWindow], now: DateTime<Utc>) -> bool {
+    windows.is_empty() || windows.iter().any(|window| window.contains(now))
+}
+
+/// The first time after `now` any of `windows` opens or closes, and with it whether a rule
+/// restricted to them applies. `None` for a rule without windows.
+pub fn next_change(windows: &[AccessWindow], now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    windows
+        .iter()
+        .filter_map(|window| window.next_change(now))
+        .min()
+}
+
+fn utc() -> FixedOffset {
+    FixedOffset::east_opt(0).expect("zero is a valid offset")
+}
+
+/// (De)serializes a [`FixedOffset`] as `+HH:MM`.
+mod utc_offset {
+    use chrono::FixedOffset;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(
+        offset: &FixedOffset,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(offset)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<FixedOffset, D::Error> {
+        let offset = String::deserialize(deserializer)?;
+        offset
+            .parse()
+            .map_err(|e| D::Error::custom(format!("invalid UTC offset {offset}: {e}")))
+    }
+}
@@ -0,0 +1,1095 @@
+use crate::Result;
+use crate::audit::AuditRecord;
+use crate::auto_ban::AutoBan;
+use crate::billing::{self, BillingFormat};
+use crate::circuit_breaker::CircuitState;
+use crate::coalescing::CoalescingStats;
+use crate::context::{SecureRpcContext, TemporaryAccessRecord};
+use crate::error::Error;
+use crate::event_history::{DEFAULT_PAGE_SIZE, EventQuery};
+use crate::fingerprint::FingerprintStats;
+use crate::firewall::{
+    AccountAccessStatus, CleanupStats, FirewallRules, FirewallStats, TemporaryGrant,
+};
+use crate::head_cache::HeadCacheStatus;
+use crate::jobs::allow_access::AccessTarget;
+use crate::labels::Labels;
+use crate::latency::MethodLatency;
+use crate::metering::UsageSnapshot;
+use crate::origin_heatmap::HeatmapOrder;
+use crate::proxy::pool::PoolStats;
+use crate::proxy::upstream::AdmissionStats;
+use crate::read_only::ReadOnlyStatus;
+use crate::roles::Roles;
+use crate::rule_sync::RuleMutation;
+use crate::sessions::SessionStats;
+use crate::shadow_policy::{ProposedPolicy, ShadowReport};
+use crate::signature_auth::SIGNATURE_HEADER;
+use crate::switchover::{self, SwitchUpstreamRequest};
+use crate::traffic_stats::{GrafanaQuery, GrafanaSearch};
+use crate::upstream_events::UpstreamStats;
+use crate::upstream_probe::{self, ChainStatus, UpstreamCapabilities};
+use crate::webhooks::{WebhookEventType, WebhookSchema, WebhookTarget};
+use axum::{
+    Extension, Json, Router,
+    body::{Body, Bytes},
+    extract::{Path, Query, Request, State},
+    http::{
+        StatusCode,
+        header::{AUTHORIZATION, CONTENT_TYPE},
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+};
+use chrono::{DateTime, Utc};
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sp_runtime::AccountId32;
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use url::Url;
+
+/// Largest body a signed admin request may carry, axum's default limit for JSON bodies.
+const MAX_SIGNED_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Starts the operator admin API, if `[admin] listen_addr` is configured.
+///
+/// - `GET /sessions` lists open WebSocket sessions.
+/// - `DELETE /sessions/{id}` terminates a session.
+/// - `POST /auth/introspect` describes the API key in the JSON body (`{"token": "..."}`).
+/// - `GET /fingerprints?min_ips=N` lists client fingerprints seen from at least `N` IPs.
+/// - `GET /upstream/stats` reports backend connection, disconnection and failure totals, and
+///   the `[rpc.global_concurrency]` queue and each route's `[rpc.concurrency]` queue: depth,
+///   requests in flight, admitted and shed, and average and longest wait, each route's HTTP
+///   connection reuse (connections open and opened, requests sent), with `[rpc.latency]`,
+///   each method's latency histogram and percentiles, and with `[rpc.coalescing]`, the backend
+///   calls made for coalescable requests and the requests that shared another's.
+/// - `GET /upstream/health` adds the backend circuit breaker's state, probed capabilities
+///   (client version, chain ID, supported methods), chain verification status and the head
+///   cache's latest and finalized blocks and hits to those totals.
+/// - `GET /upstream/health/live` probes the backend now, uncached, reporting its latency, head
+///   block, client version and chain ID.
+/// - `GET /firewall/rules` lists config and dynamic allow/deny rules, `GET /firewall/stats`
+///   the sizes of the firewall's dynamic state.
+/// - `POST /firewall/allow` and `DELETE /firewall/allow` add and remove a dynamic allow rule,
+///   `POST /firewall/deny` bans, each taking an `AccessTarget` body (`{"Ip": "10.0.0.0/8"}` or
+///   `{"Account": "5Grw..."}`), like the `allow_access`, `revoke_access` and `ban_target` jobs.
+/// - `GET /firewall/temporary` lists active temporary grants, `POST` grants temporary access
+///   (`{"target": {"Account": "5Grw..."}, "duration_secs": 3600}`, optionally with a `"plan"`)
+///   like `pay_for_access`.
+/// - `POST /firewall/cleanup` removes expired temporary grants now.
+/// - `GET /firewall/auto-bans` lists active automatic bans, `DELETE` lifts one early, taking
+///   the banned network as an `AccessTarget` (`{"Ip": "203.0.113.7/32"}`).
+/// - `GET /firewall/events` pages through recorded firewall events, oldest first, if
+///   `[event_history]` is configured. `since` and `until` (RFC 3339) bound them in time,
+///   `types` (comma-separated event types) filters them, `limit` sizes the page and `after`
+///   takes the previous page's `next_cursor`.
+/// - `GET /firewall/heatmap?order=denied|requests&limit=N` lists the client /24 (IPv4) and /48
+///   (IPv6) prefixes with the most denials or requests within the rolling window, if
+///   `[origin_heatmap]` is enabled.
+/// - `GET /webhooks` lists registered webhooks, without their secrets.
+/// - `GET /webhooks/schema` describes the current webhook payload version and each event's
+///   fields.
+/// - `GET /usage` shows metered usage of the open period, the month so far, and the periods
+///   and months not yet reported, with the labels of the accounts in it, if `[metering]` is
+///   configured.
+/// - `GET /billing/usage` exports each account's usage per metering period and
+///   `GET /billing/grants` the grants, extensions and revocations of access from the audit log,
+///   for invoicing, if `[metering]` and `[audit]` respectively are configured. `from` and `to`
+///   (RFC 3339) bound the range, `account` keeps one account's rows and `format=csv` returns CSV
+///   rather than JSON.
+/// - `GET /accounts/labels` lists labelled accounts; `GET /accounts/{account}/labels` shows an
+///   account's labels and `PUT` replaces them with a JSON object of strings (`{}` clears them).
+/// - `GET /accounts/roles` lists the accounts holding roles, and their roles.
+/// - `GET /read-only` reports whether the gateway is read-only, `PUT` switches read-only mode
+///   on (`{"reason": "Backend upgrade"}`, reason optional) and `DELETE` switches it off.
+/// - `PUT /upstream` switches the default route to other backends, like the `switch_upstream`
+///   job (`{"proxy_to_url": "http://10.0.0.5:9933", "upstreams": [], "drain_secs": 30}`), and
+///   returns the old and new backends.
+/// - `GET /audit/verify` checks the audit log's hash chain, if `[audit]` is configured.
+/// - `GET /stats/traffic` returns the time-bucketed request, error, latency and connection
+///   series, overall and by route, method and account, if `[traffic_stats]` is configured.
+///   `since` and `until` (RFC 3339) bound the buckets. `GET /stats/grafana`,
+///   `POST /stats/grafana/search` and `POST /stats/grafana/query` serve the same series as a
+///   Grafana JSON datasource, with targets such as `requests`, `errors:route:/eth` or
+///   `p99_ms:method:eth_call`.
+/// - `PUT /policy/shadow` starts a dry run of a proposed firewall policy, `GET` reports on it
+///   and `DELETE` stops it.
+pub async fn start_admin_server(ctx: Arc<SecureRpcContext>) -> Result<()> {
+    let Some(listen_addr) = ctx.config().admin.listen_addr else {
+        return Ok(());
+    };
+    let open = ctx.config().admin.token.is_none() && ctx.config().admin.accounts.is_empty();
+    if open && !listen_addr.ip().is_loopback() {
+        return Err(Error::ConfigError(::config::ConfigError::Message(format!(
+            "Refusing to serve the admin API on non-loopback address {listen_addr} without \
+             admin.token or admin.accounts"
+        ))));
+    }
+    info!(%listen_addr, "Starting admin API");
+
+    let shutdown = ctx.tasks.shutdown_token();
+    let listener = tokio::net::TcpListener::bind(listen_addr).await?;
+
+    axum::serve(
+        listener,
+        Router::new()
+            .route("/sessions", get(list_sessions))
+            .route("/sessions/:id", delete(terminate_session))
+            .route("/auth/introspect", post(introspect))
+            .route("/fingerprints", get(list_fingerprints))
+            .route("/upstream/stats", get(upstream_stats))
+            .route("/upstream/health", get(upstream_health))
+            .route("/upstream/health/live", get(upstream_health_live))
+            .route("/firewall/rules", get(firewall_rules))
+            .route("/firewall/stats", get(firewall_stats))
+            .route(
+                "/firewall/allow",
+                post(add_allow_rule).delete(remove_allow_rule),
+            )
+            .route("/firewall/deny", post(add_deny_rule))
+            .route(
+                "/firewall/temporary",
+                get(temporary_grants).post(add_temporary_grant),
+            )
+            .route("/firewall/cleanup", post(run_cleanup))
+            .route("/firewall/auto-bans", get(auto_bans).delete(lift_auto_ban))
+            .route("/firewall/events", get(firewall_events))
+            .route("/firewall/heatmap", get(origin_heatmap))
+            .route("/webhooks", get(list_webhooks))
+            .route("/webhooks/schema", get(webhook_schema))
+            .route("/usage", get(usage))
+            .route("/billing/usage", get(billing_usage))
+            .route("/billing/grants", get(billing_grants))
+            .route("/accounts/labels", get(list_account_labels))
+            .route("/accounts/roles", get(list_account_roles))
+            .route(
+                "/accounts/:account/labels",
+                get(account_labels).put(set_account_labels),
+            )
+            .route(
+                "/read-only",
+                get(read_only_status)
+                    .put(enable_read_only)
+                    .delete(disable_read_only),
+            )
+            .route("/upstream", put(switch_upstream))
+            .route("/audit/verify", get(verify_audit_log))
+            .route("/stats/traffic", get(traffic_stats))
+            .route("/stats/grafana", get(grafana_datasource))
+            .route("/stats/grafana/search", post(grafana_search))
+            .route("/stats/grafana/query", post(grafana_query))
+            .route(
+                "/policy/shadow",
+                get(shadow_report)
+                    .put(start_shadow_policy)
+                    .delete(stop_shadow_policy),
+            )
+            .layer(middleware::from_fn_with_state(ctx.clone(), require_admin))
+            .with_state(ctx),
+    )
+    .with_graceful_shutdown(shutdown.cancelled_owned())
+    .await?;
+
+    Ok(())
+}
+
+async fn list_sessions(State(ctx): State<Arc<SecureRpcContext>>) -> Json<Vec<SessionStats>> {
+    Json(ctx.sessions.list())
+}
+
+async fn terminate_session(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Path(id): Path<u64>,
+) -> StatusCode {
+    if ctx.sessions.terminate(id) {
+        info!(session_id = id, "Session terminated by operator");
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct FingerprintQuery {
+    #[serde(default)]
+    min_ips: usize,
+}
+
+async fn list_fingerprints(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Query(query): Query<FingerprintQuery>,
+) -> Json<Vec<FingerprintStats>> {
+    Json(ctx.fingerprints.list(query.min_ips))
+}
+
+#[derive(Debug, Serialize)]
+struct UpstreamMetrics {
+    #[serde(flatten)]
+    stats: UpstreamStats,
+    /// The `[rpc.global_concurrency]` queue all routes share, if configured.
+    global_queue: Option<AdmissionStats>,
+    /// Routes without a concurrency limit are left out.
+    queues: Vec<RouteQueue>,
+    /// HTTP connection reuse per route, and to the `[rpc.grpc]` backend as route `grpc`.
+    pools: Vec<RoutePool>,
+    /// Backend round trip by method, empty unless `[rpc.latency]` is configured.
+    latency: Vec<MethodLatency>,
+    /// Calls shared by identical concurrent requests, if `[rpc.coalescing]` is configured.
+    coalescing: Option<CoalescingStats>,
+}
+
+#[derive(Debug, Serialize)]
+struct RouteQueue {
+    route: String,
+    #[serde(flatten)]
+    stats: AdmissionStats,
+}
+
+#[derive(Debug, Serialize)]
+struct RoutePool {
+    route: String,
+    #[serde(flatten)]
+    stats: PoolStats,
+}
+
+fn route_queues(ctx: &SecureRpcContext) -> Vec<RouteQueue> {
+    ctx.routes
+        .routes()
+        .filter_map(|route| {
+            Some(RouteQueue {
+                route: route.name().to_string(),
+                stats: route.upstream.admission_stats()?,
+            })
+        })
+        .collect()
+}
+
+fn upstream_metrics(ctx: &SecureRpcContext) -> UpstreamMetrics {
+    UpstreamMetrics {
+        stats: ctx.upstream_events.stats(),
+        global_queue: ctx.global_concurrency.as_ref().map(|limit| limit.stats()),
+        queues: route_queues(ctx),
+        pools: ctx
+            .routes
+            .routes()
+            .map(|route| RoutePool {
+                route: route.name().to_string(),
+                stats: route.upstream.pool_stats(),
+            })
+            .chain(ctx.grpc.as_ref().map(|grpc| RoutePool {
+                route: "grpc".to_string(),
+                stats: grpc.pool_stats(),
+            }))
+            .collect(),
+        latency: ctx
+            .latency
+            .as_ref()
+            .map(|latency| latency.stats())
+            .unwrap_or_default(),
+        coalescing: ctx.coalescer.as_ref().map(|coalescer| coalescer.stats()),
+    }
+}
+
+async fn upstream_stats(State(ctx): State<Arc<SecureRpcContext>>) -> Json<UpstreamMetrics> {
+    Json(upstream_metrics(&ctx))
+}
+
+#[derive(Debug, Serialize)]
+struct UpstreamHealth {
+    /// Absent when no circuit breaker is configured.
+    circuit: Option<CircuitState>,
+    #[serde(flatten)]
+    metrics: UpstreamMetrics,
+    /// Last successful probe of the backend, when `[rpc.probe]` is configured.
+    capabilities: Option<UpstreamCapabilities>,
+    /// Absent when no `rpc.expected_chain_id` is configured.
+    chain_status: Option<ChainStatus>,
+    /// Absent when no `[rpc.head_cache]` is configured.
+    head_cache: Option<HeadCacheStatus>,
+}
+
+async fn upstream_health(State(ctx): State<Arc<SecureRpcContext>>) -> Json<UpstreamHealth> {
+    Json(UpstreamHealth {
+        circuit: ctx.circuit_breaker.as_ref().map(|breaker| breaker.state()),
+        metrics: upstream_metrics(&ctx),
+        capabilities: ctx
+            .upstream_probe
+            .as_ref()
+            .and_then(|probe| probe.capabilities()),
+        chain_status: ctx
+            .upstream_probe
+            .as_ref()
+            .and_then(|probe| probe.chain_status()),
+        head_cache: ctx.head_cache.as_ref().map(|cache| cache.status()),
+    })
+}
+
+async fn upstream_health_live(State(ctx): State<Arc<SecureRpcContext>>) -> Response {
+    let config = ctx.config();
+    let timeout = match &config.rpc.probe {
+        Some(probe) => probe.timeout_secs,
+        None => config.rpc.request_timeout_secs,
+    };
+    match upstream_probe::check_backend(&config.rpc, Duration::from_secs(timeout)).await {
+        Ok(check) => Json(check).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn firewall_rules(State(ctx): State<Arc<SecureRpcContext>>) -> Json<FirewallRules> {
+    Json(ctx.firewall.rules())
+}
+
+async fn firewall_stats(State(ctx): State<Arc<SecureRpcContext>>) -> Json<FirewallStats> {
+    Json(ctx.firewall.stats())
+}
+
+#[derive(Debug)]
+enum RuleTarget {
+    Ip(IpNetwork),
+    Account(AccountId32),
+}
+
+fn parse_target(target: &AccessTarget) -> std::result::Result<RuleTarget, Response> {
+    match target {
+        AccessTarget::Ip(ip) => IpNetwork::from_str(ip).map(RuleTarget::Ip).map_err(|e| {
+            (StatusCode::BAD_REQUEST, format!("Invalid IP/CIDR: {e}")).into_response()
+        }),
+        AccessTarget::Account(account) => AccountId32::from_str(account)
+            .map(RuleTarget::Account)
+            .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid AccountId32 format").into_response()),
+    }
+}
+
+/// Who made an admin request: `token`, an admin account, or `open` without authentication.
+#[derive(Debug, Clone)]
+struct AdminActor(String);
+
+/// Records a successful rule change in the audit log, if enabled.
+fn audit_change(ctx: &SecureRpcContext, actor: &AdminActor, action: &str, target: &AccessTarget) {
+    if let Some(audit) = &ctx.audit {
+        audit.record(AuditRecord::Admin {
+            actor: actor.0.clone(),
+            action: action.to_string(),
+            target: target.value().to_string(),
+        });
+    }
+}
+
+/// Queues a rule change for the other operators of the service, if `[sync]` is configured.
+fn share_change(ctx: &SecureRpcContext, mutation: RuleMutation) {
+    let Some(rule_sync) = &ctx.rule_sync else {
+        return;
+    };
+    if let Err(e) = rule_sync.queue(mutation) {
+        warn!(error = %e, "Failed to queue rule change for synchronization");
+    }
+}
+
+fn rule_change_response(result: Result<()>) -> Response {
+    match result {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(Error::RuleLimitReached(reason)) => (StatusCode::CONFLICT, reason).into_response(),
+        Err(e) => {
+            warn!(error = %e, "Admin rule change failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+async fn add_allow_rule(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Extension(actor): Extension<AdminActor>,
+    Json(target): Json<AccessTarget>,
+) -> Response {
+    let rule = match parse_target(&target) {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    info!(?rule, "Operator adding allow rule");
+    let result = match rule {
+        RuleTarget::Ip(ip_network) => ctx.firewall.add_ip_rule(ip_network).await,
+        RuleTarget::Account(account) => ctx.firewall.add_account_rule(account).await,
+    };
+    if result.is_ok() {
+        audit_change(&ctx, &actor, "allow", &target);
+        share_change(&ctx, RuleMutation::Allow { target });
+    }
+    rule_change_response(result)
+}
+
+async fn remove_allow_rule(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Extension(actor): Extension<AdminActor>,
+    Json(target): Json<AccessTarget>,
+) -> Response {
+    let rule = match parse_target(&target) {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    info!(?rule, "Operator removing allow rule");
+    let result = match rule {
+        RuleTarget::Ip(ip_network) => ctx.firewall.remove_ip_rule(ip_network).await,
+        RuleTarget::Account(account) => ctx.firewall.remove_account_rule(account).await,
+    };
+    if result.is_ok() {
+        audit_change(&ctx, &actor, "revoke", &target);
+        share_change(&ctx, RuleMutation::Revoke { target });
+    }
+    rule_change_response(result)
+}
+
+async fn add_deny_rule(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Extension(actor): Extension<AdminActor>,
+    Json(target): Json<AccessTarget>,
+) -> Response {
+    let rule = match parse_target(&target) {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    info!(?rule, "Operator adding ban");
+    let result = match rule {
+        RuleTarget::Ip(ip_network) => ctx.firewall.ban_ip(ip_network).await,
+        RuleTarget::Account(account) => ctx.firewall.ban_account(account).await,
+    };
+    if result.is_ok() {
+        audit_change(&ctx, &actor, "ban", &target);
+        share_change(&ctx, RuleMutation::Ban { target });
+    }
+    rule_change_response(result)
+}
+
+async fn temporary_grants(State(ctx): State<Arc<SecureRpcContext>>) -> Json<Vec<TemporaryGrant>> {
+    Json(ctx.firewall.temporary_grants())
+}
+
+#[derive(Debug, Deserialize)]
+struct TemporaryGrantRequest {
+    target: AccessTarget,
+    duration_secs: u64,
+    /// A `[plans]` tier limiting the grant.
+    #[serde(default)]
+    plan: Option<String>,
+}
+
+async fn add_temporary_grant(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Extension(actor): Extension<AdminActor>,
+    Json(request): Json<TemporaryGrantRequest>,
+) -> Response {
+    let rule = match parse_target(&request.target) {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    let now = Utc::now();
+    let expires_at =
+        chrono::Duration::try_seconds(i64::try_from(request.duration_secs).unwrap_or(i64::MAX))
+            .and_then(|duration| now.checked_add_signed(duration));
+    let expires_at = match expires_at {
+        Some(expires_at) if request.duration_secs > 0 => expires_at,
+        _ => return (StatusCode::BAD_REQUEST, "Invalid duration").into_response(),
+    };
+    if let Some(plan) = request
+        .plan
+        .as_ref()
+        .filter(|plan| ctx.plans.get(plan).is_none())
+    {
+        return (StatusCode::BAD_REQUEST, format!("Unknown plan {plan}")).into_response();
+    }
+    let record = TemporaryAccessRecord {
+        granted_at: now,
+        expires_at,
+        plan: request.plan.clone(),
+        extensions: 0,
+    };
+    info!(?rule, %expires_at, plan = ?record.plan, "Operator granting temporary access");
+    let result = match rule {
+        RuleTarget::Ip(ip_network) => {
+            ctx.firewall
+                .grant_temporary_ip_access(ip_network, record)
+                .await
+        }
+        RuleTarget::Account(account) => ctx.firewall.grant_temporary_access(account, record).await,
+    };
+    if result.is_ok() {
+        audit_change(&ctx, &actor, "grant_temporary", &request.target);
+        share_change(
+            &ctx,
+            RuleMutation::GrantTemporary {
+                target: request.target,
+                granted_at: now.timestamp(),
+                expires_at: expires_at.timestamp(),
+                plan: request.plan,
+            },
+        );
+    }
+    rule_change_response(result)
+}
+
+async fn run_cleanup(State(ctx): State<Arc<SecureRpcContext>>) -> Json<CleanupStats> {
+    let stats = ctx.firewall.cleanup_expired_access().await;
+    info!(
+        scanned = stats.scanned,
+        expired = stats.expired,
+        "Cleanup triggered by operator"
+    );
+    Json(stats)
+}
+
+async fn auto_bans(State(ctx): State<Arc<SecureRpcContext>>) -> Json<Vec<AutoBan>> {
+    Json(ctx.firewall.auto_bans())
+}
+
+async fn lift_auto_ban(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Extension(actor): Extension<AdminActor>,
+    Json(target): Json<AccessTarget>,
+) -> Response {
+    let network = match parse_target(&target) {
+        Ok(RuleTarget::Ip(network)) => network,
+        Ok(RuleTarget::Account(_)) => {
+            return (StatusCode::BAD_REQUEST, "Automatic bans apply to IPs only").into_response();
+        }
+        Err(response) => return response,
+    };
+    info!(%network, "Operator lifting automatic ban");
+    if !ctx.firewall.lift_auto_ban(&network).await {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    audit_change(&ctx, &actor, "lift_auto_ban", &target);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+async fn list_webhooks(State(ctx): State<Arc<SecureRpcContext>>) -> Json<Vec<WebhookTarget>> {
+    Json(ctx.firewall.webhooks())
+}
+
+async fn webhook_schema() -> Json<WebhookSchema> {
+    Json(WebhookSchema::current())
+}
+
+#[derive(Debug, Deserialize)]
+struct EventHistoryQuery {
+    #[serde(default)]
+    since: Option<DateTime<Utc>>,
+    #[serde(default)]
+    until: Option<DateTime<Utc>>,
+    /// Comma-separated event types, e.g. `Banned,AutoBanned`.
+    #[serde(default)]
+    types: Option<String>,
+    #[serde(default)]
+    after: Option<u64>,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+async fn firewall_events(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Query(query): Query<EventHistoryQuery>,
+) -> Response {
+    let Some(event_history) = &ctx.event_history else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let mut types = HashSet::new();
+    for name in query
+        .types
+        .iter()
+        .flat_map(|types| types.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        match serde_json::from_value::<WebhookEventType>(serde_json::Value::from(name)) {
+            Ok(event_type) => types.insert(event_type),
+            Err(_) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    format!("Unknown event type {name}"),
+                )
+                    .into_response();
+            }
+        };
+    }
+    Json(event_history.query(&EventQuery {
+        since: query.since,
+        until: query.until,
+        types,
+        after: query.after,
+        limit: query.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+    }))
+    .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct HeatmapQuery {
+    #[serde(default)]
+    order: HeatmapOrder,
+    #[serde(default = "default_heatmap_limit")]
+    limit: usize,
+}
+
+fn default_heatmap_limit() -> usize {
+    100
+}
+
+async fn origin_heatmap(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Query(query): Query<HeatmapQuery>,
+) -> Response {
+    if !ctx.config().origin_heatmap.enabled {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    Json(ctx.origin_heatmap.report(query.order, query.limit)).into_response()
+}
+
+async fn verify_audit_log(State(ctx): State<Arc<SecureRpcContext>>) -> Response {
+    let Some(audit) = ctx.audit.clone() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    // Reads every audit file, so keep it off the async workers.
+    match tokio::task::spawn_blocking(move || audit.verify()).await {
+        Ok(Ok(verification)) => Json(verification).into_response(),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TrafficStatsQuery {
+    #[serde(default)]
+    since: Option<DateTime<Utc>>,
+    #[serde(default)]
+    until: Option<DateTime<Utc>>,
+}
+
+async fn traffic_stats(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Query(query): Query<TrafficStatsQuery>,
+) -> Response {
+    let Some(stats) = &ctx.traffic_stats else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    Json(stats.series(query.since, query.until)).into_response()
+}
+
+/// Grafana's connection test for the datasource.
+async fn grafana_datasource(State(ctx): State<Arc<SecureRpcContext>>) -> Response {
+    match ctx.traffic_stats {
+        Some(_) => StatusCode::OK.into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn grafana_search(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    search: Option<Json<GrafanaSearch>>,
+) -> Response {
+    let Some(stats) = &ctx.traffic_stats else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Json(search) = search.unwrap_or_default();
+    Json(stats.grafana_targets(&search.target)).into_response()
+}
+
+async fn grafana_query(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Json(query): Json<GrafanaQuery>,
+) -> Response {
+    let Some(stats) = &ctx.traffic_stats else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match stats.grafana_query(&query) {
+        Ok(series) => Json(series).into_response(),
+        Err(message) => (StatusCode::BAD_REQUEST, message).into_response(),
+    }
+}
+
+/// What `GET /usage` returns.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageExport {
+    #[serde(flatten)]
+    pub snapshot: UsageSnapshot,
+    /// Labels of the labelled accounts in the snapshot.
+    pub labels: BTreeMap<String, Labels>,
+}
+
+async fn usage(State(ctx): State<Arc<SecureRpcContext>>) -> Response {
+    let Some(meter) = &ctx.usage_meter else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let snapshot = meter.snapshot();
+    let mut labels = ctx.account_labels.all();
+    labels.retain(|account, _| snapshot.mentions_account(account));
+    Json(UsageExport { snapshot, labels }).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct BillingQuery {
+    #[serde(default)]
+    from: Option<DateTime<Utc>>,
+    #[serde(default)]
+    to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    account: Option<String>,
+    #[serde(default)]
+    format: BillingFormat,
+}
+
+impl BillingQuery {
+    /// `from..to`, from the beginning of time until now by default.
+    fn range(&self) -> std::result::Result<(DateTime<Utc>, DateTime<Utc>), Response> {
+        let from = self.from.unwrap_or(DateTime::<Utc>::MIN_UTC);
+        let to = self.to.unwrap_or_else(Utc::now);
+        if from >= to {
+            return Err((StatusCode::BAD_REQUEST, "`from` must be before `to`").into_response());
+        }
+        Ok((from, to))
+    }
+}
+
+fn csv_response(csv: String) -> Response {
+    ([(CONTENT_TYPE, "text/csv; charset=utf-8")], csv).into_response()
+}
+
+async fn billing_usage(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Query(query): Query<BillingQuery>,
+) -> Response {
+    let Some(meter) = &ctx.usage_meter else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let (from, to) = match query.range() {
+        Ok(range) => range,
+        Err(response) => return response,
+    };
+    let rows = billing::usage_rows(&meter.account_history(from, to), query.account.as_deref());
+    match query.format {
+        BillingFormat::Json => Json(rows).into_response(),
+        BillingFormat::Csv => csv_response(billing::usage_csv(&rows)),
+    }
+}
+
+async fn billing_grants(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Query(query): Query<BillingQuery>,
+) -> Response {
+    let Some(audit) = ctx.audit.clone() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let (from, to) = match query.range() {
+        Ok(range) => range,
+        Err(response) => return response,
+    };
+    // Reads every audit file, so keep it off the async workers.
+    let entries = match tokio::task::spawn_blocking(move || audit.entries_between(from, to)).await {
+        Ok(Ok(entries)) => entries,
+        Ok(Err(e)) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    let rows = billing::grant_rows(&entries, query.account.as_deref());
+    match query.format {
+        BillingFormat::Json => Json(rows).into_response(),
+        BillingFormat::Csv => csv_response(billing::grants_csv(&rows)),
+    }
+}
+
+async fn list_account_labels(
+    State(ctx): State<Arc<SecureRpcContext>>,
+) -> Json<BTreeMap<String, Labels>> {
+    Json(ctx.account_labels.all())
+}
+
+async fn list_account_roles(
+    State(ctx): State<Arc<SecureRpcContext>>,
+) -> Json<BTreeMap<String, Roles>> {
+    Json(ctx.account_roles.all())
+}
+
+fn parse_account(account: &str) -> std::result::Result<AccountId32, Response> {
+    AccountId32::from_str(account)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid AccountId32 format").into_response())
+}
+
+async fn account_labels(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Path(account): Path<String>,
+) -> Response {
+    match parse_account(&account) {
+        Ok(account) => Json(
+            ctx.account_labels
+                .get(&account)
+                .map(|labels| (*labels).clone())
+                .unwrap_or_default(),
+        )
+        .into_response(),
+        Err(response) => response,
+    }
+}
+
+async fn set_account_labels(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Extension(actor): Extension<AdminActor>,
+    Path(account): Path<String>,
+    Json(labels): Json<Labels>,
+) -> Response {
+    let account_id = match parse_account(&account) {
+        Ok(account_id) => account_id,
+        Err(response) => return response,
+    };
+    info!(%account_id, ?labels, "Operator setting account labels");
+    match ctx.account_labels.set(&account_id, labels) {
+        Ok(()) => {
+            audit_change(&ctx, &actor, "label", &AccessTarget::Account(account));
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(Error::InvalidLabels(reason)) => (StatusCode::BAD_REQUEST, reason).into_response(),
+        Err(e) => {
+            warn!(error = %e, "Setting account labels failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+async fn read_only_status(
+    State(ctx): State<Arc<SecureRpcContext>>,
+) -> Json<Option<ReadOnlyStatus>> {
+    Json(ctx.read_only.status())
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ReadOnlyRequest {
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+async fn enable_read_only(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Extension(actor): Extension<AdminActor>,
+    request: Option<Json<ReadOnlyRequest>>,
+) -> Response {
+    let Json(request) = request.unwrap_or_default();
+    match ctx.read_only.enable(request.reason, &actor.0) {
+        Ok(status) => {
+            warn!(set_by = %actor.0, reason = ?status.reason, "Gateway switched to read-only mode");
+            if let Some(audit) = &ctx.audit {
+                audit.record(AuditRecord::Admin {
+                    actor: actor.0.clone(),
+                    action: "read_only".to_string(),
+                    target: status.reason.clone().unwrap_or_default(),
+                });
+            }
+            Json(status).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn disable_read_only(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Extension(actor): Extension<AdminActor>,
+) -> Response {
+    match ctx.read_only.disable() {
+        Ok(true) => {
+            info!(set_by = %actor.0, "Gateway left read-only mode");
+            if let Some(audit) = &ctx.audit {
+                audit.record(AuditRecord::Admin {
+                    actor: actor.0.clone(),
+                    action: "read_write".to_string(),
+                    target: String::new(),
+                });
+            }
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn switch_upstream(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Extension(actor): Extension<AdminActor>,
+    Json(request): Json<SwitchUpstreamRequest>,
+) -> Response {
+    match switchover::switch_upstream(&ctx, request, &actor.0).await {
+        Ok(switch) => {
+            if let Some(audit) = &ctx.audit {
+                let targets: Vec<String> = switch.new_targets.iter().map(Url::to_string).collect();
+                audit.record(AuditRecord::Admin {
+                    actor: actor.0.clone(),
+                    action: "switch_upstream".to_string(),
+                    target: targets.join(", "),
+                });
+            }
+            Json(switch).into_response()
+        }
+        Err(
+            Error::InvalidJobInput(reason)
+            | Error::ConfigReloadError(reason)
+            | Error::ConfigError(::config::ConfigError::Message(reason)),
+        ) => (StatusCode::BAD_REQUEST, reason).into_response(),
+        Err(e) => {
+            warn!(error = %e, "Switching backends failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IntrospectionRequest {
+    token: String,
+}
+
+/// What the gateway knows about a credential. Unknown or revoked credentials are reported
+/// as inactive, without further details.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<AccountId32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access: Option<AccountAccessStatus>,
+}
+
+async fn introspect(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Json(request): Json<IntrospectionRequest>,
+) -> Json<IntrospectionResponse> {
+    let response = match ctx.api_keys.resolve(request.token.trim()) {
+        Some(account) => IntrospectionResponse {
+            active: true,
+            token_type: Some("api_key".to_string()),
+            access: Some(ctx.firewall.account_status(&account)),
+            account: Some(account),
+        },
+        None => IntrospectionResponse {
+            active: false,
+            token_type: None,
+            account: None,
+            access: None,
+        },
+    };
+    Json(response)
+}
+
+#[derive(Debug, Deserialize)]
+struct ShadowPolicyRequest {
+    policy: ProposedPolicy,
+    /// How long to evaluate the proposed policy for.
+    duration_secs: u64,
+}
+
+async fn start_shadow_policy(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    Json(request): Json<ShadowPolicyRequest>,
+) -> Json<ShadowReport> {
+    Json(
+        ctx.firewall
+            .start_shadow_policy(request.policy, Duration::from_secs(request.duration_secs)),
+    )
+}
+
+async fn shadow_report(State(ctx): State<Arc<SecureRpcContext>>) -> Response {
+    match ctx.firewall.shadow_report() {
+        Some(report) => Json(report).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn stop_shadow_policy(State(ctx): State<Arc<SecureRpcContext>>) -> Response {
+    match ctx.firewall.stop_shadow_policy() {
+        Some(report) => Json(report).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Rejects requests without the configured `Authorization: Bearer <token>`, if one is set.
+/// Lets a request through if it carries the admin token, or is authenticated as one of the
+/// admin accounts by a signature or API key. Open when neither is configured.
+async fn require_admin(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let config = ctx.config();
+    if config.admin.token.is_none() && config.admin.accounts.is_empty() {
+        req.extensions_mut().insert(AdminActor("open".to_string()));
+        return next.run(req).await;
+    }
+
+    let provided = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|value| value.trim().to_string());
+    // Compare digests so the comparison time doesn't depend on the token's contents.
+    let token_matches = |provided: &str| {
+        config
+            .admin
+            .token
+            .as_ref()
+            .is_some_and(|token| Sha256::digest(provided) == Sha256::digest(token))
+    };
+    // Signatures cover the body, so signed requests are read in full before they're verified.
+    let mut signed_body = Bytes::new();
+    if provided.is_none() && req.headers().contains_key(SIGNATURE_HEADER) {
+        let (parts, body) = req.into_parts();
+        signed_body = match axum::body::to_bytes(body, MAX_SIGNED_BODY_BYTES).await {
+            Ok(body) => body,
+            Err(_) => {
+                return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+            }
+        };
+        req = Request::from_parts(parts, Body::from(signed_body.clone()));
+    }
+    let account = match provided.as_deref() {
+        Some(provided) if token_matches(provided) => {
+            req.extensions_mut().insert(AdminActor("token".to_string()));
+            return next.run(req).await;
+        }
+        // API keys are issued by a job any caller can submit, so they never authorize admins
+        Some(_) => None,
+        None => match ctx.signature_auth.verify(
+            req.headers(),
+            req.method(),
+            req.uri()
+                .path_and_query()
+                .map_or(req.uri().path(), |target| target.as_str()),
+            &signed_body,
+        ) {
+            // Admins' signed requests must also carry a nonce above their last one
+            Some(Ok(account)) if config.admin.accounts.contains(&account) => {
+                match ctx.admin_nonces.advance(&account, req.headers()) {
+                    Ok(()) => Some(account),
+                    Err(e @ Error::InvalidSignature(_)) => {
+                        return (StatusCode::UNAUTHORIZED, e.to_string()).into_response();
+                    }
+                    Err(e) => {
+                        return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+                    }
+                }
+            }
+            Some(Ok(account)) => Some(account),
+            Some(Err(e)) => return (StatusCode::UNAUTHORIZED, e.to_string()).into_response(),
+            None => None,
+        },
+    };
+
+    match account {
+        Some(account) if config.admin.accounts.contains(&account) => {
+            info!(%account, method = %req.method(), path = %req.uri().path(), "Admin request");
+            req.extensions_mut().insert(AdminActor(account.to_string()));
+            next.run(req).await
+        }
+        Some(_) => (StatusCode::FORBIDDEN, "Account is not an admin").into_response(),
+        None => (StatusCode::UNAUTHORIZED, "Invalid admin token").into_response(),
+    }
+}
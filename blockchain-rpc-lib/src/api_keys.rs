@@ -0,0 +1,93 @@
+use crate::Result;
+use crate::store::StateStore;
+use parking_lot::RwLock;
+use sha2::{Digest, Sha256};
+use sp_runtime::AccountId32;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+
+const API_KEYS_NAMESPACE: &str = "api_keys";
+
+/// SHA-256 digest of an API key.
+pub type ApiKeyHash = [u8; 32];
+
+/// Maps API keys to the accounts they authenticate as.
+///
+/// Only SHA-256 digests are stored, so the keys themselves never need to appear on-chain
+/// or be held by the gateway. Bindings are persisted in the state store, as the calls that
+/// issued them aren't replayed after a restart.
+#[derive(Debug)]
+pub struct ApiKeyStore {
+    store: Arc<dyn StateStore>,
+    keys: RwLock<HashMap<ApiKeyHash, AccountId32>>,
+}
+
+impl ApiKeyStore {
+    /// Loads the keys persisted in `store`. Entries that fail to decode are skipped with a
+    /// warning.
+    pub fn load(store: Arc<dyn StateStore>) -> Result<Self> {
+        let mut keys = HashMap::new();
+        for (key, value) in store.entries(API_KEYS_NAMESPACE)? {
+            let key_hash = ApiKeyHash::try_from(key.as_slice()).ok();
+            let account = <[u8; 32]>::try_from(value.as_slice())
+                .ok()
+                .map(AccountId32::new);
+            match (key_hash, account) {
+                (Some(key_hash), Some(account)) => {
+                    keys.insert(key_hash, account);
+                }
+                _ => warn!(key = ?key, "Skipping undecodable persisted API key"),
+            }
+        }
+        Ok(Self {
+            store,
+            keys: RwLock::new(keys),
+        })
+    }
+
+    /// Binds a key digest to an account. Digests are public on-chain, so a digest already
+    /// bound to another account is only moved if `may_rebind` accepts its current account.
+    pub fn insert(
+        &self,
+        key_hash: ApiKeyHash,
+        account: AccountId32,
+        may_rebind: impl FnOnce(&AccountId32) -> Result<()>,
+    ) -> Result<()> {
+        let mut keys = self.keys.write();
+        if let Some(owner) = keys.get(&key_hash) {
+            if *owner != account {
+                may_rebind(owner)?;
+            }
+        }
+        // Persist before accepting, so the key survives a restart once it works.
+        let value: &[u8] = account.as_ref();
+        self.store.insert(API_KEYS_NAMESPACE, &key_hash, value)?;
+        keys.insert(key_hash, account);
+        Ok(())
+    }
+
+    /// The account a key digest is bound to, if any.
+    pub fn owner(&self, key_hash: &ApiKeyHash) -> Option<AccountId32> {
+        self.keys.read().get(key_hash).cloned()
+    }
+
+    /// Resolves a plaintext API key to the account it was issued for.
+    pub fn resolve(&self, key: &str) -> Option<AccountId32> {
+        self.owner(&hash_key(key))
+    }
+
+    /// Number of issued keys.
+    pub fn len(&self) -> usize {
+        self.keys.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.read().is_empty()
+    }
+}
+
+/// Computes the digest under which an API key is stored.
+pub fn hash_key(key: &str) -> ApiKeyHash {
+    Sha256::digest(key.as_bytes()).into()
+}
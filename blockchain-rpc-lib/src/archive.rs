@@ -0,0 +1,255 @@
+use crate::Result;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::config::{ArchiveConfig, ArchiveHashRouting, RpcConfig};
+use crate::proxy::upstream::{ConcurrencyLimit, Upstream};
+use crate::upstream_events::UpstreamEvents;
+use crate::upstream_probe::{call, parse_chain_number, probe_client};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+use url::Url;
+
+/// A block a call reads state at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockRef {
+    /// A tag near the head (`latest`, `safe`, ...), or no block, which means the head.
+    Head,
+    Number(u64),
+    /// A block hash, whose age isn't known.
+    Hash,
+}
+
+/// Where a method takes the block it reads state at.
+enum BlockParam {
+    /// The params entry at this position: a number, tag or hash, or an EIP-1898 object.
+    At(usize),
+    /// A log filter object in the first param, starting at `fromBlock` or at `blockHash`.
+    LogFilter,
+    /// A range of blocks that always reaches into history (`state_queryStorage`).
+    Range,
+}
+
+/// Classifies JSON-RPC calls as historical: reading state at blocks more than `recent_blocks`
+/// behind the head, which a pruned node no longer has.
+///
+/// Blocks are taken from the params of the EVM (`eth_call`, `eth_getBalance`, `eth_getLogs`,
+/// ...) and Substrate (`state_getStorage`, `state_call`, ...) methods that take one. Other
+/// methods are never historical.
+#[derive(Debug, Clone)]
+pub struct HistoricalCalls {
+    recent_blocks: u64,
+    block_hashes: ArchiveHashRouting,
+}
+
+impl HistoricalCalls {
+    pub fn new(config: &ArchiveConfig) -> Self {
+        HistoricalCalls {
+            recent_blocks: config.recent_blocks,
+            block_hashes: config.block_hashes,
+        }
+    }
+
+    /// Whether any call in a JSON-RPC payload (single call or batch) is historical, given the
+    /// fast node's `head` block. Without a known head, every call at a numbered block is.
+    pub fn is_historical(&self, payload: &[u8], head: Option<u64>) -> bool {
+        match serde_json::from_slice::<Value>(payload) {
+            Ok(Value::Array(calls)) => calls.iter().any(|call| self.is_historical_call(call, head)),
+            Ok(call) => self.is_historical_call(&call, head),
+            Err(_) => false,
+        }
+    }
+
+    fn is_historical_call(&self, call: &Value, head: Option<u64>) -> bool {
+        let Some(method) = call.get("method").and_then(Value::as_str) else {
+            return false;
+        };
+        let params = call.get("params");
+        let block = match block_param(method) {
+            Some(BlockParam::At(position)) => params
+                .and_then(|params| params.get(position))
+                .map_or(Some(BlockRef::Head), block_ref),
+            Some(BlockParam::LogFilter) => {
+                params.and_then(|params| params.get(0)).and_then(|filter| {
+                    match filter.get("blockHash") {
+                        Some(_) => Some(BlockRef::Hash),
+                        None => filter
+                            .get("fromBlock")
+                            .map_or(Some(BlockRef::Head), block_ref),
+                    }
+                })
+            }
+            Some(BlockParam::Range) => return true,
+            None => return false,
+        };
+        match block {
+            Some(BlockRef::Head) | None => false,
+            Some(BlockRef::Hash) => self.block_hashes == ArchiveHashRouting::Archive,
+            Some(BlockRef::Number(number)) => {
+                head.is_none_or(|head| number.saturating_add(self.recent_blocks) < head)
+            }
+        }
+    }
+}
+
+/// Where `method` takes its block, for the methods reading state at one.
+fn block_param(method: &str) -> Option<BlockParam> {
+    let param = match method {
+        "eth_getBlockByNumber"
+        | "eth_getBlockTransactionCountByNumber"
+        | "eth_getUncleCountByBlockNumber"
+        | "eth_getTransactionByBlockNumberAndIndex"
+        | "eth_getUncleByBlockNumberAndIndex"
+        | "eth_getBlockReceipts"
+        | "debug_traceBlockByNumber"
+        | "trace_block"
+        | "trace_replayBlockTransactions"
+        | "state_getMetadata"
+        | "state_getRuntimeVersion"
+        | "state_traceBlock" => BlockParam::At(0),
+        "eth_getBalance"
+        | "eth_getCode"
+        | "eth_getTransactionCount"
+        | "eth_call"
+        | "eth_estimateGas"
+        | "eth_createAccessList"
+        | "eth_feeHistory"
+        | "debug_traceCall"
+        | "state_getStorage"
+        | "state_getStorageHash"
+        | "state_getStorageSize"
+        | "state_getKeys"
+        | "state_getPairs"
+        | "state_queryStorageAt"
+        | "state_getReadProof" => BlockParam::At(1),
+        "eth_getStorageAt"
+        | "eth_getProof"
+        | "state_call"
+        | "childstate_getStorage"
+        | "childstate_getStorageHash"
+        | "childstate_getStorageSize"
+        | "childstate_getKeys" => BlockParam::At(2),
+        "state_getKeysPaged" => BlockParam::At(3),
+        "eth_getLogs" | "eth_newFilter" => BlockParam::LogFilter,
+        "state_queryStorage" => BlockParam::Range,
+        _ => return None,
+    };
+    Some(param)
+}
+
+/// Parses a block param: a number (hex or decimal), a tag, a 32-byte hash, or an EIP-1898
+/// object naming either. `None` for anything else.
+fn block_ref(param: &Value) -> Option<BlockRef> {
+    match param {
+        Value::Null => Some(BlockRef::Head),
+        Value::Number(number) => number.as_u64().map(BlockRef::Number),
+        Value::String(block) => match block.as_str() {
+            "earliest" => Some(BlockRef::Number(0)),
+            "latest" | "pending" | "safe" | "finalized" => Some(BlockRef::Head),
+            hash if hash.len() == 66 && hash.starts_with("0x") => Some(BlockRef::Hash),
+            number => parse_chain_number(number).map(BlockRef::Number),
+        },
+        Value::Object(object) => match (object.get("blockHash"), object.get("blockNumber")) {
+            (Some(_), _) => Some(BlockRef::Hash),
+            (None, Some(number)) => block_ref(number),
+            (None, None) => None,
+        },
+        _ => None,
+    }
+}
+
+/// A route's archive node, and the fast node's head that calls are dated against.
+pub(crate) struct ArchiveRoute {
+    pub(crate) upstream: Arc<Upstream>,
+    calls: HistoricalCalls,
+    /// The fast node's head block, 0 until it is known.
+    head: AtomicU64,
+    head_client: reqwest::Client,
+    head_url: Url,
+    head_poll: Duration,
+}
+
+impl ArchiveRoute {
+    /// The archive node of `config`, for the route whose fast node is `rpc.proxy_to_url`.
+    /// Requests to it share `[rpc]`'s retries, circuit breaker settings and concurrency
+    /// limits.
+    pub(crate) fn new(
+        config: &ArchiveConfig,
+        rpc: &RpcConfig,
+        events: Arc<UpstreamEvents>,
+        global_concurrency: Option<Arc<ConcurrencyLimit>>,
+    ) -> Result<Self> {
+        let head_poll = Duration::from_secs(config.head_poll_secs.max(1));
+        let (head_client, head_url) = probe_client(rpc, head_poll)?;
+        let archive_rpc = RpcConfig {
+            proxy_to_url: config.url.clone(),
+            upstreams: Vec::new(),
+            ..rpc.clone()
+        };
+        let breaker = rpc
+            .circuit_breaker
+            .as_ref()
+            .map(|config| Arc::new(CircuitBreaker::new(config)));
+        Ok(ArchiveRoute {
+            upstream: Arc::new(Upstream::new(
+                &archive_rpc,
+                events,
+                breaker,
+                global_concurrency,
+            )?),
+            calls: HistoricalCalls::new(config),
+            head: AtomicU64::new(0),
+            head_client,
+            head_url,
+            head_poll,
+        })
+    }
+
+    /// The fast node's head block, once polled.
+    pub(crate) fn head(&self) -> Option<u64> {
+        Some(self.head.load(Ordering::Relaxed)).filter(|head| *head > 0)
+    }
+
+    /// Whether a JSON-RPC payload has to go to the archive node.
+    pub(crate) fn serves(&self, payload: &[u8]) -> bool {
+        self.calls.is_historical(payload, self.head())
+    }
+
+    /// Polls the fast node's head every `head_poll_secs` until `shutdown`: `eth_blockNumber`,
+    /// or the number of `chain_getHeader` on Substrate nodes. The last known head is kept
+    /// while polls fail.
+    pub(crate) async fn run(&self, shutdown: CancellationToken) {
+        let mut poll = tokio::time::interval(self.head_poll);
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = poll.tick() => {}
+            }
+            let head = match call(
+                &self.head_client,
+                &self.head_url,
+                "eth_blockNumber",
+                json!([]),
+            )
+            .await
+            {
+                Ok(head) => Ok(head),
+                Err(_) => call(
+                    &self.head_client,
+                    &self.head_url,
+                    "chain_getHeader",
+                    json!([]),
+                )
+                .await
+                .map(|header| header.get("number").cloned().unwrap_or(Value::Null)),
+            };
+            match head.map(|head| head.as_str().and_then(parse_chain_number)) {
+                Ok(Some(head)) => self.head.store(head, Ordering::Relaxed),
+                Ok(None) => debug!("Fast node returned no head block number"),
+                Err(e) => debug!(error = %e, "Failed to poll the fast node's head block"),
+            }
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use crate::Result;
+use crate::config::AuditConfig;
+use crate::firewall::WebhookEvent;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+const AUDIT_DIR: &str = "audit";
+const CURRENT_FILE: &str = "audit.log";
+/// `prev_hash` of the very first entry.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// What an audit entry records.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum AuditRecord {
+    /// An access decision or change to firewall state, as reported to webhooks: the source,
+    /// whether it was allowed and by which rule, rules added and removed, grants expiring.
+    Firewall { event: WebhookEvent },
+    /// A job call that changed firewall rules, grants or runtime settings.
+    JobCall {
+        job_id: u64,
+        call_id: u64,
+        action: String,
+        target: String,
+    },
+    /// A change made through the admin API, by the admin token or an admin account.
+    Admin {
+        actor: String,
+        action: String,
+        target: String,
+    },
+    /// A transaction signed and submitted by the relay for an account. `fee` is the estimated
+    /// fee, in the chain's smallest unit.
+    Relay {
+        account: String,
+        call: String,
+        tx_hash: String,
+        fee: String,
+    },
+}
+
+/// A line of the audit log. Each entry's `hash` covers its other fields, including the previous
+/// entry's hash, so editing, removing or reordering entries breaks the chain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub record: AuditRecord,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn new(seq: u64, record: AuditRecord, prev_hash: String) -> Result<Self> {
+        let mut entry = AuditEntry {
+            seq,
+            timestamp: Utc::now(),
+            record,
+            prev_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash()?;
+        Ok(entry)
+    }
+
+    /// SHA-256 of the entry serialized with an empty `hash`, hex-encoded.
+    fn compute_hash(&self) -> Result<String> {
+        let unhashed = AuditEntry {
+            hash: String::new(),
+            ..self.clone()
+        };
+        Ok(hex::encode(Sha256::digest(serde_json::to_vec(&unhashed)?)))
+    }
+}
+
+/// Result of checking the hash chain across all audit files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditVerification {
+    /// Entries checked before the first broken link, or all of them.
+    pub entries: u64,
+    pub valid: bool,
+    /// Sequence number of the first entry that doesn't match the chain.
+    pub first_invalid_seq: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Append-only, hash-chained log of access decisions and rule changes under
+/// `<data_dir>/audit`. Entries are JSON lines in `audit.log`, which is rotated to
+/// `audit-<last seq>.log` once it grows past `max_file_bytes`; the chain continues across files.
+#[derive(Debug)]
+pub struct AuditLog {
+    dir: PathBuf,
+    max_file_bytes: u64,
+    writer: Mutex<AuditWriter>,
+}
+
+#[derive(Debug)]
+struct AuditWriter {
+    file: File,
+    size: u64,
+    next_seq: u64,
+    prev_hash: String,
+}
+
+impl AuditLog {
+    /// Opens the audit log under `data_dir`, continuing the chain from its last entry.
+    pub fn open(data_dir: &Path, config: &AuditConfig) -> Result<Self> {
+        let dir = data_dir.join(AUDIT_DIR);
+        std::fs::create_dir_all(&dir)?;
+
+        let last = match last_entry(&dir.join(CURRENT_FILE))? {
+            Some(entry) => Some(entry),
+            None => match audit_files(&dir)?.iter().rev().nth(1) {
+                Some(path) => last_entry(path)?,
+                None => None,
+            },
+        };
+        let (next_seq, prev_hash) = match last {
+            Some(entry) => (entry.seq + 1, entry.hash),
+            None => (0, GENESIS_HASH.to_string()),
+        };
+        info!(dir = %dir.display(), next_seq, "Opened audit log");
+
+        let file = open_append(&dir.join(CURRENT_FILE))?;
+        let size = file.metadata()?.len();
+        Ok(AuditLog {
+            dir,
+            max_file_bytes: config.max_file_bytes,
+            writer: Mutex::new(AuditWriter {
+                file,
+                size,
+                next_seq,
+                prev_hash,
+            }),
+        })
+    }
+
+    /// Appends an entry. Failures are logged rather than returned, so auditing never fails the
+    /// request or job being audited.
+    pub fn record(&self, record: AuditRecord) {
+        if let Err(e) = self.append(record) {
+            warn!(error = %e, "Failed to write audit log entry");
+        }
+    }
+
+    /// Records a firewall event.
+    pub fn record_event(&self, event: &WebhookEvent) {
+        self.record(AuditRecord::Firewall {
+            event: event.clone(),
+        });
+    }
+
+    /// Records a job call that changed firewall rules, grants or runtime settings.
+    pub fn record_job_call(&self, job_id: u64, call_id: u64, action: &str, target: &str) {
+        self.record(AuditRecord::JobCall {
+            job_id,
+            call_id,
+            action: action.to_string(),
+            target: target.to_string(),
+        });
+    }
+
+    fn append(&self, record: AuditRecord) -> Result<()> {
+        let mut writer = self.writer.lock();
+        let entry = AuditEntry::new(writer.next_seq, record, writer.prev_hash.clone())?;
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+
+        if writer.size > 0 && writer.size + line.len() as u64 > self.max_file_bytes {
+            let rotated = self
+                .dir
+                .join(format!("audit-{:020}.log", writer.next_seq - 1));
+            writer.file.sync_all()?;
+            std::fs::rename(self.dir.join(CURRENT_FILE), &rotated)?;
+            writer.file = open_append(&self.dir.join(CURRENT_FILE))?;
+            writer.size = 0;
+            info!(rotated = %rotated.display(), "Rotated audit log");
+        }
+
+        writer.file.write_all(&line)?;
+        writer.size += line.len() as u64;
+        writer.next_seq += 1;
+        writer.prev_hash = entry.hash;
+        Ok(())
+    }
+
+    /// Walks every audit file in order, recomputing each entry's hash and checking that it
+    /// links to the previous one.
+    pub fn verify(&self) -> Result<AuditVerification> {
+        // Hold the writer so no entry is appended or rotated mid-check.
+        let _writer = self.writer.lock();
+        let mut verification = AuditVerification {
+            valid: true,
+            ..Default::default()
+        };
+        let mut expected_seq = None;
+        let mut prev_hash = GENESIS_HASH.to_string();
+
+        for path in audit_files(&self.dir)? {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                let line = line?;
+                if line.is_empty() {
+                    continue;
+                }
+                let entry: AuditEntry = match serde_json::from_str(&line) {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        return Ok(broken(
+                            verification,
+                            expected_seq,
+                            format!("Unreadable entry in {}: {e}", path.display()),
+                        ));
+                    }
+                };
+                // The oldest files may have been archived elsewhere; start from whatever is
+                // first on disk.
+                let expected = expected_seq.unwrap_or(entry.seq);
+                let problem = if entry.seq != expected {
+                    Some(format!("Expected entry {expected}, found {}", entry.seq))
+                } else if expected_seq.is_some() && entry.prev_hash != prev_hash {
+                    Some("Previous hash doesn't match the preceding entry".to_string())
+                } else if entry.compute_hash()? != entry.hash {
+                    Some("Hash doesn't match the entry's contents".to_string())
+                } else {
+                    None
+                };
+                if let Some(problem) = problem {
+                    return Ok(broken(verification, Some(expected), problem));
+                }
+                verification.entries += 1;
+                expected_seq = Some(entry.seq + 1);
+                prev_hash = entry.hash;
+            }
+        }
+        Ok(verification)
+    }
+
+    /// Entries recorded in `from..to`, oldest first, across every audit file still on disk.
+    pub fn entries_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<AuditEntry>> {
+        // Hold the writer so no file is rotated mid-read.
+        let _writer = self.writer.lock();
+        let mut entries = Vec::new();
+        for path in audit_files(&self.dir)? {
+            let file = match File::open(&path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                if line.is_empty() {
+                    continue;
+                }
+                let entry: AuditEntry = serde_json::from_str(&line)?;
+                if entry.timestamp >= from && entry.timestamp < to {
+                    entries.push(entry);
+                }
+            }
+        }
+        Ok(entries)
+    }
+}
+
+fn broken(verification: AuditVerification, seq: Option<u64>, error: String) -> AuditVerification {
+    warn!(?seq, %error, "Audit log chain is broken");
+    AuditVerification {
+        valid: false,
+        first_invalid_seq: seq,
+        error: Some(error),
+        ..verification
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    Ok(OpenOptions::new().create(true).append(true).open(path)?)
+}
+
+/// Rotated files in chain order, followed by the current file.
+fn audit_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut rotated: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("audit-") && name.ends_with(".log"))
+        })
+        .collect();
+    // Sequence numbers are zero-padded, so names sort in chain order.
+    rotated.sort();
+    rotated.push(dir.join(CURRENT_FILE));
+    Ok(rotated)
+}
+
+/// The last entry of an audit file, if it exists and has any.
+fn last_entry(path: &Path) -> Result<Option<AuditEntry>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut last = None;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if !line.is_empty() {
+            last = Some(line);
+        }
+    }
+    match last {
+        Some(line) => Ok(Some(serde_json::from_str(&line)?)),
+        None => Ok(None),
+    }
+}
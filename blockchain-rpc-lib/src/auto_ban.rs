@@ -0,0 +1,232 @@
+use crate::config::AutoBanConfig;
+use crate::firewall::{canonical_ip, network_contains};
+use chrono::{DateTime, Utc};
+use ipnetwork::IpNetwork;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Something a client did that counts towards an automatic ban.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Offense {
+    /// Refused by the firewall rules.
+    Denied,
+    /// Rejected by a rate limit.
+    RateLimited,
+    /// Invalid credentials or oversized headers.
+    Malformed,
+}
+
+/// An active automatic ban.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoBan {
+    /// The banned IP, or the CIDR it was widened to.
+    pub network: IpNetwork,
+    /// The offense that crossed the threshold.
+    pub offense: Offense,
+    /// Offenses counted in the window that triggered the ban.
+    pub offenses: u32,
+    /// How many times in a row the network has been banned, this ban included.
+    pub ban_count: u32,
+    pub banned_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct Offender {
+    window_started: Instant,
+    offenses: u32,
+    /// Bans so far, each doubling the next one's duration. Forgotten once the network stays
+    /// clean for `forget_after_secs` after its last ban ends.
+    bans: u32,
+    last_ban_ends: Option<Instant>,
+    ban: Option<AutoBan>,
+}
+
+/// Counts offenses per IP (or CIDR, with a prefix shorter than a single address) and bans
+/// sources that commit `max_offenses` of them within `window_secs`. Bans start at `ban_secs`,
+/// double with every repeat and are capped at `max_ban_secs`.
+///
+/// Bans are kept in memory only, so a restart lifts them.
+#[derive(Debug)]
+pub struct AutoBanner {
+    max_offenses: u32,
+    window: Duration,
+    ban: Duration,
+    max_ban: Duration,
+    forget_after: Duration,
+    ipv4_prefix: u8,
+    ipv6_prefix: u8,
+    exempt_ips: HashSet<IpNetwork>,
+    offenders: Mutex<HashMap<IpNetwork, Offender>>,
+}
+
+impl AutoBanner {
+    pub fn new(config: &AutoBanConfig) -> Self {
+        AutoBanner {
+            max_offenses: config.max_offenses.max(1),
+            window: Duration::from_secs(config.window_secs.max(1)),
+            ban: Duration::from_secs(config.ban_secs.max(1)),
+            max_ban: Duration::from_secs(config.max_ban_secs.max(config.ban_secs).max(1)),
+            forget_after: Duration::from_secs(config.forget_after_secs),
+            ipv4_prefix: config.ipv4_prefix.min(32),
+            ipv6_prefix: config.ipv6_prefix.min(128),
+            exempt_ips: config.exempt_ips.clone(),
+            offenders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts an offense by `ip`, returning the ban it triggered, if any. Offenses by exempt
+    /// or already banned sources aren't counted.
+    pub fn record(&self, ip: IpAddr, offense: Offense) -> Option<AutoBan> {
+        if self.exempt_ips.iter().any(|net| network_contains(net, ip)) {
+            return None;
+        }
+        let network = self.network(ip)?;
+        let now = Instant::now();
+        let mut offenders = self.offenders.lock();
+        let offender = offenders.entry(network).or_insert_with(|| Offender {
+            window_started: now,
+            offenses: 0,
+            bans: 0,
+            last_ban_ends: None,
+            ban: None,
+        });
+        let utc_now = Utc::now();
+        if offender
+            .ban
+            .as_ref()
+            .is_some_and(|ban| ban.expires_at > utc_now)
+        {
+            return None;
+        }
+        if now.duration_since(offender.window_started) > self.window {
+            offender.window_started = now;
+            offender.offenses = 0;
+        }
+        offender.offenses += 1;
+        if offender.offenses < self.max_offenses {
+            return None;
+        }
+
+        if offender
+            .last_ban_ends
+            .is_some_and(|ends| now.saturating_duration_since(ends) > self.forget_after)
+        {
+            offender.bans = 0;
+        }
+        let duration = self
+            .ban
+            .saturating_mul(2u32.saturating_pow(offender.bans))
+            .min(self.max_ban);
+        let ban = AutoBan {
+            network,
+            offense,
+            offenses: offender.offenses,
+            ban_count: offender.bans + 1,
+            banned_at: utc_now,
+            expires_at: chrono::Duration::from_std(duration)
+                .ok()
+                .and_then(|duration| utc_now.checked_add_signed(duration))
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+        };
+        offender.bans += 1;
+        offender.last_ban_ends = Some(now.checked_add(duration).unwrap_or(now));
+        offender.offenses = 0;
+        offender.ban = Some(ban.clone());
+        Some(ban)
+    }
+
+    /// Whether `ip` is covered by an active automatic ban.
+    pub fn is_banned(&self, ip: &IpAddr) -> bool {
+        self.ban_expiry(ip).is_some()
+    }
+
+    /// When the active automatic ban covering `ip` ends, if there is one.
+    pub fn ban_expiry(&self, ip: &IpAddr) -> Option<DateTime<Utc>> {
+        let network = self.network(*ip)?;
+        let now = Utc::now();
+        self.offenders
+            .lock()
+            .get(&network)
+            .and_then(|offender| offender.ban.as_ref())
+            .map(|ban| ban.expires_at)
+            .filter(|expires_at| *expires_at > now)
+    }
+
+    /// Active automatic bans, soonest to expire first.
+    pub fn bans(&self) -> Vec<AutoBan> {
+        let now = Utc::now();
+        let mut bans: Vec<AutoBan> = self
+            .offenders
+            .lock()
+            .values()
+            .filter_map(|offender| offender.ban.clone())
+            .filter(|ban| ban.expires_at > now)
+            .collect();
+        bans.sort_by_key(|ban| ban.expires_at);
+        bans
+    }
+
+    /// Lifts the active ban on `network`, returning whether there was one. Its ban count is
+    /// kept, so a relapse is banned for longer.
+    pub fn lift(&self, network: &IpNetwork) -> bool {
+        let mut offenders = self.offenders.lock();
+        let Some(offender) = offenders.get_mut(network) else {
+            return false;
+        };
+        let active = offender
+            .ban
+            .take()
+            .is_some_and(|ban| ban.expires_at > Utc::now());
+        if active {
+            offender.last_ban_ends = Some(Instant::now());
+        }
+        active
+    }
+
+    /// Drops offenders with no recent offense and no ban to remember.
+    pub fn prune(&self) {
+        let now = Instant::now();
+        let utc_now = Utc::now();
+        self.offenders.lock().retain(|_, offender| {
+            let counting = now.duration_since(offender.window_started) <= self.window;
+            let banned = offender
+                .ban
+                .as_ref()
+                .is_some_and(|ban| ban.expires_at > utc_now);
+            let remembered = offender
+                .last_ban_ends
+                .is_some_and(|ends| now.saturating_duration_since(ends) <= self.forget_after);
+            counting || banned || remembered
+        });
+    }
+
+    /// Number of active automatic bans.
+    pub fn active_bans(&self) -> usize {
+        let now = Utc::now();
+        self.offenders
+            .lock()
+            .values()
+            .filter(|offender| {
+                offender
+                    .ban
+                    .as_ref()
+                    .is_some_and(|ban| ban.expires_at > now)
+            })
+            .count()
+    }
+
+    /// The network offenses by `ip` are counted and banned under.
+    fn network(&self, ip: IpAddr) -> Option<IpNetwork> {
+        let ip = canonical_ip(ip);
+        let prefix = match ip {
+            IpAddr::V4(_) => self.ipv4_prefix,
+            IpAddr::V6(_) => self.ipv6_prefix,
+        };
+        let network = IpNetwork::new(ip, prefix).ok()?;
+        IpNetwork::new(network.network(), prefix).ok()
+    }
+}
@@ -0,0 +1,155 @@
+use crate::audit::{AuditEntry, AuditRecord};
+use crate::metering::{UsageCounters, UsageReport, UsageSubject};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Audit actions that grant, extend or revoke access.
+const GRANT_ACTIONS: &[&str] = &["allow", "revoke", "grant_temporary", "extend_temporary"];
+
+/// The format of a billing export.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BillingFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+/// One account's usage over one metering period.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRow {
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub account: String, // AccountId32 as string
+    #[serde(flatten)]
+    pub counters: UsageCounters,
+}
+
+/// A grant, extension or revocation of access, from the audit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrantRow {
+    pub timestamp: DateTime<Utc>,
+    /// `allow`, `revoke`, `grant_temporary` or `extend_temporary`.
+    pub action: String,
+    /// The account or IP network.
+    pub target: String,
+    /// The job that made the change, if a job call did.
+    pub job_id: Option<u64>,
+    pub call_id: Option<u64>,
+    /// The admin token or account, if made through the admin API.
+    pub actor: Option<String>,
+}
+
+/// Flattens metered periods into per-account rows, keeping only `account`'s if given.
+pub fn usage_rows(periods: &[UsageReport], account: Option<&str>) -> Vec<UsageRow> {
+    periods
+        .iter()
+        .flat_map(|period| {
+            period
+                .usage
+                .iter()
+                .filter_map(|record| match &record.subject {
+                    UsageSubject::Account(a) if account.is_none_or(|account| account == a) => {
+                        Some(UsageRow {
+                            period_start: period.period_start,
+                            period_end: period.period_end,
+                            account: a.clone(),
+                            counters: record.counters,
+                        })
+                    }
+                    _ => None,
+                })
+        })
+        .collect()
+}
+
+/// The grants, extensions and revocations among audit entries, keeping only those of
+/// `account` if given.
+pub fn grant_rows(entries: &[AuditEntry], account: Option<&str>) -> Vec<GrantRow> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let (action, target, job_id, call_id, actor) = match &entry.record {
+                AuditRecord::JobCall {
+                    job_id,
+                    call_id,
+                    action,
+                    target,
+                } => (action, target, Some(*job_id), Some(*call_id), None),
+                AuditRecord::Admin {
+                    actor,
+                    action,
+                    target,
+                } => (action, target, None, None, Some(actor.clone())),
+                AuditRecord::Firewall { .. } | AuditRecord::Relay { .. } => return None,
+            };
+            if !GRANT_ACTIONS.contains(&action.as_str())
+                || account.is_some_and(|account| account != target)
+            {
+                return None;
+            }
+            Some(GrantRow {
+                timestamp: entry.timestamp,
+                action: action.clone(),
+                target: target.clone(),
+                job_id,
+                call_id,
+                actor,
+            })
+        })
+        .collect()
+}
+
+/// Usage rows as CSV, with a header line.
+pub fn usage_csv(rows: &[UsageRow]) -> String {
+    let mut csv = String::from(
+        "period_start,period_end,account,requests,calls,compute_units,request_bytes,\
+         response_bytes,ws_messages_in,ws_messages_out\n",
+    );
+    for row in rows {
+        let c = &row.counters;
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{},{},{}",
+            row.period_start.to_rfc3339(),
+            row.period_end.to_rfc3339(),
+            csv_field(&row.account),
+            c.requests,
+            c.calls,
+            c.compute_units,
+            c.request_bytes,
+            c.response_bytes,
+            c.ws_messages_in,
+            c.ws_messages_out,
+        );
+    }
+    csv
+}
+
+/// Grant rows as CSV, with a header line. Missing values are empty.
+pub fn grants_csv(rows: &[GrantRow]) -> String {
+    let mut csv = String::from("timestamp,action,target,job_id,call_id,actor\n");
+    for row in rows {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{}",
+            row.timestamp.to_rfc3339(),
+            csv_field(&row.action),
+            csv_field(&row.target),
+            row.job_id.map(|id| id.to_string()).unwrap_or_default(),
+            row.call_id.map(|id| id.to_string()).unwrap_or_default(),
+            csv_field(row.actor.as_deref().unwrap_or_default()),
+        );
+    }
+    csv
+}
+
+/// Quotes a field containing a comma, quote or line break, doubling its quotes.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,117 @@
+use crate::config::CircuitBreakerConfig;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CircuitState {
+    /// Requests flow to the backend.
+    Closed,
+    /// The backend is failing; requests are rejected without being sent.
+    Open,
+    /// A limited number of trial requests probe whether the backend recovered.
+    HalfOpen,
+}
+
+/// Circuit breaker guarding the backend node.
+///
+/// Opens after `failure_threshold` consecutive failures, rejecting requests for `open_secs`.
+/// It then lets `half_open_requests` trial requests through: a success closes the circuit
+/// again, a failure re-opens it.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_for: Duration,
+    half_open_requests: u32,
+    inner: Mutex<Breaker>,
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: CircuitState,
+    consecutive_failures: u32,
+    /// When the circuit last opened or went half-open.
+    since: Instant,
+    trials: u32,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &CircuitBreakerConfig) -> Self {
+        CircuitBreaker {
+            failure_threshold: config.failure_threshold.max(1),
+            open_for: Duration::from_secs(config.open_secs),
+            half_open_requests: config.half_open_requests.max(1),
+            inner: Mutex::new(Breaker {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                since: Instant::now(),
+                trials: 0,
+            }),
+        }
+    }
+
+    /// Checks whether a request may be sent to the backend.
+    ///
+    /// Returns `Err` with the time until the circuit may let requests through again.
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let mut breaker = self.inner.lock();
+        let elapsed = breaker.since.elapsed();
+        match breaker.state {
+            CircuitState::Closed => Ok(()),
+            CircuitState::Open if elapsed < self.open_for => Err(self.open_for - elapsed),
+            CircuitState::Open => {
+                info!("Backend circuit half-open, sending trial requests");
+                breaker.state = CircuitState::HalfOpen;
+                breaker.since = Instant::now();
+                breaker.trials = 1;
+                Ok(())
+            }
+            // Trials whose outcome was never recorded (e.g. the client went away) are given
+            // up on after another open period.
+            CircuitState::HalfOpen
+                if breaker.trials < self.half_open_requests || elapsed >= self.open_for =>
+            {
+                if elapsed >= self.open_for {
+                    breaker.since = Instant::now();
+                    breaker.trials = 0;
+                }
+                breaker.trials += 1;
+                Ok(())
+            }
+            CircuitState::HalfOpen => Err(self.open_for.saturating_sub(elapsed)),
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut breaker = self.inner.lock();
+        if breaker.state != CircuitState::Closed {
+            info!("Backend recovered, circuit closed");
+        }
+        breaker.state = CircuitState::Closed;
+        breaker.consecutive_failures = 0;
+    }
+
+    pub fn record_failure(&self) {
+        let mut breaker = self.inner.lock();
+        breaker.consecutive_failures = breaker.consecutive_failures.saturating_add(1);
+        let open = match breaker.state {
+            CircuitState::Closed => breaker.consecutive_failures >= self.failure_threshold,
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+        if open {
+            warn!(
+                failures = breaker.consecutive_failures,
+                open_secs = self.open_for.as_secs(),
+                "Backend failing, circuit opened"
+            );
+            breaker.state = CircuitState::Open;
+            breaker.since = Instant::now();
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.inner.lock().state
+    }
+}
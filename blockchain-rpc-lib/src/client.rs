@@ -0,0 +1,372 @@
+use crate::Result;
+use crate::admin::{IntrospectionResponse, UsageExport};
+use crate::auto_ban::AutoBan;
+use crate::error::Error;
+use crate::firewall::{CleanupStats, FirewallRules, TemporaryGrant};
+use crate::jobs::allow_access::AccessTarget;
+use crate::proxy::local_methods::GatewayInfo;
+use crate::read_only::ReadOnlyStatus;
+use crate::signature_auth::{
+    ACCOUNT_HEADER, NONCE_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER, signing_payload,
+};
+use chrono::Utc;
+use rand::RngCore;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Method, RequestBuilder, Response};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use sp_core::Pair;
+use sp_core::sr25519::Pair as Sr25519Pair;
+use sp_runtime::AccountId32;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use url::{Position, Url};
+
+/// How a client authenticates to the gateway.
+#[derive(Clone)]
+pub enum Credentials {
+    /// No credentials: the firewall authorizes requests by client IP.
+    Anonymous,
+    /// An API key issued with `issue_api_key`, or the admin API's token, sent as
+    /// `Authorization: Bearer`.
+    Bearer(String),
+    /// Every request signed with the account's sr25519 key (`X-Account`, `X-Timestamp`,
+    /// `X-Nonce`, `X-Signature`), with a fresh nonce each time. [`AdminClient`] uses
+    /// increasing nonces, as the admin API requires.
+    Signed(Sr25519Pair),
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Anonymous => f.write_str("Anonymous"),
+            Credentials::Bearer(_) => f.write_str("Bearer(<redacted>)"),
+            Credentials::Signed(pair) => f
+                .debug_tuple("Signed")
+                .field(&AccountId32::from(pair.public().0))
+                .finish(),
+        }
+    }
+}
+
+/// An HTTP client for one gateway endpoint, adding credentials to every request.
+#[derive(Debug, Clone)]
+struct Transport {
+    http: reqwest::Client,
+    base_url: Url,
+    credentials: Credentials,
+    /// The last nonce signed with, if nonces must increase; random nonces otherwise.
+    last_nonce: Option<Arc<AtomicU64>>,
+}
+
+impl Transport {
+    fn new(base_url: Url, credentials: Credentials) -> Self {
+        Transport {
+            http: reqwest::Client::new(),
+            base_url,
+            credentials,
+            last_nonce: None,
+        }
+    }
+
+    /// Like [`Transport::new`], signing with increasing nonces: the time in microseconds, or
+    /// one above the last nonce if the clock hasn't moved past it.
+    fn with_increasing_nonces(base_url: Url, credentials: Credentials) -> Self {
+        Transport {
+            last_nonce: Some(Arc::new(AtomicU64::new(0))),
+            ..Transport::new(base_url, credentials)
+        }
+    }
+
+    fn next_nonce(&self) -> String {
+        match &self.last_nonce {
+            Some(last) => {
+                let now = Utc::now().timestamp_micros().max(0) as u64;
+                let previous = last
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| {
+                        Some(now.max(last + 1))
+                    })
+                    .unwrap_or_default();
+                now.max(previous + 1).to_string()
+            }
+            None => {
+                let mut nonce = [0u8; 16];
+                rand::thread_rng().fill_bytes(&mut nonce);
+                hex::encode(nonce)
+            }
+        }
+    }
+
+    /// `path` below the base URL, e.g. `firewall/allow` below `http://127.0.0.1:9615/`.
+    fn url(&self, path: &str) -> Url {
+        let mut url = self.base_url.clone();
+        let base = url.path().trim_end_matches('/').to_string();
+        url.set_path(&format!("{base}/{}", path.trim_start_matches('/')));
+        url
+    }
+
+    /// A request with the JSON `body`, if any, signed over the exact bytes sent.
+    fn request(&self, method: Method, url: Url, body: Option<Vec<u8>>) -> RequestBuilder {
+        let mut request = self.http.request(method.clone(), url.clone());
+        let body = body.unwrap_or_default();
+        if !body.is_empty() {
+            request = request.header(CONTENT_TYPE, "application/json");
+        }
+        let request = match &self.credentials {
+            Credentials::Anonymous => request,
+            Credentials::Bearer(token) => request.bearer_auth(token),
+            Credentials::Signed(pair) => {
+                let timestamp = Utc::now().timestamp();
+                let nonce = self.next_nonce();
+                let target = &url[Position::BeforePath..Position::AfterQuery];
+                let payload = signing_payload(timestamp, &nonce, &method, target, &body);
+                request
+                    .header(
+                        ACCOUNT_HEADER,
+                        AccountId32::from(pair.public().0).to_string(),
+                    )
+                    .header(TIMESTAMP_HEADER, timestamp.to_string())
+                    .header(NONCE_HEADER, nonce)
+                    .header(
+                        SIGNATURE_HEADER,
+                        hex::encode(pair.sign(payload.as_bytes()).0),
+                    )
+            }
+        };
+        request.body(body)
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&impl Serialize>,
+    ) -> Result<Response> {
+        let body = body.map(serde_json::to_vec).transpose()?;
+        let response = self.request(method, self.url(path), body).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::GatewayRequestFailed {
+                status: status.as_u16(),
+                message: response.text().await.unwrap_or_default(),
+            });
+        }
+        Ok(response)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        Ok(self
+            .send(Method::GET, path, None::<&()>)
+            .await?
+            .json()
+            .await?)
+    }
+}
+
+/// Typed JSON-RPC client for the gateway's proxy listener, e.g. for integrators' services and
+/// tests.
+#[derive(Debug)]
+pub struct GatewayClient {
+    transport: Transport,
+    next_id: AtomicU64,
+}
+
+impl GatewayClient {
+    /// A client for the route at `url`, e.g. `https://rpc.example.com/` or a route's prefix.
+    pub fn new(url: Url, credentials: Credentials) -> Self {
+        GatewayClient {
+            transport: Transport::new(url, credentials),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Calls `method`, returning its `result`. A JSON-RPC error, including the gateway's own
+    /// (a blocked method, a rate limit), fails with [`Error::JsonRpcError`]; a rejection
+    /// before the call is read (credentials, firewall) with [`Error::GatewayRequestFailed`].
+    pub async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id.fetch_add(1, Ordering::Relaxed),
+            "method": method,
+            "params": params,
+        });
+        let response = self
+            .transport
+            .request(
+                Method::POST,
+                self.transport.base_url.clone(),
+                Some(serde_json::to_vec(&request)?),
+            )
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.text().await?;
+        // JSON-RPC errors come with error statuses too (403 for blocked methods, 429)
+        let mut reply: Value = match serde_json::from_str(&body) {
+            Ok(reply) => reply,
+            Err(_) if !status.is_success() => {
+                return Err(Error::GatewayRequestFailed {
+                    status: status.as_u16(),
+                    message: body,
+                });
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(error) = reply.get("error") {
+            return Err(Error::JsonRpcError(error.to_string()));
+        }
+        match reply.get_mut("result") {
+            Some(result) => Ok(serde_json::from_value(result.take())?),
+            None => Err(Error::GatewayRequestFailed {
+                status: status.as_u16(),
+                message: body,
+            }),
+        }
+    }
+
+    /// Checks connectivity and credentials with the gateway-local `gateway_ping`, which never
+    /// reaches the backend. Requires `rpc.gateway_methods`.
+    pub async fn ping(&self) -> Result<()> {
+        self.call::<String>("gateway_ping", json!([]))
+            .await
+            .map(|_| ())
+    }
+
+    /// The caller's account, plan, access expiry and remaining rate limit and quota, from the
+    /// gateway-local `gateway_info`. Requires `rpc.gateway_methods`.
+    pub async fn info(&self) -> Result<GatewayInfo> {
+        self.call("gateway_info", json!([])).await
+    }
+}
+
+/// Typed client for the operator admin API, authenticated with the admin token
+/// ([`Credentials::Bearer`]) or as an admin account.
+#[derive(Debug, Clone)]
+pub struct AdminClient {
+    transport: Transport,
+}
+
+impl AdminClient {
+    /// A client for the admin API at `url`, e.g. `http://127.0.0.1:9615/`.
+    pub fn new(url: Url, credentials: Credentials) -> Self {
+        AdminClient {
+            transport: Transport::with_increasing_nonces(url, credentials),
+        }
+    }
+
+    /// The account an API key belongs to and its access status; inactive if unknown.
+    pub async fn introspect(&self, api_key: &str) -> Result<IntrospectionResponse> {
+        Ok(self
+            .transport
+            .send(
+                Method::POST,
+                "auth/introspect",
+                Some(&json!({"token": api_key})),
+            )
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Metered usage of the open period, the month so far and unreported periods and months.
+    /// Requires `[metering]`.
+    pub async fn usage(&self) -> Result<UsageExport> {
+        self.transport.get("usage").await
+    }
+
+    /// Config and dynamic allow/deny rules.
+    pub async fn rules(&self) -> Result<FirewallRules> {
+        self.transport.get("firewall/rules").await
+    }
+
+    /// Permanently allows an IP/CIDR or account, like the `allow_access` job.
+    pub async fn allow(&self, target: &AccessTarget) -> Result<()> {
+        self.transport
+            .send(Method::POST, "firewall/allow", Some(target))
+            .await
+            .map(|_| ())
+    }
+
+    /// Removes a dynamic allow rule, like the `revoke_access` job.
+    pub async fn revoke(&self, target: &AccessTarget) -> Result<()> {
+        self.transport
+            .send(Method::DELETE, "firewall/allow", Some(target))
+            .await
+            .map(|_| ())
+    }
+
+    /// Bans an IP/CIDR or account, like the `ban_target` job.
+    pub async fn ban(&self, target: &AccessTarget) -> Result<()> {
+        self.transport
+            .send(Method::POST, "firewall/deny", Some(target))
+            .await
+            .map(|_| ())
+    }
+
+    /// Active temporary grants.
+    pub async fn temporary_grants(&self) -> Result<Vec<TemporaryGrant>> {
+        self.transport.get("firewall/temporary").await
+    }
+
+    /// Grants temporary access for `duration_secs`, optionally on a `[plans]` tier.
+    pub async fn grant_temporary(
+        &self,
+        target: &AccessTarget,
+        duration_secs: u64,
+        plan: Option<&str>,
+    ) -> Result<()> {
+        let body = json!({"target": target, "duration_secs": duration_secs, "plan": plan});
+        self.transport
+            .send(Method::POST, "firewall/temporary", Some(&body))
+            .await
+            .map(|_| ())
+    }
+
+    /// Removes expired temporary grants now.
+    pub async fn cleanup(&self) -> Result<CleanupStats> {
+        Ok(self
+            .transport
+            .send(Method::POST, "firewall/cleanup", None::<&()>)
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Active automatic bans.
+    pub async fn auto_bans(&self) -> Result<Vec<AutoBan>> {
+        self.transport.get("firewall/auto-bans").await
+    }
+
+    /// Whether the gateway is read-only, and why.
+    pub async fn read_only(&self) -> Result<Option<ReadOnlyStatus>> {
+        self.transport.get("read-only").await
+    }
+
+    /// Switches read-only mode on, like the `set_read_only` job.
+    pub async fn enable_read_only(&self, reason: Option<&str>) -> Result<ReadOnlyStatus> {
+        Ok(self
+            .transport
+            .send(Method::PUT, "read-only", Some(&json!({"reason": reason})))
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Switches read-only mode off. Fails with status 404 if it was off.
+    pub async fn disable_read_only(&self) -> Result<()> {
+        self.transport
+            .send(Method::DELETE, "read-only", None::<&()>)
+            .await
+            .map(|_| ())
+    }
+
+    /// Lifts an automatic ban of an IP/CIDR early.
+    pub async fn lift_auto_ban(&self, target: &AccessTarget) -> Result<()> {
+        self.transport
+            .send(Method::DELETE, "firewall/auto-bans", Some(target))
+            .await
+            .map(|_| ())
+    }
+}
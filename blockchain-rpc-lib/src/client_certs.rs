@@ -0,0 +1,55 @@
+use crate::config::ClientAuthConfig;
+use sha2::{Digest, Sha256};
+use sp_runtime::AccountId32;
+use x509_parser::extensions::GeneralName;
+
+/// Identity presented by a client certificate during the TLS handshake.
+#[derive(Debug, Clone)]
+pub struct ClientCertificate {
+    /// SHA-256 fingerprint of the DER-encoded certificate, as lowercase hex.
+    pub fingerprint: String,
+    /// DNS names, email addresses and URIs from the subject alternative name extension.
+    pub subject_alt_names: Vec<String>,
+}
+
+impl ClientCertificate {
+    pub fn from_der(der: &[u8]) -> Self {
+        let fingerprint = hex::encode(Sha256::digest(der));
+        let subject_alt_names = x509_parser::parse_x509_certificate(der)
+            .ok()
+            .and_then(|(_, cert)| {
+                let san = cert.subject_alternative_name().ok()??;
+                Some(
+                    san.value
+                        .general_names
+                        .iter()
+                        .filter_map(|name| match name {
+                            GeneralName::DNSName(name)
+                            | GeneralName::RFC822Name(name)
+                            | GeneralName::URI(name) => Some(name.to_string()),
+                            _ => None,
+                        })
+                        .collect(),
+                )
+            })
+            .unwrap_or_default();
+        Self {
+            fingerprint,
+            subject_alt_names,
+        }
+    }
+
+    /// The account this certificate is mapped to, matched by fingerprint first and then by
+    /// subject alternative name.
+    pub fn account(&self, config: &ClientAuthConfig) -> Option<AccountId32> {
+        config
+            .accounts
+            .get(&self.fingerprint)
+            .or_else(|| {
+                self.subject_alt_names
+                    .iter()
+                    .find_map(|name| config.accounts.get(name))
+            })
+            .cloned()
+    }
+}
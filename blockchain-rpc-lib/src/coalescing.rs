@@ -0,0 +1,184 @@
+use crate::config::CoalescingConfig;
+use crate::method_matcher::MethodMatcher;
+use axum::body::{Body, Bytes};
+use axum::http::{HeaderMap, StatusCode, header::CONTENT_LENGTH};
+use axum::response::{IntoResponse, Response};
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+/// A call identical requests share: the backend it goes to, its method and its params as
+/// compact JSON, empty when the client sent none.
+type CallKey = (String, String, String);
+
+type InFlight = Mutex<HashMap<CallKey, Arc<OnceCell<SharedResponse>>>>;
+
+/// How many calls were coalesced, for the admin API.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CoalescingStats {
+    /// Backend calls made for coalescable requests since startup.
+    pub upstream_calls: u64,
+    /// Requests answered with an identical concurrent request's backend call since startup.
+    pub coalesced: u64,
+}
+
+/// Single-flight coalescing of identical concurrent calls, per `[rpc.coalescing]`: while a
+/// call to an eligible method is in flight, identical calls (same backend, method and params)
+/// wait for its response instead of making their own, each receiving it with its own request
+/// `id`. Polling clients asking the same `eth_call` or `state_call` hundreds of times at once
+/// then cost the backend a single call.
+///
+/// Only single calls are coalesced, not batches. A response is shared only with the calls
+/// that arrived while it was outstanding; nothing is cached.
+pub struct RequestCoalescer {
+    methods: MethodMatcher<()>,
+    in_flight: InFlight,
+    upstream_calls: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+/// A call eligible for coalescing, with the request `id` its response must carry.
+pub(crate) struct CoalescableCall {
+    key: CallKey,
+    id: Value,
+}
+
+/// A backend response buffered to be shared by every request of a coalesced call.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl RequestCoalescer {
+    pub fn new(config: &CoalescingConfig) -> Self {
+        RequestCoalescer {
+            methods: config.methods.iter().map(|method| (method, ())).collect(),
+            in_flight: Mutex::new(HashMap::new()),
+            upstream_calls: AtomicU64::new(0),
+            coalesced: AtomicU64::new(0),
+        }
+    }
+
+    /// The call in `body`, bound for `backend`, if it may be coalesced: a single call (not a
+    /// batch or a notification) to an eligible method.
+    pub(crate) fn coalescable(&self, backend: &str, body: &[u8]) -> Option<CoalescableCall> {
+        let Ok(Value::Object(call)) = serde_json::from_slice::<Value>(body) else {
+            return None;
+        };
+        let method = call.get("method")?.as_str()?;
+        if !self.methods.matches(method) {
+            return None;
+        }
+        Some(CoalescableCall {
+            key: (
+                backend.to_string(),
+                method.to_string(),
+                call.get("params").map(Value::to_string).unwrap_or_default(),
+            ),
+            id: call.get("id")?.clone(),
+        })
+    }
+
+    /// Answers `call` with the response of an identical call in flight, or makes the call with
+    /// `send` and shares its response with identical calls arriving meanwhile. If the request
+    /// making the call goes away before it completes, one of those waiting makes it instead.
+    pub(crate) async fn run<F, Fut>(&self, call: CoalescableCall, send: F) -> Response
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Response>,
+    {
+        let cell = self
+            .in_flight
+            .lock()
+            .entry(call.key.clone())
+            .or_default()
+            .clone();
+        let mut sent = false;
+        let shared = cell
+            .get_or_init(|| async {
+                let _done = InFlightCall {
+                    in_flight: &self.in_flight,
+                    key: &call.key,
+                    cell: &cell,
+                };
+                sent = true;
+                self.upstream_calls.fetch_add(1, Ordering::Relaxed);
+                SharedResponse::buffer(send().await).await
+            })
+            .await;
+        if !sent {
+            self.coalesced.fetch_add(1, Ordering::Relaxed);
+        }
+        shared.response_for(&call.id)
+    }
+
+    pub fn stats(&self) -> CoalescingStats {
+        CoalescingStats {
+            upstream_calls: self.upstream_calls.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Stops new requests from joining a call once it completes or is abandoned, so they make a
+/// fresh one.
+struct InFlightCall<'a> {
+    in_flight: &'a InFlight,
+    key: &'a CallKey,
+    cell: &'a Arc<OnceCell<SharedResponse>>,
+}
+
+impl Drop for InFlightCall<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock();
+        if in_flight
+            .get(self.key)
+            .is_some_and(|cell| Arc::ptr_eq(cell, self.cell))
+        {
+            in_flight.remove(self.key);
+        }
+    }
+}
+
+impl SharedResponse {
+    async fn buffer(response: Response) -> Self {
+        let (mut parts, body) = response.into_parts();
+        parts.headers.remove(CONTENT_LENGTH);
+        match axum::body::to_bytes(body, usize::MAX).await {
+            Ok(body) => SharedResponse {
+                status: parts.status,
+                headers: parts.headers,
+                body,
+            },
+            Err(e) => {
+                warn!(error = %e, "Failed to read the backend response of a coalesced call");
+                SharedResponse {
+                    status: StatusCode::BAD_GATEWAY,
+                    headers: HeaderMap::new(),
+                    body: Bytes::from_static(b"Upstream error"),
+                }
+            }
+        }
+    }
+
+    /// The response, carrying request `id` in place of the id of the request that made the
+    /// call.
+    fn response_for(&self, id: &Value) -> Response {
+        let body = match serde_json::from_slice::<Value>(&self.body) {
+            Ok(Value::Object(mut response)) if response.get("id").is_some_and(|own| own != id) => {
+                response.insert("id".to_string(), id.clone());
+                Bytes::from(Value::Object(response).to_string())
+            }
+            _ => self.body.clone(),
+        };
+        (self.status, self.headers.clone(), Body::from(body)).into_response()
+    }
+}
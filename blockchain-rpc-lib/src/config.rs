@@ -1,11 +1,20 @@
 use crate::Result;
+use crate::cors::is_wildcard;
 use crate::error::Error;
+use crate::firewall::canonical_network;
+use crate::webhooks::{WebhookEventType, WebhookTarget};
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, Utc};
 use ipnetwork::IpNetwork;
 use serde::{Deserialize, Serialize};
 use sp_runtime::AccountId32;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::net::{IpAddr, SocketAddr};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use url::Url;
 
@@ -15,32 +24,2330 @@ pub struct ServiceConfig {
     pub firewall: FirewallConfig,
     #[serde(default)]
     pub webhooks: WebhookConfig,
+    #[serde(default)]
+    pub methods: MethodFilterConfig,
+    /// Per-method size and block-range limits.
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub jobs: JobsConfig,
+    /// Sharing rule changes made through the admin API with the other operators of the
+    /// service, through `sync_rules` job calls. Disabled when absent.
+    #[serde(default)]
+    pub sync: Option<RuleSyncConfig>,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    #[serde(default)]
+    pub websocket: WebSocketConfig,
+    #[serde(default)]
+    pub fingerprint: FingerprintConfig,
+    #[serde(default)]
+    pub origin_heatmap: OriginHeatmapConfig,
+    #[serde(default)]
+    pub reload: ReloadConfig,
+    /// Per-account usage metering for billing. Disabled when absent.
+    #[serde(default)]
+    pub metering: Option<MeteringConfig>,
+    /// Hash-chained audit log of access decisions and rule changes. Disabled when absent.
+    #[serde(default)]
+    pub audit: Option<AuditConfig>,
+    /// Structured log of every request and WebSocket session, kept apart from tracing output.
+    /// Disabled when absent.
+    #[serde(default)]
+    pub access_log: Option<AccessLogConfig>,
+    /// Queryable history of recent firewall events, kept in the state store. Disabled when
+    /// absent.
+    #[serde(default)]
+    pub event_history: Option<EventHistoryConfig>,
+    /// A `DailyDigest` webhook event summarizing each UTC day's traffic. Disabled when absent.
+    #[serde(default)]
+    pub daily_digest: Option<DailyDigestConfig>,
+    /// Time-bucketed request, error, latency and connection series by route, method and
+    /// account, served by the admin API. Disabled when absent. Requires a restart to change.
+    #[serde(default)]
+    pub traffic_stats: Option<TrafficStatsConfig>,
+    /// On-chain verification of `pay_for_access` payments. When absent, payment is assumed
+    /// to have been checked by the calling contract.
+    #[serde(default)]
+    pub payments: Option<PaymentVerificationConfig>,
+    /// Access tiers sold through `pay_for_access`, by plan ID (e.g. `basic`, `pro`).
+    #[serde(default)]
+    pub plans: HashMap<String, PlanConfig>,
+    /// Signing and submitting transactions for accounts with a relay policy, with a dedicated
+    /// keystore key. Disabled when absent. Requires a restart to change.
+    #[serde(default)]
+    pub relay: Option<RelayConfig>,
+    /// Roles unlocking privileged methods, by role name (e.g. `submitter`). Accounts get roles
+    /// through the `set_account_roles` job.
+    #[serde(default)]
+    pub roles: HashMap<String, RoleConfig>,
+    /// Path prefixes served by backends other than `rpc.proxy_to_url`.
+    #[serde(default)]
+    pub routes: Vec<RouteConfig>,
+    /// Where dynamic rules, grants and metered usage are persisted.
+    #[serde(default)]
+    pub storage: StorageConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcConfig {
+    pub listen_addr: SocketAddr,
+    pub proxy_to_url: Url,
+    /// More nodes of the same chain, sharing the load with `proxy_to_url`. Each client is
+    /// pinned to one of them by consistent hashing of its account, or its IP when it doesn't
+    /// authenticate, so subscriptions and follow-up requests reach the node holding its state.
+    #[serde(default)]
+    pub upstreams: Vec<Url>,
+    #[serde(default = "default_max_body_size_bytes")]
+    pub max_body_size_bytes: usize,
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    /// Uploads that send no body data for this many seconds once the gateway starts reading
+    /// them are rejected with `408`.
+    #[serde(default = "default_body_idle_timeout_secs")]
+    pub body_idle_timeout_secs: u64,
+    /// Requests with more headers than this are rejected with `431`.
+    #[serde(default = "default_max_header_count")]
+    pub max_header_count: usize,
+    /// Requests whose header names and values exceed this many bytes in total are rejected
+    /// with `431`.
+    #[serde(default = "default_max_header_bytes")]
+    pub max_header_bytes: usize,
+    /// Individual headers with values longer than this are dropped before forwarding upstream.
+    #[serde(default = "default_max_forwarded_header_bytes")]
+    pub max_forwarded_header_bytes: usize,
+    /// Serve HTTPS/WSS instead of plaintext when set.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// TLS settings for `https://` and `wss://` connections to the backend node.
+    #[serde(default)]
+    pub upstream_tls: UpstreamTlsConfig,
+    /// Credentials sent to backends that require them, e.g. managed endpoints taking an API
+    /// key header or basic auth. Never serialized, and redacted from debug output.
+    #[serde(default, skip_serializing)]
+    pub upstream_auth: Vec<UpstreamAuthConfig>,
+    /// A proxy connections to the backends, and webhook deliveries, go through. Connections are
+    /// made directly when absent. Requires a restart to change.
+    #[serde(default)]
+    pub outbound_proxy: Option<OutboundProxyConfig>,
+    /// Pooling, keep-alive and HTTP version of HTTP connections to the backend.
+    #[serde(default)]
+    pub upstream_pool: UpstreamPoolConfig,
+    /// Stops sending requests to a failing backend for a while. Disabled when absent.
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Retries of failed backend requests.
+    #[serde(default)]
+    pub retry: UpstreamRetryConfig,
+    /// Caps HTTP requests in flight to the backend. Unlimited when absent.
+    #[serde(default)]
+    pub concurrency: Option<UpstreamConcurrencyConfig>,
+    /// Caps HTTP requests in flight to all backends together, across routes. Unlimited when
+    /// absent.
+    #[serde(default)]
+    pub global_concurrency: Option<UpstreamConcurrencyConfig>,
+    /// Maximum HTTP requests a single authenticated account may have in flight at once, until
+    /// their responses are sent. Requests over it are rejected with `429`. Plans may set their
+    /// own. Unlimited when absent.
+    #[serde(default)]
+    pub max_in_flight_requests_per_account: Option<usize>,
+    /// Per-call enforcement of JSON-RPC batches: a size cap, and a rate-limit token per call
+    /// instead of per HTTP request. Disabled when absent.
+    #[serde(default)]
+    pub batch: Option<BatchConfig>,
+    /// Strict checks of JSON-RPC requests before they are proxied, rejecting malformed ones
+    /// with JSON-RPC parse, invalid-request and invalid-params errors instead of forwarding
+    /// them. Disabled when absent. Requires a restart to change.
+    #[serde(default)]
+    pub validation: Option<RequestValidationConfig>,
+    /// Compression of responses to clients that accept it. Disabled when absent.
+    #[serde(default)]
+    pub compression: Option<CompressionConfig>,
+    /// Answering head queries from a live head subscription to the backend. Disabled when
+    /// absent.
+    #[serde(default)]
+    pub head_cache: Option<HeadCacheConfig>,
+    /// Coalescing of identical concurrent HTTP calls into one backend call. Disabled when
+    /// absent. Requires a restart to change.
+    #[serde(default)]
+    pub coalescing: Option<CoalescingConfig>,
+    /// Per-method latency of proxied HTTP calls and slow request logging. Disabled when
+    /// absent.
+    #[serde(default)]
+    pub latency: Option<LatencyConfig>,
+    /// Periodic probing of the backend's version, chain and supported methods. Disabled when
+    /// absent.
+    #[serde(default)]
+    pub probe: Option<UpstreamProbeConfig>,
+    /// `/healthz` and `/readyz` served by the gateway. Disabled when absent.
+    #[serde(default)]
+    pub health: Option<HealthConfig>,
+    /// Planned maintenance of backends, during which they are drained from rotation.
+    #[serde(default)]
+    pub maintenance: Vec<MaintenanceWindow>,
+    /// Pass-through of gRPC calls (`application/grpc`) to a gRPC backend, after the same
+    /// firewall, rate-limit and quota checks as JSON-RPC requests. Disabled when absent.
+    #[serde(default)]
+    pub grpc: Option<GrpcConfig>,
+    /// Chain the backend must be on: an EVM chain ID (`eth_chainId`, hex or decimal) or a
+    /// Substrate genesis hash. Requests are refused with `503` until the backend is verified
+    /// to be on it, and again after it reconnects until it is re-verified.
+    #[serde(default)]
+    pub expected_chain_id: Option<String>,
+    /// Sign forwarded requests with the operator's sr25519 key (`X-Gateway-Signature`).
+    #[serde(default)]
+    pub sign_upstream_requests: bool,
+    /// Answer the gateway-local methods `gateway_ping` and `gateway_info` (the caller's
+    /// resolved account, plan and remaining rate limit) over HTTP without the backend. Request
+    /// bodies are then always buffered.
+    #[serde(default)]
+    pub gateway_methods: bool,
+    /// Methods rejected while the gateway is in read-only mode (`*` suffixes match prefixes):
+    /// those submitting transactions or otherwise changing state.
+    #[serde(default = "default_non_idempotent_methods")]
+    pub read_only_methods: Vec<String>,
+    /// Clients whose W3C `traceparent`/`tracestate` headers are honored and forwarded upstream.
+    /// Trace headers from all other clients are stripped.
+    #[serde(default, deserialize_with = "deserialize_ip_networks")]
+    pub trusted_trace_sources: HashSet<IpNetwork>,
+    /// Load balancers and reverse proxies in front of the gateway. For connections from them,
+    /// the client address is taken from `Forwarded`/`X-Forwarded-For` (or the PROXY protocol
+    /// header) and used for firewall rules, rate limits and metering.
+    #[serde(default, deserialize_with = "deserialize_ip_networks")]
+    pub trusted_proxies: HashSet<IpNetwork>,
+    /// Expect a PROXY protocol (v1 or v2) header at the start of every connection from
+    /// `trusted_proxies`. Requires a restart to change.
+    #[serde(default)]
+    pub proxy_protocol: bool,
+    /// How long browsers may cache the answer to a CORS preflight, in seconds, without
+    /// `[rpc.cors]`. Requires a restart to change.
+    #[serde(default = "default_cors_max_age_secs")]
+    pub cors_max_age_secs: u64,
+    /// The CORS policy for browser clients. Every origin and header is allowed when absent.
+    /// Requires a restart to change.
+    #[serde(default)]
+    pub cors: Option<CorsConfig>,
+    /// What to do with `HEAD` requests, which JSON-RPC backends have no use for.
+    #[serde(default)]
+    pub head_requests: HeadRequests,
+    /// Gateway-originated pings on both legs of every WebSocket session, closing sessions whose
+    /// client or backend stops responding. Disabled when absent.
+    #[serde(default)]
+    pub websocket_keepalive: Option<WebSocketKeepaliveConfig>,
+    /// Serves WebSocket clients of a backend that only speaks HTTP, posting their calls to it
+    /// and emulating head subscriptions by polling. Disabled when absent. Requires a restart to
+    /// change.
+    #[serde(default)]
+    pub websocket_bridge: Option<WebSocketBridgeConfig>,
+    /// An archive node serving HTTP calls at blocks `proxy_to_url` has pruned. Disabled when
+    /// absent. Requires a restart to change.
+    #[serde(default)]
+    pub archive: Option<ArchiveConfig>,
+    /// A shadow backend sampled HTTP requests are duplicated to. Disabled when absent. Requires
+    /// a restart to change.
+    #[serde(default)]
+    pub mirror: Option<MirrorConfig>,
+}
+
+/// Duplicating a share of a route's HTTP requests to a shadow backend, e.g. a new node version
+/// to validate under real traffic before switching to it. The shadow backend's answers are
+/// discarded, and never delay or change the client's.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MirrorConfig {
+    /// The shadow backend.
+    pub url: Url,
+    /// Share of requests mirrored, in percent.
+    #[serde(default = "default_mirror_percent")]
+    pub percent: u8,
+    /// Log a warning when the shadow backend's answer differs from the primary backend's.
+    /// Buffers the primary backend's response to mirrored requests.
+    #[serde(default)]
+    pub log_differences: bool,
+    /// Mirrored requests awaiting the shadow backend at once. Further samples are skipped
+    /// rather than queued.
+    #[serde(default = "default_mirror_max_in_flight")]
+    pub max_in_flight: usize,
+    /// How long the shadow backend is given to answer, in seconds.
+    #[serde(default = "default_mirror_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl MirrorConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.percent > 100 {
+            return Err(Error::ConfigError(::config::ConfigError::Message(format!(
+                "mirror.percent must be at most 100, got {}",
+                self.percent
+            ))));
+        }
+        Ok(())
+    }
+}
+
+/// Routing of historical queries to an archive node, while calls near the head go to the
+/// route's own (pruned) backends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveConfig {
+    /// The archive node.
+    pub url: Url,
+    /// Blocks behind the head the fast node keeps state for, i.e. its pruning depth. Calls at
+    /// older blocks, `earliest`, or log filters starting there go to the archive node.
+    #[serde(default = "default_archive_recent_blocks")]
+    pub recent_blocks: u64,
+    /// How often the fast node's head block is polled, in seconds. Until it is known, every
+    /// call naming a block number goes to the archive node.
+    #[serde(default = "default_archive_head_poll_secs")]
+    pub head_poll_secs: u64,
+    /// Where calls naming a block by hash go, since the hash doesn't tell how old the block is.
+    #[serde(default)]
+    pub block_hashes: ArchiveHashRouting,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveHashRouting {
+    /// To the archive node, which can answer them at any age.
+    #[default]
+    Archive,
+    /// To the fast node, for clients that only query recent blocks by hash.
+    Fast,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebSocketKeepaliveConfig {
+    /// How often the client and the backend are pinged, in seconds.
+    #[serde(default = "default_ws_ping_interval_secs")]
+    pub ping_interval_secs: u64,
+    /// Sessions are closed once either peer has sent nothing, pongs included, for this many
+    /// seconds. Must be longer than `ping_interval_secs`, so responsive peers always answer in
+    /// time.
+    #[serde(default = "default_ws_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+}
+
+impl WebSocketKeepaliveConfig {
+    /// Checks that peers get at least one ping before they are considered idle.
+    pub fn validate(&self) -> Result<()> {
+        if self.ping_interval_secs == 0 || self.idle_timeout_secs <= self.ping_interval_secs {
+            return Err(Error::ConfigError(::config::ConfigError::Message(format!(
+                "rpc.websocket_keepalive.idle_timeout_secs ({}) must be longer than a non-zero ping_interval_secs ({})",
+                self.idle_timeout_secs, self.ping_interval_secs
+            ))));
+        }
+        Ok(())
+    }
+}
+
+/// Bridging of WebSocket sessions to a backend without a WebSocket endpoint. Each JSON-RPC
+/// message a client sends is POSTed to the backend and its answer sent back on the socket.
+/// Subscriptions to new heads (`eth_subscribe("newHeads")`, `chain_subscribeNewHeads` and
+/// `chain_subscribeFinalizedHeads`) are answered by the gateway, which polls the backend for
+/// heads while any are open; other subscriptions are refused.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebSocketBridgeConfig {
+    /// How often the backend is polled for new heads while a session is subscribed to them, in
+    /// milliseconds.
+    #[serde(default = "default_ws_bridge_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+}
+
+impl WebSocketBridgeConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.poll_interval_ms == 0 {
+            return Err(Error::ConfigError(::config::ConfigError::Message(
+                "websocket_bridge.poll_interval_ms must be positive".to_string(),
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// How `HEAD` requests are served. Either way they pass the firewall first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeadRequests {
+    /// Answered by the gateway with `200` and no body, without reaching the backend.
+    #[default]
+    Local,
+    /// Forwarded to the backend as `GET`, returning its status and headers without the body.
+    Get,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM-encoded certificate chain.
+    pub cert_path: PathBuf,
+    /// PEM-encoded private key.
+    pub key_path: PathBuf,
+    /// How often the files are checked for changes, in seconds. Changed files are reloaded
+    /// without a restart.
+    #[serde(default = "default_tls_reload_interval_secs")]
+    pub reload_interval_secs: u64,
+    /// Request client certificates (mutual TLS) when set.
+    #[serde(default)]
+    pub client_auth: Option<ClientAuthConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientAuthConfig {
+    /// PEM bundle of the CAs that issue client certificates.
+    pub ca_path: PathBuf,
+    /// Reject TLS handshakes without a client certificate. Otherwise such clients fall back
+    /// to API keys, signatures and IP rules.
+    #[serde(default)]
+    pub required: bool,
+    /// Client certificates and the account each authenticates as. Keys are either the
+    /// certificate's SHA-256 fingerprint in hex (colons optional) or a subject alternative
+    /// name (DNS name, email address or URI).
+    #[serde(default, deserialize_with = "deserialize_certificate_accounts")]
+    pub accounts: HashMap<String, AccountId32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpstreamTlsConfig {
+    /// PEM file of additional CA certificates trusted for the backend, e.g. a private CA.
+    /// The bundled web PKI roots are always trusted.
+    #[serde(default)]
+    pub ca_cert_path: Option<PathBuf>,
+    /// Server name sent via SNI and checked against the backend certificate, for every backend
+    /// in `upstreams` too. Defaults to the host of each backend's URL.
+    #[serde(default)]
+    pub server_name: Option<String>,
+}
+
+/// Credentials for one backend, set on every HTTP request and WebSocket handshake sent to it,
+/// replacing any headers of the same name from the client.
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+pub struct UpstreamAuthConfig {
+    /// The backend, as in `proxy_to_url`, `upstreams` or `archive.url` of `[rpc]` or a route.
+    pub backend: Url,
+    /// Headers to send, e.g. `{ "x-api-key" = "..." }`.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// HTTP basic auth, sent as the `Authorization` header.
+    #[serde(default)]
+    pub basic_auth: Option<BasicAuthConfig>,
+}
+
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+pub struct BasicAuthConfig {
+    pub username: String,
+    pub password: String,
+}
+
+impl UpstreamAuthConfig {
+    /// The headers to set on requests to the backend. Values are marked sensitive, so they
+    /// are left out of debug output of the requests carrying them.
+    pub fn header_map(&self) -> Result<HeaderMap> {
+        let invalid = |message: String| Error::ConfigError(::config::ConfigError::Message(message));
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+                invalid(format!(
+                    "Invalid header name {name:?} in rpc.upstream_auth for {}",
+                    self.backend
+                ))
+            })?;
+            let mut value = HeaderValue::from_str(value).map_err(|_| {
+                invalid(format!(
+                    "Invalid value of header {name} in rpc.upstream_auth for {}",
+                    self.backend
+                ))
+            })?;
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+        if let Some(basic) = &self.basic_auth {
+            if headers.contains_key(AUTHORIZATION) {
+                return Err(invalid(format!(
+                    "rpc.upstream_auth for {} sets both basic_auth and an Authorization header",
+                    self.backend
+                )));
+            }
+            let credentials = BASE64.encode(format!("{}:{}", basic.username, basic.password));
+            let mut value = HeaderValue::from_str(&format!("Basic {credentials}"))
+                .expect("Base64 is a valid header value");
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
+        Ok(headers)
+    }
+}
+
+impl fmt::Debug for UpstreamAuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpstreamAuthConfig")
+            .field("backend", &self.backend)
+            .field(
+                "headers",
+                &self
+                    .headers
+                    .keys()
+                    .map(|name| (name, "<redacted>"))
+                    .collect::<HashMap<_, _>>(),
+            )
+            .field("basic_auth", &self.basic_auth)
+            .finish()
+    }
+}
+
+impl fmt::Debug for BasicAuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BasicAuthConfig")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// An outbound proxy, for networks where the backends can only be reached through one.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboundProxyConfig {
+    /// `http://host:port` to tunnel with HTTP `CONNECT`, `socks5://host:port` for a SOCKS5
+    /// proxy, or `socks5h://host:port` to have the SOCKS5 proxy resolve backend hostnames.
+    pub url: Url,
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Never serialized, and redacted from debug output.
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+}
+
+impl OutboundProxyConfig {
+    /// Checks the proxy scheme, and that credentials are set as `username` and `password`
+    /// rather than in the URL, where they would be serialized.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Error::ConfigError(::config::ConfigError::Message(message));
+        if !matches!(self.url.scheme(), "http" | "socks5" | "socks5h") {
+            return Err(invalid(format!(
+                "rpc.outbound_proxy.url {} must be an http://, socks5:// or socks5h:// URL",
+                self.url
+            )));
+        }
+        if self.url.host_str().is_none() {
+            return Err(invalid(format!(
+                "rpc.outbound_proxy.url {} has no host",
+                self.url
+            )));
+        }
+        if !self.url.username().is_empty() || self.url.password().is_some() {
+            return Err(invalid(
+                "Set rpc.outbound_proxy credentials as username and password, not in the URL"
+                    .to_string(),
+            ));
+        }
+        if self.password.is_some() && self.username.is_none() {
+            return Err(invalid(
+                "rpc.outbound_proxy.password is set without a username".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for OutboundProxyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutboundProxyConfig")
+            .field("url", &self.url)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive backend failures (connection errors or 502/503/504 responses) that open
+    /// the circuit.
+    #[serde(default = "default_circuit_failure_threshold")]
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before trial requests are let through.
+    #[serde(default = "default_circuit_open_secs")]
+    pub open_secs: u64,
+    /// Trial requests let through while half-open. A success closes the circuit, a failure
+    /// opens it again.
+    #[serde(default = "default_circuit_half_open_requests")]
+    pub half_open_requests: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamProbeConfig {
+    /// How often the backend is probed after the probe at startup, in seconds.
+    #[serde(default = "default_probe_interval_secs")]
+    pub interval_secs: u64,
+    /// Timeout of each probe request, in seconds.
+    #[serde(default = "default_probe_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Answer calls to methods the backend didn't list as supported with a JSON-RPC
+    /// "method not found" error instead of forwarding them.
+    #[serde(default = "default_refuse_unsupported_methods")]
+    pub refuse_unsupported_methods: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthConfig {
+    /// How often `/readyz` checks the default backend and the state store, in seconds.
+    #[serde(default = "default_health_interval_secs")]
+    pub interval_secs: u64,
+    /// Timeout of each backend check, in seconds.
+    #[serde(default = "default_health_timeout_secs")]
+    pub timeout_secs: u64,
+    /// The gateway isn't ready while requests in flight to the backends reach this percentage
+    /// of `[rpc.concurrency]` or `[rpc.global_concurrency]`.
+    #[serde(default = "default_health_overload_percent")]
+    pub overload_percent: u8,
+}
+
+impl Default for UpstreamProbeConfig {
+    fn default() -> Self {
+        UpstreamProbeConfig {
+            interval_secs: default_probe_interval_secs(),
+            timeout_secs: default_probe_timeout_secs(),
+            refuse_unsupported_methods: default_refuse_unsupported_methods(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamConcurrencyConfig {
+    /// Requests sent to the backend at the same time. Further requests wait for a slot.
+    pub max_concurrent_requests: usize,
+    /// How long a request waits for a slot before it is rejected with `503`, in milliseconds.
+    #[serde(default = "default_concurrency_queue_timeout_ms")]
+    pub queue_timeout_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchConfig {
+    /// Calls allowed in one JSON-RPC batch. Larger batches are rejected.
+    pub max_batch_size: usize,
+    /// Forward HTTP batches over `max_batch_size` to the backend as several batches of at most
+    /// that size, merging the responses, instead of rejecting them.
+    #[serde(default)]
+    pub split_oversized: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestValidationConfig {
+    /// Longest method name accepted.
+    #[serde(default = "default_max_method_length")]
+    pub max_method_length: usize,
+    /// Params schemas by method pattern (e.g. `eth_getBalance`, `debug_*`), matched like
+    /// `[methods]` patterns. Calls to other methods only get the structural checks.
+    #[serde(default)]
+    pub methods: HashMap<String, MethodSchemaConfig>,
+}
+
+impl RequestValidationConfig {
+    /// Checks that method names can be accepted and that params counts are satisfiable.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Error::ConfigError(::config::ConfigError::Message(message));
+        if self.max_method_length == 0 {
+            return Err(invalid(
+                "rpc.validation.max_method_length must be at least 1".to_string(),
+            ));
+        }
+        for (pattern, schema) in &self.methods {
+            if schema.max_params.is_some_and(|max| max < schema.min_params) {
+                return Err(invalid(format!(
+                    "rpc.validation.methods.{pattern}: max_params is below min_params"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The params a method takes. Constraints that don't apply to the params structure a call uses
+/// are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MethodSchemaConfig {
+    #[serde(default)]
+    pub params: ParamsStructure,
+    /// Positional params the call must send. Later ones are optional, and may be `null`.
+    #[serde(default)]
+    pub min_params: usize,
+    #[serde(default)]
+    pub max_params: Option<usize>,
+    /// Types of the positional params, in order. Params past the end of the list aren't
+    /// checked.
+    #[serde(default)]
+    pub param_types: Vec<ParamType>,
+    /// Members named params must have.
+    #[serde(default)]
+    pub required_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamsStructure {
+    /// Positional, named or no params.
+    #[default]
+    Any,
+    /// An array of params, or none.
+    Positional,
+    /// An object of params, or none.
+    Named,
+    /// No params, or an empty array.
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamType {
+    Any,
+    String,
+    /// A `0x`-prefixed hex string, such as a quantity, an address or a hash.
+    Hex,
+    Number,
+    Boolean,
+    Object,
+    Array,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorsConfig {
+    /// Origins allowed to call the gateway from a browser, e.g. `https://app.example.com`.
+    /// `*` matches any run of characters (`https://*.example.com`); `"*"` alone allows any
+    /// origin.
+    pub allowed_origins: Vec<String>,
+    /// Methods allowed in cross-origin requests, or `["*"]` for any.
+    #[serde(default = "default_cors_allowed_methods")]
+    pub allowed_methods: Vec<String>,
+    /// Request headers allowed in cross-origin requests, or `["*"]` for any.
+    #[serde(default = "default_cors_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+    /// Let browsers send cookies and `Authorization` credentials cross-origin. Needs explicit
+    /// origins, methods and headers, not `"*"`.
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// How long browsers may cache the answer to a preflight, in seconds.
+    #[serde(default = "default_cors_max_age_secs")]
+    pub max_age_secs: u64,
+}
+
+impl CorsConfig {
+    /// Checks that every entry is a valid origin, method or header name, and that credentials
+    /// aren't combined with wildcards, which browsers reject.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Error::ConfigError(::config::ConfigError::Message(message));
+        if self.allowed_origins.is_empty() {
+            return Err(invalid(
+                "rpc.cors.allowed_origins must list at least one origin".to_string(),
+            ));
+        }
+        for origin in &self.allowed_origins {
+            if origin != "*" && (origin.is_empty() || HeaderValue::from_str(origin).is_err()) {
+                return Err(invalid(format!("Invalid CORS origin {origin:?}")));
+            }
+        }
+        for method in self.allowed_methods.iter().filter(|method| *method != "*") {
+            if Method::from_bytes(method.as_bytes()).is_err() {
+                return Err(invalid(format!("Invalid CORS method {method:?}")));
+            }
+        }
+        for header in self.allowed_headers.iter().filter(|header| *header != "*") {
+            if HeaderName::from_bytes(header.as_bytes()).is_err() {
+                return Err(invalid(format!("Invalid CORS header {header:?}")));
+            }
+        }
+        if self.allow_credentials {
+            let wildcards = [
+                ("allowed_origins", &self.allowed_origins),
+                ("allowed_methods", &self.allowed_methods),
+                ("allowed_headers", &self.allowed_headers),
+            ];
+            if let Some((key, _)) = wildcards.iter().find(|(_, values)| is_wildcard(values)) {
+                return Err(invalid(format!(
+                    "rpc.cors.allow_credentials can't be combined with \"*\" in rpc.cors.{key}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    /// Responses smaller than this many bytes are sent uncompressed. Streamed responses of
+    /// unknown length are always compressed.
+    #[serde(default = "default_compression_min_size_bytes")]
+    pub min_size_bytes: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeadCacheConfig {
+    /// Cached heads older than this many seconds aren't served, so a stalled subscription
+    /// falls back to asking the backend.
+    #[serde(default = "default_head_cache_max_age_secs")]
+    pub max_age_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoalescingConfig {
+    /// Methods whose identical concurrent calls (same method and params) share one backend
+    /// call (`*` suffixes match prefixes). Only read-only calls belong here.
+    #[serde(default = "default_coalescing_methods")]
+    pub methods: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyConfig {
+    /// Calls whose backend round trip takes at least this many milliseconds are logged as
+    /// slow requests.
+    #[serde(default = "default_latency_slow_request_ms")]
+    pub slow_request_ms: u64,
+    /// How often the slowest methods are reported as a `LatencySummary` webhook event, in
+    /// seconds.
+    #[serde(default = "default_latency_summary_interval_secs")]
+    pub summary_interval_secs: u64,
+    /// Methods included in each summary, slowest on average first.
+    #[serde(default = "default_latency_summary_methods")]
+    pub summary_methods: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamRetryConfig {
+    /// Retries per request after the first attempt. `0` disables retries. When enabled,
+    /// request bodies are buffered so they can be resent.
+    #[serde(default)]
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled after each failed attempt and jittered.
+    #[serde(default = "default_upstream_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// Upper bound on the delay between attempts.
+    #[serde(default = "default_upstream_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// JSON-RPC methods that are never retried after reaching the backend, since repeating
+    /// them may have side effects. `*` suffixes match prefixes. Requests that failed to connect
+    /// are retried regardless, as they never reached the backend.
+    #[serde(default = "default_non_idempotent_methods")]
+    pub non_idempotent_methods: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpstreamPoolConfig {
+    /// Idle connections kept open per backend for reuse.
+    #[serde(default = "default_upstream_pool_max_idle_per_host")]
+    pub max_idle_per_host: usize,
+    /// Idle connections are closed after this many seconds.
+    #[serde(default = "default_upstream_pool_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    /// TCP keep-alive probes on backend connections, every this many seconds. Off when unset.
+    #[serde(default = "default_upstream_tcp_keepalive_secs")]
+    pub tcp_keepalive_secs: Option<u64>,
+    #[serde(default)]
+    pub http_version: UpstreamHttpVersion,
+    /// HTTP/2 PINGs on backend connections every this many seconds, also while idle, so dead
+    /// connections are noticed before a request is sent on them. Off when unset.
+    #[serde(default)]
+    pub http2_keep_alive_secs: Option<u64>,
+}
+
+impl Default for UpstreamPoolConfig {
+    fn default() -> Self {
+        UpstreamPoolConfig {
+            max_idle_per_host: default_upstream_pool_max_idle_per_host(),
+            idle_timeout_secs: default_upstream_pool_idle_timeout_secs(),
+            tcp_keepalive_secs: default_upstream_tcp_keepalive_secs(),
+            http_version: UpstreamHttpVersion::default(),
+            http2_keep_alive_secs: None,
+        }
+    }
+}
+
+/// The HTTP version spoken to the backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamHttpVersion {
+    /// HTTP/2 when an `https://` backend offers it via ALPN, HTTP/1.1 otherwise.
+    #[default]
+    Auto,
+    Http1,
+    /// HTTP/2 only: negotiated via ALPN over TLS, with prior knowledge (h2c) over plain
+    /// `http://`, so every request to a backend shares a few multiplexed connections.
+    Http2,
+}
+
+/// A planned maintenance of one backend. From `starts_at` until `ends_at`, requests are sent to
+/// the route's other backends and WebSocket sessions open to it are asked to reconnect, so they
+/// move to another backend too. A backend is never drained when every backend of its route is
+/// in maintenance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    /// The backend, as in `proxy_to_url` or `upstreams` of `[rpc]` or a route.
+    pub backend: Url,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    /// How long before `starts_at` the `MaintenanceStarting` webhook event is sent, in seconds.
+    #[serde(default = "default_maintenance_notice_secs")]
+    pub notice_secs: u64,
+    /// Included in the webhook events, e.g. "Upgrade to v1.2".
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrpcConfig {
+    /// The gRPC backend: `http://` for cleartext HTTP/2 (h2c), `https://` for TLS with
+    /// `[rpc.upstream_tls]`. Connections are pooled as `[rpc.upstream_pool]` says, always over
+    /// HTTP/2.
+    pub upstream_url: Url,
+    /// Bytes a client may stream in one call, replacing `max_body_size_bytes` for gRPC.
+    #[serde(default = "default_grpc_max_stream_bytes")]
+    pub max_stream_bytes: usize,
+    /// Calls whose client sends no data for this many seconds are cut off, replacing
+    /// `body_idle_timeout_secs` for gRPC, so long-lived streams aren't.
+    #[serde(default = "default_grpc_stream_idle_timeout_secs")]
+    pub stream_idle_timeout_secs: u64,
+}
+
+impl Default for UpstreamRetryConfig {
+    fn default() -> Self {
+        UpstreamRetryConfig {
+            max_retries: 0,
+            initial_backoff_ms: default_upstream_initial_backoff_ms(),
+            max_backoff_ms: default_upstream_max_backoff_ms(),
+            non_idempotent_methods: default_non_idempotent_methods(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirewallConfig {
+    #[serde(default, deserialize_with = "deserialize_ip_networks")]
+    pub allow_ips: HashSet<IpNetwork>,
+    #[serde(default, deserialize_with = "deserialize_accounts")]
+    pub allow_accounts: HashSet<AccountId32>,
+    #[serde(default)]
+    pub allow_unrestricted_access: bool,
+    /// IPs/CIDRs that are always denied, regardless of any allow rule.
+    #[serde(default, deserialize_with = "deserialize_ip_networks")]
+    pub deny_ips: HashSet<IpNetwork>,
+    /// Accounts that are always denied, regardless of any allow rule.
+    #[serde(default, deserialize_with = "deserialize_accounts")]
+    pub deny_accounts: HashSet<AccountId32>,
+    /// MaxMind GeoLite2/GeoIP2 Country or City database used for `allow_countries` and
+    /// `deny_countries`. Loaded once at startup.
+    #[serde(default)]
+    pub geoip_database: Option<PathBuf>,
+    /// ISO 3166-1 alpha-2 country codes whose IPs are allowed access.
+    #[serde(default, deserialize_with = "deserialize_country_codes")]
+    pub allow_countries: HashSet<String>,
+    /// ISO 3166-1 alpha-2 country codes whose IPs are always denied, regardless of any allow
+    /// rule. Applies to authenticated accounts too.
+    #[serde(default, deserialize_with = "deserialize_country_codes")]
+    pub deny_countries: HashSet<String>,
+    /// Per-IP rate limiting. Disabled when absent.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Lets accounts with long-lived paid access exceed their rate limit briefly. Disabled
+    /// when absent.
+    #[serde(default)]
+    pub burst_loans: Option<BurstLoanConfig>,
+    /// Caching of allow/deny decisions per source. Disabled when absent.
+    #[serde(default)]
+    pub decision_cache: Option<DecisionCacheConfig>,
+    /// Temporary bans for IPs that keep getting denied, rate limited or sending malformed
+    /// requests. Disabled when absent.
+    #[serde(default)]
+    pub auto_ban: Option<AutoBanConfig>,
+    /// Time limit on access decisions, and what to decide when it runs out. Unlimited when
+    /// absent.
+    #[serde(default)]
+    pub decision_budget: Option<DecisionBudgetConfig>,
+    /// Which credentials identify a request, and how its account and IP combine.
+    #[serde(default)]
+    pub identity: IdentityConfig,
+    /// Ceilings on the amount of state jobs can add to the firewall.
+    #[serde(default)]
+    pub limits: RuleLimitsConfig,
+    /// How often expired temporary grants are removed, in seconds.
+    #[serde(default = "default_cleanup_interval_secs")]
+    pub cleanup_interval_secs: u64,
+    /// How long before a temporary grant ends an `AccessExpiringSoon` webhook event is sent,
+    /// in seconds. Checked on every cleanup run. Disabled when absent.
+    #[serde(default)]
+    pub expiry_warning_secs: Option<u64>,
+    /// Number of access history entries kept per account.
+    #[serde(default = "default_history_entries_per_account")]
+    pub history_entries_per_account: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionCacheConfig {
+    /// How long a decision is reused for the same `(ip, account)` pair, in milliseconds.
+    /// Rule changes invalidate the cache immediately, and decisions are dropped sooner when a
+    /// grant, ban or access window of the pair lapses first.
+    pub ttl_ms: u64,
+    /// Maximum number of cached decisions.
+    #[serde(default = "default_decision_cache_max_entries")]
+    pub max_entries: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionBudgetConfig {
+    /// Longest an access decision may take, in milliseconds. Cached decisions are always
+    /// within budget.
+    #[serde(default = "default_decision_budget_ms")]
+    pub budget_ms: u64,
+    /// Decision for HTTP requests whose evaluation runs over budget.
+    #[serde(default)]
+    pub http: FailPolicy,
+    /// Decision for WebSocket upgrades whose evaluation runs over budget.
+    #[serde(default)]
+    pub websocket: FailPolicy,
+    /// Decision for open WebSocket sessions re-checked after a rule change. Fails open by
+    /// default, so a slow check doesn't drop live sessions.
+    #[serde(default = "default_session_recheck_fail_policy")]
+    pub session_recheck: FailPolicy,
+}
+
+/// What an access decision that runs over its time budget falls back to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailPolicy {
+    /// Let the request through.
+    Open,
+    /// Refuse the request with `503`, or close the session.
+    #[default]
+    Closed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Sustained number of requests per second allowed for a single IP.
+    pub requests_per_sec: f64,
+    /// Maximum number of requests a single IP can make in a burst.
+    pub burst: u32,
+}
+
+impl RateLimitConfig {
+    /// Checks that buckets refill at a finite, positive rate.
+    pub fn validate(&self) -> Result<()> {
+        if !self.requests_per_sec.is_finite() || self.requests_per_sec <= 0.0 {
+            return Err(Error::ConfigError(::config::ConfigError::Message(format!(
+                "requests_per_sec ({}) must be a finite number greater than 0",
+                self.requests_per_sec
+            ))));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BurstLoanConfig {
+    /// Tokens an eligible account may borrow once its bucket is empty. The debt is repaid from
+    /// later refills before new requests are let through.
+    pub max_loan: u32,
+    /// Shortest temporary grant that counts as long-lived paid access, in seconds.
+    #[serde(default = "default_burst_loan_min_grant_secs")]
+    pub min_grant_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AutoBanConfig {
+    /// Offenses (denials, rate-limit hits, invalid credentials, oversized headers) within
+    /// `window_secs` that get a source banned.
+    #[serde(default = "default_auto_ban_max_offenses")]
+    pub max_offenses: u32,
+    #[serde(default = "default_auto_ban_window_secs")]
+    pub window_secs: u64,
+    /// Length of a first ban, in seconds. Every repeat ban doubles it.
+    #[serde(default = "default_auto_ban_ban_secs")]
+    pub ban_secs: u64,
+    /// Longest a ban can get, in seconds.
+    #[serde(default = "default_auto_ban_max_ban_secs")]
+    pub max_ban_secs: u64,
+    /// How long after its last ban ends a source starts over at `ban_secs`, in seconds.
+    #[serde(default = "default_auto_ban_forget_after_secs")]
+    pub forget_after_secs: u64,
+    /// Prefix lengths offenses are counted and bans applied under: 32 and 128 ban single
+    /// addresses, shorter ones the whole network around them.
+    #[serde(default = "default_auto_ban_ipv4_prefix")]
+    pub ipv4_prefix: u8,
+    #[serde(default = "default_auto_ban_ipv6_prefix")]
+    pub ipv6_prefix: u8,
+    /// IPs/CIDRs never banned automatically.
+    #[serde(default, deserialize_with = "deserialize_ip_networks")]
+    pub exempt_ips: HashSet<IpNetwork>,
+}
+
+/// A kind of credential a request can authenticate as an account with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentitySource {
+    /// A TLS client certificate mapped in `[rpc.tls.client_auth]`.
+    ClientCert,
+    /// `Authorization: Bearer <key>`.
+    ApiKey,
+    /// sr25519 signature headers.
+    Signature,
+}
+
+/// How an authenticated request's account rules and IP rules combine. IP bans and denied
+/// countries apply in every mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentityCombination {
+    /// The account's rules decide; the IP's allow rules don't count.
+    #[default]
+    Account,
+    /// Most permissive: allowed if the account or the IP is, unless the account is banned.
+    Either,
+    /// Strictest: both the account and the IP must be allowed.
+    Both,
+}
+
+/// Which identity of an authenticated request its usage is metered under.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BilledIdentity {
+    #[default]
+    Account,
+    Ip,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentityConfig {
+    /// Credential kinds tried in order. The first the request presents decides its account;
+    /// invalid API keys and signatures are rejected rather than passed over, and a client
+    /// certificate that maps to no account is. Kinds left out are ignored.
+    #[serde(default = "default_identity_order")]
+    pub order: Vec<IdentitySource>,
+    #[serde(default)]
+    pub combine: IdentityCombination,
+    #[serde(default)]
+    pub billed: BilledIdentity,
+}
+
+impl Default for IdentityConfig {
+    fn default() -> Self {
+        IdentityConfig {
+            order: default_identity_order(),
+            combine: IdentityCombination::default(),
+            billed: BilledIdentity::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RuleLimitsConfig {
+    #[serde(default)]
+    pub dynamic_ips: RuleLimit,
+    #[serde(default)]
+    pub dynamic_accounts: RuleLimit,
+    #[serde(default)]
+    pub temporary_grants: RuleLimit,
+    #[serde(default)]
+    pub webhooks: RuleLimit,
+    #[serde(default)]
+    pub bans: RuleLimit,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+pub struct RuleLimit {
+    /// Emit a `RuleLimitExceeded` warning event once the count goes above this value.
+    #[serde(default)]
+    pub warn_at: Option<usize>,
+    /// Reject additions once the count reaches this value.
+    #[serde(default)]
+    pub max: Option<usize>,
+}
+
+impl RuleLimit {
+    /// Returns an error if one more entry can't be added to a collection of `len` entries.
+    pub fn ensure_capacity(&self, len: usize, rule_type: &str) -> Result<()> {
+        match self.max {
+            Some(max) if len >= max => Err(Error::RuleLimitReached(format!(
+                "{rule_type} count is at its limit of {max}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns true if a collection growing from `before` to `after` entries just crossed the
+    /// warning threshold.
+    pub fn crossed_warning(&self, before: usize, after: usize) -> bool {
+        self.warn_at
+            .is_some_and(|warn_at| before <= warn_at && after > warn_at)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct WebhookConfig {
+    #[serde(default)]
+    pub event_urls: Vec<Url>,
+    /// Webhooks with a shared secret (deliveries are HMAC-signed) and/or an event subscription.
+    #[serde(default)]
+    pub endpoints: Vec<WebhookTarget>,
+    #[serde(default)]
+    pub retry: WebhookRetryConfig,
+    /// Re-deliver dead-lettered notifications to their (still registered) endpoints on startup.
+    #[serde(default)]
+    pub replay_dead_letters_on_startup: bool,
+    /// Domains webhooks registered via jobs may point at. A domain also allows its subdomains.
+    /// Any destination is accepted when empty.
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+    /// How `AccessGranted`, `AccessDenied` and `Banned` events from access checks are queued
+    /// and coalesced.
+    #[serde(default)]
+    pub access_events: AccessEventConfig,
+}
+
+/// Access decisions are queued rather than notified by the request that made them, and a
+/// dedicated task delivers them. Repeats of an event for the same source within
+/// `coalesce_window_secs` are dropped, so a busy client yields one event per window instead of
+/// one per request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessEventConfig {
+    /// Events waiting for delivery; further events are dropped (and counted) while it is full.
+    #[serde(default = "default_access_event_queue_size")]
+    pub queue_size: usize,
+    /// `0` delivers every event.
+    #[serde(default = "default_access_event_coalesce_window_secs")]
+    pub coalesce_window_secs: u64,
+}
+
+impl Default for AccessEventConfig {
+    fn default() -> Self {
+        AccessEventConfig {
+            queue_size: default_access_event_queue_size(),
+            coalesce_window_secs: default_access_event_coalesce_window_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookRetryConfig {
+    /// Delivery attempts per notification before it is moved to the dead-letter queue.
+    #[serde(default = "default_webhook_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled after each failed attempt.
+    #[serde(default = "default_webhook_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// Upper bound on the delay between attempts.
+    #[serde(default = "default_webhook_max_backoff_secs")]
+    pub max_backoff_secs: u64,
+}
+
+impl Default for WebhookRetryConfig {
+    fn default() -> Self {
+        WebhookRetryConfig {
+            max_attempts: default_webhook_max_attempts(),
+            initial_backoff_ms: default_webhook_initial_backoff_ms(),
+            max_backoff_secs: default_webhook_max_backoff_secs(),
+        }
+    }
+}
+
+impl WebhookConfig {
+    /// All configured webhook targets, unsigned `event_urls` first.
+    pub fn targets(&self) -> Vec<WebhookTarget> {
+        self.event_urls
+            .iter()
+            .map(|url| WebhookTarget::new(url.clone(), None, HashSet::new(), None))
+            .chain(self.endpoints.iter().cloned())
+            .collect()
+    }
+
+    /// Returns true if `url`'s host is on the egress allowlist (or the allowlist is empty).
+    pub fn is_destination_allowed(&self, url: &Url) -> bool {
+        if self.allowed_domains.is_empty() {
+            return true;
+        }
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.allowed_domains.iter().any(|domain| {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            host == domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|sub| sub.ends_with('.'))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LimitsConfig {
+    /// Limits by method pattern (e.g. `eth_getLogs`, `debug_*`), matched like `[methods]`
+    /// patterns.
+    #[serde(default)]
+    pub methods: HashMap<String, MethodLimitConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MethodLimitConfig {
+    /// Largest call to the method, in bytes. In a batch, applies to each call on its own.
+    #[serde(default)]
+    pub max_request_bytes: Option<usize>,
+    /// Largest response body to an HTTP request calling the method, in bytes.
+    #[serde(default)]
+    pub max_response_bytes: Option<usize>,
+    /// Largest `toBlock - fromBlock` of the log filter in the call's first param.
+    #[serde(default)]
+    pub max_block_range: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MethodFilterConfig {
+    /// If non-empty, only methods matching one of these patterns are forwarded.
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Methods matching any of these patterns are always rejected.
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// Rewrites applied to calls before filtering and forwarding.
+    #[serde(default)]
+    pub rewrites: Vec<MethodRewriteConfig>,
+}
+
+/// A path prefix proxied to its own backend, e.g. `/eth` to an Ethereum node next to a
+/// Substrate node at `rpc.proxy_to_url`. Retries, the circuit breaker and the concurrency
+/// ceiling are configured under `[rpc]` and apply to each backend separately.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteConfig {
+    /// Matched on whole path segments (`/eth` matches `/eth` and `/eth/ws`, not `/ethereum`)
+    /// and stripped before the request is forwarded.
+    pub prefix: String,
+    pub proxy_to_url: Url,
+    /// More nodes serving this route, like `rpc.upstreams`.
+    #[serde(default)]
+    pub upstreams: Vec<Url>,
+    /// TLS settings for an `https://`/`wss://` backend, replacing `[rpc.upstream_tls]`.
+    #[serde(default)]
+    pub upstream_tls: Option<UpstreamTlsConfig>,
+    /// Method allow/deny lists and rewrites for this route, replacing `[methods]`.
+    #[serde(default)]
+    pub methods: Option<MethodFilterConfig>,
+    /// Per-method limits for this route, replacing `[limits]`.
+    #[serde(default)]
+    pub limits: Option<LimitsConfig>,
+    /// An archive node for this route's historical queries, like `[rpc.archive]`, which
+    /// routes don't inherit.
+    #[serde(default)]
+    pub archive: Option<ArchiveConfig>,
+    /// A shadow backend for this route's traffic, like `[rpc.mirror]`, which routes don't
+    /// inherit.
+    #[serde(default)]
+    pub mirror: Option<MirrorConfig>,
+    /// Bridging of this route's WebSocket sessions to an HTTP-only backend, like
+    /// `[rpc.websocket_bridge]`, which routes don't inherit.
+    #[serde(default)]
+    pub websocket_bridge: Option<WebSocketBridgeConfig>,
+}
+
+impl RouteConfig {
+    /// `rpc` with this route's backend, for building its upstream.
+    pub fn rpc_config(&self, rpc: &RpcConfig) -> RpcConfig {
+        RpcConfig {
+            proxy_to_url: self.proxy_to_url.clone(),
+            upstreams: self.upstreams.clone(),
+            upstream_tls: self
+                .upstream_tls
+                .clone()
+                .unwrap_or_else(|| rpc.upstream_tls.clone()),
+            archive: self.archive.clone(),
+            mirror: self.mirror.clone(),
+            websocket_bridge: self.websocket_bridge.clone(),
+            ..rpc.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MethodRewriteConfig {
+    /// Method name or `*`-suffixed prefix pattern the rewrite applies to.
+    pub method: String,
+    /// Method name forwarded instead. A `*`-suffixed name replaces a matched prefix.
+    #[serde(default)]
+    pub rename_to: Option<String>,
+    /// Positional params filled in when the call has fewer. Values at positions the client
+    /// always sends are placeholders and never used.
+    #[serde(default)]
+    pub default_params: Vec<serde_json::Value>,
+}
+
+/// Who may call the jobs that change the gateway's rules and settings.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct JobsConfig {
+    /// Accounts allowed to call the admin jobs (`allow_access`, `revoke_access`, `ban_target`,
+    /// `update_config`, `set_account_labels`, `patch_config`, `set_account_roles`,
+    /// `set_read_only`, `sync_rules`), to register webhooks not scoped to their own account and
+    /// to issue API keys for other accounts. Admin jobs are rejected while empty.
+    #[serde(default, deserialize_with = "deserialize_accounts")]
+    pub admin_accounts: HashSet<AccountId32>,
+    /// Longest `duration_secs` a `pay_for_access` call may ask for; longer calls are rejected,
+    /// and no plan may grant longer. Unlimited when unset.
+    #[serde(default)]
+    pub max_access_duration_secs: Option<u64>,
+    /// How far in the future, in seconds, a temporary grant may expire. Grants that would run
+    /// longer are rejected. Unlimited when unset.
+    #[serde(default)]
+    pub max_access_horizon_secs: Option<u64>,
+}
+
+impl JobsConfig {
+    /// Checks a requested temporary access duration against `max_access_duration_secs`.
+    pub fn check_access_duration(&self, duration_secs: u64) -> Result<()> {
+        match self.max_access_duration_secs {
+            Some(max) if duration_secs > max => Err(Error::InvalidJobInput(format!(
+                "Duration of {duration_secs}s exceeds the maximum of {max}s"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// The longest grant running from `from` that `max_access_duration_secs` and
+    /// `max_access_horizon_secs` allow at `now`, or `None` if neither is set.
+    pub fn max_access_secs(&self, now: DateTime<Utc>, from: DateTime<Utc>) -> Option<u64> {
+        let horizon = self.max_access_horizon_secs.map(|max| {
+            let used = u64::try_from((from - now).num_seconds()).unwrap_or(0);
+            max.saturating_sub(used)
+        });
+        match (self.max_access_duration_secs, horizon) {
+            (Some(duration), Some(horizon)) => Some(duration.min(horizon)),
+            (max, None) | (None, max) => max,
+        }
+    }
+
+    /// Checks that a grant expiring at `expires_at` ends within `max_access_horizon_secs` of
+    /// `now`.
+    pub fn check_access_expiry(&self, now: DateTime<Utc>, expires_at: DateTime<Utc>) -> Result<()> {
+        let Some(max) = self.max_access_horizon_secs else {
+            return Ok(());
+        };
+        let horizon = chrono::Duration::try_seconds(i64::try_from(max).unwrap_or(i64::MAX))
+            .and_then(|max| now.checked_add_signed(max))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        if expires_at > horizon {
+            return Err(Error::InvalidJobInput(format!(
+                "Access would expire at {expires_at}, more than {max}s from now"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Transaction relaying: accounts POST SCALE-encoded call data to `path`, and the gateway
+/// signs it with the `signer` key, paying the fee, and submits it to the backend.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelayConfig {
+    /// Path on the RPC listener relayed calls are POSTed to. Served instead of proxied.
+    #[serde(default = "default_relay_path")]
+    pub path: String,
+    /// Account of the keystore's sr25519 key that signs relayed transactions. Must not be the
+    /// operator's key, which signs job results.
+    #[serde(deserialize_with = "deserialize_account")]
+    pub signer: AccountId32,
+    /// WebSocket endpoint of the node transactions are submitted to. Defaults to
+    /// `rpc.proxy_to_url` with a `ws`/`wss` scheme.
+    #[serde(default)]
+    pub submit_url: Option<Url>,
+    /// Who may relay what. Accounts without a policy are refused.
+    #[serde(default)]
+    pub policies: Vec<RelayPolicyConfig>,
+}
+
+impl RelayConfig {
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Error::ConfigError(::config::ConfigError::Message(message));
+        if !self.path.starts_with('/') || self.path == "/" {
+            return Err(invalid(format!(
+                "relay.path must be a path below /, got {}",
+                self.path
+            )));
+        }
+        if let Some(url) = &self.submit_url {
+            if !matches!(url.scheme(), "ws" | "wss") {
+                return Err(invalid(format!(
+                    "relay.submit_url must be a ws:// or wss:// URL, got {url}"
+                )));
+            }
+        }
+        let mut seen = HashSet::new();
+        for policy in &self.policies {
+            if policy.calls.is_empty() {
+                return Err(invalid(
+                    "every relay policy must allow at least one call".to_string(),
+                ));
+            }
+            if let Some(account) = policy
+                .accounts
+                .iter()
+                .find(|account| !seen.insert(*account))
+            {
+                return Err(invalid(format!(
+                    "account {account} is in more than one relay policy"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What the accounts of a relay policy may have relayed. Fees are in the chain's smallest unit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelayPolicyConfig {
+    #[serde(deserialize_with = "deserialize_accounts")]
+    pub accounts: HashSet<AccountId32>,
+    /// Calls they may relay, as `Pallet.call` names from the chain's metadata. Patterns ending
+    /// in `*` match by prefix, e.g. `Balances.*`.
+    pub calls: Vec<String>,
+    /// Largest estimated fee of a single relayed transaction.
+    pub max_fee: u128,
+    /// Fees of the transactions relayed for each account per UTC day.
+    pub daily_fee_budget: u128,
+}
+
+/// Rule synchronization between the operators of a service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleSyncConfig {
+    /// Operator accounts whose `sync_rules` calls are applied, in addition to
+    /// `jobs.admin_accounts`: the signer accounts of every operator sharing rules.
+    #[serde(default, deserialize_with = "deserialize_accounts")]
+    pub operators: HashSet<AccountId32>,
+    /// How often queued admin API changes are submitted, in seconds.
+    #[serde(default = "default_sync_submit_interval_secs")]
+    pub submit_interval_secs: u64,
+    /// Changes submitted per `sync_rules` call; the rest wait for the next one.
+    #[serde(default = "default_sync_max_batch")]
+    pub max_batch: usize,
+}
+
+/// Operator admin API (session inspection and eviction). Disabled unless `listen_addr` is set.
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct AdminConfig {
+    #[serde(default)]
+    pub listen_addr: Option<SocketAddr>,
+    /// Bearer token required on every admin request, if set.
+    #[serde(default, skip_serializing)]
+    pub token: Option<String>,
+    /// Accounts allowed to use the admin API, authenticated by sr25519-signed requests. API keys
+    /// issued to these accounts aren't accepted. Accepted in addition to `token`.
+    #[serde(default, deserialize_with = "deserialize_accounts")]
+    pub accounts: HashSet<AccountId32>,
+}
+
+impl fmt::Debug for AdminConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminConfig")
+            .field("listen_addr", &self.listen_addr)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("accounts", &self.accounts)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FingerprintConfig {
+    /// Compute a client fingerprint per request (JA3 over TLS, header order and user agent
+    /// otherwise), attach it to request logs and track it across IPs.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Maximum number of fingerprints tracked at once.
+    #[serde(default = "default_fingerprint_max_tracked")]
+    pub max_tracked: usize,
+    /// Fingerprints not seen for this long are forgotten.
+    #[serde(default = "default_fingerprint_idle_ttl_secs")]
+    pub idle_ttl_secs: u64,
+}
+
+impl Default for FingerprintConfig {
+    fn default() -> Self {
+        FingerprintConfig {
+            enabled: false,
+            max_tracked: default_fingerprint_max_tracked(),
+            idle_ttl_secs: default_fingerprint_idle_ttl_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OriginHeatmapConfig {
+    /// Count requests and denials per client /24 (IPv4) and /48 (IPv6) for the admin API's
+    /// heatmap.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Length of the rolling window counts are kept for, in seconds (at least 60).
+    #[serde(default = "default_origin_heatmap_window_secs")]
+    pub window_secs: u64,
+    /// Maximum number of prefixes counted at once.
+    #[serde(default = "default_origin_heatmap_max_prefixes")]
+    pub max_prefixes: usize,
+}
+
+impl Default for OriginHeatmapConfig {
+    fn default() -> Self {
+        OriginHeatmapConfig {
+            enabled: false,
+            window_secs: default_origin_heatmap_window_secs(),
+            max_prefixes: default_origin_heatmap_max_prefixes(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReloadConfig {
+    /// How often the config file's modification time is checked, in seconds. `0` disables the
+    /// file watcher; `SIGHUP` still triggers a reload.
+    #[serde(default = "default_reload_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        ReloadConfig {
+            poll_interval_secs: default_reload_poll_interval_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// The current audit file is rotated once it would grow past this many bytes.
+    #[serde(default = "default_audit_max_file_bytes")]
+    pub max_file_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccessLogConfig {
+    #[serde(default)]
+    pub format: AccessLogFormat,
+    /// The current access log file is rotated once it would grow past this many bytes.
+    #[serde(default = "default_access_log_max_file_bytes")]
+    pub max_file_bytes: u64,
+    /// Rotated files kept; the oldest are deleted first. All are kept when 0.
+    #[serde(default = "default_access_log_max_files")]
+    pub max_files: usize,
+}
+
+/// How access log entries are written, one per line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessLogFormat {
+    /// A JSON object.
+    #[default]
+    Json,
+    /// `key=value` pairs (logfmt).
+    Text,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventHistoryConfig {
+    /// Most events kept; the oldest are dropped first.
+    #[serde(default = "default_event_history_max_events")]
+    pub max_events: usize,
+    /// How long events are kept, in seconds.
+    #[serde(default = "default_event_history_retention_secs")]
+    pub retention_secs: u64,
+    /// Event types kept, named like the webhook event types. All if empty.
+    #[serde(default)]
+    pub events: HashSet<WebhookEventType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrafficStatsConfig {
+    /// Length of each bucket, in seconds.
+    #[serde(default = "default_traffic_stats_bucket_secs")]
+    pub bucket_secs: u64,
+    /// Buckets kept, the oldest dropped as new ones open; an hour of minutes by default.
+    #[serde(default = "default_traffic_stats_buckets")]
+    pub buckets: usize,
+    /// Routes, methods and accounts broken out per bucket, each. Further ones are counted
+    /// together under `(other)`, bounding the memory an account or method flood takes.
+    #[serde(default = "default_traffic_stats_max_keys")]
+    pub max_keys: usize,
+}
+
+impl TrafficStatsConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.bucket_secs == 0 || self.buckets == 0 {
+            return Err(Error::ConfigError(::config::ConfigError::Message(
+                "traffic_stats.bucket_secs and traffic_stats.buckets must be positive".to_string(),
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyDigestConfig {
+    /// Methods listed in each digest, most called first.
+    #[serde(default = "default_daily_digest_top_methods")]
+    pub top_methods: usize,
+    /// Distinct client IPs counted per day, bounding the memory the count takes.
+    #[serde(default = "default_daily_digest_max_unique_ips")]
+    pub max_unique_ips: usize,
+}
+
+/// The backend gateway state is stored in, selected by `backend`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum StorageConfig {
+    /// An embedded database under the data directory.
+    #[default]
+    Sled,
+    /// Memory only; everything is lost on restart.
+    Memory,
+    /// A Redis server, which several gateway instances can share.
+    Redis {
+        url: Url,
+        /// Prepended to every Redis key, to keep gateways sharing a server apart.
+        #[serde(default = "default_redis_key_prefix")]
+        key_prefix: String,
+        /// Keep the buckets of `[firewall.rate_limit]` and plan rate limits on the server, so
+        /// every gateway sharing it enforces one limit per client rather than one per instance.
+        #[serde(default)]
+        shared_rate_limits: bool,
+        /// How often dynamic rules and temporary grants are re-read from the server, in
+        /// seconds, picking up changes made through other gateway instances. `0` reads them
+        /// only at startup.
+        #[serde(default = "default_redis_sync_interval_secs")]
+        sync_interval_secs: u64,
+        /// Timeout of connecting to the server and of sending and receiving each command, in
+        /// seconds.
+        #[serde(default = "default_redis_timeout_secs")]
+        timeout_secs: u64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeteringConfig {
+    /// Length of a usage period, in seconds. Closed periods are queued until the
+    /// `report_usage` job collects them.
+    #[serde(default = "default_metering_report_interval_secs")]
+    pub report_interval_secs: u64,
+    /// How often usage is written to disk, in seconds.
+    #[serde(default = "default_metering_persist_interval_secs")]
+    pub persist_interval_secs: u64,
+    /// Closed periods kept until collected. The oldest are dropped beyond this.
+    #[serde(default = "default_metering_max_pending_reports")]
+    pub max_pending_reports: usize,
+    /// Compute units charged per JSON-RPC call, by method pattern (e.g. `eth_getLogs`,
+    /// `debug_*`), matched like `[methods]` patterns.
+    #[serde(default)]
+    pub compute_units: HashMap<String, u64>,
+    /// Compute units charged for calls to methods not in `compute_units`.
+    #[serde(default = "default_metering_default_compute_units")]
+    pub default_compute_units: u64,
+    /// Methods listed per account in monthly usage summaries, most called first.
+    #[serde(default = "default_metering_summary_top_methods")]
+    pub summary_top_methods: usize,
+    /// Days each account's usage per closed period is kept for billing exports. 0 keeps none.
+    #[serde(default = "default_metering_history_days")]
+    pub history_days: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentVerificationConfig {
+    /// Smallest transfer accepted for any grant, in the chain's smallest unit.
+    pub min_amount: u128,
+    /// Price per second of access. The required payment is the larger of this times the
+    /// requested duration and `min_amount`.
+    #[serde(default)]
+    pub amount_per_second: u128,
+    /// Prices access by a schedule instead of `amount_per_second`, granting as long as the
+    /// amount paid covers rather than the requested duration. Disabled when absent.
+    #[serde(default)]
+    pub pricing: Option<PriceScheduleConfig>,
+}
+
+impl PaymentVerificationConfig {
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Error::ConfigError(::config::ConfigError::Message(message));
+        let Some(pricing) = &self.pricing else {
+            return Ok(());
+        };
+        if self.amount_per_second > 0 {
+            return Err(invalid(
+                "payments.amount_per_second can't be combined with payments.pricing".to_string(),
+            ));
+        }
+        if pricing.amount_per_hour == 0 {
+            return Err(invalid(
+                "payments.pricing.amount_per_hour must be positive".to_string(),
+            ));
+        }
+        if let Some(discount) = pricing
+            .volume_discounts
+            .iter()
+            .find(|discount| discount.percent >= 100)
+        {
+            return Err(invalid(format!(
+                "payments.pricing.volume_discounts: the discount from {} hours must be below 100%",
+                discount.min_hours
+            )));
+        }
+        for surge in &pricing.surge {
+            if surge.load_percent == 0 || surge.load_percent > 100 {
+                return Err(invalid(format!(
+                    "payments.pricing.surge: load_percent {} must be 1 to 100",
+                    surge.load_percent
+                )));
+            }
+            if surge.multiplier_percent < 100 {
+                return Err(invalid(format!(
+                    "payments.pricing.surge: multiplier_percent {} at {}% load must be at least 100",
+                    surge.multiplier_percent, surge.load_percent
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A price schedule for access, in the chain's smallest unit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceScheduleConfig {
+    /// Price of an hour of access before discounts and surges.
+    pub amount_per_hour: u128,
+    /// Lower hourly prices for longer grants. The largest discount whose `min_hours` a grant
+    /// reaches applies.
+    #[serde(default)]
+    pub volume_discounts: Vec<VolumeDiscountConfig>,
+    /// Higher prices while the backends are busy. The multiplier of the highest `load_percent`
+    /// reached applies.
+    #[serde(default)]
+    pub surge: Vec<SurgeConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VolumeDiscountConfig {
+    /// Shortest grant, in hours, the discount applies to.
+    pub min_hours: u64,
+    /// Discount off the hourly price, below 100.
+    pub percent: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SurgeConfig {
+    /// Share of the `[rpc.global_concurrency]` or `[rpc.concurrency]` slots in use from which
+    /// the multiplier applies, 1 to 100.
+    pub load_percent: u8,
+    /// Price multiplier in percent, e.g. `150` for half again the price. At least 100.
+    pub multiplier_percent: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanConfig {
+    /// Rate limit for each grant on this plan, replacing `[firewall.rate_limit]`. The global
+    /// limit applies when absent.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Methods available on this plan (`*` suffix matches by prefix), on top of `[methods]`.
+    /// Every method is available when empty.
+    #[serde(default)]
+    pub allowed_methods: Vec<String>,
+    /// Length of access granted, in seconds, overriding the job's `duration_secs`.
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+    /// Smallest verified payment buying this plan. Jobs that don't name a plan get the
+    /// highest-priced plan their payment covers. Requires `[payments]`.
+    #[serde(default)]
+    pub min_payment: Option<u128>,
+    /// Whether WebSocket connections, and with them subscriptions, are available on this plan.
+    /// Upgrades under a plan without them are closed straight away.
+    #[serde(default = "default_plan_websocket")]
+    pub websocket: bool,
+    /// JSON-RPC calls each grant on this plan may make, batch calls counted individually.
+    /// Calls over it are refused until the grant is renewed; `extend_access` adds it once
+    /// more. Unlimited when absent.
+    #[serde(default)]
+    pub request_quota: Option<u64>,
+    /// Percentages of `request_quota` at which a `QuotaThresholdReached` webhook event is
+    /// sent, once per grant each.
+    #[serde(default = "default_plan_quota_warning_percents")]
+    pub quota_warning_percents: Vec<u8>,
+    /// Concurrent WebSocket connections an account with a grant on this plan may hold,
+    /// replacing `websocket.max_connections_per_account`.
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+    /// HTTP requests an account with a grant on this plan may have in flight at once,
+    /// replacing `rpc.max_in_flight_requests_per_account`.
+    #[serde(default)]
+    pub max_in_flight_requests: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RoleConfig {
+    /// Methods only accounts holding this role (or another role listing them) may call (`*`
+    /// suffix matches by prefix). The most specific matching pattern decides; methods no role
+    /// lists are open to every client.
+    #[serde(default)]
+    pub methods: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct WebSocketConfig {
+    /// Maximum concurrent connections from a single client IP. Unlimited when absent.
+    #[serde(default)]
+    pub max_connections_per_ip: Option<usize>,
+    /// Maximum concurrent connections authenticated as a single account. Unlimited when absent.
+    #[serde(default)]
+    pub max_connections_per_account: Option<usize>,
+    /// Maximum JSON-RPC subscriptions open on a single connection. Unlimited when absent.
+    #[serde(default)]
+    pub max_subscriptions_per_connection: Option<usize>,
+    /// Maximum JSON-RPC subscriptions an authenticated account holds across all its
+    /// connections. Unlimited when absent.
+    #[serde(default)]
+    pub max_subscriptions_per_account: Option<usize>,
+    /// How often open connections are re-checked against the firewall, in seconds, on top of
+    /// the re-check after every rule or grant change. Sessions whose access was revoked or
+    /// expired are closed. Disabled when absent.
+    #[serde(default)]
+    pub access_recheck_secs: Option<u64>,
+    /// Messages a single connection may send, with `requests_per_sec` and `burst` counting
+    /// messages. Connections over the rate are closed. Unlimited when absent.
+    #[serde(default)]
+    pub message_rate: Option<RateLimitConfig>,
+    /// Largest text or binary message a client may send, in bytes. Connections sending a
+    /// larger one are closed. Unlimited when absent.
+    #[serde(default)]
+    pub max_frame_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP traces endpoint, e.g. `http://localhost:4318/v1/traces`. Export is disabled
+    /// when absent.
+    #[serde(default)]
+    pub otlp_endpoint: Option<Url>,
+    #[serde(default = "default_telemetry_service_name")]
+    pub service_name: String,
+    /// Fraction of new traces sampled. Traces continued from a sampled parent are always kept.
+    #[serde(default = "default_telemetry_sample_ratio")]
+    pub sample_ratio: f64,
+    /// OTLP/HTTP metrics endpoint, e.g. `http://localhost:4318/v1/metrics`. Export is disabled
+    /// when absent.
+    #[serde(default)]
+    pub otlp_metrics_endpoint: Option<Url>,
+    /// How often metrics are exported.
+    #[serde(default = "default_telemetry_metrics_interval_secs")]
+    pub metrics_interval_secs: u64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        TelemetryConfig {
+            otlp_endpoint: None,
+            service_name: default_telemetry_service_name(),
+            sample_ratio: default_telemetry_sample_ratio(),
+            otlp_metrics_endpoint: None,
+            metrics_interval_secs: default_telemetry_metrics_interval_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthConfig {
+    /// Maximum allowed difference in seconds between a signed request's timestamp and the
+    /// gateway clock. Nonces are remembered for this long to reject replays.
+    #[serde(default = "default_signature_window_secs")]
+    pub signature_window_secs: u64,
+    /// Most nonces remembered across all accounts. Signed requests are rejected while this
+    /// many nonces inside the window are remembered, since any key can sign one.
+    #[serde(default = "default_max_tracked_nonces")]
+    pub max_tracked_nonces: usize,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            signature_window_secs: default_signature_window_secs(),
+            max_tracked_nonces: default_max_tracked_nonces(),
+        }
+    }
+}
+
+fn default_signature_window_secs() -> u64 {
+    30
+}
+
+fn default_max_tracked_nonces() -> usize {
+    100_000
+}
+
+fn default_cleanup_interval_secs() -> u64 {
+    60
+}
+
+fn default_history_entries_per_account() -> usize {
+    100
+}
+
+fn default_burst_loan_min_grant_secs() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_tls_reload_interval_secs() -> u64 {
+    60
+}
+
+fn default_decision_budget_ms() -> u64 {
+    50
+}
+
+fn default_session_recheck_fail_policy() -> FailPolicy {
+    FailPolicy::Open
+}
+
+fn default_decision_cache_max_entries() -> usize {
+    100_000
+}
+
+fn default_auto_ban_max_offenses() -> u32 {
+    50
+}
+
+fn default_auto_ban_window_secs() -> u64 {
+    60
+}
+
+fn default_auto_ban_ban_secs() -> u64 {
+    5 * 60
+}
+
+fn default_auto_ban_max_ban_secs() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_auto_ban_forget_after_secs() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_auto_ban_ipv4_prefix() -> u8 {
+    32
+}
+
+fn default_auto_ban_ipv6_prefix() -> u8 {
+    64
+}
+
+fn default_webhook_max_attempts() -> u32 {
+    5
+}
+
+fn default_webhook_initial_backoff_ms() -> u64 {
+    1000
+}
+
+fn default_webhook_max_backoff_secs() -> u64 {
+    300
+}
+
+fn default_access_event_queue_size() -> usize {
+    10_000
+}
+
+fn default_access_event_coalesce_window_secs() -> u64 {
+    60
+}
+
+fn default_telemetry_service_name() -> String {
+    "secure-rpc-gateway".to_string()
+}
+
+fn default_telemetry_sample_ratio() -> f64 {
+    1.0
+}
+
+fn default_telemetry_metrics_interval_secs() -> u64 {
+    60
+}
+
+fn default_probe_interval_secs() -> u64 {
+    300
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RpcConfig {
-    pub listen_addr: SocketAddr,
-    pub proxy_to_url: Url,
-    #[serde(default = "default_max_body_size_bytes")]
-    pub max_body_size_bytes: usize,
-    #[serde(default = "default_request_timeout_secs")]
-    pub request_timeout_secs: u64,
+fn default_probe_timeout_secs() -> u64 {
+    10
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FirewallConfig {
-    #[serde(default, deserialize_with = "deserialize_ip_networks")]
-    pub allow_ips: HashSet<IpNetwork>,
-    #[serde(default, deserialize_with = "deserialize_accounts")]
-    pub allow_accounts: HashSet<AccountId32>,
-    #[serde(default)]
-    pub allow_unrestricted_access: bool,
+fn default_health_interval_secs() -> u64 {
+    10
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct WebhookConfig {
-    #[serde(default)]
-    pub event_urls: Vec<Url>,
+fn default_health_timeout_secs() -> u64 {
+    5
+}
+
+fn default_health_overload_percent() -> u8 {
+    90
+}
+
+fn default_refuse_unsupported_methods() -> bool {
+    true
+}
+
+fn default_cors_max_age_secs() -> u64 {
+    7200 // 2 hours, the most Chromium honors
+}
+
+fn default_cors_allowed_methods() -> Vec<String> {
+    ["GET", "HEAD", "POST", "OPTIONS"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn default_cors_allowed_headers() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+fn default_compression_min_size_bytes() -> u16 {
+    1024
+}
+
+fn default_head_cache_max_age_secs() -> u64 {
+    30
+}
+
+fn default_latency_slow_request_ms() -> u64 {
+    1000
+}
+
+fn default_latency_summary_interval_secs() -> u64 {
+    3600
+}
+
+fn default_latency_summary_methods() -> usize {
+    20
+}
+
+fn default_identity_order() -> Vec<IdentitySource> {
+    vec![
+        IdentitySource::ClientCert,
+        IdentitySource::ApiKey,
+        IdentitySource::Signature,
+    ]
+}
+
+fn default_plan_websocket() -> bool {
+    true
+}
+
+fn default_plan_quota_warning_percents() -> Vec<u8> {
+    vec![80, 100]
+}
+
+fn default_circuit_failure_threshold() -> u32 {
+    5
+}
+
+fn default_circuit_open_secs() -> u64 {
+    30
+}
+
+fn default_circuit_half_open_requests() -> u32 {
+    1
+}
+
+fn default_upstream_initial_backoff_ms() -> u64 {
+    100
+}
+
+fn default_upstream_pool_max_idle_per_host() -> usize {
+    32
+}
+
+fn default_upstream_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_upstream_tcp_keepalive_secs() -> Option<u64> {
+    Some(60)
+}
+
+fn default_maintenance_notice_secs() -> u64 {
+    3600
+}
+
+fn default_grpc_max_stream_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_mirror_percent() -> u8 {
+    100
+}
+
+fn default_mirror_max_in_flight() -> usize {
+    64
+}
+
+fn default_mirror_timeout_secs() -> u64 {
+    10
+}
+
+fn default_archive_recent_blocks() -> u64 {
+    128
+}
+
+fn default_archive_head_poll_secs() -> u64 {
+    6
+}
+
+fn default_ws_ping_interval_secs() -> u64 {
+    30
+}
+
+fn default_ws_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_ws_bridge_poll_interval_ms() -> u64 {
+    1000
+}
+
+fn default_traffic_stats_bucket_secs() -> u64 {
+    60
+}
+
+fn default_traffic_stats_buckets() -> usize {
+    60
+}
+
+fn default_traffic_stats_max_keys() -> usize {
+    200
+}
+
+fn default_max_method_length() -> usize {
+    128
+}
+
+fn default_grpc_stream_idle_timeout_secs() -> u64 {
+    300
+}
+
+fn default_upstream_max_backoff_ms() -> u64 {
+    2000
+}
+
+fn default_concurrency_queue_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_coalescing_methods() -> Vec<String> {
+    vec![
+        "eth_call".to_string(),
+        "eth_getBalance".to_string(),
+        "eth_getCode".to_string(),
+        "eth_getStorageAt".to_string(),
+        "eth_getTransactionCount".to_string(),
+        "state_call".to_string(),
+        "state_getStorage".to_string(),
+    ]
+}
+
+fn default_non_idempotent_methods() -> Vec<String> {
+    vec![
+        "eth_sendRawTransaction".to_string(),
+        "eth_sendTransaction".to_string(),
+        "author_submit*".to_string(),
+    ]
+}
+
+fn default_fingerprint_max_tracked() -> usize {
+    10_000
+}
+
+fn default_fingerprint_idle_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_origin_heatmap_window_secs() -> u64 {
+    3600
+}
+
+fn default_origin_heatmap_max_prefixes() -> usize {
+    50_000
+}
+
+fn default_daily_digest_top_methods() -> usize {
+    10
+}
+
+fn default_daily_digest_max_unique_ips() -> usize {
+    1_000_000
+}
+
+fn default_sync_submit_interval_secs() -> u64 {
+    10
+}
+
+fn default_sync_max_batch() -> usize {
+    32
+}
+
+fn default_relay_path() -> String {
+    "/relay".to_string()
+}
+
+fn default_audit_max_file_bytes() -> u64 {
+    64 * 1024 * 1024 // 64 MB
+}
+
+fn default_access_log_max_file_bytes() -> u64 {
+    100 * 1024 * 1024 // 100 MB
+}
+
+fn default_access_log_max_files() -> usize {
+    10
+}
+
+fn default_event_history_max_events() -> usize {
+    100_000
+}
+
+fn default_event_history_retention_secs() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_redis_key_prefix() -> String {
+    "blockchain-rpc:".to_string()
+}
+
+fn default_redis_sync_interval_secs() -> u64 {
+    5
+}
+
+fn default_redis_timeout_secs() -> u64 {
+    2
+}
+
+fn default_reload_poll_interval_secs() -> u64 {
+    5
+}
+
+fn default_metering_report_interval_secs() -> u64 {
+    3600
+}
+
+fn default_metering_persist_interval_secs() -> u64 {
+    60
+}
+
+fn default_metering_max_pending_reports() -> usize {
+    168
+}
+
+fn default_metering_default_compute_units() -> u64 {
+    1
+}
+
+fn default_metering_summary_top_methods() -> usize {
+    10
+}
+
+fn default_metering_history_days() -> u64 {
+    90
 }
 
 fn default_max_body_size_bytes() -> usize {
@@ -51,6 +2358,237 @@ fn default_request_timeout_secs() -> u64 {
     30
 }
 
+fn default_body_idle_timeout_secs() -> u64 {
+    10
+}
+
+fn default_max_header_count() -> usize {
+    100
+}
+
+fn default_max_header_bytes() -> usize {
+    16 * 1024 // 16 KB
+}
+
+fn default_max_forwarded_header_bytes() -> usize {
+    4 * 1024 // 4 KB
+}
+
+impl RpcConfig {
+    /// The `host:port` targets the WebSocket bridge may dial, derived from the configured
+    /// upstreams. Anything else is refused, even if request routing were to select it.
+    pub fn websocket_dial_targets(&self) -> HashSet<String> {
+        std::iter::once(&self.proxy_to_url)
+            .chain(&self.upstreams)
+            .filter_map(|url| {
+                Some(format!(
+                    "{}:{}",
+                    url.host_str()?,
+                    url.port_or_known_default()?
+                ))
+            })
+            .collect()
+    }
+}
+
+impl IdentityConfig {
+    /// The account an authenticated request's usage is metered under, if it is billed to its
+    /// account rather than its IP.
+    pub fn billed_account<'a>(&self, account: Option<&'a AccountId32>) -> Option<&'a AccountId32> {
+        account.filter(|_| self.billed == BilledIdentity::Account)
+    }
+}
+
+impl FirewallConfig {
+    /// Checks that country rules come with a GeoIP database to evaluate them.
+    pub fn validate_countries(&self) -> Result<()> {
+        let has_countries = !self.allow_countries.is_empty() || !self.deny_countries.is_empty();
+        if has_countries && self.geoip_database.is_none() {
+            return Err(Error::GeoIpError(
+                "firewall.allow_countries and firewall.deny_countries require firewall.geoip_database"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A problem with a configuration, with the setting it is about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProblem {
+    /// Where the setting is, e.g. `routes[1].proxy_to_url`. Empty when it couldn't be found.
+    pub path: String,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    /// The problem a section's check failed with, without the generic error prefix.
+    fn from_error(path: &str, error: Error) -> Self {
+        let message = match error {
+            Error::ConfigError(::config::ConfigError::Message(message)) => message,
+            Error::GeoIpError(message) => message,
+            other => other.to_string(),
+        };
+        Self::new(path, message)
+    }
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// The backend settings `[rpc]` and routes have in common.
+struct SectionBackends<'a> {
+    proxy_to_url: &'a Url,
+    upstreams: &'a [Url],
+    mirror: &'a Option<MirrorConfig>,
+    websocket_bridge: &'a Option<WebSocketBridgeConfig>,
+}
+
+/// Whether binding both addresses would conflict: the same port on the same address, or on
+/// every address.
+fn binds_same(a: SocketAddr, b: SocketAddr) -> bool {
+    a.port() == b.port() && (a.ip() == b.ip() || a.ip().is_unspecified() || b.ip().is_unspecified())
+}
+
+/// Whether `url` reaches the listener at `listen_addr` from the gateway's own host.
+fn points_at(url: &Url, listen_addr: SocketAddr) -> bool {
+    if listen_addr.port() == 0 || url.port_or_known_default() != Some(listen_addr.port()) {
+        return false;
+    }
+    let listen_ip = listen_addr.ip();
+    let local = |ip: IpAddr| ip == listen_ip || (listen_ip.is_unspecified() && ip.is_loopback());
+    match url.host() {
+        Some(url::Host::Ipv4(ip)) => local(IpAddr::V4(ip)),
+        Some(url::Host::Ipv6(ip)) => local(IpAddr::V6(ip)),
+        Some(url::Host::Domain(domain)) => {
+            domain.eq_ignore_ascii_case("localhost")
+                && (listen_ip.is_loopback() || listen_ip.is_unspecified())
+        }
+        None => false,
+    }
+}
+
+/// A key or array index in the path of a setting.
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// The problem a configuration failed to deserialize with. Type errors carry the key of the
+/// setting, but errors of custom deserializers (networks, accounts, durations, ...) don't, so
+/// the setting is found by removing settings until the error goes away.
+fn deserialization_problem(
+    config: &::config::Config,
+    error: ::config::ConfigError,
+) -> ConfigProblem {
+    match error {
+        ::config::ConfigError::Message(message) => {
+            let path = config
+                .collect()
+                .ok()
+                .and_then(|root| locate_setting(root, &message))
+                .unwrap_or_default();
+            ConfigProblem::new(path, message)
+        }
+        ::config::ConfigError::Type {
+            key: Some(ref key), ..
+        } => ConfigProblem::new(key.clone(), error.to_string()),
+        other => ConfigProblem::new("", other.to_string()),
+    }
+}
+
+/// Descends from the root into the setting whose removal makes deserializing stop failing
+/// with `message`, for as long as there is one, and returns the path it reached.
+fn locate_setting(root: ::config::Map<String, ::config::Value>, message: &str) -> Option<String> {
+    let root = ::config::Value::new(None, ::config::ValueKind::Table(root));
+    let mut path: Vec<PathSegment> = Vec::new();
+    loop {
+        let node = path
+            .iter()
+            .try_fold(&root, |value, segment| child(value, segment))?;
+        let children: Vec<PathSegment> = match &node.kind {
+            ::config::ValueKind::Table(table) => {
+                table.keys().cloned().map(PathSegment::Key).collect()
+            }
+            ::config::ValueKind::Array(array) => (0..array.len()).map(PathSegment::Index).collect(),
+            _ => break,
+        };
+        let culprit = children.into_iter().find(|segment| {
+            let mut trimmed = root.clone();
+            remove_setting(&mut trimmed, &path, segment);
+            !trimmed
+                .try_deserialize::<ServiceConfig>()
+                .is_err_and(|e| e.to_string() == message)
+        });
+        match culprit {
+            Some(segment) => path.push(segment),
+            None => break,
+        }
+    }
+    if path.is_empty() {
+        return None;
+    }
+    let mut formatted = String::new();
+    for segment in &path {
+        match segment {
+            PathSegment::Key(key) if formatted.is_empty() => formatted.push_str(key),
+            PathSegment::Key(key) => {
+                formatted.push('.');
+                formatted.push_str(key);
+            }
+            PathSegment::Index(index) => formatted.push_str(&format!("[{index}]")),
+        }
+    }
+    Some(formatted)
+}
+
+fn child<'a>(value: &'a ::config::Value, segment: &PathSegment) -> Option<&'a ::config::Value> {
+    match (&value.kind, segment) {
+        (::config::ValueKind::Table(table), PathSegment::Key(key)) => table.get(key),
+        (::config::ValueKind::Array(array), PathSegment::Index(index)) => array.get(*index),
+        _ => None,
+    }
+}
+
+/// Removes the setting `segment` from the table or array at `parent`.
+fn remove_setting(root: &mut ::config::Value, parent: &[PathSegment], segment: &PathSegment) {
+    let mut node = root;
+    for step in parent {
+        let next = match (&mut node.kind, step) {
+            (::config::ValueKind::Table(table), PathSegment::Key(key)) => table.get_mut(key),
+            (::config::ValueKind::Array(array), PathSegment::Index(index)) => array.get_mut(*index),
+            _ => None,
+        };
+        match next {
+            Some(next) => node = next,
+            None => return,
+        }
+    }
+    match (&mut node.kind, segment) {
+        (::config::ValueKind::Table(table), PathSegment::Key(key)) => {
+            table.remove(key);
+        }
+        (::config::ValueKind::Array(array), PathSegment::Index(index)) => {
+            array.remove(*index);
+        }
+        _ => {}
+    }
+}
+
 impl ServiceConfig {
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         let config = ::config::Config::builder()
@@ -58,13 +2596,491 @@ impl ServiceConfig {
             .add_source(::config::Environment::with_prefix("SECURE_RPC").separator("__"))
             .build()
             .map_err(Error::ConfigError)?;
-        let service_config: ServiceConfig = config.try_deserialize().map_err(Error::ConfigError)?;
+        let service_config: ServiceConfig = match config.clone().try_deserialize() {
+            Ok(service_config) => service_config,
+            Err(e) => {
+                return Err(Error::InvalidConfig(vec![deserialization_problem(
+                    &config, e,
+                )]));
+            }
+        };
+        service_config.validate()?;
         Ok(service_config)
     }
+
+    /// Runs the checks a configuration must pass beyond deserializing, failing with every
+    /// problem found.
+    pub fn validate(&self) -> Result<()> {
+        let problems = self.problems();
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(Error::InvalidConfig(problems))
+    }
+
+    /// Every problem with the configuration rather than just the first: what the checks of
+    /// each section reject, and constraints between settings.
+    pub fn problems(&self) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+        let mut check = |path: &str, result: Result<()>| {
+            if let Err(e) = result {
+                problems.push(ConfigProblem::from_error(path, e));
+            }
+        };
+        check(
+            "firewall.geoip_database",
+            self.firewall.validate_countries(),
+        );
+        if let Some(rate_limit) = &self.firewall.rate_limit {
+            check("firewall.rate_limit", rate_limit.validate());
+        }
+        if let Some(message_rate) = &self.websocket.message_rate {
+            check("websocket.message_rate", message_rate.validate());
+        }
+        for (id, plan) in &self.plans {
+            if let Some(rate_limit) = &plan.rate_limit {
+                check(&format!("plans.{id}.rate_limit"), rate_limit.validate());
+            }
+        }
+        if let Some(cors) = &self.rpc.cors {
+            check("rpc.cors", cors.validate());
+        }
+        if let Some(keepalive) = &self.rpc.websocket_keepalive {
+            check("rpc.websocket_keepalive", keepalive.validate());
+        }
+        if let Some(proxy) = &self.rpc.outbound_proxy {
+            check("rpc.outbound_proxy", proxy.validate());
+        }
+        if let Some(validation) = &self.rpc.validation {
+            check("rpc.validation", validation.validate());
+        }
+        if let Some(payments) = &self.payments {
+            check("payments", payments.validate());
+        }
+        if let Some(traffic_stats) = &self.traffic_stats {
+            check("traffic_stats", traffic_stats.validate());
+        }
+        if let Some(relay) = &self.relay {
+            check("relay", relay.validate());
+        }
+        for (section, route) in self.sections() {
+            if let Some(mirror) = &route.mirror {
+                check(&format!("{section}.mirror"), mirror.validate());
+            }
+            if let Some(bridge) = &route.websocket_bridge {
+                check(&format!("{section}.websocket_bridge"), bridge.validate());
+            }
+        }
+        check("routes", self.validate_routes());
+        check("plans", self.validate_plan_durations());
+        check("rpc.maintenance", self.validate_maintenance());
+        check("rpc.upstream_auth", self.validate_upstream_auth());
+        problems.extend(self.listener_problems());
+        problems.extend(self.backend_problems());
+        problems.extend(self.timeout_problems());
+        problems.extend(self.webhook_problems());
+        problems
+    }
+
+    /// The path and backend settings of `[rpc]` and of each route.
+    fn sections(&self) -> Vec<(String, SectionBackends<'_>)> {
+        let rpc = SectionBackends {
+            proxy_to_url: &self.rpc.proxy_to_url,
+            upstreams: &self.rpc.upstreams,
+            mirror: &self.rpc.mirror,
+            websocket_bridge: &self.rpc.websocket_bridge,
+        };
+        std::iter::once(("rpc".to_string(), rpc))
+            .chain(self.routes.iter().enumerate().map(|(i, route)| {
+                let backends = SectionBackends {
+                    proxy_to_url: &route.proxy_to_url,
+                    upstreams: &route.upstreams,
+                    mirror: &route.mirror,
+                    websocket_bridge: &route.websocket_bridge,
+                };
+                (format!("routes[{i}]"), backends)
+            }))
+            .collect()
+    }
+
+    /// Listeners sharing an address, which would fail binding the second one at startup, and
+    /// an admin API reachable beyond loopback without authentication.
+    fn listener_problems(&self) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+        if let Some(admin) = self.admin.listen_addr {
+            if admin.port() != 0 && binds_same(admin, self.rpc.listen_addr) {
+                problems.push(ConfigProblem::new(
+                    "admin.listen_addr",
+                    format!("conflicts with rpc.listen_addr {}", self.rpc.listen_addr),
+                ));
+            }
+            let open = self.admin.token.is_none() && self.admin.accounts.is_empty();
+            if open && !admin.ip().is_loopback() {
+                problems.push(ConfigProblem::new(
+                    "admin.listen_addr",
+                    "is not a loopback address, so admin.token or admin.accounts must be set",
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Backends the gateway can't proxy to: URLs without an HTTP or WebSocket scheme and a
+    /// host, the same backend listed twice for a route, and the gateway's own listener, which
+    /// would loop requests back into it.
+    fn backend_problems(&self) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+        for (section, route) in self.sections() {
+            let mut seen = HashSet::new();
+            let urls = std::iter::once((format!("{section}.proxy_to_url"), route.proxy_to_url))
+                .chain(
+                    route
+                        .upstreams
+                        .iter()
+                        .enumerate()
+                        .map(|(i, url)| (format!("{section}.upstreams[{i}]"), url)),
+                );
+            for (path, url) in urls {
+                let problem = if !matches!(url.scheme(), "http" | "https" | "ws" | "wss")
+                    || url.host_str().is_none()
+                {
+                    format!("{url} is not an http(s) or ws(s) URL with a host")
+                } else if !seen.insert(url) {
+                    format!("{url} is already a backend of {section}")
+                } else if points_at(url, self.rpc.listen_addr) {
+                    format!("{url} is the gateway's own rpc.listen_addr")
+                } else {
+                    continue;
+                };
+                problems.push(ConfigProblem::new(path, problem));
+            }
+        }
+        problems
+    }
+
+    /// Timeouts of zero, which would fail every request, probe or stream at once.
+    fn timeout_problems(&self) -> Vec<ConfigProblem> {
+        let mut timeouts = vec![
+            (
+                "rpc.request_timeout_secs".to_string(),
+                self.rpc.request_timeout_secs,
+            ),
+            (
+                "rpc.body_idle_timeout_secs".to_string(),
+                self.rpc.body_idle_timeout_secs,
+            ),
+        ];
+        if let Some(probe) = &self.rpc.probe {
+            timeouts.push(("rpc.probe.timeout_secs".to_string(), probe.timeout_secs));
+        }
+        if let Some(health) = &self.rpc.health {
+            timeouts.push(("rpc.health.timeout_secs".to_string(), health.timeout_secs));
+        }
+        if let StorageConfig::Redis { timeout_secs, .. } = &self.storage {
+            timeouts.push(("storage.timeout_secs".to_string(), *timeout_secs));
+        }
+        if let Some(grpc) = &self.rpc.grpc {
+            timeouts.push((
+                "rpc.grpc.stream_idle_timeout_secs".to_string(),
+                grpc.stream_idle_timeout_secs,
+            ));
+        }
+        for (section, route) in self.sections() {
+            if let Some(mirror) = route.mirror {
+                timeouts.push((
+                    format!("{section}.mirror.timeout_secs"),
+                    mirror.timeout_secs,
+                ));
+            }
+        }
+        timeouts
+            .into_iter()
+            .filter(|(_, secs)| *secs == 0)
+            .map(|(path, _)| ConfigProblem::new(path, "must be positive"))
+            .collect()
+    }
+
+    /// Webhooks that can't be delivered: anything but an `http` or `https` URL with a host,
+    /// the same rule the `register_webhook` job applies.
+    fn webhook_problems(&self) -> Vec<ConfigProblem> {
+        let event_urls = self
+            .webhooks
+            .event_urls
+            .iter()
+            .enumerate()
+            .map(|(i, url)| (format!("webhooks.event_urls[{i}]"), url));
+        let endpoints = self
+            .webhooks
+            .endpoints
+            .iter()
+            .enumerate()
+            .map(|(i, endpoint)| (format!("webhooks.endpoints[{i}].url"), &endpoint.url));
+        event_urls
+            .chain(endpoints)
+            .filter(|(_, url)| {
+                !matches!(url.scheme(), "http" | "https") || url.host_str().is_none()
+            })
+            .map(|(path, url)| {
+                ConfigProblem::new(
+                    path,
+                    format!("{url} is not an http or https URL with a host"),
+                )
+            })
+            .collect()
+    }
+
+    /// Every backend URL: `proxy_to_url` and `upstreams` of `[rpc]` and of each route.
+    fn backends(&self) -> HashSet<&Url> {
+        std::iter::once(&self.rpc.proxy_to_url)
+            .chain(&self.rpc.upstreams)
+            .chain(
+                self.routes
+                    .iter()
+                    .flat_map(|route| std::iter::once(&route.proxy_to_url).chain(&route.upstreams)),
+            )
+            .collect()
+    }
+
+    /// The archive nodes of `[rpc]` and of each route.
+    fn archive_backends(&self) -> impl Iterator<Item = &Url> {
+        std::iter::once(&self.rpc.archive)
+            .chain(self.routes.iter().map(|route| &route.archive))
+            .flatten()
+            .map(|archive| &archive.url)
+    }
+
+    /// The mirrors of `[rpc]` and of each route.
+    fn mirrors(&self) -> impl Iterator<Item = &MirrorConfig> {
+        std::iter::once(&self.rpc.mirror)
+            .chain(self.routes.iter().map(|route| &route.mirror))
+            .flatten()
+    }
+
+    /// Checks that upstream credentials are valid headers, each for a configured backend and
+    /// given once.
+    pub fn validate_upstream_auth(&self) -> Result<()> {
+        let mut backends = self.backends();
+        backends.extend(self.archive_backends());
+        backends.extend(self.mirrors().map(|mirror| &mirror.url));
+        let mut seen = HashSet::new();
+        for auth in &self.rpc.upstream_auth {
+            if !backends.contains(&auth.backend) {
+                return Err(Error::ConfigError(::config::ConfigError::Message(format!(
+                    "rpc.upstream_auth for {} is not for a configured backend",
+                    auth.backend
+                ))));
+            }
+            if !seen.insert(&auth.backend) {
+                return Err(Error::ConfigError(::config::ConfigError::Message(format!(
+                    "rpc.upstream_auth for {} is configured more than once",
+                    auth.backend
+                ))));
+            }
+            auth.header_map()?;
+        }
+        Ok(())
+    }
+
+    /// Checks that maintenance windows end after they start, on a configured backend.
+    pub fn validate_maintenance(&self) -> Result<()> {
+        let backends = self.backends();
+        for window in &self.rpc.maintenance {
+            if window.ends_at <= window.starts_at {
+                return Err(Error::ConfigError(::config::ConfigError::Message(format!(
+                    "Maintenance of {} must end after it starts",
+                    window.backend
+                ))));
+            }
+            if !backends.contains(&window.backend) {
+                return Err(Error::ConfigError(::config::ConfigError::Message(format!(
+                    "Maintenance of {} is not for a configured backend",
+                    window.backend
+                ))));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that no plan grants access for longer than `jobs.max_access_duration_secs` or
+    /// `jobs.max_access_horizon_secs` allow.
+    pub fn validate_plan_durations(&self) -> Result<()> {
+        let max = match (
+            self.jobs.max_access_duration_secs,
+            self.jobs.max_access_horizon_secs,
+        ) {
+            (Some(duration), Some(horizon)) => duration.min(horizon),
+            (Some(max), None) | (None, Some(max)) => max,
+            (None, None) => return Ok(()),
+        };
+        for (id, plan) in &self.plans {
+            if let Some(duration_secs) = plan.duration_secs.filter(|duration| *duration > max) {
+                return Err(Error::ConfigError(::config::ConfigError::Message(format!(
+                    "Plan {id} grants {duration_secs}s of access, more than the {max}s jobs.max_access_* allow"
+                ))));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that route prefixes are distinct paths below `/`, without a trailing slash.
+    pub fn validate_routes(&self) -> Result<()> {
+        let mut prefixes = HashSet::new();
+        for route in &self.routes {
+            let prefix = route.prefix.as_str();
+            if !prefix.starts_with('/') || prefix.len() < 2 || prefix.ends_with('/') {
+                return Err(Error::ConfigError(::config::ConfigError::Message(format!(
+                    "Route prefix {prefix:?} must start with `/` and not end with it"
+                ))));
+            }
+            if !prefixes.insert(prefix) {
+                return Err(Error::ConfigError(::config::ConfigError::Message(format!(
+                    "Route prefix {prefix} is configured more than once"
+                ))));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that a re-read configuration can replace `current` while the service is running.
+    ///
+    /// Listeners, TLS, the GeoIP database and the admin server are set up once at startup, so
+    /// changing them requires a restart; a reload that does is rejected rather than partially
+    /// applied.
+    pub fn validate_reload(&self, current: &ServiceConfig) -> Result<()> {
+        let restart_only = [
+            (
+                "rpc.listen_addr",
+                self.rpc.listen_addr != current.rpc.listen_addr,
+            ),
+            (
+                "rpc.tls",
+                self.rpc.tls.is_some() != current.rpc.tls.is_some(),
+            ),
+            (
+                "rpc.compression",
+                self.rpc.compression != current.rpc.compression,
+            ),
+            (
+                "rpc.cors_max_age_secs",
+                self.rpc.cors_max_age_secs != current.rpc.cors_max_age_secs,
+            ),
+            ("rpc.cors", self.rpc.cors != current.rpc.cors),
+            ("rpc.archive", self.rpc.archive != current.rpc.archive),
+            ("rpc.mirror", self.rpc.mirror != current.rpc.mirror),
+            (
+                "rpc.websocket_bridge",
+                self.rpc.websocket_bridge != current.rpc.websocket_bridge,
+            ),
+            (
+                "rpc.validation",
+                self.rpc.validation != current.rpc.validation,
+            ),
+            (
+                "rpc.outbound_proxy",
+                self.rpc.outbound_proxy != current.rpc.outbound_proxy,
+            ),
+            (
+                "rpc.upstream_pool",
+                self.rpc.upstream_pool != current.rpc.upstream_pool,
+            ),
+            (
+                "rpc.global_concurrency",
+                self.rpc.global_concurrency.is_some() != current.rpc.global_concurrency.is_some(),
+            ),
+            (
+                "rpc.latency",
+                self.rpc.latency.is_some() != current.rpc.latency.is_some(),
+            ),
+            (
+                "rpc.head_cache",
+                self.rpc.head_cache != current.rpc.head_cache,
+            ),
+            (
+                "rpc.coalescing",
+                self.rpc.coalescing != current.rpc.coalescing,
+            ),
+            (
+                "rpc.health",
+                self.rpc.health.is_some() != current.rpc.health.is_some(),
+            ),
+            ("traffic_stats", self.traffic_stats != current.traffic_stats),
+            ("relay", self.relay != current.relay),
+            (
+                "rpc.grpc.upstream_url",
+                self.rpc.grpc.as_ref().map(|grpc| &grpc.upstream_url)
+                    != current.rpc.grpc.as_ref().map(|grpc| &grpc.upstream_url),
+            ),
+            (
+                "admin.listen_addr",
+                self.admin.listen_addr != current.admin.listen_addr,
+            ),
+            (
+                "firewall.auto_ban",
+                self.firewall.auto_ban != current.firewall.auto_ban,
+            ),
+            (
+                "firewall.geoip_database",
+                self.firewall.geoip_database != current.firewall.geoip_database,
+            ),
+            (
+                "origin_heatmap.window_secs",
+                self.origin_heatmap.window_secs != current.origin_heatmap.window_secs,
+            ),
+            (
+                "origin_heatmap.max_prefixes",
+                self.origin_heatmap.max_prefixes != current.origin_heatmap.max_prefixes,
+            ),
+            (
+                "webhooks.access_events",
+                self.webhooks.access_events != current.webhooks.access_events,
+            ),
+            ("access_log", self.access_log != current.access_log),
+            ("event_history", self.event_history != current.event_history),
+            (
+                "daily_digest",
+                self.daily_digest.is_some() != current.daily_digest.is_some(),
+            ),
+            (
+                "sync",
+                self.sync
+                    .as_ref()
+                    .map(|sync| (sync.submit_interval_secs, sync.max_batch))
+                    != current
+                        .sync
+                        .as_ref()
+                        .map(|sync| (sync.submit_interval_secs, sync.max_batch)),
+            ),
+            ("storage", self.storage != current.storage),
+        ];
+        let changed: Vec<&str> = restart_only
+            .iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(key, _)| *key)
+            .collect();
+        if !changed.is_empty() {
+            return Err(Error::ConfigReloadError(format!(
+                "changing {} requires a restart",
+                changed.join(", ")
+            )));
+        }
+        if self.rpc.request_timeout_secs == 0 {
+            return Err(Error::ConfigReloadError(
+                "rpc.request_timeout_secs must be greater than 0".to_string(),
+            ));
+        }
+        if self.rpc.max_body_size_bytes == 0 {
+            return Err(Error::ConfigReloadError(
+                "rpc.max_body_size_bytes must be greater than 0".to_string(),
+            ));
+        }
+        Ok(())
+    }
 }
 
 // Custom deserializer for HashSet<IpNetwork>
-fn deserialize_ip_networks<'de, D>(deserializer: D) -> Result<HashSet<IpNetwork>, D::Error>
+pub(crate) fn deserialize_ip_networks<'de, D>(
+    deserializer: D,
+) -> Result<HashSet<IpNetwork>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
@@ -73,13 +3089,74 @@ where
         .into_iter()
         .map(|s| {
             IpNetwork::from_str(&s)
+                .map(canonical_network)
                 .map_err(|e| serde::de::Error::custom(format!("Invalid IP/CIDR '{}': {}", s, e)))
         })
         .collect()
 }
 
+// Custom deserializer for ISO 3166-1 alpha-2 country codes, normalized to upper case
+fn deserialize_country_codes<'de, D>(deserializer: D) -> Result<HashSet<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let codes = Vec::<String>::deserialize(deserializer)?;
+    codes
+        .into_iter()
+        .map(|code| {
+            if code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+                Ok(code.to_ascii_uppercase())
+            } else {
+                Err(serde::de::Error::custom(format!(
+                    "Invalid country code '{}': expected ISO 3166-1 alpha-2, e.g. \"DE\"",
+                    code
+                )))
+            }
+        })
+        .collect()
+}
+
 // Custom deserializer for HashSet<AccountId32>
-fn deserialize_accounts<'de, D>(deserializer: D) -> Result<HashSet<AccountId32>, D::Error>
+fn deserialize_certificate_accounts<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, AccountId32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let entries = HashMap::<String, String>::deserialize(deserializer)?;
+    entries
+        .into_iter()
+        .map(|(key, account)| {
+            let account = AccountId32::from_str(&account).map_err(|_| {
+                serde::de::Error::custom(format!("Invalid AccountId32: {}", account))
+            })?;
+            Ok((normalize_certificate_key(&key), account))
+        })
+        .collect()
+}
+
+/// Normalizes fingerprint keys to lowercase hex without separators. SANs are kept as-is.
+pub(crate) fn normalize_certificate_key(key: &str) -> String {
+    let hex: String = key.chars().filter(|c| *c != ':').collect();
+    if hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        hex.to_ascii_lowercase()
+    } else {
+        key.to_string()
+    }
+}
+
+fn deserialize_account<'de, D>(deserializer: D) -> Result<AccountId32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let account = String::deserialize(deserializer)?;
+    AccountId32::from_str(&account)
+        .map_err(|_| serde::de::Error::custom(format!("Invalid AccountId32: {}", account)))
+}
+
+pub(crate) fn deserialize_accounts<'de, D>(
+    deserializer: D,
+) -> Result<HashSet<AccountId32>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
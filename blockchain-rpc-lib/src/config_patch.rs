@@ -0,0 +1,213 @@
+use crate::Result;
+use crate::config::ServiceConfig;
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Shown in place of values under keys naming a secret.
+const REDACTED: &str = "<redacted>";
+
+/// One setting changed by a config patch. Values are JSON, `None` where the setting is unset.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigChange {
+    /// Dotted path of the setting, e.g. `firewall.rate_limit.burst`.
+    pub path: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl ConfigChange {
+    /// The change as `path: old -> new`, for logs, the audit log and webhooks.
+    pub fn setting(&self) -> String {
+        let unset = || "unset".to_string();
+        format!(
+            "{}: {} -> {}",
+            self.path,
+            self.old.clone().unwrap_or_else(unset),
+            self.new.clone().unwrap_or_else(unset)
+        )
+    }
+}
+
+/// Applies a JSON merge patch (RFC 7396) to `config`: objects are merged key by key, `null`
+/// removes a setting (restoring its default) and any other value replaces it. The result is
+/// validated like a config file, so a patch the gateway couldn't serve with is rejected as a
+/// whole.
+pub fn patch_config(config: &ServiceConfig, patch: &Value) -> Result<ServiceConfig> {
+    if !patch.is_object() {
+        return Err(Error::InvalidJobInput(
+            "Config patch must be a JSON object".to_string(),
+        ));
+    }
+    let mut value = serde_json::to_value(config)?;
+    merge_patch(&mut value, patch);
+    let mut patched: ServiceConfig = serde_json::from_value(value)
+        .map_err(|e| Error::InvalidJobInput(format!("Invalid config patch: {e}")))?;
+    // Secrets are never serialized, so they're carried over unless the patch sets them
+    if patch.pointer("/admin/token").is_none() {
+        patched.admin.token = config.admin.token.clone();
+    }
+    if patch.pointer("/rpc/upstream_auth").is_none() {
+        patched.rpc.upstream_auth = config.rpc.upstream_auth.clone();
+    }
+    if let (Some(proxy), Some(current), None) = (
+        patched.rpc.outbound_proxy.as_mut(),
+        &config.rpc.outbound_proxy,
+        patch.pointer("/rpc/outbound_proxy/password"),
+    ) {
+        proxy.password = current.password.clone();
+    }
+    if patch.pointer("/webhooks/endpoints").is_none() {
+        patched.webhooks.endpoints = config.webhooks.endpoints.clone();
+    }
+    patched.validate()?;
+    Ok(patched)
+}
+
+/// Merges `patch` into `target` as RFC 7396 describes.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(target) = target else {
+        return;
+    };
+    for (key, value) in patch {
+        match value {
+            Value::Null => {
+                target.remove(key);
+            }
+            Value::Object(_) => merge_patch(target.entry(key).or_insert(Value::Null), value),
+            _ => {
+                target.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Like [`merge_patch`], but keeps `null`s, so the result still removes what either patch
+/// removed.
+pub fn compose_patches(first: &mut Value, second: &Value) {
+    let (Value::Object(first), Value::Object(second)) = (&mut *first, second) else {
+        *first = second.clone();
+        return;
+    };
+    for (key, value) in second {
+        match (first.get_mut(key), value) {
+            (Some(existing), Value::Object(_)) if existing.is_object() => {
+                compose_patches(existing, value)
+            }
+            _ => {
+                first.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Removes the setting at the dotted `path` from `patch`, so it no longer changes it.
+pub fn remove_from_patch(patch: &mut Value, path: &str) {
+    let (parent, key) = match path.rsplit_once('.') {
+        Some((parent, key)) => (
+            patch.pointer_mut(&format!("/{}", parent.replace('.', "/"))),
+            key,
+        ),
+        None => (Some(patch), path),
+    };
+    if let Some(Value::Object(parent)) = parent {
+        parent.remove(key);
+    }
+}
+
+/// The settings that differ between `old` and `new`, in path order. Lists are compared as a
+/// whole and without regard to order, since sets serialize in no fixed order; values under keys
+/// naming a secret or token are redacted.
+pub fn diff_configs(old: &ServiceConfig, new: &ServiceConfig) -> Result<Vec<ConfigChange>> {
+    let mut changes = Vec::new();
+    diff_values(
+        "",
+        Some(&serde_json::to_value(old)?),
+        Some(&serde_json::to_value(new)?),
+        &mut changes,
+    );
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(changes)
+}
+
+fn diff_values(
+    path: &str,
+    old: Option<&Value>,
+    new: Option<&Value>,
+    changes: &mut Vec<ConfigChange>,
+) {
+    match (old, new) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            for key in old
+                .keys()
+                .chain(new.keys().filter(|key| !old.contains_key(*key)))
+            {
+                let path = match path {
+                    "" => key.clone(),
+                    parent => format!("{parent}.{key}"),
+                };
+                diff_values(&path, old.get(key), new.get(key), changes);
+            }
+        }
+        (Some(Value::Array(old)), Some(Value::Array(new))) if same_elements(old, new) => {}
+        _ => {
+            let old = old.filter(|value| !value.is_null());
+            let new = new.filter(|value| !value.is_null());
+            if old == new {
+                return;
+            }
+            let secret = path.rsplit('.').next().is_some_and(is_secret);
+            let show = |value: &Value| {
+                if secret {
+                    REDACTED.to_string()
+                } else {
+                    redacted(value).to_string()
+                }
+            };
+            changes.push(ConfigChange {
+                path: path.to_string(),
+                old: old.map(show),
+                new: new.map(show),
+            });
+        }
+    }
+}
+
+fn is_secret(key: &str) -> bool {
+    key.contains("secret") || key.contains("token")
+}
+
+/// `value` with everything under keys naming a secret replaced, e.g. in a list of webhooks.
+fn redacted(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let value = if is_secret(key) && !value.is_null() {
+                        Value::String(REDACTED.to_string())
+                    } else {
+                        redacted(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.iter().map(redacted).collect()),
+        other => other.clone(),
+    }
+}
+
+fn same_elements(old: &[Value], new: &[Value]) -> bool {
+    old.len() == new.len()
+        && old.iter().all(|value| {
+            old.iter().filter(|other| *other == value).count()
+                == new.iter().filter(|other| *other == value).count()
+        })
+}
@@ -1,69 +1,869 @@
 use crate::Result;
-use crate::config::ServiceConfig;
+use crate::access_events::AccessEvents;
+use crate::access_log::AccessLog;
+use crate::api_keys::ApiKeyStore;
+use crate::archive::ArchiveRoute;
+use crate::audit::AuditLog;
+use crate::circuit_breaker::{CircuitBreaker, CircuitState};
+use crate::coalescing::RequestCoalescer;
+use crate::config::{ServiceConfig, StorageConfig, UpstreamProbeConfig};
 use crate::default_data_dir;
+use crate::digest::DailyDigestCollector;
 use crate::error::Error;
+use crate::event_history::EventHistory;
+use crate::fingerprint::FingerprintRegistry;
 use crate::firewall::Firewall;
+use crate::head_cache::HeadCache;
+use crate::health::HealthChecker;
+use crate::idempotency::IdempotencyStore;
+use crate::in_flight::InFlightRequests;
+use crate::labels::AccountLabels;
+use crate::latency::LatencyTracker;
+use crate::maintenance::MaintenanceScheduler;
+use crate::metering::UsageMeter;
+use crate::method_filter::MethodFilter;
+use crate::method_limits::MethodLimits;
+use crate::method_rewrite::MethodRewriter;
+use crate::mirror::Mirror;
+use crate::origin_heatmap::OriginHeatmap;
+use crate::payload_filters::PayloadFilters;
+use crate::payments::PaymentVerifier;
+use crate::plans::Plans;
+use crate::proxy::grpc::GrpcUpstream;
+use crate::proxy::routing::{Route, RoutingTable};
+use crate::proxy::upstream::{ConcurrencyLimit, Upstream};
+use crate::read_only::ReadOnlyMode;
+use crate::relay::TransactionRelay;
+use crate::replay::ProcessedCalls;
+use crate::request_validation::RequestValidator;
+use crate::roles::AccountRoles;
+use crate::rule_sync::RuleSync;
+use crate::runtime_config::RuntimeConfig;
+use crate::sessions::SessionRegistry;
+#[cfg(feature = "redis")]
+use crate::shared_ratelimit::SharedRateLimits;
+use crate::signature_auth::{AdminNonces, SignatureVerifier};
+use crate::store::{self, FirewallStore, StateStore};
+use crate::tasks::TaskRegistry;
+use crate::traffic_stats::TrafficStats;
+use crate::upstream_events::{UpstreamEvent, UpstreamEvents};
+use crate::upstream_probe::UpstreamProbe;
+use crate::upstream_signing::UpstreamSigner;
+use crate::webhooks::{DeadLetterQueue, WebhookDispatcher};
 use blueprint_sdk::crypto::sp_core::SpSr25519;
+use blueprint_sdk::crypto::tangle_pair_signer::TanglePairSigner;
 use blueprint_sdk::keystore::backends::Backend;
 use blueprint_sdk::macros::context::{KeystoreContext, TangleClientContext};
 use blueprint_sdk::runner::config::BlueprintEnvironment;
 use chrono::{DateTime, Duration, Utc};
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
-use sp_core::sr25519::Pair as Sr25519Pair;
 use sp_runtime::AccountId32;
 use std::path::PathBuf;
 use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::time::interval;
+use tracing::{debug, info, warn};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How often recorded firewall events are written to the state store.
+const EVENT_HISTORY_FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// How often the daily digest samples backend reachability and checks for the end of the day.
+const DAILY_DIGEST_SAMPLE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TemporaryAccessRecord {
     pub granted_at: DateTime<Utc>,
     pub expires_at: DateTime<Utc>,
+    /// The `[plans]` tier bought, if any.
+    #[serde(default)]
+    pub plan: Option<String>,
+    /// Times the grant was extended by `extend_access`, each adding its plan's request quota
+    /// once more.
+    #[serde(default)]
+    pub extensions: u32,
 }
 
 #[derive(Clone, TangleClientContext, KeystoreContext)]
 pub struct SecureRpcContext {
     #[config]
     pub env: BlueprintEnvironment,
-    pub service_config: Arc<ServiceConfig>,
+    pub service_config: Arc<RwLock<Arc<ServiceConfig>>>,
+    /// Settings changed by the `update_config` job, kept over `config.toml`.
+    pub runtime_config: Arc<RuntimeConfig>,
     pub data_dir: PathBuf,
+    /// Persistent gateway state, from `[storage]`.
+    pub state_store: Arc<dyn StateStore>,
     pub firewall: Arc<Firewall>,
-    pub admin_pair: Option<Arc<Sr25519Pair>>,
+    /// Operator-assigned labels per account.
+    pub account_labels: Arc<AccountLabels>,
+    /// Operator-assigned roles per account, and the methods `[roles]` restricts to them.
+    pub account_roles: Arc<AccountRoles>,
+    /// Whether transaction-submitting calls are currently rejected.
+    pub read_only: Arc<ReadOnlyMode>,
+    pub method_filter: Arc<MethodFilter>,
+    pub method_rewriter: Arc<MethodRewriter>,
+    pub method_limits: Arc<MethodLimits>,
+    pub api_keys: Arc<ApiKeyStore>,
+    pub idempotency: Arc<IdempotencyStore>,
+    pub processed_calls: Arc<ProcessedCalls>,
+    pub signature_auth: Arc<SignatureVerifier>,
+    /// Last nonce per admin account, for replay-protected signed admin requests.
+    pub admin_nonces: Arc<AdminNonces>,
+    pub upstream_signer: Option<Arc<UpstreamSigner>>,
+    /// Checks `pay_for_access` payments on-chain, if `[payments]` is configured.
+    pub payment_verifier: Option<Arc<PaymentVerifier>>,
+    /// Access tiers from `[plans]`.
+    pub plans: Arc<Plans>,
+    /// Admin API rule changes awaiting submission to the other operators, if `[sync]` is
+    /// configured.
+    pub rule_sync: Option<Arc<RuleSync>>,
+    /// Signs and submits transactions for accounts with a relay policy, if `[relay]` is
+    /// configured.
+    pub relay: Option<Arc<TransactionRelay>>,
+    pub tasks: Arc<TaskRegistry>,
+    pub sessions: Arc<SessionRegistry>,
+    /// HTTP requests in flight per account, for the per-account caps.
+    pub in_flight: Arc<InFlightRequests>,
+    /// Site-specific request and response filters, registered in code.
+    pub payload_filters: Arc<PayloadFilters>,
+    pub upstream_events: Arc<UpstreamEvents>,
+    /// Guards the backend node, if `[rpc.circuit_breaker]` is configured.
+    pub circuit_breaker: Option<Arc<CircuitBreaker>>,
+    /// The backend nodes at `rpc.proxy_to_url` and `rpc.upstreams`.
+    pub(crate) upstream: Arc<Upstream>,
+    /// Slots for requests in flight to any backend, if `[rpc.global_concurrency]` is
+    /// configured.
+    pub(crate) global_concurrency: Option<Arc<ConcurrencyLimit>>,
+    /// Backends by path prefix, from `[[routes]]`, falling back to `upstream`.
+    pub(crate) routes: Arc<RoutingTable>,
+    /// Backend capabilities and chain verification, if `[rpc.probe]` or
+    /// `rpc.expected_chain_id` is configured.
+    pub upstream_probe: Option<Arc<UpstreamProbe>>,
+    /// Readiness checks behind `/readyz`, if `[rpc.health]` is configured.
+    pub health: Option<Arc<HealthChecker>>,
+    /// The backend gRPC calls are passed through to, if `[rpc.grpc]` is configured.
+    pub(crate) grpc: Option<Arc<GrpcUpstream>>,
+    /// Head queries answered from a backend subscription, if `[rpc.head_cache]` is configured.
+    pub head_cache: Option<Arc<HeadCache>>,
+    /// Identical concurrent calls sharing one backend call, if `[rpc.coalescing]` is
+    /// configured.
+    pub coalescer: Option<Arc<RequestCoalescer>>,
+    /// Per-account usage, if `[metering]` is configured.
+    pub usage_meter: Option<Arc<UsageMeter>>,
+    /// Per-method latency of proxied calls, if `[rpc.latency]` is configured.
+    pub latency: Option<Arc<LatencyTracker>>,
+    /// Audit trail of access decisions and rule changes, if `[audit]` is configured.
+    pub audit: Option<Arc<AuditLog>>,
+    /// Per-request and per-session access log, if `[access_log]` is configured.
+    pub access_log: Option<Arc<AccessLog>>,
+    /// Time-bucketed traffic by route, method and account, if `[traffic_stats]` is configured.
+    pub traffic_stats: Option<Arc<TrafficStats>>,
+    /// Recent firewall events, if `[event_history]` is configured.
+    pub event_history: Option<Arc<EventHistory>>,
+    /// The day's statistics for the `DailyDigest` webhook event, if `[daily_digest]` is
+    /// configured.
+    pub daily_digest: Option<Arc<DailyDigestCollector>>,
+    pub fingerprints: Arc<FingerprintRegistry>,
+    /// Requests and denials per client prefix, counted while `[origin_heatmap]` is enabled.
+    pub origin_heatmap: Arc<OriginHeatmap>,
+    /// Rate-limit buckets on the Redis server, if `[storage] shared_rate_limits` is set.
+    #[cfg(feature = "redis")]
+    pub shared_rate_limits: Option<Arc<SharedRateLimits>>,
 }
 
 impl SecureRpcContext {
-    pub async fn new(env: BlueprintEnvironment, service_config: ServiceConfig) -> Result<Self> {
+    pub async fn new(env: BlueprintEnvironment, mut service_config: ServiceConfig) -> Result<Self> {
         let data_dir = env.data_dir.clone().unwrap_or_else(default_data_dir);
         if !data_dir.exists() {
             std::fs::create_dir_all(&data_dir)?;
         }
 
-        let service_config = Arc::new(service_config);
+        // The store holds the settings changed at runtime, so it's opened as `config.toml`
+        // configures it and can't be moved by them
+        let state_store = store::open(&service_config.storage, &data_dir)?;
+        let storage = service_config.storage.clone();
+        let runtime_config = Arc::new(RuntimeConfig::load(state_store.clone(), &data_dir)?);
+        let overrides = runtime_config.overrides();
+        if !overrides.is_empty() {
+            info!(settings = ?overrides.settings(), "Applying settings changed at runtime");
+            overrides.apply(&mut service_config);
+        }
+        if service_config.storage != storage {
+            warn!(
+                "Ignoring [storage] settings changed at runtime; they only apply from config.toml"
+            );
+            service_config.storage = storage;
+        }
+
+        if service_config.jobs.admin_accounts.is_empty() {
+            warn!("No jobs.admin_accounts configured; admin jobs are rejected");
+        }
+
+        let dispatcher = WebhookDispatcher::new(
+            &service_config.webhooks.retry,
+            DeadLetterQueue::open(state_store.clone(), &data_dir)?,
+            service_config.rpc.outbound_proxy.as_ref(),
+        )?;
+        let audit = match &service_config.audit {
+            Some(audit_config) => Some(Arc::new(AuditLog::open(&data_dir, audit_config)?)),
+            None => None,
+        };
+        let account_labels = Arc::new(AccountLabels::load(state_store.clone())?);
+        let account_roles = Arc::new(AccountRoles::load(
+            state_store.clone(),
+            &service_config.roles,
+        )?);
+        let read_only = Arc::new(ReadOnlyMode::load(
+            state_store.clone(),
+            &service_config.rpc.read_only_methods,
+        )?);
+        if let Some(status) = read_only.status() {
+            warn!(since = %status.since, reason = ?status.reason, "Gateway starting in read-only mode");
+        }
+        let event_history = match &service_config.event_history {
+            Some(history_config) => Some(Arc::new(EventHistory::load(
+                state_store.clone(),
+                history_config,
+            )?)),
+            None => None,
+        };
+        let daily_digest = service_config
+            .daily_digest
+            .as_ref()
+            .map(|config| Arc::new(DailyDigestCollector::new(config)));
+        let tasks = Arc::new(TaskRegistry::new());
+        let (access_events, access_event_dispatcher) =
+            AccessEvents::new(&service_config.webhooks.access_events);
         let firewall = Arc::new(Firewall::new(
             &service_config.firewall,
-            &service_config.webhooks.event_urls,
-        ));
+            &service_config.webhooks.targets(),
+            FirewallStore::new(state_store.clone()),
+            account_labels.clone(),
+            dispatcher,
+            access_events,
+            audit.clone(),
+            event_history.clone(),
+            daily_digest.clone(),
+        )?);
+        // Deliver the events access checks queue, off the request path
+        let firewall_clone = firewall.clone();
+        tasks.spawn("access-events", |shutdown| {
+            access_event_dispatcher.run(firewall_clone, shutdown)
+        });
+        if service_config.webhooks.replay_dead_letters_on_startup {
+            let replayed = firewall.replay_dead_letters()?;
+            info!(replayed, "Replaying dead-lettered webhook notifications");
+        }
+
+        // With a Redis server shared by several instances, pick up the rules and grants made
+        // through the others, and draw from shared rate-limit buckets if configured
+        let store_sync_interval = match &service_config.storage {
+            StorageConfig::Redis {
+                sync_interval_secs, ..
+            } if *sync_interval_secs > 0 => {
+                Some(std::time::Duration::from_secs(*sync_interval_secs))
+            }
+            _ => None,
+        };
+        if let Some(sync_period) = store_sync_interval {
+            let firewall_clone = firewall.clone();
+            tasks.spawn("store-sync", |shutdown| async move {
+                let mut sync_interval = interval(sync_period);
+                loop {
+                    tokio::select! {
+                        _ = shutdown.cancelled() => break,
+                        _ = sync_interval.tick() => {}
+                    }
+                    if let Err(e) = firewall_clone.sync_dynamic_state() {
+                        warn!(error = %e, "Failed to sync firewall state from the store");
+                    }
+                }
+            });
+        }
+        #[cfg(feature = "redis")]
+        let shared_rate_limits = match &service_config.storage {
+            StorageConfig::Redis {
+                url,
+                key_prefix,
+                shared_rate_limits: true,
+                ..
+            } => Some(Arc::new(
+                SharedRateLimits::connect(url, key_prefix.clone()).await?,
+            )),
+            _ => None,
+        };
+
+        let method_filter = Arc::new(MethodFilter::new(&service_config.methods));
+        let method_rewriter = Arc::new(MethodRewriter::new(&service_config.methods.rewrites));
+        let method_limits = Arc::new(MethodLimits::new(&service_config.limits));
+        let idempotency = Arc::new(IdempotencyStore::load(state_store.clone())?);
+        let processed_calls = Arc::new(ProcessedCalls::load(state_store.clone())?);
+        let signature_auth = Arc::new(SignatureVerifier::new(&service_config.auth));
+        let admin_nonces = Arc::new(AdminNonces::load(state_store.clone())?);
+        let api_keys = Arc::new(ApiKeyStore::load(state_store.clone())?);
+
+        let upstream_signer = if service_config.rpc.sign_upstream_requests {
+            let keystore = env.keystore();
+            let public = keystore
+                .first_local::<SpSr25519>()
+                .map_err(|e| Error::KeystoreError(e.to_string()))?;
+            let pair = keystore
+                .get_secret::<SpSr25519>(&public)
+                .map_err(|e| Error::KeystoreError(e.to_string()))?;
+            Some(Arc::new(UpstreamSigner::new(pair.0)))
+        } else {
+            None
+        };
+
+        let payment_verifier = match &service_config.payments {
+            Some(payments_config) => {
+                let operator = env
+                    .keystore()
+                    .first_local::<SpSr25519>()
+                    .map_err(|e| Error::KeystoreError(e.to_string()))?;
+                Some(Arc::new(PaymentVerifier::load(
+                    state_store.clone(),
+                    payments_config,
+                    AccountId32::from(operator.0),
+                )?))
+            }
+            None => None,
+        };
+
+        let plans = Arc::new(Plans::load(state_store.clone(), &service_config.plans)?);
+
+        // Submit admin API rule changes as `sync_rules` calls, signed by the operator's key
+        let rule_sync = match &service_config.sync {
+            Some(sync_config) => {
+                let keystore = env.keystore();
+                let public = keystore
+                    .first_local::<SpSr25519>()
+                    .map_err(|e| Error::KeystoreError(e.to_string()))?;
+                let pair = keystore
+                    .get_secret::<SpSr25519>(&public)
+                    .map_err(|e| Error::KeystoreError(e.to_string()))?;
+                let signer = TanglePairSigner::new(pair.0);
+                let rule_sync = Arc::new(RuleSync::load(state_store.clone(), sync_config)?);
+                let rule_sync_clone = rule_sync.clone();
+                let env_clone = env.clone();
+                tasks.spawn("rule-sync", |shutdown| {
+                    rule_sync_clone.run(env_clone, signer, shutdown)
+                });
+                Some(rule_sync)
+            }
+            None => None,
+        };
+
+        // Relayed transactions are signed by their own key, so the operator's key never pays
+        // for or signs anything a client asked for
+        let relay = match &service_config.relay {
+            Some(relay_config) => {
+                let keystore = env.keystore();
+                let operator = keystore
+                    .first_local::<SpSr25519>()
+                    .map_err(|e| Error::KeystoreError(e.to_string()))?;
+                if AccountId32::from(operator.0) == relay_config.signer {
+                    return Err(Error::KeystoreError(
+                        "relay.signer must not be the operator's key".to_string(),
+                    ));
+                }
+                let public = keystore
+                    .list_local::<SpSr25519>()
+                    .map_err(|e| Error::KeystoreError(e.to_string()))?
+                    .into_iter()
+                    .find(|public| AccountId32::from(public.0) == relay_config.signer)
+                    .ok_or_else(|| {
+                        Error::KeystoreError(format!(
+                            "No sr25519 key in the keystore for relay signer {}",
+                            relay_config.signer
+                        ))
+                    })?;
+                let pair = keystore
+                    .get_secret::<SpSr25519>(&public)
+                    .map_err(|e| Error::KeystoreError(e.to_string()))?;
+                info!(signer = %relay_config.signer, path = %relay_config.path, "Relaying transactions");
+                Some(Arc::new(TransactionRelay::new(
+                    relay_config,
+                    &service_config.rpc,
+                    pair.0,
+                    state_store.clone(),
+                )?))
+            }
+            None => None,
+        };
 
         // Start the cleanup task for expired temporary access
+        let cleanup_period =
+            std::time::Duration::from_secs(service_config.firewall.cleanup_interval_secs.max(1));
+        let firewall_clone = firewall.clone();
+        let idempotency_clone = idempotency.clone();
+        let signature_auth_clone = signature_auth.clone();
+        let fingerprints = Arc::new(FingerprintRegistry::new(&service_config.fingerprint));
+        let fingerprints_clone = fingerprints.clone();
+        let origin_heatmap = Arc::new(OriginHeatmap::new(&service_config.origin_heatmap));
+        let origin_heatmap_clone = origin_heatmap.clone();
+        let plans_clone = plans.clone();
+        tasks.spawn("cleanup", |shutdown| async move {
+            let mut cleanup_interval = interval(cleanup_period);
+            loop {
+                let stopping = tokio::select! {
+                    _ = shutdown.cancelled() => true,
+                    _ = cleanup_interval.tick() => false,
+                };
+                // Quota usage is written on every pass and at shutdown, so it survives restarts
+                if let Err(e) = plans_clone.persist() {
+                    warn!(error = %e, "Failed to persist plan quota usage");
+                }
+                if stopping {
+                    break;
+                }
+                let stats = firewall_clone.cleanup_expired_access().await;
+                if stats.expired > 0 {
+                    info!(
+                        scanned = stats.scanned,
+                        expired = stats.expired,
+                        "Expired temporary access cleaned up"
+                    );
+                } else {
+                    debug!(
+                        scanned = stats.scanned,
+                        "No expired temporary access to clean up"
+                    );
+                }
+                let warned = firewall_clone.notify_expiring_access().await;
+                if warned > 0 {
+                    info!(warned, "Sent expiry warnings for temporary access");
+                }
+                firewall_clone.prune_rate_limits();
+                firewall_clone.prune_decision_cache();
+                firewall_clone.prune_auto_bans();
+                firewall_clone.record_gauges();
+                idempotency_clone.prune();
+                signature_auth_clone.prune();
+                fingerprints_clone.prune();
+                origin_heatmap_clone.prune();
+                plans_clone.prune();
+            }
+        });
+
+        // Probe the backend's version, chain and methods at startup and then periodically.
+        // An expected chain ID needs the probe even without `[rpc.probe]`, but then only to
+        // verify the chain.
+        let probe_config = match (
+            &service_config.rpc.probe,
+            &service_config.rpc.expected_chain_id,
+        ) {
+            (Some(probe_config), _) => Some(probe_config.clone()),
+            (None, Some(_)) => Some(UpstreamProbeConfig {
+                refuse_unsupported_methods: false,
+                ..Default::default()
+            }),
+            (None, None) => None,
+        };
+        let upstream_probe = match probe_config {
+            Some(probe_config) => {
+                let probe = Arc::new(UpstreamProbe::new(&service_config.rpc, &probe_config)?);
+                let probe_clone = probe.clone();
+                let period = std::time::Duration::from_secs(probe_config.interval_secs.max(1));
+                tasks.spawn("upstream-probe", |shutdown| async move {
+                    probe_clone.run(period, shutdown).await;
+                });
+                Some(probe)
+            }
+            None => None,
+        };
+
+        // Check the backend and the state store for `/readyz`
+        let health = match &service_config.rpc.health {
+            Some(health_config) => {
+                let checker = Arc::new(HealthChecker::new(&service_config.rpc, health_config)?);
+                let checker_clone = checker.clone();
+                let store_clone = state_store.clone();
+                tasks.spawn("health-check", |shutdown| async move {
+                    checker_clone.run(store_clone, shutdown).await;
+                });
+                Some(checker)
+            }
+            None => None,
+        };
+
+        let grpc = match &service_config.rpc.grpc {
+            Some(grpc_config) => {
+                info!(upstream_url = %grpc_config.upstream_url, "Passing gRPC calls through");
+                Some(Arc::new(GrpcUpstream::new(
+                    &service_config.rpc,
+                    grpc_config,
+                )?))
+            }
+            None => None,
+        };
+
+        // Close usage periods and months for reporting and persist the counters, once more on
+        // shutdown
+        let usage_meter = match &service_config.metering {
+            Some(metering_config) => {
+                let meter = Arc::new(UsageMeter::load(
+                    state_store.clone(),
+                    &data_dir,
+                    metering_config,
+                )?);
+                let meter_clone = meter.clone();
+                let firewall_clone = firewall.clone();
+                let period =
+                    std::time::Duration::from_secs(metering_config.persist_interval_secs.max(1));
+                tasks.spawn("usage-metering", |shutdown| async move {
+                    let mut persist_interval = interval(period);
+                    loop {
+                        let stopping = tokio::select! {
+                            _ = shutdown.cancelled() => true,
+                            _ = persist_interval.tick() => false,
+                        };
+                        if meter_clone.close_period_if_due() {
+                            info!("Closed usage metering period");
+                        }
+                        if let Some(summary) = meter_clone.close_month_if_due() {
+                            info!(month = %summary.month, "Closed monthly usage summary");
+                            firewall_clone.report_monthly_usage(&summary).await;
+                        }
+                        if let Err(e) = meter_clone.persist() {
+                            warn!(error = %e, "Failed to persist usage counters");
+                        }
+                        if stopping {
+                            break;
+                        }
+                    }
+                });
+                Some(meter)
+            }
+            None => None,
+        };
+
+        // Report the slowest methods of each summary period
+        let latency = match &service_config.rpc.latency {
+            Some(latency_config) => {
+                let tracker = Arc::new(LatencyTracker::new(latency_config));
+                let tracker_clone = tracker.clone();
+                let firewall_clone = firewall.clone();
+                let period =
+                    std::time::Duration::from_secs(latency_config.summary_interval_secs.max(1));
+                tasks.spawn("latency-summary", |shutdown| async move {
+                    let mut summary_interval = interval(period);
+                    // The first tick completes immediately
+                    summary_interval.tick().await;
+                    loop {
+                        tokio::select! {
+                            _ = shutdown.cancelled() => break,
+                            _ = summary_interval.tick() => {}
+                        }
+                        if let Some(summary) = tracker_clone.close_period() {
+                            info!(
+                                methods = summary.methods.len(),
+                                "Closed latency summary period"
+                            );
+                            firewall_clone.report_latency_summary(summary).await;
+                        }
+                    }
+                });
+                Some(tracker)
+            }
+            None => None,
+        };
+
+        let access_log = match &service_config.access_log {
+            Some(access_log_config) => {
+                let (access_log, writer) = AccessLog::open(&data_dir, access_log_config)?;
+                tasks.spawn("access-log", |shutdown| writer.run(shutdown));
+                Some(Arc::new(access_log))
+            }
+            None => None,
+        };
+        let traffic_stats = service_config
+            .traffic_stats
+            .as_ref()
+            .map(|traffic_stats_config| Arc::new(TrafficStats::new(traffic_stats_config)));
+
+        // Write recorded firewall events to the state store in batches
+        if let Some(event_history) = event_history.clone() {
+            tasks.spawn("event-history", |shutdown| async move {
+                let mut flush_interval = interval(EVENT_HISTORY_FLUSH_INTERVAL);
+                loop {
+                    let stopping = tokio::select! {
+                        _ = shutdown.cancelled() => true,
+                        _ = flush_interval.tick() => false,
+                    };
+                    if let Err(e) = event_history.flush() {
+                        warn!(error = %e, "Failed to persist firewall event history");
+                    }
+                    if stopping {
+                        break;
+                    }
+                }
+            });
+        }
+
+        // Forward backend connectivity changes to webhooks
+        let upstream_events = Arc::new(UpstreamEvents::new());
+        let mut upstream_rx = upstream_events.subscribe();
         let firewall_clone = firewall.clone();
-        tokio::spawn(async move {
-            let mut cleanup_interval = interval(Duration::seconds(60).to_std().unwrap());
+        let probe_clone = upstream_probe.clone();
+        tasks.spawn("upstream-events", |shutdown| async move {
             loop {
-                cleanup_interval.tick().await;
-                firewall_clone.cleanup_expired_access();
+                let event = tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    event = upstream_rx.recv() => event,
+                };
+                match event {
+                    Ok(event) => {
+                        // The backend may come back as a different node; re-check its chain
+                        if let (
+                            Some(probe),
+                            UpstreamEvent::ConnectFailed { .. }
+                            | UpstreamEvent::HandshakeFailed { .. },
+                        ) = (&probe_clone, &event)
+                        {
+                            probe.backend_reconnecting();
+                        }
+                        firewall_clone.report_upstream_event(event).await
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!(
+                            missed,
+                            "Upstream event webhooks fell behind; events dropped"
+                        );
+                    }
+                    Err(RecvError::Closed) => break,
+                }
             }
         });
 
+        let sessions = Arc::new(SessionRegistry::new(&service_config.websocket));
+        let circuit_breaker = service_config
+            .rpc
+            .circuit_breaker
+            .as_ref()
+            .map(|config| Arc::new(CircuitBreaker::new(config)));
+
+        // Sample backend reachability and deliver each day's digest once it's over
+        if let Some(digest) = daily_digest.clone() {
+            let firewall_clone = firewall.clone();
+            let health_clone = health.clone();
+            let breaker_clone = circuit_breaker.clone();
+            tasks.spawn("daily-digest", |shutdown| async move {
+                let mut sample_interval = interval(DAILY_DIGEST_SAMPLE_INTERVAL);
+                loop {
+                    tokio::select! {
+                        _ = shutdown.cancelled() => break,
+                        _ = sample_interval.tick() => {}
+                    }
+                    if health_clone.is_some() || breaker_clone.is_some() {
+                        let circuit_open = breaker_clone
+                            .as_ref()
+                            .is_some_and(|breaker| breaker.state() == CircuitState::Open);
+                        let check_passed = health_clone
+                            .as_ref()
+                            .is_none_or(|health| health.backend_check_passed());
+                        digest.sample_upstream(!circuit_open && check_passed);
+                    }
+                    if let Some(summary) = digest.close_day_if_due(Utc::now()) {
+                        info!(day = %summary.day, requests = summary.requests, "Closed daily digest");
+                        firewall_clone.report_daily_digest(summary).await;
+                    }
+                }
+            });
+        }
+        let global_concurrency = service_config
+            .rpc
+            .global_concurrency
+            .as_ref()
+            .map(|config| Arc::new(ConcurrencyLimit::new(config)));
+        let upstream = Arc::new(Upstream::new(
+            &service_config.rpc,
+            upstream_events.clone(),
+            circuit_breaker.clone(),
+            global_concurrency.clone(),
+        )?);
+
+        // Follow the default backend's heads to answer polling clients without it
+        let head_cache = match &service_config.rpc.head_cache {
+            Some(head_cache_config) => {
+                let cache = Arc::new(HeadCache::new(head_cache_config));
+                let cache_clone = cache.clone();
+                let upstream_clone = upstream.clone();
+                tasks.spawn("head-cache", |shutdown| async move {
+                    cache_clone.run(&upstream_clone, shutdown).await;
+                });
+                Some(cache)
+            }
+            None => None,
+        };
+        let coalescer = service_config
+            .rpc
+            .coalescing
+            .as_ref()
+            .map(|config| Arc::new(RequestCoalescer::new(config)));
+        let default_route = Route {
+            prefix: None,
+            upstream: upstream.clone(),
+            method_filter: method_filter.clone(),
+            method_rewriter: method_rewriter.clone(),
+            method_limits: method_limits.clone(),
+            validator: service_config
+                .rpc
+                .validation
+                .as_ref()
+                .map(|config| Arc::new(RequestValidator::new(config))),
+            probe: upstream_probe.clone(),
+            head_cache: head_cache.clone(),
+            archive: match &service_config.rpc.archive {
+                Some(archive) => Some(Arc::new(ArchiveRoute::new(
+                    archive,
+                    &service_config.rpc,
+                    upstream_events.clone(),
+                    global_concurrency.clone(),
+                )?)),
+                None => None,
+            },
+            mirror: match &service_config.rpc.mirror {
+                Some(mirror) => Some(Arc::new(Mirror::new(mirror, &service_config.rpc)?)),
+                None => None,
+            },
+            websocket_bridge: service_config.rpc.websocket_bridge.clone(),
+        };
+        let routes = service_config
+            .routes
+            .iter()
+            .map(|route| {
+                Route::new(
+                    route,
+                    &service_config.rpc,
+                    &default_route,
+                    upstream_events.clone(),
+                    global_concurrency.clone(),
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let routes = Arc::new(RoutingTable::new(default_route, routes));
+
+        // Date calls against each fast node's head, for routes with an archive node
+        for archive in routes.routes().filter_map(|route| route.archive.clone()) {
+            tasks.spawn("archive-head", |shutdown| async move {
+                archive.run(shutdown).await;
+            });
+        }
+
+        // Drain backends during their scheduled maintenance windows, including windows added
+        // by a reload
+        let service_config = Arc::new(RwLock::new(Arc::new(service_config)));
+        let scheduler =
+            MaintenanceScheduler::new(service_config.clone(), routes.clone(), firewall.clone());
+        tasks.spawn("maintenance", |shutdown| scheduler.run(shutdown));
         Ok(Self {
             env,
             service_config,
+            runtime_config,
             data_dir,
+            state_store,
             firewall,
-            admin_pair: None,
+            account_labels,
+            account_roles,
+            read_only,
+            method_filter,
+            method_rewriter,
+            method_limits,
+            api_keys,
+            idempotency,
+            processed_calls,
+            signature_auth,
+            admin_nonces,
+            upstream_signer,
+            payment_verifier,
+            plans,
+            rule_sync,
+            relay,
+            tasks,
+            sessions,
+            in_flight: Arc::new(InFlightRequests::default()),
+            payload_filters: Arc::new(PayloadFilters::default()),
+            upstream_events,
+            circuit_breaker,
+            upstream,
+            global_concurrency,
+            routes,
+            upstream_probe,
+            health,
+            grpc,
+            head_cache,
+            coalescer,
+            usage_meter,
+            latency,
+            audit,
+            access_log,
+            traffic_stats,
+            event_history,
+            daily_digest,
+            fingerprints,
+            origin_heatmap,
+            #[cfg(feature = "redis")]
+            shared_rate_limits,
         })
     }
 
-    pub fn config(&self) -> &ServiceConfig {
-        &self.service_config
+    /// The current configuration. Hold on to the returned snapshot rather than calling this
+    /// repeatedly when settings must be consistent with each other.
+    pub fn config(&self) -> Arc<ServiceConfig> {
+        self.service_config.read().clone()
+    }
+
+    /// Switches to a re-read configuration: the firewall's config rules and rate limit are
+    /// replaced, a changed backend URL or credentials are used from now on, and per-request
+    /// settings take effect for new requests. Dynamic state (rules added by jobs, temporary
+    /// grants, bans, rate-limit buckets under unchanged limits, sessions) is kept.
+    pub fn apply_config(&self, config: ServiceConfig) -> Result<()> {
+        self.reconfigure(config, None)
+    }
+
+    /// Like [`apply_config`](Self::apply_config), and once `drain_after` has passed, asks
+    /// WebSocket sessions open to backends that are no longer configured to reconnect.
+    pub(crate) fn apply_config_draining(
+        &self,
+        config: ServiceConfig,
+        drain_after: std::time::Duration,
+    ) -> Result<()> {
+        self.reconfigure(config, Some(drain_after))
+    }
+
+    fn reconfigure(
+        &self,
+        config: ServiceConfig,
+        drain_after: Option<std::time::Duration>,
+    ) -> Result<()> {
+        let current = self.config();
+        if config.rpc.proxy_to_url != current.rpc.proxy_to_url
+            || config.rpc.upstreams != current.rpc.upstreams
+            || config.rpc.upstream_auth != current.rpc.upstream_auth
+        {
+            self.upstream.retarget(&config.rpc, drain_after)?;
+            if let Some(probe) = &self.upstream_probe {
+                probe.retarget(&config.rpc)?;
+            }
+            if let Some(health) = &self.health {
+                health.retarget(&config.rpc)?;
+            }
+            if let Some(head_cache) = &self.head_cache {
+                head_cache.resubscribe();
+            }
+        }
+        self.firewall.reload_static_rules(&config.firewall);
+        self.account_roles.reload(&config.roles);
+        self.read_only.reload(&config.rpc.read_only_methods);
+        *self.service_config.write() = Arc::new(config);
+        Ok(())
+    }
+
+    /// Stops all background tasks, waiting up to 10 seconds for them to finish.
+    pub async fn shutdown(&self) {
+        self.tasks
+            .shutdown(Duration::seconds(10).to_std().unwrap())
+            .await;
     }
 }
@@ -0,0 +1,129 @@
+use crate::config::{CorsConfig, RpcConfig};
+use axum::http::{HeaderName, HeaderValue, Method};
+use std::time::Duration;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer};
+
+/// The methods the gateway serves, allowed when `[rpc.cors]` isn't configured.
+const DEFAULT_METHODS: [Method; 4] = [Method::GET, Method::HEAD, Method::POST, Method::OPTIONS];
+
+/// Origin patterns from `rpc.cors.allowed_origins`. `*` matches any run of characters, so
+/// `https://*.example.com` matches every subdomain; matching ignores ASCII case, like hosts
+/// and schemes do.
+#[derive(Debug, Clone)]
+pub struct OriginPatterns {
+    patterns: Vec<String>,
+}
+
+impl OriginPatterns {
+    pub fn new(patterns: &[String]) -> Self {
+        OriginPatterns {
+            patterns: patterns
+                .iter()
+                .map(|pattern| pattern.to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Whether `origin` (e.g. `https://app.example.com`) matches any pattern.
+    pub fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+        self.patterns
+            .iter()
+            .any(|pattern| wildcard_match(pattern.as_bytes(), origin.as_bytes()))
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` matches any run of bytes, including none.
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    // Greedy matching, backtracking to the last `*` on a mismatch
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&byte) if byte == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&byte| byte == b'*')
+}
+
+/// Builds the gateway's CORS layer. Preflights are answered by it and never reach the
+/// backend. Without `[rpc.cors]` every origin and header is allowed, as before it existed.
+pub fn cors_layer(rpc: &RpcConfig) -> CorsLayer {
+    let Some(cors) = &rpc.cors else {
+        return CorsLayer::new()
+            .allow_methods(DEFAULT_METHODS)
+            .allow_origin(Any)
+            .allow_headers(Any)
+            .max_age(Duration::from_secs(rpc.cors_max_age_secs));
+    };
+    CorsLayer::new()
+        .allow_origin(allow_origin(cors))
+        .allow_methods(allow_methods(cors))
+        .allow_headers(allow_headers(cors))
+        .allow_credentials(cors.allow_credentials)
+        .max_age(Duration::from_secs(cors.max_age_secs))
+}
+
+fn allow_origin(cors: &CorsConfig) -> AllowOrigin {
+    if is_wildcard(&cors.allowed_origins) {
+        return AllowOrigin::any();
+    }
+    if !cors
+        .allowed_origins
+        .iter()
+        .any(|origin| origin.contains('*'))
+    {
+        // Exact origins only; checked at config load
+        return AllowOrigin::list(
+            cors.allowed_origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        );
+    }
+    let patterns = OriginPatterns::new(&cors.allowed_origins);
+    AllowOrigin::predicate(move |origin, _| {
+        origin.to_str().is_ok_and(|origin| patterns.matches(origin))
+    })
+}
+
+fn allow_methods(cors: &CorsConfig) -> AllowMethods {
+    if is_wildcard(&cors.allowed_methods) {
+        return AllowMethods::any();
+    }
+    AllowMethods::list(
+        cors.allowed_methods
+            .iter()
+            .filter_map(|method| Method::from_bytes(method.as_bytes()).ok()),
+    )
+}
+
+fn allow_headers(cors: &CorsConfig) -> AllowHeaders {
+    if is_wildcard(&cors.allowed_headers) {
+        return AllowHeaders::any();
+    }
+    AllowHeaders::list(
+        cors.allowed_headers
+            .iter()
+            .filter_map(|header| HeaderName::from_bytes(header.as_bytes()).ok()),
+    )
+}
+
+/// Whether a list is the `["*"]` wildcard, allowing anything.
+pub(crate) fn is_wildcard(values: &[String]) -> bool {
+    values.iter().any(|value| value == "*")
+}
@@ -0,0 +1,129 @@
+use crate::config::DecisionCacheConfig;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use sp_runtime::AccountId32;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+type DecisionKey = (IpAddr, Option<AccountId32>);
+
+/// Short-lived cache of firewall decisions per `(ip, account)` pair.
+///
+/// Every rule change invalidates the whole cache. Decisions are tagged with the generation
+/// they were evaluated in, so an evaluation racing with a rule change is never cached. Rules
+/// that lapse on their own, like expiring grants and closing access windows, aren't rule
+/// changes, so each decision is cached no longer than the rules it was made by stay as they
+/// are.
+#[derive(Debug)]
+pub struct DecisionCache {
+    ttl: Duration,
+    max_entries: usize,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    generation: u64,
+    entries: HashMap<DecisionKey, CachedDecision>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CachedDecision {
+    allowed: bool,
+    expires_at: Instant,
+}
+
+impl DecisionCache {
+    pub fn new(config: &DecisionCacheConfig) -> Self {
+        DecisionCache {
+            ttl: Duration::from_millis(config.ttl_ms),
+            max_entries: config.max_entries,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Returns the cached decision for a source, if still fresh.
+    pub fn get(&self, ip: IpAddr, account: Option<&AccountId32>) -> Option<bool> {
+        let key = (ip, account.cloned());
+        let state = self.state.lock();
+        state
+            .entries
+            .get(&key)
+            .filter(|decision| decision.expires_at > Instant::now())
+            .map(|decision| decision.allowed)
+    }
+
+    /// Current generation, to be read before evaluating a decision that will be cached.
+    pub fn generation(&self) -> u64 {
+        self.state.lock().generation
+    }
+
+    /// Caches a decision evaluated in `generation`, for the TTL or until `valid_until`, when
+    /// the rules for the source next change by themselves, if sooner. Dropped if the rules
+    /// changed since.
+    pub fn insert(
+        &self,
+        ip: IpAddr,
+        account: Option<&AccountId32>,
+        allowed: bool,
+        generation: u64,
+        valid_until: Option<DateTime<Utc>>,
+    ) {
+        let ttl = match valid_until {
+            Some(valid_until) => match (valid_until - Utc::now()).to_std() {
+                Ok(remaining) => remaining.min(self.ttl),
+                Err(_) => return,
+            },
+            None => self.ttl,
+        };
+        if ttl.is_zero() {
+            return;
+        }
+        let mut state = self.state.lock();
+        if state.generation != generation {
+            return;
+        }
+        let now = Instant::now();
+        if state.entries.len() >= self.max_entries {
+            state
+                .entries
+                .retain(|_, decision| decision.expires_at > now);
+            if state.entries.len() >= self.max_entries {
+                return;
+            }
+        }
+        state.entries.insert(
+            (ip, account.cloned()),
+            CachedDecision {
+                allowed,
+                expires_at: now + ttl,
+            },
+        );
+    }
+
+    /// Number of cached decisions, including expired ones not yet pruned.
+    pub fn len(&self) -> usize {
+        self.state.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every cached decision. Called whenever firewall rules change.
+    pub fn invalidate(&self) {
+        let mut state = self.state.lock();
+        state.generation = state.generation.wrapping_add(1);
+        state.entries.clear();
+    }
+
+    /// Drops expired decisions.
+    pub fn prune(&self) {
+        let now = Instant::now();
+        self.state
+            .lock()
+            .entries
+            .retain(|_, decision| decision.expires_at > now);
+    }
+}
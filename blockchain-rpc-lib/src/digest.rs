@@ -0,0 +1,179 @@
+use crate::config::DailyDigestConfig;
+use crate::firewall::{WebhookEvent, canonical_ip};
+use crate::metering::MethodCount;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+
+/// Distinct method names counted per day; calls to further methods aren't listed.
+const MAX_TRACKED_METHODS: usize = 1024;
+
+/// A day's traffic in aggregate, delivered as a `DailyDigest` webhook event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyDigest {
+    /// The UTC day, `YYYY-MM-DD`.
+    pub day: NaiveDate,
+    /// When counting started: midnight, or the gateway's start on the day it started.
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub requests: u64,
+    /// Distinct client IPs, counting up to `max_unique_ips`.
+    pub unique_ips: u64,
+    /// The most called JSON-RPC methods, most calls first.
+    pub top_methods: Vec<MethodCount>,
+    /// Requests rejected for invalid credentials, firewall rules or rate limits.
+    pub denials: u64,
+    /// Bans added, by the operator or automatically.
+    pub bans: u64,
+    /// Requests rejected because their plan's quota was used up.
+    pub quota_exhaustions: u64,
+    /// Share of the day the backend was reachable, in percent, as seen by `[rpc.health]`
+    /// checks and the circuit breaker. `None` if neither is configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_availability_percent: Option<f64>,
+}
+
+#[derive(Debug)]
+struct Day {
+    day: NaiveDate,
+    since: DateTime<Utc>,
+    requests: u64,
+    ips: HashSet<IpAddr>,
+    methods: HashMap<String, u64>,
+    denials: u64,
+    bans: u64,
+    quota_exhaustions: u64,
+    samples: u64,
+    reachable_samples: u64,
+}
+
+impl Day {
+    fn starting(now: DateTime<Utc>) -> Self {
+        Day {
+            day: now.date_naive(),
+            since: now,
+            requests: 0,
+            ips: HashSet::new(),
+            methods: HashMap::new(),
+            denials: 0,
+            bans: 0,
+            quota_exhaustions: 0,
+            samples: 0,
+            reachable_samples: 0,
+        }
+    }
+
+    fn summarize(self, until: DateTime<Utc>, top_methods: usize) -> DailyDigest {
+        let mut methods: Vec<MethodCount> = self
+            .methods
+            .into_iter()
+            .map(|(method, calls)| MethodCount { method, calls })
+            .collect();
+        methods.sort_by(|a, b| b.calls.cmp(&a.calls).then_with(|| a.method.cmp(&b.method)));
+        methods.truncate(top_methods);
+        DailyDigest {
+            day: self.day,
+            since: self.since,
+            until,
+            requests: self.requests,
+            unique_ips: self.ips.len() as u64,
+            top_methods: methods,
+            denials: self.denials,
+            bans: self.bans,
+            quota_exhaustions: self.quota_exhaustions,
+            upstream_availability_percent: (self.samples > 0)
+                .then(|| self.reachable_samples as f64 * 100.0 / self.samples as f64),
+        }
+    }
+}
+
+/// Collects the day's statistics for the `DailyDigest` webhook event: a single summary per
+/// UTC day for operators, instead of following the per-request events.
+///
+/// Counts are kept in memory only; the digest of a day the gateway restarted on covers the
+/// time since the restart.
+#[derive(Debug)]
+pub struct DailyDigestCollector {
+    top_methods: usize,
+    max_unique_ips: usize,
+    day: Mutex<Day>,
+}
+
+impl DailyDigestCollector {
+    pub fn new(config: &DailyDigestConfig) -> Self {
+        DailyDigestCollector {
+            top_methods: config.top_methods,
+            max_unique_ips: config.max_unique_ips,
+            day: Mutex::new(Day::starting(Utc::now())),
+        }
+    }
+
+    /// Counts a request from `ip`.
+    pub fn record_request(&self, ip: IpAddr) {
+        let mut day = self.day.lock();
+        day.requests += 1;
+        if day.ips.len() < self.max_unique_ips {
+            day.ips.insert(canonical_ip(ip));
+        }
+    }
+
+    /// Counts the JSON-RPC calls of a request or WebSocket message, by method.
+    pub fn record_calls(&self, methods: &[String]) {
+        if methods.is_empty() {
+            return;
+        }
+        let mut day = self.day.lock();
+        for method in methods {
+            match day.methods.get_mut(method) {
+                Some(calls) => *calls += 1,
+                None if day.methods.len() < MAX_TRACKED_METHODS => {
+                    day.methods.insert(method.clone(), 1);
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Counts a rejected request.
+    pub fn record_denial(&self) {
+        self.day.lock().denials += 1;
+    }
+
+    /// Counts a request rejected for its plan's exhausted quota.
+    pub fn record_quota_exhausted(&self) {
+        self.day.lock().quota_exhaustions += 1;
+    }
+
+    /// Counts the bans among firewall events: deny rules added and automatic bans.
+    pub fn observe(&self, event: &WebhookEvent) {
+        let ban = match event {
+            WebhookEvent::RuleAdded { rule_type, .. } => rule_type.starts_with("Deny"),
+            WebhookEvent::AutoBanned { .. } => true,
+            _ => false,
+        };
+        if ban {
+            self.day.lock().bans += 1;
+        }
+    }
+
+    /// Records whether the backend is reachable right now, sampled at a fixed interval.
+    pub fn sample_upstream(&self, reachable: bool) {
+        let mut day = self.day.lock();
+        day.samples += 1;
+        day.reachable_samples += u64::from(reachable);
+    }
+
+    /// Closes the day if `now` is past it, returning its digest. Counting continues from the
+    /// start of `now`'s day.
+    pub fn close_day_if_due(&self, now: DateTime<Utc>) -> Option<DailyDigest> {
+        let mut day = self.day.lock();
+        if now.date_naive() <= day.day {
+            return None;
+        }
+        let midnight = now.date_naive().and_time(NaiveTime::MIN).and_utc();
+        let closed = std::mem::replace(&mut *day, Day::starting(midnight));
+        Some(closed.summarize(midnight, self.top_methods))
+    }
+}
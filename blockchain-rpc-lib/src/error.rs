@@ -33,6 +33,13 @@ pub enum Error {
     #[error("Axum error: {0}")]
     AxumError(#[from] axum::Error),
 
+    #[error("Storage error: {0}")]
+    StorageError(#[from] sled::Error),
+
+    #[cfg(feature = "redis")]
+    #[error("Redis error: {0}")]
+    RedisError(#[from] redis::RedisError),
+
     #[error("Invalid IP address or CIDR: {0}")]
     InvalidIpNetwork(#[from] ipnetwork::IpNetworkError),
 
@@ -45,9 +52,63 @@ pub enum Error {
     #[error("Access denied for Account: {0}")]
     AccessDeniedAccount(sp_runtime::AccountId32),
 
+    #[error("Admin access denied: {0}")]
+    AccessDeniedAdmin(String),
+
     #[error("Webhook sending failed: {0}")]
     WebhookFailed(String),
 
     #[error("Invalid job input: {0}")]
     InvalidJobInput(String),
+
+    #[error("Keystore error: {0}")]
+    KeystoreError(String),
+
+    #[error("Invalid request signature: {0}")]
+    InvalidSignature(String),
+
+    #[error("Rule limit reached: {0}")]
+    RuleLimitReached(String),
+
+    #[error("Telemetry error: {0}")]
+    TelemetryError(String),
+
+    #[error("TLS error: {0}")]
+    TlsError(String),
+
+    #[error("GeoIP error: {0}")]
+    GeoIpError(String),
+
+    #[error("Configuration error: {0}")]
+    ConfigError(#[from] ::config::ConfigError),
+
+    #[error(
+        "Invalid configuration:{}",
+        .0.iter().map(|problem| format!("\n  - {problem}")).collect::<String>()
+    )]
+    InvalidConfig(Vec<crate::config::ConfigProblem>),
+
+    #[error("Config reload rejected: {0}")]
+    ConfigReloadError(String),
+
+    #[error("Payment verification failed: {0}")]
+    PaymentVerificationFailed(String),
+
+    #[error("Rule synchronization failed: {0}")]
+    RuleSyncFailed(String),
+
+    #[error("Invalid account labels: {0}")]
+    InvalidLabels(String),
+
+    #[error("Invalid account roles: {0}")]
+    InvalidRoles(String),
+
+    #[error("Gateway request failed with status {status}: {message}")]
+    GatewayRequestFailed { status: u16, message: String },
+
+    #[error("JSON-RPC error: {0}")]
+    JsonRpcError(String),
+
+    #[error("Transaction relay failed: {0}")]
+    RelayFailed(String),
 }
@@ -0,0 +1,210 @@
+use crate::Result;
+use crate::config::EventHistoryConfig;
+use crate::error::Error;
+use crate::firewall::WebhookEvent;
+use crate::store::StateStore;
+use crate::webhooks::WebhookEventType;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+const EVENTS_NAMESPACE: &str = "firewall_events";
+
+/// Events returned per page when the query doesn't say.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// A firewall event as it was reported to webhooks, with when it happened.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoricalEvent {
+    /// Increases with every recorded event; the cursor for paging.
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub event_type: WebhookEventType,
+    pub event: WebhookEvent,
+}
+
+/// Which recorded events to return, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct EventQuery {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Event types to include; all if empty.
+    pub types: HashSet<WebhookEventType>,
+    /// Only events after this `seq`, i.e. the previous page's `next_cursor`.
+    pub after: Option<u64>,
+    pub limit: usize,
+}
+
+/// A page of matching events.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventPage {
+    pub events: Vec<HistoricalEvent>,
+    /// Pass as `after` to get the next page; absent on the last one.
+    pub next_cursor: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct HistoryState {
+    events: VecDeque<HistoricalEvent>,
+    next_seq: u64,
+    /// Recorded events not yet written to the store.
+    unflushed: Vec<HistoricalEvent>,
+    /// Dropped events still to be removed from the store.
+    dropped: Vec<u64>,
+}
+
+/// Recent firewall events (the same ones webhooks receive: grants, denials, bans, rule
+/// changes, ...), kept for `retention_secs` up to `max_events`, so operators can look back at
+/// what happened without external log tooling.
+///
+/// Events are recorded in memory and written to the state store in batches by
+/// [`EventHistory::flush`], so recording never waits on the store; events recorded since the
+/// last flush are lost on a crash.
+#[derive(Debug)]
+pub struct EventHistory {
+    store: Arc<dyn StateStore>,
+    max_events: usize,
+    retention: chrono::Duration,
+    types: HashSet<WebhookEventType>,
+    state: Mutex<HistoryState>,
+}
+
+impl EventHistory {
+    /// Loads the events persisted in `store`, dropping those past retention. Entries that fail
+    /// to decode are skipped with a warning.
+    pub fn load(store: Arc<dyn StateStore>, config: &EventHistoryConfig) -> Result<Self> {
+        let mut events = Vec::new();
+        for (key, value) in store.entries(EVENTS_NAMESPACE)? {
+            match serde_json::from_slice::<HistoricalEvent>(&value) {
+                Ok(event) => events.push(event),
+                Err(e) => warn!(key = ?key, error = %e, "Skipping undecodable persisted event"),
+            }
+        }
+        events.sort_by_key(|event| event.seq);
+        let next_seq = events.last().map_or(0, |event| event.seq + 1);
+        let history = EventHistory {
+            store,
+            max_events: config.max_events.max(1),
+            retention: chrono::Duration::try_seconds(
+                i64::try_from(config.retention_secs).unwrap_or(i64::MAX),
+            )
+            .unwrap_or(chrono::Duration::MAX),
+            types: config.events.clone(),
+            state: Mutex::new(HistoryState {
+                events: events.into(),
+                next_seq,
+                ..HistoryState::default()
+            }),
+        };
+        history.expire();
+        debug!(
+            events = history.state.lock().events.len(),
+            "Restored firewall event history"
+        );
+        Ok(history)
+    }
+
+    /// Records `event`, unless its type isn't kept.
+    pub fn record(&self, event: &WebhookEvent) {
+        let event_type = event.event_type();
+        if !self.types.is_empty() && !self.types.contains(&event_type) {
+            return;
+        }
+        let mut state = self.state.lock();
+        let event = HistoricalEvent {
+            seq: state.next_seq,
+            timestamp: Utc::now(),
+            event_type,
+            event: event.clone(),
+        };
+        state.next_seq += 1;
+        state.unflushed.push(event.clone());
+        state.events.push_back(event);
+        while state.events.len() > self.max_events {
+            if let Some(dropped) = state.events.pop_front() {
+                state.dropped.push(dropped.seq);
+            }
+        }
+    }
+
+    /// Events matching `query`, oldest first.
+    pub fn query(&self, query: &EventQuery) -> EventPage {
+        let limit = query.limit.clamp(1, MAX_PAGE_SIZE);
+        let state = self.state.lock();
+        let mut matching = state.events.iter().filter(|event| {
+            query.after.is_none_or(|after| event.seq > after)
+                && query.since.is_none_or(|since| event.timestamp >= since)
+                && query.until.is_none_or(|until| event.timestamp <= until)
+                && (query.types.is_empty() || query.types.contains(&event.event_type))
+        });
+        let events: Vec<HistoricalEvent> = matching.by_ref().take(limit).cloned().collect();
+        let next_cursor = matching.next().and(events.last()).map(|event| event.seq);
+        EventPage {
+            events,
+            next_cursor,
+        }
+    }
+
+    /// Drops events past retention, then writes recorded events to the store and removes
+    /// dropped ones from it. Events that fail to be written are retried on the next flush.
+    pub fn flush(&self) -> Result<()> {
+        self.expire();
+        let (unflushed, dropped) = {
+            let mut state = self.state.lock();
+            (
+                std::mem::take(&mut state.unflushed),
+                std::mem::take(&mut state.dropped),
+            )
+        };
+        let dropped_set: HashSet<u64> = dropped.iter().copied().collect();
+        let mut result = Ok(());
+        let mut failed = Vec::new();
+        for event in unflushed {
+            if dropped_set.contains(&event.seq) {
+                continue;
+            }
+            let written = serde_json::to_vec(&event)
+                .map_err(Error::from)
+                .and_then(|value| {
+                    self.store
+                        .insert(EVENTS_NAMESPACE, &event.seq.to_be_bytes(), &value)
+                });
+            if let Err(e) = written {
+                failed.push(event);
+                result = Err(e);
+            }
+        }
+        for seq in dropped {
+            if let Err(e) = self.store.remove(EVENTS_NAMESPACE, &seq.to_be_bytes()) {
+                self.state.lock().dropped.push(seq);
+                result = Err(e);
+            }
+        }
+        if !failed.is_empty() {
+            let mut state = self.state.lock();
+            failed.append(&mut state.unflushed);
+            state.unflushed = failed;
+        }
+        result
+    }
+
+    /// Moves events past retention to the dropped list.
+    fn expire(&self) {
+        let cutoff = Utc::now()
+            .checked_sub_signed(self.retention)
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+        let mut state = self.state.lock();
+        while let Some(oldest) = state.events.front() {
+            if oldest.timestamp >= cutoff && state.events.len() <= self.max_events {
+                break;
+            }
+            if let Some(dropped) = state.events.pop_front() {
+                state.dropped.push(dropped.seq);
+            }
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::config::FingerprintConfig;
+use axum::http::HeaderMap;
+use axum::http::header::{ACCEPT_ENCODING, ACCEPT_LANGUAGE, USER_AGENT};
+use chrono::{DateTime, Utc};
+use md5::Md5;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Distinct IPs remembered per fingerprint. Further IPs are still counted as requests.
+const MAX_IPS_PER_FINGERPRINT: usize = 256;
+
+/// A coarse identifier of the client software behind a request, used to correlate abuse
+/// across rotating IPs. Derived from the TLS ClientHello (JA3) when the gateway terminates
+/// TLS, and from header order and a few client-identifying header values otherwise. Request
+/// payloads are never part of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientFingerprint(String);
+
+impl ClientFingerprint {
+    pub fn new(ja3: Option<&str>, headers: &HeaderMap) -> Self {
+        match ja3 {
+            Some(ja3) => ClientFingerprint(format!("ja3:{ja3}")),
+            None => ClientFingerprint(format!("hdr:{}", header_fingerprint(headers))),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// SHA-256 over the header names in received order plus the `User-Agent`, `Accept-Language`
+/// and `Accept-Encoding` values, truncated to 16 hex characters.
+fn header_fingerprint(headers: &HeaderMap) -> String {
+    let mut hasher = Sha256::new();
+    for name in headers.keys() {
+        hasher.update(name.as_str().as_bytes());
+        hasher.update(b",");
+    }
+    for name in [USER_AGENT, ACCEPT_LANGUAGE, ACCEPT_ENCODING] {
+        hasher.update(b"|");
+        if let Some(value) = headers.get(name) {
+            hasher.update(value.as_bytes());
+        }
+    }
+    hex::encode(&hasher.finalize()[..8])
+}
+
+/// Computes the JA3 hash of a TLS record holding a ClientHello: the MD5 of
+/// `version,ciphers,extensions,groups,point_formats`, with GREASE values left out.
+/// Returns `None` if the record isn't a complete ClientHello.
+pub fn ja3_fingerprint(record: &[u8]) -> Option<String> {
+    let mut record = Reader(record);
+    if record.u8()? != 0x16 {
+        return None;
+    }
+    record.take(2)?; // record-layer version
+    let mut handshake = record.vec_u16()?;
+    if handshake.u8()? != 0x01 {
+        return None;
+    }
+    let hello_len = handshake.u24()?;
+    let mut hello = Reader(handshake.take(hello_len)?);
+
+    let version = hello.u16()?;
+    hello.take(32)?; // random
+    hello.vec_u8()?; // session id
+    let mut cipher_list = hello.vec_u16()?;
+    let mut ciphers = Vec::new();
+    while let Some(cipher) = cipher_list.u16() {
+        if !is_grease(cipher) {
+            ciphers.push(cipher);
+        }
+    }
+    hello.vec_u8()?; // compression methods
+
+    let mut extensions = Vec::new();
+    let mut groups = Vec::new();
+    let mut point_formats = Vec::new();
+    if let Some(mut extension_list) = hello.vec_u16() {
+        while let Some(extension) = extension_list.u16() {
+            let mut data = extension_list.vec_u16()?;
+            if is_grease(extension) {
+                continue;
+            }
+            extensions.push(extension);
+            match extension {
+                // supported_groups
+                10 => {
+                    let mut list = data.vec_u16()?;
+                    while let Some(group) = list.u16() {
+                        if !is_grease(group) {
+                            groups.push(group);
+                        }
+                    }
+                }
+                // ec_point_formats
+                11 => {
+                    let mut list = data.vec_u8()?;
+                    while let Some(format) = list.u8() {
+                        point_formats.push(u16::from(format));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let ja3 = format!(
+        "{},{},{},{},{}",
+        version,
+        join(&ciphers),
+        join(&extensions),
+        join(&groups),
+        join(&point_formats)
+    );
+    Some(hex::encode(Md5::digest(ja3.as_bytes())))
+}
+
+fn join(values: &[u16]) -> String {
+    values
+        .iter()
+        .map(u16::to_string)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// GREASE values (RFC 8701) are random per connection and excluded from JA3.
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+/// Big-endian reader over TLS wire data.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u24(&mut self) -> Option<usize> {
+        self.take(3)
+            .map(|b| (usize::from(b[0]) << 16) | (usize::from(b[1]) << 8) | usize::from(b[2]))
+    }
+
+    fn vec_u8(&mut self) -> Option<Reader<'a>> {
+        let len = usize::from(self.u8()?);
+        self.take(len).map(Reader)
+    }
+
+    fn vec_u16(&mut self) -> Option<Reader<'a>> {
+        let len = usize::from(self.u16()?);
+        self.take(len).map(Reader)
+    }
+}
+
+/// Activity seen for one fingerprint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FingerprintStats {
+    pub fingerprint: String,
+    /// Distinct client IPs seen with this fingerprint (capped at 256).
+    pub distinct_ips: usize,
+    pub requests: u64,
+    /// Requests rejected for invalid credentials, firewall rules or rate limits.
+    pub rejected: u64,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    ips: HashSet<IpAddr>,
+    requests: u64,
+    rejected: u64,
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+}
+
+/// Aggregates requests per client fingerprint, so one client spreading requests over many
+/// IPs stands out. Only fingerprints, IPs and counters are kept.
+#[derive(Debug)]
+pub struct FingerprintRegistry {
+    max_entries: usize,
+    idle_ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl FingerprintRegistry {
+    pub fn new(config: &FingerprintConfig) -> Self {
+        FingerprintRegistry {
+            max_entries: config.max_tracked,
+            idle_ttl: Duration::from_secs(config.idle_ttl_secs),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a request from `ip`. New fingerprints are ignored while the registry is full.
+    pub fn record(&self, fingerprint: &ClientFingerprint, ip: IpAddr) {
+        let now = Utc::now();
+        let mut entries = self.entries.lock();
+        if !entries.contains_key(fingerprint.as_str()) && entries.len() >= self.max_entries {
+            return;
+        }
+        let entry = entries
+            .entry(fingerprint.as_str().to_string())
+            .or_insert_with(|| Entry {
+                ips: HashSet::new(),
+                requests: 0,
+                rejected: 0,
+                first_seen: now,
+                last_seen: now,
+            });
+        entry.requests += 1;
+        entry.last_seen = now;
+        if entry.ips.len() < MAX_IPS_PER_FINGERPRINT {
+            entry.ips.insert(ip);
+        }
+    }
+
+    /// Records that a request with this fingerprint was rejected.
+    pub fn record_rejection(&self, fingerprint: &ClientFingerprint) {
+        if let Some(entry) = self.entries.lock().get_mut(fingerprint.as_str()) {
+            entry.rejected += 1;
+        }
+    }
+
+    /// Fingerprints seen from at least `min_ips` distinct IPs, most widespread first.
+    pub fn list(&self, min_ips: usize) -> Vec<FingerprintStats> {
+        let mut stats: Vec<FingerprintStats> = self
+            .entries
+            .lock()
+            .iter()
+            .filter(|(_, entry)| entry.ips.len() >= min_ips)
+            .map(|(fingerprint, entry)| FingerprintStats {
+                fingerprint: fingerprint.clone(),
+                distinct_ips: entry.ips.len(),
+                requests: entry.requests,
+                rejected: entry.rejected,
+                first_seen: entry.first_seen,
+                last_seen: entry.last_seen,
+            })
+            .collect();
+        stats.sort_by(|a, b| {
+            b.distinct_ips
+                .cmp(&a.distinct_ips)
+                .then(b.requests.cmp(&a.requests))
+        });
+        stats
+    }
+
+    /// Drops fingerprints not seen within the idle TTL.
+    pub fn prune(&self) {
+        let idle_ttl = chrono::Duration::from_std(self.idle_ttl).unwrap_or(chrono::Duration::MAX);
+        let cutoff = Utc::now()
+            .checked_sub_signed(idle_ttl)
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+        self.entries
+            .lock()
+            .retain(|_, entry| entry.last_seen >= cutoff);
+    }
+}
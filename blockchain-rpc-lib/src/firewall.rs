@@ -1,33 +1,181 @@
 use crate::Result;
-use crate::config::FirewallConfig;
+use crate::access_events::AccessEvents;
+use crate::access_windows::{self, AccessWindow};
+use crate::audit::AuditLog;
+use crate::auto_ban::{AutoBan, AutoBanner, Offense};
+use crate::config::{
+    BurstLoanConfig, DecisionBudgetConfig, FailPolicy, FirewallConfig, IdentityCombination,
+    MaintenanceWindow, RateLimitConfig, RuleLimit, RuleLimitsConfig,
+};
 use crate::context::TemporaryAccessRecord;
+use crate::decision_cache::DecisionCache;
+use crate::digest::{DailyDigest, DailyDigestCollector};
 use crate::error::Error;
+use crate::event_history::EventHistory;
+use crate::geoip::GeoIp;
+use crate::history::{AccessHistory, AccessHistoryEntry, AccessHistoryEvent};
+use crate::labels::AccountLabels;
+use crate::latency::{LatencySummary, MethodLatency};
+use crate::metering::{MethodCount, MonthlySummary, UsageCounters};
+use crate::ratelimit::RateLimiter;
+use crate::shadow_policy::{ProposedPolicy, ShadowPolicy, ShadowReport};
+use crate::store::FirewallStore;
+use crate::switchover::UpstreamSwitch;
+use crate::telemetry::FirewallGauges;
+use crate::upstream_events::{UpstreamEvent, UpstreamTransport};
+use crate::webhooks::{WebhookDispatcher, WebhookEventType, WebhookPayload, WebhookTarget};
 use chrono::{DateTime, Utc};
-use ipnetwork::IpNetwork;
+use ipnetwork::{IpNetwork, Ipv4Network};
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use sp_runtime::AccountId32;
 use std::collections::{HashMap, HashSet};
 use std::net::IpAddr;
+use std::str::FromStr;
 use std::sync::Arc;
-use tracing::debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
 use url::Url;
 
 #[derive(Debug, Clone)]
 pub struct Firewall {
-    // Permanent allow lists from config
-    allow_ips_config: HashSet<IpNetwork>,
-    allow_accounts_config: HashSet<AccountId32>,
-    allow_unrestricted_access: bool,
+    // Allow and deny lists from config, replaced wholesale on config reload
+    static_rules: Arc<RwLock<Arc<StaticRules>>>,
+
+    // Deny lists, taking precedence over every allow rule
+    deny_ips_dynamic: Arc<RwLock<HashSet<IpNetwork>>>,
+    deny_accounts_dynamic: Arc<RwLock<HashSet<AccountId32>>>,
 
-    // Dynamic allow lists managed by jobs
-    allow_ips_dynamic: Arc<RwLock<HashSet<IpNetwork>>>,
-    allow_accounts_dynamic: Arc<RwLock<HashSet<AccountId32>>>,
+    // Dynamic allow lists managed by jobs, each rule with the windows it is restricted to
+    allow_ips_dynamic: Arc<RwLock<HashMap<IpNetwork, Vec<AccessWindow>>>>,
+    allow_accounts_dynamic: Arc<RwLock<HashMap<AccountId32, Vec<AccessWindow>>>>,
     temporary_access: Arc<RwLock<HashMap<AccountId32, TemporaryAccessRecord>>>,
+    temporary_ips: Arc<RwLock<HashMap<IpNetwork, TemporaryAccessRecord>>>,
+
+    // Write-through persistence for the dynamic state above
+    store: FirewallStore,
+
+    // Per-account timeline of grants and expiries
+    history: Arc<AccessHistory>,
+
+    // Operator-assigned labels, attached to webhook events about an account
+    labels: Arc<AccountLabels>,
+
+    // Country lookups for the country rules, if a GeoIP database is configured
+    geoip: Option<Arc<GeoIp>>,
+
+    // Per-IP rate limiting, if configured; replaced when the limits change
+    rate_limiter: Arc<RwLock<Option<Arc<RateLimiter>>>>,
+
+    // Rate-limit debt allowed to accounts with long-lived paid access, if configured
+    burst_loans: Option<BurstLoanConfig>,
+
+    // Recent allow/deny decisions per source, if configured
+    decision_cache: Option<Arc<DecisionCache>>,
+
+    // Temporary bans for repeat offenders, if configured
+    auto_ban: Option<Arc<AutoBanner>>,
+
+    // Bumped on every rule or grant change, so open sessions re-check their access
+    rule_changes: Arc<watch::Sender<u64>>,
+
+    // Proposed policy evaluated alongside the active one, if any
+    shadow: Arc<RwLock<Option<Arc<ShadowPolicy>>>>,
+
+    // Number of temporary grants removed by cleanup
+    expired_total: Arc<AtomicU64>,
+
+    // Number of WebSocket sessions closed for breaking a message limit
+    websocket_violations_total: Arc<AtomicU64>,
+
+    // Access decisions that ran over their time budget, by `AccessSurface`
+    budget_breaches: Arc<[AtomicU64; 3]>,
+
+    // Lead time for expiry warnings, and the expiry each grant was last warned about
+    expiry_warning: Option<Duration>,
+    expiry_warned: Arc<RwLock<HashMap<String, DateTime<Utc>>>>,
+
+    // Ceilings on dynamic state
+    limits: RuleLimitsConfig,
+
+    // Audit trail of every event below, if configured
+    audit: Option<Arc<AuditLog>>,
+
+    // Queryable history of recent events below, if configured
+    event_history: Option<Arc<EventHistory>>,
+
+    // Daily statistics, counting the bans among the events below, if configured
+    daily_digest: Option<Arc<DailyDigestCollector>>,
 
     // Webhooks for notifications
-    webhooks: Arc<RwLock<Vec<Url>>>,
-    http_client: reqwest::Client,
+    webhooks: Arc<RwLock<Vec<WebhookTarget>>>,
+    dispatcher: WebhookDispatcher,
+
+    // Events raised by access checks, delivered off the request path
+    access_events: AccessEvents,
+
+    // The sizes in `FirewallStats`, exported as metrics
+    gauges: FirewallGauges,
+}
+
+/// The rules read from `[firewall]` in the config file.
+#[derive(Debug)]
+struct StaticRules {
+    allow_ips: HashSet<IpNetwork>,
+    allow_accounts: HashSet<AccountId32>,
+    allow_unrestricted_access: bool,
+    deny_ips: HashSet<IpNetwork>,
+    deny_accounts: HashSet<AccountId32>,
+    allow_countries: HashSet<String>,
+    deny_countries: HashSet<String>,
+    combine: IdentityCombination,
+    decision_budget: Option<DecisionBudgetConfig>,
+}
+
+impl StaticRules {
+    fn new(config: &FirewallConfig) -> Self {
+        StaticRules {
+            allow_ips: config.allow_ips.clone(),
+            allow_accounts: config.allow_accounts.clone(),
+            allow_unrestricted_access: config.allow_unrestricted_access,
+            deny_ips: config.deny_ips.clone(),
+            deny_accounts: config.deny_accounts.clone(),
+            allow_countries: config.allow_countries.clone(),
+            deny_countries: config.deny_countries.clone(),
+            combine: config.identity.combine,
+            decision_budget: config.decision_budget.clone(),
+        }
+    }
+}
+
+/// Where an access decision is made, each with its own fail policy under
+/// `[firewall.decision_budget]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessSurface {
+    Http,
+    /// A WebSocket upgrade request.
+    WebSocket,
+    /// An open WebSocket session re-checked after a rule change.
+    SessionRecheck,
+}
+
+/// The outcome of [`Firewall::check_access`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessDecision {
+    Allowed,
+    Denied,
+    /// The decision ran over its time budget and the surface fails closed.
+    Unavailable,
+}
+
+/// Access decisions that ran over `[firewall.decision_budget]` since startup, by surface.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BudgetBreaches {
+    pub http: u64,
+    pub websocket: u64,
+    pub session_recheck: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -38,120 +186,564 @@ pub enum WebhookEvent {
     },
     AccessDenied {
         source: String, // IP
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        country: Option<String>, // ISO 3166-1 alpha-2, with a GeoIP database
+    },
+    Banned {
+        source: String, // IP or AccountId
     },
     TemporaryAccessExpired {
         account: AccountId32,
     },
+    TemporaryIpAccessExpired {
+        ip: String, // IP or CIDR
+    },
+    AccessExpiringSoon {
+        source: String, // AccountId, IP or CIDR
+        expires_at: DateTime<Utc>,
+    },
     RuleAdded {
-        rule_type: String, // "IP", "Account"
+        rule_type: String, // "IP", "Account", "DenyIP", "DenyAccount"
+        value: String,
+    },
+    RuleRemoved {
+        rule_type: String, // "IP", "Account", "AutoBan"
         value: String,
     },
     WebhookRegistered {
         url: Url,
     },
+    WebhookRemoved {
+        url: Url,
+    },
+    RateLimited {
+        source: String, // IP
+        retry_after_secs: u64,
+    },
+    RuleLimitExceeded {
+        rule_type: String, // "IP", "Account", "TemporaryGrant", "Webhook", "Ban"
+        count: usize,
+        warn_at: usize,
+    },
+    ConnectionLimitExceeded {
+        source: String,     // IP or AccountId
+        limit_type: String, // "IP", "Account"
+        max: usize,
+    },
+    WebSocketLimitExceeded {
+        source: String,     // IP
+        limit_type: String, // "MessageRate", "FrameSize"
+        limit: String,      // "20 messages/s, burst 40", "1048576 bytes"
+    },
+    UpstreamConnected {
+        transport: UpstreamTransport,
+        target: String,
+    },
+    UpstreamDisconnected {
+        transport: UpstreamTransport,
+        target: String,
+        duration_secs: u64,
+    },
+    UpstreamConnectFailed {
+        transport: UpstreamTransport,
+        target: String,
+        error: String,
+    },
+    UpstreamHandshakeFailed {
+        transport: UpstreamTransport,
+        target: String,
+        error: String,
+    },
+    MonthlyUsageSummary {
+        account: String, // AccountId
+        month: String,   // "YYYY-MM"
+        usage: UsageCounters,
+        top_methods: Vec<MethodCount>,
+    },
+    ConfigUpdated {
+        settings: Vec<String>, // "rpc.request_timeout_secs = 60"
+    },
+    LatencySummary {
+        period_start: DateTime<Utc>,
+        period_end: DateTime<Utc>,
+        methods: Vec<MethodLatency>, // Slowest on average first
+    },
+    AutoBanned {
+        source: String, // IP or CIDR
+        offense: Offense,
+        offenses: u32,
+        ban_secs: u64,
+        expires_at: DateTime<Utc>,
+    },
+    QuotaThresholdReached {
+        source: String, // AccountId, IP or CIDR holding the grant
+        plan: String,
+        percent: u8,
+        used: u64,
+        quota: u64,
+    },
+    MaintenanceStarting {
+        backend: Url,
+        starts_at: DateTime<Utc>,
+        ends_at: DateTime<Utc>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    MaintenanceEnded {
+        backend: Url,
+        starts_at: DateTime<Utc>,
+        ends_at: DateTime<Utc>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    UpstreamSwitched {
+        old_targets: Vec<Url>, // proxy_to_url first
+        new_targets: Vec<Url>,
+        drain_secs: u64,
+        switched_by: String, // Admin account, token or "open"
+    },
+    DailyDigest(DailyDigest),
+}
+
+impl WebhookEvent {
+    pub fn event_type(&self) -> WebhookEventType {
+        match self {
+            WebhookEvent::AccessGranted { .. } => WebhookEventType::AccessGranted,
+            WebhookEvent::AccessDenied { .. } => WebhookEventType::AccessDenied,
+            WebhookEvent::Banned { .. } => WebhookEventType::Banned,
+            WebhookEvent::TemporaryAccessExpired { .. } => WebhookEventType::TemporaryAccessExpired,
+            WebhookEvent::TemporaryIpAccessExpired { .. } => {
+                WebhookEventType::TemporaryIpAccessExpired
+            }
+            WebhookEvent::AccessExpiringSoon { .. } => WebhookEventType::AccessExpiringSoon,
+            WebhookEvent::RuleAdded { .. } => WebhookEventType::RuleAdded,
+            WebhookEvent::RuleRemoved { .. } => WebhookEventType::RuleRemoved,
+            WebhookEvent::WebhookRegistered { .. } => WebhookEventType::WebhookRegistered,
+            WebhookEvent::WebhookRemoved { .. } => WebhookEventType::WebhookRemoved,
+            WebhookEvent::RateLimited { .. } => WebhookEventType::RateLimited,
+            WebhookEvent::RuleLimitExceeded { .. } => WebhookEventType::RuleLimitExceeded,
+            WebhookEvent::ConnectionLimitExceeded { .. } => {
+                WebhookEventType::ConnectionLimitExceeded
+            }
+            WebhookEvent::WebSocketLimitExceeded { .. } => WebhookEventType::WebSocketLimitExceeded,
+            WebhookEvent::UpstreamConnected { .. } => WebhookEventType::UpstreamConnected,
+            WebhookEvent::UpstreamDisconnected { .. } => WebhookEventType::UpstreamDisconnected,
+            WebhookEvent::UpstreamConnectFailed { .. } => WebhookEventType::UpstreamConnectFailed,
+            WebhookEvent::UpstreamHandshakeFailed { .. } => {
+                WebhookEventType::UpstreamHandshakeFailed
+            }
+            WebhookEvent::MonthlyUsageSummary { .. } => WebhookEventType::MonthlyUsageSummary,
+            WebhookEvent::ConfigUpdated { .. } => WebhookEventType::ConfigUpdated,
+            WebhookEvent::LatencySummary { .. } => WebhookEventType::LatencySummary,
+            WebhookEvent::AutoBanned { .. } => WebhookEventType::AutoBanned,
+            WebhookEvent::QuotaThresholdReached { .. } => WebhookEventType::QuotaThresholdReached,
+            WebhookEvent::MaintenanceStarting { .. } => WebhookEventType::MaintenanceStarting,
+            WebhookEvent::MaintenanceEnded { .. } => WebhookEventType::MaintenanceEnded,
+            WebhookEvent::UpstreamSwitched { .. } => WebhookEventType::UpstreamSwitched,
+            WebhookEvent::DailyDigest(_) => WebhookEventType::DailyDigest,
+        }
+    }
+
+    /// Returns true if the event is about `account`: its grants, rules, bans, limits, quotas
+    /// and expirations. Backend, webhook, configuration, latency and digest events concern no
+    /// account.
+    pub fn concerns_account(&self, account: &AccountId32) -> bool {
+        self.account().as_ref() == Some(account)
+    }
+
+    /// The account the event is about, if any. Events about an IP or CIDR have none.
+    pub fn account(&self) -> Option<AccountId32> {
+        let source = match self {
+            WebhookEvent::TemporaryAccessExpired { account } => return Some(account.clone()),
+            WebhookEvent::MonthlyUsageSummary { account, .. } => account,
+            WebhookEvent::AccessGranted { source, .. }
+            | WebhookEvent::AccessDenied { source, .. }
+            | WebhookEvent::Banned { source }
+            | WebhookEvent::AccessExpiringSoon { source, .. }
+            | WebhookEvent::RateLimited { source, .. }
+            | WebhookEvent::QuotaThresholdReached { source, .. }
+            | WebhookEvent::ConnectionLimitExceeded { source, .. }
+            | WebhookEvent::WebSocketLimitExceeded { source, .. }
+            | WebhookEvent::RuleAdded { value: source, .. }
+            | WebhookEvent::RuleRemoved { value: source, .. } => source,
+            WebhookEvent::TemporaryIpAccessExpired { .. }
+            | WebhookEvent::WebhookRegistered { .. }
+            | WebhookEvent::WebhookRemoved { .. }
+            | WebhookEvent::RuleLimitExceeded { .. }
+            | WebhookEvent::UpstreamConnected { .. }
+            | WebhookEvent::UpstreamDisconnected { .. }
+            | WebhookEvent::UpstreamConnectFailed { .. }
+            | WebhookEvent::UpstreamHandshakeFailed { .. }
+            | WebhookEvent::ConfigUpdated { .. }
+            | WebhookEvent::LatencySummary { .. }
+            | WebhookEvent::AutoBanned { .. }
+            | WebhookEvent::MaintenanceStarting { .. }
+            | WebhookEvent::MaintenanceEnded { .. }
+            | WebhookEvent::UpstreamSwitched { .. }
+            | WebhookEvent::DailyDigest(_) => return None,
+        };
+        AccountId32::from_str(source).ok()
+    }
+}
+
+/// An account's current standing with the firewall.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountAccessStatus {
+    /// Whether requests authenticated as the account are currently let through.
+    pub allowed: bool,
+    /// Allowed by a config or dynamic allow rule.
+    pub permanent: bool,
+    /// Expiry of an active temporary grant.
+    pub temporary_expires_at: Option<DateTime<Utc>>,
+    /// Denied by a config or dynamic ban.
+    pub banned: bool,
+}
+
+/// Point-in-time sizes of the firewall's dynamic state.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FirewallStats {
+    pub dynamic_ips: usize,
+    pub dynamic_accounts: usize,
+    pub temporary_grants: usize,
+    pub temporary_ip_grants: usize,
+    pub webhooks: usize,
+    pub bans: usize,
+    /// Active automatic bans.
+    pub auto_bans: usize,
+    /// Temporary grants removed by cleanup since startup.
+    pub expired_total: u64,
+    /// WebSocket sessions closed for breaking a message rate or size limit since startup.
+    pub websocket_violations_total: u64,
+    /// Access decisions that ran over their time budget since startup.
+    pub decision_budget_breaches: BudgetBreaches,
+    /// Access events dropped because their queue was full, since startup.
+    pub access_events_dropped: u64,
+    /// Access events not delivered as repeats within the coalescing window, since startup.
+    pub access_events_coalesced: u64,
+}
+
+/// Allow and deny lists, as returned by [`Firewall::rules`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleSet {
+    pub allow_ips: Vec<IpNetwork>,
+    pub allow_accounts: Vec<AccountId32>,
+    pub deny_ips: Vec<IpNetwork>,
+    pub deny_accounts: Vec<AccountId32>,
+}
+
+/// The firewall's permanent rules, split by where they came from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirewallRules {
+    pub allow_unrestricted_access: bool,
+    /// Rules from `[firewall]` in the config file.
+    pub config: RuleSet,
+    /// Rules added at runtime by jobs or the admin API.
+    pub dynamic: RuleSet,
+    /// The windows dynamic allow rules are restricted to, keyed by IP/CIDR or account. Rules
+    /// not listed always apply.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub access_windows: HashMap<String, Vec<AccessWindow>>,
+}
+
+/// An active temporary grant.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemporaryGrant {
+    /// AccountId, IP or CIDR.
+    pub source: String,
+    pub granted_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub plan: Option<String>,
+}
+
+/// Outcome of a single cleanup pass.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CleanupStats {
+    /// Temporary grants (accounts and IPs) examined.
+    pub scanned: usize,
+    /// Temporary grants removed because they expired.
+    pub expired: usize,
 }
 
 impl Firewall {
-    pub fn new(config: &FirewallConfig, webhook_config: &[Url]) -> Self {
-        Firewall {
-            allow_ips_config: config.allow_ips.clone(),
-            allow_accounts_config: config.allow_accounts.clone(),
-            allow_unrestricted_access: config.allow_unrestricted_access,
-            allow_ips_dynamic: Arc::new(RwLock::new(HashSet::new())),
-            allow_accounts_dynamic: Arc::new(RwLock::new(HashSet::new())),
-            temporary_access: Arc::new(RwLock::new(HashMap::new())),
+    /// Creates the firewall, restoring dynamic rules and temporary grants from `store`.
+    pub fn new(
+        config: &FirewallConfig,
+        webhook_config: &[WebhookTarget],
+        store: FirewallStore,
+        labels: Arc<AccountLabels>,
+        dispatcher: WebhookDispatcher,
+        access_events: AccessEvents,
+        audit: Option<Arc<AuditLog>>,
+        event_history: Option<Arc<EventHistory>>,
+        daily_digest: Option<Arc<DailyDigestCollector>>,
+    ) -> Result<Self> {
+        let geoip = match &config.geoip_database {
+            Some(path) => {
+                let geoip = GeoIp::open(path)?;
+                info!(path = %path.display(), "Loaded GeoIP database");
+                Some(Arc::new(geoip))
+            }
+            None => None,
+        };
+        let persisted = store.load()?;
+        debug!(
+            ips = persisted.allow_ips.len(),
+            accounts = persisted.allow_accounts.len(),
+            temporary = persisted.temporary_access.len() + persisted.temporary_ips.len(),
+            bans = persisted.deny_ips.len() + persisted.deny_accounts.len(),
+            "Restored persisted firewall state"
+        );
+
+        let firewall = Firewall {
+            static_rules: Arc::new(RwLock::new(Arc::new(StaticRules::new(config)))),
+            deny_ips_dynamic: Arc::new(RwLock::new(persisted.deny_ips)),
+            deny_accounts_dynamic: Arc::new(RwLock::new(persisted.deny_accounts)),
+            allow_ips_dynamic: Arc::new(RwLock::new(persisted.allow_ips)),
+            allow_accounts_dynamic: Arc::new(RwLock::new(persisted.allow_accounts)),
+            temporary_access: Arc::new(RwLock::new(persisted.temporary_access)),
+            temporary_ips: Arc::new(RwLock::new(persisted.temporary_ips)),
+            store,
+            history: Arc::new(AccessHistory::new(config.history_entries_per_account)),
+            labels,
+            geoip,
+            rate_limiter: Arc::new(RwLock::new(
+                config
+                    .rate_limit
+                    .as_ref()
+                    .map(|cfg| Arc::new(RateLimiter::new(cfg))),
+            )),
+            burst_loans: config.burst_loans.clone(),
+            decision_cache: config
+                .decision_cache
+                .as_ref()
+                .map(|cfg| Arc::new(DecisionCache::new(cfg))),
+            auto_ban: config
+                .auto_ban
+                .as_ref()
+                .map(|cfg| Arc::new(AutoBanner::new(cfg))),
+            rule_changes: Arc::new(watch::Sender::new(0)),
+            shadow: Arc::new(RwLock::new(None)),
+            expired_total: Arc::new(AtomicU64::new(0)),
+            websocket_violations_total: Arc::new(AtomicU64::new(0)),
+            budget_breaches: Arc::new(Default::default()),
+            expiry_warning: config.expiry_warning_secs.map(Duration::from_secs),
+            expiry_warned: Arc::new(RwLock::new(HashMap::new())),
+            limits: config.limits.clone(),
+            audit,
+            event_history,
+            daily_digest,
             webhooks: Arc::new(RwLock::new(webhook_config.to_vec())),
-            http_client: reqwest::Client::new(),
+            dispatcher,
+            access_events,
+            gauges: FirewallGauges::new(),
+        };
+        firewall.record_gauges();
+        Ok(firewall)
+    }
+
+    /// Checks if an IP address is on a deny list or automatically banned, emitting a `Banned`
+    /// event if so.
+    pub async fn is_ip_banned(&self, ip: &IpAddr) -> bool {
+        let banned = self
+            .static_rules()
+            .deny_ips
+            .iter()
+            .any(|net| network_contains(net, *ip))
+            || self
+                .deny_ips_dynamic
+                .read()
+                .iter()
+                .any(|net| network_contains(net, *ip))
+            || self.is_auto_banned(ip);
+        if banned {
+            debug!(%ip, "Access denied: IP found in deny list");
+            self.access_events.queue(WebhookEvent::Banned {
+                source: ip.to_string(),
+            });
+        }
+        banned
+    }
+
+    /// Checks if an account is on a deny list, emitting a `Banned` event if so.
+    pub async fn is_account_banned(&self, account: &AccountId32) -> bool {
+        let banned = self.static_rules().deny_accounts.contains(account)
+            || self.deny_accounts_dynamic.read().contains(account);
+        if banned {
+            debug!(%account, "Account access denied: Found in deny list");
+            self.access_events.queue(WebhookEvent::Banned {
+                source: account.to_string(),
+            });
+        }
+        banned
+    }
+
+    /// Returns true if a deny rule (IP, country or account) or an automatic ban applies,
+    /// without emitting events.
+    pub fn is_banned(&self, ip: &IpAddr, account: Option<&AccountId32>) -> bool {
+        let static_rules = self.static_rules();
+        static_rules
+            .deny_ips
+            .iter()
+            .any(|net| network_contains(net, *ip))
+            || self
+                .deny_ips_dynamic
+                .read()
+                .iter()
+                .any(|net| network_contains(net, *ip))
+            || self.is_auto_banned(ip)
+            || (!static_rules.deny_countries.is_empty()
+                && self
+                    .country(ip)
+                    .is_some_and(|country| static_rules.deny_countries.contains(&country)))
+            || account.is_some_and(|account| {
+                static_rules.deny_accounts.contains(account)
+                    || self.deny_accounts_dynamic.read().contains(account)
+            })
+    }
+
+    /// The country an IP address is located in, if a GeoIP database is configured and knows it.
+    pub fn country(&self, ip: &IpAddr) -> Option<String> {
+        self.geoip.as_ref()?.country(*ip)
+    }
+
+    /// Checks if an IP address is located in a denied country, emitting an `AccessDenied` event
+    /// if so.
+    pub async fn is_country_denied(&self, ip: &IpAddr) -> bool {
+        let static_rules = self.static_rules();
+        if static_rules.deny_countries.is_empty() {
+            return false;
         }
+        let Some(country) = self
+            .country(ip)
+            .filter(|country| static_rules.deny_countries.contains(country))
+        else {
+            return false;
+        };
+        debug!(%ip, %country, "Access denied: IP located in a denied country");
+        self.access_events.queue(WebhookEvent::AccessDenied {
+            source: ip.to_string(),
+            country: Some(country),
+        });
+        true
     }
 
     /// Checks if an IP address is allowed access.
-    /// Order of checks: Unrestricted -> Config IPs -> Dynamic IPs -> Temporary (via lookup)
+    /// Order of checks: Deny lists -> Denied countries -> Unrestricted -> Config IPs ->
+    /// Dynamic IPs (within their access windows) -> Temporary IPs -> Allowed countries
     pub async fn is_allowed(&self, ip: &IpAddr) -> bool {
-        if self.allow_unrestricted_access {
+        if self.is_ip_banned(ip).await || self.is_country_denied(ip).await {
+            return false;
+        }
+
+        let static_rules = self.static_rules();
+        if static_rules.allow_unrestricted_access {
             debug!(%ip, "Access granted: Unrestricted access enabled");
-            self.notify_webhook(WebhookEvent::AccessGranted {
+            self.access_events.queue(WebhookEvent::AccessGranted {
                 source: ip.to_string(),
                 access_type: "Unrestricted".to_string(),
-            })
-            .await;
+            });
             return true;
         }
 
-        if self.allow_ips_config.iter().any(|net| net.contains(*ip)) {
+        if static_rules
+            .allow_ips
+            .iter()
+            .any(|net| network_contains(net, *ip))
+        {
             debug!(%ip, "Access granted: IP found in static config allowlist");
-            self.notify_webhook(WebhookEvent::AccessGranted {
+            self.access_events.queue(WebhookEvent::AccessGranted {
                 source: ip.to_string(),
                 access_type: "Permanent (Config)".to_string(),
-            })
-            .await;
+            });
+            return true;
+        }
+
+        let now = Utc::now();
+        if self.dynamic_ip_rule_applies(ip, now) {
+            debug!(%ip, "Access granted: IP found in dynamic allowlist");
+            self.access_events.queue(WebhookEvent::AccessGranted {
+                source: ip.to_string(),
+                access_type: "Permanent (Dynamic)".to_string(),
+            });
             return true;
         }
 
         if self
-            .allow_ips_dynamic
+            .temporary_ips
             .read()
             .iter()
-            .any(|net| net.contains(*ip))
+            .any(|(net, record)| network_contains(net, *ip) && record.expires_at > now)
         {
-            debug!(%ip, "Access granted: IP found in dynamic allowlist");
-            self.notify_webhook(WebhookEvent::AccessGranted {
+            debug!(%ip, "Access granted: IP found in temporary access list");
+            self.access_events.queue(WebhookEvent::AccessGranted {
                 source: ip.to_string(),
-                access_type: "Permanent (Dynamic)".to_string(),
-            })
-            .await;
+                access_type: "Temporary".to_string(),
+            });
+            return true;
+        }
+
+        let country = self.country(ip);
+        if country
+            .as_ref()
+            .is_some_and(|country| static_rules.allow_countries.contains(country))
+        {
+            debug!(%ip, ?country, "Access granted: IP located in an allowed country");
+            self.access_events.queue(WebhookEvent::AccessGranted {
+                source: ip.to_string(),
+                access_type: "Country (Config)".to_string(),
+            });
             return true;
         }
 
-        // Note: Temporary access check is usually tied to an account derived from auth token
-        // in a real scenario. Here we only check permanent lists based on IP.
         debug!(%ip, "Access denied: IP not found in any allowlist");
-        self.notify_webhook(WebhookEvent::AccessDenied {
+        self.access_events.queue(WebhookEvent::AccessDenied {
             source: ip.to_string(),
-        })
-        .await;
+            country,
+        });
         false
     }
 
-    /// Checks if an account is allowed (config, dynamic, or temporary).
+    /// Checks if an account is allowed (config, dynamic within its access windows, or
+    /// temporary).
     pub async fn is_account_allowed(&self, account: &AccountId32) -> bool {
-        if self.allow_unrestricted_access {
+        if self.is_account_banned(account).await {
+            return false;
+        }
+
+        let static_rules = self.static_rules();
+        if static_rules.allow_unrestricted_access {
             debug!(%account, "Account access granted: Unrestricted access enabled");
-            self.notify_webhook(WebhookEvent::AccessGranted {
+            self.access_events.queue(WebhookEvent::AccessGranted {
                 source: account.to_string(),
                 access_type: "Unrestricted".to_string(),
-            })
-            .await;
+            });
             return true;
         }
 
-        if self.allow_accounts_config.contains(account) {
+        if static_rules.allow_accounts.contains(account) {
             debug!(%account, "Account access granted: Found in static config allowlist");
-            self.notify_webhook(WebhookEvent::AccessGranted {
+            self.access_events.queue(WebhookEvent::AccessGranted {
                 source: account.to_string(),
                 access_type: "Permanent (Config)".to_string(),
-            })
-            .await;
+            });
             return true;
         }
 
-        if self.allow_accounts_dynamic.read().contains(account) {
+        if self.dynamic_account_rule_applies(account, Utc::now()) {
             debug!(%account, "Account access granted: Found in dynamic allowlist");
-            self.notify_webhook(WebhookEvent::AccessGranted {
+            self.access_events.queue(WebhookEvent::AccessGranted {
                 source: account.to_string(),
                 access_type: "Permanent (Dynamic)".to_string(),
-            })
-            .await;
+            });
             return true;
         }
 
         if self.check_temporary_access(account).await {
             debug!(%account, "Account access granted: Found in temporary access list");
-            self.notify_webhook(WebhookEvent::AccessGranted {
+            self.access_events.queue(WebhookEvent::AccessGranted {
                 source: account.to_string(),
                 access_type: "Temporary".to_string(),
-            })
-            .await;
+            });
             return true;
         }
 
@@ -160,126 +752,1513 @@ impl Firewall {
         false
     }
 
-    /// Adds a dynamic IP rule (can be single IP or CIDR).
-    pub async fn add_ip_rule(&self, ip_network: IpNetwork) -> Result<()> {
-        let inserted = self.allow_ips_dynamic.write().insert(ip_network);
-        if inserted {
-            debug!(rule = %ip_network, "Added dynamic IP rule");
-            self.notify_webhook(WebhookEvent::RuleAdded {
-                rule_type: "IP".to_string(),
-                value: ip_network.to_string(),
-            })
-            .await;
+    /// Decides whether a request may proceed: by the IP rules if it is anonymous, otherwise by
+    /// the account's rules combined with the IP's as `firewall.identity.combine` says. Served from the decision cache when enabled.
+    ///
+    /// With `[firewall.decision_budget]`, the outcome of an evaluation that ran over budget is
+    /// discarded and `surface`'s fail policy decides instead; such decisions aren't cached.
+    pub async fn check_access(
+        &self,
+        ip: &IpAddr,
+        account: Option<&AccountId32>,
+        surface: AccessSurface,
+    ) -> AccessDecision {
+        let cached = self
+            .decision_cache
+            .as_ref()
+            .and_then(|cache| cache.get(*ip, account));
+        let allowed = match cached {
+            Some(allowed) => allowed,
+            None => {
+                let generation = self.decision_cache.as_ref().map(|cache| cache.generation());
+                let Some(allowed) = self.evaluate_within_budget(ip, account, surface).await else {
+                    return self.over_budget(ip, surface);
+                };
+                if let (Some(cache), Some(generation)) = (&self.decision_cache, generation) {
+                    let valid_until = self.next_rule_change(ip, account);
+                    cache.insert(*ip, account, allowed, generation, valid_until);
+                }
+                allowed
+            }
+        };
+        self.shadow_evaluate(ip, account, allowed);
+        if allowed {
+            AccessDecision::Allowed
+        } else {
+            AccessDecision::Denied
         }
-        Ok(())
     }
 
-    /// Adds a dynamic account rule.
-    pub async fn add_account_rule(&self, account: AccountId32) -> Result<()> {
-        let inserted = self.allow_accounts_dynamic.write().insert(account.clone());
-        if inserted {
-            debug!(%account, "Added dynamic account rule");
-            self.notify_webhook(WebhookEvent::RuleAdded {
-                rule_type: "Account".to_string(),
-                value: account.to_string(),
-            })
-            .await;
+    /// Evaluates access, timed against `[firewall.decision_budget]`'s `budget_ms`. Returns
+    /// `None` if the evaluation took longer. The evaluation doesn't yield while it runs, so it
+    /// is measured rather than cut short.
+    async fn evaluate_within_budget(
+        &self,
+        ip: &IpAddr,
+        account: Option<&AccountId32>,
+        surface: AccessSurface,
+    ) -> Option<bool> {
+        let Some(budget_ms) = self
+            .static_rules()
+            .decision_budget
+            .as_ref()
+            .map(|budget| budget.budget_ms)
+        else {
+            return Some(self.evaluate_access(ip, account).await);
+        };
+        let started = Instant::now();
+        let allowed = self.evaluate_access(ip, account).await;
+        if started.elapsed() > Duration::from_millis(budget_ms) {
+            self.budget_breaches[surface as usize].fetch_add(1, Ordering::Relaxed);
+            return None;
         }
-        Ok(())
+        Some(allowed)
     }
 
-    /// Grants temporary access to an account.
-    pub async fn grant_temporary_access(
-        &self,
-        account: AccountId32,
-        record: TemporaryAccessRecord,
-    ) -> Result<()> {
-        debug!(%account, expires_at = %record.expires_at, "Granting temporary access");
-        self.temporary_access.write().insert(account, record);
-        // Notification happens during check usually, or could add one here
-        Ok(())
+    /// The decision for an evaluation that ran over budget, by `surface`'s fail policy.
+    fn over_budget(&self, ip: &IpAddr, surface: AccessSurface) -> AccessDecision {
+        let policy =
+            self.static_rules()
+                .decision_budget
+                .as_ref()
+                .map_or(FailPolicy::Closed, |budget| match surface {
+                    AccessSurface::Http => budget.http,
+                    AccessSurface::WebSocket => budget.websocket,
+                    AccessSurface::SessionRecheck => budget.session_recheck,
+                });
+        warn!(%ip, ?surface, ?policy, "Access decision ran over its time budget");
+        match policy {
+            FailPolicy::Open => AccessDecision::Allowed,
+            FailPolicy::Closed => AccessDecision::Unavailable,
+        }
     }
 
-    /// Checks if temporary access for an account is still valid.
-    async fn check_temporary_access(&self, account: &AccountId32) -> bool {
+    /// Compares the active decision with the shadow policy's, logging divergences.
+    fn shadow_evaluate(&self, ip: &IpAddr, account: Option<&AccountId32>, allowed: bool) {
+        let Some(shadow) = self.shadow.read().clone() else {
+            return;
+        };
+        if !shadow.is_active() {
+            return;
+        }
+        let proposed = self.evaluate_proposed(&shadow.policy, ip, account);
+        if shadow.record(allowed, proposed) {
+            info!(
+                %ip,
+                account = ?account.map(ToString::to_string),
+                active = allowed,
+                proposed,
+                "Shadow policy decision differs from active policy"
+            );
+        }
+    }
+
+    /// Evaluates a request against a proposed policy and the current dynamic state, without
+    /// side effects.
+    fn evaluate_proposed(
+        &self,
+        policy: &ProposedPolicy,
+        ip: &IpAddr,
+        account: Option<&AccountId32>,
+    ) -> bool {
+        let ip_banned = policy.deny_ips.iter().any(|net| network_contains(net, *ip))
+            || self
+                .deny_ips_dynamic
+                .read()
+                .iter()
+                .any(|net| network_contains(net, *ip))
+            || self.is_auto_banned(ip);
+        // The proposed policy doesn't cover countries, so the active country rules carry over.
+        let static_rules = self.static_rules();
+        let country = self.country(ip);
+        let in_countries =
+            |countries: &HashSet<String>| country.as_ref().is_some_and(|c| countries.contains(c));
+        if ip_banned || in_countries(&static_rules.deny_countries) {
+            return false;
+        }
+
         let now = Utc::now();
-        let mut access_map = self.temporary_access.write();
+        let ip_allowed = || {
+            policy.allow_unrestricted_access
+                || policy
+                    .allow_ips
+                    .iter()
+                    .any(|net| network_contains(net, *ip))
+                || self.dynamic_ip_rule_applies(ip, now)
+                || self
+                    .temporary_ips
+                    .read()
+                    .iter()
+                    .any(|(net, record)| network_contains(net, *ip) && record.expires_at > now)
+                || in_countries(&static_rules.allow_countries)
+        };
+        let Some(account) = account else {
+            return ip_allowed();
+        };
+        if policy.deny_accounts.contains(account)
+            || self.deny_accounts_dynamic.read().contains(account)
+        {
+            return false;
+        }
+        let account_allowed = policy.allow_unrestricted_access
+            || policy.allow_accounts.contains(account)
+            || self.dynamic_account_rule_applies(account, now)
+            || self
+                .temporary_access
+                .read()
+                .get(account)
+                .is_some_and(|record| record.expires_at > now);
+        // The proposed policy is combined with the account's IP like the active one.
+        match static_rules.combine {
+            IdentityCombination::Account => account_allowed,
+            IdentityCombination::Either => account_allowed || ip_allowed(),
+            IdentityCombination::Both => account_allowed && ip_allowed(),
+        }
+    }
 
-        if let Some(record) = access_map.get(account) {
-            if record.expires_at > now {
-                return true; // Access valid
+    /// Starts evaluating `policy` alongside the active policy for `duration`, replacing any
+    /// shadow policy already running.
+    pub fn start_shadow_policy(&self, policy: ProposedPolicy, duration: Duration) -> ShadowReport {
+        let expires_at = chrono::Duration::from_std(duration)
+            .ok()
+            .and_then(|duration| Utc::now().checked_add_signed(duration))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        let shadow = Arc::new(ShadowPolicy::new(policy, expires_at));
+        info!(%expires_at, "Started shadow evaluation of proposed firewall policy");
+        *self.shadow.write() = Some(shadow.clone());
+        shadow.report()
+    }
+
+    /// Stops the shadow evaluation, returning its final report.
+    pub fn stop_shadow_policy(&self) -> Option<ShadowReport> {
+        let shadow = self.shadow.write().take()?;
+        info!("Stopped shadow evaluation of proposed firewall policy");
+        Some(shadow.report())
+    }
+
+    /// Report of the current (or last, if expired) shadow evaluation.
+    pub fn shadow_report(&self) -> Option<ShadowReport> {
+        self.shadow.read().as_ref().map(|shadow| shadow.report())
+    }
+
+    async fn evaluate_access(&self, ip: &IpAddr, account: Option<&AccountId32>) -> bool {
+        let Some(account) = account else {
+            return self.is_allowed(ip).await;
+        };
+        match self.static_rules().combine {
+            IdentityCombination::Account => {
+                !self.is_ip_banned(ip).await
+                    && !self.is_country_denied(ip).await
+                    && self.is_account_allowed(account).await
+            }
+            // A ban on either identity still denies, however permissive the other one is.
+            IdentityCombination::Either => {
+                !self.is_ip_banned(ip).await
+                    && !self.is_country_denied(ip).await
+                    && !self.is_account_banned(account).await
+                    && (self.is_account_allowed(account).await || self.is_allowed(ip).await)
+            }
+            IdentityCombination::Both => {
+                self.is_allowed(ip).await && self.is_account_allowed(account).await
             }
-            // Access expired
-            debug!(%account, "Temporary access expired");
-            access_map.remove(account);
-            self.notify_webhook(WebhookEvent::TemporaryAccessExpired {
-                account: account.clone(),
-            })
-            .await;
         }
-        false
     }
 
-    /// Cleans up expired temporary access records.
-    pub fn cleanup_expired_access(&self) {
-        let now = Utc::now();
-        let mut access_map = self.temporary_access.write();
-        let expired_accounts: Vec<AccountId32> = access_map
-            .iter()
-            .filter(|(_, record)| record.expires_at <= now)
-            .map(|(account, _)| account.clone())
-            .collect();
+    fn static_rules(&self) -> Arc<StaticRules> {
+        self.static_rules.read().clone()
+    }
 
-        for account in expired_accounts {
-            debug!(%account, "Cleaning up expired temporary access");
-            access_map.remove(&account);
-            // Consider if notification is needed here too, though check_temporary_access handles it
-            // self.notify_webhook(WebhookEvent::TemporaryAccessExpired { account }).await;
-        }
+    /// Replaces the allow and deny lists taken from config. Rules added by jobs, temporary
+    /// grants and rate-limit state are kept.
+    pub fn reload_static_rules(&self, config: &FirewallConfig) {
+        *self.static_rules.write() = Arc::new(StaticRules::new(config));
+        self.invalidate_decisions();
+        self.reload_rate_limit(config.rate_limit.as_ref());
+        info!(
+            allow_ips = config.allow_ips.len(),
+            allow_accounts = config.allow_accounts.len(),
+            deny_ips = config.deny_ips.len(),
+            deny_accounts = config.deny_accounts.len(),
+            allow_countries = config.allow_countries.len(),
+            deny_countries = config.deny_countries.len(),
+            unrestricted = config.allow_unrestricted_access,
+            "Reloaded firewall rules from config"
+        );
     }
 
-    /// Registers a new webhook URL.
-    pub async fn add_webhook(&self, url: Url) -> Result<()> {
-        debug!(%url, "Registering new webhook");
-        self.webhooks.write().push(url.clone());
-        self.notify_webhook(WebhookEvent::WebhookRegistered { url })
-            .await;
-        Ok(())
+    /// Replaces the dynamic rules and temporary grants with those in the store, picking up
+    /// changes made by other gateway instances sharing it. Returns whether any changed.
+    pub fn sync_dynamic_state(&self) -> Result<bool> {
+        let persisted = self.store.load()?;
+        let changed = [
+            replace_if_changed(&self.allow_ips_dynamic, persisted.allow_ips),
+            replace_if_changed(&self.allow_accounts_dynamic, persisted.allow_accounts),
+            replace_if_changed(&self.deny_ips_dynamic, persisted.deny_ips),
+            replace_if_changed(&self.deny_accounts_dynamic, persisted.deny_accounts),
+            replace_if_changed(&self.temporary_access, persisted.temporary_access),
+            replace_if_changed(&self.temporary_ips, persisted.temporary_ips),
+        ]
+        .contains(&true);
+        if changed {
+            self.invalidate_decisions();
+            debug!("Picked up dynamic firewall state changed by another instance");
+        }
+        Ok(changed)
     }
 
-    /// Sends an event notification to all registered webhooks.
-    async fn notify_webhook(&self, event: WebhookEvent) {
-        let urls = self.webhooks.read().clone();
-        if urls.is_empty() {
+    /// Replaces the per-IP rate limiter if its limits changed. Clients start over with a full
+    /// bucket under new limits; unchanged limits keep every bucket as it is.
+    fn reload_rate_limit(&self, config: Option<&RateLimitConfig>) {
+        let mut limiter = self.rate_limiter.write();
+        let unchanged = match (limiter.as_ref(), config) {
+            (Some(current), Some(config)) => current.has_limits(config),
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged {
             return;
         }
+        *limiter = config.map(|cfg| Arc::new(RateLimiter::new(cfg)));
+        info!(
+            requests_per_sec = config.map(|cfg| cfg.requests_per_sec),
+            burst = config.map(|cfg| cfg.burst),
+            "Reloaded per-IP rate limit"
+        );
+    }
 
-        let client = self.http_client.clone();
-        let event_json = match serde_json::to_value(&event) {
-            Ok(json) => json,
-            Err(e) => {
-                tracing::error!(error = %e, "Failed to serialize webhook event");
-                return;
+    /// Called after every change to rules or grants: drops cached decisions and signals
+    /// [`Self::subscribe_rule_changes`] subscribers.
+    fn invalidate_decisions(&self) {
+        if let Some(cache) = &self.decision_cache {
+            cache.invalidate();
+        }
+        self.rule_changes.send_modify(|generation| *generation += 1);
+        self.record_gauges();
+    }
+
+    /// Records the current [`Self::stats`] and decision cache size as metrics. Called on every
+    /// rule or webhook change, and by the periodic cleanup for the sizes that change without
+    /// one, like expiring automatic bans and cached decisions.
+    pub fn record_gauges(&self) {
+        let cached_decisions = self.decision_cache.as_ref().map_or(0, |cache| cache.len());
+        self.gauges.record(&self.stats(), cached_decisions);
+    }
+
+    /// Notifies the receiver whenever rules or grants change, including config reloads,
+    /// bans, revocations and expired temporary grants.
+    pub fn subscribe_rule_changes(&self) -> watch::Receiver<u64> {
+        self.rule_changes.subscribe()
+    }
+
+    /// Adds a dynamic IP rule (can be single IP or CIDR).
+    pub async fn add_ip_rule(&self, ip_network: IpNetwork) -> Result<()> {
+        self.add_scheduled_ip_rule(ip_network, Vec::new()).await
+    }
+
+    /// Adds a dynamic IP rule that only applies within `windows`, or always if there are none.
+    /// Adding an existing rule replaces its windows.
+    pub async fn add_scheduled_ip_rule(
+        &self,
+        ip_network: IpNetwork,
+        windows: Vec<AccessWindow>,
+    ) -> Result<()> {
+        let ip_network = canonical_network(ip_network);
+        let (before, after) = {
+            let mut rules = self.allow_ips_dynamic.write();
+            let before = rules.len();
+            match rules.get(&ip_network) {
+                Some(existing) if *existing == windows => return Ok(()),
+                Some(_) => {}
+                None => self
+                    .limits
+                    .dynamic_ips
+                    .ensure_capacity(rules.len(), "Dynamic IP rule")?,
             }
+            self.store.insert_ip_rule(&ip_network, &windows)?;
+            rules.insert(ip_network, windows);
+            (before, rules.len())
         };
 
-        for url in urls {
-            let client = client.clone();
-            let event_json = event_json.clone();
-            tokio::spawn(async move {
-                match client.post(url.clone()).json(&event_json).send().await {
-                    Ok(response) => {
-                        if !response.status().is_success() {
-                            tracing::warn!(%url, status = %response.status(), "Webhook notification failed");
-                        } else {
-                            tracing::debug!(%url, status = %response.status(), "Webhook notification sent successfully");
-                        }
-                    }
-                    Err(e) => {
-                        tracing::warn!(%url, error = %e, "Webhook notification failed");
-                    }
-                }
-            });
-        }
+        self.invalidate_decisions();
+        debug!(rule = %ip_network, "Added dynamic IP rule");
+        self.notify_webhook(WebhookEvent::RuleAdded {
+            rule_type: "IP".to_string(),
+            value: ip_network.to_string(),
+        })
+        .await;
+        self.check_rule_warning("IP", &self.limits.dynamic_ips, before, after)
+            .await;
+        Ok(())
+    }
+
+    /// Adds a dynamic account rule.
+    pub async fn add_account_rule(&self, account: AccountId32) -> Result<()> {
+        self.add_scheduled_account_rule(account, Vec::new()).await
+    }
+
+    /// Adds a dynamic account rule that only applies within `windows`, or always if there are
+    /// none. Adding an existing rule replaces its windows.
+    pub async fn add_scheduled_account_rule(
+        &self,
+        account: AccountId32,
+        windows: Vec<AccessWindow>,
+    ) -> Result<()> {
+        let (before, after) = {
+            let mut rules = self.allow_accounts_dynamic.write();
+            let before = rules.len();
+            match rules.get(&account) {
+                Some(existing) if *existing == windows => return Ok(()),
+                Some(_) => {}
+                None => self
+                    .limits
+                    .dynamic_accounts
+                    .ensure_capacity(rules.len(), "Dynamic account rule")?,
+            }
+            self.store.insert_account_rule(&account, &windows)?;
+            rules.insert(account.clone(), windows);
+            (before, rules.len())
+        };
+
+        self.invalidate_decisions();
+        debug!(%account, "Added dynamic account rule");
+        self.history
+            .record(&account, AccessHistoryEvent::PermanentGranted);
+        self.notify_webhook(WebhookEvent::RuleAdded {
+            rule_type: "Account".to_string(),
+            value: account.to_string(),
+        })
+        .await;
+        self.check_rule_warning("Account", &self.limits.dynamic_accounts, before, after)
+            .await;
+        Ok(())
+    }
+
+    /// Removes a dynamic IP rule. Removing a rule that doesn't exist is a no-op.
+    pub async fn remove_ip_rule(&self, ip_network: IpNetwork) -> Result<()> {
+        let ip_network = canonical_network(ip_network);
+        {
+            let mut rules = self.allow_ips_dynamic.write();
+            if !rules.contains_key(&ip_network) {
+                return Ok(());
+            }
+            self.store.remove_ip_rule(&ip_network)?;
+            rules.remove(&ip_network);
+        }
+
+        self.invalidate_decisions();
+        debug!(rule = %ip_network, "Removed dynamic IP rule");
+        self.notify_webhook(WebhookEvent::RuleRemoved {
+            rule_type: "IP".to_string(),
+            value: ip_network.to_string(),
+        })
+        .await;
+        Ok(())
+    }
+
+    /// Removes a dynamic account rule. Removing a rule that doesn't exist is a no-op.
+    pub async fn remove_account_rule(&self, account: AccountId32) -> Result<()> {
+        {
+            let mut rules = self.allow_accounts_dynamic.write();
+            if !rules.contains_key(&account) {
+                return Ok(());
+            }
+            self.store.remove_account_rule(&account)?;
+            rules.remove(&account);
+        }
+
+        self.invalidate_decisions();
+        debug!(%account, "Removed dynamic account rule");
+        self.history.record(&account, AccessHistoryEvent::Revoked);
+        self.notify_webhook(WebhookEvent::RuleRemoved {
+            rule_type: "Account".to_string(),
+            value: account.to_string(),
+        })
+        .await;
+        Ok(())
+    }
+
+    /// Adds a dynamic deny rule for an IP or CIDR.
+    pub async fn ban_ip(&self, ip_network: IpNetwork) -> Result<()> {
+        let ip_network = canonical_network(ip_network);
+        let (before, after) = {
+            let mut rules = self.deny_ips_dynamic.write();
+            if rules.contains(&ip_network) {
+                return Ok(());
+            }
+            let total = rules.len() + self.deny_accounts_dynamic.read().len();
+            self.limits.bans.ensure_capacity(total, "Ban")?;
+            self.store.insert_deny_ip(&ip_network)?;
+            rules.insert(ip_network);
+            (total, total + 1)
+        };
+
+        self.invalidate_decisions();
+        debug!(rule = %ip_network, "Added dynamic IP deny rule");
+        self.notify_webhook(WebhookEvent::RuleAdded {
+            rule_type: "DenyIP".to_string(),
+            value: ip_network.to_string(),
+        })
+        .await;
+        self.check_rule_warning("Ban", &self.limits.bans, before, after)
+            .await;
+        Ok(())
+    }
+
+    /// Adds a dynamic deny rule for an account.
+    pub async fn ban_account(&self, account: AccountId32) -> Result<()> {
+        let (before, after) = {
+            let mut rules = self.deny_accounts_dynamic.write();
+            if rules.contains(&account) {
+                return Ok(());
+            }
+            let total = rules.len() + self.deny_ips_dynamic.read().len();
+            self.limits.bans.ensure_capacity(total, "Ban")?;
+            self.store.insert_deny_account(&account)?;
+            rules.insert(account.clone());
+            (total, total + 1)
+        };
+
+        self.invalidate_decisions();
+        debug!(%account, "Added dynamic account deny rule");
+        self.history.record(&account, AccessHistoryEvent::Banned);
+        self.notify_webhook(WebhookEvent::RuleAdded {
+            rule_type: "DenyAccount".to_string(),
+            value: account.to_string(),
+        })
+        .await;
+        self.check_rule_warning("Ban", &self.limits.bans, before, after)
+            .await;
+        Ok(())
+    }
+
+    /// Grants temporary access to an account.
+    pub async fn grant_temporary_access(
+        &self,
+        account: AccountId32,
+        record: TemporaryAccessRecord,
+    ) -> Result<()> {
+        self.update_temporary_access(account, |_| Ok(record))
+            .await
+            .map(|_| ())
+    }
+
+    /// Extends an account's active temporary grant by `duration`, keeping its plan and
+    /// stacking its quota, or grants access for `duration` from now on `plan` if it has none.
+    /// Returns the resulting grant.
+    pub async fn extend_temporary_access(
+        &self,
+        account: AccountId32,
+        duration: Duration,
+        plan: Option<String>,
+    ) -> Result<TemporaryAccessRecord> {
+        let now = Utc::now();
+        self.update_temporary_access(account, |current| {
+            extend_grant(current, now, duration, plan)
+        })
+        .await
+    }
+
+    /// The account's temporary grant, if it is active.
+    pub fn active_temporary_access(&self, account: &AccountId32) -> Option<TemporaryAccessRecord> {
+        let now = Utc::now();
+        self.temporary_access
+            .read()
+            .get(account)
+            .filter(|record| record.expires_at > now)
+            .cloned()
+    }
+
+    /// Replaces an account's temporary grant with the one `update` derives from the current
+    /// one, under the lock.
+    async fn update_temporary_access(
+        &self,
+        account: AccountId32,
+        update: impl FnOnce(Option<&TemporaryAccessRecord>) -> Result<TemporaryAccessRecord>,
+    ) -> Result<TemporaryAccessRecord> {
+        let (record, before, after) = {
+            let mut access_map = self.temporary_access.write();
+            let record = update(access_map.get(&account))?;
+            debug!(%account, expires_at = %record.expires_at, "Granting temporary access");
+            let ip_grants = self.temporary_ips.read().len();
+            let before = access_map.len() + ip_grants;
+            if !access_map.contains_key(&account) {
+                self.limits
+                    .temporary_grants
+                    .ensure_capacity(access_map.len() + ip_grants, "Temporary grant")?;
+            }
+            self.store.insert_temporary_access(&account, &record)?;
+            access_map.insert(account.clone(), record.clone());
+            (record, before, access_map.len() + ip_grants)
+        };
+        let expires_at = record.expires_at;
+        self.invalidate_decisions();
+        self.history.record(
+            &account,
+            AccessHistoryEvent::TemporaryGranted { expires_at },
+        );
+        // Notification happens during check usually, or could add one here
+        self.check_rule_warning(
+            "TemporaryGrant",
+            &self.limits.temporary_grants,
+            before,
+            after,
+        )
+        .await;
+        Ok(record)
+    }
+
+    /// Grants temporary access to an IP or CIDR.
+    pub async fn grant_temporary_ip_access(
+        &self,
+        ip_network: IpNetwork,
+        record: TemporaryAccessRecord,
+    ) -> Result<()> {
+        self.update_temporary_ip_access(ip_network, |_| Ok(record))
+            .await
+            .map(|_| ())
+    }
+
+    /// Like [`Firewall::extend_temporary_access`], for the grant of exactly `ip_network`.
+    pub async fn extend_temporary_ip_access(
+        &self,
+        ip_network: IpNetwork,
+        duration: Duration,
+        plan: Option<String>,
+    ) -> Result<TemporaryAccessRecord> {
+        let now = Utc::now();
+        self.update_temporary_ip_access(ip_network, |current| {
+            extend_grant(current, now, duration, plan)
+        })
+        .await
+    }
+
+    /// The temporary grant of exactly `ip_network`, if it is active.
+    pub fn active_temporary_ip_access(
+        &self,
+        ip_network: IpNetwork,
+    ) -> Option<TemporaryAccessRecord> {
+        let now = Utc::now();
+        self.temporary_ips
+            .read()
+            .get(&canonical_network(ip_network))
+            .filter(|record| record.expires_at > now)
+            .cloned()
+    }
+
+    async fn update_temporary_ip_access(
+        &self,
+        ip_network: IpNetwork,
+        update: impl FnOnce(Option<&TemporaryAccessRecord>) -> Result<TemporaryAccessRecord>,
+    ) -> Result<TemporaryAccessRecord> {
+        let ip_network = canonical_network(ip_network);
+        let (record, before, after) = {
+            let mut access_map = self.temporary_ips.write();
+            let record = update(access_map.get(&ip_network))?;
+            debug!(rule = %ip_network, expires_at = %record.expires_at, "Granting temporary IP access");
+            let account_grants = self.temporary_access.read().len();
+            let before = access_map.len() + account_grants;
+            if !access_map.contains_key(&ip_network) {
+                self.limits
+                    .temporary_grants
+                    .ensure_capacity(before, "Temporary grant")?;
+            }
+            self.store.insert_temporary_ip(&ip_network, &record)?;
+            access_map.insert(ip_network, record.clone());
+            (record, before, access_map.len() + account_grants)
+        };
+        self.invalidate_decisions();
+        self.check_rule_warning(
+            "TemporaryGrant",
+            &self.limits.temporary_grants,
+            before,
+            after,
+        )
+        .await;
+        Ok(record)
+    }
+
+    /// Checks if temporary access for an account is still valid.
+    async fn check_temporary_access(&self, account: &AccountId32) -> bool {
+        let now = Utc::now();
+        let expired = {
+            let mut access_map = self.temporary_access.write();
+            match access_map
+                .get(account)
+                .map(|record| record.expires_at > now)
+            {
+                Some(true) => return true, // Access valid
+                Some(false) => access_map.remove(account).is_some(),
+                None => false,
+            }
+        };
+
+        if expired {
+            self.invalidate_decisions();
+            debug!(%account, "Temporary access expired");
+            if let Err(e) = self.store.remove_temporary_access(account) {
+                warn!(%account, error = %e, "Failed to remove persisted temporary access");
+            }
+            self.history.record(account, AccessHistoryEvent::Expired);
+            self.notify_webhook(WebhookEvent::TemporaryAccessExpired {
+                account: account.clone(),
+            })
+            .await;
+        }
+        false
+    }
+
+    /// Removes expired temporary access records, notifying webhooks for each expiry.
+    pub async fn cleanup_expired_access(&self) -> CleanupStats {
+        let now = Utc::now();
+
+        let (scanned_accounts, expired_accounts) = {
+            let mut access_map = self.temporary_access.write();
+            let scanned = access_map.len();
+            let expired: Vec<AccountId32> = access_map
+                .iter()
+                .filter(|(_, record)| record.expires_at <= now)
+                .map(|(account, _)| account.clone())
+                .collect();
+            for account in &expired {
+                access_map.remove(account);
+            }
+            (scanned, expired)
+        };
+
+        let (scanned_ips, expired_ips) = {
+            let mut access_map = self.temporary_ips.write();
+            let scanned = access_map.len();
+            let expired: Vec<IpNetwork> = access_map
+                .iter()
+                .filter(|(_, record)| record.expires_at <= now)
+                .map(|(ip_network, _)| *ip_network)
+                .collect();
+            for ip_network in &expired {
+                access_map.remove(ip_network);
+            }
+            (scanned, expired)
+        };
+
+        let stats = CleanupStats {
+            scanned: scanned_accounts + scanned_ips,
+            expired: expired_accounts.len() + expired_ips.len(),
+        };
+        self.expired_total
+            .fetch_add(stats.expired as u64, Ordering::Relaxed);
+        if stats.expired > 0 {
+            self.invalidate_decisions();
+        }
+
+        for account in expired_accounts {
+            debug!(%account, "Cleaning up expired temporary access");
+            if let Err(e) = self.store.remove_temporary_access(&account) {
+                warn!(%account, error = %e, "Failed to remove persisted temporary access");
+            }
+            self.history.record(&account, AccessHistoryEvent::Expired);
+            self.notify_webhook(WebhookEvent::TemporaryAccessExpired { account })
+                .await;
+        }
+
+        for ip_network in expired_ips {
+            debug!(rule = %ip_network, "Cleaning up expired temporary IP access");
+            if let Err(e) = self.store.remove_temporary_ip(&ip_network) {
+                warn!(rule = %ip_network, error = %e, "Failed to remove persisted temporary IP access");
+            }
+            self.notify_webhook(WebhookEvent::TemporaryIpAccessExpired {
+                ip: ip_network.to_string(),
+            })
+            .await;
+        }
+
+        stats
+    }
+
+    /// Sends an `AccessExpiringSoon` event for each temporary grant ending within the
+    /// configured warning period, once per grant. Renewing a grant re-arms its warning.
+    /// Returns the number of warnings sent.
+    pub async fn notify_expiring_access(&self) -> usize {
+        let Some(warning) = self.expiry_warning else {
+            return 0;
+        };
+        let now = Utc::now();
+        let warning = chrono::Duration::from_std(warning).unwrap_or(chrono::Duration::MAX);
+        let horizon = now
+            .checked_add_signed(warning)
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        let expiring_soon = |record: &TemporaryAccessRecord| {
+            record.expires_at > now && record.expires_at <= horizon
+        };
+
+        let mut expiring: Vec<(String, DateTime<Utc>)> = self
+            .temporary_access
+            .read()
+            .iter()
+            .filter(|(_, record)| expiring_soon(record))
+            .map(|(account, record)| (account.to_string(), record.expires_at))
+            .collect();
+        expiring.extend(
+            self.temporary_ips
+                .read()
+                .iter()
+                .filter(|(_, record)| expiring_soon(record))
+                .map(|(ip_network, record)| (ip_network.to_string(), record.expires_at)),
+        );
+
+        let expiring: Vec<(String, DateTime<Utc>)> = {
+            let mut warned = self.expiry_warned.write();
+            warned.retain(|_, expires_at| *expires_at > now);
+            expiring
+                .into_iter()
+                .filter(|(source, expires_at)| {
+                    warned.insert(source.clone(), *expires_at) != Some(*expires_at)
+                })
+                .collect()
+        };
+
+        for (source, expires_at) in &expiring {
+            debug!(%source, %expires_at, "Temporary access expiring soon");
+            self.notify_webhook(WebhookEvent::AccessExpiringSoon {
+                source: source.clone(),
+                expires_at: *expires_at,
+            })
+            .await;
+        }
+        expiring.len()
+    }
+
+    /// Returns an account's access timeline, oldest entry first.
+    pub fn access_history(&self, account: &AccountId32) -> Vec<AccessHistoryEntry> {
+        self.history.get(account)
+    }
+
+    /// The per-IP rate-limit buckets, or `None` without a per-IP rate limit.
+    pub fn rate_limiter(&self) -> Option<Arc<RateLimiter>> {
+        self.rate_limiter.read().clone()
+    }
+
+    /// Tokens left in `ip`'s rate-limit bucket, or `None` without a per-IP rate limit.
+    pub fn rate_limit_remaining(&self, ip: &IpAddr) -> Option<u32> {
+        let limiter = self.rate_limiter.read().clone()?;
+        Some(limiter.remaining(ip))
+    }
+
+    /// The rate-limit tokens `account` may borrow: the configured loan if it holds an active
+    /// temporary grant at least `min_grant_secs` long, i.e. long-lived paid access, else 0.
+    pub fn burst_loan(&self, account: Option<&AccountId32>) -> u32 {
+        let (Some(config), Some(account)) = (&self.burst_loans, account) else {
+            return 0;
+        };
+        let now = Utc::now();
+        let min_grant = chrono::Duration::seconds(config.min_grant_secs as i64);
+        let eligible = self
+            .temporary_access
+            .read()
+            .get(account)
+            .is_some_and(|record| {
+                record.expires_at > now && record.expires_at - record.granted_at >= min_grant
+            });
+        if eligible { config.max_loan } else { 0 }
+    }
+
+    /// Reports settings changed at runtime by the `update_config` job.
+    pub async fn report_config_updated(&self, settings: Vec<String>) {
+        self.notify_webhook(WebhookEvent::ConfigUpdated { settings })
+            .await;
+    }
+
+    /// Sends each account's part of a monthly usage summary as a `MonthlyUsageSummary` event.
+    pub async fn report_monthly_usage(&self, summary: &MonthlySummary) {
+        for account in &summary.accounts {
+            self.notify_webhook(WebhookEvent::MonthlyUsageSummary {
+                account: account.account.clone(),
+                month: summary.month.clone(),
+                usage: account.counters,
+                top_methods: account.top_methods.clone(),
+            })
+            .await;
+        }
+    }
+
+    /// Reports a closed latency summary period as a `LatencySummary` event.
+    pub async fn report_latency_summary(&self, summary: LatencySummary) {
+        self.notify_webhook(WebhookEvent::LatencySummary {
+            period_start: summary.period_start,
+            period_end: summary.period_end,
+            methods: summary.methods,
+        })
+        .await;
+    }
+
+    /// Announces a backend's maintenance window as a `MaintenanceStarting` event.
+    pub async fn report_maintenance_starting(&self, window: &MaintenanceWindow) {
+        self.notify_webhook(WebhookEvent::MaintenanceStarting {
+            backend: window.backend.clone(),
+            starts_at: window.starts_at,
+            ends_at: window.ends_at,
+            reason: window.reason.clone(),
+        })
+        .await;
+    }
+
+    /// Reports the end of a backend's maintenance window as a `MaintenanceEnded` event.
+    pub async fn report_maintenance_ended(&self, window: &MaintenanceWindow) {
+        self.notify_webhook(WebhookEvent::MaintenanceEnded {
+            backend: window.backend.clone(),
+            starts_at: window.starts_at,
+            ends_at: window.ends_at,
+            reason: window.reason.clone(),
+        })
+        .await;
+    }
+
+    /// Reports a switch of the default route's backends as an `UpstreamSwitched` event.
+    pub async fn report_upstream_switched(&self, switch: &UpstreamSwitch, switched_by: &str) {
+        self.notify_webhook(WebhookEvent::UpstreamSwitched {
+            old_targets: switch.old_targets.clone(),
+            new_targets: switch.new_targets.clone(),
+            drain_secs: switch.drain_secs,
+            switched_by: switched_by.to_string(),
+        })
+        .await;
+    }
+
+    /// Delivers a closed day's statistics as a `DailyDigest` event.
+    pub async fn report_daily_digest(&self, digest: DailyDigest) {
+        self.notify_webhook(WebhookEvent::DailyDigest(digest)).await;
+    }
+
+    fn is_auto_banned(&self, ip: &IpAddr) -> bool {
+        self.auto_ban
+            .as_ref()
+            .is_some_and(|auto_ban| auto_ban.is_banned(ip))
+    }
+
+    /// Counts an offense by `ip` towards an automatic ban, banning it once it has committed
+    /// too many. Does nothing without `[firewall.auto_ban]`.
+    pub async fn record_offense(&self, ip: &IpAddr, offense: Offense) {
+        let Some(ban) = self
+            .auto_ban
+            .as_ref()
+            .and_then(|auto_ban| auto_ban.record(*ip, offense))
+        else {
+            return;
+        };
+        self.invalidate_decisions();
+        let ban_secs = (ban.expires_at - ban.banned_at).num_seconds().max(0) as u64;
+        warn!(
+            network = %ban.network,
+            ?offense,
+            offenses = ban.offenses,
+            ban_count = ban.ban_count,
+            ban_secs,
+            "Automatically banned repeat offender"
+        );
+        self.notify_webhook(WebhookEvent::AutoBanned {
+            source: ban.network.to_string(),
+            offense,
+            offenses: ban.offenses,
+            ban_secs,
+            expires_at: ban.expires_at,
+        })
+        .await;
+    }
+
+    /// Active automatic bans, soonest to expire first.
+    pub fn auto_bans(&self) -> Vec<AutoBan> {
+        self.auto_ban
+            .as_ref()
+            .map(|auto_ban| auto_ban.bans())
+            .unwrap_or_default()
+    }
+
+    /// Lifts an automatic ban early, returning whether `network` was banned. A later ban of
+    /// the same network still gets the longer, repeat-offense duration.
+    pub async fn lift_auto_ban(&self, network: &IpNetwork) -> bool {
+        let lifted = self
+            .auto_ban
+            .as_ref()
+            .is_some_and(|auto_ban| auto_ban.lift(network));
+        if !lifted {
+            return false;
+        }
+        self.invalidate_decisions();
+        info!(%network, "Lifted automatic ban");
+        self.notify_webhook(WebhookEvent::RuleRemoved {
+            rule_type: "AutoBan".to_string(),
+            value: network.to_string(),
+        })
+        .await;
+        true
+    }
+
+    /// Reports a grant on `plan` reaching `percent` of its request quota.
+    pub async fn report_quota_threshold(
+        &self,
+        source: String,
+        plan: &str,
+        percent: u8,
+        used: u64,
+        quota: u64,
+    ) {
+        info!(%source, plan, percent, used, quota, "Grant reached request quota threshold");
+        self.notify_webhook(WebhookEvent::QuotaThresholdReached {
+            source,
+            plan: plan.to_string(),
+            percent,
+            used,
+            quota,
+        })
+        .await;
+    }
+
+    /// Reports a request from `source` rejected by a rate limit.
+    pub async fn report_rate_limited(&self, source: String, retry_after: Duration) {
+        debug!(%source, ?retry_after, "Request rate limited");
+        self.notify_webhook(WebhookEvent::RateLimited {
+            source,
+            retry_after_secs: retry_after_secs(retry_after),
+        })
+        .await;
+    }
+
+    /// Reports a WebSocket connection refused because `source` reached its connection cap.
+    pub async fn report_connection_limit(&self, source: String, limit_type: &str, max: usize) {
+        debug!(%source, limit_type, max, "WebSocket connection limit reached");
+        self.notify_webhook(WebhookEvent::ConnectionLimitExceeded {
+            source,
+            limit_type: limit_type.to_string(),
+            max,
+        })
+        .await;
+    }
+
+    /// Reports a WebSocket session closed because `source` broke a per-connection message
+    /// limit.
+    pub async fn report_websocket_violation(
+        &self,
+        source: String,
+        limit_type: &str,
+        limit: String,
+    ) {
+        debug!(%source, limit_type, %limit, "WebSocket message limit exceeded");
+        self.websocket_violations_total
+            .fetch_add(1, Ordering::Relaxed);
+        self.notify_webhook(WebhookEvent::WebSocketLimitExceeded {
+            source,
+            limit_type: limit_type.to_string(),
+            limit,
+        })
+        .await;
+    }
+
+    /// Forwards a change in backend connectivity to subscribed webhooks.
+    pub async fn report_upstream_event(&self, event: UpstreamEvent) {
+        let event = match event {
+            UpstreamEvent::Connected { transport, target } => {
+                WebhookEvent::UpstreamConnected { transport, target }
+            }
+            UpstreamEvent::Disconnected {
+                transport,
+                target,
+                duration_secs,
+            } => WebhookEvent::UpstreamDisconnected {
+                transport,
+                target,
+                duration_secs,
+            },
+            UpstreamEvent::ConnectFailed {
+                transport,
+                target,
+                error,
+            } => WebhookEvent::UpstreamConnectFailed {
+                transport,
+                target,
+                error,
+            },
+            UpstreamEvent::HandshakeFailed {
+                transport,
+                target,
+                error,
+            } => WebhookEvent::UpstreamHandshakeFailed {
+                transport,
+                target,
+                error,
+            },
+        };
+        self.notify_webhook(event).await;
+    }
+
+    /// Drops idle rate-limit buckets.
+    pub fn prune_rate_limits(&self) {
+        if let Some(limiter) = self.rate_limiter.read().as_ref() {
+            limiter.prune();
+        }
+    }
+
+    /// Drops offense counts and ban histories no longer needed.
+    pub fn prune_auto_bans(&self) {
+        if let Some(auto_ban) = &self.auto_ban {
+            auto_ban.prune();
+        }
+    }
+
+    /// Drops expired cached decisions.
+    pub fn prune_decision_cache(&self) {
+        if let Some(cache) = &self.decision_cache {
+            cache.prune();
+        }
+    }
+
+    /// Registers a new webhook URL, optionally with a secret used to sign its deliveries and
+    /// scoped to the events concerning `account`. Registering a URL again replaces its secret
+    /// and events instead of adding a second subscription, unless it is scoped to another
+    /// account.
+    pub async fn add_webhook(
+        &self,
+        url: Url,
+        secret: Option<String>,
+        events: HashSet<WebhookEventType>,
+        account: Option<AccountId32>,
+    ) -> Result<()> {
+        debug!(%url, "Registering new webhook");
+        let (before, after) = {
+            let mut webhooks = self.webhooks.write();
+            let before = webhooks.len();
+            let target = WebhookTarget::new(url.clone(), secret, events, account);
+            match webhooks.iter_mut().find(|webhook| webhook.url == url) {
+                Some(existing) if existing.account != target.account => {
+                    return Err(Error::InvalidJobInput(format!(
+                        "Webhook {url} is already registered for another account"
+                    )));
+                }
+                Some(existing) => *existing = target,
+                None => {
+                    self.limits
+                        .webhooks
+                        .ensure_capacity(webhooks.len(), "Webhook")?;
+                    webhooks.push(target);
+                }
+            }
+            (before, webhooks.len())
+        };
+        self.record_gauges();
+        self.notify_webhook(WebhookEvent::WebhookRegistered { url })
+            .await;
+        self.check_rule_warning("Webhook", &self.limits.webhooks, before, after)
+            .await;
+        Ok(())
+    }
+
+    /// The next time a rule for `ip` or `account` changes by itself, without a rule change:
+    /// a temporary grant or automatic ban expiring, or an access window opening or closing.
+    fn next_rule_change(
+        &self,
+        ip: &IpAddr,
+        account: Option<&AccountId32>,
+    ) -> Option<DateTime<Utc>> {
+        let now = Utc::now();
+        let ip_grants: Vec<DateTime<Utc>> = self
+            .temporary_ips
+            .read()
+            .iter()
+            .filter(|(net, _)| network_contains(net, *ip))
+            .map(|(_, record)| record.expires_at)
+            .collect();
+        let ip_windows: Vec<DateTime<Utc>> = self
+            .allow_ips_dynamic
+            .read()
+            .iter()
+            .filter(|(net, _)| network_contains(net, *ip))
+            .filter_map(|(_, windows)| access_windows::next_change(windows, now))
+            .collect();
+        let account_grant = account.and_then(|account| {
+            self.temporary_access
+                .read()
+                .get(account)
+                .map(|record| record.expires_at)
+        });
+        let account_windows = account.and_then(|account| {
+            self.allow_accounts_dynamic
+                .read()
+                .get(account)
+                .and_then(|windows| access_windows::next_change(windows, now))
+        });
+        let auto_ban = self
+            .auto_ban
+            .as_ref()
+            .and_then(|auto_ban| auto_ban.ban_expiry(ip));
+        ip_grants
+            .into_iter()
+            .chain(ip_windows)
+            .chain(account_grant)
+            .chain(account_windows)
+            .chain(auto_ban)
+            .filter(|time| *time > now)
+            .min()
+    }
+
+    /// Returns true if a dynamic rule allows `ip` at `now`, within the rule's access windows.
+    fn dynamic_ip_rule_applies(&self, ip: &IpAddr, now: DateTime<Utc>) -> bool {
+        self.allow_ips_dynamic.read().iter().any(|(net, windows)| {
+            network_contains(net, *ip) && access_windows::is_open(windows, now)
+        })
+    }
+
+    /// Returns true if a dynamic rule allows `account` at `now`, within the rule's access
+    /// windows.
+    fn dynamic_account_rule_applies(&self, account: &AccountId32, now: DateTime<Utc>) -> bool {
+        self.allow_accounts_dynamic
+            .read()
+            .get(account)
+            .is_some_and(|windows| access_windows::is_open(windows, now))
+    }
+
+    /// Returns the permanent allow and deny rules.
+    pub fn rules(&self) -> FirewallRules {
+        let static_rules = self.static_rules();
+        FirewallRules {
+            allow_unrestricted_access: static_rules.allow_unrestricted_access,
+            config: RuleSet {
+                allow_ips: static_rules.allow_ips.iter().copied().collect(),
+                allow_accounts: static_rules.allow_accounts.iter().cloned().collect(),
+                deny_ips: static_rules.deny_ips.iter().copied().collect(),
+                deny_accounts: static_rules.deny_accounts.iter().cloned().collect(),
+            },
+            dynamic: RuleSet {
+                allow_ips: self.allow_ips_dynamic.read().keys().copied().collect(),
+                allow_accounts: self.allow_accounts_dynamic.read().keys().cloned().collect(),
+                deny_ips: self.deny_ips_dynamic.read().iter().copied().collect(),
+                deny_accounts: self.deny_accounts_dynamic.read().iter().cloned().collect(),
+            },
+            access_windows: self
+                .allow_ips_dynamic
+                .read()
+                .iter()
+                .map(|(ip_network, windows)| (ip_network.to_string(), windows))
+                .chain(
+                    self.allow_accounts_dynamic
+                        .read()
+                        .iter()
+                        .map(|(account, windows)| (account.to_string(), windows)),
+                )
+                .filter(|(_, windows)| !windows.is_empty())
+                .map(|(rule, windows)| (rule, windows.clone()))
+                .collect(),
+        }
+    }
+
+    /// Returns the temporary grants that haven't expired yet, soonest expiry first.
+    pub fn temporary_grants(&self) -> Vec<TemporaryGrant> {
+        let now = Utc::now();
+        let grant = |source: String, record: &TemporaryAccessRecord| {
+            (record.expires_at > now).then(|| TemporaryGrant {
+                source,
+                granted_at: record.granted_at,
+                expires_at: record.expires_at,
+                plan: record.plan.clone(),
+            })
+        };
+        let mut grants: Vec<TemporaryGrant> = self
+            .temporary_access
+            .read()
+            .iter()
+            .filter_map(|(account, record)| grant(account.to_string(), record))
+            .collect();
+        grants.extend(
+            self.temporary_ips
+                .read()
+                .iter()
+                .filter_map(|(ip_network, record)| grant(ip_network.to_string(), record)),
+        );
+        grants.sort_by_key(|grant| grant.expires_at);
+        grants
+    }
+
+    /// Returns the grant carrying the plan a request is served under, and its holder (account,
+    /// or IP/CIDR for unauthenticated requests). Requests also covered by a permanent rule or
+    /// unrestricted access aren't limited by a plan.
+    pub fn active_plan(
+        &self,
+        ip: &IpAddr,
+        account: Option<&AccountId32>,
+    ) -> Option<(String, String, TemporaryAccessRecord)> {
+        let static_rules = self.static_rules();
+        if static_rules.allow_unrestricted_access {
+            return None;
+        }
+        let now = Utc::now();
+        match account {
+            Some(account) => {
+                if static_rules.allow_accounts.contains(account)
+                    || self.dynamic_account_rule_applies(account, now)
+                {
+                    return None;
+                }
+                let record = self
+                    .temporary_access
+                    .read()
+                    .get(account)
+                    .filter(|record| record.expires_at > now)?
+                    .clone();
+                Some((account.to_string(), record.plan.clone()?, record))
+            }
+            None => {
+                if static_rules
+                    .allow_ips
+                    .iter()
+                    .any(|net| network_contains(net, *ip))
+                    || self.dynamic_ip_rule_applies(ip, now)
+                {
+                    return None;
+                }
+                self.temporary_ips
+                    .read()
+                    .iter()
+                    .filter(|(net, record)| network_contains(net, *ip) && record.expires_at > now)
+                    .find_map(|(net, record)| {
+                        Some((net.to_string(), record.plan.clone()?, record.clone()))
+                    })
+            }
+        }
+    }
+
+    /// Unregisters the webhook with `url`, returning it, or `None` if no webhook has that URL.
+    /// Deliveries already queued for it are still attempted.
+    pub async fn remove_webhook(&self, url: &Url) -> Option<WebhookTarget> {
+        let removed = {
+            let mut webhooks = self.webhooks.write();
+            let index = webhooks.iter().position(|webhook| &webhook.url == url)?;
+            webhooks.remove(index)
+        };
+        self.record_gauges();
+        debug!(%url, "Removed webhook");
+        self.notify_webhook(WebhookEvent::WebhookRemoved { url: url.clone() })
+            .await;
+        Some(removed)
+    }
+
+    /// Returns the registered webhooks. Secrets are not serialized.
+    pub fn webhooks(&self) -> Vec<WebhookTarget> {
+        self.webhooks.read().clone()
+    }
+
+    /// Returns an account's standing without recording access or emitting webhook events.
+    pub fn account_status(&self, account: &AccountId32) -> AccountAccessStatus {
+        let static_rules = self.static_rules();
+        let banned = static_rules.deny_accounts.contains(account)
+            || self.deny_accounts_dynamic.read().contains(account);
+        let now = Utc::now();
+        let permanent = static_rules.allow_accounts.contains(account)
+            || self.dynamic_account_rule_applies(account, now);
+        let temporary_expires_at = self
+            .temporary_access
+            .read()
+            .get(account)
+            .map(|record| record.expires_at)
+            .filter(|expires_at| *expires_at > now);
+        AccountAccessStatus {
+            allowed: !banned
+                && (static_rules.allow_unrestricted_access
+                    || permanent
+                    || temporary_expires_at.is_some()),
+            permanent,
+            temporary_expires_at,
+            banned,
+        }
+    }
+
+    /// Returns the current sizes of the dynamic allowlists, temporary grants and webhooks.
+    pub fn stats(&self) -> FirewallStats {
+        FirewallStats {
+            dynamic_ips: self.allow_ips_dynamic.read().len(),
+            dynamic_accounts: self.allow_accounts_dynamic.read().len(),
+            temporary_grants: self.temporary_access.read().len(),
+            temporary_ip_grants: self.temporary_ips.read().len(),
+            webhooks: self.webhooks.read().len(),
+            bans: self.deny_ips_dynamic.read().len() + self.deny_accounts_dynamic.read().len(),
+            auto_bans: self
+                .auto_ban
+                .as_ref()
+                .map_or(0, |auto_ban| auto_ban.active_bans()),
+            expired_total: self.expired_total.load(Ordering::Relaxed),
+            websocket_violations_total: self.websocket_violations_total.load(Ordering::Relaxed),
+            decision_budget_breaches: BudgetBreaches {
+                http: self.budget_breaches[AccessSurface::Http as usize].load(Ordering::Relaxed),
+                websocket: self.budget_breaches[AccessSurface::WebSocket as usize]
+                    .load(Ordering::Relaxed),
+                session_recheck: self.budget_breaches[AccessSurface::SessionRecheck as usize]
+                    .load(Ordering::Relaxed),
+            },
+            access_events_dropped: self.access_events.dropped(),
+            access_events_coalesced: self.access_events.coalesced(),
+        }
+    }
+
+    /// Emits a warning event if a collection growing from `before` to `after` entries just
+    /// passed its configured threshold.
+    async fn check_rule_warning(
+        &self,
+        rule_type: &str,
+        limit: &RuleLimit,
+        before: usize,
+        after: usize,
+    ) {
+        if !limit.crossed_warning(before, after) {
+            return;
+        }
+        let count = after;
+        let warn_at = limit.warn_at.unwrap_or_default();
+        tracing::warn!(
+            rule_type,
+            count,
+            warn_at,
+            "Firewall rule count exceeded warning threshold"
+        );
+        self.notify_webhook(WebhookEvent::RuleLimitExceeded {
+            rule_type: rule_type.to_string(),
+            count,
+            warn_at,
+        })
+        .await;
+    }
+
+    /// Sends an event notification to all registered webhooks.
+    pub(crate) async fn notify_webhook(&self, event: WebhookEvent) {
+        if let Some(audit) = &self.audit {
+            audit.record_event(&event);
+        }
+        if let Some(event_history) = &self.event_history {
+            event_history.record(&event);
+        }
+        if let Some(daily_digest) = &self.daily_digest {
+            daily_digest.observe(&event);
+        }
+
+        let targets: Vec<WebhookTarget> = self
+            .webhooks
+            .read()
+            .iter()
+            .filter(|target| target.is_subscribed(&event))
+            .cloned()
+            .collect();
+        if targets.is_empty() {
+            return;
+        }
+
+        let labels = event
+            .account()
+            .and_then(|account| self.labels.get(&account));
+        let body = match serde_json::to_vec(&WebhookPayload::new(&event, labels.as_deref())) {
+            Ok(body) => Arc::new(body),
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to serialize webhook event");
+                return;
+            }
+        };
+
+        for target in targets {
+            self.dispatcher.dispatch(target, body.clone());
+        }
+    }
+
+    /// Re-queues dead-lettered webhook deliveries. Deliveries for endpoints that are no longer
+    /// registered stay in the dead-letter queue. Returns the number of deliveries re-queued.
+    pub fn replay_dead_letters(&self) -> Result<usize> {
+        let dead_letters = self.dispatcher.dead_letters();
+        let letters = dead_letters.take_all()?;
+        let targets = self.webhooks.read().clone();
+
+        let mut replayed = 0;
+        for letter in letters {
+            match targets.iter().find(|target| target.url == letter.url) {
+                Some(target) => {
+                    let body = Arc::new(serde_json::to_vec(&letter.event)?);
+                    self.dispatcher.dispatch(target.clone(), body);
+                    replayed += 1;
+                }
+                None => {
+                    warn!(url = %letter.url, "Keeping dead-lettered webhook for unregistered endpoint");
+                    dead_letters.push(&letter)?;
+                }
+            }
+        }
+        Ok(replayed)
+    }
+}
+
+/// `current` extended by `duration` if it is still active at `now`, keeping its plan and
+/// counting the extension so its quota stacks; otherwise a new grant on `plan` from `now`.
+fn extend_grant(
+    current: Option<&TemporaryAccessRecord>,
+    now: DateTime<Utc>,
+    duration: Duration,
+    plan: Option<String>,
+) -> Result<TemporaryAccessRecord> {
+    let end = |from: DateTime<Utc>| {
+        chrono::Duration::from_std(duration)
+            .ok()
+            .and_then(|duration| from.checked_add_signed(duration))
+            .ok_or_else(|| Error::InvalidJobInput("Duration too long".to_string()))
+    };
+    Ok(match current.filter(|record| record.expires_at > now) {
+        Some(record) => TemporaryAccessRecord {
+            granted_at: record.granted_at,
+            expires_at: end(record.expires_at)?,
+            plan: record.plan.clone(),
+            extensions: record.extensions.saturating_add(1),
+        },
+        None => TemporaryAccessRecord {
+            granted_at: now,
+            expires_at: end(now)?,
+            plan,
+            extensions: 0,
+        },
+    })
+}
+
+/// Sets `current` to `value` unless they are equal. Returns whether it changed.
+fn replace_if_changed<T: PartialEq>(current: &RwLock<T>, value: T) -> bool {
+    let mut current = current.write();
+    if *current == value {
+        return false;
+    }
+    *current = value;
+    true
+}
+
+/// The address an IPv4-mapped IPv6 address (`::ffff:1.2.3.4`) stands for, as clients of a
+/// dual-stack `[::]` listener arrive with. Any other address is returned as it is.
+pub fn canonical_ip(ip: IpAddr) -> IpAddr {
+    ip.to_canonical()
+}
+
+/// The IPv4 network an IPv4-mapped IPv6 network (`::ffff:10.0.0.0/104`) covers (`10.0.0.0/8`),
+/// so a rule is stored and reported the same whichever way it was written. Any other network is
+/// returned as it is.
+pub fn canonical_network(network: IpNetwork) -> IpNetwork {
+    match network {
+        IpNetwork::V6(v6) if v6.prefix() >= 96 => match v6.ip().to_ipv4_mapped() {
+            Some(ip) => Ipv4Network::new(ip, v6.prefix() - 96)
+                .map(IpNetwork::V4)
+                .unwrap_or(network),
+            None => network,
+        },
+        _ => network,
+    }
+}
+
+/// Whether a rule's network covers `ip`, with IPv4-mapped IPv6 addresses and networks on either
+/// side matching their IPv4 counterparts, so IPv4 and IPv6 rules can be mixed freely.
+pub fn network_contains(network: &IpNetwork, ip: IpAddr) -> bool {
+    canonical_network(*network).contains(canonical_ip(ip))
+}
+
+/// Rounds a retry delay up to whole seconds, as used by the `Retry-After` header.
+pub fn retry_after_secs(retry_after: Duration) -> u64 {
+    let secs = retry_after.as_secs();
+    if retry_after.subsec_nanos() > 0 {
+        secs.saturating_add(1)
+    } else {
+        secs
     }
 }
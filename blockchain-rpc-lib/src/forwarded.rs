@@ -0,0 +1,224 @@
+use crate::firewall::{canonical_ip, network_contains};
+use axum::Extension;
+use axum::http::HeaderMap;
+use axum::http::header::FORWARDED;
+use axum::middleware::AddExtension;
+use axum_server::accept::Accept;
+use futures::future::BoxFuture;
+use ipnetwork::IpNetwork;
+use std::collections::HashSet;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+use tower::Layer;
+
+/// How long a trusted proxy gets to send the PROXY protocol header of a new connection.
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest PROXY protocol v1 header, including the trailing CRLF.
+const MAX_V1_HEADER_BYTES: usize = 107;
+/// Signature opening every PROXY protocol v2 header.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The client address a trusted proxy reported in the PROXY protocol header of a connection,
+/// attached to every request on it. `None` for health checks the proxy sends on its own behalf.
+#[derive(Debug, Clone, Copy)]
+pub struct ProxiedAddr(pub Option<SocketAddr>);
+
+/// Resolves the address of the client behind any trusted proxies.
+///
+/// `peer` is the address the connection came from. As long as the current hop is one of
+/// `trusted`, the next address to the left in `Forwarded` (or, without it, `X-Forwarded-For`)
+/// is taken as the one it received the request from. The first untrusted hop is the client;
+/// addresses further left were supplied by the client and can't be believed. IPv4-mapped IPv6
+/// addresses are resolved to the IPv4 address, as [`canonical_ip`] does.
+pub fn client_addr(
+    peer: SocketAddr,
+    headers: &HeaderMap,
+    trusted: &HashSet<IpNetwork>,
+) -> SocketAddr {
+    let is_trusted = |ip: IpAddr| trusted.iter().any(|net| network_contains(net, ip));
+    let peer_ip = canonical_ip(peer.ip());
+    if !is_trusted(peer_ip) {
+        return SocketAddr::new(peer_ip, peer.port());
+    }
+
+    let hops = if headers.contains_key(FORWARDED) {
+        forwarded_hops(headers)
+    } else {
+        x_forwarded_for_hops(headers)
+    };
+    let mut client = peer_ip;
+    for hop in hops.into_iter().rev() {
+        match hop.filter(|_| is_trusted(client)) {
+            Some(ip) => client = canonical_ip(ip),
+            None => break,
+        }
+    }
+    SocketAddr::new(client, peer.port())
+}
+
+/// The `for=` addresses of all `Forwarded` headers, in order. Obfuscated identifiers and
+/// `unknown` are kept as `None`, ending the walk through trusted hops.
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all(FORWARDED)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|element| {
+            element
+                .split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                .and_then(|(_, node)| parse_node(node.trim().trim_matches('"')))
+        })
+        .collect()
+}
+
+/// The addresses of all `X-Forwarded-For` headers, in order.
+fn x_forwarded_for_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|hop| parse_node(hop.trim()))
+        .collect()
+}
+
+/// Parses a forwarded node: an IP address, optionally with a port (`[v6]:port`, `v4:port`).
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Ok(ip) = node.parse() {
+        return Some(ip);
+    }
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    node.parse::<SocketAddr>().ok().map(|addr| addr.ip())
+}
+
+/// Wraps the listener's acceptor to read the PROXY protocol header (v1 or v2) that trusted
+/// load balancers send ahead of each connection, attaching the reported client address as a
+/// [`ProxiedAddr`] extension.
+///
+/// Connections from other peers are passed through untouched, so a client can't spoof its
+/// address by sending a header of its own.
+#[derive(Clone)]
+pub struct ProxyProtocolAcceptor<A> {
+    inner: A,
+    trusted: Arc<HashSet<IpNetwork>>,
+}
+
+impl<A> ProxyProtocolAcceptor<A> {
+    pub fn new(inner: A, trusted: HashSet<IpNetwork>) -> Self {
+        Self {
+            inner,
+            trusted: Arc::new(trusted),
+        }
+    }
+}
+
+impl<A, S> Accept<TcpStream, S> for ProxyProtocolAcceptor<A>
+where
+    A: Accept<TcpStream, AddExtension<S, ProxiedAddr>> + Clone + Send + Sync + 'static,
+    A::Future: Send,
+    S: Send + 'static,
+{
+    type Stream = A::Stream;
+    type Service = A::Service;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, mut stream: TcpStream, service: S) -> Self::Future {
+        let inner = self.inner.clone();
+        let trusted = self.trusted.clone();
+        Box::pin(async move {
+            let peer = stream.peer_addr()?;
+            let source = if trusted.iter().any(|net| network_contains(net, peer.ip())) {
+                tokio::time::timeout(PROXY_HEADER_TIMEOUT, read_proxy_header(&mut stream))
+                    .await
+                    .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??
+            } else {
+                None
+            };
+            inner
+                .accept(stream, Extension(ProxiedAddr(source)).layer(service))
+                .await
+        })
+    }
+}
+
+/// Reads a PROXY protocol header off a fresh connection, consuming exactly its bytes. Returns
+/// the source address, or `None` for `UNKNOWN`/`LOCAL` connections and unsupported families.
+async fn read_proxy_header(stream: &mut TcpStream) -> io::Result<Option<SocketAddr>> {
+    let mut header = vec![0u8; 5];
+    stream.read_exact(&mut header).await?;
+    if header == b"PROXY" {
+        while !header.ends_with(b"\r\n") {
+            if header.len() >= MAX_V1_HEADER_BYTES {
+                return Err(invalid_header("PROXY v1 header too long"));
+            }
+            header.push(stream.read_u8().await?);
+        }
+        return parse_v1(&header);
+    }
+    if header[..] != V2_SIGNATURE[..5] {
+        return Err(invalid_header("missing PROXY protocol header"));
+    }
+
+    header.resize(16, 0);
+    stream.read_exact(&mut header[5..]).await?;
+    if header[..12] != V2_SIGNATURE || header[12] >> 4 != 2 {
+        return Err(invalid_header("invalid PROXY v2 header"));
+    }
+    let len = usize::from(u16::from_be_bytes([header[14], header[15]]));
+    let mut addresses = vec![0u8; len];
+    stream.read_exact(&mut addresses).await?;
+    // Command 0 (LOCAL) is a connection the proxy makes on its own behalf.
+    if header[12] & 0x0f == 0 {
+        return Ok(None);
+    }
+    Ok(parse_v2_addresses(header[13] >> 4, &addresses))
+}
+
+fn parse_v1(header: &[u8]) -> io::Result<Option<SocketAddr>> {
+    let line =
+        std::str::from_utf8(header).map_err(|_| invalid_header("invalid PROXY v1 header"))?;
+    let fields: Vec<&str> = line.trim_end().split(' ').collect();
+    match fields.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", "TCP4" | "TCP6", source, _, port, _] => {
+            let ip: IpAddr = source
+                .parse()
+                .map_err(|_| invalid_header("invalid PROXY v1 source address"))?;
+            let port = port
+                .parse()
+                .map_err(|_| invalid_header("invalid PROXY v1 source port"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid_header("invalid PROXY v1 header")),
+    }
+}
+
+/// The source address of a v2 header's address block, for the IPv4 (1) and IPv6 (2) families.
+fn parse_v2_addresses(family: u8, addresses: &[u8]) -> Option<SocketAddr> {
+    match family {
+        1 if addresses.len() >= 12 => {
+            let ip: [u8; 4] = addresses[..4].try_into().ok()?;
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+            Some(SocketAddr::new(Ipv4Addr::from(ip).into(), port))
+        }
+        2 if addresses.len() >= 36 => {
+            let ip: [u8; 16] = addresses[..16].try_into().ok()?;
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+            Some(SocketAddr::new(Ipv6Addr::from(ip).into(), port))
+        }
+        _ => None,
+    }
+}
+
+fn invalid_header(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
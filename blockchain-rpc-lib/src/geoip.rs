@@ -0,0 +1,36 @@
+use crate::Result;
+use crate::error::Error;
+use maxminddb::{Reader, geoip2};
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Country lookups in a MaxMind GeoLite2/GeoIP2 Country (or City) database.
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+impl std::fmt::Debug for GeoIp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GeoIp")
+            .field("database_type", &self.reader.metadata.database_type)
+            .finish()
+    }
+}
+
+impl GeoIp {
+    /// Loads the database at `path` into memory.
+    pub fn open(path: &Path) -> Result<Self> {
+        let reader = Reader::open_readfile(path)
+            .map_err(|e| Error::GeoIpError(format!("Failed to open {}: {e}", path.display())))?;
+        Ok(GeoIp { reader })
+    }
+
+    /// The ISO 3166-1 alpha-2 code of the country `ip` is located in, if the database knows it.
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        let record: geoip2::Country = self.reader.lookup(ip).ok()?;
+        record
+            .country?
+            .iso_code
+            .map(|code| code.to_ascii_uppercase())
+    }
+}
@@ -0,0 +1,376 @@
+use crate::config::HeadCacheConfig;
+use crate::proxy::upstream::Upstream;
+use crate::upstream_probe::parse_chain_number;
+use chrono::{DateTime, Utc};
+use futures::{SinkExt, StreamExt};
+use parking_lot::RwLock;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::Notify;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// How long to wait before resubscribing after the subscription ended or failed.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+type BackendSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// A call answered from the cache: its method and its params as compact JSON, `[]` when the
+/// client sent none.
+type CallKey = (String, String);
+
+/// Which head subscription the backend accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum HeadSource {
+    /// `eth_subscribe(newHeads)`.
+    Evm,
+    /// `chain_subscribeNewHeads` and `chain_subscribeFinalizedHeads`.
+    Substrate,
+}
+
+/// The cache's view of the chain, for the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct HeadCacheStatus {
+    /// `None` while not subscribed.
+    pub source: Option<HeadSource>,
+    pub latest_block: Option<u64>,
+    pub finalized_block: Option<u64>,
+    /// When the latest head arrived.
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Calls answered from the cache since startup.
+    pub hits: u64,
+}
+
+#[derive(Debug, Default)]
+struct Heads {
+    source: Option<HeadSource>,
+    latest_block: Option<u64>,
+    finalized_block: Option<u64>,
+    /// Results valid for the current heads, replaced as new ones arrive.
+    results: HashMap<CallKey, Value>,
+    updated: Option<(Instant, DateTime<Utc>)>,
+}
+
+/// Keeps a head subscription open to the default backend and answers head queries from it,
+/// so clients polling for new blocks don't each reach the backend:
+///
+/// - EVM: `eth_blockNumber`, and `eth_getBlockByNumber("finalized", false)`, fetched once per
+///   new head.
+/// - Substrate: `chain_getHeader` for the best block, `system_syncState`, fetched once per new
+///   head, and `chain_getFinalizedHead`, fetched once per finalized head.
+///
+/// Nothing is served while the subscription is down or its latest head is older than
+/// `max_age_secs`; calls then go to the backend as usual.
+#[derive(Debug)]
+pub struct HeadCache {
+    max_age: Duration,
+    heads: RwLock<Heads>,
+    hits: AtomicU64,
+    resubscribe: Notify,
+}
+
+impl HeadCache {
+    pub fn new(config: &HeadCacheConfig) -> Self {
+        HeadCache {
+            max_age: Duration::from_secs(config.max_age_secs.max(1)),
+            heads: RwLock::new(Heads::default()),
+            hits: AtomicU64::new(0),
+            resubscribe: Notify::new(),
+        }
+    }
+
+    /// Follows the backend's heads until `shutdown`, resubscribing whenever the connection
+    /// drops.
+    pub(crate) async fn run(&self, upstream: &Upstream, shutdown: CancellationToken) {
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = self.resubscribe.notified() => {
+                    info!("Backend changed, resubscribing to heads");
+                }
+                result = self.follow(upstream) => match result {
+                    Ok(()) => info!("Backend closed the head subscription"),
+                    Err(e) => warn!(error = %e, "Head subscription failed"),
+                },
+            }
+            *self.heads.write() = Heads::default();
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = tokio::time::sleep(RESUBSCRIBE_DELAY) => {}
+            }
+        }
+    }
+
+    /// Drops the current subscription so the next one is made to the backend now configured.
+    pub(crate) fn resubscribe(&self) {
+        self.resubscribe.notify_one();
+    }
+
+    /// The JSON-RPC response to `body` if it is a single call the cache can answer.
+    pub(crate) fn answer(&self, body: &[u8]) -> Option<Vec<u8>> {
+        let Ok(Value::Object(call)) = serde_json::from_slice::<Value>(body) else {
+            return None;
+        };
+        let method = call.get("method")?.as_str()?;
+        let params = match call.get("params") {
+            None | Some(Value::Null) => "[]".to_string(),
+            Some(params) => params.to_string(),
+        };
+        let result = {
+            let heads = self.heads.read();
+            let (received, _) = heads.updated?;
+            if received.elapsed() > self.max_age {
+                return None;
+            }
+            heads.results.get(&(method.to_string(), params))?.clone()
+        };
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        let response = json!({
+            "jsonrpc": "2.0",
+            "id": call.get("id").cloned().unwrap_or(Value::Null),
+            "result": result,
+        });
+        Some(response.to_string().into_bytes())
+    }
+
+    pub fn status(&self) -> HeadCacheStatus {
+        let heads = self.heads.read();
+        HeadCacheStatus {
+            source: heads.source,
+            latest_block: heads.latest_block,
+            finalized_block: heads.finalized_block,
+            updated_at: heads.updated.map(|(_, at)| at),
+            hits: self.hits.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Subscribes to the backend's heads and keeps the cache current until the connection
+    /// closes.
+    async fn follow(&self, upstream: &Upstream) -> Result<(), String> {
+        let (socket, _connection) = upstream
+            .connect_websocket(None, None, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        let mut subscription = Subscription {
+            socket,
+            next_id: 0,
+            pending: HashMap::new(),
+        };
+        subscription
+            .request(Pending::EvmSubscribe, "eth_subscribe", json!(["newHeads"]))
+            .await?;
+
+        while let Some(message) = subscription.socket.next().await {
+            let Message::Text(text) = message.map_err(|e| format!("failed to receive: {e}"))?
+            else {
+                continue;
+            };
+            let Ok(message) = serde_json::from_str::<Value>(&text) else {
+                continue;
+            };
+            let head = message.pointer("/params/result");
+            match (message.get("method").and_then(Value::as_str), head) {
+                (Some("eth_subscription"), Some(head)) => {
+                    self.evm_head(&mut subscription, head).await?
+                }
+                (Some("chain_newHead"), Some(head)) => {
+                    self.substrate_head(&mut subscription, head).await?
+                }
+                (Some("chain_finalizedHead"), Some(head)) => {
+                    self.substrate_finalized_head(&mut subscription, head)
+                        .await?
+                }
+                (Some(_), _) => {}
+                (None, _) => self.response(&mut subscription, &message).await?,
+            }
+        }
+        Ok(())
+    }
+
+    async fn response(
+        &self,
+        subscription: &mut Subscription,
+        message: &Value,
+    ) -> Result<(), String> {
+        let Some(pending) = message
+            .get("id")
+            .and_then(Value::as_u64)
+            .and_then(|id| subscription.pending.remove(&id))
+        else {
+            return Ok(());
+        };
+        let result = message
+            .get("result")
+            .filter(|_| message.get("error").is_none());
+        match (pending, result) {
+            (Pending::EvmSubscribe, Some(_)) => self.subscribed(HeadSource::Evm),
+            // Not an EVM node; try Substrate's head subscriptions instead
+            (Pending::EvmSubscribe, None) => {
+                subscription
+                    .request(
+                        Pending::SubstrateSubscribe,
+                        "chain_subscribeNewHeads",
+                        json!([]),
+                    )
+                    .await?;
+                subscription
+                    .request(
+                        Pending::SubstrateSubscribe,
+                        "chain_subscribeFinalizedHeads",
+                        json!([]),
+                    )
+                    .await?;
+            }
+            (Pending::SubstrateSubscribe, Some(_)) => self.subscribed(HeadSource::Substrate),
+            (Pending::SubstrateSubscribe, None) => {
+                return Err(format!(
+                    "backend supports neither eth_subscribe nor chain_subscribeNewHeads: {}",
+                    message.get("error").unwrap_or(&Value::Null)
+                ));
+            }
+            (Pending::Result(key), Some(result)) => {
+                self.heads.write().results.insert(key, result.clone());
+            }
+            (Pending::Result((method, _)), None) => {
+                debug!(%method, "Backend didn't answer a head cache query");
+            }
+        }
+        Ok(())
+    }
+
+    fn subscribed(&self, source: HeadSource) {
+        let mut heads = self.heads.write();
+        if heads.source != Some(source) {
+            info!(?source, "Subscribed to backend heads");
+            heads.source = Some(source);
+        }
+    }
+
+    async fn evm_head(&self, subscription: &mut Subscription, head: &Value) -> Result<(), String> {
+        let Some(number) = head.get("number") else {
+            return Ok(());
+        };
+        {
+            let mut heads = self.heads.write();
+            heads.latest_block = number.as_str().and_then(parse_chain_number);
+            heads.updated = Some((Instant::now(), Utc::now()));
+            heads.results.clear();
+            heads
+                .results
+                .insert(call_key("eth_blockNumber", json!([])), number.clone());
+        }
+        let finalized = json!(["finalized", false]);
+        subscription
+            .request(
+                Pending::Result(call_key("eth_getBlockByNumber", finalized.clone())),
+                "eth_getBlockByNumber",
+                finalized,
+            )
+            .await
+    }
+
+    async fn substrate_head(
+        &self,
+        subscription: &mut Subscription,
+        head: &Value,
+    ) -> Result<(), String> {
+        {
+            let mut heads = self.heads.write();
+            heads.latest_block = head
+                .get("number")
+                .and_then(Value::as_str)
+                .and_then(parse_chain_number);
+            heads.updated = Some((Instant::now(), Utc::now()));
+            heads
+                .results
+                .remove(&call_key("system_syncState", json!([])));
+            for params in [json!([]), json!([null])] {
+                heads
+                    .results
+                    .insert(call_key("chain_getHeader", params), head.clone());
+            }
+        }
+        subscription
+            .request(
+                Pending::Result(call_key("system_syncState", json!([]))),
+                "system_syncState",
+                json!([]),
+            )
+            .await
+    }
+
+    async fn substrate_finalized_head(
+        &self,
+        subscription: &mut Subscription,
+        head: &Value,
+    ) -> Result<(), String> {
+        let Some(number) = head.get("number") else {
+            return Ok(());
+        };
+        {
+            let mut heads = self.heads.write();
+            heads.finalized_block = number.as_str().and_then(parse_chain_number);
+            heads
+                .results
+                .remove(&call_key("chain_getFinalizedHead", json!([])));
+        }
+        // Headers don't carry their own hash; ask for the finalized block's
+        subscription
+            .request(
+                Pending::Result(call_key("chain_getFinalizedHead", json!([]))),
+                "chain_getBlockHash",
+                json!([number]),
+            )
+            .await
+    }
+}
+
+/// What a request sent on the subscription's connection was for.
+#[derive(Debug)]
+enum Pending {
+    /// `eth_subscribe(newHeads)`; an error means the backend isn't an EVM node.
+    EvmSubscribe,
+    SubstrateSubscribe,
+    /// A result to cache under the call it answers.
+    Result(CallKey),
+}
+
+/// The backend connection carrying the head subscription, with the requests it is waiting on.
+struct Subscription {
+    socket: BackendSocket,
+    next_id: u64,
+    pending: HashMap<u64, Pending>,
+}
+
+impl Subscription {
+    async fn request(
+        &mut self,
+        pending: Pending,
+        method: &str,
+        params: Value,
+    ) -> Result<(), String> {
+        self.next_id += 1;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id,
+            "method": method,
+            "params": params,
+        });
+        self.socket
+            .send(Message::text(request.to_string()))
+            .await
+            .map_err(|e| format!("failed to send {method}: {e}"))?;
+        self.pending.insert(self.next_id, pending);
+        Ok(())
+    }
+}
+
+fn call_key(method: &str, params: Value) -> CallKey {
+    (method.to_string(), params.to_string())
+}
@@ -0,0 +1,198 @@
+use crate::Result;
+use crate::circuit_breaker::CircuitState;
+use crate::config::{HealthConfig, RpcConfig};
+use crate::context::SecureRpcContext;
+use crate::proxy::upstream::AdmissionStats;
+use crate::store::StateStore;
+use crate::upstream_probe::{call, probe_client};
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+use url::Url;
+
+/// State store namespace read from to check the store answers.
+const STORE_CHECK_NAMESPACE: &str = "health";
+
+/// Backend checks older than this many intervals count as failed, in case checking stalled.
+const STALE_CHECK_INTERVALS: u32 = 3;
+
+#[derive(Debug, Clone)]
+struct Check {
+    at: Instant,
+    checked_at: DateTime<Utc>,
+    backend_error: Option<String>,
+    store_error: Option<String>,
+}
+
+/// What `/readyz` reports. The gateway is ready when every check passes.
+#[derive(Debug, Clone, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    /// The default backend answered the last check and its circuit breaker isn't open.
+    pub backend_reachable: bool,
+    /// Why the last backend check failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend_error: Option<String>,
+    /// The backend is on `rpc.expected_chain_id`, or none is configured.
+    pub chain_verified: bool,
+    /// The firewall's rules are loaded and the state store holding them answers.
+    pub firewall_loaded: bool,
+    /// Requests in flight to the backends are at `overload_percent` of their concurrency
+    /// limit or above.
+    pub overloaded: bool,
+    /// When the backend and the state store were last checked.
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+/// Checks the default backend and the state store every `interval_secs` for the gateway's
+/// `/readyz`, so probes from Kubernetes or a load balancer never reach the backend themselves.
+#[derive(Debug)]
+pub struct HealthChecker {
+    client: reqwest::Client,
+    url: RwLock<Url>,
+    interval: Duration,
+    overload_percent: u8,
+    last: RwLock<Option<Check>>,
+}
+
+impl HealthChecker {
+    pub fn new(rpc: &RpcConfig, config: &HealthConfig) -> Result<Self> {
+        let (client, url) = probe_client(rpc, Duration::from_secs(config.timeout_secs.max(1)))?;
+        Ok(HealthChecker {
+            client,
+            url: RwLock::new(url),
+            interval: Duration::from_secs(config.interval_secs.max(1)),
+            overload_percent: config.overload_percent.clamp(1, 100),
+            last: RwLock::new(None),
+        })
+    }
+
+    /// Checks now and then every `interval_secs` until `shutdown`.
+    /// Returns true if the backend answered the last check, and it is recent.
+    pub fn backend_check_passed(&self) -> bool {
+        self.recent_check()
+            .is_some_and(|check| check.backend_error.is_none())
+    }
+
+    fn recent_check(&self) -> Option<Check> {
+        self.last
+            .read()
+            .clone()
+            .filter(|check| check.at.elapsed() <= self.interval * STALE_CHECK_INTERVALS)
+    }
+
+    pub async fn run(&self, store: Arc<dyn StateStore>, shutdown: CancellationToken) {
+        loop {
+            self.check(store.as_ref()).await;
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = tokio::time::sleep(self.interval) => {}
+            }
+        }
+    }
+
+    /// Checks the backend now configured from now on.
+    pub fn retarget(&self, rpc: &RpcConfig) -> Result<()> {
+        let (_, url) = probe_client(rpc, Duration::ZERO)?;
+        *self.url.write() = url;
+        Ok(())
+    }
+
+    pub fn readiness(&self, ctx: &SecureRpcContext) -> Readiness {
+        let last = self.recent_check();
+        let circuit_open = ctx
+            .circuit_breaker
+            .as_ref()
+            .is_some_and(|breaker| breaker.state() == CircuitState::Open);
+        let backend_reachable = !circuit_open
+            && last
+                .as_ref()
+                .is_some_and(|check| check.backend_error.is_none());
+        let backend_error = match (&last, circuit_open) {
+            (_, true) => Some("circuit breaker open".to_string()),
+            (Some(check), false) => check.backend_error.clone(),
+            (None, false) => Some("not checked recently".to_string()),
+        };
+        let chain_verified = ctx
+            .upstream_probe
+            .as_ref()
+            .is_none_or(|probe| probe.is_chain_verified());
+        let firewall_loaded = last
+            .as_ref()
+            .is_some_and(|check| check.store_error.is_none());
+        let overloaded = ctx
+            .global_concurrency
+            .as_ref()
+            .map(|limit| limit.stats())
+            .into_iter()
+            .chain(ctx.upstream.admission_stats())
+            .any(|stats| self.is_overloaded(&stats));
+        Readiness {
+            ready: backend_reachable && chain_verified && firewall_loaded && !overloaded,
+            backend_reachable,
+            backend_error,
+            chain_verified,
+            firewall_loaded,
+            overloaded,
+            checked_at: last.map(|check| check.checked_at),
+        }
+    }
+
+    async fn check(&self, store: &dyn StateStore) {
+        let url = self.url.read().clone();
+        // Any head query answered will do; EVM nodes answer the first, Substrate nodes the second
+        let backend_error = match call(&self.client, &url, "eth_blockNumber", json!([])).await {
+            Ok(_) => None,
+            Err(_) => call(&self.client, &url, "chain_getHeader", json!([]))
+                .await
+                .err(),
+        };
+        let store_error = store
+            .get(STORE_CHECK_NAMESPACE, b"check")
+            .err()
+            .map(|e| e.to_string());
+        match (&backend_error, &store_error) {
+            (None, None) => debug!("Readiness checks passed"),
+            _ => warn!(?backend_error, ?store_error, "Readiness checks failed"),
+        }
+        *self.last.write() = Some(Check {
+            at: Instant::now(),
+            checked_at: Utc::now(),
+            backend_error,
+            store_error,
+        });
+    }
+
+    fn is_overloaded(&self, stats: &AdmissionStats) -> bool {
+        stats.in_flight * 100 >= stats.max_concurrent_requests * usize::from(self.overload_percent)
+    }
+}
+
+/// `GET /healthz`: the process is up and serving requests.
+pub(crate) async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// `GET /readyz`: `200` when the gateway is ready for traffic, `503` otherwise, with the
+/// checks' results either way.
+pub(crate) async fn readyz(State(ctx): State<Arc<SecureRpcContext>>) -> Response {
+    let Some(health) = &ctx.health else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let readiness = health.readiness(&ctx);
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness)).into_response()
+}
@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use sp_runtime::AccountId32;
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AccessHistoryEvent {
+    /// Permanent access added by a job.
+    PermanentGranted,
+    /// Temporary access granted (or replaced) until `expires_at`.
+    TemporaryGranted { expires_at: DateTime<Utc> },
+    /// Temporary access lapsed.
+    Expired,
+    /// Permanent access removed by a job.
+    Revoked,
+    /// Account added to the deny list.
+    Banned,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessHistoryEntry {
+    pub at: DateTime<Utc>,
+    pub event: AccessHistoryEvent,
+}
+
+/// Bounded per-account timeline of access changes.
+///
+/// Only the most recent `max_entries_per_account` entries are kept for each account.
+#[derive(Debug)]
+pub struct AccessHistory {
+    max_entries_per_account: usize,
+    entries: RwLock<HashMap<AccountId32, VecDeque<AccessHistoryEntry>>>,
+}
+
+impl AccessHistory {
+    pub fn new(max_entries_per_account: usize) -> Self {
+        AccessHistory {
+            max_entries_per_account,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Appends an event to an account's timeline, evicting the oldest entry if it is full.
+    pub fn record(&self, account: &AccountId32, event: AccessHistoryEvent) {
+        if self.max_entries_per_account == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.write();
+        let timeline = entries.entry(account.clone()).or_default();
+        if timeline.len() >= self.max_entries_per_account {
+            timeline.pop_front();
+        }
+        timeline.push_back(AccessHistoryEntry {
+            at: Utc::now(),
+            event,
+        });
+    }
+
+    /// Returns an account's timeline, oldest entry first.
+    pub fn get(&self, account: &AccountId32) -> Vec<AccessHistoryEntry> {
+        self.entries
+            .read()
+            .get(account)
+            .map(|timeline| timeline.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
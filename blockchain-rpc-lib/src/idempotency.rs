@@ -0,0 +1,147 @@
+use crate::Result;
+use crate::store::StateStore;
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tracing::warn;
+
+/// How long a processed idempotency key is remembered.
+pub const IDEMPOTENCY_KEY_TTL_SECS: i64 = 7 * 24 * 60 * 60;
+
+const IDEMPOTENCY_NAMESPACE: &str = "idempotency_keys";
+
+/// A job ID and an idempotency key submitted with it.
+type KeyId = (u64, String);
+
+#[derive(Debug, Default)]
+struct Keys {
+    processed: HashMap<KeyId, DateTime<Utc>>,
+    /// Keys of calls being applied right now.
+    in_flight: HashSet<KeyId>,
+}
+
+/// Remembers idempotency keys of successfully processed mutation jobs, so retried or
+/// duplicated job deliveries are not applied twice.
+///
+/// Keys are scoped per job ID and persisted in the state store, so duplicates delivered after
+/// a restart are skipped too.
+#[derive(Debug)]
+pub struct IdempotencyStore {
+    store: Arc<dyn StateStore>,
+    keys: Mutex<Keys>,
+}
+
+impl IdempotencyStore {
+    /// Loads the keys persisted in `store`. Entries that fail to decode are skipped with a
+    /// warning.
+    pub fn load(store: Arc<dyn StateStore>) -> Result<Self> {
+        let mut processed = HashMap::new();
+        for (key, value) in store.entries(IDEMPOTENCY_NAMESPACE)? {
+            let id = decode_key(&key);
+            let processed_at = <[u8; 8]>::try_from(value.as_slice())
+                .ok()
+                .and_then(|secs| DateTime::from_timestamp(i64::from_be_bytes(secs), 0));
+            match (id, processed_at) {
+                (Some(id), Some(processed_at)) => {
+                    processed.insert(id, processed_at);
+                }
+                _ => warn!(key = ?key, "Skipping undecodable persisted idempotency key"),
+            }
+        }
+        Ok(Self {
+            store,
+            keys: Mutex::new(Keys {
+                processed,
+                in_flight: HashSet::new(),
+            }),
+        })
+    }
+
+    /// Claims `key` for a call of `job_id` about to be applied. Returns `None` if a call with
+    /// the same key was already processed or is being applied right now; checking and
+    /// claiming is one step, so concurrent duplicates can't both pass.
+    pub fn claim(self: &Arc<Self>, job_id: u64, key: &str) -> Option<IdempotencyClaim> {
+        let id = (job_id, key.to_string());
+        let mut keys = self.keys.lock();
+        if keys.processed.contains_key(&id) || !keys.in_flight.insert(id.clone()) {
+            return None;
+        }
+        Some(IdempotencyClaim {
+            keys: self.clone(),
+            id: Some(id),
+        })
+    }
+
+    /// Returns true if a job with this ID and key has already been processed.
+    pub fn is_processed(&self, job_id: u64, key: &str) -> bool {
+        self.keys
+            .lock()
+            .processed
+            .contains_key(&(job_id, key.to_string()))
+    }
+
+    /// Forgets keys older than [`IDEMPOTENCY_KEY_TTL_SECS`].
+    pub fn prune(&self) {
+        let cutoff = Utc::now() - Duration::seconds(IDEMPOTENCY_KEY_TTL_SECS);
+        let mut keys = self.keys.lock();
+        keys.processed.retain(|id, processed_at| {
+            if *processed_at > cutoff {
+                return true;
+            }
+            if let Err(e) = self.store.remove(IDEMPOTENCY_NAMESPACE, &encode_key(id)) {
+                warn!(error = %e, "Failed to remove expired idempotency key");
+            }
+            false
+        });
+    }
+}
+
+/// An idempotency key claimed by [`IdempotencyStore::claim`]. Completing it records the key
+/// as processed; dropping it without completing releases the key, so a failed call can be
+/// retried with the same key.
+#[derive(Debug)]
+pub struct IdempotencyClaim {
+    keys: Arc<IdempotencyStore>,
+    id: Option<KeyId>,
+}
+
+impl IdempotencyClaim {
+    /// Records the key as processed. Call only after the job has been applied successfully.
+    pub fn complete(mut self) -> Result<()> {
+        let Some(id) = self.id.take() else {
+            return Ok(());
+        };
+        let processed_at = Utc::now();
+        let mut keys = self.keys.keys.lock();
+        keys.in_flight.remove(&id);
+        let persisted = self.keys.store.insert(
+            IDEMPOTENCY_NAMESPACE,
+            &encode_key(&id),
+            &processed_at.timestamp().to_be_bytes(),
+        );
+        keys.processed.insert(id, processed_at);
+        persisted
+    }
+}
+
+impl Drop for IdempotencyClaim {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            self.keys.keys.lock().in_flight.remove(&id);
+        }
+    }
+}
+
+/// The job ID as 8 big-endian bytes, followed by the key.
+fn encode_key((job_id, key): &KeyId) -> Vec<u8> {
+    let mut encoded = job_id.to_be_bytes().to_vec();
+    encoded.extend_from_slice(key.as_bytes());
+    encoded
+}
+
+fn decode_key(encoded: &[u8]) -> Option<KeyId> {
+    let (job_id, key) = encoded.split_at_checked(8)?;
+    let job_id = u64::from_be_bytes(job_id.try_into().ok()?);
+    Some((job_id, String::from_utf8(key.to_vec()).ok()?))
+}
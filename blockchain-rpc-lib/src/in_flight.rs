@@ -0,0 +1,66 @@
+use axum::body::Body;
+use futures::StreamExt;
+use parking_lot::Mutex;
+use sp_runtime::AccountId32;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// HTTP requests in flight per authenticated account, for the caps of
+/// `rpc.max_in_flight_requests_per_account` and of plans, so a single account can't hold
+/// every backend slot.
+#[derive(Debug, Default)]
+pub struct InFlightRequests {
+    counts: Mutex<HashMap<AccountId32, usize>>,
+}
+
+impl InFlightRequests {
+    /// Takes one of `account`'s `max` slots, unless all are in use. The slot is given back
+    /// when the returned handle is dropped.
+    pub fn acquire(self: &Arc<Self>, account: &AccountId32, max: usize) -> Option<InFlightSlot> {
+        let mut counts = self.counts.lock();
+        let count = counts.entry(account.clone()).or_default();
+        if *count >= max {
+            return None;
+        }
+        *count += 1;
+        Some(InFlightSlot {
+            requests: self.clone(),
+            account: account.clone(),
+        })
+    }
+
+    /// Requests `account` has in flight.
+    pub fn in_flight(&self, account: &AccountId32) -> usize {
+        self.counts.lock().get(account).copied().unwrap_or(0)
+    }
+}
+
+/// One of an account's in-flight request slots, held until its response is sent.
+#[derive(Debug)]
+pub struct InFlightSlot {
+    requests: Arc<InFlightRequests>,
+    account: AccountId32,
+}
+
+impl InFlightSlot {
+    /// Wraps a response body so the slot is held until the body is sent, or dropped when the
+    /// client goes away.
+    pub fn hold_until_sent(self, body: Body) -> Body {
+        Body::from_stream(body.into_data_stream().map(move |chunk| {
+            let _slot = &self;
+            chunk
+        }))
+    }
+}
+
+impl Drop for InFlightSlot {
+    fn drop(&mut self) {
+        let mut counts = self.requests.counts.lock();
+        if let Some(count) = counts.get_mut(&self.account) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&self.account);
+            }
+        }
+    }
+}
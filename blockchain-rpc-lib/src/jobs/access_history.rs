@@ -0,0 +1,27 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::history::AccessHistoryEntry;
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::macros::debug_job;
+use blueprint_sdk::tangle::extract::{TangleArg, TangleResult};
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::AccountId32;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccessHistoryInput {
+    pub account: String, // AccountId32 as string
+}
+
+/// Job handler returning an account's access timeline (grants, revocations and expiries), oldest first.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    TangleArg(input): TangleArg<AccessHistoryInput>,
+) -> Result<TangleResult<Vec<AccessHistoryEntry>>> {
+    let account = AccountId32::from_str(&input.account)
+        .map_err(|_| Error::InvalidJobInput("Invalid AccountId32 format".to_string()))?;
+
+    Ok(TangleResult(ctx.firewall.access_history(&account)))
+}
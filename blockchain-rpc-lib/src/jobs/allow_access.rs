@@ -1,51 +1,105 @@
 use crate::Result;
+use crate::access_windows::AccessWindow;
 use crate::context::SecureRpcContext;
 use crate::error::Error;
+use crate::jobs::{ALLOW_ACCESS_JOB_ID, ensure_admin};
 use blueprint_sdk::{
     extract::Context,
     macros::debug_job,
-    tangle::extract::{TangleArg, TangleResult},
+    tangle::extract::{CallId, Caller, TangleArg, TangleResult},
 };
 use ipnetwork::IpNetwork;
+use parity_scale_codec::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 use sp_core::crypto::AccountId32;
 use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum AccessTarget {
     Ip(String),      // Can be single IP or CIDR
     Account(String), // AccountId32 as string
 }
 
+impl AccessTarget {
+    /// The IP/CIDR or account, as given.
+    pub fn value(&self) -> &str {
+        match self {
+            AccessTarget::Ip(value) | AccessTarget::Account(value) => value,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AllowAccessInput {
     pub target: AccessTarget,
+    /// Windows the rule is restricted to, e.g. business hours; empty means it always applies.
+    /// Allowing a target again replaces its windows.
+    #[serde(default)]
+    pub windows: Vec<AccessWindow>,
+    /// Optional key making retried submissions of the same request a no-op.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
 }
 
-/// Job handler to add a permanent access rule (IP or Account).
-/// Should ideally check if the caller is an authorized admin.
+/// Job handler to add a permanent access rule (IP or Account), optionally restricted to
+/// recurring access windows.
+/// Only `jobs.admin_accounts` may call it.
 #[debug_job]
 pub async fn handler(
     Context(ctx): Context<SecureRpcContext>,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
     TangleArg(input): TangleArg<AllowAccessInput>,
 ) -> Result<TangleResult<()>> {
-    // Optional: Add admin check here using ctx.admin_pair and job metadata (caller)
-    // if !is_admin(&ctx, &job_metadata.caller) {
-    //     return Err(Error::AccessDeniedAdmin("Only admin can call allow_access"));
-    // }
+    ensure_admin(&ctx, "allow_access", &AccountId32::from(caller))?;
+
+    if ctx
+        .processed_calls
+        .is_processed(ALLOW_ACCESS_JOB_ID, call_id)
+    {
+        tracing::info!(call_id, "Skipping already processed allow_access job call");
+        return Ok(TangleResult(()));
+    }
 
-    match input.target {
+    let claim = match &input.idempotency_key {
+        Some(key) => match ctx.idempotency.claim(ALLOW_ACCESS_JOB_ID, key) {
+            Some(claim) => Some(claim),
+            None => {
+                tracing::info!(idempotency_key = %key, "Skipping duplicate allow_access job");
+                return Ok(TangleResult(()));
+            }
+        },
+        None => None,
+    };
+
+    for window in &input.windows {
+        window.validate()?;
+    }
+    match &input.target {
         AccessTarget::Ip(ip_str) => {
             let ip_network = IpNetwork::from_str(&ip_str)
                 .map_err(|e| Error::InvalidJobInput(format!("Invalid IP/CIDR: {}", e)))?;
-            ctx.firewall.add_ip_rule(ip_network).await?;
-            Ok(TangleResult(()))
+            ctx.firewall
+                .add_scheduled_ip_rule(ip_network, input.windows)
+                .await?;
         }
         AccessTarget::Account(account_str) => {
             let account_id = AccountId32::from_str(&account_str)
                 .map_err(|_| Error::InvalidJobInput("Invalid AccountId32 format".to_string()))?;
-            ctx.firewall.add_account_rule(account_id).await?;
-            Ok(TangleResult(()))
+            ctx.firewall
+                .add_scheduled_account_rule(account_id, input.windows)
+                .await?;
         }
     }
+    if let Some(audit) = &ctx.audit {
+        audit.record_job_call(ALLOW_ACCESS_JOB_ID, call_id, "allow", input.target.value());
+    }
+
+    ctx.processed_calls
+        .mark_processed(ALLOW_ACCESS_JOB_ID, call_id)?;
+
+    if let Some(claim) = claim {
+        claim.complete()?;
+    }
+    Ok(TangleResult(()))
 }
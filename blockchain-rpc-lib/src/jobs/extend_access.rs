@@ -0,0 +1,180 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::jobs::EXTEND_ACCESS_JOB_ID;
+use crate::jobs::allow_access::AccessTarget;
+use crate::jobs::pay_for_access::{
+    Beneficiary, PayForAccessInput, plan_duration, priced_duration, release_payment,
+    requested_duration, verify_payment,
+};
+use crate::pricing::current_pricing;
+use blueprint_sdk::{
+    extract::Context,
+    macros::debug_job,
+    tangle::extract::{CallId, Caller, TangleArg, TangleResult},
+};
+use chrono::Utc;
+use sp_runtime::AccountId32;
+use std::time::Duration;
+
+/// Job handler for users to extend paid temporary access, taking the same input as
+/// `pay_for_access`. An active grant is extended by the purchased duration from its current
+/// expiry, keeping its plan and stacking the plan's request quota; without one, access is
+/// granted from now, as by `pay_for_access`. Returns the new expiry as a Unix timestamp;
+/// retried calls return the grant's current one. A verified payment is released again if the
+/// access can't be extended.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
+    TangleArg(input): TangleArg<PayForAccessInput>,
+) -> Result<TangleResult<i64>> {
+    if ctx
+        .processed_calls
+        .is_processed(EXTEND_ACCESS_JOB_ID, call_id)
+    {
+        tracing::info!(call_id, "Skipping already processed extend_access job call");
+        return Ok(TangleResult(current_expiry(&ctx, &input.beneficiary)));
+    }
+
+    let claim = match &input.idempotency_key {
+        Some(key) => match ctx.idempotency.claim(EXTEND_ACCESS_JOB_ID, key) {
+            Some(claim) => Some(claim),
+            None => {
+                tracing::info!(idempotency_key = %key, "Skipping duplicate extend_access job");
+                return Ok(TangleResult(current_expiry(&ctx, &input.beneficiary)));
+            }
+        },
+        None => None,
+    };
+
+    // Parsed before the payment is checked, so a malformed beneficiary doesn't use it up.
+    let beneficiary = Beneficiary::parse(&input.beneficiary)?;
+    let current = match &beneficiary {
+        Beneficiary::Ip(ip_network) => ctx.firewall.active_temporary_ip_access(*ip_network),
+        Beneficiary::Account(account) => ctx.firewall.active_temporary_access(account),
+    };
+
+    // An extension buys more of the grant's plan; a different plan needs a new grant.
+    let plan_id = match &current {
+        Some(record) => {
+            if input.plan.is_some() && input.plan != record.plan {
+                return Err(Error::InvalidJobInput(format!(
+                    "The active grant is on plan {}, which an extension keeps",
+                    record.plan.as_deref().unwrap_or("none")
+                )));
+            }
+            record.plan.clone()
+        }
+        None => input.plan.clone(),
+    };
+    let mut plan = match &plan_id {
+        Some(id) => Some(
+            ctx.plans
+                .get(id)
+                .ok_or_else(|| Error::InvalidJobInput(format!("Unknown plan {id}")))?,
+        ),
+        None => None,
+    };
+
+    // Without a plan of fixed duration, a priced extension lasts as long as the payment covers.
+    let pricing = current_pricing(&ctx).filter(|_| plan_duration(plan.as_deref()).is_none());
+    let now = Utc::now();
+    let extends_from = current.as_ref().map_or(now, |record| record.expires_at);
+    // Checked before the payment is, so an overlong extension doesn't use it up.
+    let requested_secs = match &pricing {
+        Some(_) => None,
+        None => Some(requested_duration(
+            &ctx,
+            &input,
+            plan.as_deref(),
+            now,
+            extends_from,
+        )?),
+    };
+
+    let payers = beneficiary.payers(AccountId32::from(caller));
+    let paid = verify_payment(
+        &ctx,
+        call_id,
+        &input,
+        &payers,
+        plan.as_deref(),
+        requested_secs,
+    )
+    .await?;
+    if let Some(amount) = paid {
+        // Only a new grant may be on a plan chosen by the payment
+        if plan.is_none() && current.is_none() {
+            plan = ctx.plans.select_by_payment(amount);
+        }
+    }
+
+    let extended: Result<_> = async {
+        let duration_secs = match (plan_duration(plan.as_deref()), &pricing, paid) {
+            (Some(duration_secs), _, _) => duration_secs,
+            (None, Some((schedule, surge_percent)), Some(amount)) => priced_duration(
+                &ctx,
+                schedule.duration_secs(amount, *surge_percent),
+                now,
+                extends_from,
+            )?,
+            (None, _, _) => input.duration_secs,
+        };
+        let duration = Duration::from_secs(duration_secs);
+        let plan_id = plan.map(|plan| plan.id.clone());
+        let record = match beneficiary {
+            Beneficiary::Ip(ip_network) => {
+                ctx.firewall
+                    .extend_temporary_ip_access(ip_network, duration, plan_id)
+                    .await?
+            }
+            Beneficiary::Account(account_id) => {
+                ctx.firewall
+                    .extend_temporary_access(account_id, duration, plan_id)
+                    .await?
+            }
+        };
+        Ok((duration_secs, record))
+    }
+    .await;
+    let (duration_secs, record) = match extended {
+        Ok(extended) => extended,
+        Err(e) => {
+            if paid.is_some() {
+                release_payment(&ctx, call_id, &input);
+            }
+            return Err(e);
+        }
+    };
+    if let Some(audit) = &ctx.audit {
+        audit.record_job_call(
+            EXTEND_ACCESS_JOB_ID,
+            call_id,
+            "extend_temporary",
+            input.beneficiary.value(),
+        );
+    }
+
+    ctx.processed_calls
+        .mark_processed(EXTEND_ACCESS_JOB_ID, call_id)?;
+
+    if let Some(claim) = claim {
+        claim.complete()?;
+    }
+
+    tracing::info!(beneficiary = ?input.beneficiary, duration_secs, expires_at = %record.expires_at, extensions = record.extensions, plan = ?record.plan, "Extended temporary access via paid job");
+
+    Ok(TangleResult(record.expires_at.timestamp()))
+}
+
+/// The expiry of `target`'s active grant as a Unix timestamp, or 0 if it has none.
+fn current_expiry(ctx: &SecureRpcContext, target: &AccessTarget) -> i64 {
+    let record = match Beneficiary::parse(target) {
+        Ok(Beneficiary::Ip(ip_network)) => ctx.firewall.active_temporary_ip_access(ip_network),
+        Ok(Beneficiary::Account(account)) => ctx.firewall.active_temporary_access(&account),
+        Err(_) => None,
+    };
+    record.map_or(0, |record| record.expires_at.timestamp())
+}
@@ -0,0 +1,62 @@
+use crate::Result;
+use crate::api_keys::ApiKeyHash;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::jobs::{ISSUE_API_KEY_JOB_ID, ensure_admin_or_owner};
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::macros::debug_job;
+use blueprint_sdk::tangle::extract::{CallId, Caller, TangleArg, TangleResult};
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::AccountId32;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IssueApiKeyInput {
+    /// AccountId32 the key authenticates as.
+    pub account: String,
+    /// Hex-encoded SHA-256 digest of the API key. The key itself is generated by the
+    /// client and never submitted on-chain.
+    pub key_hash: String,
+}
+
+/// Job handler to issue an API key bound to an account.
+/// Requests carrying the key as `Authorization: Bearer <key>` are checked against the
+/// account's access rules instead of the client IP.
+/// Only `jobs.admin_accounts` may call it, or the account issuing a key for itself. A key
+/// already bound to another account may only be moved by that account or an admin.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
+    TangleArg(input): TangleArg<IssueApiKeyInput>,
+) -> Result<TangleResult<()>> {
+    if ctx
+        .processed_calls
+        .is_processed(ISSUE_API_KEY_JOB_ID, call_id)
+    {
+        tracing::info!(call_id, "Skipping already processed issue_api_key job call");
+        return Ok(TangleResult(()));
+    }
+
+    let caller = AccountId32::from(caller);
+    let account = AccountId32::from_str(&input.account)
+        .map_err(|_| Error::InvalidJobInput("Invalid AccountId32 format".to_string()))?;
+    ensure_admin_or_owner(&ctx, "issue_api_key", &caller, Some(&account))?;
+
+    let key_hash: ApiKeyHash = hex::decode(input.key_hash.trim_start_matches("0x"))
+        .map_err(|e| Error::InvalidJobInput(format!("Invalid key hash hex: {}", e)))?
+        .try_into()
+        .map_err(|_| Error::InvalidJobInput("Key hash must be 32 bytes".to_string()))?;
+
+    // Only the account a key is bound to, or an admin, may move it to another account
+    ctx.api_keys.insert(key_hash, account.clone(), |owner| {
+        ensure_admin_or_owner(&ctx, "issue_api_key", &caller, Some(owner))
+    })?;
+    ctx.processed_calls
+        .mark_processed(ISSUE_API_KEY_JOB_ID, call_id)?;
+
+    tracing::info!(%account, "Issued API key");
+
+    Ok(TangleResult(()))
+}
@@ -0,0 +1,48 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::jobs::ensure_admin_or_owner;
+use crate::webhooks::WebhookTarget;
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::macros::debug_job;
+use blueprint_sdk::tangle::extract::{Caller, TangleArg, TangleResult};
+use serde::{Deserialize, Serialize};
+use sp_runtime::AccountId32;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ListWebhooksInput {
+    /// Optional SS58 account: only webhooks scoped to it are listed.
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+/// Job handler returning the registered webhooks, without their secrets. Read-only.
+/// Only `jobs.admin_accounts` may list every webhook; an account may list those scoped to it.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    Caller(caller): Caller,
+    TangleArg(input): TangleArg<ListWebhooksInput>,
+) -> Result<TangleResult<Vec<WebhookTarget>>> {
+    let account = input
+        .account
+        .as_deref()
+        .map(AccountId32::from_str)
+        .transpose()
+        .map_err(|_| Error::InvalidJobInput("Invalid AccountId32 format".to_string()))?;
+    ensure_admin_or_owner(
+        &ctx,
+        "list_webhooks",
+        &AccountId32::from(caller),
+        account.as_ref(),
+    )?;
+
+    let webhooks = ctx
+        .firewall
+        .webhooks()
+        .into_iter()
+        .filter(|webhook| account.is_none() || webhook.account == account)
+        .collect();
+    Ok(TangleResult(webhooks))
+}
@@ -1,6 +1,31 @@
+pub mod access_history;
 pub mod allow_access;
+pub mod ban_target;
+pub mod extend_access;
+pub mod issue_api_key;
+pub mod list_webhooks;
+pub mod patch_config;
 pub mod pay_for_access;
+pub mod price_schedule;
+pub mod query_rules;
+pub mod query_usage;
 pub mod register_webhook;
+pub mod remove_webhook;
+pub mod report_usage;
+pub mod revoke_access;
+pub mod set_account_labels;
+pub mod set_account_roles;
+pub mod set_read_only;
+pub mod switch_upstream;
+pub mod sync_rules;
+pub mod update_config;
+pub mod usage_summary;
+pub mod vectors;
+
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use sp_runtime::AccountId32;
 
 /// Job ID for the admin function to permanently allow an IP/CIDR or AccountId.
 pub const ALLOW_ACCESS_JOB_ID: u64 = 0;
@@ -10,3 +35,84 @@ pub const PAY_FOR_ACCESS_JOB_ID: u64 = 1;
 
 /// Job ID for users/admins to register a webhook URL for notifications.
 pub const REGISTER_WEBHOOK_JOB_ID: u64 = 2;
+
+/// Job ID for issuing an API key bound to an AccountId.
+pub const ISSUE_API_KEY_JOB_ID: u64 = 3;
+
+/// Job ID for querying an account's access grant history.
+pub const ACCESS_HISTORY_JOB_ID: u64 = 4;
+
+/// Job ID for the admin function to remove a permanent IP/CIDR or AccountId rule.
+pub const REVOKE_ACCESS_JOB_ID: u64 = 5;
+
+/// Job ID for the admin function to deny an IP/CIDR or AccountId.
+pub const BAN_TARGET_JOB_ID: u64 = 6;
+
+/// Job ID for submitting metered usage on-chain for billing.
+pub const REPORT_USAGE_JOB_ID: u64 = 7;
+
+/// Job ID for submitting monthly per-account usage summaries on-chain.
+pub const USAGE_SUMMARY_JOB_ID: u64 = 8;
+
+/// Job ID for exporting the current firewall rules, grants and webhooks.
+pub const QUERY_RULES_JOB_ID: u64 = 9;
+
+/// Job ID for the admin function to change the backend URL, request limits and rate limit.
+pub const UPDATE_CONFIG_JOB_ID: u64 = 10;
+
+/// Job ID for the admin function to replace an account's labels.
+pub const SET_ACCOUNT_LABELS_JOB_ID: u64 = 11;
+
+/// Job ID for the admin function to apply a partial config patch and report the changes.
+pub const PATCH_CONFIG_JOB_ID: u64 = 12;
+
+/// Job ID for the admin function to replace the roles an account holds.
+pub const SET_ACCOUNT_ROLES_JOB_ID: u64 = 13;
+
+/// Job ID for the admin function to switch read-only mode on or off.
+pub const SET_READ_ONLY_JOB_ID: u64 = 14;
+
+/// Job ID for users to extend paid temporary access from its current expiry.
+pub const EXTEND_ACCESS_JOB_ID: u64 = 15;
+
+/// Job ID for applying rule changes shared by another operator of the service.
+pub const SYNC_RULES_JOB_ID: u64 = 16;
+
+/// Job ID for querying an account's usage, remaining quota and access expiry.
+pub const QUERY_USAGE_JOB_ID: u64 = 17;
+
+/// Job ID for querying the price schedule of paid access at the current load.
+pub const PRICE_SCHEDULE_JOB_ID: u64 = 18;
+
+/// Job ID for the admin function to switch the default route to other backends at runtime.
+pub const SWITCH_UPSTREAM_JOB_ID: u64 = 19;
+
+/// Job ID for users/admins to unregister a webhook URL.
+pub const REMOVE_WEBHOOK_JOB_ID: u64 = 20;
+
+/// Job ID for listing the registered webhooks.
+pub const LIST_WEBHOOKS_JOB_ID: u64 = 21;
+
+/// Rejects a call to an admin job from a caller not in `jobs.admin_accounts`. Every caller is
+/// rejected while the list is empty.
+pub fn ensure_admin(ctx: &SecureRpcContext, job: &str, caller: &AccountId32) -> Result<()> {
+    ensure_admin_or_owner(ctx, job, caller, None)
+}
+
+/// Like [`ensure_admin`], but also accepts `owner` acting on their own account.
+pub fn ensure_admin_or_owner(
+    ctx: &SecureRpcContext,
+    job: &str,
+    caller: &AccountId32,
+    owner: Option<&AccountId32>,
+) -> Result<()> {
+    let config = ctx.config();
+    let admins = &config.jobs.admin_accounts;
+    if admins.contains(caller) || owner == Some(caller) {
+        return Ok(());
+    }
+    tracing::warn!(job, %caller, "Rejected job call from unauthorized caller");
+    Err(Error::AccessDeniedAdmin(format!(
+        "{caller} is not allowed to call {job}"
+    )))
+}
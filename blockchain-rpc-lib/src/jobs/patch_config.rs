@@ -0,0 +1,79 @@
+use crate::Result;
+use crate::config_patch::{ConfigChange, diff_configs, patch_config};
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::jobs::{PATCH_CONFIG_JOB_ID, ensure_admin};
+use crate::runtime_config::RpcOverrides;
+use blueprint_sdk::{
+    extract::Context,
+    macros::debug_job,
+    tangle::extract::{CallId, Caller, TangleArg, TangleResult},
+};
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::AccountId32;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PatchConfigInput {
+    /// A JSON merge patch (RFC 7396) of the configuration, as `config.toml` would deserialize,
+    /// e.g. `{"firewall": {"rate_limit": {"burst": 200}}}`.
+    pub patch: String,
+}
+
+/// Job handler applying a partial configuration change, such as one rate limit or an added
+/// backend, without redeploying the whole file. The patched configuration is validated like a
+/// config reload, diffed against the running one and swapped in as a whole, or not at all. The
+/// patch is persisted so it takes precedence over `config.toml` across reloads and restarts,
+/// and the effective changes are returned (secrets redacted), recorded in the audit log and
+/// sent as a `ConfigUpdated` webhook event.
+/// Only `jobs.admin_accounts` may call it.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
+    TangleArg(input): TangleArg<PatchConfigInput>,
+) -> Result<TangleResult<Vec<ConfigChange>>> {
+    ensure_admin(&ctx, "patch_config", &AccountId32::from(caller))?;
+
+    if ctx
+        .processed_calls
+        .is_processed(PATCH_CONFIG_JOB_ID, call_id)
+    {
+        tracing::info!(call_id, "Skipping already processed patch_config job call");
+        return Ok(TangleResult(Vec::new()));
+    }
+
+    let patch: serde_json::Value = serde_json::from_str(&input.patch)
+        .map_err(|e| Error::InvalidJobInput(format!("Config patch is not valid JSON: {e}")))?;
+    let current = ctx.config();
+    let updated = patch_config(&current, &patch)?;
+    let changes = diff_configs(&current, &updated)?;
+    if changes.is_empty() {
+        return Err(Error::InvalidJobInput(
+            "Config patch changes no settings".to_string(),
+        ));
+    }
+    updated.validate_reload(&current)?;
+    ctx.apply_config(updated)?;
+    ctx.runtime_config.update(RpcOverrides {
+        config_patch: Some(patch),
+        ..RpcOverrides::default()
+    })?;
+
+    let settings: Vec<String> = changes.iter().map(ConfigChange::setting).collect();
+    tracing::info!(call_id, ?settings, "Patched runtime configuration");
+    if let Some(audit) = &ctx.audit {
+        audit.record_job_call(
+            PATCH_CONFIG_JOB_ID,
+            call_id,
+            "patch_config",
+            &settings.join(", "),
+        );
+    }
+    ctx.firewall.report_config_updated(settings).await;
+
+    ctx.processed_calls
+        .mark_processed(PATCH_CONFIG_JOB_ID, call_id)?;
+
+    Ok(TangleResult(changes))
+}
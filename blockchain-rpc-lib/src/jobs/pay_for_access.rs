@@ -1,52 +1,313 @@
 use crate::Result;
 use crate::context::{SecureRpcContext, TemporaryAccessRecord};
 use crate::error::Error;
+use crate::jobs::PAY_FOR_ACCESS_JOB_ID;
+use crate::jobs::allow_access::AccessTarget;
+use crate::payments::PaymentReference;
+use crate::plans::Plan;
+use crate::pricing::current_pricing;
+use blueprint_sdk::extract::Context;
 use blueprint_sdk::macros::debug_job;
-use blueprint_sdk::tangle::extract::{Context, DecodedArgs, TangleResult};
-use chrono::{Duration, Utc};
+use blueprint_sdk::tangle::extract::{CallId, Caller, TangleArg, TangleResult};
+use chrono::{DateTime, Duration, Utc};
+use ipnetwork::IpNetwork;
 use parity_scale_codec::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 use sp_runtime::AccountId32;
+use std::str::FromStr;
 
 /// Input arguments for the pay_for_access job, encoded using SCALE codec.
 #[derive(Encode, Decode, Debug, Clone, Serialize, Deserialize)]
 pub struct PayForAccessInput {
-    /// The account that paid and should receive temporary access.
-    pub beneficiary: AccountId32,
-    /// Duration in seconds for which access should be granted.
+    /// The account or IP/CIDR that should receive temporary access.
+    pub beneficiary: AccessTarget,
+    /// Duration in seconds for which access should be granted. Ignored when
+    /// `[payments.pricing]` sets the duration from the amount paid.
     pub duration_secs: u64,
+    /// Optional key making retried submissions of the same payment a no-op.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    /// The transfer paying for access. Required when `[payments]` verification is configured.
+    pub payment: Option<PaymentReference>,
+    /// The `[plans]` tier to grant. Without one, a verified payment buys the highest-priced plan
+    /// it covers, and otherwise access is not limited by a plan.
+    pub plan: Option<String>,
 }
 
 /// Job handler for users to pay for temporary access.
 /// The beneficiary is passed explicitly in the arguments, as the contract proxies the call.
+/// A verified payment is released again if access can't be granted.
 #[debug_job]
 pub async fn handler(
     Context(ctx): Context<SecureRpcContext>,
-    TangleArgs2(input): TangleArgs2<PayForAccessInput>,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
+    TangleArg(input): TangleArg<PayForAccessInput>,
 ) -> Result<TangleResult<()>> {
+    if ctx
+        .processed_calls
+        .is_processed(PAY_FOR_ACCESS_JOB_ID, call_id)
+    {
+        tracing::info!(
+            call_id,
+            "Skipping already processed pay_for_access job call"
+        );
+        return Ok(TangleResult(()));
+    }
+
+    let claim = match &input.idempotency_key {
+        Some(key) => match ctx.idempotency.claim(PAY_FOR_ACCESS_JOB_ID, key) {
+            Some(claim) => Some(claim),
+            None => {
+                tracing::info!(idempotency_key = %key, "Skipping duplicate pay_for_access job");
+                return Ok(TangleResult(()));
+            }
+        },
+        None => None,
+    };
+
+    // Parsed before the payment is checked, so a malformed beneficiary doesn't use it up.
+    let beneficiary = Beneficiary::parse(&input.beneficiary)?;
+
+    let mut plan = match &input.plan {
+        Some(id) => Some(
+            ctx.plans
+                .get(id)
+                .ok_or_else(|| Error::InvalidJobInput(format!("Unknown plan {id}")))?,
+        ),
+        None => None,
+    };
+
+    // Without a plan of fixed duration, a priced grant lasts as long as the payment covers.
+    let pricing = current_pricing(&ctx).filter(|_| plan_duration(plan.as_deref()).is_none());
+    let now = Utc::now();
+    // Checked before the payment is, so an overlong grant doesn't use it up. Plans chosen by
+    // the payment are kept within the limits when the config is loaded.
+    let requested_secs = match &pricing {
+        Some(_) => None,
+        None => Some(requested_duration(&ctx, &input, plan.as_deref(), now, now)?),
+    };
+
+    let payers = beneficiary.payers(AccountId32::from(caller));
+    let paid = verify_payment(
+        &ctx,
+        call_id,
+        &input,
+        &payers,
+        plan.as_deref(),
+        requested_secs,
+    )
+    .await?;
+    if let Some(amount) = paid {
+        if plan.is_none() {
+            plan = ctx.plans.select_by_payment(amount);
+        }
+    }
+
+    let granted: Result<_> = async {
+        let duration_secs = match (plan_duration(plan.as_deref()), &pricing, paid) {
+            (Some(duration_secs), _, _) => duration_secs,
+            (None, Some((schedule, surge_percent)), Some(amount)) => priced_duration(
+                &ctx,
+                schedule.duration_secs(amount, *surge_percent),
+                now,
+                now,
+            )?,
+            (None, _, _) => input.duration_secs,
+        };
+        let plan_id = plan.map(|plan| plan.id.clone());
+        let now = Utc::now();
+        let expires_at = expiry(now, duration_secs)?;
+        let record = TemporaryAccessRecord {
+            granted_at: now,
+            expires_at,
+            plan: plan_id.clone(),
+            extensions: 0,
+        };
+
+        // Grant access to the beneficiary specified in the input args
+        match beneficiary {
+            Beneficiary::Ip(ip_network) => {
+                ctx.firewall
+                    .grant_temporary_ip_access(ip_network, record)
+                    .await?;
+            }
+            Beneficiary::Account(account_id) => {
+                ctx.firewall
+                    .grant_temporary_access(account_id, record)
+                    .await?;
+            }
+        }
+        Ok((duration_secs, expires_at, plan_id))
+    }
+    .await;
+    let (duration_secs, expires_at, plan_id) = match granted {
+        Ok(granted) => granted,
+        Err(e) => {
+            if paid.is_some() {
+                release_payment(&ctx, call_id, &input);
+            }
+            return Err(e);
+        }
+    };
+    if let Some(audit) = &ctx.audit {
+        audit.record_job_call(
+            PAY_FOR_ACCESS_JOB_ID,
+            call_id,
+            "grant_temporary",
+            input.beneficiary.value(),
+        );
+    }
+
+    ctx.processed_calls
+        .mark_processed(PAY_FOR_ACCESS_JOB_ID, call_id)?;
+
+    if let Some(claim) = claim {
+        claim.complete()?;
+    }
+
+    tracing::info!(beneficiary = ?input.beneficiary, duration_secs, expires_at = %expires_at, plan = ?plan_id, "Granted temporary access via paid job");
+
+    // Return empty result on success
+    Ok(TangleResult(()))
+}
+
+/// The duration a plan fixes for its grants, if any.
+pub(crate) fn plan_duration(plan: Option<&Plan>) -> Option<u64> {
+    plan.and_then(|plan| plan.duration_secs)
+}
+
+/// The duration `input` asks for, or `plan` fixes, checked against the `[jobs]` limits for a
+/// grant running from `from`.
+pub(crate) fn requested_duration(
+    ctx: &SecureRpcContext,
+    input: &PayForAccessInput,
+    plan: Option<&Plan>,
+    now: DateTime<Utc>,
+    from: DateTime<Utc>,
+) -> Result<u64> {
     if input.duration_secs == 0 {
         return Err(Error::InvalidJobInput(
             "Duration must be positive".to_string(),
         ));
     }
+    let config = ctx.config();
+    config.jobs.check_access_duration(input.duration_secs)?;
+    let requested_secs = plan_duration(plan).unwrap_or(input.duration_secs);
+    config
+        .jobs
+        .check_access_expiry(now, expiry(from, requested_secs)?)?;
+    Ok(requested_secs)
+}
 
-    // Payment verification is assumed to have happened in the calling contract.
-    // The contract took the ERC20 payment before calling `SERVICES_CONTRACT.callJob`.
+/// The duration a payment bought under `[payments.pricing]`, capped at the `[jobs]` limits
+/// for a grant running from `from`, since the payment is already used.
+pub(crate) fn priced_duration(
+    ctx: &SecureRpcContext,
+    bought_secs: u64,
+    now: DateTime<Utc>,
+    from: DateTime<Utc>,
+) -> Result<u64> {
+    let duration_secs = match ctx.config().jobs.max_access_secs(now, from) {
+        Some(max) if bought_secs > max => {
+            tracing::warn!(
+                bought_secs,
+                max,
+                "Capped priced grant at the longest allowed"
+            );
+            max
+        }
+        _ => bought_secs,
+    };
+    if duration_secs == 0 {
+        return Err(Error::InvalidJobInput(
+            "The payment buys no access at the current price".to_string(),
+        ));
+    }
+    Ok(duration_secs)
+}
 
-    let now = Utc::now();
-    let expires_at = now + Duration::seconds(input.duration_secs as i64);
-    let record = TemporaryAccessRecord {
-        granted_at: now,
-        expires_at,
+/// Verifies the payment, made by one of `payers`, for `requested_secs` of access, or for
+/// whatever duration it buys under `[payments.pricing]` when `None`, returning the amount paid.
+/// Returns `None` without `[payments]`, when payment verification is assumed to have happened
+/// in the calling contract, which took the ERC20 payment before calling
+/// `SERVICES_CONTRACT.callJob`.
+pub(crate) async fn verify_payment(
+    ctx: &SecureRpcContext,
+    call_id: u64,
+    input: &PayForAccessInput,
+    payers: &[AccountId32],
+    plan: Option<&Plan>,
+    requested_secs: Option<u64>,
+) -> Result<Option<u128>> {
+    let Some(verifier) = &ctx.payment_verifier else {
+        return Ok(None);
     };
+    let payment = input
+        .payment
+        .as_ref()
+        .ok_or_else(|| Error::InvalidJobInput("A payment reference is required".to_string()))?;
+    let plan_price = plan.and_then(|plan| plan.min_payment);
+    let required = match requested_secs {
+        Some(duration_secs) => verifier.required_amount(duration_secs),
+        None => verifier.min_amount(),
+    }
+    .max(plan_price.unwrap_or(0));
+    let amount = verifier
+        .verify(ctx, payment, payers, required)
+        .await
+        .inspect_err(|e| tracing::warn!(call_id, error = %e, "Rejected payment for access"))?;
+    tracing::info!(call_id, amount, "Verified payment for temporary access");
+    Ok(Some(amount))
+}
 
-    // Grant access to the beneficiary specified in the input args
-    ctx.firewall
-        .grant_temporary_access(input.beneficiary.clone(), record)
-        .await?;
+/// Makes the payment verified for `input` usable again, after the access it paid for couldn't
+/// be granted.
+pub(crate) fn release_payment(ctx: &SecureRpcContext, call_id: u64, input: &PayForAccessInput) {
+    let (Some(verifier), Some(payment)) = (&ctx.payment_verifier, &input.payment) else {
+        return;
+    };
+    match verifier.release(payment) {
+        Ok(()) => tracing::info!(call_id, "Released payment for access that wasn't granted"),
+        Err(e) => tracing::warn!(call_id, error = %e, "Failed to release unused payment"),
+    }
+}
 
-    tracing::info!(account = %input.beneficiary, duration_secs = input.duration_secs, expires_at = %expires_at, "Granted temporary access via paid job");
+/// When access granted at `now` for `duration_secs` ends.
+pub(crate) fn expiry(now: DateTime<Utc>, duration_secs: u64) -> Result<DateTime<Utc>> {
+    i64::try_from(duration_secs)
+        .ok()
+        .and_then(Duration::try_seconds)
+        .and_then(|duration| now.checked_add_signed(duration))
+        .ok_or_else(|| Error::InvalidJobInput("Duration too long".to_string()))
+}
 
-    // Return empty result on success
-    Ok(TangleResult(()))
+pub(crate) enum Beneficiary {
+    Ip(IpNetwork),
+    Account(AccountId32),
+}
+
+impl Beneficiary {
+    pub(crate) fn parse(target: &AccessTarget) -> Result<Self> {
+        Ok(match target {
+            AccessTarget::Ip(ip_str) => Beneficiary::Ip(
+                IpNetwork::from_str(ip_str)
+                    .map_err(|e| Error::InvalidJobInput(format!("Invalid IP/CIDR: {}", e)))?,
+            ),
+            AccessTarget::Account(account_str) => {
+                Beneficiary::Account(AccountId32::from_str(account_str).map_err(|_| {
+                    Error::InvalidJobInput("Invalid AccountId32 format".to_string())
+                })?)
+            }
+        })
+    }
+
+    /// The accounts whose transfers may pay for this beneficiary's access: the caller, and
+    /// the beneficiary itself when it is an account.
+    pub(crate) fn payers(&self, caller: AccountId32) -> Vec<AccountId32> {
+        match self {
+            Beneficiary::Ip(_) => vec![caller],
+            Beneficiary::Account(account) => vec![caller, account.clone()],
+        }
+    }
 }
@@ -0,0 +1,29 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::pricing::{PriceQuote, PriceSchedule, load_percent};
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::macros::debug_job;
+use blueprint_sdk::tangle::extract::TangleResult;
+
+/// Job handler returning the `[payments.pricing]` schedule `pay_for_access` and
+/// `extend_access` grants are priced by, with the surge multiplier at the current load, so
+/// buyers can work out what a payment gets them before making it. Open to anyone.
+#[debug_job]
+pub async fn handler(Context(ctx): Context<SecureRpcContext>) -> Result<TangleResult<PriceQuote>> {
+    let config = ctx.config();
+    let (Some(verifier), Some(pricing)) = (
+        &ctx.payment_verifier,
+        config
+            .payments
+            .as_ref()
+            .and_then(|payments| payments.pricing.as_ref()),
+    ) else {
+        return Err(Error::InvalidJobInput(
+            "Access is not priced by a schedule".to_string(),
+        ));
+    };
+    Ok(TangleResult(
+        PriceSchedule::new(pricing).quote(verifier.min_amount(), load_percent(&ctx)),
+    ))
+}
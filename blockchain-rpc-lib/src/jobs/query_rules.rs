@@ -0,0 +1,107 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::firewall::RuleSet;
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::macros::debug_job;
+use blueprint_sdk::tangle::extract::TangleResult;
+use ipnetwork::IpNetwork;
+use parity_scale_codec::{Decode, Encode};
+use sp_core::crypto::AccountId32;
+
+/// Allow and deny lists from one source (config or dynamic), with IPs/CIDRs as strings.
+#[derive(Encode, Decode, Debug, Clone, Default)]
+pub struct RuleListState {
+    pub allow_ips: Vec<String>,
+    pub allow_accounts: Vec<AccountId32>,
+    pub deny_ips: Vec<String>,
+    pub deny_accounts: Vec<AccountId32>,
+}
+
+impl From<RuleSet> for RuleListState {
+    fn from(rules: RuleSet) -> Self {
+        let ips = |ips: Vec<IpNetwork>| ips.iter().map(ToString::to_string).collect();
+        RuleListState {
+            allow_ips: ips(rules.allow_ips),
+            allow_accounts: rules.allow_accounts,
+            deny_ips: ips(rules.deny_ips),
+            deny_accounts: rules.deny_accounts,
+        }
+    }
+}
+
+/// An active temporary grant. Times are Unix timestamps in seconds.
+#[derive(Encode, Decode, Debug, Clone)]
+pub struct TemporaryGrantState {
+    /// AccountId, IP or CIDR.
+    pub source: String,
+    pub granted_at: i64,
+    pub expires_at: i64,
+    pub plan: Option<String>,
+}
+
+/// A registered webhook. Secrets are never included.
+#[derive(Encode, Decode, Debug, Clone)]
+pub struct WebhookState {
+    pub url: String,
+    /// Subscribed event types; empty means every event.
+    pub events: Vec<String>,
+    pub account: Option<AccountId32>,
+}
+
+/// The firewall's current configuration, as returned SCALE-encoded by `query_rules`.
+#[derive(Encode, Decode, Debug, Clone)]
+pub struct FirewallState {
+    pub allow_unrestricted_access: bool,
+    /// Rules from `[firewall]` in the config file.
+    pub config: RuleListState,
+    /// Rules added at runtime by jobs or the admin API.
+    pub dynamic: RuleListState,
+    pub temporary_grants: Vec<TemporaryGrantState>,
+    pub webhooks: Vec<WebhookState>,
+}
+
+/// Job handler returning the current firewall state (config and dynamic rules, active
+/// temporary grants and registered webhooks) as a SCALE-encoded [`FirewallState`], so service
+/// owners can verify the operator's configuration on-chain. Read-only.
+#[debug_job]
+pub async fn handler(Context(ctx): Context<SecureRpcContext>) -> Result<TangleResult<Vec<u8>>> {
+    let rules = ctx.firewall.rules();
+    let temporary_grants = ctx
+        .firewall
+        .temporary_grants()
+        .into_iter()
+        .map(|grant| TemporaryGrantState {
+            source: grant.source,
+            granted_at: grant.granted_at.timestamp(),
+            expires_at: grant.expires_at.timestamp(),
+            plan: grant.plan,
+        })
+        .collect();
+    let webhooks = ctx
+        .firewall
+        .webhooks()
+        .into_iter()
+        .map(|webhook| {
+            let mut events: Vec<String> = webhook
+                .events
+                .iter()
+                .map(|event| format!("{event:?}"))
+                .collect();
+            events.sort();
+            WebhookState {
+                url: webhook.url.to_string(),
+                events,
+                account: webhook.account,
+            }
+        })
+        .collect();
+
+    let state = FirewallState {
+        allow_unrestricted_access: rules.allow_unrestricted_access,
+        config: rules.config.into(),
+        dynamic: rules.dynamic.into(),
+        temporary_grants,
+        webhooks,
+    };
+    Ok(TangleResult(state.encode()))
+}
@@ -0,0 +1,66 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::metering::AccountCounters;
+use crate::plans::ActivePlan;
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::macros::debug_job;
+use blueprint_sdk::tangle::extract::{TangleArg, TangleResult};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::AccountId32;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueryUsageInput {
+    pub account: String, // AccountId32 as string
+}
+
+/// An account's consumption and what is left of its paid access.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountUsage {
+    pub account: String,
+    /// Requests and bytes this month and since metering began; `None` without `[metering]`.
+    pub usage: Option<AccountCounters>,
+    /// The `[plans]` tier of the account's active grant.
+    pub plan: Option<String>,
+    /// Calls left on the active grant's plan, if it has a request quota.
+    pub quota_remaining: Option<u64>,
+    pub access_expires_at: Option<DateTime<Utc>>,
+}
+
+/// Job handler returning an account's usage, remaining quota and access expiry. Open to
+/// anyone, so users can check their own consumption.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    TangleArg(input): TangleArg<QueryUsageInput>,
+) -> Result<TangleResult<AccountUsage>> {
+    let account = AccountId32::from_str(&input.account)
+        .map_err(|_| Error::InvalidJobInput("Invalid AccountId32 format".to_string()))?;
+
+    let active_plan = ctx
+        .firewall
+        .active_temporary_access(&account)
+        .and_then(|record| {
+            Some(ActivePlan {
+                plan: ctx.plans.get(record.plan.as_deref()?)?,
+                holder: account.to_string(),
+                granted_at: record.granted_at,
+                expires_at: record.expires_at,
+                extensions: record.extensions,
+            })
+        });
+    Ok(TangleResult(AccountUsage {
+        account: account.to_string(),
+        usage: ctx
+            .usage_meter
+            .as_ref()
+            .map(|meter| meter.account_counters(&account.to_string())),
+        plan: active_plan.as_ref().map(|active| active.plan.id.clone()),
+        quota_remaining: active_plan
+            .as_ref()
+            .and_then(|active| active.plan.quota_remaining(active)),
+        access_expires_at: ctx.firewall.account_status(&account).temporary_expires_at,
+    }))
+}
@@ -1,23 +1,67 @@
 use crate::Result;
 use crate::context::SecureRpcContext;
 use crate::error::Error;
+use crate::jobs::{REGISTER_WEBHOOK_JOB_ID, ensure_admin_or_owner};
+use crate::webhooks::WebhookEventType;
 use blueprint_sdk::extract::Context;
 use blueprint_sdk::macros::debug_job;
-use blueprint_sdk::tangle::extract::{TangleArg, TangleResult};
+use blueprint_sdk::tangle::extract::{CallId, Caller, TangleArg, TangleResult};
 use serde::{Deserialize, Serialize};
+use sp_runtime::AccountId32;
+use std::str::FromStr;
 use url::Url;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RegisterWebhookInput {
     pub url: String,
+    /// Optional shared secret used to HMAC-sign deliveries to this webhook.
+    /// Note that job inputs are public on-chain; prefer configuring secrets in `config.toml`
+    /// when confidentiality matters.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Event types to deliver to this webhook, e.g. `AccessGranted`. Empty subscribes to all.
+    #[serde(default)]
+    pub events: Vec<WebhookEventType>,
+    /// Optional SS58 account to scope the webhook to: it then only receives events concerning
+    /// that account (its grants, expirations, rules and limits), not other tenants' activity.
+    #[serde(default)]
+    pub account: Option<String>,
+    /// Optional key making retried submissions of the same registration a no-op.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
 }
 
 /// Job handler to register a new webhook URL for firewall event notifications.
+/// Only `jobs.admin_accounts` may call it, or an account registering a webhook scoped to itself.
 #[debug_job]
 pub async fn handler(
     Context(ctx): Context<SecureRpcContext>,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
     TangleArg(input): TangleArg<RegisterWebhookInput>,
 ) -> Result<TangleResult<()>> {
+    if ctx
+        .processed_calls
+        .is_processed(REGISTER_WEBHOOK_JOB_ID, call_id)
+    {
+        tracing::info!(
+            call_id,
+            "Skipping already processed register_webhook job call"
+        );
+        return Ok(TangleResult(()));
+    }
+
+    let claim = match &input.idempotency_key {
+        Some(key) => match ctx.idempotency.claim(REGISTER_WEBHOOK_JOB_ID, key) {
+            Some(claim) => Some(claim),
+            None => {
+                tracing::info!(idempotency_key = %key, "Skipping duplicate register_webhook job");
+                return Ok(TangleResult(()));
+            }
+        },
+        None => None,
+    };
+
     let url = Url::parse(&input.url)
         .map_err(|e| Error::InvalidJobInput(format!("Invalid URL: {}", e)))?;
 
@@ -28,7 +72,41 @@ pub async fn handler(
         ));
     }
 
-    ctx.firewall.add_webhook(url).await?;
+    if !ctx.config().webhooks.is_destination_allowed(&url) {
+        return Err(Error::InvalidJobInput(format!(
+            "Webhook destination {} is not on the operator's allowlist",
+            url.host_str().unwrap_or_default()
+        )));
+    }
+
+    let account = input
+        .account
+        .as_deref()
+        .map(AccountId32::from_str)
+        .transpose()
+        .map_err(|_| Error::InvalidJobInput("Invalid AccountId32 format".to_string()))?;
+    ensure_admin_or_owner(
+        &ctx,
+        "register_webhook",
+        &AccountId32::from(caller),
+        account.as_ref(),
+    )?;
+
+    ctx.firewall
+        .add_webhook(
+            url,
+            input.secret,
+            input.events.into_iter().collect(),
+            account,
+        )
+        .await?;
+
+    ctx.processed_calls
+        .mark_processed(REGISTER_WEBHOOK_JOB_ID, call_id)?;
+
+    if let Some(claim) = claim {
+        claim.complete()?;
+    }
 
     tracing::info!(url = %input.url, "Registered new webhook");
 
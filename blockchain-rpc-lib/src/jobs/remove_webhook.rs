@@ -0,0 +1,65 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::jobs::{REMOVE_WEBHOOK_JOB_ID, ensure_admin_or_owner};
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::macros::debug_job;
+use blueprint_sdk::tangle::extract::{CallId, Caller, TangleArg, TangleResult};
+use serde::{Deserialize, Serialize};
+use sp_runtime::AccountId32;
+use url::Url;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RemoveWebhookInput {
+    pub url: String,
+}
+
+/// Job handler to unregister a webhook, e.g. a dead or compromised endpoint. Emits a
+/// `WebhookRemoved` event to the remaining webhooks.
+/// Only `jobs.admin_accounts` may call it, or the account a webhook is scoped to.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
+    TangleArg(input): TangleArg<RemoveWebhookInput>,
+) -> Result<TangleResult<()>> {
+    if ctx
+        .processed_calls
+        .is_processed(REMOVE_WEBHOOK_JOB_ID, call_id)
+    {
+        tracing::info!(
+            call_id,
+            "Skipping already processed remove_webhook job call"
+        );
+        return Ok(TangleResult(()));
+    }
+
+    let url = Url::parse(&input.url)
+        .map_err(|e| Error::InvalidJobInput(format!("Invalid URL: {}", e)))?;
+    let owner = ctx
+        .firewall
+        .webhooks()
+        .into_iter()
+        .find(|webhook| webhook.url == url)
+        .ok_or_else(|| Error::InvalidJobInput(format!("Webhook {url} is not registered")))?
+        .account;
+    let caller = AccountId32::from(caller);
+    ensure_admin_or_owner(&ctx, "remove_webhook", &caller, owner.as_ref())?;
+
+    ctx.firewall.remove_webhook(&url).await;
+    if let Some(audit) = &ctx.audit {
+        audit.record_job_call(
+            REMOVE_WEBHOOK_JOB_ID,
+            call_id,
+            "remove_webhook",
+            url.as_str(),
+        );
+    }
+
+    ctx.processed_calls
+        .mark_processed(REMOVE_WEBHOOK_JOB_ID, call_id)?;
+
+    tracing::info!(%url, "Removed webhook");
+    Ok(TangleResult(()))
+}
@@ -0,0 +1,42 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::jobs::REPORT_USAGE_JOB_ID;
+use crate::metering::UsageReport;
+use blueprint_sdk::{
+    extract::Context,
+    macros::debug_job,
+    tangle::extract::{CallId, TangleResult},
+};
+
+/// Job handler returning the usage periods closed since the last call, oldest first. The
+/// result is submitted on-chain, where billing is reconciled against it; the returned periods
+/// are not reported again.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    CallId(call_id): CallId,
+) -> Result<TangleResult<Vec<UsageReport>>> {
+    let Some(meter) = &ctx.usage_meter else {
+        return Err(Error::InvalidJobInput(
+            "Usage metering is not enabled".to_string(),
+        ));
+    };
+
+    if ctx
+        .processed_calls
+        .is_processed(REPORT_USAGE_JOB_ID, call_id)
+    {
+        tracing::info!(call_id, "Skipping already processed report_usage job call");
+        return Ok(TangleResult(Vec::new()));
+    }
+
+    meter.close_period_if_due();
+    let reports = meter.take_reports();
+    meter.persist()?;
+    ctx.processed_calls
+        .mark_processed(REPORT_USAGE_JOB_ID, call_id)?;
+    tracing::info!(call_id, periods = reports.len(), "Reported usage");
+
+    Ok(TangleResult(reports))
+}
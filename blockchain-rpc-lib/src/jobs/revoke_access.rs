@@ -0,0 +1,66 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::jobs::allow_access::AccessTarget;
+use crate::jobs::{REVOKE_ACCESS_JOB_ID, ensure_admin};
+use blueprint_sdk::{
+    extract::Context,
+    macros::debug_job,
+    tangle::extract::{CallId, Caller, TangleArg, TangleResult},
+};
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::AccountId32;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RevokeAccessInput {
+    pub target: AccessTarget,
+}
+
+/// Job handler to remove a permanent access rule (IP or Account) added by `allow_access`.
+/// Rules from the static config are not affected.
+/// Only `jobs.admin_accounts` may call it.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
+    TangleArg(input): TangleArg<RevokeAccessInput>,
+) -> Result<TangleResult<()>> {
+    ensure_admin(&ctx, "revoke_access", &AccountId32::from(caller))?;
+
+    if ctx
+        .processed_calls
+        .is_processed(REVOKE_ACCESS_JOB_ID, call_id)
+    {
+        tracing::info!(call_id, "Skipping already processed revoke_access job call");
+        return Ok(TangleResult(()));
+    }
+
+    match &input.target {
+        AccessTarget::Ip(ip_str) => {
+            let ip_network = IpNetwork::from_str(&ip_str)
+                .map_err(|e| Error::InvalidJobInput(format!("Invalid IP/CIDR: {}", e)))?;
+            ctx.firewall.remove_ip_rule(ip_network).await?;
+        }
+        AccessTarget::Account(account_str) => {
+            let account_id = AccountId32::from_str(&account_str)
+                .map_err(|_| Error::InvalidJobInput("Invalid AccountId32 format".to_string()))?;
+            ctx.firewall.remove_account_rule(account_id).await?;
+        }
+    }
+    if let Some(audit) = &ctx.audit {
+        audit.record_job_call(
+            REVOKE_ACCESS_JOB_ID,
+            call_id,
+            "revoke",
+            input.target.value(),
+        );
+    }
+
+    ctx.processed_calls
+        .mark_processed(REVOKE_ACCESS_JOB_ID, call_id)?;
+
+    Ok(TangleResult(()))
+}
@@ -0,0 +1,80 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::jobs::{SET_ACCOUNT_ROLES_JOB_ID, ensure_admin};
+use crate::roles::Roles;
+use blueprint_sdk::{
+    extract::Context,
+    macros::debug_job,
+    tangle::extract::{CallId, Caller, TangleArg, TangleResult},
+};
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::AccountId32;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetAccountRolesInput {
+    pub account: String, // AccountId32 as string
+    /// The account's new roles, each defined under `[roles]`, replacing all previous ones.
+    /// Empty clears them.
+    pub roles: Vec<String>,
+    /// Optional key making retried submissions of the same request a no-op.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+/// Job handler to replace an account's roles, which unlock the methods `[roles]` restricts,
+/// e.g. transaction submission. Only `jobs.admin_accounts` may call it.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
+    TangleArg(input): TangleArg<SetAccountRolesInput>,
+) -> Result<TangleResult<()>> {
+    ensure_admin(&ctx, "set_account_roles", &AccountId32::from(caller))?;
+
+    if ctx
+        .processed_calls
+        .is_processed(SET_ACCOUNT_ROLES_JOB_ID, call_id)
+    {
+        tracing::info!(
+            call_id,
+            "Skipping already processed set_account_roles job call"
+        );
+        return Ok(TangleResult(()));
+    }
+
+    let claim = match &input.idempotency_key {
+        Some(key) => match ctx.idempotency.claim(SET_ACCOUNT_ROLES_JOB_ID, key) {
+            Some(claim) => Some(claim),
+            None => {
+                tracing::info!(idempotency_key = %key, "Skipping duplicate set_account_roles job");
+                return Ok(TangleResult(()));
+            }
+        },
+        None => None,
+    };
+
+    let account_id = AccountId32::from_str(&input.account)
+        .map_err(|_| Error::InvalidJobInput("Invalid AccountId32 format".to_string()))?;
+    let roles: Roles = input.roles.into_iter().collect();
+    ctx.account_roles
+        .set(&account_id, roles.clone(), &ctx.config().roles)
+        .map_err(|e| match e {
+            Error::InvalidRoles(reason) => Error::InvalidJobInput(reason),
+            e => e,
+        })?;
+    tracing::info!(call_id, account = %account_id, ?roles, "Updated account roles");
+    if let Some(audit) = &ctx.audit {
+        audit.record_job_call(SET_ACCOUNT_ROLES_JOB_ID, call_id, "role", &input.account);
+    }
+
+    ctx.processed_calls
+        .mark_processed(SET_ACCOUNT_ROLES_JOB_ID, call_id)?;
+
+    if let Some(claim) = claim {
+        claim.complete()?;
+    }
+    Ok(TangleResult(()))
+}
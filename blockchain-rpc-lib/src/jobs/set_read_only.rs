@@ -0,0 +1,75 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::jobs::{SET_READ_ONLY_JOB_ID, ensure_admin};
+use blueprint_sdk::{
+    extract::Context,
+    macros::debug_job,
+    tangle::extract::{CallId, Caller, TangleArg, TangleResult},
+};
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::AccountId32;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetReadOnlyInput {
+    /// `true` rejects `rpc.read_only_methods` until switched off again; `false` switches it off.
+    pub enabled: bool,
+    /// Shown to clients whose calls are rejected, e.g. `Backend upgrade`.
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Optional key making retried submissions of the same request a no-op.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+/// Job handler to switch read-only mode on or off. While on, transaction-submitting calls are
+/// rejected and queries are served. Only `jobs.admin_accounts` may call it.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
+    TangleArg(input): TangleArg<SetReadOnlyInput>,
+) -> Result<TangleResult<()>> {
+    let caller = AccountId32::from(caller);
+    ensure_admin(&ctx, "set_read_only", &caller)?;
+
+    if ctx
+        .processed_calls
+        .is_processed(SET_READ_ONLY_JOB_ID, call_id)
+    {
+        tracing::info!(call_id, "Skipping already processed set_read_only job call");
+        return Ok(TangleResult(()));
+    }
+
+    let claim = match &input.idempotency_key {
+        Some(key) => match ctx.idempotency.claim(SET_READ_ONLY_JOB_ID, key) {
+            Some(claim) => Some(claim),
+            None => {
+                tracing::info!(idempotency_key = %key, "Skipping duplicate set_read_only job");
+                return Ok(TangleResult(()));
+            }
+        },
+        None => None,
+    };
+
+    let action = if input.enabled {
+        let status = ctx.read_only.enable(input.reason, &caller.to_string())?;
+        tracing::warn!(call_id, %caller, reason = ?status.reason, "Gateway switched to read-only mode");
+        "read_only"
+    } else {
+        ctx.read_only.disable()?;
+        tracing::info!(call_id, %caller, "Gateway left read-only mode");
+        "read_write"
+    };
+    if let Some(audit) = &ctx.audit {
+        audit.record_job_call(SET_READ_ONLY_JOB_ID, call_id, action, &caller.to_string());
+    }
+
+    ctx.processed_calls
+        .mark_processed(SET_READ_ONLY_JOB_ID, call_id)?;
+
+    if let Some(claim) = claim {
+        claim.complete()?;
+    }
+    Ok(TangleResult(()))
+}
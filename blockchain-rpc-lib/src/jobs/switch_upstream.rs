@@ -0,0 +1,88 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::jobs::{SWITCH_UPSTREAM_JOB_ID, ensure_admin};
+use crate::switchover::{SwitchUpstreamRequest, UpstreamSwitch, switch_upstream};
+use blueprint_sdk::{
+    extract::Context,
+    macros::debug_job,
+    tangle::extract::{CallId, Caller, TangleArg, TangleResult},
+};
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::AccountId32;
+use url::Url;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SwitchUpstreamInput {
+    /// New backend URL (`http(s)://` or `ws(s)://`).
+    pub proxy_to_url: String,
+    /// New additional backends, replacing `rpc.upstreams`.
+    #[serde(default)]
+    pub upstreams: Vec<String>,
+    /// Seconds WebSocket sessions may stay on the replaced backends before they are asked to
+    /// reconnect; `0` asks them right away.
+    #[serde(default)]
+    pub drain_secs: u64,
+}
+
+impl SwitchUpstreamInput {
+    fn into_request(self) -> Result<SwitchUpstreamRequest> {
+        let parse = |url: &str| {
+            Url::parse(url)
+                .map_err(|e| Error::InvalidJobInput(format!("Invalid backend URL {url}: {e}")))
+        };
+        Ok(SwitchUpstreamRequest {
+            proxy_to_url: parse(&self.proxy_to_url)?,
+            upstreams: self
+                .upstreams
+                .iter()
+                .map(|url| parse(url))
+                .collect::<Result<_>>()?,
+            drain_secs: self.drain_secs,
+        })
+    }
+}
+
+/// Job handler switching the default route to other backends without a restart, for
+/// blue/green backend upgrades. New requests go to the new backends at once, WebSocket
+/// sessions on the replaced ones are asked to reconnect after `drain_secs`, and the switch is
+/// persisted, recorded in the audit log and sent as an `UpstreamSwitched` webhook event.
+/// Returns the old and new backends.
+/// Only `jobs.admin_accounts` may call it.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
+    TangleArg(input): TangleArg<SwitchUpstreamInput>,
+) -> Result<TangleResult<Option<UpstreamSwitch>>> {
+    let caller = AccountId32::from(caller);
+    ensure_admin(&ctx, "switch_upstream", &caller)?;
+
+    if ctx
+        .processed_calls
+        .is_processed(SWITCH_UPSTREAM_JOB_ID, call_id)
+    {
+        tracing::info!(
+            call_id,
+            "Skipping already processed switch_upstream job call"
+        );
+        return Ok(TangleResult(None));
+    }
+
+    let switch = switch_upstream(&ctx, input.into_request()?, &caller.to_string()).await?;
+    if let Some(audit) = &ctx.audit {
+        let targets: Vec<String> = switch.new_targets.iter().map(Url::to_string).collect();
+        audit.record_job_call(
+            SWITCH_UPSTREAM_JOB_ID,
+            call_id,
+            "switch_upstream",
+            &targets.join(", "),
+        );
+    }
+
+    ctx.processed_calls
+        .mark_processed(SWITCH_UPSTREAM_JOB_ID, call_id)?;
+
+    Ok(TangleResult(Some(switch)))
+}
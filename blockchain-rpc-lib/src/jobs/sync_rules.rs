@@ -0,0 +1,65 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::jobs::{SYNC_RULES_JOB_ID, ensure_admin};
+use crate::rule_sync::SyncRulesInput;
+use blueprint_sdk::{
+    extract::Context,
+    macros::debug_job,
+    tangle::extract::{CallId, Caller, TangleArg, TangleResult},
+};
+use sp_core::crypto::AccountId32;
+
+/// Job handler applying rule changes another operator made through its admin API, submitted
+/// by its `[sync]` queue. Every instance of the service applies them, so allow rules, bans and
+/// temporary grants converge across operators. Changes that can't be applied here (e.g. past
+/// a rule limit) are skipped with a warning rather than failing the rest. Returns how many
+/// were applied. Only `sync.operators` and `jobs.admin_accounts` may call it.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
+    TangleArg(input): TangleArg<SyncRulesInput>,
+) -> Result<TangleResult<u32>> {
+    let caller = AccountId32::from(caller);
+    let peer = ctx
+        .config()
+        .sync
+        .as_ref()
+        .is_some_and(|sync| sync.operators.contains(&caller));
+    if !peer {
+        ensure_admin(&ctx, "sync_rules", &caller)?;
+    }
+
+    if ctx.processed_calls.is_processed(SYNC_RULES_JOB_ID, call_id) {
+        tracing::info!(call_id, "Skipping already processed sync_rules job call");
+        return Ok(TangleResult(0));
+    }
+
+    let mut applied = 0;
+    for mutation in &input.mutations {
+        match mutation.apply(&ctx.firewall).await {
+            Ok(()) => {
+                applied += 1;
+                if let Some(audit) = &ctx.audit {
+                    audit.record_job_call(
+                        SYNC_RULES_JOB_ID,
+                        call_id,
+                        mutation.action(),
+                        mutation.target().value(),
+                    );
+                }
+            }
+            Err(e) => {
+                tracing::warn!(call_id, %caller, ?mutation, error = %e, "Skipping synchronized rule change");
+            }
+        }
+    }
+
+    ctx.processed_calls
+        .mark_processed(SYNC_RULES_JOB_ID, call_id)?;
+
+    tracing::info!(call_id, %caller, applied, total = input.mutations.len(), "Applied synchronized rule changes");
+
+    Ok(TangleResult(applied))
+}
@@ -0,0 +1,107 @@
+use crate::Result;
+use crate::config::RateLimitConfig;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::jobs::{UPDATE_CONFIG_JOB_ID, ensure_admin};
+use crate::runtime_config::RpcOverrides;
+use blueprint_sdk::{
+    extract::Context,
+    macros::debug_job,
+    tangle::extract::{CallId, Caller, TangleArg, TangleResult},
+};
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::AccountId32;
+use url::Url;
+
+/// Settings to change; omitted fields are left as they are.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct UpdateConfigInput {
+    /// New backend URL (`http(s)://` or `ws(s)://`).
+    #[serde(default)]
+    pub proxy_to_url: Option<String>,
+    #[serde(default)]
+    pub request_timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub max_body_size_bytes: Option<u64>,
+    /// New per-IP rate limit, replacing `[firewall.rate_limit]`.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+impl UpdateConfigInput {
+    fn into_overrides(self) -> Result<RpcOverrides> {
+        let proxy_to_url = match self.proxy_to_url {
+            Some(url) => Some(
+                Url::parse(&url)
+                    .map_err(|e| Error::InvalidJobInput(format!("Invalid backend URL: {e}")))?,
+            ),
+            None => None,
+        };
+        let max_body_size_bytes = match self.max_body_size_bytes {
+            Some(size) => Some(usize::try_from(size).map_err(|_| {
+                Error::InvalidJobInput("max_body_size_bytes is too large".to_string())
+            })?),
+            None => None,
+        };
+        let overrides = RpcOverrides {
+            proxy_to_url,
+            request_timeout_secs: self.request_timeout_secs,
+            max_body_size_bytes,
+            rate_limit: self.rate_limit,
+        };
+        overrides.validate()?;
+        Ok(overrides)
+    }
+}
+
+/// Job handler to change the backend URL, request timeout, body size limit or per-IP rate
+/// limit at runtime. The changes are validated, applied like a config reload, persisted so
+/// they take precedence over `config.toml` across reloads and restarts, and reported to the
+/// audit log and as a `ConfigUpdated` webhook event.
+/// Only `jobs.admin_accounts` may call it.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
+    TangleArg(input): TangleArg<UpdateConfigInput>,
+) -> Result<TangleResult<()>> {
+    ensure_admin(&ctx, "update_config", &AccountId32::from(caller))?;
+
+    if ctx
+        .processed_calls
+        .is_processed(UPDATE_CONFIG_JOB_ID, call_id)
+    {
+        tracing::info!(call_id, "Skipping already processed update_config job call");
+        return Ok(TangleResult(()));
+    }
+
+    let changes = input.into_overrides()?;
+    if changes.is_empty() {
+        return Err(Error::InvalidJobInput("No settings to update".to_string()));
+    }
+
+    let current = ctx.config();
+    let mut updated = (*current).clone();
+    changes.apply(&mut updated);
+    updated.validate_reload(&current)?;
+    ctx.apply_config(updated)?;
+    ctx.runtime_config.update(changes.clone())?;
+
+    let settings = changes.settings();
+    tracing::info!(call_id, ?settings, "Updated runtime configuration");
+    if let Some(audit) = &ctx.audit {
+        audit.record_job_call(
+            UPDATE_CONFIG_JOB_ID,
+            call_id,
+            "update_config",
+            &settings.join(", "),
+        );
+    }
+    ctx.firewall.report_config_updated(settings).await;
+
+    ctx.processed_calls
+        .mark_processed(UPDATE_CONFIG_JOB_ID, call_id)?;
+
+    Ok(TangleResult(()))
+}
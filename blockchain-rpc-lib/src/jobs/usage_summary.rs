@@ -0,0 +1,50 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::jobs::USAGE_SUMMARY_JOB_ID;
+use crate::metering::MonthlySummary;
+use blueprint_sdk::{
+    extract::Context,
+    macros::debug_job,
+    tangle::extract::{CallId, TangleResult},
+};
+
+/// Job handler returning the monthly usage summaries closed since the last call, oldest
+/// first: per account, its requests, calls, compute units, bytes and top methods. The result
+/// is submitted on-chain as a statement service owners can invoice their users from; the
+/// returned summaries are not reported again. Each summary is also sent to webhooks as
+/// `MonthlyUsageSummary` events when the month closes.
+#[debug_job]
+pub async fn handler(
+    Context(ctx): Context<SecureRpcContext>,
+    CallId(call_id): CallId,
+) -> Result<TangleResult<Vec<MonthlySummary>>> {
+    let Some(meter) = &ctx.usage_meter else {
+        return Err(Error::InvalidJobInput(
+            "Usage metering is not enabled".to_string(),
+        ));
+    };
+
+    if ctx
+        .processed_calls
+        .is_processed(USAGE_SUMMARY_JOB_ID, call_id)
+    {
+        tracing::info!(call_id, "Skipping already processed usage_summary job call");
+        return Ok(TangleResult(Vec::new()));
+    }
+
+    if let Some(summary) = meter.close_month_if_due() {
+        ctx.firewall.report_monthly_usage(&summary).await;
+    }
+    let summaries = meter.take_summaries();
+    meter.persist()?;
+    ctx.processed_calls
+        .mark_processed(USAGE_SUMMARY_JOB_ID, call_id)?;
+    tracing::info!(
+        call_id,
+        months = summaries.len(),
+        "Reported monthly usage summaries"
+    );
+
+    Ok(TangleResult(summaries))
+}
@@ -0,0 +1,193 @@
+//! Canonical SCALE encodings of the job inputs and outputs that cross the chain SCALE-encoded:
+//! `pay_for_access`'s [`PayForAccessInput`] and `query_rules`'s [`FirewallState`]. The other
+//! jobs take their arguments as Tangle fields and aren't covered.
+//!
+//! The vectors are checked in as `fixtures/scale_vectors.json`, so the contract calling
+//! `SERVICES_CONTRACT.callJob` can assert its encoder produces the same bytes, and any change
+//! to these types' encoding fails this crate's tests before it breaks the contract.
+
+use crate::jobs::allow_access::AccessTarget;
+use crate::jobs::pay_for_access::PayForAccessInput;
+use crate::jobs::query_rules::{FirewallState, RuleListState, TemporaryGrantState, WebhookState};
+use crate::jobs::{PAY_FOR_ACCESS_JOB_ID, QUERY_RULES_JOB_ID};
+use crate::payments::PaymentReference;
+use parity_scale_codec::{Decode, DecodeAll, Encode};
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::AccountId32;
+
+/// Alice's well-known development account.
+const ALICE: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+const ALICE_ID: [u8; 32] = [
+    0xd4, 0x35, 0x93, 0xc7, 0x15, 0xfd, 0xd3, 0x1c, 0x61, 0x14, 0x1a, 0xbd, 0x04, 0xa9, 0x9f, 0xd6,
+    0x82, 0x2c, 0x85, 0x58, 0x85, 0x4c, 0xcd, 0xe3, 0x9a, 0x56, 0x84, 0xe7, 0xa5, 0x6d, 0xa2, 0x7d,
+];
+
+/// One value of a SCALE-encoded job type and its canonical encoding.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobVector {
+    pub job_id: u64,
+    pub job: String,
+    /// `input` or `output`.
+    pub direction: String,
+    /// The Rust type's name, as [`verify`] takes it.
+    pub type_name: String,
+    /// What the value exercises.
+    pub name: String,
+    /// Lowercase hex, without `0x`.
+    pub hex: String,
+}
+
+/// Why bytes aren't the canonical encoding of a job type.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum VectorError {
+    #[error("unknown job type {0}")]
+    UnknownType(String),
+    #[error("invalid hex: {0}")]
+    InvalidHex(String),
+    #[error("does not decode: {0}")]
+    Decode(String),
+    #[error("decodes, but re-encodes as {0}")]
+    NotCanonical(String),
+}
+
+/// Every vector, in the order of `fixtures/scale_vectors.json`.
+pub fn job_vectors() -> Vec<JobVector> {
+    let payment = PaymentReference {
+        block_hash: format!("0x{}", "ab".repeat(32)),
+        event_index: 3,
+    };
+    vec![
+        pay_for_access_input(
+            "AccessTarget",
+            "ip",
+            &AccessTarget::Ip("203.0.113.0/24".to_string()),
+        ),
+        pay_for_access_input(
+            "AccessTarget",
+            "account",
+            &AccessTarget::Account(ALICE.to_string()),
+        ),
+        pay_for_access_input("PaymentReference", "prefixed_block_hash", &payment),
+        pay_for_access_input(
+            "PayForAccessInput",
+            "account_only",
+            &PayForAccessInput {
+                beneficiary: AccessTarget::Account(ALICE.to_string()),
+                duration_secs: 3600,
+                idempotency_key: None,
+                payment: None,
+                plan: None,
+            },
+        ),
+        pay_for_access_input(
+            "PayForAccessInput",
+            "cidr_with_payment_and_plan",
+            &PayForAccessInput {
+                beneficiary: AccessTarget::Ip("203.0.113.0/24".to_string()),
+                duration_secs: 86_400,
+                idempotency_key: Some("order-42".to_string()),
+                payment: Some(payment),
+                plan: Some("pro".to_string()),
+            },
+        ),
+        query_rules_output(
+            "unrestricted_empty",
+            &FirewallState {
+                allow_unrestricted_access: true,
+                config: RuleListState::default(),
+                dynamic: RuleListState::default(),
+                temporary_grants: Vec::new(),
+                webhooks: Vec::new(),
+            },
+        ),
+        query_rules_output(
+            "rules_grants_and_webhooks",
+            &FirewallState {
+                allow_unrestricted_access: false,
+                config: RuleListState {
+                    allow_ips: vec!["10.0.0.0/8".to_string()],
+                    ..RuleListState::default()
+                },
+                dynamic: RuleListState {
+                    allow_accounts: vec![AccountId32::new(ALICE_ID)],
+                    deny_ips: vec!["198.51.100.7/32".to_string()],
+                    ..RuleListState::default()
+                },
+                temporary_grants: vec![TemporaryGrantState {
+                    source: "203.0.113.0/24".to_string(),
+                    granted_at: 1_700_000_000,
+                    expires_at: 1_700_086_400,
+                    plan: Some("pro".to_string()),
+                }],
+                webhooks: vec![WebhookState {
+                    url: "https://hooks.example.com/rpc".to_string(),
+                    events: vec!["AccessGranted".to_string(), "Banned".to_string()],
+                    account: Some(AccountId32::new(ALICE_ID)),
+                }],
+            },
+        ),
+    ]
+}
+
+/// Checks `hex` (with or without `0x`) is the canonical encoding of a `type_name` value: it
+/// decodes with no bytes left over and encodes back to the same bytes.
+pub fn verify(type_name: &str, hex: &str) -> Result<(), VectorError> {
+    let bytes = hex::decode(hex.trim_start_matches("0x"))
+        .map_err(|e| VectorError::InvalidHex(e.to_string()))?;
+    match type_name {
+        "AccessTarget" => round_trip::<AccessTarget>(&bytes),
+        "PaymentReference" => round_trip::<PaymentReference>(&bytes),
+        "PayForAccessInput" => round_trip::<PayForAccessInput>(&bytes),
+        "FirewallState" => round_trip::<FirewallState>(&bytes),
+        other => Err(VectorError::UnknownType(other.to_string())),
+    }
+}
+
+fn round_trip<T: Encode + Decode>(bytes: &[u8]) -> Result<(), VectorError> {
+    let value = T::decode_all(&mut &bytes[..]).map_err(|e| VectorError::Decode(e.to_string()))?;
+    let encoded = value.encode();
+    if encoded != bytes {
+        return Err(VectorError::NotCanonical(hex::encode(encoded)));
+    }
+    Ok(())
+}
+
+fn pay_for_access_input(type_name: &str, name: &str, value: &impl Encode) -> JobVector {
+    vector(
+        PAY_FOR_ACCESS_JOB_ID,
+        "pay_for_access",
+        "input",
+        type_name,
+        name,
+        value,
+    )
+}
+
+fn query_rules_output(name: &str, value: &FirewallState) -> JobVector {
+    vector(
+        QUERY_RULES_JOB_ID,
+        "query_rules",
+        "output",
+        "FirewallState",
+        name,
+        value,
+    )
+}
+
+fn vector(
+    job_id: u64,
+    job: &str,
+    direction: &str,
+    type_name: &str,
+    name: &str,
+    value: &impl Encode,
+) -> JobVector {
+    JobVector {
+        job_id,
+        job: job.to_string(),
+        direction: direction.to_string(),
+        type_name: type_name.to_string(),
+        name: name.to_string(),
+        hex: hex::encode(value.encode()),
+    }
+}
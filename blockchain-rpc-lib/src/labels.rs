@@ -0,0 +1,112 @@
+use crate::Result;
+use crate::error::Error;
+use crate::store::StateStore;
+use parking_lot::RwLock;
+use sp_runtime::AccountId32;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tracing::warn;
+
+const LABELS_NAMESPACE: &str = "account_labels";
+
+/// Labels one account may carry, so they stay cheap to attach to every log line and event.
+pub const MAX_LABELS_PER_ACCOUNT: usize = 16;
+pub const MAX_LABEL_KEY_CHARS: usize = 64;
+pub const MAX_LABEL_VALUE_CHARS: usize = 256;
+
+/// Free-form labels on an account, e.g. `customer: acme`, `tier: pro`.
+pub type Labels = BTreeMap<String, String>;
+
+/// Operator-assigned labels per account, attached to request logs, usage exports and webhook
+/// events about the account, so multi-customer deployments can tell accounts apart.
+///
+/// Labels are persisted in the state store and written through on every change.
+#[derive(Debug)]
+pub struct AccountLabels {
+    store: Arc<dyn StateStore>,
+    labels: RwLock<HashMap<AccountId32, Arc<Labels>>>,
+}
+
+impl AccountLabels {
+    /// Loads the labels persisted in `store`. Entries that fail to decode are skipped with a
+    /// warning.
+    pub fn load(store: Arc<dyn StateStore>) -> Result<Self> {
+        let mut labels = HashMap::new();
+        for (key, value) in store.entries(LABELS_NAMESPACE)? {
+            let account = <[u8; 32]>::try_from(key.as_slice())
+                .ok()
+                .map(AccountId32::new);
+            match (account, serde_json::from_slice::<Labels>(&value)) {
+                (Some(account), Ok(decoded)) => {
+                    labels.insert(account, Arc::new(decoded));
+                }
+                _ => warn!(key = ?key, "Skipping undecodable persisted account labels"),
+            }
+        }
+        Ok(Self {
+            store,
+            labels: RwLock::new(labels),
+        })
+    }
+
+    pub fn get(&self, account: &AccountId32) -> Option<Arc<Labels>> {
+        self.labels.read().get(account).cloned()
+    }
+
+    /// Every labelled account.
+    pub fn all(&self) -> BTreeMap<String, Labels> {
+        self.labels
+            .read()
+            .iter()
+            .map(|(account, labels)| (account.to_string(), (**labels).clone()))
+            .collect()
+    }
+
+    /// Replaces an account's labels. An empty set removes them.
+    pub fn set(&self, account: &AccountId32, labels: Labels) -> Result<()> {
+        validate_labels(&labels)?;
+        let key: &[u8] = account.as_ref();
+        if labels.is_empty() {
+            self.store.remove(LABELS_NAMESPACE, key)?;
+            self.labels.write().remove(account);
+        } else {
+            self.store
+                .insert(LABELS_NAMESPACE, key, &serde_json::to_vec(&labels)?)?;
+            self.labels
+                .write()
+                .insert(account.clone(), Arc::new(labels));
+        }
+        Ok(())
+    }
+}
+
+/// Checks labels against the count and length limits. Keys must be non-empty.
+fn validate_labels(labels: &Labels) -> Result<()> {
+    if labels.len() > MAX_LABELS_PER_ACCOUNT {
+        return Err(Error::InvalidLabels(format!(
+            "at most {MAX_LABELS_PER_ACCOUNT} labels per account"
+        )));
+    }
+    for (key, value) in labels {
+        if key.is_empty() || key.chars().count() > MAX_LABEL_KEY_CHARS {
+            return Err(Error::InvalidLabels(format!(
+                "label keys must be 1 to {MAX_LABEL_KEY_CHARS} characters"
+            )));
+        }
+        if value.chars().count() > MAX_LABEL_VALUE_CHARS {
+            return Err(Error::InvalidLabels(format!(
+                "label {key} is over {MAX_LABEL_VALUE_CHARS} characters"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Labels as `key=value` pairs joined by commas, for log fields.
+pub fn format_labels(labels: &Labels) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join(",")
+}
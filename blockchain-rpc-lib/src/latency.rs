@@ -0,0 +1,192 @@
+use crate::config::LatencyConfig;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Upper bounds of the latency histogram buckets, in milliseconds. Slower calls land in a
+/// final, unbounded bucket.
+pub const LATENCY_BUCKETS_MS: [u64; 12] = [
+    5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10_000, 30_000,
+];
+
+/// What batches are recorded as, since their calls share one round trip.
+pub const BATCH_METHOD: &str = "batch";
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS_MS.len() + 1],
+    count: u64,
+    total_ms: u64,
+    max_ms: u64,
+    slow: u64,
+}
+
+impl Histogram {
+    pub(crate) fn record(&mut self, elapsed_ms: u64, slow: bool) {
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| elapsed_ms <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.total_ms = self.total_ms.saturating_add(elapsed_ms);
+        self.max_ms = self.max_ms.max(elapsed_ms);
+        if slow {
+            self.slow += 1;
+        }
+    }
+
+    /// The upper bound of the bucket holding the `quantile`th call, capped at the slowest call
+    /// seen.
+    pub(crate) fn quantile_ms(&self, quantile: f64) -> u64 {
+        let rank = ((self.count as f64) * quantile).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let bound = LATENCY_BUCKETS_MS.get(bucket).copied().unwrap_or(u64::MAX);
+                return bound.min(self.max_ms);
+            }
+        }
+        self.max_ms
+    }
+
+    fn stats(&self, method: &str) -> MethodLatency {
+        MethodLatency {
+            method: method.to_string(),
+            calls: self.count,
+            slow: self.slow,
+            avg_ms: self.total_ms / self.count.max(1),
+            p50_ms: self.quantile_ms(0.5),
+            p90_ms: self.quantile_ms(0.9),
+            p99_ms: self.quantile_ms(0.99),
+            max_ms: self.max_ms,
+            buckets: LATENCY_BUCKETS_MS
+                .iter()
+                .map(|bound| Some(*bound))
+                .chain(std::iter::once(None))
+                .zip(self.buckets)
+                .map(|(le_ms, calls)| LatencyBucket { le_ms, calls })
+                .collect(),
+        }
+    }
+}
+
+/// Calls that took at most `le_ms` milliseconds and longer than the previous bucket's bound.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyBucket {
+    /// `None` for the last bucket, which has no upper bound.
+    pub le_ms: Option<u64>,
+    pub calls: u64,
+}
+
+/// One method's latency distribution. Percentiles are the upper bounds of the buckets they
+/// fall in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MethodLatency {
+    pub method: String,
+    pub calls: u64,
+    /// Calls over `rpc.latency.slow_request_ms`.
+    pub slow: u64,
+    pub avg_ms: u64,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+    pub buckets: Vec<LatencyBucket>,
+}
+
+/// Latency over a summary period, slowest methods by average first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub methods: Vec<MethodLatency>,
+}
+
+#[derive(Debug)]
+struct Period {
+    started_at: DateTime<Utc>,
+    methods: HashMap<String, Histogram>,
+}
+
+/// Backend round-trip time of proxied JSON-RPC calls, by method: since startup for the admin
+/// API, and per summary period for the `LatencySummary` webhook event.
+#[derive(Debug)]
+pub struct LatencyTracker {
+    totals: Mutex<HashMap<String, Histogram>>,
+    period: Mutex<Period>,
+    summary_methods: usize,
+}
+
+impl LatencyTracker {
+    pub fn new(config: &LatencyConfig) -> Self {
+        LatencyTracker {
+            totals: Mutex::new(HashMap::new()),
+            period: Mutex::new(Period {
+                started_at: Utc::now(),
+                methods: HashMap::new(),
+            }),
+            summary_methods: config.summary_methods,
+        }
+    }
+
+    /// Records a call to `method` that took `elapsed`, and whether that was over the slow
+    /// request threshold.
+    pub fn record(&self, method: &str, elapsed: Duration, slow: bool) {
+        let elapsed_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+        self.totals
+            .lock()
+            .entry(method.to_string())
+            .or_default()
+            .record(elapsed_ms, slow);
+        self.period
+            .lock()
+            .methods
+            .entry(method.to_string())
+            .or_default()
+            .record(elapsed_ms, slow);
+    }
+
+    /// Every method's latency since startup, by name.
+    pub fn stats(&self) -> Vec<MethodLatency> {
+        let mut methods: Vec<MethodLatency> = self
+            .totals
+            .lock()
+            .iter()
+            .map(|(method, histogram)| histogram.stats(method))
+            .collect();
+        methods.sort_by(|a, b| a.method.cmp(&b.method));
+        methods
+    }
+
+    /// Closes the current summary period, returning its slowest methods, or `None` if no call
+    /// was made in it.
+    pub fn close_period(&self) -> Option<LatencySummary> {
+        let mut period = self.period.lock();
+        let now = Utc::now();
+        let started_at = std::mem::replace(&mut period.started_at, now);
+        let closed = std::mem::take(&mut period.methods);
+        drop(period);
+        if closed.is_empty() {
+            return None;
+        }
+        let mut methods: Vec<MethodLatency> = closed
+            .iter()
+            .map(|(method, histogram)| histogram.stats(method))
+            .collect();
+        methods.sort_by(|a, b| {
+            b.avg_ms
+                .cmp(&a.avg_ms)
+                .then_with(|| a.method.cmp(&b.method))
+        });
+        methods.truncate(self.summary_methods.max(1));
+        Some(LatencySummary {
+            period_start: started_at,
+            period_end: now,
+            methods,
+        })
+    }
+}
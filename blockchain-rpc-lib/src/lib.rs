@@ -1,9 +1,81 @@
+pub mod access_events;
+pub mod access_log;
+pub mod access_windows;
+pub mod admin;
+pub mod api_keys;
+pub mod archive;
+pub mod audit;
+pub mod auto_ban;
+pub mod billing;
+pub mod circuit_breaker;
+pub mod client;
+pub mod client_certs;
+pub mod coalescing;
 pub mod config;
+pub mod config_patch;
+pub mod config_reload;
 pub mod context;
+pub mod cors;
+pub mod decision_cache;
+pub mod digest;
 pub mod error;
+pub mod event_history;
+pub mod fingerprint;
 pub mod firewall;
+pub mod forwarded;
+pub mod geoip;
+pub mod head_cache;
+pub mod health;
+pub mod history;
+pub mod idempotency;
+pub mod in_flight;
 pub mod jobs;
-pub mod rpc;
+pub mod labels;
+pub mod latency;
+pub mod listener;
+pub mod maintenance;
+pub mod metering;
+pub mod method_filter;
+pub mod method_limits;
+pub mod method_matcher;
+pub mod method_rewrite;
+pub mod mirror;
+pub mod origin_heatmap;
+pub mod payload_filters;
+pub mod payments;
+pub mod plans;
+pub mod pricing;
+pub mod proxy;
+pub mod ratelimit;
+pub mod read_only;
+pub mod relay;
+pub mod replay;
+pub mod request_id;
+pub mod request_validation;
+pub mod roles;
+pub mod rule_sync;
+pub mod runtime_config;
+pub mod self_test;
+pub mod sessions;
+pub mod shadow_policy;
+#[cfg(feature = "redis")]
+pub mod shared_ratelimit;
+pub mod signature_auth;
+pub mod store;
+pub mod subscriptions;
+pub mod switchover;
+pub mod tasks;
+pub mod telemetry;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod tls;
+pub mod trace_context;
+pub mod traffic_stats;
+pub mod upload;
+pub mod upstream_events;
+pub mod upstream_probe;
+pub mod upstream_signing;
+pub mod webhooks;
 
 pub use context::SecureRpcContext;
 pub use error::Error;
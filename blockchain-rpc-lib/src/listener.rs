@@ -0,0 +1,217 @@
+use crate::Result;
+use crate::access_log;
+use crate::context::SecureRpcContext;
+use crate::cors::cors_layer;
+use crate::forwarded::ProxyProtocolAcceptor;
+use crate::health;
+use crate::proxy::grpc::is_grpc;
+use crate::proxy::rpc_handler;
+use crate::request_id::{RequestId, assign_request_id};
+use crate::tls::{GatewayTlsAcceptor, load_rustls_config, spawn_certificate_reloader};
+use crate::traffic_stats;
+use crate::upload::{LimitedUpload, UploadError, UploadStatus};
+use axum::{
+    Router,
+    body::Body,
+    extract::State,
+    http::{Extensions, HeaderMap, Request, StatusCode, Version, header::CONTENT_LENGTH},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{any, get},
+};
+use axum_server::accept::DefaultAcceptor;
+use axum_server::tls_rustls::RustlsAcceptor;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tower_http::compression::CompressionLayer;
+use tower_http::compression::predicate::{NotForContentType, Predicate, SizeAbove};
+use tower_http::trace::TraceLayer;
+use tracing::info;
+
+/// Starts the main RPC gateway server.
+pub async fn start_rpc_gateway(ctx: Arc<SecureRpcContext>) -> Result<()> {
+    let listen_addr = ctx.config().rpc.listen_addr;
+    info!(%listen_addr, proxy_url = %ctx.upstream.proxy_url(), "Starting RPC gateway");
+    for route in ctx.routes.routes().skip(1) {
+        info!(
+            prefix = route.name(),
+            proxy_url = %route.upstream.proxy_url(),
+            "Routing path prefix"
+        );
+    }
+
+    // Preflights are answered here and never reach the backend.
+    let cors = cors_layer(&ctx.config().rpc);
+
+    let shutdown = ctx.tasks.shutdown_token();
+    let tls_config = ctx.config().rpc.tls.clone();
+    let tasks = ctx.tasks.clone();
+    let fingerprint = ctx.config().fingerprint.enabled;
+    let proxy_protocol = ctx
+        .config()
+        .rpc
+        .proxy_protocol
+        .then(|| ctx.config().rpc.trusted_proxies.clone());
+
+    let mut app = Router::new();
+    if ctx.config().rpc.health.is_some() {
+        // Answered by the gateway itself: no firewall check, never proxied
+        app = app
+            .route("/healthz", get(health::healthz))
+            .route("/readyz", get(health::readyz));
+    }
+    let mut app = app
+        .route("/", any(rpc_handler))
+        .route("/*path", any(rpc_handler));
+    if let Some(compression) = &ctx.config().rpc.compression {
+        // Negotiated per response from `Accept-Encoding`. Backend responses that already carry
+        // a `Content-Encoding` are passed through, and streamed bodies are compressed as they
+        // stream.
+        info!(
+            min_size_bytes = compression.min_size_bytes,
+            "Compressing responses"
+        );
+        app = app.layer(
+            CompressionLayer::new().compress_when(
+                SizeAbove::new(compression.min_size_bytes)
+                    .and(NotForContentType::GRPC)
+                    .and(NotForContentType::IMAGES)
+                    .and(NotForContentType::SSE)
+                    .and(is_not_upgrade),
+            ),
+        );
+    }
+    let app = app
+        .layer(
+            TraceLayer::new_for_http().make_span_with(|req: &Request<Body>| {
+                let request_id = req.extensions().get::<RequestId>();
+                tracing::info_span!(
+                    "request",
+                    method = %req.method(),
+                    uri = %req.uri(),
+                    request_id = request_id.map(RequestId::as_str),
+                    trace_id = tracing::field::Empty,
+                    fingerprint = tracing::field::Empty,
+                    account_labels = tracing::field::Empty,
+                )
+            }),
+        )
+        .layer(cors)
+        .layer(middleware::from_fn_with_state(ctx.clone(), request_limits))
+        .layer(middleware::from_fn_with_state(
+            ctx.clone(),
+            traffic_stats::record_request,
+        ))
+        .layer(middleware::from_fn_with_state(
+            ctx.clone(),
+            access_log::log_request,
+        ))
+        .layer(middleware::from_fn(assign_request_id))
+        .with_state(ctx)
+        .into_make_service_with_connect_info::<SocketAddr>();
+
+    let handle = axum_server::Handle::new();
+    if tls_config.is_some() || proxy_protocol.is_some() {
+        let shutdown_handle = handle.clone();
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            shutdown.cancelled().await;
+            shutdown_handle.graceful_shutdown(None);
+        });
+    }
+
+    match (tls_config, proxy_protocol) {
+        (Some(tls_config), proxy_protocol) => {
+            info!(cert_path = %tls_config.cert_path.display(), "Serving RPC gateway over TLS");
+            let rustls_config = load_rustls_config(&tls_config).await?;
+            spawn_certificate_reloader(&tasks, tls_config, rustls_config.clone());
+
+            let acceptor = GatewayTlsAcceptor::new(RustlsAcceptor::new(rustls_config), fingerprint);
+            match proxy_protocol {
+                Some(trusted) => {
+                    info!("Expecting PROXY protocol headers from trusted proxies");
+                    axum_server::bind(listen_addr)
+                        .acceptor(ProxyProtocolAcceptor::new(acceptor, trusted))
+                        .handle(handle)
+                        .serve(app)
+                        .await?;
+                }
+                None => {
+                    axum_server::bind(listen_addr)
+                        .acceptor(acceptor)
+                        .handle(handle)
+                        .serve(app)
+                        .await?;
+                }
+            }
+        }
+        (None, Some(trusted)) => {
+            info!("Expecting PROXY protocol headers from trusted proxies");
+            axum_server::bind(listen_addr)
+                .acceptor(ProxyProtocolAcceptor::new(DefaultAcceptor, trusted))
+                .handle(handle)
+                .serve(app)
+                .await?;
+        }
+        (None, None) => {
+            let listener = tokio::net::TcpListener::bind(listen_addr).await?;
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown.cancelled_owned())
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the body-size limit, body idle timeout and request timeout, read from the current
+/// config on every request so a config reload takes effect without restarting the listener.
+async fn request_limits(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let config = ctx.config();
+    let request_timeout = Duration::from_secs(config.rpc.request_timeout_secs);
+    // gRPC streams may run long and idle between messages, so they have limits of their own.
+    let (max_body_size, body_idle_timeout) = match &config.rpc.grpc {
+        Some(grpc) if ctx.grpc.is_some() && is_grpc(req.headers()) => {
+            (grpc.max_stream_bytes, grpc.stream_idle_timeout_secs)
+        }
+        _ => (
+            config.rpc.max_body_size_bytes,
+            config.rpc.body_idle_timeout_secs,
+        ),
+    };
+    let body_idle_timeout = Duration::from_secs(body_idle_timeout.max(1));
+
+    let declared_length = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if declared_length.is_some_and(|length| length > max_body_size) {
+        return UploadError::TooLarge.response();
+    }
+    let status = UploadStatus::default();
+    let mut req = req.map(|body| {
+        Body::new(LimitedUpload::new(
+            body,
+            max_body_size,
+            body_idle_timeout,
+            status.clone(),
+        ))
+    });
+    req.extensions_mut().insert(status);
+
+    match tokio::time::timeout(request_timeout, next.run(req)).await {
+        Ok(response) => response,
+        Err(_) => (StatusCode::REQUEST_TIMEOUT, "Request timed out").into_response(),
+    }
+}
+
+/// Keeps WebSocket upgrade responses uncompressed, whatever the size threshold.
+fn is_not_upgrade(status: StatusCode, _: Version, _: &HeaderMap, _: &Extensions) -> bool {
+    status != StatusCode::SWITCHING_PROTOCOLS
+}
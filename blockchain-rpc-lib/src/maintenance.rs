@@ -0,0 +1,114 @@
+use crate::config::{MaintenanceWindow, ServiceConfig};
+use crate::firewall::Firewall;
+use crate::proxy::routing::RoutingTable;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::interval;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use url::Url;
+
+/// How often the configured maintenance windows are checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Drains backends from rotation during their `[[rpc.maintenance]]` windows, read from the live
+/// configuration so windows added or removed by a reload apply on the next check.
+/// `MaintenanceStarting` is sent `notice_secs` before a window starts and `MaintenanceEnded`
+/// once it ends, or once it is removed after being announced.
+pub(crate) struct MaintenanceScheduler {
+    service_config: Arc<RwLock<Arc<ServiceConfig>>>,
+    routes: Arc<RoutingTable>,
+    firewall: Arc<Firewall>,
+    /// Windows announced and not yet ended, by backend and start.
+    announced: HashMap<(Url, DateTime<Utc>), MaintenanceWindow>,
+    /// Backends in maintenance left in rotation because every other backend of their route
+    /// is in maintenance too.
+    kept: HashSet<Url>,
+}
+
+impl MaintenanceScheduler {
+    pub(crate) fn new(
+        service_config: Arc<RwLock<Arc<ServiceConfig>>>,
+        routes: Arc<RoutingTable>,
+        firewall: Arc<Firewall>,
+    ) -> Self {
+        MaintenanceScheduler {
+            service_config,
+            routes,
+            firewall,
+            announced: HashMap::new(),
+            kept: HashSet::new(),
+        }
+    }
+
+    /// Checks the windows every few seconds until `shutdown`.
+    pub(crate) async fn run(mut self, shutdown: CancellationToken) {
+        let mut check_interval = interval(CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = check_interval.tick() => {}
+            }
+            self.check(Utc::now()).await;
+        }
+    }
+
+    async fn check(&mut self, now: DateTime<Utc>) {
+        let windows = self.service_config.read().rpc.maintenance.clone();
+        let mut current = HashSet::new();
+        let mut in_maintenance = HashSet::new();
+        for window in windows {
+            let notice = chrono::Duration::seconds(window.notice_secs as i64);
+            if now >= window.ends_at || now < window.starts_at - notice {
+                continue;
+            }
+            let key = (window.backend.clone(), window.starts_at);
+            if now >= window.starts_at {
+                in_maintenance.insert(window.backend.clone());
+            }
+            current.insert(key.clone());
+            if !self.announced.contains_key(&key) {
+                info!(
+                    backend = %window.backend,
+                    starts_at = %window.starts_at,
+                    ends_at = %window.ends_at,
+                    "Announcing backend maintenance"
+                );
+                self.firewall.report_maintenance_starting(&window).await;
+            }
+            // Keep the latest copy, so the end is reported as last configured
+            self.announced.insert(key, window);
+        }
+
+        let ended: Vec<_> = self
+            .announced
+            .keys()
+            .filter(|key| !current.contains(*key))
+            .cloned()
+            .collect();
+        for key in ended {
+            if let Some(window) = self.announced.remove(&key) {
+                info!(backend = %window.backend, "Backend maintenance ended");
+                self.firewall.report_maintenance_ended(&window).await;
+            }
+        }
+
+        // Backends are set on every check, since a reload of backend URLs replaces them
+        let drained: HashSet<Url> = self
+            .routes
+            .routes()
+            .flat_map(|route| route.upstream.set_maintenance(&in_maintenance))
+            .collect();
+        let kept: HashSet<Url> = in_maintenance.difference(&drained).cloned().collect();
+        for backend in kept.difference(&self.kept) {
+            warn!(
+                %backend,
+                "Backend in maintenance kept in rotation: every backend of its route is in maintenance"
+            );
+        }
+        self.kept = kept;
+    }
+}
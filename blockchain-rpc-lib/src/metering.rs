@@ -0,0 +1,598 @@
+use crate::Result;
+use crate::config::MeteringConfig;
+use crate::method_matcher::MethodMatcher;
+use crate::store::StateStore;
+use axum::body::Body;
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use futures::TryStreamExt;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sp_runtime::AccountId32;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+
+const METERING_NAMESPACE: &str = "metering";
+const USAGE_KEY: &[u8] = b"usage";
+const HISTORY_KEY: &[u8] = b"history";
+/// Where usage was kept before it moved to the state store; still read if the store has none.
+const LEGACY_USAGE_FILE: &str = "usage.json";
+/// Monthly summaries kept until the `usage_summary` job collects them.
+const MAX_PENDING_SUMMARIES: usize = 12;
+
+/// Who usage is billed to: the authenticated account, or the client IP for requests without one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UsageSubject {
+    Account(String), // AccountId32 as string
+    Ip(IpAddr),
+}
+
+impl UsageSubject {
+    pub fn new(client_ip: IpAddr, account: Option<&AccountId32>) -> Self {
+        match account {
+            Some(account) => UsageSubject::Account(account.to_string()),
+            None => UsageSubject::Ip(client_ip),
+        }
+    }
+}
+
+/// Consumption counted for one subject. Bytes are body bytes for HTTP and payload bytes for
+/// WebSocket messages.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct UsageCounters {
+    /// HTTP requests and WebSocket connections.
+    pub requests: u64,
+    /// Bytes sent by the client.
+    pub request_bytes: u64,
+    /// Bytes sent back to the client.
+    pub response_bytes: u64,
+    pub ws_messages_in: u64,
+    pub ws_messages_out: u64,
+    /// JSON-RPC calls, counting each call in a batch.
+    #[serde(default)]
+    pub calls: u64,
+    /// Calls weighted by the compute units of their methods.
+    #[serde(default)]
+    pub compute_units: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub subject: UsageSubject,
+    #[serde(flatten)]
+    pub counters: UsageCounters,
+}
+
+/// One account's usage this month and since metering began.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct AccountCounters {
+    /// The calendar month (UTC) so far.
+    pub month: UsageCounters,
+    pub total: UsageCounters,
+}
+
+/// Usage of all subjects over one closed period.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub usage: Vec<UsageRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MethodCount {
+    pub method: String,
+    pub calls: u64,
+}
+
+/// One account's usage over a month.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountUsageSummary {
+    pub account: String, // AccountId32 as string
+    #[serde(flatten)]
+    pub counters: UsageCounters,
+    /// Most called methods, most calls first.
+    pub top_methods: Vec<MethodCount>,
+}
+
+/// Per-account usage of one calendar month (UTC), a statement service owners can invoice
+/// their users from. Requests without an account aren't included.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonthlySummary {
+    /// `YYYY-MM`.
+    pub month: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub accounts: Vec<AccountUsageSummary>,
+}
+
+/// The open period and the closed periods not yet reported, as shown by the admin API and
+/// stored on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageSnapshot {
+    pub period_start: DateTime<Utc>,
+    pub current: Vec<UsageRecord>,
+    pub pending: Vec<UsageReport>,
+    /// The month so far, with every method called rather than only the top ones.
+    #[serde(default)]
+    pub month: Option<MonthlySummary>,
+    /// Closed months not yet collected by the `usage_summary` job.
+    #[serde(default)]
+    pub pending_summaries: Vec<MonthlySummary>,
+    /// Per-account usage since metering began.
+    #[serde(default)]
+    pub totals: BTreeMap<String, UsageCounters>,
+}
+
+impl UsageSnapshot {
+    /// Whether `account` has usage anywhere in the snapshot.
+    pub fn mentions_account(&self, account: &str) -> bool {
+        let in_periods = self
+            .current
+            .iter()
+            .chain(self.pending.iter().flat_map(|report| &report.usage))
+            .any(|record| matches!(&record.subject, UsageSubject::Account(a) if a == account));
+        let in_months = self
+            .month
+            .iter()
+            .chain(&self.pending_summaries)
+            .flat_map(|summary| &summary.accounts)
+            .any(|summary| summary.account == account);
+        in_periods || in_months || self.totals.contains_key(account)
+    }
+}
+
+#[derive(Debug)]
+struct MeterState {
+    period_start: DateTime<Utc>,
+    current: HashMap<UsageSubject, UsageCounters>,
+    pending: VecDeque<UsageReport>,
+    month: MonthState,
+    pending_summaries: VecDeque<MonthlySummary>,
+    totals: HashMap<String, UsageCounters>,
+    /// Account usage of closed periods within `history_days`, oldest first.
+    history: VecDeque<UsageReport>,
+}
+
+/// Per-account totals and method counts of the current month.
+#[derive(Debug)]
+struct MonthState {
+    start: DateTime<Utc>,
+    accounts: HashMap<String, (UsageCounters, HashMap<String, u64>)>,
+}
+
+impl MonthState {
+    fn starting(now: DateTime<Utc>) -> Self {
+        MonthState {
+            start: month_start(now),
+            accounts: HashMap::new(),
+        }
+    }
+
+    fn restore(summary: MonthlySummary) -> Self {
+        MonthState {
+            start: summary.period_start,
+            accounts: summary
+                .accounts
+                .into_iter()
+                .map(|account| {
+                    let methods = account
+                        .top_methods
+                        .into_iter()
+                        .map(|count| (count.method, count.calls))
+                        .collect();
+                    (account.account, (account.counters, methods))
+                })
+                .collect(),
+        }
+    }
+
+    /// Summarizes the month up to `end`, listing at most `top_methods` methods per account.
+    fn summarize(&self, end: DateTime<Utc>, top_methods: usize) -> MonthlySummary {
+        let mut accounts: Vec<AccountUsageSummary> = self
+            .accounts
+            .iter()
+            .map(|(account, (counters, methods))| {
+                let mut methods: Vec<MethodCount> = methods
+                    .iter()
+                    .map(|(method, calls)| MethodCount {
+                        method: method.clone(),
+                        calls: *calls,
+                    })
+                    .collect();
+                methods.sort_by(|a, b| b.calls.cmp(&a.calls).then_with(|| a.method.cmp(&b.method)));
+                methods.truncate(top_methods);
+                AccountUsageSummary {
+                    account: account.clone(),
+                    counters: *counters,
+                    top_methods: methods,
+                }
+            })
+            .collect();
+        accounts.sort_by(|a, b| a.account.cmp(&b.account));
+        MonthlySummary {
+            month: self.start.format("%Y-%m").to_string(),
+            period_start: self.start,
+            period_end: end,
+            accounts,
+        }
+    }
+}
+
+/// Counts requests, calls, compute units, bytes and WebSocket messages per account (or IP) in
+/// fixed periods.
+///
+/// Closed periods are queued until the `report_usage` job collects them and submits them
+/// on-chain, so billing can be reconciled against what the gateway actually served. Account
+/// usage is also totalled per calendar month into summaries for the `usage_summary` job, and
+/// since metering began for the `query_usage` job. Closed periods' account usage is kept for
+/// `history_days` for billing exports. The open period, the month so far, the totals, the
+/// history and the queues are persisted to the state store, so usage isn't lost across
+/// restarts.
+#[derive(Debug)]
+pub struct UsageMeter {
+    store: Arc<dyn StateStore>,
+    period: chrono::Duration,
+    max_pending_reports: usize,
+    compute_units: MethodMatcher<u64>,
+    default_compute_units: u64,
+    summary_top_methods: usize,
+    history: chrono::Duration,
+    state: Mutex<MeterState>,
+}
+
+impl UsageMeter {
+    /// Loads usage from `store`, or from the usage file earlier versions kept under
+    /// `data_dir`, starting a new period if none exists yet.
+    pub fn load(
+        store: Arc<dyn StateStore>,
+        data_dir: &Path,
+        config: &MeteringConfig,
+    ) -> Result<Self> {
+        let bytes = match store.get(METERING_NAMESPACE, USAGE_KEY)? {
+            Some(bytes) => Some(bytes),
+            None => match std::fs::read(data_dir.join(LEGACY_USAGE_FILE)) {
+                Ok(bytes) => Some(bytes),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            },
+        };
+        let snapshot = bytes.and_then(|bytes| {
+            serde_json::from_slice::<UsageSnapshot>(&bytes)
+                .map_err(|e| warn!(error = %e, "Ignoring corrupt persisted usage"))
+                .ok()
+        });
+        let history: VecDeque<UsageReport> = match store.get(METERING_NAMESPACE, HISTORY_KEY)? {
+            Some(bytes) => serde_json::from_slice::<Vec<UsageReport>>(&bytes)
+                .map_err(|e| warn!(error = %e, "Ignoring corrupt persisted usage history"))
+                .unwrap_or_default()
+                .into(),
+            None => VecDeque::new(),
+        };
+        let state = match snapshot {
+            Some(snapshot) => {
+                let month = match snapshot.month {
+                    Some(month) => MonthState::restore(month),
+                    None => MonthState::starting(Utc::now()),
+                };
+                // Usage persisted before totals were kept starts them from the month so far
+                let totals = if snapshot.totals.is_empty() {
+                    month
+                        .accounts
+                        .iter()
+                        .map(|(account, (counters, _))| (account.clone(), *counters))
+                        .collect()
+                } else {
+                    snapshot.totals.into_iter().collect()
+                };
+                MeterState {
+                    period_start: snapshot.period_start,
+                    current: snapshot
+                        .current
+                        .into_iter()
+                        .map(|record| (record.subject, record.counters))
+                        .collect(),
+                    pending: snapshot.pending.into(),
+                    month,
+                    pending_summaries: snapshot.pending_summaries.into(),
+                    totals,
+                    history,
+                }
+            }
+            None => MeterState {
+                period_start: Utc::now(),
+                current: HashMap::new(),
+                pending: VecDeque::new(),
+                month: MonthState::starting(Utc::now()),
+                pending_summaries: VecDeque::new(),
+                totals: HashMap::new(),
+                history,
+            },
+        };
+
+        Ok(UsageMeter {
+            store,
+            period: chrono::Duration::seconds(config.report_interval_secs.max(1) as i64),
+            max_pending_reports: config.max_pending_reports,
+            compute_units: config
+                .compute_units
+                .iter()
+                .map(|(pattern, units)| (pattern, *units))
+                .collect(),
+            default_compute_units: config.default_compute_units,
+            summary_top_methods: config.summary_top_methods,
+            history: chrono::Duration::days(config.history_days.min(i32::MAX as u64) as i64),
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn record_request(&self, subject: &UsageSubject) {
+        self.update(subject, |counters| counters.requests += 1);
+    }
+
+    pub fn record_request_bytes(&self, subject: &UsageSubject, bytes: usize) {
+        self.update(subject, |counters| counters.request_bytes += bytes as u64);
+    }
+
+    pub fn record_response_bytes(&self, subject: &UsageSubject, bytes: usize) {
+        self.update(subject, |counters| counters.response_bytes += bytes as u64);
+    }
+
+    /// Records the JSON-RPC calls of a request or WebSocket message, by method.
+    pub fn record_calls(&self, subject: &UsageSubject, methods: &[String]) {
+        if methods.is_empty() {
+            return;
+        }
+        let compute_units: u64 = methods
+            .iter()
+            .map(|method| {
+                self.compute_units
+                    .lookup(method)
+                    .copied()
+                    .unwrap_or(self.default_compute_units)
+            })
+            .sum();
+        let mut state = self.state.lock();
+        let record = |counters: &mut UsageCounters| {
+            counters.calls += methods.len() as u64;
+            counters.compute_units += compute_units;
+        };
+        record(state.current.entry(subject.clone()).or_default());
+        if let UsageSubject::Account(account) = subject {
+            let (counters, counts) = state.month.accounts.entry(account.clone()).or_default();
+            record(counters);
+            for method in methods {
+                *counts.entry(method.clone()).or_default() += 1;
+            }
+            record(state.totals.entry(account.clone()).or_default());
+        }
+    }
+
+    /// Records a WebSocket message from the client.
+    pub fn record_ws_inbound(&self, subject: &UsageSubject, bytes: usize) {
+        self.update(subject, |counters| {
+            counters.ws_messages_in += 1;
+            counters.request_bytes += bytes as u64;
+        });
+    }
+
+    /// Records a WebSocket message to the client.
+    pub fn record_ws_outbound(&self, subject: &UsageSubject, bytes: usize) {
+        self.update(subject, |counters| {
+            counters.ws_messages_out += 1;
+            counters.response_bytes += bytes as u64;
+        });
+    }
+
+    /// Wraps a request body so its bytes are counted as they are read.
+    pub fn count_request_body(self: &Arc<Self>, subject: UsageSubject, body: Body) -> Body {
+        let meter = self.clone();
+        Body::from_stream(
+            body.into_data_stream()
+                .inspect_ok(move |chunk| meter.record_request_bytes(&subject, chunk.len())),
+        )
+    }
+
+    /// Wraps a response body so its bytes are counted as they are sent.
+    pub fn count_response_body(self: &Arc<Self>, subject: UsageSubject, body: Body) -> Body {
+        let meter = self.clone();
+        Body::from_stream(
+            body.into_data_stream()
+                .inspect_ok(move |chunk| meter.record_response_bytes(&subject, chunk.len())),
+        )
+    }
+
+    /// Closes the open period if it has run its length. Returns true if a period was closed.
+    ///
+    /// Periods without any usage are not queued. When the queue is full the oldest report is
+    /// dropped. The period's account usage is also added to the history, and periods older than
+    /// `history_days` are dropped from it.
+    pub fn close_period_if_due(&self) -> bool {
+        let now = Utc::now();
+        let mut state = self.state.lock();
+        if now - state.period_start < self.period {
+            return false;
+        }
+
+        let usage = std::mem::take(&mut state.current);
+        let report = UsageReport {
+            period_start: std::mem::replace(&mut state.period_start, now),
+            period_end: now,
+            usage: into_records(usage),
+        };
+        let cutoff = now - self.history;
+        while state
+            .history
+            .front()
+            .is_some_and(|period| period.period_end <= cutoff)
+        {
+            state.history.pop_front();
+        }
+        if report.usage.is_empty() {
+            return true;
+        }
+        let accounts = account_usage(&report);
+        if self.history > chrono::Duration::zero() && !accounts.usage.is_empty() {
+            state.history.push_back(accounts);
+        }
+        state.pending.push_back(report);
+        while state.pending.len() > self.max_pending_reports {
+            if let Some(dropped) = state.pending.pop_front() {
+                warn!(
+                    period_start = %dropped.period_start,
+                    "Dropping unreported usage period, the report_usage job isn't collecting them"
+                );
+            }
+        }
+        true
+    }
+
+    /// Closes the month once a new calendar month (UTC) has begun, returning its summary if any
+    /// account used the gateway. The summary is also queued for the `usage_summary` job; when
+    /// the queue is full the oldest summary is dropped.
+    pub fn close_month_if_due(&self) -> Option<MonthlySummary> {
+        let now = Utc::now();
+        let mut state = self.state.lock();
+        if month_start(now) <= state.month.start {
+            return None;
+        }
+
+        let month = std::mem::replace(&mut state.month, MonthState::starting(now));
+        let summary = month.summarize(month_start(now), self.summary_top_methods);
+        if summary.accounts.is_empty() {
+            return None;
+        }
+        state.pending_summaries.push_back(summary.clone());
+        while state.pending_summaries.len() > MAX_PENDING_SUMMARIES {
+            if let Some(dropped) = state.pending_summaries.pop_front() {
+                warn!(
+                    month = %dropped.month,
+                    "Dropping uncollected monthly usage summary, the usage_summary job isn't collecting them"
+                );
+            }
+        }
+        Some(summary)
+    }
+
+    /// Removes and returns the monthly summaries not collected yet, oldest first.
+    pub fn take_summaries(&self) -> Vec<MonthlySummary> {
+        self.state.lock().pending_summaries.drain(..).collect()
+    }
+
+    /// Removes and returns the closed periods not reported yet, oldest first.
+    pub fn take_reports(&self) -> Vec<UsageReport> {
+        self.state.lock().pending.drain(..).collect()
+    }
+
+    pub fn snapshot(&self) -> UsageSnapshot {
+        let state = self.state.lock();
+        UsageSnapshot {
+            period_start: state.period_start,
+            current: into_records(state.current.clone()),
+            pending: state.pending.iter().cloned().collect(),
+            month: Some(state.month.summarize(Utc::now(), usize::MAX)),
+            pending_summaries: state.pending_summaries.iter().cloned().collect(),
+            totals: state
+                .totals
+                .iter()
+                .map(|(account, counters)| (account.clone(), *counters))
+                .collect(),
+        }
+    }
+
+    /// The account's usage this month and since metering began, zero if it has none.
+    pub fn account_counters(&self, account: &str) -> AccountCounters {
+        let state = self.state.lock();
+        AccountCounters {
+            month: state
+                .month
+                .accounts
+                .get(account)
+                .map(|(counters, _)| *counters)
+                .unwrap_or_default(),
+            total: state.totals.get(account).copied().unwrap_or_default(),
+        }
+    }
+
+    /// Account usage of the periods overlapping `from..to`: closed periods still in the history
+    /// and the open period so far, oldest first. Periods are included whole.
+    pub fn account_history(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<UsageReport> {
+        let state = self.state.lock();
+        let open = UsageReport {
+            period_start: state.period_start,
+            period_end: Utc::now(),
+            usage: into_records(state.current.clone()),
+        };
+        state
+            .history
+            .iter()
+            .cloned()
+            .chain(std::iter::once(account_usage(&open)))
+            .filter(|period| period.period_start < to && period.period_end > from)
+            .filter(|period| !period.usage.is_empty())
+            .collect()
+    }
+
+    /// Writes the open period, the month so far, the totals, unreported periods and months and
+    /// the history to the state store.
+    pub fn persist(&self) -> Result<()> {
+        let bytes = serde_json::to_vec(&self.snapshot())?;
+        self.store.insert(METERING_NAMESPACE, USAGE_KEY, &bytes)?;
+        let history = serde_json::to_vec(&self.state.lock().history)?;
+        self.store.insert(METERING_NAMESPACE, HISTORY_KEY, &history)
+    }
+
+    /// Applies `f` to the subject's counters of the open period, and of the month and the
+    /// totals for accounts.
+    fn update(&self, subject: &UsageSubject, f: impl Fn(&mut UsageCounters)) {
+        let mut state = self.state.lock();
+        match state.current.get_mut(subject) {
+            Some(counters) => f(counters),
+            None => f(state.current.entry(subject.clone()).or_default()),
+        }
+        if let UsageSubject::Account(account) = subject {
+            match state.month.accounts.get_mut(account) {
+                Some((counters, _)) => f(counters),
+                None => f(&mut state.month.accounts.entry(account.clone()).or_default().0),
+            }
+            match state.totals.get_mut(account) {
+                Some(counters) => f(counters),
+                None => f(state.totals.entry(account.clone()).or_default()),
+            }
+        }
+    }
+}
+
+/// Midnight UTC on the first day of `now`'s month.
+fn month_start(now: DateTime<Utc>) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .single()
+        .unwrap_or(now)
+}
+
+/// The report's account records, sorted by account.
+fn account_usage(report: &UsageReport) -> UsageReport {
+    let mut usage: Vec<(String, UsageRecord)> = report
+        .usage
+        .iter()
+        .filter_map(|record| match &record.subject {
+            UsageSubject::Account(account) => Some((account.clone(), record.clone())),
+            UsageSubject::Ip(_) => None,
+        })
+        .collect();
+    usage.sort_by(|a, b| a.0.cmp(&b.0));
+    let usage = usage.into_iter().map(|(_, record)| record).collect();
+    UsageReport {
+        usage,
+        ..report.clone()
+    }
+}
+
+fn into_records(usage: HashMap<UsageSubject, UsageCounters>) -> Vec<UsageRecord> {
+    usage
+        .into_iter()
+        .map(|(subject, counters)| UsageRecord { subject, counters })
+        .collect()
+}
@@ -0,0 +1,104 @@
+use crate::config::MethodFilterConfig;
+use crate::method_matcher::MethodMatcher;
+use serde_json::Value;
+
+/// Filters JSON-RPC calls by method name before they reach the backend.
+///
+/// Patterns ending in `*` match by prefix (e.g. `personal_*`), all other patterns match exactly.
+/// The denylist always takes precedence. An empty allowlist allows every method not denied.
+/// Both lists are compiled into [`MethodMatcher`]s when the filter is built.
+#[derive(Debug, Clone, Default)]
+pub struct MethodFilter {
+    allow: MethodMatcher<()>,
+    deny: MethodMatcher<()>,
+}
+
+impl MethodFilter {
+    pub fn new(config: &MethodFilterConfig) -> Self {
+        MethodFilter {
+            allow: config.allow.iter().map(|p| (p, ())).collect(),
+            deny: config.deny.iter().map(|p| (p, ())).collect(),
+        }
+    }
+
+    /// Returns true if the filter has no rules and every method is forwarded.
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Checks a single method name against the deny and allow lists.
+    pub fn is_method_allowed(&self, method: &str) -> bool {
+        if self.deny.matches(method) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.matches(method)
+    }
+
+    /// Returns the first blocked method in a JSON-RPC payload (single call or batch), if any.
+    /// While any rule is set, a payload whose calls can't all be read is blocked as a whole,
+    /// named by what's wrong with it.
+    pub fn find_blocked_method(&self, payload: &[u8]) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+        match checked_calls(payload) {
+            Ok(calls) => calls
+                .into_iter()
+                .map(|(method, _)| method)
+                .find(|method| !self.is_method_allowed(method)),
+            Err(unreadable) => Some(unreadable),
+        }
+    }
+}
+
+/// The calls of a JSON-RPC payload (single call or batch) with their method names, for checks
+/// that must see every call. Fails with a description of the problem unless the payload is
+/// JSON and each call an object with a string `method`. Go backends match keys ignoring case,
+/// so a call with another spelling of `method`, e.g. `METHOD`, fails too.
+pub fn checked_calls(payload: &[u8]) -> Result<Vec<(String, Value)>, String> {
+    let value: Value =
+        serde_json::from_slice(payload).map_err(|_| "(payload is not JSON)".to_string())?;
+    let calls = match value {
+        Value::Array(calls) => calls,
+        call => vec![call],
+    };
+    calls
+        .into_iter()
+        .map(|call| {
+            let object = call
+                .as_object()
+                .ok_or_else(|| "(call is not an object)".to_string())?;
+            let spellings = object
+                .keys()
+                .filter(|key| key.eq_ignore_ascii_case("method"))
+                .count();
+            let method = object
+                .get("method")
+                .and_then(Value::as_str)
+                .filter(|_| spellings == 1)
+                .ok_or_else(|| "(call without a single string method)".to_string())?
+                .to_string();
+            Ok((method, call))
+        })
+        .collect()
+}
+
+/// Returns the number of calls in a JSON-RPC batch, or `None` if the payload isn't a batch.
+pub fn jsonrpc_batch_len(payload: &[u8]) -> Option<usize> {
+    match serde_json::from_slice::<Value>(payload).ok()? {
+        Value::Array(calls) => Some(calls.len()),
+        _ => None,
+    }
+}
+
+/// Returns the method names of a JSON-RPC payload (single call or batch), in request order.
+pub fn jsonrpc_methods(payload: &[u8]) -> Vec<String> {
+    let Ok(value) = serde_json::from_slice::<Value>(payload) else {
+        return Vec::new();
+    };
+    let method_of = |call: &Value| call.get("method")?.as_str().map(str::to_string);
+    match value {
+        Value::Array(calls) => calls.iter().filter_map(method_of).collect(),
+        call => method_of(&call).into_iter().collect(),
+    }
+}
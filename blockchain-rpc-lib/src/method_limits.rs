@@ -0,0 +1,108 @@
+use crate::config::{LimitsConfig, MethodLimitConfig};
+use crate::method_filter::checked_calls;
+use crate::method_matcher::MethodMatcher;
+use serde_json::Value;
+
+/// Per-method limits from `[limits]`, enforced on parsed JSON-RPC calls: the size of each call,
+/// the block range of log filters, and the size of the response to requests calling the method.
+///
+/// Methods are matched like the method filter's patterns: the exact pattern wins, then the
+/// longest prefix.
+#[derive(Debug, Clone, Default)]
+pub struct MethodLimits {
+    limits: MethodMatcher<MethodLimitConfig>,
+}
+
+impl MethodLimits {
+    pub fn new(config: &LimitsConfig) -> Self {
+        MethodLimits {
+            limits: config
+                .methods
+                .iter()
+                .map(|(pattern, limit)| (pattern, limit.clone()))
+                .collect(),
+        }
+    }
+
+    /// Returns true if no limits are configured.
+    pub fn is_empty(&self) -> bool {
+        self.limits.is_empty()
+    }
+
+    /// Checks every call in a JSON-RPC payload (single call or batch) against its method's
+    /// limits. Returns a description of the first violation, if any, or of why the calls
+    /// couldn't all be read.
+    pub fn check_request(&self, payload: &[u8]) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+        match checked_calls(payload) {
+            Ok(calls) => calls
+                .iter()
+                .find_map(|(method, call)| self.check_call(method, call)),
+            Err(unreadable) => Some(format!("Unreadable request {unreadable}")),
+        }
+    }
+
+    /// The smallest response size limit among `methods`, if any of them has one.
+    pub fn max_response_bytes(&self, methods: &[String]) -> Option<usize> {
+        methods
+            .iter()
+            .filter_map(|method| self.limits.lookup(method)?.max_response_bytes)
+            .min()
+    }
+
+    fn check_call(&self, method: &str, call: &Value) -> Option<String> {
+        let limit = self.limits.lookup(method)?;
+
+        if let Some(max) = limit.max_request_bytes {
+            let size = call.to_string().len();
+            if size > max {
+                return Some(format!(
+                    "Request for {method} too large: {size} bytes (max {max})"
+                ));
+            }
+        }
+
+        let max_range = limit.max_block_range?;
+        let filter = call.get("params")?.get(0)?;
+        match block_range(filter) {
+            Ok(Some(range)) if range > max_range => Some(format!(
+                "Block range of {method} too large: {range} blocks (max {max_range})"
+            )),
+            Ok(_) => None,
+            Err(()) => Some(format!(
+                "{method} must use numeric fromBlock and toBlock (max range {max_range} blocks)"
+            )),
+        }
+    }
+}
+
+/// The number of blocks a log filter (`fromBlock`/`toBlock`) spans.
+///
+/// Returns `Ok(None)` when the range can't be large: a `blockHash` filter, or both bounds being
+/// tags near the chain head (`latest`, `safe`, ..., the default when omitted). A range between a
+/// number and a head tag can't be measured without knowing the head, so it is an error.
+fn block_range(filter: &Value) -> Result<Option<u64>, ()> {
+    if filter.get("blockHash").is_some() {
+        return Ok(None);
+    }
+    let bound = |key: &str| match filter.get(key).and_then(Value::as_str) {
+        None => Ok(None),
+        Some("earliest") => Ok(Some(0)),
+        Some("latest" | "pending" | "safe" | "finalized") => Ok(None),
+        Some(number) => parse_block_number(number).map(Some).ok_or(()),
+    };
+    match (bound("fromBlock")?, bound("toBlock")?) {
+        (Some(from), Some(to)) => Ok(Some(to.saturating_sub(from))),
+        (None, None) => Ok(None),
+        _ => Err(()),
+    }
+}
+
+fn parse_block_number(number: &str) -> Option<u64> {
+    match number.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => number.parse().ok(),
+    }
+}
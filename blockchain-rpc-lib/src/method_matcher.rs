@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+/// Method-name patterns compiled into a byte trie, so a lookup costs O(method length)
+/// regardless of how many patterns are configured.
+///
+/// Patterns ending in `*` match by prefix (`*` alone matches everything), all other patterns
+/// match exactly. When several patterns match, the exact one wins, then the longest prefix.
+#[derive(Debug, Clone)]
+pub struct MethodMatcher<T> {
+    nodes: Vec<Node<T>>,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    children: HashMap<u8, usize>,
+    exact: Option<T>,
+    prefix: Option<T>,
+}
+
+impl<T> Node<T> {
+    fn new() -> Self {
+        Node {
+            children: HashMap::new(),
+            exact: None,
+            prefix: None,
+        }
+    }
+}
+
+impl<T> Default for MethodMatcher<T> {
+    fn default() -> Self {
+        MethodMatcher {
+            nodes: vec![Node::new()],
+            len: 0,
+        }
+    }
+}
+
+impl<T> MethodMatcher<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a pattern, replacing the value of an identical pattern added earlier.
+    pub fn insert(&mut self, pattern: &str, value: T) {
+        let (key, is_prefix) = match pattern.strip_suffix('*') {
+            Some(prefix) => (prefix, true),
+            None => (pattern, false),
+        };
+
+        let mut node = 0;
+        for byte in key.bytes() {
+            node = match self.nodes[node].children.get(&byte) {
+                Some(&child) => child,
+                None => {
+                    self.nodes.push(Node::new());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.insert(byte, child);
+                    child
+                }
+            };
+        }
+
+        let slot = if is_prefix {
+            &mut self.nodes[node].prefix
+        } else {
+            &mut self.nodes[node].exact
+        };
+        if slot.replace(value).is_none() {
+            self.len += 1;
+        }
+    }
+
+    /// Returns the value of the most specific pattern matching `method`.
+    pub fn lookup(&self, method: &str) -> Option<&T> {
+        let mut node = &self.nodes[0];
+        let mut longest_prefix = node.prefix.as_ref();
+        for byte in method.bytes() {
+            match node.children.get(&byte) {
+                Some(&child) => node = &self.nodes[child],
+                None => return longest_prefix,
+            }
+            longest_prefix = node.prefix.as_ref().or(longest_prefix);
+        }
+        node.exact.as_ref().or(longest_prefix)
+    }
+
+    /// Returns true if any pattern matches `method`.
+    pub fn matches(&self, method: &str) -> bool {
+        self.lookup(method).is_some()
+    }
+
+    /// Number of patterns.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<S: AsRef<str>, T> FromIterator<(S, T)> for MethodMatcher<T> {
+    fn from_iter<I: IntoIterator<Item = (S, T)>>(iter: I) -> Self {
+        let mut matcher = MethodMatcher::new();
+        for (pattern, value) in iter {
+            matcher.insert(pattern.as_ref(), value);
+        }
+        matcher
+    }
+}
@@ -0,0 +1,109 @@
+use crate::config::MethodRewriteConfig;
+use crate::method_matcher::MethodMatcher;
+use serde_json::Value;
+
+/// Rewrites JSON-RPC calls before they are forwarded, to smooth over differences between what
+/// clients send and what the backend node supports.
+///
+/// A rule can rename the method (a `*` pattern renamed to a `*` target swaps the prefix, so
+/// `parity_*` -> `trace_*` maps `parity_block` to `trace_block`) and fill in trailing
+/// positional params the client left out. Rules are matched like the method filter's
+/// patterns: the exact pattern wins, then the longest prefix.
+#[derive(Debug, Clone, Default)]
+pub struct MethodRewriter {
+    rules: MethodMatcher<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    /// Matched prefix, for prefix patterns.
+    prefix: Option<String>,
+    rename_to: Option<String>,
+    default_params: Vec<Value>,
+}
+
+impl MethodRewriter {
+    pub fn new(config: &[MethodRewriteConfig]) -> Self {
+        MethodRewriter {
+            rules: config
+                .iter()
+                .map(|rewrite| {
+                    let rule = Rule {
+                        prefix: rewrite.method.strip_suffix('*').map(str::to_string),
+                        rename_to: rewrite.rename_to.clone(),
+                        default_params: rewrite.default_params.clone(),
+                    };
+                    (rewrite.method.as_str(), rule)
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns true if there are no rules and payloads are forwarded unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Applies the rules to a JSON-RPC payload (single call or batch). Returns the rewritten
+    /// payload, or `None` if no rule changed it.
+    pub fn rewrite(&self, payload: &[u8]) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut value: Value = serde_json::from_slice(payload).ok()?;
+        let changed = match &mut value {
+            Value::Array(calls) => calls
+                .iter_mut()
+                .fold(false, |changed, call| self.rewrite_call(call) || changed),
+            call => self.rewrite_call(call),
+        };
+        changed.then(|| value.to_string())
+    }
+
+    fn rewrite_call(&self, call: &mut Value) -> bool {
+        let Some(object) = call.as_object_mut() else {
+            return false;
+        };
+        let Some(method) = object.get("method").and_then(Value::as_str) else {
+            return false;
+        };
+        let Some(rule) = self.rules.lookup(method) else {
+            return false;
+        };
+        let mut changed = false;
+
+        if let Some(renamed) = rule.rename(method) {
+            object.insert("method".to_string(), Value::String(renamed));
+            changed = true;
+        }
+
+        // Only positional params can be filled in; named params are left alone.
+        if !rule.default_params.is_empty() {
+            match object
+                .entry("params")
+                .or_insert_with(|| Value::Array(Vec::new()))
+            {
+                Value::Array(params) if params.len() < rule.default_params.len() => {
+                    params.extend_from_slice(&rule.default_params[params.len()..]);
+                    changed = true;
+                }
+                _ => {}
+            }
+        }
+        changed
+    }
+}
+
+impl Rule {
+    fn rename(&self, method: &str) -> Option<String> {
+        let rename_to = self.rename_to.as_deref()?;
+        let renamed = match (&self.prefix, rename_to.strip_suffix('*')) {
+            (Some(prefix), Some(new_prefix)) => {
+                format!("{new_prefix}{}", method.strip_prefix(prefix.as_str())?)
+            }
+            _ => rename_to.to_string(),
+        };
+        (renamed != method).then_some(renamed)
+    }
+}
@@ -0,0 +1,147 @@
+use crate::Result;
+use crate::config::{MirrorConfig, RpcConfig};
+use crate::proxy::upstream::Upstream;
+use crate::upstream_events::UpstreamEvents;
+use axum::body::{Body, Bytes};
+use axum::http::{Request, header::CONTENT_LENGTH, request::Parts};
+use http_body_util::BodyExt;
+use rand::Rng;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tracing::{debug, warn};
+
+/// Bodies in difference warnings are cut off after this many bytes.
+const MAX_LOGGED_BODY_BYTES: usize = 1024;
+
+/// Duplicates a sampled share of a route's HTTP requests to a shadow backend, per
+/// `[rpc.mirror]` or a route's `mirror`.
+///
+/// Mirrored requests are sent once the primary backend has answered, in the background, so
+/// the client never waits for the shadow backend. They bypass the circuit breaker, retries and
+/// concurrency limits of the route's backends, and failures to reach the shadow backend aren't
+/// reported as upstream events.
+pub(crate) struct Mirror {
+    upstream: Upstream,
+    percent: u8,
+    log_differences: bool,
+    in_flight: Arc<Semaphore>,
+    timeout: Duration,
+}
+
+impl Mirror {
+    pub(crate) fn new(config: &MirrorConfig, rpc: &RpcConfig) -> Result<Self> {
+        let mirror_rpc = RpcConfig {
+            proxy_to_url: config.url.clone(),
+            upstreams: Vec::new(),
+            concurrency: None,
+            ..rpc.clone()
+        };
+        Ok(Mirror {
+            upstream: Upstream::new(&mirror_rpc, Arc::new(UpstreamEvents::new()), None, None)?,
+            percent: config.percent,
+            log_differences: config.log_differences,
+            in_flight: Arc::new(Semaphore::new(config.max_in_flight)),
+            timeout: Duration::from_secs(config.timeout_secs.max(1)),
+        })
+    }
+
+    /// Whether to mirror the next request, with a chance of `percent` percent.
+    pub(crate) fn samples(&self) -> bool {
+        self.percent > 0 && rand::thread_rng().gen_range(0..100) < self.percent
+    }
+
+    /// Whether the primary backend's answer is needed to compare the shadow backend's with.
+    pub(crate) fn compares(&self) -> bool {
+        self.log_differences
+    }
+
+    /// Sends a copy of a request to the shadow backend in the background, comparing its answer
+    /// with `primary` if given. Skipped if `max_in_flight` mirrored requests are awaiting
+    /// the shadow backend already.
+    pub(crate) fn send(
+        self: &Arc<Self>,
+        parts: &Parts,
+        path_and_query: &str,
+        body: Bytes,
+        primary: Option<Bytes>,
+    ) {
+        let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+            debug!("Skipping mirrored request, too many awaiting the shadow backend");
+            return;
+        };
+        let target_uri = match self.upstream.target_uri(path_and_query, None) {
+            Ok(uri) => uri,
+            Err(e) => {
+                warn!(error = %e, "Failed to build the shadow backend's URI");
+                return;
+            }
+        };
+        let mut request = Request::new(Body::from(body.clone()));
+        *request.method_mut() = parts.method.clone();
+        *request.uri_mut() = target_uri;
+        *request.version_mut() = parts.version;
+        *request.headers_mut() = parts.headers.clone();
+        request
+            .headers_mut()
+            .insert(CONTENT_LENGTH, body.len().into());
+
+        let mirror = self.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let answer = tokio::time::timeout(mirror.timeout, async {
+                let response = mirror
+                    .upstream
+                    .send(request)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let status = response.status();
+                let body = response
+                    .into_body()
+                    .collect()
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .to_bytes();
+                Ok::<_, String>((status, body))
+            })
+            .await;
+            let (status, shadow) = match answer {
+                Ok(Ok(answer)) => answer,
+                Ok(Err(e)) => {
+                    debug!(error = %e, "Mirrored request failed");
+                    return;
+                }
+                Err(_) => {
+                    debug!("Mirrored request timed out");
+                    return;
+                }
+            };
+            debug!(%status, "Shadow backend answered mirrored request");
+            if let Some(primary) = primary.filter(|primary| differs(primary, &shadow)) {
+                warn!(
+                    request = %excerpt(&body),
+                    primary = %excerpt(&primary),
+                    shadow = %excerpt(&shadow),
+                    "Shadow backend's answer differs from the primary backend's"
+                );
+            }
+        });
+    }
+}
+
+/// The start of a body, for logs.
+fn excerpt(body: &[u8]) -> String {
+    String::from_utf8_lossy(&body[..body.len().min(MAX_LOGGED_BODY_BYTES)]).into_owned()
+}
+
+/// Whether two answers differ, compared as JSON if both are, so formatting doesn't count.
+pub fn differs(primary: &[u8], shadow: &[u8]) -> bool {
+    match (
+        serde_json::from_slice::<Value>(primary),
+        serde_json::from_slice::<Value>(shadow),
+    ) {
+        (Ok(primary), Ok(shadow)) => primary != shadow,
+        _ => primary != shadow,
+    }
+}
@@ -0,0 +1,187 @@
+use crate::config::OriginHeatmapConfig;
+use crate::firewall::canonical_ip;
+use chrono::{DateTime, Utc};
+use ipnetwork::{IpNetwork, Ipv4Network, Ipv6Network};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Slots the rolling window is divided into; counts expire one slot at a time.
+const SLOTS: i64 = 60;
+
+/// The network a client is counted under: its /24 for IPv4 and its /48 for IPv6, the smallest
+/// blocks routed between networks and so the usual unit to ban or serve from elsewhere.
+/// IPv4-mapped IPv6 clients are counted under their IPv4 /24.
+pub fn origin_prefix(ip: IpAddr) -> IpNetwork {
+    match canonical_ip(ip) {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            IpNetwork::V4(
+                Ipv4Network::new(Ipv4Addr::new(a, b, c, 0), 24).expect("/24 is a valid prefix"),
+            )
+        }
+        IpAddr::V6(ip) => {
+            let [a, b, c, ..] = ip.segments();
+            IpNetwork::V6(
+                Ipv6Network::new(Ipv6Addr::new(a, b, c, 0, 0, 0, 0, 0), 48)
+                    .expect("/48 is a valid prefix"),
+            )
+        }
+    }
+}
+
+/// Which count the heatmap is ordered by, highest first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeatmapOrder {
+    #[default]
+    Denied,
+    Requests,
+}
+
+/// Traffic from one prefix within the window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OriginPrefixStats {
+    pub prefix: IpNetwork,
+    pub requests: u64,
+    /// Requests rejected for invalid credentials, firewall rules or rate limits.
+    pub denied: u64,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// The busiest prefixes of the window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OriginHeatmapReport {
+    pub window_secs: u64,
+    /// Prefixes with traffic in the window, including those not listed.
+    pub tracked_prefixes: usize,
+    pub prefixes: Vec<OriginPrefixStats>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    index: i64,
+    requests: u64,
+    denied: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    slots: VecDeque<Slot>,
+    last_seen: DateTime<Utc>,
+}
+
+/// Rolling request and denial counts per client prefix, so operators can see which networks
+/// abuse the gateway (candidates for a CIDR ban) and where legitimate traffic comes from.
+/// Only prefixes and counters are kept.
+#[derive(Debug)]
+pub struct OriginHeatmap {
+    window_secs: u64,
+    slot_secs: i64,
+    max_prefixes: usize,
+    entries: Mutex<HashMap<IpNetwork, Entry>>,
+}
+
+impl OriginHeatmap {
+    pub fn new(config: &OriginHeatmapConfig) -> Self {
+        let window_secs = config.window_secs.max(SLOTS as u64);
+        OriginHeatmap {
+            window_secs,
+            slot_secs: (window_secs as i64 / SLOTS).max(1),
+            max_prefixes: config.max_prefixes,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts a request from `ip`. New prefixes are ignored while the heatmap is full.
+    pub fn record(&self, ip: IpAddr) {
+        self.count(ip, |slot| slot.requests += 1);
+    }
+
+    /// Counts a request from `ip` as denied. Call after [`OriginHeatmap::record`].
+    pub fn record_denial(&self, ip: IpAddr) {
+        self.count(ip, |slot| slot.denied += 1);
+    }
+
+    fn count(&self, ip: IpAddr, update: impl FnOnce(&mut Slot)) {
+        let now = Utc::now();
+        let index = self.slot_index(now);
+        let prefix = origin_prefix(ip);
+        let mut entries = self.entries.lock();
+        if !entries.contains_key(&prefix) && entries.len() >= self.max_prefixes {
+            return;
+        }
+        let entry = entries.entry(prefix).or_insert_with(|| Entry {
+            slots: VecDeque::new(),
+            last_seen: now,
+        });
+        entry.last_seen = now;
+        if entry.slots.back().is_none_or(|slot| slot.index != index) {
+            entry.slots.push_back(Slot {
+                index,
+                requests: 0,
+                denied: 0,
+            });
+        }
+        if let Some(slot) = entry.slots.back_mut() {
+            update(slot);
+        }
+        while entry
+            .slots
+            .front()
+            .is_some_and(|slot| slot.index <= index - SLOTS)
+        {
+            entry.slots.pop_front();
+        }
+    }
+
+    /// The `limit` prefixes with the most denials or requests within the window.
+    pub fn report(&self, order: HeatmapOrder, limit: usize) -> OriginHeatmapReport {
+        let oldest = self.slot_index(Utc::now()) - SLOTS;
+        let mut prefixes: Vec<OriginPrefixStats> = self
+            .entries
+            .lock()
+            .iter()
+            .filter_map(|(prefix, entry)| {
+                let (requests, denied) = entry
+                    .slots
+                    .iter()
+                    .filter(|slot| slot.index > oldest)
+                    .fold((0, 0), |(requests, denied), slot| {
+                        (requests + slot.requests, denied + slot.denied)
+                    });
+                (requests > 0 || denied > 0).then(|| OriginPrefixStats {
+                    prefix: *prefix,
+                    requests,
+                    denied,
+                    last_seen: entry.last_seen,
+                })
+            })
+            .collect();
+        prefixes.sort_by(|a, b| match order {
+            HeatmapOrder::Denied => b.denied.cmp(&a.denied).then(b.requests.cmp(&a.requests)),
+            HeatmapOrder::Requests => b.requests.cmp(&a.requests).then(b.denied.cmp(&a.denied)),
+        });
+        let tracked_prefixes = prefixes.len();
+        prefixes.truncate(limit);
+        OriginHeatmapReport {
+            window_secs: self.window_secs,
+            tracked_prefixes,
+            prefixes,
+        }
+    }
+
+    /// Drops counts older than the window and prefixes left without any.
+    pub fn prune(&self) {
+        let oldest = self.slot_index(Utc::now()) - SLOTS;
+        self.entries.lock().retain(|_, entry| {
+            entry.slots.retain(|slot| slot.index > oldest);
+            !entry.slots.is_empty()
+        });
+    }
+
+    fn slot_index(&self, at: DateTime<Utc>) -> i64 {
+        at.timestamp().div_euclid(self.slot_secs)
+    }
+}
@@ -0,0 +1,100 @@
+use parking_lot::RwLock;
+use serde_json::Value;
+use sp_runtime::AccountId32;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Where a filtered payload comes from or goes to.
+#[derive(Debug, Clone)]
+pub struct FilterContext {
+    /// Name of the route the request was proxied along.
+    pub route: String,
+    pub client_ip: IpAddr,
+    /// The account the client authenticated as, if any.
+    pub account: Option<AccountId32>,
+    /// Whether the payload is a WebSocket frame rather than an HTTP body.
+    pub websocket: bool,
+}
+
+/// Site-specific rewriting of JSON-RPC requests, e.g. rewriting certain params or injecting
+/// defaults.
+pub trait RequestFilter: fmt::Debug + Send + Sync {
+    /// Inspects a request (a call or a batch) from the client, which it may change in place.
+    /// Runs before the gateway's own checks, so they see the filtered request. An error
+    /// rejects the request, answering the client with a JSON-RPC error carrying the reason.
+    fn filter_request(&self, ctx: &FilterContext, request: &mut Value) -> Result<(), String>;
+}
+
+/// Site-specific rewriting of JSON-RPC responses, e.g. scrubbing sensitive fields.
+pub trait ResponseFilter: fmt::Debug + Send + Sync {
+    /// Inspects a response (a result, an error, a batch of them or a subscription
+    /// notification) from the backend, which it may change in place before it reaches the
+    /// client.
+    fn filter_response(&self, ctx: &FilterContext, response: &mut Value);
+}
+
+/// Request and response filters applied to HTTP bodies and WebSocket text frames, in the order
+/// they were added.
+///
+/// Filters are registered in code by whoever embeds the gateway, through
+/// `SecureRpcContext::payload_filters`, and apply to requests from then on. Payloads that
+/// aren't JSON are passed through untouched. Without filters, bodies aren't buffered for them.
+#[derive(Debug, Default)]
+pub struct PayloadFilters {
+    request: RwLock<Vec<Arc<dyn RequestFilter>>>,
+    response: RwLock<Vec<Arc<dyn ResponseFilter>>>,
+}
+
+impl PayloadFilters {
+    pub fn add_request_filter(&self, filter: Arc<dyn RequestFilter>) {
+        self.request.write().push(filter);
+    }
+
+    pub fn add_response_filter(&self, filter: Arc<dyn ResponseFilter>) {
+        self.response.write().push(filter);
+    }
+
+    pub fn filters_requests(&self) -> bool {
+        !self.request.read().is_empty()
+    }
+
+    pub fn filters_responses(&self) -> bool {
+        !self.response.read().is_empty()
+    }
+
+    /// Runs the request filters over `payload`. Returns the filtered request, or `None` if no
+    /// filter is registered or the payload isn't JSON, or the first filter's rejection.
+    pub fn filter_request(
+        &self,
+        ctx: &FilterContext,
+        payload: &[u8],
+    ) -> Result<Option<String>, String> {
+        // Filters run outside the lock, so they can't block registration.
+        let filters = self.request.read().clone();
+        if filters.is_empty() {
+            return Ok(None);
+        }
+        let Ok(mut request) = serde_json::from_slice::<Value>(payload) else {
+            return Ok(None);
+        };
+        for filter in &filters {
+            filter.filter_request(ctx, &mut request)?;
+        }
+        Ok(Some(request.to_string()))
+    }
+
+    /// Runs the response filters over `payload`. Returns the filtered response, or `None` if
+    /// no filter is registered or the payload isn't JSON.
+    pub fn filter_response(&self, ctx: &FilterContext, payload: &[u8]) -> Option<String> {
+        let filters = self.response.read().clone();
+        if filters.is_empty() {
+            return None;
+        }
+        let mut response = serde_json::from_slice::<Value>(payload).ok()?;
+        for filter in &filters {
+            filter.filter_response(ctx, &mut response);
+        }
+        Some(response.to_string())
+    }
+}
@@ -0,0 +1,216 @@
+use crate::Result;
+use crate::config::PaymentVerificationConfig;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::store::StateStore;
+use blueprint_sdk::contexts::tangle::TangleClientContext;
+use blueprint_sdk::tangle_subxt::subxt::utils::H256;
+use blueprint_sdk::tangle_subxt::tangle_testnet_runtime::api::balances::events::Transfer;
+use parity_scale_codec::{Decode, Encode};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sp_runtime::AccountId32;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+const USED_PAYMENTS_NAMESPACE: &str = "used_payments";
+
+/// Points at the on-chain transfer that paid for access: the `Balances::Transfer` event at
+/// `event_index` in the block with hash `block_hash`.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone)]
+pub struct PaymentReference {
+    /// Hex-encoded block hash, with or without `0x`.
+    pub block_hash: String,
+    pub event_index: u32,
+}
+
+impl PaymentReference {
+    fn key(&self) -> String {
+        format!(
+            "{}:{}",
+            self.block_hash.trim_start_matches("0x").to_lowercase(),
+            self.event_index
+        )
+    }
+}
+
+/// Confirms on Tangle that `pay_for_access` calls were paid for: the referenced transfer must
+/// come from the caller or the account getting access, go to the operator account and cover
+/// the configured price. Each transfer pays for a single grant; used transfers are persisted
+/// so they can't be replayed after a restart.
+#[derive(Debug)]
+pub struct PaymentVerifier {
+    operator: AccountId32,
+    min_amount: u128,
+    amount_per_second: u128,
+    store: Arc<dyn StateStore>,
+    used: Mutex<HashSet<String>>,
+}
+
+impl PaymentVerifier {
+    /// Loads the used payments stored in `store`, starting empty if none exist yet.
+    pub fn load(
+        store: Arc<dyn StateStore>,
+        config: &PaymentVerificationConfig,
+        operator: AccountId32,
+    ) -> Result<Self> {
+        let mut used = HashSet::new();
+        for (key, _) in store.entries(USED_PAYMENTS_NAMESPACE)? {
+            let key = String::from_utf8(key).map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Corrupt used payment record: {e}"),
+                )
+            })?;
+            used.insert(key);
+        }
+
+        Ok(PaymentVerifier {
+            operator,
+            min_amount: config.min_amount,
+            amount_per_second: config.amount_per_second,
+            store,
+            used: Mutex::new(used),
+        })
+    }
+
+    /// The smallest payment accepted for any grant.
+    pub fn min_amount(&self) -> u128 {
+        self.min_amount
+    }
+
+    /// The smallest payment accepted for `duration_secs` of access.
+    pub fn required_amount(&self, duration_secs: u64) -> u128 {
+        self.amount_per_second
+            .saturating_mul(duration_secs as u128)
+            .max(self.min_amount)
+    }
+
+    /// Checks that `payment` is an unused transfer from one of `payers` to the operator of at
+    /// least `required_amount`, and marks it used. Returns the amount paid.
+    pub async fn verify(
+        &self,
+        ctx: &SecureRpcContext,
+        payment: &PaymentReference,
+        payers: &[AccountId32],
+        required_amount: u128,
+    ) -> Result<u128> {
+        let key = payment.key();
+        if self.used.lock().contains(&key) {
+            return Err(Error::PaymentVerificationFailed(format!(
+                "Payment {key} was already used"
+            )));
+        }
+
+        let transfer = find_transfer(ctx, payment).await?;
+        let transfer = ChainTransfer {
+            from: AccountId32::new(transfer.from.0),
+            to: AccountId32::new(transfer.to.0),
+            amount: transfer.amount,
+        };
+        self.redeem(payment, &transfer, payers, required_amount)
+    }
+
+    /// Checks that `transfer`, which `payment` points at on-chain, is unused, from one of
+    /// `payers` and to the operator of at least `required_amount`, and marks it used. Returns
+    /// the amount paid. The transfer must have been read from the chain.
+    pub(crate) fn redeem(
+        &self,
+        payment: &PaymentReference,
+        transfer: &ChainTransfer,
+        payers: &[AccountId32],
+        required_amount: u128,
+    ) -> Result<u128> {
+        let key = payment.key();
+        let ChainTransfer { from, to, amount } = transfer;
+        let amount = *amount;
+        if !payers.contains(from) {
+            return Err(Error::PaymentVerificationFailed(format!(
+                "Payment {key} was made by {from}, not by the caller or the account getting access"
+            )));
+        }
+        if *to != self.operator {
+            return Err(Error::PaymentVerificationFailed(format!(
+                "Payment {key} was not made to the operator account {}",
+                self.operator
+            )));
+        }
+        if amount < required_amount {
+            return Err(Error::PaymentVerificationFailed(format!(
+                "Payment {key} of {amount} is less than the required {required_amount}"
+            )));
+        }
+
+        // Checked under the lock, so a payment verified concurrently is only redeemed once.
+        let mut used = self.used.lock();
+        if used.contains(&key) {
+            return Err(Error::PaymentVerificationFailed(format!(
+                "Payment {key} was already used"
+            )));
+        }
+        self.store
+            .insert(USED_PAYMENTS_NAMESPACE, key.as_bytes(), &[])?;
+        used.insert(key);
+        Ok(amount)
+    }
+
+    /// Makes a redeemed payment usable again, for a grant that failed after it was verified.
+    pub(crate) fn release(&self, payment: &PaymentReference) -> Result<()> {
+        let key = payment.key();
+        let mut used = self.used.lock();
+        self.store.remove(USED_PAYMENTS_NAMESPACE, key.as_bytes())?;
+        used.remove(&key);
+        Ok(())
+    }
+}
+
+/// A balance transfer as read from the chain.
+#[derive(Debug, Clone)]
+pub(crate) struct ChainTransfer {
+    pub(crate) from: AccountId32,
+    pub(crate) to: AccountId32,
+    pub(crate) amount: u128,
+}
+
+/// Fetches the `Balances::Transfer` event a payment reference points at.
+async fn find_transfer(ctx: &SecureRpcContext, payment: &PaymentReference) -> Result<Transfer> {
+    let key = payment.key();
+    let block_hash = hex::decode(payment.block_hash.trim_start_matches("0x"))
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .map(H256::from)
+        .ok_or_else(|| {
+            Error::InvalidJobInput(format!("Invalid block hash {}", payment.block_hash))
+        })?;
+
+    let client = ctx
+        .tangle_client()
+        .await
+        .map_err(|e| Error::PaymentVerificationFailed(format!("Tangle client unavailable: {e}")))?;
+    let events = client
+        .rpc_client
+        .blocks()
+        .at(block_hash)
+        .await
+        .map_err(|e| {
+            Error::PaymentVerificationFailed(format!("Block {} not found: {e}", payment.block_hash))
+        })?
+        .events()
+        .await
+        .map_err(|e| Error::PaymentVerificationFailed(format!("Failed to read events: {e}")))?;
+
+    let event = events
+        .iter()
+        .nth(payment.event_index as usize)
+        .ok_or_else(|| Error::PaymentVerificationFailed(format!("Event {key} not found")))?
+        .map_err(|e| Error::PaymentVerificationFailed(format!("Failed to decode event: {e}")))?;
+    match event.as_event::<Transfer>() {
+        Ok(Some(transfer)) => Ok(transfer),
+        Ok(None) => Err(Error::PaymentVerificationFailed(format!(
+            "Event {key} is not a balance transfer"
+        ))),
+        Err(e) => Err(Error::PaymentVerificationFailed(format!(
+            "Failed to decode event {key}: {e}"
+        ))),
+    }
+}
@@ -0,0 +1,285 @@
+use crate::Result;
+use crate::config::{MethodFilterConfig, PlanConfig};
+use crate::method_filter::MethodFilter;
+use crate::ratelimit::RateLimiter;
+use crate::store::StateStore;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+
+const PLANS_NAMESPACE: &str = "plans";
+const QUOTA_USAGE_KEY: &[u8] = b"quota_usage";
+
+/// An access tier from `[plans]`, with its own rate limit and method allowlist.
+#[derive(Debug)]
+pub struct Plan {
+    pub id: String,
+    pub duration_secs: Option<u64>,
+    pub min_payment: Option<u128>,
+    /// Whether WebSocket connections are served on this plan.
+    pub websocket: bool,
+    /// Concurrent WebSocket connections per account, if the plan sets its own cap.
+    pub max_connections: Option<usize>,
+    /// HTTP requests in flight per account, if the plan sets its own cap.
+    pub max_in_flight_requests: Option<usize>,
+    methods: MethodFilter,
+    /// Buckets per grant holder (account or IP/CIDR), if the plan has its own rate limit.
+    rate_limiter: Option<RateLimiter<String>>,
+    /// Calls each grant may make, if the plan has a quota. Each extension of a grant adds it
+    /// once more.
+    pub request_quota: Option<u64>,
+    /// Percentages of the quota reporting a `QuotaThresholdReached` event, ascending.
+    quota_warning_percents: Vec<u8>,
+    /// Calls made per grant holder against `request_quota`.
+    quota_usage: Mutex<HashMap<String, QuotaUsage>>,
+}
+
+/// Calls a grant has made against its plan's quota.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct QuotaUsage {
+    /// When the grant counted against began; a new grant starts over.
+    granted_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    used: u64,
+}
+
+/// Outcome of charging calls against a grant's request quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaCharge {
+    /// Calls made so far, the charged ones included unless refused.
+    pub used: u64,
+    pub quota: u64,
+    /// Whether the calls were refused because they would exceed the quota.
+    pub exhausted: bool,
+    /// The highest `quota_warning_percents` threshold this charge took the grant past.
+    pub crossed_percent: Option<u8>,
+}
+
+impl Plan {
+    fn new(id: &str, config: &PlanConfig) -> Self {
+        Plan {
+            id: id.to_string(),
+            duration_secs: config.duration_secs,
+            min_payment: config.min_payment,
+            websocket: config.websocket,
+            max_connections: config.max_connections,
+            max_in_flight_requests: config.max_in_flight_requests,
+            methods: MethodFilter::new(&MethodFilterConfig {
+                allow: config.allowed_methods.clone(),
+                ..Default::default()
+            }),
+            rate_limiter: config.rate_limit.as_ref().map(RateLimiter::new),
+            request_quota: config.request_quota,
+            quota_warning_percents: {
+                let mut percents: Vec<u8> = config
+                    .quota_warning_percents
+                    .iter()
+                    .copied()
+                    .filter(|percent| (1..=100).contains(percent))
+                    .collect();
+                percents.sort_unstable();
+                percents.dedup();
+                percents
+            },
+            quota_usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns true if the plan limits which methods may be called.
+    pub fn restricts_methods(&self) -> bool {
+        !self.methods.is_empty()
+    }
+
+    /// Returns the first method in a JSON-RPC payload (single call or batch) that isn't part
+    /// of the plan, if any.
+    pub fn find_blocked_method(&self, payload: &[u8]) -> Option<String> {
+        self.methods.find_blocked_method(payload)
+    }
+
+    /// The buckets per grant holder, or `None` if the plan has no rate limit of its own, so
+    /// the global one applies.
+    pub fn rate_limiter(&self) -> Option<&RateLimiter<String>> {
+        self.rate_limiter.as_ref()
+    }
+
+    /// Tokens left in `holder`'s bucket, or `None` if the plan has no rate limit of its own.
+    pub fn rate_limit_remaining(&self, holder: &str) -> Option<u32> {
+        let limiter = self.rate_limiter.as_ref()?;
+        Some(limiter.remaining(&holder.to_string()))
+    }
+
+    /// Charges `calls` against the quota of `active`'s grant, refusing them all if they would
+    /// exceed it. Returns `None` if the plan has no quota.
+    pub fn charge_quota(&self, active: &ActivePlan, calls: u32) -> Option<QuotaCharge> {
+        let quota = self.grant_quota(active)?;
+        let mut quota_usage = self.quota_usage.lock();
+        let usage = quota_usage
+            .entry(active.holder.clone())
+            .and_modify(|usage| {
+                if usage.granted_at != active.granted_at {
+                    *usage = QuotaUsage {
+                        granted_at: active.granted_at,
+                        expires_at: active.expires_at,
+                        used: 0,
+                    };
+                }
+                // Extensions of the same grant move its expiry
+                usage.expires_at = active.expires_at;
+            })
+            .or_insert_with(|| QuotaUsage {
+                granted_at: active.granted_at,
+                expires_at: active.expires_at,
+                used: 0,
+            });
+        let before = usage.used;
+        let after = before.saturating_add(u64::from(calls));
+        if after > quota {
+            return Some(QuotaCharge {
+                used: before,
+                quota,
+                exhausted: true,
+                crossed_percent: None,
+            });
+        }
+        usage.used = after;
+        let reached = |used: u64, percent: u8| {
+            u128::from(used) * 100 >= u128::from(quota) * u128::from(percent)
+        };
+        let crossed_percent = self
+            .quota_warning_percents
+            .iter()
+            .rev()
+            .copied()
+            .find(|percent| reached(after, *percent) && !reached(before, *percent));
+        Some(QuotaCharge {
+            used: after,
+            quota,
+            exhausted: false,
+            crossed_percent,
+        })
+    }
+
+    /// Calls left in the quota of `active`'s grant, or `None` if the plan has no quota.
+    pub fn quota_remaining(&self, active: &ActivePlan) -> Option<u64> {
+        let quota = self.grant_quota(active)?;
+        let used = self
+            .quota_usage
+            .lock()
+            .get(&active.holder)
+            .filter(|usage| usage.granted_at == active.granted_at)
+            .map_or(0, |usage| usage.used);
+        Some(quota.saturating_sub(used))
+    }
+
+    /// The quota of `active`'s grant: the plan's, stacked once per extension.
+    fn grant_quota(&self, active: &ActivePlan) -> Option<u64> {
+        let quota = self.request_quota?;
+        Some(quota.saturating_mul(u64::from(active.extensions) + 1))
+    }
+}
+
+/// The plan a request is served under, attached to the request by the gateway.
+#[derive(Debug, Clone)]
+pub struct ActivePlan {
+    /// The account or IP/CIDR holding the grant.
+    pub holder: String,
+    pub plan: Arc<Plan>,
+    pub granted_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Times the grant was extended, stacking the plan's quota.
+    pub extensions: u32,
+}
+
+/// The configured plans, by ID.
+#[derive(Debug, Default)]
+pub struct Plans {
+    plans: HashMap<String, Arc<Plan>>,
+    /// Where quota usage is persisted, when loaded from a store.
+    store: Option<Arc<dyn StateStore>>,
+}
+
+impl Plans {
+    pub fn new(config: &HashMap<String, PlanConfig>) -> Self {
+        Plans {
+            plans: config
+                .iter()
+                .map(|(id, plan)| (id.clone(), Arc::new(Plan::new(id, plan))))
+                .collect(),
+            store: None,
+        }
+    }
+
+    /// Like [`Plans::new`], restoring the quota usage of unexpired grants from `store`, which
+    /// [`Plans::persist`] writes it back to, so a restart doesn't refill quotas.
+    pub fn load(store: Arc<dyn StateStore>, config: &HashMap<String, PlanConfig>) -> Result<Self> {
+        let mut plans = Plans::new(config);
+        if let Some(bytes) = store.get(PLANS_NAMESPACE, QUOTA_USAGE_KEY)? {
+            match serde_json::from_slice::<HashMap<String, HashMap<String, QuotaUsage>>>(&bytes) {
+                Ok(persisted) => {
+                    let now = Utc::now();
+                    for (id, usage) in persisted {
+                        // Usage of plans no longer configured is dropped
+                        if let Some(plan) = plans.plans.get(&id) {
+                            plan.quota_usage.lock().extend(
+                                usage
+                                    .into_iter()
+                                    .filter(|(_, usage)| usage.expires_at > now),
+                            );
+                        }
+                    }
+                }
+                Err(e) => warn!(error = %e, "Ignoring corrupt persisted quota usage"),
+            }
+        }
+        plans.store = Some(store);
+        Ok(plans)
+    }
+
+    /// Writes the quota usage of every plan to the store it was loaded from, if any.
+    pub fn persist(&self) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let usage: HashMap<&str, HashMap<String, QuotaUsage>> = self
+            .plans
+            .iter()
+            .map(|(id, plan)| (id.as_str(), plan.quota_usage.lock().clone()))
+            .filter(|(_, usage)| !usage.is_empty())
+            .collect();
+        store.insert(
+            PLANS_NAMESPACE,
+            QUOTA_USAGE_KEY,
+            &serde_json::to_vec(&usage)?,
+        )
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<Plan>> {
+        self.plans.get(id).cloned()
+    }
+
+    /// The highest-priced plan a payment of `amount` covers, among plans with a price.
+    pub fn select_by_payment(&self, amount: u128) -> Option<Arc<Plan>> {
+        self.plans
+            .values()
+            .filter(|plan| plan.min_payment.is_some_and(|price| price <= amount))
+            .max_by_key(|plan| plan.min_payment)
+            .cloned()
+    }
+
+    /// Drops rate-limit buckets that have refilled completely and quota usage of expired
+    /// grants.
+    pub fn prune(&self) {
+        let now = Utc::now();
+        for plan in self.plans.values() {
+            if let Some(limiter) = &plan.rate_limiter {
+                limiter.prune();
+            }
+            plan.quota_usage
+                .lock()
+                .retain(|_, usage| usage.expires_at > now);
+        }
+    }
+}
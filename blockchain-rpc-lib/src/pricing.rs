@@ -0,0 +1,127 @@
+use crate::config::{PriceScheduleConfig, SurgeConfig, VolumeDiscountConfig};
+use crate::context::SecureRpcContext;
+use serde::{Deserialize, Serialize};
+
+const SECS_PER_HOUR: u128 = 3600;
+
+/// Prices access by `[payments.pricing]`: an hourly price, lowered for long grants by volume
+/// discounts and raised by a surge multiplier while the backends are busy.
+#[derive(Debug, Clone)]
+pub struct PriceSchedule {
+    amount_per_hour: u128,
+    /// By `min_hours`, ascending.
+    volume_discounts: Vec<VolumeDiscountConfig>,
+    /// By `load_percent`, ascending.
+    surge: Vec<SurgeConfig>,
+}
+
+impl PriceSchedule {
+    pub fn new(config: &PriceScheduleConfig) -> Self {
+        let mut volume_discounts = config.volume_discounts.clone();
+        volume_discounts.sort_by_key(|discount| discount.min_hours);
+        let mut surge = config.surge.clone();
+        surge.sort_by_key(|surge| surge.load_percent);
+        PriceSchedule {
+            amount_per_hour: config.amount_per_hour,
+            volume_discounts,
+            surge,
+        }
+    }
+
+    /// The price multiplier, in percent, with `load_percent` of the backends' capacity in use.
+    pub fn surge_percent(&self, load_percent: u8) -> u32 {
+        self.surge
+            .iter()
+            .rev()
+            .find(|surge| surge.load_percent <= load_percent)
+            .map_or(100, |surge| surge.multiplier_percent)
+    }
+
+    /// What `duration_secs` of access costs at `surge_percent`, rounded up.
+    pub fn price(&self, duration_secs: u64, surge_percent: u32) -> u128 {
+        let discount = self
+            .volume_discounts
+            .iter()
+            .rev()
+            .find(|discount| u128::from(discount.min_hours) * SECS_PER_HOUR <= duration_secs.into())
+            .map_or(0, |discount| discount.percent);
+        let hourly = self.hourly_price(discount, surge_percent);
+        hourly
+            .saturating_mul(duration_secs.into())
+            .div_ceil(SECS_PER_HOUR * 100 * 100)
+    }
+
+    /// The longest access `amount` pays for at `surge_percent`: the longest of the durations
+    /// it buys at each discount whose `min_hours` that duration reaches.
+    pub fn duration_secs(&self, amount: u128, surge_percent: u32) -> u64 {
+        std::iter::once(&VolumeDiscountConfig {
+            min_hours: 0,
+            percent: 0,
+        })
+        .chain(&self.volume_discounts)
+        .map(|discount| {
+            let hourly = self.hourly_price(discount.percent, surge_percent).max(1);
+            let secs = amount.saturating_mul(SECS_PER_HOUR * 100 * 100) / hourly;
+            (u64::try_from(secs).unwrap_or(u64::MAX), discount.min_hours)
+        })
+        .filter(|(secs, min_hours)| u128::from(*secs) >= u128::from(*min_hours) * SECS_PER_HOUR)
+        .map(|(secs, _)| secs)
+        .max()
+        .unwrap_or(0)
+    }
+
+    /// The hourly price after `discount_percent` and `surge_percent`, scaled by 100 × 100.
+    fn hourly_price(&self, discount_percent: u8, surge_percent: u32) -> u128 {
+        self.amount_per_hour
+            .saturating_mul(u128::from(100 - discount_percent.min(100)))
+            .saturating_mul(u128::from(surge_percent))
+    }
+
+    /// The schedule as it applies now, with `load_percent` of the backends' capacity in use.
+    pub fn quote(&self, min_amount: u128, load_percent: u8) -> PriceQuote {
+        PriceQuote {
+            amount_per_hour: self.amount_per_hour,
+            min_amount,
+            volume_discounts: self.volume_discounts.clone(),
+            load_percent,
+            surge_percent: self.surge_percent(load_percent),
+        }
+    }
+}
+
+/// The price schedule at the current load, as returned by the `price_schedule` job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceQuote {
+    /// Price of an hour of access before discounts and the surge multiplier.
+    pub amount_per_hour: u128,
+    /// Smallest payment accepted for any grant.
+    pub min_amount: u128,
+    pub volume_discounts: Vec<VolumeDiscountConfig>,
+    /// Share of the backends' capacity in use.
+    pub load_percent: u8,
+    /// The multiplier applied to prices at that load, in percent.
+    pub surge_percent: u32,
+}
+
+/// The schedule grants are priced by, if payments are verified under `[payments.pricing]`,
+/// and the surge multiplier at the current load.
+pub(crate) fn current_pricing(ctx: &SecureRpcContext) -> Option<(PriceSchedule, u32)> {
+    ctx.payment_verifier.as_ref()?;
+    let config = ctx.config();
+    let schedule = PriceSchedule::new(config.payments.as_ref()?.pricing.as_ref()?);
+    let surge_percent = schedule.surge_percent(load_percent(ctx));
+    Some((schedule, surge_percent))
+}
+
+/// The share of request slots in use, in percent, at the busiest of `[rpc.global_concurrency]`
+/// and `[rpc.concurrency]`. 0 without either.
+pub(crate) fn load_percent(ctx: &SecureRpcContext) -> u8 {
+    ctx.global_concurrency
+        .as_ref()
+        .map(|limit| limit.stats())
+        .into_iter()
+        .chain(ctx.upstream.admission_stats())
+        .map(|stats| stats.in_flight * 100 / stats.max_concurrent_requests.max(1))
+        .max()
+        .map_or(0, |percent| percent.min(100) as u8)
+}
@@ -0,0 +1,115 @@
+use super::RpcGatewayState;
+use super::pool::{HttpClient, PoolCounters, PoolStats, grpc_client};
+use crate::Result;
+use crate::access_log::AccessDetails;
+use crate::config::{GrpcConfig, RpcConfig};
+use crate::upload::UploadStatus;
+use crate::upstream_signing::strip_gateway_headers;
+use axum::{
+    body::Body,
+    http::{
+        HeaderMap, Request, StatusCode, Uri, Version,
+        header::{CONTENT_TYPE, HOST},
+    },
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+const GRPC_STATUS_HEADER: &str = "grpc-status";
+const GRPC_MESSAGE_HEADER: &str = "grpc-message";
+/// gRPC status code for a backend that can't be reached.
+const GRPC_UNAVAILABLE: &str = "14";
+
+/// Whether a request is a gRPC call: `application/grpc`, or `application/grpc+<codec>`.
+pub(crate) fn is_grpc(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| {
+            content_type == "application/grpc" || content_type.starts_with("application/grpc+")
+        })
+}
+
+/// The gRPC backend from `[rpc.grpc]`, reached over pooled HTTP/2 connections.
+pub(crate) struct GrpcUpstream {
+    client: HttpClient,
+    /// Scheme and authority of `upstream_url`.
+    target: String,
+    pool_counters: Arc<PoolCounters>,
+}
+
+impl GrpcUpstream {
+    pub(crate) fn new(rpc: &RpcConfig, config: &GrpcConfig) -> Result<Self> {
+        let pool_counters = Arc::new(PoolCounters::default());
+        let target = config.upstream_url.origin().ascii_serialization();
+        Ok(GrpcUpstream {
+            client: grpc_client(rpc, pool_counters.clone())?,
+            target,
+            pool_counters,
+        })
+    }
+
+    pub(crate) fn pool_stats(&self) -> PoolStats {
+        self.pool_counters.stats()
+    }
+}
+
+/// Proxies a gRPC call to the `[rpc.grpc]` backend, streaming frames both ways as they arrive,
+/// trailers (and so the call's `grpc-status`) included. A backend that can't be reached is
+/// reported to the client as gRPC status `UNAVAILABLE`.
+pub(crate) async fn proxy_grpc_request(
+    state: RpcGatewayState,
+    grpc: &GrpcUpstream,
+    req: Request<Body>,
+) -> Response {
+    let (mut parts, body) = req.into_parts();
+    let path_and_query = state.route.forward_path_and_query(&parts.uri);
+    if let Some(access) = parts.extensions.get::<AccessDetails>() {
+        // The call's `/package.Service/Method` path names the method
+        access.set_rpc_methods(vec![parts.uri.path().trim_start_matches('/').to_string()]);
+    }
+    parts.uri = match format!("{}{path_and_query}", grpc.target).parse::<Uri>() {
+        Ok(uri) => uri,
+        Err(e) => {
+            warn!(error = %e, "Failed to parse gRPC target URI");
+            return (StatusCode::BAD_REQUEST, "Invalid target URI").into_response();
+        }
+    };
+    if let Some(access) = parts.extensions.get::<AccessDetails>() {
+        access.set_upstream(grpc.target.clone());
+    }
+    parts.version = Version::HTTP_2;
+    parts.headers.remove(HOST);
+    strip_gateway_headers(&mut parts.headers);
+    let upload = parts.extensions.get::<UploadStatus>().cloned();
+
+    debug!(uri = %parts.uri, "Proxying gRPC call");
+    grpc.pool_counters.record_request();
+    match grpc.client.request(Request::from_parts(parts, body)).await {
+        Ok(response) => response.map(Body::new),
+        Err(e) => {
+            // A request stream cut off before the backend answered fails the call; tell the
+            // client why instead.
+            if let Some(failure) = upload.as_ref().and_then(UploadStatus::failure) {
+                warn!(error = %failure, "Rejected gRPC request stream");
+                return failure.response();
+            }
+            warn!(error = %e, "gRPC backend request failed");
+            unavailable_response()
+        }
+    }
+}
+
+/// A trailers-only gRPC response with status `UNAVAILABLE`, which clients may retry.
+fn unavailable_response() -> Response {
+    (
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE.as_str(), "application/grpc"),
+            (GRPC_STATUS_HEADER, GRPC_UNAVAILABLE),
+            (GRPC_MESSAGE_HEADER, "backend unavailable"),
+        ],
+    )
+        .into_response()
+}
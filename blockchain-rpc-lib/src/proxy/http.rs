@@ -0,0 +1,773 @@
+use super::local_methods;
+use super::upstream::{AffinityKey, Upstream, UpstreamHttpError};
+use super::{
+    AuthenticatedAccount, BurstLoan, LimitStatus, RpcGatewayState, batch_too_large_payload,
+    charge_quota, check_rate_limit, filter_rejected_payload, limit_exceeded_payload,
+    method_not_allowed_payload, method_not_supported_payload, quota_exhausted_response,
+    rate_limited_response, read_only_payload,
+};
+use crate::access_log::AccessDetails;
+use crate::config::BatchConfig;
+use crate::error::Error;
+use crate::firewall::retry_after_secs;
+use crate::latency::BATCH_METHOD;
+use crate::metering::UsageSubject;
+use crate::method_filter::jsonrpc_methods;
+use crate::mirror::Mirror;
+use crate::payload_filters::FilterContext;
+use crate::plans::ActivePlan;
+use crate::upload::UploadStatus;
+use crate::upstream_signing::strip_gateway_headers;
+use axum::{
+    body::{Body, Bytes},
+    extract::ConnectInfo,
+    http::{
+        Request, Response as HttpResponse, StatusCode,
+        header::{ACCEPT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, RETRY_AFTER, TRANSFER_ENCODING},
+        request::Parts,
+    },
+    response::{IntoResponse, Response},
+};
+use http_body_util::{BodyExt, Limited};
+use hyper::body::Incoming;
+use serde_json::Value;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, warn};
+
+/// Largest upstream error body inspected for a JSON-RPC error to pass through.
+const MAX_UPSTREAM_ERROR_BODY_BYTES: usize = 64 * 1024;
+/// Upstream error messages passed through to clients are truncated to this many characters.
+const MAX_UPSTREAM_ERROR_MESSAGE_CHARS: usize = 512;
+
+/// Proxies a standard HTTP request to the backend RPC node.
+pub(crate) async fn proxy_http_request(
+    state: RpcGatewayState,
+    req: Request<Body>,
+) -> Result<Response, Error> {
+    let (mut parts, body) = req.into_parts();
+
+    // Construct the target URI, without the route's prefix
+    let path_and_query = state.route.forward_path_and_query(&parts.uri);
+
+    let affinity = parts.extensions.get::<AffinityKey>();
+    let target_uri = match state.route.upstream.target_uri(&path_and_query, affinity) {
+        Ok(uri) => uri,
+        Err(e) => {
+            error!(error = %e, "Failed to parse target URI");
+            return Ok((StatusCode::BAD_REQUEST, "Invalid target URI").into_response());
+        }
+    };
+
+    let access = parts.extensions.get::<AccessDetails>().cloned();
+    if let (Some(access), Some(scheme), Some(authority)) =
+        (&access, target_uri.scheme(), target_uri.authority())
+    {
+        access.set_upstream(format!("{scheme}://{authority}"));
+    }
+    parts.uri = target_uri;
+    // Clear host header to avoid mismatches
+    parts.headers.remove(hyper::header::HOST);
+    strip_gateway_headers(&mut parts.headers);
+
+    // Bodies are streamed to the backend unless something below has to see the calls first:
+    // - validation, method rewriting, method filters, plans, roles, read-only mode, probes
+    // - batch and per-method limits
+    // - metering, the daily digest, latency, the access log and traffic stats
+    // - upstream signing, the head cache, coalescing and gateway-local methods
+    // - archive routing, retries, mirroring and payload filters
+    // The body-size limit applies either way, to streamed bodies as they are read.
+    let active_plan = parts.extensions.get::<ActivePlan>().cloned();
+    let needs_buffering = state.route.validator.is_some()
+        || !state.route.method_rewriter.is_empty()
+        || !state.route.method_filter.is_empty()
+        || active_plan
+            .as_ref()
+            .is_some_and(|active| active.plan.restricts_methods())
+        || !state.ctx.account_roles.methods().is_empty()
+        || state.ctx.read_only.is_enabled()
+        || state
+            .route
+            .probe
+            .as_ref()
+            .is_some_and(|probe| probe.restricts_methods())
+        || state.ctx.config().rpc.batch.is_some()
+        || !state.route.method_limits.is_empty()
+        || state.ctx.usage_meter.is_some()
+        || state.ctx.daily_digest.is_some()
+        || state.ctx.upstream_signer.is_some()
+        || state.route.head_cache.is_some()
+        || state.ctx.coalescer.is_some()
+        || state.ctx.config().rpc.gateway_methods
+        || state.ctx.latency.is_some()
+        || state.ctx.access_log.is_some()
+        || state.ctx.traffic_stats.is_some()
+        || state.route.archive.is_some()
+        || state.route.upstream.buffers_requests()
+        || state.route.mirror.is_some()
+        || state.ctx.payload_filters.filters_requests();
+    let filters = &state.ctx.payload_filters;
+    let filter_ctx = (filters.filters_requests() || filters.filters_responses())
+        .then(|| filter_context(&state, &parts));
+    let upload = parts.extensions.get::<UploadStatus>().cloned();
+    let upload_failure = || upload.as_ref().and_then(UploadStatus::failure);
+    if !needs_buffering {
+        let proxy_req = Request::from_parts(parts, body);
+        let result = state.route.upstream.send(proxy_req).await;
+        // A body cut off mid-stream fails the backend call; tell the client why instead.
+        if let Some(failure) = upload_failure() {
+            warn!(error = %failure, "Rejected streamed request body");
+            return Ok(failure.response());
+        }
+        let response = upstream_response(result).await?;
+        return Ok(filter_response(&state, filter_ctx.as_ref(), response).await);
+    }
+
+    let body_bytes = match body.collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(e) => {
+            if let Some(failure) = upload_failure() {
+                warn!(error = %failure, "Rejected request body");
+                return Ok(failure.response());
+            }
+            error!(error = %e, "Failed to read request body");
+            return Ok((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read request body",
+            )
+                .into_response());
+        }
+    };
+
+    if let Some(access) = &access {
+        access.set_rpc_methods(jsonrpc_methods(&body_bytes));
+    }
+
+    // Every later check sees the request as filtered.
+    let filtered = filter_ctx
+        .as_ref()
+        .map(|filter_ctx| filters.filter_request(filter_ctx, &body_bytes));
+    let body_bytes = match filtered {
+        Some(Err(reason)) => {
+            warn!(%reason, "Payload filter rejected JSON-RPC request");
+            return Ok((
+                StatusCode::BAD_REQUEST,
+                [(CONTENT_TYPE, "application/json")],
+                filter_rejected_payload(&reason),
+            )
+                .into_response());
+        }
+        Some(Ok(Some(filtered))) => {
+            parts.headers.insert(CONTENT_LENGTH, filtered.len().into());
+            Bytes::from(filtered)
+        }
+        Some(Ok(None)) | None => body_bytes,
+    };
+
+    if let Some(invalid) = state
+        .route
+        .validator
+        .as_ref()
+        .and_then(|validator| validator.validate(&body_bytes).err())
+    {
+        warn!(error = %invalid, "Rejected invalid JSON-RPC request");
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            [(CONTENT_TYPE, "application/json")],
+            invalid.payload(),
+        )
+            .into_response());
+    }
+
+    // Gateway-local methods never reach the backend, so no filter, limit or meter applies.
+    let local_answer = state
+        .ctx
+        .config()
+        .rpc
+        .gateway_methods
+        .then(|| local_methods::answer(&state, &parts, active_plan.as_ref(), &body_bytes))
+        .flatten();
+    if let Some(answer) = local_answer {
+        debug!("Answered gateway-local JSON-RPC call");
+        return Ok(([(CONTENT_TYPE, "application/json")], answer).into_response());
+    }
+
+    // Filtering sees the rewritten calls, i.e. what would reach the backend.
+    let body_bytes = match state.route.method_rewriter.rewrite(&body_bytes) {
+        Some(rewritten) => {
+            debug!("Rewrote JSON-RPC request");
+            parts.headers.insert(CONTENT_LENGTH, rewritten.len().into());
+            Bytes::from(rewritten)
+        }
+        None => body_bytes,
+    };
+
+    if let Some(method) = state.route.method_filter.find_blocked_method(&body_bytes) {
+        warn!(%method, "Blocked JSON-RPC method in HTTP request");
+        return Ok((
+            StatusCode::FORBIDDEN,
+            [(CONTENT_TYPE, "application/json")],
+            method_not_allowed_payload(&method),
+        )
+            .into_response());
+    }
+
+    if let Some(method) = active_plan
+        .as_ref()
+        .and_then(|active| active.plan.find_blocked_method(&body_bytes))
+    {
+        warn!(%method, "Blocked JSON-RPC method outside the client's plan");
+        return Ok((
+            StatusCode::FORBIDDEN,
+            [(CONTENT_TYPE, "application/json")],
+            method_not_allowed_payload(&method),
+        )
+            .into_response());
+    }
+
+    let account = parts
+        .extensions
+        .get::<AuthenticatedAccount>()
+        .map(|AuthenticatedAccount(account)| account);
+    if let Some(method) = state
+        .ctx
+        .account_roles
+        .find_unauthorized_method(&body_bytes, account)
+    {
+        warn!(%method, "Blocked JSON-RPC method requiring a role the client lacks");
+        return Ok((
+            StatusCode::FORBIDDEN,
+            [(CONTENT_TYPE, "application/json")],
+            method_not_allowed_payload(&method),
+        )
+            .into_response());
+    }
+
+    if let Some((method, status)) = state.ctx.read_only.find_rejected_method(&body_bytes) {
+        warn!(%method, "Rejected JSON-RPC call while the gateway is read-only");
+        return Ok((
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(CONTENT_TYPE, "application/json")],
+            read_only_payload(&method, &status),
+        )
+            .into_response());
+    }
+
+    if let Some(violation) = state.route.method_limits.check_request(&body_bytes) {
+        warn!(%violation, "Rejected JSON-RPC call over its method limits");
+        return Ok((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            [(CONTENT_TYPE, "application/json")],
+            limit_exceeded_payload(&violation),
+        )
+            .into_response());
+    }
+
+    if let Some(method) = state
+        .route
+        .probe
+        .as_ref()
+        .and_then(|probe| probe.find_unsupported_method(&body_bytes))
+    {
+        warn!(%method, "Refused JSON-RPC method the backend doesn't support");
+        return Ok((
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            method_not_supported_payload(&method),
+        )
+            .into_response());
+    }
+
+    let chunks = match &state.ctx.config().rpc.batch {
+        Some(config) => {
+            match enforce_batch(&state, &parts, active_plan.as_ref(), config, &body_bytes).await {
+                Ok(chunks) => chunks,
+                Err(rejection) => return Ok(rejection),
+            }
+        }
+        None => None,
+    };
+
+    let methods = jsonrpc_methods(&body_bytes);
+    if let (Some(meter), Some(subject)) = (
+        &state.ctx.usage_meter,
+        parts.extensions.get::<UsageSubject>(),
+    ) {
+        meter.record_calls(subject, &methods);
+    }
+    if let Some(daily_digest) = &state.ctx.daily_digest {
+        daily_digest.record_calls(&methods);
+    }
+    if let Some(answer) = state
+        .route
+        .head_cache
+        .as_ref()
+        .and_then(|cache| cache.answer(&body_bytes))
+    {
+        debug!(methods = %methods.join(","), "Answered JSON-RPC call from the head cache");
+        let response = ([(CONTENT_TYPE, "application/json")], answer).into_response();
+        return Ok(filter_response(&state, filter_ctx.as_ref(), response).await);
+    }
+    // Calls at blocks the fast node has pruned go to the archive node; a batch with any such
+    // call goes there whole
+    let upstream: &Upstream = match state
+        .route
+        .archive
+        .as_ref()
+        .filter(|archive| archive.serves(&body_bytes))
+    {
+        Some(archive) => {
+            let target_uri = match archive.upstream.target_uri(&path_and_query, None) {
+                Ok(uri) => uri,
+                Err(e) => {
+                    error!(error = %e, "Failed to parse archive target URI");
+                    return Ok((StatusCode::BAD_REQUEST, "Invalid target URI").into_response());
+                }
+            };
+            if let (Some(access), Some(scheme), Some(authority)) =
+                (&access, target_uri.scheme(), target_uri.authority())
+            {
+                access.set_upstream(format!("{scheme}://{authority}"));
+            }
+            debug!(methods = %methods.join(","), "Routing historical JSON-RPC call to the archive node");
+            parts.uri = target_uri;
+            &*archive.upstream
+        }
+        None => &*state.route.upstream,
+    };
+    let idempotent = upstream.is_idempotent(&parts, &methods);
+    let max_response_bytes = state.route.method_limits.max_response_bytes(&methods);
+    let request_body = body_bytes.clone();
+    let started = Instant::now();
+    let response = match chunks {
+        Some(chunks) => send_split_batch(&state, upstream, &parts, chunks, idempotent).await?,
+        None => {
+            if let Some(signer) = &state.ctx.upstream_signer {
+                signer.sign_headers(&mut parts.headers, &methods.join(","));
+            }
+            let coalescable = state.ctx.coalescer.as_ref().and_then(|coalescer| {
+                let call = coalescer.coalescable(upstream.proxy_url().as_str(), &body_bytes)?;
+                Some((coalescer, call))
+            });
+            match coalescable {
+                Some((coalescer, call)) => {
+                    // The response is shared with clients that may not accept this one's
+                    // encodings.
+                    parts.headers.remove(ACCEPT_ENCODING);
+                    let parts = &parts;
+                    coalescer
+                        .run(call, || async move {
+                            let result = upstream
+                                .send_with_retries(parts, body_bytes, idempotent)
+                                .await;
+                            match upstream_response(result).await {
+                                Ok(response) => response,
+                                Err(e) => {
+                                    error!(error = %e, "Failed to proxy coalesced call");
+                                    (StatusCode::BAD_GATEWAY, "Upstream error").into_response()
+                                }
+                            }
+                        })
+                        .await
+                }
+                None => {
+                    let result = upstream
+                        .send_with_retries(&parts, body_bytes, idempotent)
+                        .await;
+                    upstream_response(result).await?
+                }
+            }
+        }
+    };
+    let latency_method = match methods.as_slice() {
+        [] => None,
+        [method] => Some(method.as_str()),
+        _ => Some(BATCH_METHOD),
+    };
+    if let (Some(latency), Some(config), Some(method)) = (
+        &state.ctx.latency,
+        &state.ctx.config().rpc.latency,
+        latency_method,
+    ) {
+        let elapsed = started.elapsed();
+        let slow = elapsed >= Duration::from_millis(config.slow_request_ms);
+        latency.record(method, elapsed, slow);
+        if slow {
+            warn!(
+                method,
+                params_bytes = params_bytes(&request_body),
+                route = state.route.name(),
+                upstream = %upstream.proxy_url(),
+                duration_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX),
+                "Slow request"
+            );
+        }
+    }
+    let response = match max_response_bytes {
+        Some(max) => limit_response_body(response, max).await,
+        None => response,
+    };
+    // Calls sent to the archive node aren't mirrored; the shadow backend stands in for the
+    // route's own.
+    let mirror = state
+        .route
+        .mirror
+        .as_ref()
+        .filter(|mirror| std::ptr::eq(upstream, &*state.route.upstream) && mirror.samples());
+    let response = match mirror {
+        Some(mirror) => {
+            mirror_request(mirror, &parts, &path_and_query, request_body, response).await
+        }
+        None => response,
+    };
+    Ok(filter_response(&state, filter_ctx.as_ref(), response).await)
+}
+
+/// Duplicates a request to the route's shadow backend, first buffering the primary backend's
+/// successful response if the answers are compared.
+async fn mirror_request(
+    mirror: &Arc<Mirror>,
+    parts: &Parts,
+    path_and_query: &str,
+    body: Bytes,
+    response: Response,
+) -> Response {
+    if !mirror.compares() || !response.status().is_success() {
+        mirror.send(parts, path_and_query, body, None);
+        return response;
+    }
+    let (response_parts, response_body) = response.into_parts();
+    match response_body.collect().await {
+        Ok(collected) => {
+            let primary = collected.to_bytes();
+            mirror.send(parts, path_and_query, body, Some(primary.clone()));
+            Response::from_parts(response_parts, Body::from(primary))
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to read upstream response");
+            (StatusCode::BAD_GATEWAY, "Upstream error").into_response()
+        }
+    }
+}
+
+/// What payload filters see of the request.
+fn filter_context(state: &RpcGatewayState, parts: &Parts) -> FilterContext {
+    FilterContext {
+        route: state.route.name().to_string(),
+        client_ip: parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |ConnectInfo(addr)| {
+                addr.ip()
+            }),
+        account: parts
+            .extensions
+            .get::<AuthenticatedAccount>()
+            .map(|AuthenticatedAccount(account)| account.clone()),
+        websocket: false,
+    }
+}
+
+/// Runs the response filters over a successful response, buffering its body.
+async fn filter_response(
+    state: &RpcGatewayState,
+    filter_ctx: Option<&FilterContext>,
+    response: Response,
+) -> Response {
+    let Some(filter_ctx) = filter_ctx.filter(|_| {
+        response.status().is_success() && state.ctx.payload_filters.filters_responses()
+    }) else {
+        return response;
+    };
+    let (mut parts, body) = response.into_parts();
+    let body = match body.collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(e) => {
+            error!(error = %e, "Failed to read upstream response");
+            return (StatusCode::BAD_GATEWAY, "Upstream error").into_response();
+        }
+    };
+    match state.ctx.payload_filters.filter_response(filter_ctx, &body) {
+        Some(filtered) => {
+            parts.headers.remove(TRANSFER_ENCODING);
+            parts.headers.insert(CONTENT_LENGTH, filtered.len().into());
+            Response::from_parts(parts, Body::from(filtered))
+        }
+        None => Response::from_parts(parts, Body::from(body)),
+    }
+}
+
+/// Size of a single call's params as sent, or of the whole body for a batch.
+fn params_bytes(body: &[u8]) -> usize {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(Value::Object(call)) => call
+            .get("params")
+            .map_or(0, |params| params.to_string().len()),
+        _ => body.len(),
+    }
+}
+
+/// Buffers a response up to `max` bytes, replacing it with a JSON-RPC error if the backend's
+/// answer is larger, so oversized results never reach the client.
+async fn limit_response_body(response: Response, max: usize) -> Response {
+    let (parts, body) = response.into_parts();
+    match Limited::new(body, max).collect().await {
+        Ok(collected) => Response::from_parts(parts, Body::from(collected.to_bytes())),
+        Err(e) if e.is::<http_body_util::LengthLimitError>() => {
+            warn!(max, "Response exceeds its method's size limit");
+            (
+                StatusCode::OK,
+                [(CONTENT_TYPE, "application/json")],
+                limit_exceeded_payload(&format!("Response too large (max {max} bytes)")),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to read upstream response");
+            (StatusCode::BAD_GATEWAY, "Upstream error").into_response()
+        }
+    }
+}
+
+/// Applies `[rpc.batch]` to a JSON-RPC batch: rejects it if it has more calls than allowed
+/// (unless oversized batches are split) or if the client doesn't have a rate-limit token left
+/// for each call. The request itself already consumed one token. Returns the batches to forward
+/// instead of the original if it has to be split.
+async fn enforce_batch(
+    state: &RpcGatewayState,
+    parts: &Parts,
+    active_plan: Option<&ActivePlan>,
+    config: &BatchConfig,
+    body: &[u8],
+) -> Result<Option<Vec<Bytes>>, Response> {
+    let Ok(Value::Array(calls)) = serde_json::from_slice::<Value>(body) else {
+        return Ok(None);
+    };
+
+    let max_batch_size = config.max_batch_size.max(1);
+    if calls.len() > max_batch_size && !config.split_oversized {
+        warn!(
+            calls = calls.len(),
+            max = max_batch_size,
+            "Rejected oversized JSON-RPC batch"
+        );
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            [(CONTENT_TYPE, "application/json")],
+            batch_too_large_payload(calls.len(), max_batch_size),
+        )
+            .into_response());
+    }
+
+    let extra_calls = u32::try_from(calls.len().saturating_sub(1)).unwrap_or(u32::MAX);
+    let client_ip = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+        .filter(|_| extra_calls > 0);
+    let burst_loan = parts
+        .extensions
+        .get::<BurstLoan>()
+        .copied()
+        .unwrap_or(BurstLoan(0));
+    let limits = parts
+        .extensions
+        .get::<LimitStatus>()
+        .cloned()
+        .unwrap_or_default();
+    let rate_limited = match client_ip {
+        Some(client_ip) => {
+            check_rate_limit(
+                &state.ctx,
+                client_ip,
+                active_plan,
+                burst_loan,
+                &limits,
+                extra_calls,
+            )
+            .await
+        }
+        None => None,
+    };
+    if let Some(retry_after) = rate_limited {
+        warn!(
+            ?client_ip,
+            calls = calls.len(),
+            "Rate limited JSON-RPC batch"
+        );
+        return Err(rate_limited_response(retry_after));
+    }
+    if extra_calls > 0 && !charge_quota(&state.ctx, active_plan, &limits, extra_calls).await {
+        warn!(
+            ?client_ip,
+            calls = calls.len(),
+            "Rejected JSON-RPC batch over its plan's quota"
+        );
+        return Err(quota_exhausted_response());
+    }
+
+    if calls.len() <= max_batch_size {
+        return Ok(None);
+    }
+    debug!(
+        calls = calls.len(),
+        max = max_batch_size,
+        "Splitting oversized JSON-RPC batch"
+    );
+    Ok(Some(
+        calls
+            .chunks(max_batch_size)
+            .map(|chunk| Bytes::from(Value::from(chunk.to_vec()).to_string()))
+            .collect(),
+    ))
+}
+
+/// Forwards the parts of a split batch concurrently to `upstream` and merges the backend's
+/// answers into a single batch response, in request order. If any part fails, its failure is
+/// returned.
+async fn send_split_batch(
+    state: &RpcGatewayState,
+    upstream: &Upstream,
+    parts: &Parts,
+    chunks: Vec<Bytes>,
+    idempotent: bool,
+) -> Result<Response, Error> {
+    let sends = chunks.into_iter().map(|chunk| async move {
+        let (mut chunk_parts, ()) = Request::new(()).into_parts();
+        chunk_parts.method = parts.method.clone();
+        chunk_parts.uri = parts.uri.clone();
+        chunk_parts.version = parts.version;
+        chunk_parts.headers = parts.headers.clone();
+        chunk_parts
+            .headers
+            .insert(CONTENT_LENGTH, chunk.len().into());
+        if let Some(signer) = &state.ctx.upstream_signer {
+            signer.sign_headers(&mut chunk_parts.headers, &jsonrpc_methods(&chunk).join(","));
+        }
+        upstream
+            .send_with_retries(&chunk_parts, chunk, idempotent)
+            .await
+    });
+
+    let mut merged = Vec::new();
+    for result in futures::future::join_all(sends).await {
+        let resp = match result {
+            Ok(resp) if resp.status().is_success() => resp,
+            failed => return upstream_response(failed).await,
+        };
+        let body = match resp.into_body().collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(e) => {
+                error!(error = %e, "Failed to read upstream batch response");
+                return Ok((StatusCode::BAD_GATEWAY, "Upstream error").into_response());
+            }
+        };
+        match serde_json::from_slice::<Value>(&body) {
+            Ok(Value::Array(items)) => merged.extend(items),
+            Ok(item) => merged.push(item),
+            Err(e) => {
+                error!(error = %e, "Upstream returned an invalid batch response");
+                return Ok((StatusCode::BAD_GATEWAY, "Upstream error").into_response());
+            }
+        }
+    }
+    Ok((
+        [(CONTENT_TYPE, "application/json")],
+        Value::Array(merged).to_string(),
+    )
+        .into_response())
+}
+
+/// Turns the backend's answer into the client response, streaming successful response bodies
+/// back to the client.
+async fn upstream_response(
+    result: Result<HttpResponse<Incoming>, UpstreamHttpError>,
+) -> Result<Response, Error> {
+    match result {
+        Ok(resp) if !resp.status().is_success() => {
+            let status = resp.status();
+            let body = Limited::new(resp.into_body(), MAX_UPSTREAM_ERROR_BODY_BYTES)
+                .collect()
+                .await;
+            match body
+                .ok()
+                .and_then(|collected| sanitize_upstream_error(&collected.to_bytes()))
+            {
+                Some(payload) => {
+                    warn!(%status, "Upstream returned a JSON-RPC error");
+                    Ok((status, [(CONTENT_TYPE, "application/json")], payload).into_response())
+                }
+                None => {
+                    warn!(%status, "Upstream request failed");
+                    Ok((status, format!("Upstream error: {}", status)).into_response())
+                }
+            }
+        }
+        Ok(resp) => Ok(resp.map(Body::new)),
+        Err(UpstreamHttpError::CircuitOpen(retry_after)) => {
+            warn!("Rejected request while the backend circuit is open");
+            Ok((
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(RETRY_AFTER, retry_after_secs(retry_after).to_string())],
+                "Backend unavailable",
+            )
+                .into_response())
+        }
+        Err(UpstreamHttpError::Saturated) => Ok((
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(RETRY_AFTER, "1")],
+            "Backend busy",
+        )
+            .into_response()),
+        Err(e) => {
+            error!(error = %e, "Failed to proxy request");
+            Ok((
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("Proxy error: {}", e),
+            )
+                .into_response())
+        }
+    }
+}
+
+/// Rebuilds a JSON-RPC error response (or a batch of them) from an upstream error body,
+/// keeping only the `id` and the error's `code` and truncated `message`. Anything else,
+/// including `error.data`, is dropped so node internals don't leak to clients.
+pub(super) fn sanitize_upstream_error(body: &[u8]) -> Option<String> {
+    let sanitized = match serde_json::from_slice(body).ok()? {
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(sanitize_jsonrpc_error)
+                .collect::<Option<_>>()?,
+        ),
+        item => sanitize_jsonrpc_error(&item)?,
+    };
+    Some(sanitized.to_string())
+}
+
+fn sanitize_jsonrpc_error(item: &Value) -> Option<Value> {
+    let error = item.get("error")?;
+    let code = error.get("code")?.as_i64()?;
+    let message: String = error
+        .get("message")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_UPSTREAM_ERROR_MESSAGE_CHARS)
+        .collect();
+    let id = match item.get("id") {
+        Some(id @ (Value::Number(_) | Value::String(_))) => id.clone(),
+        _ => Value::Null,
+    };
+    Some(serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {
+            "code": code,
+            "message": message,
+        },
+    }))
+}
@@ -0,0 +1,451 @@
+use super::http::sanitize_upstream_error;
+use super::routing::Route;
+use super::upstream::AffinityKey;
+use crate::config::WebSocketBridgeConfig;
+use crate::context::SecureRpcContext;
+use crate::method_filter::jsonrpc_methods;
+use axum::body::Bytes;
+use axum::http::{HeaderMap, HeaderValue, Request, header::CONTENT_TYPE};
+use futures::channel::mpsc;
+use futures::stream::FuturesUnordered;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use http_body_util::BodyExt;
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tracing::{debug, warn};
+
+/// Messages queued in each direction between a bridged session and its bridge.
+const BRIDGE_QUEUE_LEN: usize = 64;
+
+/// JSON-RPC error code returned for calls the backend didn't answer.
+const BACKEND_ERROR_CODE: i64 = -32603;
+
+/// JSON-RPC error code returned for subscriptions the bridge doesn't emulate.
+const SUBSCRIPTION_NOT_SUPPORTED_CODE: i64 = -32601;
+
+/// Serves a WebSocket session from a backend that only speaks HTTP, per `websocket_bridge`:
+/// each message the client sends is POSTed to the backend and its answer sent back, while head
+/// subscriptions are answered by the bridge itself and fed by polling the backend.
+///
+/// The session is forwarded by a [`super::ws::WsBridge`] as usual, with a [`BridgeSocket`] in
+/// place of the backend connection, so its limits, filters and metering all apply.
+pub(crate) struct HttpBridge {
+    backend: BridgeBackend,
+    poll_interval: Duration,
+}
+
+/// Where a bridged session's calls go.
+struct BridgeBackend {
+    ctx: Arc<SecureRpcContext>,
+    route: Arc<Route>,
+    affinity: AffinityKey,
+    /// Sent with every call, e.g. the trace context and request ID of the upgrade.
+    headers: HeaderMap,
+}
+
+impl HttpBridge {
+    pub(crate) fn new(
+        config: &WebSocketBridgeConfig,
+        ctx: Arc<SecureRpcContext>,
+        route: Arc<Route>,
+        affinity: AffinityKey,
+        headers: HeaderMap,
+    ) -> Self {
+        HttpBridge {
+            backend: BridgeBackend {
+                ctx,
+                route,
+                affinity,
+                headers,
+            },
+            poll_interval: Duration::from_millis(config.poll_interval_ms),
+        }
+    }
+
+    /// Opens the session: returns the socket standing in for the backend connection, and the
+    /// future serving it, which completes once the socket is closed or dropped.
+    pub(crate) fn open(self) -> (BridgeSocket, impl Future<Output = ()>) {
+        let (to_bridge, inbound) = mpsc::channel(BRIDGE_QUEUE_LEN);
+        let (outbound, from_bridge) = mpsc::channel(BRIDGE_QUEUE_LEN);
+        let socket = BridgeSocket {
+            to_bridge,
+            from_bridge,
+        };
+        (socket, self.serve(inbound, outbound))
+    }
+
+    /// Answers messages from the session, several at a time, and sends head notifications to
+    /// its subscriptions.
+    async fn serve(
+        self,
+        mut inbound: mpsc::Receiver<Message>,
+        mut outbound: mpsc::Sender<Message>,
+    ) {
+        let backend = &self.backend;
+        let mut subscriptions = HeadSubscriptions::default();
+        let mut calls = FuturesUnordered::new();
+        let mut polls = FuturesUnordered::new();
+        let mut poll = tokio::time::interval(self.poll_interval);
+        poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            let replies = tokio::select! {
+                message = inbound.next() => match message {
+                    Some(Message::Text(text)) => match subscriptions.answer(text.to_string()) {
+                        Answer::Local(reply) => vec![Message::text(reply.to_string())],
+                        Answer::Backend(request, local) => {
+                            calls.push(backend.forward(request, local));
+                            Vec::new()
+                        }
+                    },
+                    // Keeps the session's keepalive satisfied that the backend is there
+                    Some(Message::Ping(ping)) => vec![Message::Pong(ping)],
+                    Some(Message::Binary(_)) => {
+                        debug!("Ignoring binary frame on a WebSocket session bridged over HTTP");
+                        Vec::new()
+                    }
+                    Some(Message::Close(_)) | None => break,
+                    Some(_) => Vec::new(),
+                },
+                Some(reply) = calls.next() => vec![Message::text(reply)],
+                Some((kind, polled)) = polls.next() => {
+                    subscriptions.polling.remove(&kind);
+                    match polled {
+                        Ok((marker, head)) => subscriptions.polled(kind, &marker, &head),
+                        Err(e) => {
+                            warn!(?kind, error = %e, "Failed to poll the backend for heads");
+                            Vec::new()
+                        }
+                    }
+                }
+                _ = poll.tick(), if !subscriptions.open.is_empty() => {
+                    for kind in subscriptions.start_polls() {
+                        polls.push(async move { (kind, kind.fetch(backend).await) });
+                    }
+                    Vec::new()
+                }
+            };
+            for reply in replies {
+                if outbound.send(reply).await.is_err() {
+                    return;
+                }
+            }
+        }
+        debug!("WebSocket session bridged over HTTP finished");
+    }
+}
+
+impl BridgeBackend {
+    /// Sends `request` to the backend, answering with an error for each of its calls if that
+    /// fails. The answers in `local`, to other calls of the same batch, are added to the
+    /// backend's.
+    async fn forward(&self, request: String, local: Vec<Value>) -> String {
+        let reply = match self.post(request.clone()).await {
+            Ok(reply) => reply,
+            Err(e) => {
+                warn!(error = %e, "Failed to bridge WebSocket message to the backend");
+                backend_error_payload(&request, &e)
+            }
+        };
+        if local.is_empty() {
+            return reply;
+        }
+        let mut responses = match serde_json::from_str(&reply) {
+            Ok(Value::Array(responses)) => responses,
+            Ok(response) => vec![response],
+            Err(_) => Vec::new(),
+        };
+        responses.extend(local);
+        Value::Array(responses).to_string()
+    }
+
+    /// Calls `method` on the backend, returning its result.
+    async fn call(&self, method: &str, params: Value) -> Result<Value, String> {
+        let request = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+        let reply: Value = serde_json::from_str(&self.post(request.to_string()).await?)
+            .map_err(|e| format!("invalid response to {method}: {e}"))?;
+        match reply.get("result") {
+            Some(result) if reply.get("error").is_none() => Ok(result.clone()),
+            _ => Err(format!(
+                "{method} failed: {}",
+                reply.get("error").unwrap_or(&Value::Null)
+            )),
+        }
+    }
+
+    /// POSTs `body` to the backend the session is pinned to, at its URL like WebSocket
+    /// connections are dialed to it, with retries and upstream signing as for HTTP requests.
+    async fn post(&self, body: String) -> Result<String, String> {
+        let upstream = &self.route.upstream;
+        let uri = upstream.target_uri("", Some(&self.affinity))?;
+        let (mut parts, ()) = Request::post(uri)
+            .body(())
+            .map_err(|e| e.to_string())?
+            .into_parts();
+        parts.headers = self.headers.clone();
+        parts
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let methods = jsonrpc_methods(body.as_bytes());
+        if let Some(signer) = &self.ctx.upstream_signer {
+            signer.sign_headers(&mut parts.headers, &methods.join(","));
+        }
+        let idempotent = upstream.is_idempotent(&parts, &methods);
+        let response = upstream
+            .send_with_retries(&parts, Bytes::from(body), idempotent)
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = response.status();
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| format!("failed to read the backend response: {e}"))?
+            .to_bytes();
+        if !status.is_success() {
+            return sanitize_upstream_error(&body)
+                .ok_or_else(|| format!("backend answered {status}"));
+        }
+        String::from_utf8(body.to_vec()).map_err(|_| "backend answered invalid UTF-8".to_string())
+    }
+}
+
+/// How a client message is answered.
+enum Answer {
+    /// By the bridge alone.
+    Local(Value),
+    /// By the backend, sent the message (or the calls of a batch the bridge didn't answer), with
+    /// the bridge's answers to the rest of the batch.
+    Backend(String, Vec<Value>),
+}
+
+/// A head subscription the bridge emulates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum HeadKind {
+    /// `eth_subscribe("newHeads")`, polled with `eth_getBlockByNumber("latest", false)`.
+    Evm,
+    /// `chain_subscribeNewHeads`, polled with `chain_getHeader`.
+    Substrate,
+    /// `chain_subscribeFinalizedHeads`, polled with `chain_getFinalizedHead` and
+    /// `chain_getHeader`.
+    SubstrateFinalized,
+}
+
+impl HeadKind {
+    /// The head subscription a call to `method` with `params` opens, if it is emulated.
+    fn of(method: &str, params: Option<&Value>) -> Option<Self> {
+        match method {
+            "eth_subscribe" => {
+                let kind = params?.get(0)?.as_str()?;
+                (kind == "newHeads").then_some(HeadKind::Evm)
+            }
+            "chain_subscribeNewHeads" | "chain_subscribeNewHead" => Some(HeadKind::Substrate),
+            "chain_subscribeFinalizedHeads" | "chain_subscribeFinalisedHeads" => {
+                Some(HeadKind::SubstrateFinalized)
+            }
+            _ => None,
+        }
+    }
+
+    fn notification_method(self) -> &'static str {
+        match self {
+            HeadKind::Evm => "eth_subscription",
+            HeadKind::Substrate => "chain_newHead",
+            HeadKind::SubstrateFinalized => "chain_finalizedHead",
+        }
+    }
+
+    /// Fetches the current head, with what tells it apart from the previous one: its hash, or
+    /// the whole header for Substrate's best head, since headers don't carry their hash.
+    async fn fetch(self, backend: &BridgeBackend) -> Result<(Value, Value), String> {
+        match self {
+            HeadKind::Evm => {
+                let block = backend
+                    .call("eth_getBlockByNumber", json!(["latest", false]))
+                    .await?;
+                let hash = block
+                    .get("hash")
+                    .cloned()
+                    .ok_or("latest block has no hash")?;
+                Ok((hash, block))
+            }
+            HeadKind::Substrate => {
+                let header = backend.call("chain_getHeader", json!([])).await?;
+                Ok((header.clone(), header))
+            }
+            HeadKind::SubstrateFinalized => {
+                let hash = backend.call("chain_getFinalizedHead", json!([])).await?;
+                let header = backend.call("chain_getHeader", json!([hash])).await?;
+                Ok((hash, header))
+            }
+        }
+    }
+}
+
+/// The head subscriptions open on a bridged session.
+#[derive(Debug, Default)]
+struct HeadSubscriptions {
+    /// By subscription ID: the heads followed, and the last one sent.
+    open: HashMap<String, (HeadKind, Option<Value>)>,
+    /// Heads being polled for.
+    polling: HashSet<HeadKind>,
+}
+
+impl HeadSubscriptions {
+    /// Answers the subscribe and unsubscribe calls of a client message, leaving the rest to the
+    /// backend.
+    fn answer(&mut self, text: String) -> Answer {
+        match serde_json::from_str(&text) {
+            Ok(Value::Array(calls)) => {
+                let mut local = Vec::new();
+                let mut forwarded = Vec::new();
+                for call in calls {
+                    match self.answer_call(&call) {
+                        Some(answer) => local.push(answer),
+                        None => forwarded.push(call),
+                    }
+                }
+                if local.is_empty() {
+                    Answer::Backend(text, local)
+                } else if forwarded.is_empty() {
+                    Answer::Local(Value::Array(local))
+                } else {
+                    Answer::Backend(Value::Array(forwarded).to_string(), local)
+                }
+            }
+            Ok(call) => match self.answer_call(&call) {
+                Some(answer) => Answer::Local(answer),
+                None => Answer::Backend(text, Vec::new()),
+            },
+            Err(_) => Answer::Backend(text, Vec::new()),
+        }
+    }
+
+    /// Opens or closes a head subscription for a subscribe or unsubscribe call, refusing other
+    /// subscriptions. Other calls are left to the backend.
+    fn answer_call(&mut self, call: &Value) -> Option<Value> {
+        let method = call.get("method")?.as_str()?;
+        let (_, name) = method.split_once('_')?;
+        let id = call.get("id").cloned().unwrap_or(Value::Null);
+        let params = call.get("params");
+        let result = if let Some(kind) = HeadKind::of(method, params) {
+            let subscription = format!("0x{:016x}", rand::random::<u64>());
+            debug!(?kind, %subscription, "Opened emulated head subscription");
+            self.open.insert(subscription.clone(), (kind, None));
+            json!(subscription)
+        } else if name.starts_with("subscribe") {
+            return Some(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {
+                    "code": SUBSCRIPTION_NOT_SUPPORTED_CODE,
+                    "message": format!("Subscription not available over HTTP: {method}"),
+                },
+            }));
+        } else if name.starts_with("unsubscribe") {
+            let subscription = params
+                .and_then(|params| params.get(0))
+                .and_then(Value::as_str);
+            json!(subscription.is_some_and(|subscription| self.open.remove(subscription).is_some()))
+        } else {
+            return None;
+        };
+        Some(json!({"jsonrpc": "2.0", "id": id, "result": result}))
+    }
+
+    /// The heads subscriptions follow that aren't being polled for already, marked as polled
+    /// for.
+    fn start_polls(&mut self) -> Vec<HeadKind> {
+        let kinds: HashSet<HeadKind> = self.open.values().map(|(kind, _)| *kind).collect();
+        kinds
+            .into_iter()
+            .filter(|kind| self.polling.insert(*kind))
+            .collect()
+    }
+
+    /// Notifications of `head` for the subscriptions following `kind` that weren't sent it yet.
+    /// A new subscription is sent the current head at the first poll, then every new one.
+    fn polled(&mut self, kind: HeadKind, marker: &Value, head: &Value) -> Vec<Message> {
+        self.open
+            .iter_mut()
+            .filter(|(_, (followed, sent))| *followed == kind && sent.as_ref() != Some(marker))
+            .map(|(subscription, (_, sent))| {
+                *sent = Some(marker.clone());
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": kind.notification_method(),
+                    "params": {"subscription": subscription, "result": head},
+                });
+                Message::text(notification.to_string())
+            })
+            .collect()
+    }
+}
+
+/// The session's end of a bridge, in place of a backend WebSocket: messages sent to it go to
+/// the bridge, and the bridge's answers and notifications are read from it.
+pub(crate) struct BridgeSocket {
+    to_bridge: mpsc::Sender<Message>,
+    from_bridge: mpsc::Receiver<Message>,
+}
+
+impl Stream for BridgeSocket {
+    type Item = Result<Message, tungstenite::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.from_bridge
+            .poll_next_unpin(cx)
+            .map(|message| message.map(Ok))
+    }
+}
+
+impl Sink<Message> for BridgeSocket {
+    type Error = tungstenite::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.to_bridge.poll_ready_unpin(cx).map_err(bridge_closed)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, message: Message) -> Result<(), Self::Error> {
+        self.to_bridge
+            .start_send_unpin(message)
+            .map_err(bridge_closed)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.to_bridge.poll_flush_unpin(cx).map_err(bridge_closed)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.to_bridge.poll_close_unpin(cx).map_err(bridge_closed)
+    }
+}
+
+fn bridge_closed(_: mpsc::SendError) -> tungstenite::Error {
+    tungstenite::Error::ConnectionClosed
+}
+
+/// Error responses for every call of `request`, which the backend didn't answer.
+fn backend_error_payload(request: &str, reason: &str) -> String {
+    let error_for = |call: &Value| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": call.get("id").cloned().unwrap_or(Value::Null),
+            "error": {
+                "code": BACKEND_ERROR_CODE,
+                "message": format!("Backend request failed: {reason}"),
+            },
+        })
+    };
+    match serde_json::from_str(request) {
+        Ok(Value::Array(calls)) => Value::Array(calls.iter().map(error_for).collect()).to_string(),
+        Ok(call) => error_for(&call).to_string(),
+        Err(_) => error_for(&Value::Null).to_string(),
+    }
+}
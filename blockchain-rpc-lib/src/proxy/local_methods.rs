@@ -0,0 +1,91 @@
+use super::{AuthenticatedAccount, RpcGatewayState};
+use crate::plans::ActivePlan;
+use axum::extract::ConnectInfo;
+use axum::http::request::Parts;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::net::SocketAddr;
+
+/// What `gateway_info` reports about the gateway and the caller.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayInfo {
+    pub version: String,
+    /// The route the request was served on, `/` for the default one.
+    pub route: String,
+    pub client_ip: Option<String>,
+    /// The account the request authenticated as, if any.
+    pub account: Option<String>,
+    /// The plan the request is served under, if a plan grant covers it.
+    pub plan: Option<String>,
+    /// When the account's temporary access ends.
+    pub access_expires_at: Option<DateTime<Utc>>,
+    /// Rate-limit tokens left after this request, from the plan's limit if it has one, else
+    /// the per-IP limit. Absent when no rate limit applies.
+    pub rate_limit_remaining: Option<u32>,
+    /// Calls left in the grant's request quota, if its plan has one.
+    pub quota_remaining: Option<u64>,
+}
+
+/// Answers a single call to a gateway-local method, `gateway_ping` or `gateway_info`, without
+/// the backend, so integrators can check their credentials and connectivity for free. Returns
+/// `None` for any other call.
+pub(crate) fn answer(
+    state: &RpcGatewayState,
+    parts: &Parts,
+    active_plan: Option<&ActivePlan>,
+    body: &[u8],
+) -> Option<Vec<u8>> {
+    let Ok(Value::Object(call)) = serde_json::from_slice::<Value>(body) else {
+        return None;
+    };
+    let result = match call.get("method").and_then(Value::as_str)? {
+        "gateway_ping" => json!("pong"),
+        "gateway_info" => serde_json::to_value(gateway_info(state, parts, active_plan)).ok()?,
+        _ => return None,
+    };
+    let response = json!({
+        "jsonrpc": "2.0",
+        "id": call.get("id").cloned().unwrap_or(Value::Null),
+        "result": result,
+    });
+    Some(response.to_string().into_bytes())
+}
+
+fn gateway_info(
+    state: &RpcGatewayState,
+    parts: &Parts,
+    active_plan: Option<&ActivePlan>,
+) -> GatewayInfo {
+    let client_ip = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let account = parts
+        .extensions
+        .get::<AuthenticatedAccount>()
+        .map(|AuthenticatedAccount(account)| account);
+    let plan_remaining =
+        active_plan.and_then(|active| active.plan.rate_limit_remaining(&active.holder));
+    let rate_limit_remaining = match (plan_remaining, client_ip) {
+        (Some(remaining), _) => Some(remaining),
+        (None, Some(client_ip)) => state.ctx.firewall.rate_limit_remaining(&client_ip),
+        (None, None) => None,
+    };
+    GatewayInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        route: state.route.name().to_string(),
+        client_ip: client_ip.map(|ip| ip.to_string()),
+        account: account.map(ToString::to_string),
+        plan: active_plan.map(|active| active.plan.id.clone()),
+        access_expires_at: account.and_then(|account| {
+            state
+                .ctx
+                .firewall
+                .account_status(account)
+                .temporary_expires_at
+        }),
+        rate_limit_remaining,
+        quota_remaining: active_plan.and_then(|active| active.plan.quota_remaining(active)),
+    }
+}
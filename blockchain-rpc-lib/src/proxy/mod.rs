@@ -0,0 +1,789 @@
+pub(crate) mod grpc;
+pub(crate) mod http;
+pub(crate) mod http_bridge;
+pub mod local_methods;
+pub(crate) mod outbound;
+pub(crate) mod pool;
+pub(crate) mod routing;
+pub(crate) mod upstream;
+pub(crate) mod ws;
+
+use crate::access_log::AccessDetails;
+use crate::auto_ban::Offense;
+use crate::client_certs::ClientCertificate;
+use crate::config::{HeadRequests, IdentitySource, RpcConfig};
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::fingerprint::ClientFingerprint;
+use crate::firewall::{AccessDecision, AccessSurface, network_contains, retry_after_secs};
+use crate::forwarded::{ProxiedAddr, client_addr};
+use crate::labels::format_labels;
+use crate::metering::UsageSubject;
+use crate::plans::{ActivePlan, QuotaCharge};
+use crate::ratelimit::RateLimiter;
+use crate::read_only::ReadOnlyStatus;
+use crate::request_id::RequestId;
+use crate::signature_auth::SIGNATURE_HEADER;
+use crate::telemetry;
+use crate::tls::TlsClientInfo;
+use crate::trace_context::{TraceContext, strip_trace_headers};
+use crate::upload::UploadStatus;
+use axum::{
+    Extension,
+    body::Body,
+    extract::{ConnectInfo, State, ws::WebSocketUpgrade},
+    http::{
+        HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode,
+        header::{ALLOW, AUTHORIZATION, CONNECTION, RETRY_AFTER, UPGRADE},
+    },
+    response::{IntoResponse, Response},
+};
+use http_body_util::BodyExt;
+use parking_lot::Mutex;
+use routing::Route;
+use sp_runtime::AccountId32;
+use std::fmt;
+use std::hash::Hash;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{Span, debug, warn};
+use upstream::AffinityKey;
+
+/// A request being proxied, and the route it is proxied along.
+#[derive(Clone)]
+pub(crate) struct RpcGatewayState {
+    pub(crate) ctx: Arc<SecureRpcContext>,
+    pub(crate) route: Arc<Route>,
+}
+
+/// Response header with the burst of the rate-limit bucket the request was charged against.
+pub const RATE_LIMIT_LIMIT_HEADER: &str = "x-ratelimit-limit";
+/// Response header with the whole tokens left in that bucket.
+pub const RATE_LIMIT_REMAINING_HEADER: &str = "x-ratelimit-remaining";
+/// Response header with the seconds until that bucket is full again, or on `429` until the
+/// request can be retried.
+pub const RATE_LIMIT_RESET_HEADER: &str = "x-ratelimit-reset";
+/// Response header with the calls left in the grant's plan quota.
+pub const QUOTA_REMAINING_HEADER: &str = "x-quota-remaining";
+
+/// What the gateway answers `OPTIONS` and local `HEAD` requests with.
+const ALLOWED_METHODS: &str = "GET, HEAD, POST, OPTIONS";
+
+/// Main handler for both HTTP and WebSocket upgrade requests, proxied to the backend of the
+/// route their path matches.
+pub(crate) async fn rpc_handler(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    ws: Option<WebSocketUpgrade>,
+    tls_info: Option<Extension<TlsClientInfo>>,
+    proxied: Option<Extension<ProxiedAddr>>,
+    headers: HeaderMap,
+    mut req: Request<Body>,
+) -> Result<Response, Error> {
+    let route = ctx.routes.resolve(req.uri().path());
+    let state = RpcGatewayState { ctx, route };
+
+    // Behind trusted load balancers, every check applies to the client they forwarded for.
+    let peer = proxied
+        .and_then(|Extension(ProxiedAddr(source))| source)
+        .unwrap_or(peer);
+    let addr = client_addr(peer, &headers, &state.ctx.config().rpc.trusted_proxies);
+    req.extensions_mut().insert(ConnectInfo(addr));
+    let access = req.extensions().get::<AccessDetails>().cloned();
+    if let Some(access) = &access {
+        access.set_client(addr.ip(), state.route.name());
+    }
+    debug!(
+        client_ip = %addr.ip(),
+        method = %req.method(),
+        uri = %req.uri(),
+        route = state.route.name(),
+        "Received request"
+    );
+
+    if let Some(reason) = header_limit_violation(&headers, &state.ctx.config().rpc) {
+        warn!(client_ip = %addr.ip(), %reason, "Rejected request with oversized headers");
+        state
+            .ctx
+            .firewall
+            .record_offense(&addr.ip(), Offense::Malformed)
+            .await;
+        return Ok((StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE, reason).into_response());
+    }
+    // Preflights are answered by the CORS layer; any other `OPTIONS` is answered here, since
+    // JSON-RPC backends mishandle it.
+    if req.method() == Method::OPTIONS {
+        return Ok((StatusCode::NO_CONTENT, [(ALLOW, ALLOWED_METHODS)]).into_response());
+    }
+    strip_oversized_headers(
+        req.headers_mut(),
+        state.ctx.config().rpc.max_forwarded_header_bytes,
+    );
+
+    // Continue distributed traces started by trusted callers; drop trace headers from anyone else.
+    let trusted_trace_source = state
+        .ctx
+        .config()
+        .rpc
+        .trusted_trace_sources
+        .iter()
+        .any(|net| network_contains(net, addr.ip()));
+    let trace_context = trusted_trace_source
+        .then(|| TraceContext::from_headers(&headers))
+        .flatten();
+    match &trace_context {
+        Some(trace_context) => {
+            Span::current().record("trace_id", trace_context.trace_id());
+            telemetry::set_parent_from_headers(&Span::current(), &headers);
+        }
+        None => strip_trace_headers(req.headers_mut()),
+    }
+    // With OTLP export enabled, the upstream sees the gateway's span as its parent.
+    telemetry::inject_current_context(req.headers_mut());
+    let trace_context = TraceContext::from_headers(req.headers());
+    let request_id = req.extensions().get::<RequestId>().cloned();
+
+    let tls_info = tls_info.map(|Extension(info)| info).unwrap_or_default();
+    let fingerprint = state.ctx.config().fingerprint.enabled.then(|| {
+        let fingerprint = ClientFingerprint::new(tls_info.ja3.as_deref(), &headers);
+        Span::current().record("fingerprint", fingerprint.as_str());
+        state.ctx.fingerprints.record(&fingerprint, addr.ip());
+        fingerprint
+    });
+    let origin_heatmap = state.ctx.config().origin_heatmap.enabled;
+    if origin_heatmap {
+        state.ctx.origin_heatmap.record(addr.ip());
+    }
+    if let Some(daily_digest) = &state.ctx.daily_digest {
+        daily_digest.record_request(addr.ip());
+    }
+    let record_rejection = || {
+        if let Some(fingerprint) = &fingerprint {
+            state.ctx.fingerprints.record_rejection(fingerprint);
+        }
+        if origin_heatmap {
+            state.ctx.origin_heatmap.record_denial(addr.ip());
+        }
+        if let Some(daily_digest) = &state.ctx.daily_digest {
+            daily_digest.record_denial();
+        }
+    };
+
+    // Signatures cover the body, so signed requests are read in full before they're verified.
+    let signed_body = if headers.contains_key(SIGNATURE_HEADER) {
+        let (parts, body) = req.into_parts();
+        let upload = parts.extensions.get::<UploadStatus>().cloned();
+        let bytes = match body.collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(e) => {
+                if let Some(failure) = upload.as_ref().and_then(UploadStatus::failure) {
+                    warn!(error = %failure, "Rejected signed request body");
+                    return Ok(failure.response());
+                }
+                warn!(error = %e, "Failed to read signed request body");
+                return Ok((StatusCode::BAD_REQUEST, "Failed to read request body").into_response());
+            }
+        };
+        req = Request::from_parts(parts, Body::from(bytes.clone()));
+        Some(bytes)
+    } else {
+        None
+    };
+
+    // --- Firewall Check ---
+    // Requests authenticated as an account are authorized by the account's rules, all others by IP.
+    let account = match resolve_account(
+        &state,
+        tls_info.certificate.as_ref(),
+        &headers,
+        req.method(),
+        req.uri()
+            .path_and_query()
+            .map_or(req.uri().path(), |target| target.as_str()),
+        signed_body.as_deref().unwrap_or_default(),
+    ) {
+        Ok(account) => account,
+        Err(reason) => {
+            warn!(client_ip = %addr.ip(), %reason, "Rejected request with invalid credentials");
+            record_rejection();
+            state
+                .ctx
+                .firewall
+                .record_offense(&addr.ip(), Offense::Malformed)
+                .await;
+            return Ok((StatusCode::UNAUTHORIZED, reason).into_response());
+        }
+    };
+    if let (Some(access), Some(account)) = (&access, &account) {
+        access.set_account(account);
+    }
+    if let Some(labels) = account
+        .as_ref()
+        .and_then(|account| state.ctx.account_labels.get(account))
+    {
+        Span::current().record("account_labels", format_labels(&labels).as_str());
+    }
+    let surface = if ws.is_some() {
+        AccessSurface::WebSocket
+    } else {
+        AccessSurface::Http
+    };
+    match state
+        .ctx
+        .firewall
+        .check_access(&addr.ip(), account.as_ref(), surface)
+        .await
+    {
+        AccessDecision::Allowed => {}
+        // Over the decision budget and failing closed; not the client's fault
+        AccessDecision::Unavailable => {
+            return Ok((
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(RETRY_AFTER, "1")],
+                "Access check unavailable",
+            )
+                .into_response());
+        }
+        AccessDecision::Denied => {
+            warn!(client_ip = %addr.ip(), "Blocked request due to firewall rules");
+            record_rejection();
+            state
+                .ctx
+                .firewall
+                .record_offense(&addr.ip(), Offense::Denied)
+                .await;
+            return Ok((StatusCode::FORBIDDEN, "Access Denied").into_response());
+        }
+    }
+    if req.method() == Method::HEAD {
+        match state.ctx.config().rpc.head_requests {
+            HeadRequests::Local => {
+                debug!(client_ip = %addr.ip(), "Answered HEAD request");
+                return Ok((StatusCode::OK, [(ALLOW, ALLOWED_METHODS)]).into_response());
+            }
+            // The response body is dropped on the way out, as for any `HEAD` request.
+            HeadRequests::Get => *req.method_mut() = Method::GET,
+        }
+    }
+
+    // Grants on a plan with its own rate limit are limited per grant instead of per IP.
+    let active_plan = state
+        .ctx
+        .firewall
+        .active_plan(&addr.ip(), account.as_ref())
+        .and_then(|(holder, plan, grant)| {
+            Some(ActivePlan {
+                plan: state.ctx.plans.get(&plan)?,
+                holder,
+                granted_at: grant.granted_at,
+                expires_at: grant.expires_at,
+                extensions: grant.extensions,
+            })
+        });
+    let burst_loan = BurstLoan(state.ctx.firewall.burst_loan(account.as_ref()));
+    let limits = LimitStatus::default();
+    if let Some(retry_after) = check_rate_limit(
+        &state.ctx,
+        addr.ip(),
+        active_plan.as_ref(),
+        burst_loan,
+        &limits,
+        1,
+    )
+    .await
+    {
+        warn!(client_ip = %addr.ip(), "Rate limited request");
+        record_rejection();
+        return Ok(limits.apply(rate_limited_response(retry_after)));
+    }
+    // WebSocket sessions are capped by the session registry instead.
+    let in_flight_slot = match (&account, ws.is_none()) {
+        (Some(account), true) => {
+            let max = active_plan
+                .as_ref()
+                .and_then(|active| active.plan.max_in_flight_requests)
+                .or(state.ctx.config().rpc.max_in_flight_requests_per_account);
+            match max {
+                Some(max) => match state.ctx.in_flight.acquire(account, max) {
+                    Some(slot) => Some(slot),
+                    None => {
+                        warn!(client_ip = %addr.ip(), %account, max, "Rejected request over the account's in-flight limit");
+                        record_rejection();
+                        return Ok(limits.apply(
+                            (
+                                StatusCode::TOO_MANY_REQUESTS,
+                                "Too many concurrent requests",
+                            )
+                                .into_response(),
+                        ));
+                    }
+                },
+                None => None,
+            }
+        }
+        _ => None,
+    };
+    if !charge_quota(&state.ctx, active_plan.as_ref(), &limits, 1).await {
+        warn!(client_ip = %addr.ip(), "Rejected request over its plan's quota");
+        return Ok(limits.apply(quota_exhausted_response()));
+    }
+
+    // gRPC calls go to their own backend, which chain verification doesn't cover.
+    let grpc = state.ctx.grpc.clone().filter(|_| grpc::is_grpc(&headers));
+
+    // Never proxy to a backend that hasn't proven it is on the configured chain.
+    if grpc.is_none()
+        && state
+            .route
+            .probe
+            .as_ref()
+            .is_some_and(|probe| !probe.is_chain_verified())
+    {
+        warn!(client_ip = %addr.ip(), "Rejected request while the backend chain is unverified");
+        return Ok((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Backend chain not verified",
+        )
+            .into_response());
+    }
+
+    let billed_account = state
+        .ctx
+        .config()
+        .firewall
+        .identity
+        .billed_account(account.as_ref())
+        .cloned();
+    let usage_subject = UsageSubject::new(addr.ip(), billed_account.as_ref());
+    if let Some(meter) = &state.ctx.usage_meter {
+        meter.record_request(&usage_subject);
+    }
+
+    // --- Transaction Relay ---
+    if let Some(relay) = state
+        .ctx
+        .relay
+        .clone()
+        .filter(|relay| relay.serves(req.uri().path()))
+    {
+        debug!(client_ip = %addr.ip(), "Handling relay request");
+        let response = relay
+            .handle(account.as_ref(), state.ctx.audit.as_deref(), req)
+            .await;
+        return Ok(limits.apply(response));
+    }
+
+    if let Some(grpc) = grpc {
+        debug!(client_ip = %addr.ip(), "Proxying gRPC call");
+        let response = limits.apply(grpc::proxy_grpc_request(state, &grpc, req).await);
+        return Ok(match in_flight_slot {
+            Some(slot) => response.map(|body| slot.hold_until_sent(body)),
+            None => response,
+        });
+    }
+
+    // --- WebSocket Handling ---
+    if let Some(ws) = ws {
+        // Check if it's a WebSocket upgrade request
+        if headers.contains_key(UPGRADE) && headers.contains_key(CONNECTION) {
+            // TODO CHECK header value properly
+            debug!(client_ip = %addr.ip(), "Handling WebSocket upgrade request");
+            return Ok(limits.apply(ws.on_upgrade(move |socket| {
+                ws::handle_websocket(
+                    socket,
+                    state,
+                    trace_context,
+                    request_id,
+                    account,
+                    active_plan,
+                    addr,
+                )
+            })));
+        }
+    }
+
+    // --- HTTP Proxy Handling ---
+    debug!(client_ip = %addr.ip(), "Proxying HTTP request");
+    if let Some(active_plan) = active_plan {
+        req.extensions_mut().insert(active_plan);
+    }
+    req.extensions_mut().insert(burst_loan);
+    req.extensions_mut().insert(limits.clone());
+    if let Some(account) = &account {
+        req.extensions_mut()
+            .insert(AuthenticatedAccount(account.clone()));
+    }
+    req.extensions_mut()
+        .insert(AffinityKey::new(addr.ip(), account.as_ref()));
+    let response = match state.ctx.usage_meter.clone() {
+        Some(meter) => {
+            req.extensions_mut().insert(usage_subject.clone());
+            let req = req.map(|body| meter.count_request_body(usage_subject.clone(), body));
+            let response = http::proxy_http_request(state, req).await?;
+            response.map(|body| meter.count_response_body(usage_subject, body))
+        }
+        None => http::proxy_http_request(state, req).await?,
+    };
+    // Batches charge their extra calls while being proxied, so the status is complete only now.
+    let response = limits.apply(response);
+    Ok(match in_flight_slot {
+        Some(slot) => response.map(|body| slot.hold_until_sent(body)),
+        None => response,
+    })
+}
+
+/// The account an HTTP request authenticated as, attached to the request.
+#[derive(Debug, Clone)]
+pub(crate) struct AuthenticatedAccount(pub(crate) AccountId32);
+
+/// Rate-limit tokens the client may borrow beyond an empty bucket, attached to the request.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BurstLoan(pub(crate) u32);
+
+/// The rate-limit bucket and plan quota a request was last charged against, attached to the
+/// request and returned to the client in the `x-ratelimit-*` and `x-quota-remaining` headers so
+/// it can pace itself.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LimitStatus(Arc<Mutex<LimitState>>);
+
+#[derive(Debug, Default)]
+struct LimitState {
+    /// The bucket's burst, whole tokens left, and seconds until it is full again.
+    rate_limit: Option<(u32, u32, Option<u64>)>,
+    quota_remaining: Option<u64>,
+}
+
+impl LimitStatus {
+    fn record_tokens<K: Hash + Eq>(&self, limiter: &RateLimiter<K>, remaining: u32) {
+        let rate = limiter.requests_per_sec();
+        let reset_secs = (rate > 0.0)
+            .then(|| ((limiter.burst() - f64::from(remaining)).max(0.0) / rate).ceil() as u64);
+        self.0.lock().rate_limit = Some((limiter.burst() as u32, remaining, reset_secs));
+    }
+
+    fn record_rate_limited<K: Hash + Eq>(&self, limiter: &RateLimiter<K>, retry_after: Duration) {
+        self.0.lock().rate_limit = Some((
+            limiter.burst() as u32,
+            0,
+            Some(retry_after_secs(retry_after)),
+        ));
+    }
+
+    fn record_quota(&self, charge: &QuotaCharge) {
+        self.0.lock().quota_remaining = Some(charge.quota.saturating_sub(charge.used));
+    }
+
+    /// Adds the headers for the limits recorded so far to `response`.
+    fn apply(&self, mut response: Response) -> Response {
+        let state = self.0.lock();
+        let headers = response.headers_mut();
+        if let Some((limit, remaining, reset_secs)) = state.rate_limit {
+            headers.insert(RATE_LIMIT_LIMIT_HEADER, HeaderValue::from(limit));
+            headers.insert(RATE_LIMIT_REMAINING_HEADER, HeaderValue::from(remaining));
+            if let Some(reset_secs) = reset_secs {
+                headers.insert(RATE_LIMIT_RESET_HEADER, HeaderValue::from(reset_secs));
+            }
+        }
+        if let Some(remaining) = state.quota_remaining {
+            headers.insert(QUOTA_REMAINING_HEADER, HeaderValue::from(remaining));
+        }
+        response
+    }
+}
+
+/// Consumes `calls` rate-limit tokens: from the grant's bucket if its plan has its own rate
+/// limit, otherwise from the client IP's, recording the bucket's state in `limits`. Returns how
+/// long to wait if they aren't available, counting the hit towards an automatic ban of the
+/// client IP.
+async fn check_rate_limit(
+    ctx: &SecureRpcContext,
+    client_ip: IpAddr,
+    active_plan: Option<&ActivePlan>,
+    BurstLoan(loan): BurstLoan,
+    limits: &LimitStatus,
+    calls: u32,
+) -> Option<Duration> {
+    let plan_limiter = active_plan.and_then(|active| Some((active, active.plan.rate_limiter()?)));
+    let (source, retry_after) = match plan_limiter {
+        Some((active, limiter)) => {
+            let scope = format!("plan:{}", active.plan.id);
+            let holder = active.holder.clone();
+            match take_tokens(ctx, &scope, limiter, holder.clone(), calls, loan).await {
+                Ok(remaining) => {
+                    limits.record_tokens(limiter, remaining);
+                    return None;
+                }
+                Err(retry_after) => {
+                    limits.record_rate_limited(limiter, retry_after);
+                    (holder, retry_after)
+                }
+            }
+        }
+        None => {
+            let limiter = ctx.firewall.rate_limiter()?;
+            match take_tokens(ctx, "ip", &limiter, client_ip, calls, loan).await {
+                Ok(remaining) => {
+                    limits.record_tokens(&limiter, remaining);
+                    return None;
+                }
+                Err(retry_after) => {
+                    limits.record_rate_limited(&limiter, retry_after);
+                    (client_ip.to_string(), retry_after)
+                }
+            }
+        }
+    };
+    ctx.firewall.report_rate_limited(source, retry_after).await;
+    ctx.firewall
+        .record_offense(&client_ip, Offense::RateLimited)
+        .await;
+    Some(retry_after)
+}
+
+/// Takes `calls` tokens from `key`'s bucket in `limiter`, or from its bucket under `scope` on
+/// the Redis server if `[storage] shared_rate_limits` is set. Returns the whole tokens left.
+async fn take_tokens<K: Hash + Eq + Clone + fmt::Display>(
+    ctx: &SecureRpcContext,
+    scope: &str,
+    limiter: &RateLimiter<K>,
+    key: K,
+    calls: u32,
+    loan: u32,
+) -> Result<u32, Duration> {
+    #[cfg(feature = "redis")]
+    if let Some(shared) = &ctx.shared_rate_limits {
+        return shared
+            .check_with_loan(scope, limiter, key, calls, loan)
+            .await;
+    }
+    #[cfg(not(feature = "redis"))]
+    let _ = (ctx, scope);
+    limiter.check_with_loan(key.clone(), calls, loan)?;
+    Ok(limiter.remaining(&key))
+}
+
+/// Charges `calls` against the grant's request quota, if its plan has one, reporting any
+/// warning threshold crossed and recording what is left in `limits`. Returns false if they
+/// would exceed the quota.
+async fn charge_quota(
+    ctx: &SecureRpcContext,
+    active_plan: Option<&ActivePlan>,
+    limits: &LimitStatus,
+    calls: u32,
+) -> bool {
+    let Some((active, charge)) =
+        active_plan.and_then(|active| Some((active, active.plan.charge_quota(active, calls)?)))
+    else {
+        return true;
+    };
+    limits.record_quota(&charge);
+    if let Some(percent) = charge.crossed_percent {
+        ctx.firewall
+            .report_quota_threshold(
+                active.holder.clone(),
+                &active.plan.id,
+                percent,
+                charge.used,
+                charge.quota,
+            )
+            .await;
+    }
+    if let Some(daily_digest) = ctx.daily_digest.as_ref().filter(|_| charge.exhausted) {
+        daily_digest.record_quota_exhausted();
+    }
+    !charge.exhausted
+}
+
+fn quota_exhausted_response() -> Response {
+    (StatusCode::PAYMENT_REQUIRED, "Request quota exhausted").into_response()
+}
+
+fn rate_limited_response(retry_after: Duration) -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, retry_after_secs(retry_after).to_string())],
+        "Too Many Requests",
+    )
+        .into_response()
+}
+
+/// Resolves the account a request authenticates as, via a mapped client certificate, API key
+/// or sr25519 signature headers, tried in `firewall.identity.order`. Returns `Ok(None)` for
+/// anonymous requests, which fall back to IP-based checks. `path_and_query` and `body` are the
+/// target and body of a signed request.
+fn resolve_account(
+    state: &RpcGatewayState,
+    client_cert: Option<&ClientCertificate>,
+    headers: &HeaderMap,
+    method: &Method,
+    path_and_query: &str,
+    body: &[u8],
+) -> Result<Option<AccountId32>, String> {
+    let config = state.ctx.config();
+    let client_auth = config
+        .rpc
+        .tls
+        .as_ref()
+        .and_then(|tls| tls.client_auth.as_ref());
+    for source in &config.firewall.identity.order {
+        let resolved = match source {
+            IdentitySource::ClientCert => match (client_cert, client_auth) {
+                (Some(cert), Some(client_auth)) => {
+                    let account = cert.account(client_auth);
+                    if account.is_none() {
+                        debug!(fingerprint = %cert.fingerprint, "Client certificate isn't mapped to an account");
+                    }
+                    account.map(Ok)
+                }
+                _ => None,
+            },
+            IdentitySource::ApiKey => bearer_token(headers).map(|key| {
+                state
+                    .ctx
+                    .api_keys
+                    .resolve(key)
+                    .ok_or_else(|| "Invalid API key".to_string())
+            }),
+            IdentitySource::Signature => state
+                .ctx
+                .signature_auth
+                .verify(headers, method, path_and_query, body)
+                .map(|result| result.map_err(|e| e.to_string())),
+        };
+        if let Some(resolved) = resolved {
+            return resolved.map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// Checks inbound headers against the configured count and total size limits.
+fn header_limit_violation(headers: &HeaderMap, config: &RpcConfig) -> Option<String> {
+    if headers.len() > config.max_header_count {
+        return Some(format!(
+            "Too many headers: {} (max {})",
+            headers.len(),
+            config.max_header_count
+        ));
+    }
+    let total_bytes: usize = headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum();
+    if total_bytes > config.max_header_bytes {
+        return Some(format!(
+            "Headers too large: {} bytes (max {})",
+            total_bytes, config.max_header_bytes
+        ));
+    }
+    None
+}
+
+/// Drops headers whose value is longer than `max_value_bytes`, so they aren't forwarded.
+fn strip_oversized_headers(headers: &mut HeaderMap, max_value_bytes: usize) {
+    let oversized: Vec<HeaderName> = headers
+        .iter()
+        .filter(|(_, value)| value.len() > max_value_bytes)
+        .map(|(name, _)| name.clone())
+        .collect();
+    for name in oversized {
+        debug!(header = %name, "Dropping oversized header");
+        headers.remove(&name);
+    }
+}
+
+/// Extracts the key from an `Authorization: Bearer <key>` header, if present.
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Builds the JSON-RPC error returned for a call to a method the backend doesn't support.
+fn method_not_supported_payload(method: &str) -> String {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": {
+            "code": -32601,
+            "message": format!("Method not supported by the backend: {method}"),
+        },
+    })
+    .to_string()
+}
+
+/// Builds the JSON-RPC error returned for a request rejected by a payload filter.
+fn filter_rejected_payload(reason: &str) -> String {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": {
+            "code": -32600,
+            "message": format!("Request rejected: {reason}"),
+        },
+    })
+    .to_string()
+}
+
+/// Builds the JSON-RPC error returned for a batch with more calls than allowed.
+fn batch_too_large_payload(calls: usize, max: usize) -> String {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": {
+            "code": -32600,
+            "message": format!("Batch too large: {calls} calls (max {max})"),
+        },
+    })
+    .to_string()
+}
+
+/// Builds the JSON-RPC error returned when a call or its response exceeds a `[limits]` limit.
+fn limit_exceeded_payload(reason: &str) -> String {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": {
+            "code": -32005,
+            "message": reason,
+        },
+    })
+    .to_string()
+}
+
+/// Builds the JSON-RPC error returned for a call that would change state while the gateway is
+/// read-only.
+fn read_only_payload(method: &str, status: &ReadOnlyStatus) -> String {
+    let reason = status
+        .reason
+        .as_deref()
+        .map(|reason| format!(": {reason}"))
+        .unwrap_or_default();
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": {
+            "code": -32003,
+            "message": format!("Gateway is read-only, {method} is unavailable{reason}"),
+            "data": {"read_only_since": status.since},
+        },
+    })
+    .to_string()
+}
+
+/// Builds the JSON-RPC error returned when a call is rejected by the method filter.
+fn method_not_allowed_payload(method: &str) -> String {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": {
+            "code": -32601,
+            "message": format!("Method not allowed: {method}"),
+        },
+    })
+    .to_string()
+}
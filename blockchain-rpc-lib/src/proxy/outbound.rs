@@ -0,0 +1,333 @@
+use crate::Result;
+use crate::config::OutboundProxyConfig;
+use axum::http::Uri;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioIo;
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, lookup_host};
+use tower_service::Service;
+
+/// Longest response header to a `CONNECT` accepted from an HTTP proxy.
+const MAX_CONNECT_RESPONSE_BYTES: usize = 8 * 1024;
+
+const SOCKS_VERSION: u8 = 5;
+const SOCKS_NO_AUTH: u8 = 0;
+const SOCKS_USERNAME_PASSWORD: u8 = 2;
+const SOCKS_CONNECT: u8 = 1;
+const SOCKS_IPV4: u8 = 1;
+const SOCKS_DOMAIN: u8 = 3;
+const SOCKS_IPV6: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    /// HTTP `CONNECT`.
+    Http,
+    /// SOCKS5, with backend hostnames resolved by the proxy when `remote_dns` is set, and by
+    /// the gateway otherwise.
+    Socks5 { remote_dns: bool },
+}
+
+/// The `[rpc.outbound_proxy]` connections to backends are tunneled through.
+pub(crate) struct OutboundProxy {
+    protocol: Protocol,
+    /// The proxy's `host:port`.
+    addr: String,
+    credentials: Option<(String, String)>,
+}
+
+impl OutboundProxy {
+    pub(crate) fn new(config: &OutboundProxyConfig) -> Result<Self> {
+        config.validate()?;
+        let protocol = match config.url.scheme() {
+            "http" => Protocol::Http,
+            scheme => Protocol::Socks5 {
+                remote_dns: scheme == "socks5h",
+            },
+        };
+        let port = config.url.port_or_known_default().unwrap_or(1080);
+        Ok(OutboundProxy {
+            protocol,
+            addr: format!("{}:{port}", config.url.host_str().unwrap_or_default()),
+            credentials: config
+                .username
+                .clone()
+                .map(|username| (username, config.password.clone().unwrap_or_default())),
+        })
+    }
+
+    /// Opens a TCP connection to `target` (`host:port`, IPv6 hosts in brackets) through the
+    /// proxy. Once this returns, the stream carries bytes to and from `target` unchanged.
+    pub(crate) async fn connect(&self, target: &str) -> io::Result<TcpStream> {
+        let mut stream = TcpStream::connect(&self.addr).await.map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Failed to connect to outbound proxy {}: {e}", self.addr),
+            )
+        })?;
+        stream.set_nodelay(true)?;
+        match self.protocol {
+            Protocol::Http => self.http_connect(&mut stream, target).await?,
+            Protocol::Socks5 { remote_dns } => {
+                self.socks5_connect(&mut stream, target, remote_dns).await?
+            }
+        }
+        Ok(stream)
+    }
+
+    async fn http_connect(&self, stream: &mut TcpStream, target: &str) -> io::Result<()> {
+        let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+        if let Some((username, password)) = &self.credentials {
+            let credentials = BASE64.encode(format!("{username}:{password}"));
+            request.push_str(&format!("Proxy-Authorization: Basic {credentials}\r\n"));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        // Read a byte at a time, so nothing past the header, which belongs to the tunnel, is
+        // consumed
+        let mut response = Vec::new();
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() >= MAX_CONNECT_RESPONSE_BYTES {
+                return Err(invalid_data(
+                    "Outbound proxy sent an oversized CONNECT response",
+                ));
+            }
+            response.push(stream.read_u8().await?);
+        }
+        let response = String::from_utf8_lossy(&response);
+        let status_line = response.lines().next().unwrap_or_default();
+        let accepted = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse::<u16>().ok())
+            .is_some_and(|status| (200..300).contains(&status));
+        if !accepted {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("Outbound proxy refused CONNECT to {target}: {status_line}"),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn socks5_connect(
+        &self,
+        stream: &mut TcpStream,
+        target: &str,
+        remote_dns: bool,
+    ) -> io::Result<()> {
+        let (host, port) = split_target(target)?;
+
+        let methods: &[u8] = match self.credentials {
+            Some(_) => &[SOCKS_NO_AUTH, SOCKS_USERNAME_PASSWORD],
+            None => &[SOCKS_NO_AUTH],
+        };
+        let mut greeting = vec![SOCKS_VERSION, methods.len() as u8];
+        greeting.extend_from_slice(methods);
+        stream.write_all(&greeting).await?;
+        let mut choice = [0; 2];
+        stream.read_exact(&mut choice).await?;
+        if choice[0] != SOCKS_VERSION {
+            return Err(invalid_data("Outbound proxy is not a SOCKS5 proxy"));
+        }
+        match choice[1] {
+            SOCKS_NO_AUTH => {}
+            SOCKS_USERNAME_PASSWORD => self.socks5_authenticate(stream).await?,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "SOCKS5 proxy accepts none of the offered authentication methods",
+                ));
+            }
+        }
+
+        let mut request = vec![SOCKS_VERSION, SOCKS_CONNECT, 0];
+        let ip = match host.parse::<IpAddr>() {
+            Ok(ip) => Some(ip),
+            Err(_) if remote_dns => None,
+            Err(_) => Some(
+                lookup_host((host, port))
+                    .await?
+                    .next()
+                    .ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::NotFound, format!("{host} did not resolve"))
+                    })?
+                    .ip(),
+            ),
+        };
+        match ip {
+            Some(IpAddr::V4(ip)) => {
+                request.push(SOCKS_IPV4);
+                request.extend_from_slice(&ip.octets());
+            }
+            Some(IpAddr::V6(ip)) => {
+                request.push(SOCKS_IPV6);
+                request.extend_from_slice(&ip.octets());
+            }
+            None => {
+                let len = u8::try_from(host.len()).map_err(|_| {
+                    invalid_input(format!("Hostname {host} is too long for SOCKS5"))
+                })?;
+                request.push(SOCKS_DOMAIN);
+                request.push(len);
+                request.extend_from_slice(host.as_bytes());
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        let mut reply = [0; 4];
+        stream.read_exact(&mut reply).await?;
+        if reply[0] != SOCKS_VERSION {
+            return Err(invalid_data("Outbound proxy is not a SOCKS5 proxy"));
+        }
+        if reply[1] != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!(
+                    "SOCKS5 proxy failed to connect to {target}: {}",
+                    socks5_error(reply[1])
+                ),
+            ));
+        }
+        // The address the proxy bound for the connection, unused
+        let bound_len = match reply[3] {
+            SOCKS_IPV4 => 4,
+            SOCKS_IPV6 => 16,
+            SOCKS_DOMAIN => usize::from(stream.read_u8().await?),
+            _ => return Err(invalid_data("SOCKS5 proxy sent an unknown address type")),
+        };
+        let mut bound = vec![0; bound_len + 2];
+        stream.read_exact(&mut bound).await?;
+        Ok(())
+    }
+
+    /// Username/password authentication (RFC 1929), once the proxy has asked for it.
+    async fn socks5_authenticate(&self, stream: &mut TcpStream) -> io::Result<()> {
+        let Some((username, password)) = &self.credentials else {
+            return Err(invalid_data(
+                "SOCKS5 proxy chose authentication that wasn't offered",
+            ));
+        };
+        let too_long = || invalid_input("SOCKS5 credentials are limited to 255 bytes each");
+        let mut request = vec![1, u8::try_from(username.len()).map_err(|_| too_long())?];
+        request.extend_from_slice(username.as_bytes());
+        request.push(u8::try_from(password.len()).map_err(|_| too_long())?);
+        request.extend_from_slice(password.as_bytes());
+        stream.write_all(&request).await?;
+
+        let mut reply = [0; 2];
+        stream.read_exact(&mut reply).await?;
+        if reply[1] != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "SOCKS5 proxy rejected the credentials",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Splits `host:port`, taking IPv6 hosts out of their brackets.
+fn split_target(target: &str) -> io::Result<(&str, u16)> {
+    target
+        .rsplit_once(':')
+        .and_then(|(host, port)| {
+            let host = host.strip_prefix('[').unwrap_or(host);
+            let host = host.strip_suffix(']').unwrap_or(host);
+            Some((host, port.parse().ok()?))
+        })
+        .ok_or_else(|| invalid_input(format!("Invalid connection target {target}")))
+}
+
+fn socks5_error(code: u8) -> &'static str {
+    match code {
+        1 => "general failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn invalid_input(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message.into())
+}
+
+/// The outbound proxy for `reqwest` clients, e.g. those probing backends or delivering
+/// webhooks.
+pub(crate) fn reqwest_proxy(config: &OutboundProxyConfig) -> Result<reqwest::Proxy> {
+    let proxy = reqwest::Proxy::all(config.url.as_str())?;
+    Ok(match &config.username {
+        Some(username) => {
+            proxy.basic_auth(username, config.password.as_deref().unwrap_or_default())
+        }
+        None => proxy,
+    })
+}
+
+/// Connects the HTTP client to backends through the outbound proxy when one is configured,
+/// and directly otherwise.
+#[derive(Clone)]
+pub(crate) struct UpstreamConnector {
+    http: HttpConnector,
+    proxy: Option<Arc<OutboundProxy>>,
+}
+
+impl UpstreamConnector {
+    pub(crate) fn new(http: HttpConnector, proxy: Option<Arc<OutboundProxy>>) -> Self {
+        UpstreamConnector { http, proxy }
+    }
+}
+
+impl Service<Uri> for UpstreamConnector {
+    type Response = TokioIo<TcpStream>;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.proxy {
+            Some(_) => Poll::Ready(Ok(())),
+            None => self.http.poll_ready(cx).map_err(Into::into),
+        }
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let Some(proxy) = self.proxy.clone() else {
+            let connecting = self.http.call(uri);
+            return Box::pin(async move { Ok(connecting.await?) });
+        };
+        Box::pin(async move {
+            let host = uri
+                .host()
+                .ok_or_else(|| invalid_input(format!("Backend URI {uri} has no host")))?;
+            let port = uri
+                .port_u16()
+                .unwrap_or(if uri.scheme_str() == Some("https") {
+                    443
+                } else {
+                    80
+                });
+            Ok(TokioIo::new(
+                proxy.connect(&format!("{host}:{port}")).await?,
+            ))
+        })
+    }
+}
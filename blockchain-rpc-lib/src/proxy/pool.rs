@@ -0,0 +1,223 @@
+use super::outbound::{OutboundProxy, UpstreamConnector};
+use crate::Result;
+use crate::config::{RpcConfig, UpstreamHttpVersion};
+use crate::error::Error;
+use crate::tls::upstream_client_config;
+use axum::body::Body;
+use axum::http::Uri;
+use hyper::rt::{Read, ReadBufCursor, Write};
+use hyper_rustls::{FixedServerNameResolver, HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::{Connected, Connection, HttpConnector};
+use hyper_util::rt::{TokioExecutor, TokioTimer};
+use rustls::pki_types::ServerName;
+use serde::Serialize;
+use std::future::Future;
+use std::io::{self, IoSlice};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// The HTTP client requests are proxied with.
+pub(crate) type HttpClient = Client<CountingConnector<HttpsConnector<UpstreamConnector>>, Body>;
+
+/// Builds the HTTP client for a route's backends from `[rpc.upstream_pool]`: connections are
+/// pooled per backend and kept alive, `https://` backends are reached over TLS with
+/// `[rpc.upstream_tls]`, and HTTP/2 is used as `http_version` says. Connections go through
+/// `[rpc.outbound_proxy]` when it is set.
+pub(crate) fn http_client(config: &RpcConfig, counters: Arc<PoolCounters>) -> Result<HttpClient> {
+    client(config, config.upstream_pool.http_version, counters)
+}
+
+/// Builds the HTTP/2-only client gRPC calls are proxied with, pooled and reaching `https://`
+/// backends like [`http_client`].
+pub(crate) fn grpc_client(config: &RpcConfig, counters: Arc<PoolCounters>) -> Result<HttpClient> {
+    client(config, UpstreamHttpVersion::Http2, counters)
+}
+
+fn client(
+    config: &RpcConfig,
+    http_version: UpstreamHttpVersion,
+    counters: Arc<PoolCounters>,
+) -> Result<HttpClient> {
+    let pool = &config.upstream_pool;
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    http.set_nodelay(true);
+    http.set_keepalive(pool.tcp_keepalive_secs.map(Duration::from_secs));
+    let proxy = match &config.outbound_proxy {
+        Some(proxy) => Some(Arc::new(OutboundProxy::new(proxy)?)),
+        None => None,
+    };
+    let connector = UpstreamConnector::new(http, proxy);
+
+    let tls = Arc::unwrap_or_clone(upstream_client_config(&config.upstream_tls)?);
+    let builder = HttpsConnectorBuilder::new().with_tls_config(tls);
+    let builder = match &config.upstream_tls.server_name {
+        Some(name) => {
+            let server_name = ServerName::try_from(name.clone())
+                .map_err(|e| Error::TlsError(format!("Invalid server name {name}: {e}")))?;
+            builder
+                .https_or_http()
+                .with_server_name_resolver(FixedServerNameResolver::new(server_name))
+        }
+        None => builder.https_or_http(),
+    };
+    let https = match http_version {
+        UpstreamHttpVersion::Auto => builder.enable_all_versions().wrap_connector(connector),
+        UpstreamHttpVersion::Http1 => builder.enable_http1().wrap_connector(connector),
+        UpstreamHttpVersion::Http2 => builder.enable_http2().wrap_connector(connector),
+    };
+
+    let mut client = Client::builder(TokioExecutor::new());
+    client
+        .pool_max_idle_per_host(pool.max_idle_per_host)
+        .pool_idle_timeout(Duration::from_secs(pool.idle_timeout_secs))
+        .pool_timer(TokioTimer::new())
+        .http2_only(http_version == UpstreamHttpVersion::Http2);
+    if let Some(interval) = pool.http2_keep_alive_secs {
+        client
+            .timer(TokioTimer::new())
+            .http2_keep_alive_interval(Duration::from_secs(interval.max(1)))
+            .http2_keep_alive_while_idle(true);
+    }
+    Ok(client.build(CountingConnector {
+        inner: https,
+        counters,
+    }))
+}
+
+/// Connections a route's HTTP client opened to its backends, and requests sent over them.
+#[derive(Debug, Default)]
+pub(crate) struct PoolCounters {
+    open: AtomicU64,
+    opened: AtomicU64,
+    requests: AtomicU64,
+}
+
+/// How well a route's backend connections are reused, since startup.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PoolStats {
+    /// Connections open to the backends, idle in the pool or carrying requests.
+    pub(crate) open_connections: u64,
+    pub(crate) connections_opened: u64,
+    pub(crate) requests: u64,
+    /// Requests per connection opened; well above 1 when connections are reused.
+    pub(crate) requests_per_connection: f64,
+}
+
+impl PoolCounters {
+    pub(crate) fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stats(&self) -> PoolStats {
+        let opened = self.opened.load(Ordering::Relaxed);
+        let requests = self.requests.load(Ordering::Relaxed);
+        PoolStats {
+            open_connections: self.open.load(Ordering::Relaxed),
+            connections_opened: opened,
+            requests,
+            requests_per_connection: if opened == 0 {
+                0.0
+            } else {
+                requests as f64 / opened as f64
+            },
+        }
+    }
+}
+
+/// Counts the connections its inner connector opens, and how many are still open.
+#[derive(Clone)]
+pub(crate) struct CountingConnector<C> {
+    inner: C,
+    counters: Arc<PoolCounters>,
+}
+
+impl<C> tower_service::Service<Uri> for CountingConnector<C>
+where
+    C: tower_service::Service<Uri>,
+    C::Response: Send + 'static,
+    C::Error: 'static,
+    C::Future: Send + 'static,
+{
+    type Response = CountedConnection<C::Response>;
+    type Error = C::Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connecting = self.inner.call(uri);
+        let counters = self.counters.clone();
+        Box::pin(async move {
+            let inner = connecting.await?;
+            counters.opened.fetch_add(1, Ordering::Relaxed);
+            counters.open.fetch_add(1, Ordering::Relaxed);
+            Ok(CountedConnection { inner, counters })
+        })
+    }
+}
+
+/// A backend connection, counted as open until dropped.
+pub(crate) struct CountedConnection<T> {
+    inner: T,
+    counters: Arc<PoolCounters>,
+}
+
+impl<T> Drop for CountedConnection<T> {
+    fn drop(&mut self) {
+        self.counters.open.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl<T: Read + Unpin> Read for CountedConnection<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: Write + Unpin> Write for CountedConnection<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write_vectored(cx, bufs)
+    }
+}
+
+impl<T: Connection> Connection for CountedConnection<T> {
+    fn connected(&self) -> Connected {
+        self.inner.connected()
+    }
+}
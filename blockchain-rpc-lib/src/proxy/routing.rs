@@ -0,0 +1,160 @@
+use super::upstream::{ConcurrencyLimit, Upstream};
+use crate::Result;
+use crate::archive::ArchiveRoute;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::config::{RouteConfig, RpcConfig, WebSocketBridgeConfig};
+use crate::head_cache::HeadCache;
+use crate::method_filter::MethodFilter;
+use crate::method_limits::MethodLimits;
+use crate::method_rewrite::MethodRewriter;
+use crate::mirror::Mirror;
+use crate::request_validation::RequestValidator;
+use crate::upstream_events::UpstreamEvents;
+use crate::upstream_probe::UpstreamProbe;
+use axum::http::Uri;
+use std::sync::Arc;
+
+/// A backend requests are proxied to, with the method filter, rewrites and limits applied to
+/// the calls sent to it.
+pub(crate) struct Route {
+    /// `None` for the default route, which serves every path no prefix matches.
+    pub(crate) prefix: Option<String>,
+    pub(crate) upstream: Arc<Upstream>,
+    pub(crate) method_filter: Arc<MethodFilter>,
+    pub(crate) method_rewriter: Arc<MethodRewriter>,
+    pub(crate) method_limits: Arc<MethodLimits>,
+    /// `[rpc.validation]`, shared by every route.
+    pub(crate) validator: Option<Arc<RequestValidator>>,
+    /// Capability and chain checks, which only the default backend has.
+    pub(crate) probe: Option<Arc<UpstreamProbe>>,
+    /// Head queries answered without the backend, which only the default route has.
+    pub(crate) head_cache: Option<Arc<HeadCache>>,
+    /// The archive node historical HTTP calls are sent to instead of `upstream`, if configured.
+    pub(crate) archive: Option<Arc<ArchiveRoute>>,
+    /// The shadow backend a share of HTTP requests is duplicated to, if configured.
+    pub(crate) mirror: Option<Arc<Mirror>>,
+    /// Set when WebSocket sessions are bridged to the backend over HTTP instead of dialed to it.
+    pub(crate) websocket_bridge: Option<WebSocketBridgeConfig>,
+}
+
+impl Route {
+    /// A route for `[[routes]]` entry `config`, inheriting `[rpc]` settings it doesn't replace
+    /// and the global method filter and limits unless it has its own.
+    pub(crate) fn new(
+        config: &RouteConfig,
+        rpc: &RpcConfig,
+        default: &Route,
+        events: Arc<UpstreamEvents>,
+        global_concurrency: Option<Arc<ConcurrencyLimit>>,
+    ) -> Result<Self> {
+        let breaker = rpc
+            .circuit_breaker
+            .as_ref()
+            .map(|config| Arc::new(CircuitBreaker::new(config)));
+        let (method_filter, method_rewriter) = match &config.methods {
+            Some(methods) => (
+                Arc::new(MethodFilter::new(methods)),
+                Arc::new(MethodRewriter::new(&methods.rewrites)),
+            ),
+            None => (
+                default.method_filter.clone(),
+                default.method_rewriter.clone(),
+            ),
+        };
+        let route_rpc = config.rpc_config(rpc);
+        let archive = match &config.archive {
+            Some(archive) => Some(Arc::new(ArchiveRoute::new(
+                archive,
+                &route_rpc,
+                events.clone(),
+                global_concurrency.clone(),
+            )?)),
+            None => None,
+        };
+        let mirror = match &config.mirror {
+            Some(mirror) => Some(Arc::new(Mirror::new(mirror, &route_rpc)?)),
+            None => None,
+        };
+        Ok(Route {
+            prefix: Some(config.prefix.clone()),
+            upstream: Arc::new(Upstream::new(
+                &route_rpc,
+                events,
+                breaker,
+                global_concurrency,
+            )?),
+            method_filter,
+            method_rewriter,
+            method_limits: match &config.limits {
+                Some(limits) => Arc::new(MethodLimits::new(limits)),
+                None => default.method_limits.clone(),
+            },
+            validator: default.validator.clone(),
+            probe: None,
+            head_cache: None,
+            archive,
+            mirror,
+            websocket_bridge: config.websocket_bridge.clone(),
+        })
+    }
+
+    /// The part of `path` below this route's prefix, if it matches on whole segments.
+    fn strip_prefix<'a>(&self, path: &'a str) -> Option<&'a str> {
+        let Some(prefix) = &self.prefix else {
+            return Some(path);
+        };
+        let rest = path.strip_prefix(prefix.as_str())?;
+        match rest {
+            "" => Some("/"),
+            rest if rest.starts_with('/') => Some(rest),
+            _ => None,
+        }
+    }
+
+    /// The path and query forwarded to the backend for `uri`, without the route's prefix.
+    pub(crate) fn forward_path_and_query(&self, uri: &Uri) -> String {
+        let path = self.strip_prefix(uri.path()).unwrap_or(uri.path());
+        match uri.query() {
+            Some(query) => format!("{path}?{query}"),
+            None => path.to_string(),
+        }
+    }
+
+    /// The prefix, or `/` for the default route, for logs.
+    pub(crate) fn name(&self) -> &str {
+        self.prefix.as_deref().unwrap_or("/")
+    }
+}
+
+/// The routes from `[[routes]]`, consulted for every HTTP request and WebSocket upgrade, and
+/// the default route to `rpc.proxy_to_url`.
+pub(crate) struct RoutingTable {
+    /// Longest prefix first, so nested prefixes win over their parents.
+    routes: Vec<Arc<Route>>,
+    default: Arc<Route>,
+}
+
+impl RoutingTable {
+    pub(crate) fn new(default: Route, routes: Vec<Route>) -> Self {
+        let mut routes: Vec<Arc<Route>> = routes.into_iter().map(Arc::new).collect();
+        routes.sort_by_key(|route| std::cmp::Reverse(route.name().len()));
+        RoutingTable {
+            routes,
+            default: Arc::new(default),
+        }
+    }
+
+    /// The route serving `path`.
+    pub(crate) fn resolve(&self, path: &str) -> Arc<Route> {
+        self.routes
+            .iter()
+            .find(|route| route.strip_prefix(path).is_some())
+            .unwrap_or(&self.default)
+            .clone()
+    }
+
+    /// Every route, the default one first.
+    pub(crate) fn routes(&self) -> impl Iterator<Item = &Arc<Route>> {
+        std::iter::once(&self.default).chain(&self.routes)
+    }
+}
@@ -0,0 +1,855 @@
+use super::outbound::OutboundProxy;
+use super::pool::{HttpClient, PoolCounters, PoolStats, http_client};
+use crate::Result;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::config::{RpcConfig, UpstreamConcurrencyConfig, UpstreamRetryConfig};
+use crate::error::Error;
+use crate::method_matcher::MethodMatcher;
+use crate::request_id::RequestId;
+use crate::tls::upstream_client_config;
+use crate::trace_context::TraceContext;
+use crate::upload::UploadStatus;
+use crate::upstream_events::{UpstreamEvent, UpstreamEvents, UpstreamTransport};
+use axum::body::{Body, Bytes};
+use axum::extract::ws::{CloseFrame, close_code};
+use axum::http::{HeaderMap, Request, Response, StatusCode, Uri, request::Parts};
+use hyper::body::Incoming;
+use parking_lot::RwLock;
+use rand::Rng;
+use rustls::pki_types::ServerName;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sp_runtime::AccountId32;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::{Semaphore, SemaphorePermit, watch};
+use tokio_rustls::TlsConnector;
+use tokio_tungstenite::tungstenite;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::{debug, info, warn};
+use url::Url;
+
+/// Points each backend gets on the hash ring; more points spread clients more evenly.
+const RING_POINTS_PER_BACKEND: usize = 64;
+
+/// Errors raised while sending an HTTP request to the upstream node.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum UpstreamHttpError {
+    #[error("Backend circuit is open")]
+    CircuitOpen(Duration),
+    #[error("Backend concurrency limit reached")]
+    Saturated,
+    #[error(transparent)]
+    Request(#[from] hyper_util::client::legacy::Error),
+}
+
+/// Errors raised while opening a WebSocket connection to the upstream node.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum UpstreamWsError {
+    #[error("WebSocket target {0} is outside the upstream allowlist")]
+    NotAllowed(String),
+    #[error("Backend circuit is open")]
+    CircuitOpen,
+    #[error("Failed to connect to {target}: {source}")]
+    Connect {
+        target: String,
+        source: std::io::Error,
+    },
+    #[error("TLS handshake with {server_name} failed: {source}")]
+    Tls {
+        server_name: String,
+        source: std::io::Error,
+    },
+    #[error("Invalid backend WebSocket URL {url}: {source}")]
+    InvalidUrl {
+        url: String,
+        source: tungstenite::Error,
+    },
+    #[error("WebSocket handshake with {url} failed: {source}")]
+    Handshake {
+        url: String,
+        source: tungstenite::Error,
+    },
+}
+
+impl UpstreamWsError {
+    /// The close frame sent to the client when its backend connection can't be opened.
+    pub(crate) fn close_frame(&self) -> CloseFrame<'static> {
+        let (code, reason) = match self {
+            Self::NotAllowed(_) => (close_code::POLICY, "Backend not allowed"),
+            Self::CircuitOpen => (close_code::AGAIN, "Backend unavailable"),
+            Self::Connect { .. } | Self::Tls { .. } | Self::InvalidUrl { .. } => {
+                (close_code::ERROR, "Backend connection failed")
+            }
+            Self::Handshake { .. } => (close_code::ERROR, "Backend handshake failed"),
+        };
+        CloseFrame {
+            code,
+            reason: reason.into(),
+        }
+    }
+
+    /// Whether the backend itself failed, as opposed to the gateway refusing to dial it.
+    fn is_backend_failure(&self) -> bool {
+        matches!(
+            self,
+            Self::Connect { .. } | Self::Tls { .. } | Self::Handshake { .. }
+        )
+    }
+
+    /// The upstream event this failure is reported as, if it reflects backend connectivity.
+    fn upstream_event(&self, target: String) -> Option<UpstreamEvent> {
+        let transport = UpstreamTransport::WebSocket;
+        match self {
+            Self::Connect { source, .. } => Some(UpstreamEvent::ConnectFailed {
+                transport,
+                target,
+                error: source.to_string(),
+            }),
+            Self::Tls { source, .. } => Some(UpstreamEvent::HandshakeFailed {
+                transport,
+                target,
+                error: source.to_string(),
+            }),
+            Self::Handshake { source, .. } => Some(UpstreamEvent::HandshakeFailed {
+                transport,
+                target,
+                error: source.to_string(),
+            }),
+            Self::NotAllowed(_) | Self::CircuitOpen | Self::InvalidUrl { .. } => None,
+        }
+    }
+}
+
+/// An open backend WebSocket connection. Reports the disconnect when dropped.
+pub(crate) struct UpstreamConnection {
+    events: Arc<UpstreamEvents>,
+    target: String,
+    opened_at: Instant,
+    /// Whether the backend is drained for maintenance.
+    maintenance: watch::Receiver<bool>,
+}
+
+impl UpstreamConnection {
+    /// The backend the connection is open to.
+    pub(crate) fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Resolves once the backend is drained for maintenance, or replaced by a backend switch
+    /// draining its sessions. Never resolves after the backend was replaced by a change of
+    /// backend URLs otherwise.
+    pub(crate) async fn maintenance_started(&mut self) {
+        if self.maintenance.wait_for(|drained| *drained).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+impl Drop for UpstreamConnection {
+    fn drop(&mut self) {
+        self.events.emit(UpstreamEvent::Disconnected {
+            transport: UpstreamTransport::WebSocket,
+            target: std::mem::take(&mut self.target),
+            duration_secs: self.opened_at.elapsed().as_secs(),
+        });
+    }
+}
+
+/// Who a request comes from, for pinning it to one backend: the authenticated account, or
+/// the client IP for requests without one.
+#[derive(Debug, Clone)]
+pub(crate) struct AffinityKey(String);
+
+impl AffinityKey {
+    pub(crate) fn new(client_ip: IpAddr, account: Option<&AccountId32>) -> Self {
+        match account {
+            Some(account) => AffinityKey(format!("account:{account}")),
+            None => AffinityKey(format!("ip:{client_ip}")),
+        }
+    }
+}
+
+/// The backend RPC nodes the gateway proxies to, over HTTP and WebSocket.
+pub(crate) struct Upstream {
+    /// Replaced when the backend URLs change at runtime; requests already under way keep the
+    /// backend they started with.
+    backends: RwLock<Arc<BackendPool>>,
+    events: Arc<UpstreamEvents>,
+    /// Counts the HTTP connections of every client `backends` has had.
+    pool_counters: Arc<PoolCounters>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    retry: UpstreamRetryConfig,
+    /// Methods from `retry.non_idempotent_methods`.
+    non_idempotent: MethodMatcher<()>,
+    concurrency: Option<ConcurrencyLimit>,
+    /// Shared by every route's upstream.
+    global_concurrency: Option<Arc<ConcurrencyLimit>>,
+}
+
+/// Bounds requests in flight, to one backend from `[rpc.concurrency]` or to all of them from
+/// `[rpc.global_concurrency]`, and counts the contention for its slots.
+pub(crate) struct ConcurrencyLimit {
+    permits: Semaphore,
+    max_concurrent_requests: usize,
+    queue_timeout: Duration,
+    /// Requests waiting for a slot.
+    queued: AtomicU64,
+    admitted: AtomicU64,
+    /// Requests rejected after waiting the queue timeout.
+    shed: AtomicU64,
+    /// Total and longest wait of admitted requests, in microseconds.
+    total_wait_micros: AtomicU64,
+    max_wait_micros: AtomicU64,
+}
+
+/// Contention for a concurrency limit's slots since startup.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AdmissionStats {
+    pub(crate) max_concurrent_requests: usize,
+    /// Requests holding a slot.
+    pub(crate) in_flight: usize,
+    /// Requests waiting for a slot.
+    pub(crate) queue_depth: u64,
+    pub(crate) admitted: u64,
+    /// Requests rejected with `503 Backend busy` after waiting `queue_timeout_ms`.
+    pub(crate) shed: u64,
+    /// Average and longest time admitted requests waited for a slot.
+    pub(crate) avg_wait_ms: f64,
+    pub(crate) max_wait_ms: f64,
+}
+
+/// Counts a request as queued until dropped, including when the request is abandoned while
+/// waiting.
+struct Queued<'a>(&'a AtomicU64);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl ConcurrencyLimit {
+    pub(crate) fn new(config: &UpstreamConcurrencyConfig) -> Self {
+        let max_concurrent_requests = config.max_concurrent_requests.max(1);
+        Self {
+            permits: Semaphore::new(max_concurrent_requests),
+            max_concurrent_requests,
+            queue_timeout: Duration::from_millis(config.queue_timeout_ms),
+            queued: AtomicU64::new(0),
+            admitted: AtomicU64::new(0),
+            shed: AtomicU64::new(0),
+            total_wait_micros: AtomicU64::new(0),
+            max_wait_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// Waits up to the queue timeout for a slot. Returns `None` if none freed up in time.
+    async fn admit(&self) -> Option<SemaphorePermit<'_>> {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        let _queued = Queued(&self.queued);
+        let started = Instant::now();
+        match tokio::time::timeout(self.queue_timeout, self.permits.acquire()).await {
+            Ok(Ok(permit)) => {
+                let waited = u64::try_from(started.elapsed().as_micros()).unwrap_or(u64::MAX);
+                self.admitted.fetch_add(1, Ordering::Relaxed);
+                self.total_wait_micros.fetch_add(waited, Ordering::Relaxed);
+                self.max_wait_micros.fetch_max(waited, Ordering::Relaxed);
+                Some(permit)
+            }
+            // The semaphore is never closed, so only the timeout ends up here.
+            Ok(Err(_)) | Err(_) => {
+                self.shed.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn stats(&self) -> AdmissionStats {
+        let admitted = self.admitted.load(Ordering::Relaxed);
+        let total_wait_ms = self.total_wait_micros.load(Ordering::Relaxed) as f64 / 1000.0;
+        AdmissionStats {
+            max_concurrent_requests: self.max_concurrent_requests,
+            in_flight: self.max_concurrent_requests - self.permits.available_permits(),
+            queue_depth: self.queued.load(Ordering::Relaxed),
+            admitted,
+            shed: self.shed.load(Ordering::Relaxed),
+            avg_wait_ms: if admitted == 0 {
+                0.0
+            } else {
+                total_wait_ms / admitted as f64
+            },
+            max_wait_ms: self.max_wait_micros.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+}
+
+/// `proxy_to_url` and `upstreams`, the hash ring assigning clients to them, and the pooled
+/// HTTP client reaching them.
+struct BackendPool {
+    /// `proxy_to_url` first.
+    backends: Vec<Arc<Backend>>,
+    /// Points sorted by hash, each with the index of the backend it belongs to.
+    ring: Vec<(u64, usize)>,
+    http_client: HttpClient,
+}
+
+impl BackendPool {
+    fn new(config: &RpcConfig, pool_counters: Arc<PoolCounters>) -> Result<Self> {
+        let ws_dial_targets = config.websocket_dial_targets();
+        let backends = std::iter::once(&config.proxy_to_url)
+            .chain(&config.upstreams)
+            .map(|url| Backend::new(url, config, ws_dial_targets.clone()).map(Arc::new))
+            .collect::<Result<Vec<_>>>()?;
+        // Points are placed by backend URL, so removing a backend only moves the clients
+        // pinned to it, and adding one only takes over a share of the others' clients.
+        let mut ring: Vec<(u64, usize)> = backends
+            .iter()
+            .enumerate()
+            .flat_map(|(index, backend)| {
+                (0..RING_POINTS_PER_BACKEND)
+                    .map(move |point| (ring_hash(&format!("{}#{point}", backend.proxy_url)), index))
+            })
+            .collect();
+        ring.sort_unstable();
+        Ok(Self {
+            backends,
+            ring,
+            http_client: http_client(config, pool_counters)?,
+        })
+    }
+
+    fn primary(&self) -> &Arc<Backend> {
+        &self.backends[0]
+    }
+
+    /// The backend `uri` was built for by [`Upstream::target_uri`].
+    fn backend_for(&self, uri: &Uri) -> Option<&Arc<Backend>> {
+        self.backends.iter().find(|backend| {
+            let url = &backend.proxy_url;
+            uri.scheme_str() == Some(url.scheme())
+                && uri.host() == url.host_str()
+                && uri.port_u16() == url.port()
+                && uri.path().starts_with(url.path().trim_end_matches('/'))
+        })
+    }
+
+    /// The backend `affinity` is pinned to: the one owning the first ring point at or after
+    /// its hash. Requests without a key go to `proxy_to_url`. Backends drained for maintenance
+    /// are skipped, moving only the clients pinned to them.
+    fn select(&self, affinity: Option<&AffinityKey>) -> Arc<Backend> {
+        let Some(AffinityKey(key)) = affinity.filter(|_| self.backends.len() > 1) else {
+            return self
+                .backends
+                .iter()
+                .find(|backend| !backend.in_maintenance())
+                .unwrap_or(self.primary())
+                .clone();
+        };
+        let hash = ring_hash(key);
+        let point = self.ring.partition_point(|(point, _)| *point < hash) % self.ring.len();
+        self.ring
+            .iter()
+            .cycle()
+            .skip(point)
+            .take(self.ring.len())
+            .map(|(_, index)| &self.backends[*index])
+            .find(|backend| !backend.in_maintenance())
+            .unwrap_or(&self.backends[self.ring[point].1])
+            .clone()
+    }
+
+    /// Drains the backends in `maintenance` from rotation, and returns them to it once they
+    /// are no longer listed. When every backend is listed, none is drained. Returns the URLs
+    /// of the backends drained now.
+    fn set_maintenance(&self, maintenance: &HashSet<Url>) -> Vec<Url> {
+        let all_listed = self
+            .backends
+            .iter()
+            .all(|backend| maintenance.contains(&backend.proxy_url));
+        self.backends
+            .iter()
+            .filter(|backend| {
+                let drained = !all_listed && maintenance.contains(&backend.proxy_url);
+                backend.maintenance.send_if_modified(|current| {
+                    let changed = *current != drained;
+                    *current = drained;
+                    changed
+                });
+                drained
+            })
+            .map(|backend| backend.proxy_url.clone())
+            .collect()
+    }
+}
+
+/// A backend address, the credentials it requires, and how WebSocket connections to it are
+/// dialed.
+struct Backend {
+    proxy_url: Url,
+    /// Headers from `[[rpc.upstream_auth]]`, set on every request and handshake.
+    auth: HeaderMap,
+    ws_dial_targets: HashSet<String>,
+    /// Set when the backend is reached over `wss://`.
+    ws_tls: Option<UpstreamTls>,
+    /// Set when WebSocket connections are tunneled through `[rpc.outbound_proxy]`.
+    ws_proxy: Option<OutboundProxy>,
+    /// Whether the backend is drained for maintenance.
+    maintenance: watch::Sender<bool>,
+}
+
+struct UpstreamTls {
+    connector: TlsConnector,
+    server_name: ServerName<'static>,
+}
+
+impl Backend {
+    fn new(proxy_url: &Url, config: &RpcConfig, ws_dial_targets: HashSet<String>) -> Result<Self> {
+        let proxy_url = proxy_url.clone();
+        let ws_tls = if matches!(proxy_url.scheme(), "https" | "wss") {
+            let name = config
+                .upstream_tls
+                .server_name
+                .clone()
+                .or_else(|| proxy_url.host_str().map(str::to_string))
+                .ok_or_else(|| Error::TlsError("Backend URL has no host".to_string()))?;
+            let server_name = ServerName::try_from(name.clone())
+                .map_err(|e| Error::TlsError(format!("Invalid server name {name}: {e}")))?;
+            Some(UpstreamTls {
+                connector: TlsConnector::from(upstream_client_config(&config.upstream_tls)?),
+                server_name,
+            })
+        } else {
+            None
+        };
+
+        let auth = match config
+            .upstream_auth
+            .iter()
+            .find(|auth| auth.backend == proxy_url)
+        {
+            Some(auth) => auth.header_map()?,
+            None => HeaderMap::new(),
+        };
+        let ws_proxy = config
+            .outbound_proxy
+            .as_ref()
+            .map(OutboundProxy::new)
+            .transpose()?;
+
+        Ok(Self {
+            ws_dial_targets,
+            ws_proxy,
+            auth,
+            proxy_url,
+            ws_tls,
+            maintenance: watch::Sender::new(false),
+        })
+    }
+
+    fn in_maintenance(&self) -> bool {
+        *self.maintenance.borrow()
+    }
+
+    /// Sets the backend's credentials on `headers`, replacing any the client sent.
+    fn authorize(&self, headers: &mut HeaderMap) {
+        for (name, value) in &self.auth {
+            headers.insert(name, value.clone());
+        }
+    }
+
+    /// The `host:port` address WebSocket connections are dialed to.
+    fn ws_target_addr(&self) -> String {
+        let host = self.proxy_url.host_str().unwrap_or("localhost");
+        let port = self.proxy_url.port_or_known_default().unwrap_or(80); // Default WS port
+        format!("{}:{}", host, port)
+    }
+
+    /// The URL used for the WebSocket handshake with the upstream node.
+    fn ws_url(&self) -> String {
+        let ws_scheme = if self.ws_tls.is_some() { "wss" } else { "ws" };
+        format!(
+            "{}://{}{}",
+            ws_scheme,
+            self.proxy_url.host_str().unwrap_or("localhost"),
+            self.proxy_url.path()
+        )
+    }
+}
+
+impl Upstream {
+    pub(crate) fn new(
+        config: &RpcConfig,
+        events: Arc<UpstreamEvents>,
+        breaker: Option<Arc<CircuitBreaker>>,
+        global_concurrency: Option<Arc<ConcurrencyLimit>>,
+    ) -> Result<Self> {
+        let pool_counters = Arc::new(PoolCounters::default());
+        Ok(Self {
+            backends: RwLock::new(Arc::new(BackendPool::new(config, pool_counters.clone())?)),
+            events,
+            pool_counters,
+            breaker,
+            non_idempotent: config
+                .retry
+                .non_idempotent_methods
+                .iter()
+                .map(|method| (method.as_str(), ()))
+                .collect(),
+            retry: config.retry.clone(),
+            concurrency: config.concurrency.as_ref().map(ConcurrencyLimit::new),
+            global_concurrency,
+        })
+    }
+
+    fn backends(&self) -> Arc<BackendPool> {
+        self.backends.read().clone()
+    }
+
+    /// The URL of `proxy_to_url`'s backend.
+    pub(crate) fn proxy_url(&self) -> Url {
+        self.backends().primary().proxy_url.clone()
+    }
+
+    /// Switches to the backends at `config.proxy_to_url` and `config.upstreams`, for new
+    /// requests and WebSocket connections. Open connections stay with their previous backend
+    /// until they close, or with `drain_after`, until that has passed: WebSocket sessions open
+    /// to a backend no longer listed are then asked to reconnect, as for maintenance.
+    pub(crate) fn retarget(&self, config: &RpcConfig, drain_after: Option<Duration>) -> Result<()> {
+        let pool = BackendPool::new(config, self.pool_counters.clone())?;
+        info!(
+            proxy_url = %pool.primary().proxy_url,
+            upstreams = pool.backends.len() - 1,
+            "Switching to new backend URLs"
+        );
+        let kept: HashSet<Url> = pool
+            .backends
+            .iter()
+            .map(|backend| backend.proxy_url.clone())
+            .collect();
+        let previous = std::mem::replace(&mut *self.backends.write(), Arc::new(pool));
+        if let Some(drain_after) = drain_after {
+            // Holding on to the previous pool keeps its sessions' drain signals alive
+            tokio::spawn(async move {
+                tokio::time::sleep(drain_after).await;
+                for backend in &previous.backends {
+                    if !kept.contains(&backend.proxy_url) {
+                        info!(backend = %backend.proxy_url, "Draining sessions of replaced backend");
+                        backend.maintenance.send_replace(true);
+                    }
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Drains the backends in `maintenance` from rotation for new requests and connections,
+    /// asks WebSocket sessions open to them to reconnect, and returns the others to rotation.
+    /// Returns the URLs of the backends drained now.
+    pub(crate) fn set_maintenance(&self, maintenance: &HashSet<Url>) -> Vec<Url> {
+        self.backends().set_maintenance(maintenance)
+    }
+
+    /// Builds the upstream URI for a request's path and query, on the backend `affinity` is
+    /// pinned to.
+    pub(crate) fn target_uri(
+        &self,
+        path_and_query: &str,
+        affinity: Option<&AffinityKey>,
+    ) -> Result<Uri, String> {
+        let target = format!(
+            "{}{}",
+            self.backends()
+                .select(affinity)
+                .proxy_url
+                .as_str()
+                .trim_end_matches('/'),
+            path_and_query
+        );
+        target
+            .parse::<Uri>()
+            .map_err(|e| format!("{}: {}", target, e))
+    }
+
+    /// Contention for the `[rpc.concurrency]` slots, if a limit is configured.
+    pub(crate) fn admission_stats(&self) -> Option<AdmissionStats> {
+        self.concurrency.as_ref().map(ConcurrencyLimit::stats)
+    }
+
+    /// How well HTTP connections to the backends are reused.
+    pub(crate) fn pool_stats(&self) -> PoolStats {
+        self.pool_counters.stats()
+    }
+
+    /// Whether request bodies must be buffered, so they can be resent on retry.
+    pub(crate) fn buffers_requests(&self) -> bool {
+        self.retry.max_retries > 0
+    }
+
+    /// Whether a request may be resent after it reached the backend: safe HTTP methods, and
+    /// JSON-RPC calls none of whose methods are configured as non-idempotent.
+    pub(crate) fn is_idempotent(&self, parts: &Parts, methods: &[String]) -> bool {
+        parts.method.is_safe()
+            || (!methods.is_empty()
+                && methods
+                    .iter()
+                    .all(|method| !self.non_idempotent.matches(method)))
+    }
+
+    /// Sends an HTTP request to the upstream node, once, with the backend's credentials. HTTP
+    /// connections are pooled, so only failures to connect are reported as upstream events.
+    ///
+    /// With a concurrency limit, the request holds a slot until the backend answers and waits
+    /// up to the queue timeout for one. The global limit's slot is only taken once the
+    /// backend's own limit admitted the request, so a saturated backend doesn't tie up slots
+    /// other backends could use.
+    pub(crate) async fn send(
+        &self,
+        mut req: Request<Body>,
+    ) -> Result<Response<Incoming>, UpstreamHttpError> {
+        let _permit = match &self.concurrency {
+            Some(limit) => match limit.admit().await {
+                Some(permit) => Some(permit),
+                None => {
+                    warn!("Backend concurrency limit reached, rejecting request");
+                    return Err(UpstreamHttpError::Saturated);
+                }
+            },
+            None => None,
+        };
+        let _global_permit = match &self.global_concurrency {
+            Some(limit) => match limit.admit().await {
+                Some(permit) => Some(permit),
+                None => {
+                    warn!("Global concurrency limit reached, rejecting request");
+                    return Err(UpstreamHttpError::Saturated);
+                }
+            },
+            None => None,
+        };
+
+        if let Some(breaker) = &self.breaker {
+            breaker
+                .try_acquire()
+                .map_err(UpstreamHttpError::CircuitOpen)?;
+        }
+
+        let target = format!(
+            "{}://{}",
+            req.uri().scheme_str().unwrap_or("http"),
+            req.uri()
+                .authority()
+                .map_or("", |authority| authority.as_str())
+        );
+        let upload = req.extensions().get::<UploadStatus>().cloned();
+        let backends = self.backends();
+        if let Some(backend) = backends.backend_for(req.uri()) {
+            backend.authorize(req.headers_mut());
+        }
+        self.pool_counters.record_request();
+        let result = backends.http_client.request(req).await;
+        if let Some(e) = result.as_ref().err().filter(|e| e.is_connect()) {
+            self.events.emit(UpstreamEvent::ConnectFailed {
+                transport: UpstreamTransport::Http,
+                target,
+                error: e.to_string(),
+            });
+        }
+        if let Some(breaker) = &self.breaker {
+            match &result {
+                Ok(resp) if !is_transient_status(resp.status()) => breaker.record_success(),
+                // The client's upload was cut off; the backend isn't at fault.
+                Err(_) if upload.as_ref().and_then(UploadStatus::failure).is_some() => {}
+                _ => breaker.record_failure(),
+            }
+        }
+        Ok(result?)
+    }
+
+    /// Sends a buffered HTTP request, retrying transient failures with jittered exponential
+    /// backoff. Connection failures are retried for every request, since it never reached the
+    /// backend; other errors and 502/503/504 responses only for idempotent requests.
+    pub(crate) async fn send_with_retries(
+        &self,
+        parts: &Parts,
+        body: Bytes,
+        idempotent: bool,
+    ) -> Result<Response<Incoming>, UpstreamHttpError> {
+        let mut attempt = 0;
+        loop {
+            let result = self.send(rebuild_request(parts, body.clone())).await;
+            let retryable = match &result {
+                Ok(resp) => idempotent && is_transient_status(resp.status()),
+                Err(UpstreamHttpError::Request(e)) => idempotent || e.is_connect(),
+                Err(UpstreamHttpError::CircuitOpen(_) | UpstreamHttpError::Saturated) => false,
+            };
+            if !retryable || attempt >= self.retry.max_retries {
+                return result;
+            }
+            attempt += 1;
+            let delay = self.backoff(attempt);
+            warn!(attempt, ?delay, "Retrying failed backend request");
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Delay before retry number `attempt`: the initial backoff doubled per failed attempt,
+    /// capped at the maximum backoff, then jittered down by up to half so concurrent retries
+    /// spread out.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let initial = Duration::from_millis(self.retry.initial_backoff_ms);
+        let max = Duration::from_millis(self.retry.max_backoff_ms);
+        let delay = initial
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(max);
+        delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+
+    /// Opens a WebSocket connection to the backend `affinity` is pinned to, continuing
+    /// `trace_context`, passing on `request_id` if set and sending the backend's credentials in
+    /// the handshake. `wss://` backends are connected over TLS, with SNI set to the configured
+    /// server name. The connection's lifecycle is reported as upstream events.
+    pub(crate) async fn connect_websocket(
+        &self,
+        trace_context: Option<&TraceContext>,
+        request_id: Option<&RequestId>,
+        affinity: Option<&AffinityKey>,
+    ) -> Result<
+        (
+            WebSocketStream<MaybeTlsStream<TcpStream>>,
+            UpstreamConnection,
+        ),
+        UpstreamWsError,
+    > {
+        if let Some(breaker) = &self.breaker {
+            breaker
+                .try_acquire()
+                .map_err(|_| UpstreamWsError::CircuitOpen)?;
+        }
+
+        let backend = self.backends().select(affinity);
+        let target = backend.ws_target_addr();
+        let result = self
+            .dial_websocket(&backend, &target, trace_context, request_id)
+            .await;
+        if let Some(breaker) = &self.breaker {
+            match &result {
+                Ok(_) => breaker.record_success(),
+                Err(e) if e.is_backend_failure() => breaker.record_failure(),
+                Err(_) => {}
+            }
+        }
+        match result {
+            Ok(socket) => {
+                self.events.emit(UpstreamEvent::Connected {
+                    transport: UpstreamTransport::WebSocket,
+                    target: target.clone(),
+                });
+                let connection = UpstreamConnection {
+                    events: self.events.clone(),
+                    target,
+                    opened_at: Instant::now(),
+                    maintenance: backend.maintenance.subscribe(),
+                };
+                Ok((socket, connection))
+            }
+            Err(e) => {
+                if let Some(event) = e.upstream_event(target) {
+                    self.events.emit(event);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn dial_websocket(
+        &self,
+        backend: &Backend,
+        target_addr: &str,
+        trace_context: Option<&TraceContext>,
+        request_id: Option<&RequestId>,
+    ) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, UpstreamWsError> {
+        if !backend.ws_dial_targets.contains(target_addr) {
+            return Err(UpstreamWsError::NotAllowed(target_addr.to_string()));
+        }
+
+        debug!(%target_addr, "Attempting to establish backend WebSocket connection");
+        let connecting = match &backend.ws_proxy {
+            Some(proxy) => proxy.connect(target_addr).await,
+            None => TcpStream::connect(target_addr).await,
+        };
+        let stream = connecting.map_err(|source| UpstreamWsError::Connect {
+            target: target_addr.to_string(),
+            source,
+        })?;
+        let stream = match &backend.ws_tls {
+            Some(tls) => {
+                let tls_stream = tls
+                    .connector
+                    .connect(tls.server_name.clone(), stream)
+                    .await
+                    .map_err(|source| UpstreamWsError::Tls {
+                        server_name: tls.server_name.to_str().into_owned(),
+                        source,
+                    })?;
+                MaybeTlsStream::Rustls(tls_stream)
+            }
+            None => MaybeTlsStream::Plain(stream),
+        };
+
+        let url = backend.ws_url();
+        let mut request =
+            url.as_str()
+                .into_client_request()
+                .map_err(|source| UpstreamWsError::InvalidUrl {
+                    url: url.clone(),
+                    source,
+                })?;
+        if let Some(trace_context) = trace_context {
+            trace_context.inject(request.headers_mut());
+        }
+        if let Some(request_id) = request_id {
+            request_id.inject(request.headers_mut());
+        }
+        backend.authorize(request.headers_mut());
+
+        let (socket, _response) = tokio_tungstenite::client_async(request, stream)
+            .await
+            .map_err(|source| UpstreamWsError::Handshake { url, source })?;
+        debug!(%target_addr, "Backend WebSocket connection established");
+        Ok(socket)
+    }
+}
+
+/// Position of `key` on the hash ring: the first eight bytes of its SHA-256 digest.
+fn ring_hash(key: &str) -> u64 {
+    let digest = Sha256::digest(key.as_bytes());
+    u64::from_be_bytes(
+        digest[..8]
+            .try_into()
+            .expect("SHA-256 digests are 32 bytes"),
+    )
+}
+
+/// Statuses a proxy or node returns while the backend is restarting or overloaded.
+fn is_transient_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Builds a fresh request from buffered parts, for each attempt.
+fn rebuild_request(parts: &Parts, body: Bytes) -> Request<Body> {
+    let mut req = Request::new(Body::from(body));
+    *req.method_mut() = parts.method.clone();
+    *req.uri_mut() = parts.uri.clone();
+    *req.version_mut() = parts.version;
+    *req.headers_mut() = parts.headers.clone();
+    req
+}
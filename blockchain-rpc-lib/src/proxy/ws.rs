@@ -0,0 +1,801 @@
+use super::http_bridge::HttpBridge;
+use super::routing::Route;
+use super::upstream::{AffinityKey, UpstreamConnection};
+use super::{
+    RpcGatewayState, batch_too_large_payload, filter_rejected_payload, limit_exceeded_payload,
+    method_not_allowed_payload, method_not_supported_payload, read_only_payload,
+};
+use crate::access_log::{AccessRecord, elapsed_ms};
+use crate::config::{RateLimitConfig, WebSocketKeepaliveConfig};
+use crate::context::SecureRpcContext;
+use crate::firewall::{AccessDecision, AccessSurface};
+use crate::metering::UsageSubject;
+use crate::method_filter::{jsonrpc_batch_len, jsonrpc_methods};
+use crate::payload_filters::FilterContext;
+use crate::plans::ActivePlan;
+use crate::ratelimit::RateLimiter;
+use crate::request_id::RequestId;
+use crate::sessions::{SessionHandle, SessionLimit};
+use crate::subscriptions::SubscriptionTracker;
+use crate::trace_context::TraceContext;
+use axum::extract::ws::{CloseFrame, Message, WebSocket, close_code};
+use axum::http::HeaderMap;
+use futures::stream::{SplitSink, SplitStream};
+use futures::{Sink, Stream, sink::SinkExt, stream::StreamExt};
+use parking_lot::Mutex;
+use sp_runtime::AccountId32;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::{debug, error, info, warn};
+
+type ClientSink = tokio::sync::Mutex<SplitSink<WebSocket, Message>>;
+type BackendSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type BackendSink<B> = tokio::sync::Mutex<SplitSink<B, tungstenite::Message>>;
+
+/// Close code for sessions whose access expired or was revoked without a ban, mirroring HTTP
+/// `402 Payment Required` in the application-defined range.
+const CLOSE_PAYMENT_REQUIRED: u16 = 4402;
+
+/// Why the gateway ended a WebSocket session, sent to the client in the close frame.
+#[derive(Debug, Clone, Copy)]
+enum SessionEnd {
+    /// Terminated by an operator through the admin API.
+    Terminated,
+    /// The client's IP, country or account was banned.
+    Banned,
+    /// The grant the session relied on expired or was revoked.
+    AccessExpired,
+    /// The gateway is shutting down.
+    ShuttingDown,
+    /// The client sent messages faster than `websocket.message_rate` allows.
+    MessageRateExceeded,
+    /// The client sent a message over `websocket.max_frame_bytes`.
+    MessageTooLarge,
+    /// The client's plan doesn't include WebSocket connections.
+    NotInPlan,
+    /// The backend was drained for a maintenance window; the client should reconnect.
+    BackendMaintenance,
+    /// The client sent nothing, not even a pong, for `rpc.websocket_keepalive.idle_timeout_secs`.
+    ClientIdle,
+    /// The backend sent nothing, not even a pong, for `rpc.websocket_keepalive.idle_timeout_secs`.
+    BackendUnresponsive,
+    /// The client sent a binary frame that isn't UTF-8, so it can't be checked as JSON-RPC.
+    UnsupportedBinary,
+}
+
+impl SessionEnd {
+    fn close_frame(self) -> CloseFrame<'static> {
+        let (code, reason) = match self {
+            SessionEnd::Terminated => (close_code::POLICY, "Session terminated"),
+            SessionEnd::Banned => (close_code::POLICY, "Access denied"),
+            SessionEnd::AccessExpired => (CLOSE_PAYMENT_REQUIRED, "Payment required"),
+            SessionEnd::ShuttingDown => (close_code::AWAY, "Gateway shutting down"),
+            SessionEnd::MessageRateExceeded => (close_code::POLICY, "Message rate exceeded"),
+            SessionEnd::MessageTooLarge => (close_code::POLICY, "Message too large"),
+            SessionEnd::NotInPlan => (CLOSE_PAYMENT_REQUIRED, "WebSocket not included in plan"),
+            SessionEnd::BackendMaintenance => {
+                (close_code::RESTART, "Backend maintenance, reconnect")
+            }
+            SessionEnd::ClientIdle => (close_code::AWAY, "Idle timeout"),
+            SessionEnd::BackendUnresponsive => (close_code::AGAIN, "Backend not responding"),
+            SessionEnd::UnsupportedBinary => (
+                close_code::UNSUPPORTED,
+                "Binary frames must be UTF-8 JSON-RPC",
+            ),
+        };
+        CloseFrame {
+            code,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// What a session's messages are forwarded to.
+enum SessionBackend {
+    /// A WebSocket connection to the backend.
+    WebSocket(BackendSocket, UpstreamConnection),
+    /// The backend's HTTP endpoint, for routes with `websocket_bridge` set.
+    Http(HttpBridge),
+}
+
+/// Handles a WebSocket connection, proxying messages between client and backend.
+pub(crate) async fn handle_websocket(
+    mut client_socket: WebSocket,
+    state: RpcGatewayState,
+    trace_context: Option<TraceContext>,
+    request_id: Option<RequestId>,
+    account: Option<AccountId32>,
+    active_plan: Option<ActivePlan>,
+    client_addr: SocketAddr,
+) {
+    let ctx = state.ctx;
+    if let Some(active) = active_plan.as_ref().filter(|active| !active.plan.websocket) {
+        warn!(%client_addr, plan = %active.plan.id, "Refusing WebSocket connection on an HTTP-only plan");
+        let _ = client_socket
+            .send(Message::Close(Some(SessionEnd::NotInPlan.close_frame())))
+            .await;
+        return;
+    }
+
+    let account_limit = active_plan
+        .as_ref()
+        .and_then(|active| active.plan.max_connections);
+    let session = match ctx
+        .sessions
+        .open(client_addr.ip(), account.clone(), account_limit)
+    {
+        Ok(session) => session,
+        Err(limit) => {
+            let (source, limit_type, max) = match limit {
+                SessionLimit::PerIp(max) => (client_addr.ip().to_string(), "IP", max),
+                SessionLimit::PerAccount(max) => (
+                    account.map(|a| a.to_string()).unwrap_or_default(),
+                    "Account",
+                    max,
+                ),
+            };
+            warn!(%client_addr, limit_type, max, "Refusing WebSocket connection over the connection limit");
+            ctx.firewall
+                .report_connection_limit(source, limit_type, max)
+                .await;
+            let _ = client_socket
+                .send(Message::Close(Some(CloseFrame {
+                    code: close_code::POLICY,
+                    reason: "Too many connections".into(),
+                })))
+                .await;
+            return;
+        }
+    };
+
+    let affinity = AffinityKey::new(client_addr.ip(), account.as_ref());
+    let backend = match &state.route.websocket_bridge {
+        Some(config) => {
+            let mut headers = HeaderMap::new();
+            if let Some(trace_context) = &trace_context {
+                trace_context.inject(&mut headers);
+            }
+            if let Some(request_id) = &request_id {
+                request_id.inject(&mut headers);
+            }
+            SessionBackend::Http(HttpBridge::new(
+                config,
+                ctx.clone(),
+                state.route.clone(),
+                affinity,
+                headers,
+            ))
+        }
+        None => match state
+            .route
+            .upstream
+            .connect_websocket(trace_context.as_ref(), request_id.as_ref(), Some(&affinity))
+            .await
+        {
+            Ok((socket, connection)) => SessionBackend::WebSocket(socket, connection),
+            Err(e) => {
+                error!(%client_addr, error = %e, "Failed to open backend WebSocket connection");
+                let _ = client_socket
+                    .send(Message::Close(Some(e.close_frame())))
+                    .await;
+                return;
+            }
+        },
+    };
+
+    if let Some(access_log) = &ctx.access_log {
+        access_log.record(AccessRecord::WebSocketOpen {
+            request_id: request_id.as_ref().map(ToString::to_string),
+            session_id: session.id(),
+            client_ip: client_addr.ip(),
+            account: account.as_ref().map(ToString::to_string),
+            route: state.route.name().to_string(),
+            upstream: match &backend {
+                SessionBackend::WebSocket(_, connection) => connection.target().to_string(),
+                SessionBackend::Http(_) => state.route.upstream.proxy_url().to_string(),
+            },
+        });
+    }
+    if let Some(traffic_stats) = &ctx.traffic_stats {
+        let account = account.as_ref().map(ToString::to_string);
+        traffic_stats.session_opened(state.route.name(), account.as_deref());
+    }
+
+    let billed_account = ctx
+        .config()
+        .firewall
+        .identity
+        .billed_account(account.as_ref())
+        .cloned();
+    let usage_subject = UsageSubject::new(client_addr.ip(), billed_account.as_ref());
+    let bridge = WsBridge::new(
+        ctx,
+        state.route,
+        session,
+        client_addr,
+        usage_subject,
+        active_plan,
+    );
+    match backend {
+        SessionBackend::WebSocket(socket, connection) => {
+            bridge.run(client_socket, socket, Some(connection)).await
+        }
+        SessionBackend::Http(http) => {
+            let (socket, serve) = http.open();
+            tokio::join!(bridge.run(client_socket, socket, None), serve);
+        }
+    }
+}
+
+/// Forwards frames between a client WebSocket and its backend connection, applying the message
+/// rate and size limits, payload filters, method rewrites, the batch size cap, the method
+/// filter, per-method limits and subscription limits to client frames on the way through,
+/// payload filters to backend frames, and pinging both sides if keepalives are configured.
+pub(crate) struct WsBridge {
+    ctx: Arc<SecureRpcContext>,
+    /// The route the connection was opened on, whose method filter and limits apply to its
+    /// frames.
+    route: Arc<Route>,
+    session: SessionHandle,
+    subscriptions: Mutex<SubscriptionTracker>,
+    client_addr: SocketAddr,
+    usage_subject: UsageSubject,
+    /// The plan the connection was opened under, whose method allowlist applies to its frames.
+    active_plan: Option<ActivePlan>,
+    /// What payload filters see of the connection.
+    filter_ctx: FilterContext,
+    /// `websocket.message_rate` when the connection opened, and the client's message bucket.
+    message_rate: Option<(RateLimitConfig, RateLimiter<()>)>,
+    max_frame_bytes: Option<usize>,
+    /// `rpc.websocket_keepalive` when the connection opened.
+    keepalive: Option<WebSocketKeepaliveConfig>,
+    /// When the client and the backend last sent a frame.
+    client_seen: Mutex<Instant>,
+    backend_seen: Mutex<Instant>,
+    opened_at: Instant,
+}
+
+impl WsBridge {
+    pub(crate) fn new(
+        ctx: Arc<SecureRpcContext>,
+        route: Arc<Route>,
+        session: SessionHandle,
+        client_addr: SocketAddr,
+        usage_subject: UsageSubject,
+        active_plan: Option<ActivePlan>,
+    ) -> Self {
+        let config = ctx.config();
+        let subscriptions = Mutex::new(SubscriptionTracker::new(&config.websocket));
+        let message_rate = config
+            .websocket
+            .message_rate
+            .as_ref()
+            .map(|rate| (rate.clone(), RateLimiter::new(rate)));
+        let max_frame_bytes = config.websocket.max_frame_bytes;
+        let keepalive = config.rpc.websocket_keepalive.clone();
+        let filter_ctx = FilterContext {
+            route: route.name().to_string(),
+            client_ip: client_addr.ip(),
+            account: session.account().cloned(),
+            websocket: true,
+        };
+        Self {
+            ctx,
+            route,
+            session,
+            subscriptions,
+            client_addr,
+            usage_subject,
+            active_plan,
+            filter_ctx,
+            message_rate,
+            max_frame_bytes,
+            keepalive,
+            client_seen: Mutex::new(Instant::now()),
+            backend_seen: Mutex::new(Instant::now()),
+            opened_at: Instant::now(),
+        }
+    }
+
+    /// Checks a message from the client against the connection's message rate and size
+    /// limits. A violation is reported to the firewall and ends the session.
+    async fn check_message_limits(&self, msg: &Message) -> Option<SessionEnd> {
+        let client_addr = self.client_addr;
+        let size = data_len(msg);
+        let (end, limit_type, limit) = match (&self.message_rate, self.max_frame_bytes) {
+            (_, Some(max)) if size > max => {
+                warn!(%client_addr, size, max, "Closing WebSocket session over the message size limit");
+                (
+                    SessionEnd::MessageTooLarge,
+                    "FrameSize",
+                    format!("{max} bytes"),
+                )
+            }
+            (Some((rate, limiter)), _) if limiter.check(()).is_err() => {
+                warn!(%client_addr, "Closing WebSocket session over the message rate limit");
+                (
+                    SessionEnd::MessageRateExceeded,
+                    "MessageRate",
+                    format!("{} messages/s, burst {}", rate.requests_per_sec, rate.burst),
+                )
+            }
+            _ => return None,
+        };
+        self.ctx
+            .firewall
+            .report_websocket_violation(client_addr.ip().to_string(), limit_type, limit)
+            .await;
+        Some(end)
+    }
+
+    /// Checks a text frame from the client. Returns the JSON-RPC error to answer it with
+    /// if it must not be forwarded to the backend.
+    pub(crate) fn check_client_text(&self, payload: &[u8]) -> Option<String> {
+        let client_addr = self.client_addr;
+        if let Some(invalid) = self
+            .route
+            .validator
+            .as_ref()
+            .and_then(|validator| validator.validate(payload).err())
+        {
+            warn!(%client_addr, error = %invalid, "Rejected invalid JSON-RPC request in WebSocket frame");
+            return Some(invalid.payload());
+        }
+        let oversized_batch = self.ctx.config().rpc.batch.as_ref().and_then(|config| {
+            let max = config.max_batch_size.max(1);
+            let calls = jsonrpc_batch_len(payload).filter(|calls| *calls > max)?;
+            Some((calls, max))
+        });
+        if let Some((calls, max)) = oversized_batch {
+            warn!(%client_addr, calls, max, "Rejected oversized JSON-RPC batch in WebSocket frame");
+            return Some(batch_too_large_payload(calls, max));
+        }
+        if let Some(method) = self.route.method_filter.find_blocked_method(payload) {
+            warn!(%client_addr, %method, "Blocked JSON-RPC method in WebSocket frame");
+            return Some(method_not_allowed_payload(&method));
+        }
+        if let Some(method) = self
+            .active_plan
+            .as_ref()
+            .and_then(|active| active.plan.find_blocked_method(payload))
+        {
+            warn!(%client_addr, %method, "Blocked JSON-RPC method outside the client's plan");
+            return Some(method_not_allowed_payload(&method));
+        }
+        if let Some(method) = self
+            .ctx
+            .account_roles
+            .find_unauthorized_method(payload, self.session.account())
+        {
+            warn!(%client_addr, %method, "Blocked JSON-RPC method requiring a role the client lacks");
+            return Some(method_not_allowed_payload(&method));
+        }
+        if let Some((method, status)) = self.ctx.read_only.find_rejected_method(payload) {
+            warn!(%client_addr, %method, "Rejected JSON-RPC call in WebSocket frame while the gateway is read-only");
+            return Some(read_only_payload(&method, &status));
+        }
+        if let Some(violation) = self.route.method_limits.check_request(payload) {
+            warn!(%client_addr, %violation, "Rejected JSON-RPC call over its method limits in WebSocket frame");
+            return Some(limit_exceeded_payload(&violation));
+        }
+        if let Some(method) = self
+            .route
+            .probe
+            .as_ref()
+            .and_then(|probe| probe.find_unsupported_method(payload))
+        {
+            warn!(%client_addr, %method, "Refused JSON-RPC method the backend doesn't support");
+            return Some(method_not_supported_payload(&method));
+        }
+
+        let mut tracker = self.subscriptions.lock();
+        let rejection = tracker.check_client_frame(payload, self.session.account_subscriptions());
+        self.session.set_subscriptions(tracker.count());
+        if rejection.is_some() {
+            warn!(%client_addr, "Rejected WebSocket subscription over the limit");
+        }
+        rejection
+    }
+
+    /// Records subscriptions confirmed or cancelled by a text frame from the backend.
+    pub(crate) fn observe_backend_text(&self, payload: &[u8]) {
+        let mut tracker = self.subscriptions.lock();
+        tracker.observe_backend_frame(payload);
+        self.session.set_subscriptions(tracker.count());
+    }
+
+    /// Forwards frames in both directions until either side closes, or the gateway ends the
+    /// session: an operator terminates it, its access is lost, its backend is drained for
+    /// maintenance, either side stops responding or the gateway shuts down. Sessions the gateway
+    /// ends are closed on both legs. Sessions bridged over HTTP have no `connection`.
+    pub(crate) async fn run<B>(
+        self,
+        client_socket: WebSocket,
+        backend_socket: B,
+        mut connection: Option<UpstreamConnection>,
+    ) where
+        B: Stream<Item = Result<tungstenite::Message, tungstenite::Error>>
+            + Sink<tungstenite::Message, Error = tungstenite::Error>,
+    {
+        let client_addr = self.client_addr;
+        let (client_tx, client_rx) = client_socket.split();
+        let client_tx = tokio::sync::Mutex::new(client_tx);
+        let (backend_tx, backend_rx) = backend_socket.split();
+        let backend_tx = tokio::sync::Mutex::new(backend_tx);
+        let shutdown = self.ctx.tasks.shutdown_token();
+
+        let mut closed_by = "ClientClosed";
+        let ended = tokio::select! {
+            end = self.client_to_backend(client_rx, &client_tx, &backend_tx) => {
+                if end.is_none() {
+                    info!(%client_addr, "Client WebSocket connection closed.");
+                }
+                end
+            }
+            _ = self.backend_to_client(backend_rx, &client_tx) => {
+                info!(%client_addr, "Backend WebSocket connection closed.");
+                closed_by = "BackendClosed";
+                None
+            }
+            _ = self.session.terminated() => Some(SessionEnd::Terminated),
+            end = self.watch_access() => Some(end),
+            _ = maintenance_started(connection.as_mut()) => Some(SessionEnd::BackendMaintenance),
+            end = self.keep_alive(&client_tx, &backend_tx) => Some(end),
+            _ = shutdown.cancelled() => Some(SessionEnd::ShuttingDown),
+        };
+
+        if let Some(end) = ended {
+            info!(%client_addr, session_id = self.session.id(), reason = ?end, "Closing WebSocket session");
+            let _ = client_tx
+                .lock()
+                .await
+                .send(Message::Close(Some(end.close_frame())))
+                .await;
+            let _ = backend_tx
+                .lock()
+                .await
+                .send(tungstenite::Message::Close(None))
+                .await;
+        }
+        if let Some(access_log) = &self.ctx.access_log {
+            access_log.record(AccessRecord::WebSocketClose {
+                session_id: self.session.id(),
+                client_ip: client_addr.ip(),
+                account: self.session.account().map(ToString::to_string),
+                route: self.route.name().to_string(),
+                duration_ms: elapsed_ms(self.opened_at),
+                messages_in: self.session.messages_in(),
+                messages_out: self.session.messages_out(),
+                reason: match ended {
+                    Some(end) => format!("{end:?}"),
+                    None => closed_by.to_string(),
+                },
+            });
+        }
+        if let Some(traffic_stats) = &self.ctx.traffic_stats {
+            let account = self.session.account().map(ToString::to_string);
+            traffic_stats.session_closed(self.route.name(), account.as_deref());
+        }
+    }
+
+    /// Re-checks the session's access whenever firewall rules or grants change, and every
+    /// `access_recheck_secs` if set, resolving once it is lost.
+    async fn watch_access(&self) -> SessionEnd {
+        let mut recheck = self
+            .ctx
+            .config()
+            .websocket
+            .access_recheck_secs
+            .map(|period| tokio::time::interval(Duration::from_secs(period.max(1))));
+        if let Some(recheck) = &mut recheck {
+            // The first tick completes immediately; access was just checked on upgrade.
+            recheck.tick().await;
+        }
+        let mut rule_changes = self.ctx.firewall.subscribe_rule_changes();
+
+        let ip = self.client_addr.ip();
+        let account = self.session.account();
+        loop {
+            let periodic = async {
+                match &mut recheck {
+                    Some(recheck) => {
+                        recheck.tick().await;
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            // The sender lives in the firewall, which outlives every session, so `changed`
+            // doesn't fail.
+            tokio::select! {
+                _ = periodic => {}
+                _ = rule_changes.changed() => {}
+            }
+            let decision = self
+                .ctx
+                .firewall
+                .check_access(&ip, account, AccessSurface::SessionRecheck)
+                .await;
+            if decision == AccessDecision::Allowed {
+                continue;
+            }
+            return if self.ctx.firewall.is_banned(&ip, account) {
+                SessionEnd::Banned
+            } else {
+                SessionEnd::AccessExpired
+            };
+        }
+    }
+
+    /// Pings the client and the backend every `ping_interval_secs`, resolving once either has
+    /// sent nothing for `idle_timeout_secs`. Never resolves without keepalives configured.
+    async fn keep_alive<B: Sink<tungstenite::Message>>(
+        &self,
+        client_tx: &ClientSink,
+        backend_tx: &BackendSink<B>,
+    ) -> SessionEnd {
+        let Some(keepalive) = &self.keepalive else {
+            return std::future::pending().await;
+        };
+        let client_addr = self.client_addr;
+        let idle_timeout = Duration::from_secs(keepalive.idle_timeout_secs);
+        let mut ping = tokio::time::interval(Duration::from_secs(keepalive.ping_interval_secs));
+        // The first tick completes immediately; both sides just connected.
+        ping.tick().await;
+        loop {
+            ping.tick().await;
+            if self.client_seen.lock().elapsed() >= idle_timeout {
+                warn!(%client_addr, "Closing WebSocket session whose client stopped responding");
+                return SessionEnd::ClientIdle;
+            }
+            if self.backend_seen.lock().elapsed() >= idle_timeout {
+                warn!(%client_addr, "Closing WebSocket session whose backend stopped responding");
+                return SessionEnd::BackendUnresponsive;
+            }
+            // Failed sends end the forwarding loops, which end the session
+            let _ = client_tx.lock().await.send(Message::Ping(Vec::new())).await;
+            let _ = backend_tx
+                .lock()
+                .await
+                .send(tungstenite::Message::Ping(Default::default()))
+                .await;
+        }
+    }
+
+    /// Forwards messages from the client to the backend, until either side closes or the
+    /// client breaks a message limit.
+    async fn client_to_backend<B: Sink<tungstenite::Message>>(
+        &self,
+        mut client_rx: SplitStream<WebSocket>,
+        client_tx: &ClientSink,
+        backend_tx: &BackendSink<B>,
+    ) -> Option<SessionEnd> {
+        let client_addr = self.client_addr;
+        while let Some(msg) = client_rx.next().await {
+            *self.client_seen.lock() = Instant::now();
+            self.session.record_inbound();
+            let violation = match &msg {
+                Ok(Message::Close(_)) | Err(_) => None,
+                Ok(msg) => self.check_message_limits(msg).await,
+            };
+            if let Some(end) = violation {
+                let _ = backend_tx
+                    .lock()
+                    .await
+                    .send(tungstenite::Message::Close(None))
+                    .await;
+                return Some(end);
+            }
+            if let (Some(meter), Ok(data @ (Message::Text(_) | Message::Binary(_)))) =
+                (&self.ctx.usage_meter, &msg)
+            {
+                meter.record_ws_inbound(&self.usage_subject, data_len(data));
+            }
+            // Backends accept JSON-RPC in binary frames too, so those are checked like text
+            // frames and forwarded as binary
+            let (msg, binary) = match msg {
+                Ok(Message::Binary(bin)) => match String::from_utf8(bin) {
+                    Ok(text) => (Ok(Message::Text(text)), true),
+                    Err(_) => {
+                        warn!(%client_addr, "Closing WebSocket session sending a binary frame that isn't UTF-8");
+                        let _ = backend_tx
+                            .lock()
+                            .await
+                            .send(tungstenite::Message::Close(None))
+                            .await;
+                        return Some(SessionEnd::UnsupportedBinary);
+                    }
+                },
+                msg => (msg, false),
+            };
+            match msg {
+                Ok(Message::Text(text)) => {
+                    let filtered = self
+                        .ctx
+                        .payload_filters
+                        .filter_request(&self.filter_ctx, text.as_bytes());
+                    let text = match filtered {
+                        Ok(filtered) => filtered.unwrap_or(text),
+                        Err(reason) => {
+                            warn!(%client_addr, %reason, "Payload filter rejected JSON-RPC request in WebSocket frame");
+                            if client_tx
+                                .lock()
+                                .await
+                                .send(Message::Text(filter_rejected_payload(&reason)))
+                                .await
+                                .is_err()
+                            {
+                                break;
+                            }
+                            continue;
+                        }
+                    };
+                    let text = self
+                        .route
+                        .method_rewriter
+                        .rewrite(text.as_bytes())
+                        .unwrap_or(text);
+                    if let Some(payload) = self.check_client_text(text.as_bytes()) {
+                        if client_tx
+                            .lock()
+                            .await
+                            .send(Message::Text(payload))
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                        continue;
+                    }
+                    if self.ctx.usage_meter.is_some() || self.ctx.daily_digest.is_some() {
+                        let methods = jsonrpc_methods(text.as_bytes());
+                        if let Some(meter) = &self.ctx.usage_meter {
+                            meter.record_calls(&self.usage_subject, &methods);
+                        }
+                        if let Some(daily_digest) = &self.ctx.daily_digest {
+                            daily_digest.record_calls(&methods);
+                        }
+                    }
+                    let forwarded = if binary {
+                        tungstenite::Message::Binary(text.into_bytes())
+                    } else {
+                        tungstenite::Message::Text(text)
+                    };
+                    if backend_tx.lock().await.send(forwarded).await.is_err() {
+                        warn!(%client_addr, "Failed sending message to backend, connection likely closed");
+                        break;
+                    }
+                }
+                Ok(Message::Ping(ping)) => {
+                    if backend_tx
+                        .lock()
+                        .await
+                        .send(tungstenite::Message::Ping(ping))
+                        .await
+                        .is_err()
+                    {
+                        warn!(%client_addr, "Failed sending Ping message to backend, connection likely closed");
+                        break;
+                    }
+                }
+                Ok(Message::Pong(pong)) => {
+                    if backend_tx
+                        .lock()
+                        .await
+                        .send(tungstenite::Message::Pong(pong))
+                        .await
+                        .is_err()
+                    {
+                        warn!(%client_addr, "Failed sending Pong message to backend, connection likely closed");
+                        break;
+                    }
+                }
+                Ok(Message::Close(_)) => {
+                    debug!(%client_addr, "Client closed WebSocket connection gracefully");
+                    let _ = backend_tx
+                        .lock()
+                        .await
+                        .send(tungstenite::Message::Close(None))
+                        .await;
+                    break;
+                }
+                Err(e) => {
+                    warn!(%client_addr, error = %e, "Error receiving message from client");
+                    let _ = backend_tx
+                        .lock()
+                        .await
+                        .send(tungstenite::Message::Close(None))
+                        .await;
+                    break;
+                }
+            }
+        }
+        debug!(%client_addr, "Client-to-Backend WebSocket forwarding task finished");
+        None
+    }
+
+    /// Forwards messages from the backend to the client.
+    async fn backend_to_client<B>(&self, mut backend_rx: SplitStream<B>, client_tx: &ClientSink)
+    where
+        B: Stream<Item = Result<tungstenite::Message, tungstenite::Error>>,
+    {
+        let client_addr = self.client_addr;
+        while let Some(msg) = backend_rx.next().await {
+            *self.backend_seen.lock() = Instant::now();
+            self.session.record_outbound();
+            let forwarded = match msg {
+                Ok(tungstenite::Message::Text(text)) => {
+                    self.observe_backend_text(text.as_bytes());
+                    let filtered = self
+                        .ctx
+                        .payload_filters
+                        .filter_response(&self.filter_ctx, text.as_bytes());
+                    Message::Text(filtered.unwrap_or(text))
+                }
+                Ok(tungstenite::Message::Binary(bin)) => Message::Binary(bin),
+                Ok(tungstenite::Message::Ping(ping)) => Message::Ping(ping),
+                Ok(tungstenite::Message::Pong(pong)) => Message::Pong(pong),
+                Ok(tungstenite::Message::Close(close)) => {
+                    debug!(%client_addr, "Backend closed WebSocket connection gracefully");
+                    let _ = client_tx
+                        .lock()
+                        .await
+                        .send(Message::Close(close.map(|cf| CloseFrame {
+                            code: cf.code.into(),
+                            reason: cf.reason,
+                        })))
+                        .await;
+                    break;
+                }
+                Ok(tungstenite::Message::Frame(_)) => {
+                    // Raw frames usually indicate lower-level control, ignore for basic proxying
+                    debug!(%client_addr, "Ignoring raw WebSocket frame from backend");
+                    continue;
+                }
+                Err(e) => {
+                    warn!(%client_addr, error = %e, "Error receiving message from backend");
+                    let _ = client_tx
+                        .lock()
+                        .await
+                        .send(Message::Close(Some(CloseFrame {
+                            code: close_code::ERROR,
+                            reason: "Backend error".into(),
+                        })))
+                        .await;
+                    break;
+                }
+            };
+            if let (Some(meter), Message::Text(_) | Message::Binary(_)) =
+                (&self.ctx.usage_meter, &forwarded)
+            {
+                meter.record_ws_outbound(&self.usage_subject, data_len(&forwarded));
+            }
+            if client_tx.lock().await.send(forwarded).await.is_err() {
+                warn!(%client_addr, "Failed sending message to client, connection likely closed");
+                break;
+            }
+        }
+        debug!(%client_addr, "Backend-to-Client WebSocket forwarding task finished");
+    }
+}
+
+/// Resolves once the backend of `connection` is drained for maintenance. Never resolves for
+/// sessions bridged over HTTP, whose calls each go to a backend in rotation.
+async fn maintenance_started(connection: Option<&mut UpstreamConnection>) {
+    match connection {
+        Some(connection) => connection.maintenance_started().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Payload size of a data message, for usage metering.
+fn data_len(msg: &Message) -> usize {
+    match msg {
+        Message::Text(text) => text.len(),
+        Message::Binary(bin) => bin.len(),
+        _ => 0,
+    }
+}
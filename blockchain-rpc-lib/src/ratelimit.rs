@@ -0,0 +1,117 @@
+use crate::config::RateLimitConfig;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Token-bucket rate limiter, per IP unless keyed otherwise.
+///
+/// Each key gets a bucket holding up to `burst` tokens, refilled at `requests_per_sec`.
+/// Every request consumes one token, or one per call for JSON-RPC batches. Callers may grant a
+/// loan, letting the bucket go into debt that later refills pay back first.
+#[derive(Debug)]
+pub struct RateLimiter<K = IpAddr> {
+    requests_per_sec: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<K, TokenBucket>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        RateLimiter {
+            requests_per_sec: config.requests_per_sec,
+            burst: f64::from(config.burst.max(1)),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether this limiter enforces the limits in `config`.
+    pub fn has_limits(&self, config: &RateLimitConfig) -> bool {
+        self.requests_per_sec == config.requests_per_sec
+            && self.burst == f64::from(config.burst.max(1))
+    }
+
+    pub fn requests_per_sec(&self) -> f64 {
+        self.requests_per_sec
+    }
+
+    pub fn burst(&self) -> f64 {
+        self.burst
+    }
+
+    /// Attempts to consume a token for `key`.
+    ///
+    /// Returns `Err` with the time until the next token becomes available if the bucket is empty.
+    pub fn check(&self, key: K) -> Result<(), Duration> {
+        self.check_n(key, 1)
+    }
+
+    /// Attempts to consume `tokens` tokens for `key` at once; none are consumed unless all are
+    /// available. Returns `Err` with the time until enough tokens are available, which is
+    /// `Duration::MAX` if `tokens` exceeds the burst.
+    pub fn check_n(&self, key: K, tokens: u32) -> Result<(), Duration> {
+        self.check_with_loan(key, tokens, 0)
+    }
+
+    /// Like [`Self::check_n`], but lets the bucket go up to `loan` tokens into debt.
+    pub fn check_with_loan(&self, key: K, tokens: u32, loan: u32) -> Result<(), Duration> {
+        let tokens = f64::from(tokens);
+        let loan = f64::from(loan);
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock();
+        let bucket = buckets.entry(key).or_insert(TokenBucket {
+            tokens: self.burst,
+            last_refill: now,
+        });
+
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.requests_per_sec).min(self.burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens + loan >= tokens {
+            bucket.tokens -= tokens;
+            return Ok(());
+        }
+        Err(self.retry_after(bucket.tokens, tokens, loan))
+    }
+
+    /// How long until a bucket holding `available` tokens can give `tokens` of them, going up
+    /// to `loan` tokens into debt.
+    pub(crate) fn retry_after(&self, available: f64, tokens: f64, loan: f64) -> Duration {
+        if self.requests_per_sec <= 0.0 || tokens > self.burst + loan {
+            return Duration::MAX;
+        }
+        let missing = tokens - loan - available;
+        Duration::from_secs_f64(missing / self.requests_per_sec)
+    }
+
+    /// The whole tokens `key` has left, without consuming any.
+    pub fn remaining(&self, key: &K) -> u32 {
+        let tokens = match self.buckets.lock().get(key) {
+            Some(bucket) => {
+                let elapsed = bucket.last_refill.elapsed().as_secs_f64();
+                (bucket.tokens + elapsed * self.requests_per_sec).min(self.burst)
+            }
+            None => self.burst,
+        };
+        tokens.max(0.0) as u32
+    }
+
+    /// Drops buckets that have refilled completely, as they are equivalent to a fresh bucket.
+    pub fn prune(&self) {
+        let now = Instant::now();
+        let rate = self.requests_per_sec;
+        let burst = self.burst;
+        self.buckets.lock().retain(|_, bucket| {
+            let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+            bucket.tokens + elapsed * rate < burst
+        });
+    }
+}
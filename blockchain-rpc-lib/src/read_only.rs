@@ -0,0 +1,113 @@
+use crate::Result;
+use crate::method_matcher::MethodMatcher;
+use crate::store::StateStore;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::warn;
+
+const READ_ONLY_NAMESPACE: &str = "gateway_mode";
+const READ_ONLY_KEY: &[u8] = b"read_only";
+
+/// Why and since when the gateway is read-only.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadOnlyStatus {
+    /// Shown to clients whose calls are rejected, e.g. `Backend upgrade`.
+    pub reason: Option<String>,
+    pub since: DateTime<Utc>,
+    /// Who switched it on: an admin account, `token`, or a job caller.
+    pub set_by: String,
+}
+
+/// Read-only mode: while on, calls to `rpc.read_only_methods` (transaction submission) are
+/// rejected with a JSON-RPC error and everything else is served, e.g. during a backend
+/// upgrade or an incident.
+///
+/// The mode is persisted in the state store, so it survives restarts. The method list follows
+/// the live configuration.
+#[derive(Debug)]
+pub struct ReadOnlyMode {
+    store: Arc<dyn StateStore>,
+    status: RwLock<Option<ReadOnlyStatus>>,
+    write_methods: RwLock<Arc<MethodMatcher<()>>>,
+}
+
+impl ReadOnlyMode {
+    /// Loads the persisted mode. An undecodable entry is ignored with a warning, leaving the
+    /// gateway writable.
+    pub fn load(store: Arc<dyn StateStore>, write_methods: &[String]) -> Result<Self> {
+        let status = match store.get(READ_ONLY_NAMESPACE, READ_ONLY_KEY)? {
+            Some(value) => match serde_json::from_slice(&value) {
+                Ok(status) => Some(status),
+                Err(e) => {
+                    warn!(error = %e, "Ignoring undecodable persisted read-only mode");
+                    None
+                }
+            },
+            None => None,
+        };
+        Ok(Self {
+            store,
+            status: RwLock::new(status),
+            write_methods: RwLock::new(Arc::new(compile(write_methods))),
+        })
+    }
+
+    /// Replaces the methods rejected while read-only, after a config reload.
+    pub fn reload(&self, write_methods: &[String]) {
+        *self.write_methods.write() = Arc::new(compile(write_methods));
+    }
+
+    /// Why the gateway is read-only, or `None` if it isn't.
+    pub fn status(&self) -> Option<ReadOnlyStatus> {
+        self.status.read().clone()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.status.read().is_some()
+    }
+
+    /// Switches read-only mode on, replacing the reason if it already was.
+    pub fn enable(&self, reason: Option<String>, set_by: &str) -> Result<ReadOnlyStatus> {
+        let status = ReadOnlyStatus {
+            reason,
+            since: Utc::now(),
+            set_by: set_by.to_string(),
+        };
+        self.store.insert(
+            READ_ONLY_NAMESPACE,
+            READ_ONLY_KEY,
+            &serde_json::to_vec(&status)?,
+        )?;
+        *self.status.write() = Some(status.clone());
+        Ok(status)
+    }
+
+    /// Switches read-only mode off. Returns false if it was off.
+    pub fn disable(&self) -> Result<bool> {
+        self.store.remove(READ_ONLY_NAMESPACE, READ_ONLY_KEY)?;
+        Ok(self.status.write().take().is_some())
+    }
+
+    /// Returns the first call of a JSON-RPC payload (single call or batch) rejected while
+    /// read-only, with the mode's status, if the gateway is read-only and there is one.
+    pub fn find_rejected_method(&self, payload: &[u8]) -> Option<(String, ReadOnlyStatus)> {
+        let status = self.status()?;
+        let write_methods = self.write_methods.read().clone();
+        let rejected = |call: &Value| {
+            let method = call.get("method")?.as_str()?;
+            write_methods.matches(method).then(|| method.to_string())
+        };
+        let method = match serde_json::from_slice::<Value>(payload).ok()? {
+            Value::Array(calls) => calls.iter().find_map(rejected),
+            call => rejected(&call),
+        }?;
+        Some((method, status))
+    }
+}
+
+fn compile(write_methods: &[String]) -> MethodMatcher<()> {
+    write_methods.iter().map(|pattern| (pattern, ())).collect()
+}
@@ -0,0 +1,404 @@
+use crate::Result;
+use crate::audit::{AuditLog, AuditRecord};
+use crate::config::{RelayConfig, RpcConfig};
+use crate::error::Error;
+use crate::method_matcher::MethodMatcher;
+use crate::store::StateStore;
+use axum::body::{Body, to_bytes};
+use axum::http::{Method, Request, StatusCode, header::ALLOW};
+use axum::response::{IntoResponse, Json, Response};
+use blueprint_sdk::crypto::tangle_pair_signer::TanglePairSigner;
+use blueprint_sdk::tangle_subxt::subxt::{self, Metadata, OnlineClient, PolkadotConfig};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sp_core::sr25519::Pair as Sr25519Pair;
+use sp_runtime::AccountId32;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+use url::Url;
+
+const SPEND_NAMESPACE: &str = "relay_spend";
+
+/// Why a relay request was refused.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RelayRejection {
+    #[error("Relaying requires an authenticated account")]
+    Unauthenticated,
+    #[error("Account {0} has no relay policy")]
+    NoPolicy(AccountId32),
+    #[error("Call {0} is not allowed by the account's relay policy")]
+    CallNotAllowed(String),
+    #[error("Estimated fee {fee} exceeds the per-transaction limit of {max}")]
+    FeeTooHigh { fee: u128, max: u128 },
+    #[error("Estimated fee {fee} exceeds the {remaining} left of today's relay budget")]
+    BudgetExhausted { fee: u128, remaining: u128 },
+}
+
+impl RelayRejection {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            RelayRejection::Unauthenticated => StatusCode::UNAUTHORIZED,
+            RelayRejection::BudgetExhausted { .. } => StatusCode::TOO_MANY_REQUESTS,
+            _ => StatusCode::FORBIDDEN,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RelayPolicy {
+    calls: MethodMatcher<()>,
+    max_fee: u128,
+    daily_fee_budget: u128,
+}
+
+/// Fees an account had relayed on `day`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct DailySpend {
+    day: NaiveDate,
+    fees: u128,
+}
+
+/// The relay policies of `[relay]`, and the fees relayed for each account today. Spending is
+/// persisted in the state store, so a restart doesn't reset an account's daily budget.
+#[derive(Debug)]
+pub struct RelayPolicies {
+    policies: HashMap<AccountId32, Arc<RelayPolicy>>,
+    store: Arc<dyn StateStore>,
+}
+
+impl RelayPolicies {
+    pub fn new(config: &RelayConfig, store: Arc<dyn StateStore>) -> Self {
+        let mut policies = HashMap::new();
+        for policy_config in &config.policies {
+            let policy = Arc::new(RelayPolicy {
+                calls: policy_config.calls.iter().map(|call| (call, ())).collect(),
+                max_fee: policy_config.max_fee,
+                daily_fee_budget: policy_config.daily_fee_budget,
+            });
+            for account in &policy_config.accounts {
+                policies.insert(account.clone(), policy.clone());
+            }
+        }
+        RelayPolicies { policies, store }
+    }
+
+    /// Checks that `account` may relay `call`, a `Pallet.call` name, before its fee is known.
+    pub fn check_call(
+        &self,
+        account: &AccountId32,
+        call: &str,
+    ) -> std::result::Result<(), RelayRejection> {
+        let policy = self.policy(account)?;
+        if !policy.calls.matches(call) {
+            return Err(RelayRejection::CallNotAllowed(call.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Checks that a transaction with an estimated `fee` fits `account`'s limits.
+    pub fn check_fee(
+        &self,
+        account: &AccountId32,
+        fee: u128,
+    ) -> std::result::Result<(), RelayRejection> {
+        let policy = self.policy(account)?;
+        if fee > policy.max_fee {
+            return Err(RelayRejection::FeeTooHigh {
+                fee,
+                max: policy.max_fee,
+            });
+        }
+        let remaining = policy
+            .daily_fee_budget
+            .saturating_sub(self.spent_today(account));
+        if fee > remaining {
+            return Err(RelayRejection::BudgetExhausted { fee, remaining });
+        }
+        Ok(())
+    }
+
+    /// Fees relayed for `account` so far today (UTC).
+    pub fn spent_today(&self, account: &AccountId32) -> u128 {
+        let today = Utc::now().date_naive();
+        match self.load_spend(account) {
+            Some(spend) if spend.day == today => spend.fees,
+            _ => 0,
+        }
+    }
+
+    /// Adds `fee` to `account`'s spending today, returning the new total.
+    pub fn record_spend(&self, account: &AccountId32, fee: u128) -> Result<u128> {
+        let spend = DailySpend {
+            day: Utc::now().date_naive(),
+            fees: self.spent_today(account).saturating_add(fee),
+        };
+        self.store.insert(
+            SPEND_NAMESPACE,
+            account.as_ref(),
+            &serde_json::to_vec(&spend)?,
+        )?;
+        Ok(spend.fees)
+    }
+
+    fn policy(&self, account: &AccountId32) -> std::result::Result<&RelayPolicy, RelayRejection> {
+        self.policies
+            .get(account)
+            .map(Arc::as_ref)
+            .ok_or_else(|| RelayRejection::NoPolicy(account.clone()))
+    }
+
+    fn load_spend(&self, account: &AccountId32) -> Option<DailySpend> {
+        match self.store.get(SPEND_NAMESPACE, account.as_ref()) {
+            Ok(value) => value.and_then(|value| serde_json::from_slice(&value).ok()),
+            Err(e) => {
+                warn!(%account, error = %e, "Failed to read relay spending");
+                None
+            }
+        }
+    }
+}
+
+/// What relay requests carry: a call of the backend chain, SCALE-encoded as in an extrinsic,
+/// i.e. pallet index, call index and arguments.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RelayRequest {
+    /// Hex, with or without `0x`.
+    pub call: String,
+}
+
+/// What a relayed transaction was submitted as.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayReceipt {
+    pub tx_hash: String,
+    /// `Pallet.call`.
+    pub call: String,
+    /// Estimated fee, paid by the relay signer.
+    pub fee: u128,
+    /// The account's relayed fees today, this transaction's included.
+    pub spent_today: u128,
+    pub signer: String,
+}
+
+/// Call data passed through to the extrinsic as is.
+struct RawCall(Vec<u8>);
+
+impl subxt::tx::Payload for RawCall {
+    fn encode_call_data_to(
+        &self,
+        _metadata: &Metadata,
+        out: &mut Vec<u8>,
+    ) -> std::result::Result<(), subxt::ext::subxt_core::Error> {
+        out.extend_from_slice(&self.0);
+        Ok(())
+    }
+}
+
+/// Signs calls POSTed by accounts with a relay policy with a dedicated keystore key, which pays
+/// their fees, and submits them to the backend node. Transactions are created and submitted
+/// one at a time, so concurrent requests never reuse the signer's nonce.
+pub struct TransactionRelay {
+    path: String,
+    signer: TanglePairSigner<Sr25519Pair>,
+    signer_account: AccountId32,
+    submit_url: Url,
+    policies: RelayPolicies,
+    /// Connected on first use, and again after a failed submission.
+    client: tokio::sync::Mutex<Option<OnlineClient<PolkadotConfig>>>,
+}
+
+impl TransactionRelay {
+    pub fn new(
+        config: &RelayConfig,
+        rpc: &RpcConfig,
+        pair: Sr25519Pair,
+        store: Arc<dyn StateStore>,
+    ) -> Result<Self> {
+        let submit_url = match &config.submit_url {
+            Some(url) => url.clone(),
+            None => submit_url(&rpc.proxy_to_url)?,
+        };
+        Ok(TransactionRelay {
+            path: config.path.clone(),
+            signer: TanglePairSigner::new(pair),
+            signer_account: config.signer.clone(),
+            submit_url,
+            policies: RelayPolicies::new(config, store),
+            client: tokio::sync::Mutex::new(None),
+        })
+    }
+
+    /// Whether `path` is the relay's, rather than proxied.
+    pub fn serves(&self, path: &str) -> bool {
+        path.trim_end_matches('/') == self.path.trim_end_matches('/')
+    }
+
+    pub fn policies(&self) -> &RelayPolicies {
+        &self.policies
+    }
+
+    /// Answers a relay request from `account`: checks the call against its policy, estimates
+    /// the fee and checks it against its limits, then signs and submits the transaction.
+    pub async fn handle(
+        &self,
+        account: Option<&AccountId32>,
+        audit: Option<&AuditLog>,
+        req: Request<Body>,
+    ) -> Response {
+        if req.method() != Method::POST {
+            return (StatusCode::METHOD_NOT_ALLOWED, [(ALLOW, "POST")]).into_response();
+        }
+        let Some(account) = account else {
+            return rejected(RelayRejection::Unauthenticated);
+        };
+        // Bodies are capped by the listener's request limits already.
+        let request: RelayRequest = match to_bytes(req.into_body(), usize::MAX)
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|body| serde_json::from_slice(&body).map_err(|e| e.to_string()))
+        {
+            Ok(request) => request,
+            Err(e) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid relay request: {e}"),
+                )
+                    .into_response();
+            }
+        };
+        let call = match hex::decode(request.call.trim_start_matches("0x")) {
+            Ok(call) => call,
+            Err(e) => {
+                return (StatusCode::BAD_REQUEST, format!("Invalid call data: {e}"))
+                    .into_response();
+            }
+        };
+        match self.relay(account, call).await {
+            Ok(receipt) => {
+                info!(%account, call = %receipt.call, tx_hash = %receipt.tx_hash, fee = %receipt.fee, "Relayed transaction");
+                if let Some(audit) = audit {
+                    audit.record(AuditRecord::Relay {
+                        account: account.to_string(),
+                        call: receipt.call.clone(),
+                        tx_hash: receipt.tx_hash.clone(),
+                        fee: receipt.fee.to_string(),
+                    });
+                }
+                Json(receipt).into_response()
+            }
+            Err(RelayFailure::Rejected(rejection)) => {
+                warn!(%account, reason = %rejection, "Refused to relay transaction");
+                rejected(rejection)
+            }
+            Err(RelayFailure::InvalidCall(reason)) => {
+                (StatusCode::BAD_REQUEST, reason).into_response()
+            }
+            Err(RelayFailure::Backend(e)) => {
+                warn!(%account, error = %e, "Failed to relay transaction");
+                (StatusCode::BAD_GATEWAY, e.to_string()).into_response()
+            }
+        }
+    }
+
+    async fn relay(
+        &self,
+        account: &AccountId32,
+        call: Vec<u8>,
+    ) -> std::result::Result<RelayReceipt, RelayFailure> {
+        let mut client = self.client.lock().await;
+        let online = match client.as_ref() {
+            Some(online) => online.clone(),
+            None => {
+                let online = self.connect().await.map_err(RelayFailure::Backend)?;
+                *client = Some(online.clone());
+                online
+            }
+        };
+        let name = call_name(&online.metadata(), &call).ok_or_else(|| {
+            RelayFailure::InvalidCall("Call data names no call of the chain".to_string())
+        })?;
+        self.policies
+            .check_call(account, &name)
+            .map_err(RelayFailure::Rejected)?;
+
+        let result = async {
+            let extrinsic = online
+                .tx()
+                .create_signed(&RawCall(call), &self.signer, Default::default())
+                .await?;
+            let fee = extrinsic.partial_fee_estimate().await?;
+            if let Err(rejection) = self.policies.check_fee(account, fee) {
+                return Ok(Err(rejection));
+            }
+            let tx_hash = extrinsic.submit().await?;
+            Ok::<_, subxt::Error>(Ok((tx_hash, fee)))
+        }
+        .await;
+        let (tx_hash, fee) = match result {
+            Ok(Ok(submitted)) => submitted,
+            Ok(Err(rejection)) => return Err(RelayFailure::Rejected(rejection)),
+            Err(e) => {
+                // Reconnect next time, in case the connection is what failed.
+                *client = None;
+                return Err(RelayFailure::Backend(Error::RelayFailed(e.to_string())));
+            }
+        };
+        let spent_today = self
+            .policies
+            .record_spend(account, fee)
+            .map_err(RelayFailure::Backend)?;
+        Ok(RelayReceipt {
+            tx_hash: format!("{tx_hash:?}"),
+            call: name,
+            fee,
+            spent_today,
+            signer: self.signer_account.to_string(),
+        })
+    }
+
+    async fn connect(&self) -> Result<OnlineClient<PolkadotConfig>> {
+        let url = self.submit_url.as_str();
+        let client = if self.submit_url.scheme() == "wss" {
+            OnlineClient::<PolkadotConfig>::from_url(url).await
+        } else {
+            OnlineClient::<PolkadotConfig>::from_insecure_url(url).await
+        };
+        client.map_err(|e| Error::RelayFailed(format!("Failed to connect to {url}: {e}")))
+    }
+}
+
+enum RelayFailure {
+    Rejected(RelayRejection),
+    InvalidCall(String),
+    Backend(Error),
+}
+
+fn rejected(rejection: RelayRejection) -> Response {
+    (rejection.status(), rejection.to_string()).into_response()
+}
+
+/// `Pallet.call` for SCALE-encoded call data, from the pallet and call indices it starts with.
+fn call_name(metadata: &Metadata, call: &[u8]) -> Option<String> {
+    let [pallet_index, call_index, ..] = call else {
+        return None;
+    };
+    let pallet = metadata.pallet_by_index(*pallet_index)?;
+    let variant = pallet.call_variant_by_index(*call_index)?;
+    Some(format!("{}.{}", pallet.name(), variant.name))
+}
+
+/// The backend URL with a WebSocket scheme, which transactions are submitted over.
+fn submit_url(proxy_to_url: &Url) -> Result<Url> {
+    let mut url = proxy_to_url.clone();
+    let scheme = match url.scheme() {
+        "http" => Some("ws"),
+        "https" => Some("wss"),
+        _ => None,
+    };
+    if let Some(scheme) = scheme {
+        url.set_scheme(scheme).map_err(|()| {
+            Error::AddressParseError(format!("Invalid backend URL {proxy_to_url}"))
+        })?;
+    }
+    Ok(url)
+}
@@ -0,0 +1,114 @@
+use crate::Result;
+use crate::store::StateStore;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+const PROCESSED_CALLS_NAMESPACE: &str = "processed_calls";
+
+/// Most call IDs a job keeps above its watermark. Call IDs are shared by every job and service
+/// on the chain, so a job's own calls rarely form a contiguous run; past this many, the
+/// watermark moves up to the lowest of them, and calls it passes over are treated as processed.
+pub const MAX_CALLS_ABOVE_WATERMARK: usize = 1024;
+
+/// The calls processed for one job.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JobCalls {
+    /// Every call at or below this ID counts as processed.
+    #[serde(default)]
+    watermark: Option<u64>,
+    /// Call IDs above the watermark processed successfully.
+    #[serde(default)]
+    above: BTreeSet<u64>,
+}
+
+impl JobCalls {
+    fn contains(&self, call_id: u64) -> bool {
+        self.watermark.is_some_and(|watermark| call_id <= watermark)
+            || self.above.contains(&call_id)
+    }
+
+    fn insert(&mut self, call_id: u64) {
+        if self.contains(call_id) {
+            return;
+        }
+        self.above.insert(call_id);
+        if self.above.len() > MAX_CALLS_ABOVE_WATERMARK {
+            self.watermark = self.above.pop_first();
+        }
+        // Fold the run of calls directly above the watermark into it
+        while let Some(&next) = self.above.first() {
+            let follows = match self.watermark {
+                Some(watermark) => watermark.checked_add(1) == Some(next),
+                None => next == 0,
+            };
+            if !follows {
+                break;
+            }
+            self.watermark = self.above.pop_first();
+        }
+    }
+}
+
+/// Persistent record of the job calls processed successfully.
+///
+/// Handlers consult this before mutating state, which makes re-delivered job calls (after a
+/// restart or reorg) no-ops from the firewall's perspective. Each job keeps a watermark and
+/// the call IDs above it, so a call that failed stays eligible for redelivery after a later
+/// call succeeds, until [`MAX_CALLS_ABOVE_WATERMARK`] later calls have. Each job's calls are
+/// kept in the state store under its ID.
+#[derive(Debug)]
+pub struct ProcessedCalls {
+    store: Arc<dyn StateStore>,
+    calls: Mutex<HashMap<u64, JobCalls>>,
+}
+
+impl ProcessedCalls {
+    /// Loads the calls recorded in `store`, starting empty if there are none. A record that
+    /// fails to decode is an error rather than a reason to start over, which would replay every
+    /// call.
+    pub fn load(store: Arc<dyn StateStore>) -> Result<Self> {
+        let mut calls = HashMap::new();
+        for (key, value) in store.entries(PROCESSED_CALLS_NAMESPACE)? {
+            let job_id = <[u8; 8]>::try_from(key.as_slice())
+                .map(u64::from_be_bytes)
+                .map_err(|_| corrupt(format!("undecodable job ID {key:?}")))?;
+            let job_calls: JobCalls = serde_json::from_slice(&value)
+                .map_err(|e| corrupt(format!("job {job_id}: {e}")))?;
+            calls.insert(job_id, job_calls);
+        }
+        Ok(Self {
+            store,
+            calls: Mutex::new(calls),
+        })
+    }
+
+    /// Returns true if `call_id` for `job_id` was already processed.
+    pub fn is_processed(&self, job_id: u64, call_id: u64) -> bool {
+        self.calls
+            .lock()
+            .get(&job_id)
+            .is_some_and(|calls| calls.contains(call_id))
+    }
+
+    /// Records `call_id` as processed for `job_id` and persists the record. Call only after the
+    /// job has been applied successfully.
+    pub fn mark_processed(&self, job_id: u64, call_id: u64) -> Result<()> {
+        let mut calls = self.calls.lock();
+        let job_calls = calls.entry(job_id).or_default();
+        job_calls.insert(call_id);
+        self.store.insert(
+            PROCESSED_CALLS_NAMESPACE,
+            &job_id.to_be_bytes(),
+            &serde_json::to_vec(job_calls)?,
+        )
+    }
+}
+
+fn corrupt(detail: String) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("Corrupt processed calls record: {detail}"),
+    )
+}
@@ -0,0 +1,67 @@
+use axum::body::Body;
+use axum::http::{HeaderMap, HeaderValue, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use rand::RngCore;
+use std::fmt;
+
+/// Header carrying the request ID, to the backend and back to the client.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest request ID accepted from a client.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Identifies one request across the gateway's tracing output, access log and the backend's
+/// logs, and is returned to the client in `X-Request-Id`.
+#[derive(Debug, Clone)]
+pub struct RequestId(HeaderValue);
+
+impl RequestId {
+    /// The client's `X-Request-Id`, if it is 1 to 128 characters of letters, digits and
+    /// `-_.:/+=`.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let value = headers.get(REQUEST_ID_HEADER)?;
+        let bytes = value.as_bytes();
+        let valid = !bytes.is_empty()
+            && bytes.len() <= MAX_REQUEST_ID_LEN
+            && bytes
+                .iter()
+                .all(|b| b.is_ascii_alphanumeric() || b"-_.:/+=".contains(b));
+        valid.then(|| RequestId(value.clone()))
+    }
+
+    /// A new random ID: 32 hex digits.
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        RequestId(HeaderValue::from_str(&hex::encode(bytes)).expect("hex is a valid header value"))
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only ever built from visible ASCII
+        self.0.to_str().unwrap_or_default()
+    }
+
+    /// Writes the ID into an outgoing request's or a response's headers.
+    pub fn inject(&self, headers: &mut HeaderMap) {
+        headers.insert(REQUEST_ID_HEADER, self.0.clone());
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Gives every request an ID, the client's `X-Request-Id` if it sent a usable one: the ID is
+/// attached to the request for the tracing span and the access log, forwarded to the backend
+/// and returned in the response's `X-Request-Id`.
+pub(crate) async fn assign_request_id(mut req: Request<Body>, next: Next) -> Response {
+    let request_id = RequestId::from_headers(req.headers()).unwrap_or_else(RequestId::generate);
+    request_id.inject(req.headers_mut());
+    req.extensions_mut().insert(request_id.clone());
+    let mut response = next.run(req).await;
+    request_id.inject(response.headers_mut());
+    response
+}
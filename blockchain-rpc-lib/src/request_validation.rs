@@ -0,0 +1,246 @@
+use crate::config::{MethodSchemaConfig, ParamType, ParamsStructure, RequestValidationConfig};
+use crate::method_matcher::MethodMatcher;
+use serde_json::{Map, Value, json};
+use std::fmt;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Why a JSON-RPC payload was rejected, as a JSON-RPC error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidRequest {
+    /// `-32700` (parse error), `-32600` (invalid request) or `-32602` (invalid params).
+    pub code: i64,
+    pub message: String,
+    /// The id of the rejected call, or `null` if it has no usable one or is part of a batch.
+    pub id: Value,
+}
+
+impl InvalidRequest {
+    fn new(code: i64, id: &Value, message: impl Into<String>) -> Self {
+        InvalidRequest {
+            code,
+            message: message.into(),
+            id: id.clone(),
+        }
+    }
+
+    /// The JSON-RPC error response the payload is answered with.
+    pub fn payload(&self) -> String {
+        json!({
+            "jsonrpc": "2.0",
+            "id": self.id,
+            "error": {
+                "code": self.code,
+                "message": self.message,
+            },
+        })
+        .to_string()
+    }
+}
+
+impl fmt::Display for InvalidRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+/// Strict checks from `[rpc.validation]` on JSON-RPC payloads before they are proxied: every
+/// call is an object with `jsonrpc: "2.0"`, a string, integer or null `id` (or none, for
+/// notifications), a method name of allowed characters and length, and array or object params.
+/// Calls to methods with a schema have their params checked against it.
+///
+/// Methods are matched like the method filter's patterns: the exact pattern wins, then the
+/// longest prefix.
+#[derive(Debug, Clone)]
+pub struct RequestValidator {
+    max_method_length: usize,
+    schemas: MethodMatcher<MethodSchemaConfig>,
+}
+
+impl RequestValidator {
+    pub fn new(config: &RequestValidationConfig) -> Self {
+        RequestValidator {
+            max_method_length: config.max_method_length,
+            schemas: config
+                .methods
+                .iter()
+                .map(|(pattern, schema)| (pattern, schema.clone()))
+                .collect(),
+        }
+    }
+
+    /// Checks a JSON-RPC payload, a single call or a batch. A batch is rejected as a whole
+    /// with the first invalid call it contains.
+    pub fn validate(&self, payload: &[u8]) -> Result<(), InvalidRequest> {
+        let value: Value = serde_json::from_slice(payload).map_err(|e| {
+            InvalidRequest::new(PARSE_ERROR, &Value::Null, format!("Parse error: {e}"))
+        })?;
+        match value {
+            Value::Array(calls) if calls.is_empty() => Err(InvalidRequest::new(
+                INVALID_REQUEST,
+                &Value::Null,
+                "Empty batch",
+            )),
+            Value::Array(calls) => calls.iter().enumerate().try_for_each(|(index, call)| {
+                self.validate_call(call).map_err(|invalid| InvalidRequest {
+                    message: format!("Batch call {index}: {}", invalid.message),
+                    id: Value::Null,
+                    ..invalid
+                })
+            }),
+            call => self.validate_call(&call),
+        }
+    }
+
+    fn validate_call(&self, call: &Value) -> Result<(), InvalidRequest> {
+        let Some(call) = call.as_object() else {
+            return Err(InvalidRequest::new(
+                INVALID_REQUEST,
+                &Value::Null,
+                "Request must be an object",
+            ));
+        };
+        // The id is checked first, so later errors can answer with it
+        let id = match call.get("id") {
+            None => Value::Null,
+            Some(id @ (Value::Null | Value::String(_))) => id.clone(),
+            Some(Value::Number(id)) if id.is_i64() || id.is_u64() => Value::Number(id.clone()),
+            Some(_) => {
+                return Err(InvalidRequest::new(
+                    INVALID_REQUEST,
+                    &Value::Null,
+                    "id must be a string, an integer or null",
+                ));
+            }
+        };
+        let invalid = |message: String| InvalidRequest::new(INVALID_REQUEST, &id, message);
+
+        if call.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+            return Err(invalid(r#"jsonrpc must be "2.0""#.to_string()));
+        }
+        let Some(method) = call.get("method").and_then(Value::as_str) else {
+            return Err(invalid("method must be a string".to_string()));
+        };
+        if method.is_empty() || method.len() > self.max_method_length {
+            return Err(invalid(format!(
+                "method must be 1 to {} characters long",
+                self.max_method_length
+            )));
+        }
+        if !method
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'.' | b'-' | b':'))
+        {
+            return Err(invalid(format!(
+                "method {method:?} may only contain letters, digits, `_`, `.`, `-` and `:`"
+            )));
+        }
+        let params = call.get("params");
+        if params.is_some_and(|params| !params.is_array() && !params.is_object()) {
+            return Err(invalid("params must be an array or an object".to_string()));
+        }
+
+        match self.schemas.lookup(method) {
+            Some(schema) => check_params(method, schema, params)
+                .map_err(|message| InvalidRequest::new(INVALID_PARAMS, &id, message)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Checks a call's params, an array, an object or none, against its method's schema.
+fn check_params(
+    method: &str,
+    schema: &MethodSchemaConfig,
+    params: Option<&Value>,
+) -> Result<(), String> {
+    match (schema.params, params) {
+        (ParamsStructure::None, Some(Value::Array(params))) if params.is_empty() => Ok(()),
+        (ParamsStructure::None, Some(_)) => Err(format!("{method} takes no params")),
+        (ParamsStructure::Positional, Some(Value::Object(_))) => {
+            Err(format!("{method} takes positional params"))
+        }
+        (ParamsStructure::Named, Some(Value::Array(params))) if !params.is_empty() => {
+            Err(format!("{method} takes named params"))
+        }
+        (_, Some(Value::Object(params))) => check_named(method, schema, params),
+        (ParamsStructure::Named, _) => check_named(method, schema, &Map::new()),
+        (_, Some(Value::Array(params))) => check_positional(method, schema, params),
+        (_, _) => check_positional(method, schema, &[]),
+    }
+}
+
+fn check_positional(
+    method: &str,
+    schema: &MethodSchemaConfig,
+    params: &[Value],
+) -> Result<(), String> {
+    if params.len() < schema.min_params {
+        return Err(format!(
+            "{method} takes at least {} params, got {}",
+            schema.min_params,
+            params.len()
+        ));
+    }
+    if let Some(max) = schema.max_params.filter(|max| params.len() > *max) {
+        return Err(format!(
+            "{method} takes at most {max} params, got {}",
+            params.len()
+        ));
+    }
+    for (position, (param, expected)) in params.iter().zip(&schema.param_types).enumerate() {
+        let optional = position >= schema.min_params;
+        if !(optional && param.is_null()) && !has_type(param, *expected) {
+            return Err(format!(
+                "Param {position} of {method} must be of type {}",
+                type_name(*expected)
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_named(
+    method: &str,
+    schema: &MethodSchemaConfig,
+    params: &Map<String, Value>,
+) -> Result<(), String> {
+    match schema
+        .required_keys
+        .iter()
+        .find(|key| !params.contains_key(key.as_str()))
+    {
+        Some(key) => Err(format!("{method} requires param {key:?}")),
+        None => Ok(()),
+    }
+}
+
+fn has_type(param: &Value, expected: ParamType) -> bool {
+    match expected {
+        ParamType::Any => true,
+        ParamType::String => param.is_string(),
+        ParamType::Hex => param.as_str().is_some_and(|param| {
+            param
+                .strip_prefix("0x")
+                .is_some_and(|digits| digits.bytes().all(|byte| byte.is_ascii_hexdigit()))
+        }),
+        ParamType::Number => param.is_number(),
+        ParamType::Boolean => param.is_boolean(),
+        ParamType::Object => param.is_object(),
+        ParamType::Array => param.is_array(),
+    }
+}
+
+fn type_name(param_type: ParamType) -> &'static str {
+    match param_type {
+        ParamType::Any => "any",
+        ParamType::String => "string",
+        ParamType::Hex => "hex string",
+        ParamType::Number => "number",
+        ParamType::Boolean => "boolean",
+        ParamType::Object => "object",
+        ParamType::Array => "array",
+    }
+}
@@ -0,0 +1,184 @@
+use crate::Result;
+use crate::config::RoleConfig;
+use crate::error::Error;
+use crate::method_filter::checked_calls;
+use crate::method_matcher::MethodMatcher;
+use crate::store::StateStore;
+use parking_lot::RwLock;
+use sp_runtime::AccountId32;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use tracing::warn;
+
+const ROLES_NAMESPACE: &str = "account_roles";
+
+/// Roles one account may hold.
+pub const MAX_ROLES_PER_ACCOUNT: usize = 16;
+
+/// Role names, e.g. `submitter`, `debug`.
+pub type Roles = BTreeSet<String>;
+
+/// The roles `[roles]` requires per method pattern. A method matched by a pattern may only be
+/// called by accounts holding one of the roles listing the most specific matching pattern;
+/// methods no role lists are open to every client.
+#[derive(Debug, Clone, Default)]
+pub struct MethodRoles {
+    required: MethodMatcher<Roles>,
+}
+
+impl MethodRoles {
+    pub fn new(config: &HashMap<String, RoleConfig>) -> Self {
+        let mut patterns: HashMap<&str, Roles> = HashMap::new();
+        for (role, role_config) in config {
+            for pattern in &role_config.methods {
+                patterns
+                    .entry(pattern.as_str())
+                    .or_default()
+                    .insert(role.clone());
+            }
+        }
+        MethodRoles {
+            required: patterns.into_iter().collect(),
+        }
+    }
+
+    /// Returns true if no method requires a role.
+    pub fn is_empty(&self) -> bool {
+        self.required.is_empty()
+    }
+
+    /// The roles allowed to call `method`, any one of which is needed. `None` if it is open.
+    pub fn required_roles(&self, method: &str) -> Option<&Roles> {
+        self.required.lookup(method)
+    }
+
+    /// Checks a single method name against the roles an account holds.
+    pub fn is_method_allowed(&self, method: &str, roles: Option<&Roles>) -> bool {
+        match self.required_roles(method) {
+            Some(required) => roles.is_some_and(|roles| !required.is_disjoint(roles)),
+            None => true,
+        }
+    }
+
+    /// Returns the first method in a JSON-RPC payload (single call or batch) that `roles`
+    /// don't allow, if any. While any method is restricted, a payload whose calls can't all be
+    /// read is refused as a whole, named by what's wrong with it.
+    pub fn find_unauthorized_method(
+        &self,
+        payload: &[u8],
+        roles: Option<&Roles>,
+    ) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+        match checked_calls(payload) {
+            Ok(calls) => calls
+                .into_iter()
+                .map(|(method, _)| method)
+                .find(|method| !self.is_method_allowed(method, roles)),
+            Err(unreadable) => Some(unreadable),
+        }
+    }
+}
+
+/// Operator-assigned roles per account, unlocking the methods `[roles]` restricts, e.g.
+/// transaction submission or unsafe debug calls, while every other method stays open.
+///
+/// Assignments are persisted in the state store and written through on every change. The
+/// method requirements follow the live configuration.
+#[derive(Debug)]
+pub struct AccountRoles {
+    store: Arc<dyn StateStore>,
+    roles: RwLock<HashMap<AccountId32, Arc<Roles>>>,
+    methods: RwLock<Arc<MethodRoles>>,
+}
+
+impl AccountRoles {
+    /// Loads the roles persisted in `store`. Entries that fail to decode are skipped with a
+    /// warning.
+    pub fn load(store: Arc<dyn StateStore>, config: &HashMap<String, RoleConfig>) -> Result<Self> {
+        let mut roles = HashMap::new();
+        for (key, value) in store.entries(ROLES_NAMESPACE)? {
+            let account = <[u8; 32]>::try_from(key.as_slice())
+                .ok()
+                .map(AccountId32::new);
+            match (account, serde_json::from_slice::<Roles>(&value)) {
+                (Some(account), Ok(decoded)) => {
+                    roles.insert(account, Arc::new(decoded));
+                }
+                _ => warn!(key = ?key, "Skipping undecodable persisted account roles"),
+            }
+        }
+        Ok(Self {
+            store,
+            roles: RwLock::new(roles),
+            methods: RwLock::new(Arc::new(MethodRoles::new(config))),
+        })
+    }
+
+    /// Replaces the method requirements, after a config reload.
+    pub fn reload(&self, config: &HashMap<String, RoleConfig>) {
+        *self.methods.write() = Arc::new(MethodRoles::new(config));
+    }
+
+    /// The current method requirements.
+    pub fn methods(&self) -> Arc<MethodRoles> {
+        self.methods.read().clone()
+    }
+
+    pub fn get(&self, account: &AccountId32) -> Option<Arc<Roles>> {
+        self.roles.read().get(account).cloned()
+    }
+
+    /// Every account holding a role.
+    pub fn all(&self) -> BTreeMap<String, Roles> {
+        self.roles
+            .read()
+            .iter()
+            .map(|(account, roles)| (account.to_string(), (**roles).clone()))
+            .collect()
+    }
+
+    /// Returns the first method in a JSON-RPC payload that `account` (`None` for clients
+    /// without one) may not call, if any.
+    pub fn find_unauthorized_method(
+        &self,
+        payload: &[u8],
+        account: Option<&AccountId32>,
+    ) -> Option<String> {
+        let methods = self.methods();
+        if methods.is_empty() {
+            return None;
+        }
+        let roles = account.and_then(|account| self.get(account));
+        methods.find_unauthorized_method(payload, roles.as_deref())
+    }
+
+    /// Replaces an account's roles, each of which must be defined in `config`. An empty set
+    /// removes them.
+    pub fn set(
+        &self,
+        account: &AccountId32,
+        roles: Roles,
+        config: &HashMap<String, RoleConfig>,
+    ) -> Result<()> {
+        if roles.len() > MAX_ROLES_PER_ACCOUNT {
+            return Err(Error::InvalidRoles(format!(
+                "at most {MAX_ROLES_PER_ACCOUNT} roles per account"
+            )));
+        }
+        if let Some(role) = roles.iter().find(|role| !config.contains_key(*role)) {
+            return Err(Error::InvalidRoles(format!("role {role} is not defined")));
+        }
+        let key: &[u8] = account.as_ref();
+        if roles.is_empty() {
+            self.store.remove(ROLES_NAMESPACE, key)?;
+            self.roles.write().remove(account);
+        } else {
+            self.store
+                .insert(ROLES_NAMESPACE, key, &serde_json::to_vec(&roles)?)?;
+            self.roles.write().insert(account.clone(), Arc::new(roles));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+use crate::Result;
+use crate::config::RuleSyncConfig;
+use crate::context::TemporaryAccessRecord;
+use crate::error::Error;
+use crate::firewall::Firewall;
+use crate::jobs::SYNC_RULES_JOB_ID;
+use crate::jobs::allow_access::AccessTarget;
+use crate::jobs::pay_for_access::Beneficiary;
+use crate::store::StateStore;
+use blueprint_sdk::contexts::tangle::TangleClientContext;
+use blueprint_sdk::crypto::tangle_pair_signer::TanglePairSigner;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::serde::to_field;
+use blueprint_sdk::tangle_subxt::tangle_testnet_runtime::api;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sp_core::sr25519::Pair as Sr25519Pair;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::interval;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+const OUTBOX_NAMESPACE: &str = "rule_sync_outbox";
+
+/// A change to the dynamic rules, as carried by a `sync_rules` job call.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RuleMutation {
+    Allow {
+        target: AccessTarget,
+    },
+    Revoke {
+        target: AccessTarget,
+    },
+    Ban {
+        target: AccessTarget,
+    },
+    /// A temporary grant, with Unix timestamps so every instance ends it at the same time.
+    GrantTemporary {
+        target: AccessTarget,
+        granted_at: i64,
+        expires_at: i64,
+        #[serde(default)]
+        plan: Option<String>,
+    },
+}
+
+impl RuleMutation {
+    /// The action recorded in the audit log, e.g. `allow`.
+    pub fn action(&self) -> &'static str {
+        match self {
+            RuleMutation::Allow { .. } => "allow",
+            RuleMutation::Revoke { .. } => "revoke",
+            RuleMutation::Ban { .. } => "ban",
+            RuleMutation::GrantTemporary { .. } => "grant_temporary",
+        }
+    }
+
+    pub fn target(&self) -> &AccessTarget {
+        match self {
+            RuleMutation::Allow { target }
+            | RuleMutation::Revoke { target }
+            | RuleMutation::Ban { target }
+            | RuleMutation::GrantTemporary { target, .. } => target,
+        }
+    }
+
+    /// Applies the change to `firewall`. Every change is idempotent, so instances that
+    /// already made it, including the one it came from, are left as they are. A temporary
+    /// grant that has expired in the meantime is skipped.
+    pub async fn apply(&self, firewall: &Firewall) -> Result<()> {
+        let target = Beneficiary::parse(self.target())?;
+        match (self, target) {
+            (RuleMutation::Allow { .. }, Beneficiary::Ip(ip_network)) => {
+                firewall.add_ip_rule(ip_network).await
+            }
+            (RuleMutation::Allow { .. }, Beneficiary::Account(account)) => {
+                firewall.add_account_rule(account).await
+            }
+            (RuleMutation::Revoke { .. }, Beneficiary::Ip(ip_network)) => {
+                firewall.remove_ip_rule(ip_network).await
+            }
+            (RuleMutation::Revoke { .. }, Beneficiary::Account(account)) => {
+                firewall.remove_account_rule(account).await
+            }
+            (RuleMutation::Ban { .. }, Beneficiary::Ip(ip_network)) => {
+                firewall.ban_ip(ip_network).await
+            }
+            (RuleMutation::Ban { .. }, Beneficiary::Account(account)) => {
+                firewall.ban_account(account).await
+            }
+            (
+                RuleMutation::GrantTemporary {
+                    granted_at,
+                    expires_at,
+                    plan,
+                    ..
+                },
+                target,
+            ) => {
+                let (Some(granted_at), Some(expires_at)) = (
+                    DateTime::<Utc>::from_timestamp(*granted_at, 0),
+                    DateTime::<Utc>::from_timestamp(*expires_at, 0),
+                ) else {
+                    return Err(Error::InvalidJobInput(
+                        "Invalid temporary grant timestamps".to_string(),
+                    ));
+                };
+                if expires_at <= Utc::now() {
+                    debug!(target = ?self.target(), "Skipping synchronized grant that has expired");
+                    return Ok(());
+                }
+                let record = TemporaryAccessRecord {
+                    granted_at,
+                    expires_at,
+                    plan: plan.clone(),
+                    extensions: 0,
+                };
+                match target {
+                    Beneficiary::Ip(ip_network) => {
+                        firewall.grant_temporary_ip_access(ip_network, record).await
+                    }
+                    Beneficiary::Account(account) => {
+                        firewall.grant_temporary_access(account, record).await
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Input of the `sync_rules` job: rule changes made on one operator's instance, in order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncRulesInput {
+    pub mutations: Vec<RuleMutation>,
+}
+
+/// Shares the rule changes made through this instance's admin API with the other operators
+/// of the service.
+///
+/// Jobs reach every operator's instance already, as each one's producer sees every job call.
+/// Admin API changes only happen locally, so they are queued here and submitted on-chain in
+/// batches as `sync_rules` job calls, which every instance, this one included, then applies.
+/// The queue is persisted in the state store until a batch is submitted, so changes made
+/// while Tangle is unreachable are shared once it is back.
+#[derive(Debug)]
+pub struct RuleSync {
+    store: Arc<dyn StateStore>,
+    max_batch: usize,
+    submit_interval: Duration,
+    outbox: Mutex<BTreeMap<u64, RuleMutation>>,
+}
+
+impl RuleSync {
+    /// Loads the changes not yet submitted. Undecodable entries are dropped with a warning.
+    pub fn load(store: Arc<dyn StateStore>, config: &RuleSyncConfig) -> Result<Self> {
+        let mut outbox = BTreeMap::new();
+        for (key, value) in store.entries(OUTBOX_NAMESPACE)? {
+            let sequence = <[u8; 8]>::try_from(key.as_slice()).map(u64::from_be_bytes);
+            match (sequence, serde_json::from_slice(&value)) {
+                (Ok(sequence), Ok(mutation)) => {
+                    outbox.insert(sequence, mutation);
+                }
+                _ => {
+                    warn!("Dropping undecodable queued rule change");
+                    store.remove(OUTBOX_NAMESPACE, &key)?;
+                }
+            }
+        }
+        if !outbox.is_empty() {
+            info!(
+                pending = outbox.len(),
+                "Loaded rule changes awaiting synchronization"
+            );
+        }
+        Ok(Self {
+            store,
+            max_batch: config.max_batch.max(1),
+            submit_interval: Duration::from_secs(config.submit_interval_secs.max(1)),
+            outbox: Mutex::new(outbox),
+        })
+    }
+
+    /// Queues a change made on this instance for the other operators.
+    pub fn queue(&self, mutation: RuleMutation) -> Result<()> {
+        let mut outbox = self.outbox.lock();
+        let sequence = outbox.last_key_value().map_or(0, |(last, _)| last + 1);
+        self.store.insert(
+            OUTBOX_NAMESPACE,
+            &sequence.to_be_bytes(),
+            &serde_json::to_vec(&mutation)?,
+        )?;
+        outbox.insert(sequence, mutation);
+        Ok(())
+    }
+
+    /// Changes not yet submitted.
+    pub fn pending(&self) -> usize {
+        self.outbox.lock().len()
+    }
+
+    /// The oldest queued changes, up to `max_batch`, and the sequence of the last one.
+    pub fn next_batch(&self) -> Option<(u64, Vec<RuleMutation>)> {
+        let outbox = self.outbox.lock();
+        let batch: Vec<(u64, RuleMutation)> = outbox
+            .iter()
+            .take(self.max_batch)
+            .map(|(sequence, mutation)| (*sequence, mutation.clone()))
+            .collect();
+        let last = batch.last()?.0;
+        Some((
+            last,
+            batch.into_iter().map(|(_, mutation)| mutation).collect(),
+        ))
+    }
+
+    /// Removes the changes up to and including `last`, once submitted.
+    pub fn acknowledge(&self, last: u64) -> Result<()> {
+        let mut outbox = self.outbox.lock();
+        let submitted: Vec<u64> = outbox
+            .range(..=last)
+            .map(|(sequence, _)| *sequence)
+            .collect();
+        for sequence in submitted {
+            self.store
+                .remove(OUTBOX_NAMESPACE, &sequence.to_be_bytes())?;
+            outbox.remove(&sequence);
+        }
+        Ok(())
+    }
+
+    /// Submits queued changes every `submit_interval_secs` until `shutdown`, signed by the
+    /// operator's key. A failed submission is retried with the same changes.
+    pub async fn run(
+        self: Arc<Self>,
+        env: BlueprintEnvironment,
+        signer: TanglePairSigner<Sr25519Pair>,
+        shutdown: CancellationToken,
+    ) {
+        let mut submit_interval = interval(self.submit_interval);
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = submit_interval.tick() => {}
+            }
+            let Some((last, mutations)) = self.next_batch() else {
+                continue;
+            };
+            let count = mutations.len();
+            match submit(&env, &signer, mutations).await {
+                Ok(()) => {
+                    info!(count, "Submitted rule changes for synchronization");
+                    if let Err(e) = self.acknowledge(last) {
+                        warn!(error = %e, "Failed to remove submitted rule changes");
+                    }
+                }
+                Err(e) => {
+                    warn!(count, error = %e, "Failed to submit rule changes; will retry");
+                }
+            }
+        }
+    }
+}
+
+/// Calls the `sync_rules` job of this service with `mutations`, waiting for finalization.
+async fn submit(
+    env: &BlueprintEnvironment,
+    signer: &TanglePairSigner<Sr25519Pair>,
+    mutations: Vec<RuleMutation>,
+) -> Result<()> {
+    let service_id = env
+        .protocol_settings
+        .tangle()
+        .map_err(|e| Error::RuleSyncFailed(e.to_string()))?
+        .service_id
+        .ok_or_else(|| Error::RuleSyncFailed("No service ID configured".to_string()))?;
+    let args = to_field(SyncRulesInput { mutations })
+        .map_err(|e| Error::RuleSyncFailed(format!("Failed to encode rule changes: {e}")))?;
+    let call = api::tx()
+        .services()
+        .call(service_id, SYNC_RULES_JOB_ID as u8, vec![args]);
+
+    let client = env
+        .tangle_client()
+        .await
+        .map_err(|e| Error::RuleSyncFailed(format!("Tangle client unavailable: {e}")))?;
+    client
+        .rpc_client
+        .tx()
+        .sign_and_submit_then_watch_default(&call, signer)
+        .await
+        .map_err(|e| Error::RuleSyncFailed(format!("Failed to submit job call: {e}")))?
+        .wait_for_finalized_success()
+        .await
+        .map_err(|e| Error::RuleSyncFailed(format!("Job call failed: {e}")))?;
+    Ok(())
+}
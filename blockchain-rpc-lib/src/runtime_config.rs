@@ -0,0 +1,260 @@
+use crate::Result;
+use crate::config::{RateLimitConfig, ServiceConfig};
+use crate::config_patch::{compose_patches, patch_config, remove_from_patch};
+use crate::error::Error;
+use crate::store::StateStore;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+use url::Url;
+
+const RUNTIME_CONFIG_NAMESPACE: &str = "runtime_config";
+const OVERRIDES_KEY: &[u8] = b"overrides";
+const LEGACY_RUNTIME_CONFIG_FILE: &str = "runtime_config.json";
+
+/// Settings changed at runtime by the `update_config` and `patch_config` jobs. Unset fields keep
+/// the value from `config.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RpcOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_to_url: Option<Url>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_timeout_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_body_size_bytes: Option<usize>,
+    /// Replaces `[firewall.rate_limit]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// The `patch_config` patches so far, composed into one JSON merge patch. Applied before
+    /// the settings above.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_patch: Option<Value>,
+}
+
+impl RpcOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == RpcOverrides::default()
+    }
+
+    /// Rejects values the gateway couldn't serve with.
+    pub fn validate(&self) -> Result<()> {
+        let unsupported_url = self.proxy_to_url.as_ref().filter(|url| {
+            !matches!(url.scheme(), "http" | "https" | "ws" | "wss") || url.host_str().is_none()
+        });
+        if let Some(url) = unsupported_url {
+            return Err(Error::InvalidJobInput(format!(
+                "Unsupported backend URL {url}"
+            )));
+        }
+        if self.request_timeout_secs == Some(0) {
+            return Err(Error::InvalidJobInput(
+                "request_timeout_secs must be greater than 0".to_string(),
+            ));
+        }
+        if self.max_body_size_bytes == Some(0) {
+            return Err(Error::InvalidJobInput(
+                "max_body_size_bytes must be greater than 0".to_string(),
+            ));
+        }
+        let invalid_rate_limit = self.rate_limit.as_ref().filter(|rate_limit| {
+            !rate_limit.requests_per_sec.is_finite()
+                || rate_limit.requests_per_sec <= 0.0
+                || rate_limit.burst == 0
+        });
+        if invalid_rate_limit.is_some() {
+            return Err(Error::InvalidJobInput(
+                "rate_limit needs a positive requests_per_sec and burst".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Layers `changes` on top of these overrides. A setting changed by one job replaces what
+    /// the other set for it.
+    pub fn merge(&mut self, changes: RpcOverrides) {
+        if let Some(patch) = &changes.config_patch {
+            // Settings set directly are applied after the patch, so those the new patch touches
+            // move into it first
+            let mut composed = self.config_patch.take().unwrap_or_else(|| json!({}));
+            compose_patches(&mut composed, &self.take_settings_under(patch));
+            compose_patches(&mut composed, patch);
+            self.config_patch = Some(composed);
+        }
+        if let Some(existing) = &mut self.config_patch {
+            if changes.proxy_to_url.is_some() {
+                remove_from_patch(existing, "rpc.proxy_to_url");
+            }
+            if changes.request_timeout_secs.is_some() {
+                remove_from_patch(existing, "rpc.request_timeout_secs");
+            }
+            if changes.max_body_size_bytes.is_some() {
+                remove_from_patch(existing, "rpc.max_body_size_bytes");
+            }
+            if changes.rate_limit.is_some() {
+                remove_from_patch(existing, "firewall.rate_limit");
+            }
+        }
+        if changes.proxy_to_url.is_some() {
+            self.proxy_to_url = changes.proxy_to_url;
+        }
+        if changes.request_timeout_secs.is_some() {
+            self.request_timeout_secs = changes.request_timeout_secs;
+        }
+        if changes.max_body_size_bytes.is_some() {
+            self.max_body_size_bytes = changes.max_body_size_bytes;
+        }
+        if changes.rate_limit.is_some() {
+            self.rate_limit = changes.rate_limit;
+        }
+    }
+
+    /// Overwrites the settings in `config` that are overridden. A stored patch `config` no
+    /// longer accepts, e.g. after `config.toml` changed, is skipped with a warning.
+    pub fn apply(&self, config: &mut ServiceConfig) {
+        if let Some(patch) = &self.config_patch {
+            match patch_config(config, patch) {
+                Ok(patched) => *config = patched,
+                Err(e) => warn!(error = %e, "Skipping stored config patch"),
+            }
+        }
+        if let Some(url) = &self.proxy_to_url {
+            config.rpc.proxy_to_url = url.clone();
+        }
+        if let Some(timeout) = self.request_timeout_secs {
+            config.rpc.request_timeout_secs = timeout;
+        }
+        if let Some(max_body_size) = self.max_body_size_bytes {
+            config.rpc.max_body_size_bytes = max_body_size;
+        }
+        if let Some(rate_limit) = &self.rate_limit {
+            config.firewall.rate_limit = Some(rate_limit.clone());
+        }
+    }
+
+    /// Removes the settings `patch` touches and returns them as a patch of their own.
+    fn take_settings_under(&mut self, patch: &Value) -> Value {
+        let mut settings = json!({});
+        if let Some(url) = self
+            .proxy_to_url
+            .take_if(|_| patch_sets(patch, "rpc.proxy_to_url"))
+        {
+            settings["rpc"]["proxy_to_url"] = json!(url);
+        }
+        if let Some(timeout) = self
+            .request_timeout_secs
+            .take_if(|_| patch_sets(patch, "rpc.request_timeout_secs"))
+        {
+            settings["rpc"]["request_timeout_secs"] = json!(timeout);
+        }
+        if let Some(max_body_size) = self
+            .max_body_size_bytes
+            .take_if(|_| patch_sets(patch, "rpc.max_body_size_bytes"))
+        {
+            settings["rpc"]["max_body_size_bytes"] = json!(max_body_size);
+        }
+        if let Some(rate_limit) = self
+            .rate_limit
+            .take_if(|_| patch_sets(patch, "firewall.rate_limit"))
+        {
+            settings["firewall"]["rate_limit"] = json!(rate_limit);
+        }
+        settings
+    }
+
+    /// The overridden settings as `key = value`, for logs, the audit log and webhooks. A config
+    /// patch's settings are reported by the `patch_config` job instead.
+    pub fn settings(&self) -> Vec<String> {
+        let mut settings = Vec::new();
+        if let Some(url) = &self.proxy_to_url {
+            settings.push(format!("rpc.proxy_to_url = {url}"));
+        }
+        if let Some(timeout) = self.request_timeout_secs {
+            settings.push(format!("rpc.request_timeout_secs = {timeout}"));
+        }
+        if let Some(max_body_size) = self.max_body_size_bytes {
+            settings.push(format!("rpc.max_body_size_bytes = {max_body_size}"));
+        }
+        if let Some(rate_limit) = &self.rate_limit {
+            settings.push(format!(
+                "firewall.rate_limit = {} requests/s, burst {}",
+                rate_limit.requests_per_sec, rate_limit.burst
+            ));
+        }
+        settings
+    }
+}
+
+/// The [`RpcOverrides`] in effect, persisted in the state store and applied on top of
+/// `config.toml` at startup and on every reload, so a change made by job outlives both.
+#[derive(Debug)]
+pub struct RuntimeConfig {
+    store: Arc<dyn StateStore>,
+    overrides: Mutex<RpcOverrides>,
+}
+
+impl RuntimeConfig {
+    /// Loads the overrides stored in `store`, or from the file earlier versions kept under
+    /// `data_dir`, starting without any if neither exists.
+    pub fn load(store: Arc<dyn StateStore>, data_dir: &Path) -> Result<Self> {
+        let bytes = match store.get(RUNTIME_CONFIG_NAMESPACE, OVERRIDES_KEY)? {
+            Some(bytes) => Some(bytes),
+            None => match std::fs::read(data_dir.join(LEGACY_RUNTIME_CONFIG_FILE)) {
+                Ok(bytes) => Some(bytes),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            },
+        };
+        let overrides = match bytes {
+            Some(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!(error = %e, "Ignoring corrupt persisted runtime config");
+                RpcOverrides::default()
+            }),
+            None => RpcOverrides::default(),
+        };
+
+        Ok(Self {
+            store,
+            overrides: Mutex::new(overrides),
+        })
+    }
+
+    pub fn overrides(&self) -> RpcOverrides {
+        self.overrides.lock().clone()
+    }
+
+    /// Applies the stored overrides to a configuration read from `config.toml`.
+    pub fn apply(&self, config: &mut ServiceConfig) {
+        self.overrides.lock().apply(config);
+    }
+
+    /// Merges `changes` into the stored overrides and persists them.
+    pub fn update(&self, changes: RpcOverrides) -> Result<()> {
+        let mut overrides = self.overrides.lock();
+        let mut updated = overrides.clone();
+        updated.merge(changes);
+
+        self.store.insert(
+            RUNTIME_CONFIG_NAMESPACE,
+            OVERRIDES_KEY,
+            &serde_json::to_vec(&updated)?,
+        )?;
+        *overrides = updated;
+        Ok(())
+    }
+}
+
+/// Whether `patch` sets the setting at the dotted `path`, or something under or around it.
+fn patch_sets(patch: &Value, path: &str) -> bool {
+    let mut value = patch;
+    for key in path.split('.') {
+        match value.get(key) {
+            Some(Value::Object(_)) => value = &value[key],
+            Some(_) => return true,
+            None => return false,
+        }
+    }
+    true
+}
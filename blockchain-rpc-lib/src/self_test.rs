@@ -0,0 +1,215 @@
+use crate::config::RpcConfig;
+use crate::context::SecureRpcContext;
+use crate::proxy::upstream::Upstream;
+use crate::tls::load_rustls_config;
+use crate::upstream_probe;
+use blueprint_sdk::crypto::sp_core::SpSr25519;
+use blueprint_sdk::keystore::backends::Backend;
+use futures::{SinkExt, StreamExt};
+use serde::Serialize;
+use serde_json::{Value, json};
+use sp_runtime::AccountId32;
+use std::net::{SocketAddr, TcpListener};
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Outcome of a single self-test check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// Nothing to check, e.g. no webhooks configured.
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    /// What was found, or why the check failed.
+    pub detail: String,
+}
+
+/// Results of `--self-test`, in the order the checks ran.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SelfTestReport {
+    pub checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestReport {
+    /// Whether no check failed.
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Failed)
+    }
+
+    pub fn record(&mut self, name: impl Into<String>, result: Result<String, String>) {
+        let (status, detail) = match result {
+            Ok(detail) => (CheckStatus::Passed, detail),
+            Err(detail) => (CheckStatus::Failed, detail),
+        };
+        self.checks.push(SelfTestCheck {
+            name: name.into(),
+            status,
+            detail,
+        });
+    }
+
+    pub fn skip(&mut self, name: impl Into<String>, detail: impl Into<String>) {
+        self.checks.push(SelfTestCheck {
+            name: name.into(),
+            status: CheckStatus::Skipped,
+            detail: detail.into(),
+        });
+    }
+}
+
+/// Checks that a gateway booted from its config could serve: its listeners can bind, every
+/// backend answers a JSON-RPC call over HTTP and each route's `proxy_to_url` over WebSocket,
+/// the keystore holds the operator's signing key and every webhook is reachable. Nothing is served and no webhook
+/// is notified.
+pub async fn run(ctx: &SecureRpcContext) -> SelfTestReport {
+    let config = ctx.config();
+    let mut report = SelfTestReport::default();
+    let timeout = Duration::from_secs(match &config.rpc.probe {
+        Some(probe) => probe.timeout_secs,
+        None => config.rpc.request_timeout_secs,
+    });
+
+    report.record("rpc listener", check_bind(config.rpc.listen_addr));
+    if let Some(tls) = &config.rpc.tls {
+        let result = load_rustls_config(tls)
+            .await
+            .map(|_| format!("loaded {}", tls.cert_path.display()))
+            .map_err(|e| e.to_string());
+        report.record("rpc tls", result);
+    }
+    match config.admin.listen_addr {
+        Some(listen_addr) => report.record("admin listener", check_bind(listen_addr)),
+        None => report.skip("admin listener", "admin API disabled"),
+    }
+
+    let backends = std::iter::once(("/".to_string(), config.rpc.clone())).chain(
+        config
+            .routes
+            .iter()
+            .map(|route| (route.prefix.clone(), route.rpc_config(&config.rpc))),
+    );
+    for (prefix, rpc) in backends {
+        let urls = std::iter::once(rpc.proxy_to_url.clone()).chain(rpc.upstreams.clone());
+        for proxy_to_url in urls {
+            let backend = RpcConfig {
+                proxy_to_url,
+                ..rpc.clone()
+            };
+            report.record(
+                format!("upstream http {prefix}"),
+                check_http_backend(&backend, timeout).await,
+            );
+        }
+
+        let route = ctx.routes.resolve(&prefix);
+        let result = tokio::time::timeout(timeout, websocket_round_trip(&route.upstream))
+            .await
+            .unwrap_or_else(|_| Err(format!("no response within {}s", timeout.as_secs())));
+        report.record(format!("upstream ws {prefix}"), result);
+    }
+
+    report.record("keystore signer", check_signer(ctx));
+
+    let webhooks = ctx.firewall.webhooks();
+    if webhooks.is_empty() {
+        report.skip("webhooks", "none configured");
+    }
+    let client = reqwest::Client::builder().timeout(timeout).build();
+    for webhook in webhooks {
+        let result = match &client {
+            // Any response means the receiver is up, even one rejecting an empty request.
+            Ok(client) => match client.head(webhook.url.clone()).send().await {
+                Ok(response) => Ok(format!("responded {}", response.status())),
+                Err(e) => Err(e.to_string()),
+            },
+            Err(e) => Err(e.to_string()),
+        };
+        report.record(format!("webhook {}", webhook.url), result);
+    }
+
+    report
+}
+
+/// Sends the probe's JSON-RPC calls to the backend at `rpc.proxy_to_url` over HTTP.
+async fn check_http_backend(rpc: &RpcConfig, timeout: Duration) -> Result<String, String> {
+    match upstream_probe::check_backend(rpc, timeout).await {
+        Ok(check) if check.reachable => Ok(format!(
+            "{} answered in {} ms, head block {}, client {}",
+            check.url,
+            check.latency_ms.unwrap_or_default(),
+            check
+                .head_block
+                .map_or_else(|| "unknown".to_string(), |head| head.to_string()),
+            check.client_version.as_deref().unwrap_or("unknown"),
+        )),
+        Ok(check) => Err(format!(
+            "{} unreachable: {}",
+            check.url,
+            check.error.unwrap_or_default()
+        )),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Binds `addr` and releases it straight away.
+fn check_bind(addr: SocketAddr) -> Result<String, String> {
+    TcpListener::bind(addr)
+        .map(|_| format!("{addr} is free"))
+        .map_err(|e| format!("can't bind {addr}: {e}"))
+}
+
+/// Opens a backend WebSocket connection and waits for the answer to a call. An error
+/// response, e.g. from a node that doesn't know the method, completes the round trip too.
+async fn websocket_round_trip(upstream: &Upstream) -> Result<String, String> {
+    let started = Instant::now();
+    let (mut socket, _connection) = upstream
+        .connect_websocket(None, None, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "eth_blockNumber",
+        "params": [],
+    });
+    socket
+        .send(Message::text(request.to_string()))
+        .await
+        .map_err(|e| format!("failed to send: {e}"))?;
+    while let Some(message) = socket.next().await {
+        let Message::Text(text) = message.map_err(|e| format!("failed to receive: {e}"))? else {
+            continue;
+        };
+        let answered = serde_json::from_str::<Value>(&text)
+            .is_ok_and(|response| response.get("id") == Some(&json!(1)));
+        if answered {
+            let _ = socket.close(None).await;
+            return Ok(format!(
+                "{} answered in {} ms",
+                upstream.proxy_url(),
+                started.elapsed().as_millis()
+            ));
+        }
+    }
+    Err("connection closed before a response".to_string())
+}
+
+/// The operator's sr25519 key, which signs job results.
+fn check_signer(ctx: &SecureRpcContext) -> Result<String, String> {
+    let keystore = ctx.env.keystore();
+    let public = keystore
+        .first_local::<SpSr25519>()
+        .map_err(|e| format!("no sr25519 key: {e}"))?;
+    keystore
+        .get_secret::<SpSr25519>(&public)
+        .map_err(|e| format!("secret key unavailable: {e}"))?;
+    Ok(format!("signing as {}", AccountId32::from(public.0)))
+}
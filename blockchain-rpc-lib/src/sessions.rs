@@ -0,0 +1,233 @@
+use crate::config::WebSocketConfig;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sp_runtime::AccountId32;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio_util::sync::CancellationToken;
+
+/// Snapshot of an open WebSocket session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionStats {
+    pub id: u64,
+    pub client_ip: IpAddr,
+    pub account: Option<AccountId32>,
+    pub opened_at: DateTime<Utc>,
+    pub age_secs: i64,
+    /// Frames received from the client.
+    pub messages_in: u64,
+    /// Frames sent to the client.
+    pub messages_out: u64,
+    /// Open (or pending) JSON-RPC subscriptions.
+    pub subscriptions: u64,
+}
+
+#[derive(Debug)]
+struct Session {
+    client_ip: IpAddr,
+    account: Option<AccountId32>,
+    opened_at: DateTime<Utc>,
+    counters: Arc<SessionCounters>,
+    terminate: CancellationToken,
+}
+
+#[derive(Debug, Default)]
+struct SessionCounters {
+    messages_in: AtomicU64,
+    messages_out: AtomicU64,
+    subscriptions: AtomicU64,
+}
+
+/// A concurrent connection limit that would be exceeded by a new session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionLimit {
+    PerIp(usize),
+    PerAccount(usize),
+}
+
+/// Open WebSocket sessions, for operator inspection and eviction, with optional caps on
+/// concurrent sessions per client IP and per account.
+#[derive(Debug, Default)]
+pub struct SessionRegistry {
+    max_per_ip: Option<usize>,
+    max_per_account: Option<usize>,
+    next_id: AtomicU64,
+    sessions: Mutex<HashMap<u64, Session>>,
+}
+
+impl SessionRegistry {
+    pub fn new(config: &WebSocketConfig) -> Self {
+        SessionRegistry {
+            max_per_ip: config.max_connections_per_ip,
+            max_per_account: config.max_connections_per_account,
+            ..Self::default()
+        }
+    }
+
+    /// Registers a new session unless it would exceed a connection limit. `account_limit`, e.g.
+    /// from the account's plan, replaces the configured cap per account. The session is
+    /// removed again when the returned handle is dropped.
+    pub fn open(
+        self: &Arc<Self>,
+        client_ip: IpAddr,
+        account: Option<AccountId32>,
+        account_limit: Option<usize>,
+    ) -> Result<SessionHandle, SessionLimit> {
+        let mut sessions = self.sessions.lock();
+        if let Some(max) = self.max_per_ip {
+            let open = sessions
+                .values()
+                .filter(|s| s.client_ip == client_ip)
+                .count();
+            if open >= max {
+                return Err(SessionLimit::PerIp(max));
+            }
+        }
+        if let (Some(max), Some(account)) = (account_limit.or(self.max_per_account), &account) {
+            let open = sessions
+                .values()
+                .filter(|s| s.account.as_ref() == Some(account))
+                .count();
+            if open >= max {
+                return Err(SessionLimit::PerAccount(max));
+            }
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let counters = Arc::new(SessionCounters::default());
+        let terminate = CancellationToken::new();
+        sessions.insert(
+            id,
+            Session {
+                client_ip,
+                account: account.clone(),
+                opened_at: Utc::now(),
+                counters: counters.clone(),
+                terminate: terminate.clone(),
+            },
+        );
+        Ok(SessionHandle {
+            id,
+            account,
+            registry: self.clone(),
+            counters,
+            terminate,
+        })
+    }
+
+    /// Returns stats for all open sessions, oldest first.
+    pub fn list(&self) -> Vec<SessionStats> {
+        let now = Utc::now();
+        let mut stats: Vec<SessionStats> = self
+            .sessions
+            .lock()
+            .iter()
+            .map(|(id, session)| SessionStats {
+                id: *id,
+                client_ip: session.client_ip,
+                account: session.account.clone(),
+                opened_at: session.opened_at,
+                age_secs: (now - session.opened_at).num_seconds(),
+                messages_in: session.counters.messages_in.load(Ordering::Relaxed),
+                messages_out: session.counters.messages_out.load(Ordering::Relaxed),
+                subscriptions: session.counters.subscriptions.load(Ordering::Relaxed),
+            })
+            .collect();
+        stats.sort_by_key(|s| s.id);
+        stats
+    }
+
+    /// Total subscriptions held by an account across all its sessions.
+    pub fn account_subscriptions(&self, account: &AccountId32) -> usize {
+        self.sessions
+            .lock()
+            .values()
+            .filter(|session| session.account.as_ref() == Some(account))
+            .map(|session| session.counters.subscriptions.load(Ordering::Relaxed) as usize)
+            .sum()
+    }
+
+    /// Signals a session to close. Returns false if no such session is open.
+    pub fn terminate(&self, id: u64) -> bool {
+        match self.sessions.lock().get(&id) {
+            Some(session) => {
+                session.terminate.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A registered session, held by the connection's task for its lifetime.
+#[derive(Debug)]
+pub struct SessionHandle {
+    id: u64,
+    account: Option<AccountId32>,
+    registry: Arc<SessionRegistry>,
+    counters: Arc<SessionCounters>,
+    terminate: CancellationToken,
+}
+
+impl SessionHandle {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn account(&self) -> Option<&AccountId32> {
+        self.account.as_ref()
+    }
+
+    pub fn record_inbound(&self) {
+        self.counters.messages_in.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_outbound(&self) {
+        self.counters.messages_out.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Frames received from the client so far.
+    pub fn messages_in(&self) -> u64 {
+        self.counters.messages_in.load(Ordering::Relaxed)
+    }
+
+    /// Frames sent to the client so far.
+    pub fn messages_out(&self) -> u64 {
+        self.counters.messages_out.load(Ordering::Relaxed)
+    }
+
+    pub fn set_subscriptions(&self, count: usize) {
+        self.counters
+            .subscriptions
+            .store(count as u64, Ordering::Relaxed);
+    }
+
+    /// Subscriptions held by this session's account across all its sessions, or `None` for
+    /// anonymous sessions.
+    pub fn account_subscriptions(&self) -> Option<usize> {
+        let account = self.account.as_ref()?;
+        Some(self.registry.account_subscriptions(account))
+    }
+
+    /// Resolves when an operator terminates this session.
+    pub async fn terminated(&self) {
+        self.terminate.cancelled().await
+    }
+}
+
+impl Drop for SessionHandle {
+    fn drop(&mut self) {
+        self.registry.sessions.lock().remove(&self.id);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::config::{deserialize_accounts, deserialize_ip_networks};
+use chrono::{DateTime, Utc};
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use sp_runtime::AccountId32;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A candidate set of static firewall rules, replacing the `[firewall]` config lists when
+/// evaluated. Dynamic rules, temporary grants and bans added by jobs apply to it unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProposedPolicy {
+    #[serde(default)]
+    pub allow_unrestricted_access: bool,
+    #[serde(default, deserialize_with = "deserialize_ip_networks")]
+    pub allow_ips: HashSet<IpNetwork>,
+    #[serde(default, deserialize_with = "deserialize_accounts")]
+    pub allow_accounts: HashSet<AccountId32>,
+    #[serde(default, deserialize_with = "deserialize_ip_networks")]
+    pub deny_ips: HashSet<IpNetwork>,
+    #[serde(default, deserialize_with = "deserialize_accounts")]
+    pub deny_accounts: HashSet<AccountId32>,
+}
+
+/// A proposed policy evaluated alongside the active one until `expires_at`, without
+/// affecting any decision.
+#[derive(Debug)]
+pub struct ShadowPolicy {
+    pub policy: ProposedPolicy,
+    pub started_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    evaluations: AtomicU64,
+    would_allow: AtomicU64,
+    would_deny: AtomicU64,
+}
+
+/// Outcome of a shadow evaluation so far.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShadowReport {
+    pub policy: ProposedPolicy,
+    pub started_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub active: bool,
+    /// Requests evaluated against both policies.
+    pub evaluations: u64,
+    /// Requests the active policy denied but the proposed one would allow.
+    pub would_allow: u64,
+    /// Requests the active policy allowed but the proposed one would deny.
+    pub would_deny: u64,
+}
+
+impl ShadowPolicy {
+    pub fn new(policy: ProposedPolicy, expires_at: DateTime<Utc>) -> Self {
+        ShadowPolicy {
+            policy,
+            started_at: Utc::now(),
+            expires_at,
+            evaluations: AtomicU64::new(0),
+            would_allow: AtomicU64::new(0),
+            would_deny: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        Utc::now() < self.expires_at
+    }
+
+    /// Records a pair of decisions. Returns true if they differ.
+    pub fn record(&self, active: bool, proposed: bool) -> bool {
+        self.evaluations.fetch_add(1, Ordering::Relaxed);
+        match (active, proposed) {
+            (false, true) => self.would_allow.fetch_add(1, Ordering::Relaxed),
+            (true, false) => self.would_deny.fetch_add(1, Ordering::Relaxed),
+            _ => return false,
+        };
+        true
+    }
+
+    pub fn report(&self) -> ShadowReport {
+        ShadowReport {
+            policy: self.policy.clone(),
+            started_at: self.started_at,
+            expires_at: self.expires_at,
+            active: self.is_active(),
+            evaluations: self.evaluations.load(Ordering::Relaxed),
+            would_allow: self.would_allow.load(Ordering::Relaxed),
+            would_deny: self.would_deny.load(Ordering::Relaxed),
+        }
+    }
+}
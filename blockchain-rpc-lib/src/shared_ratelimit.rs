@@ -0,0 +1,110 @@
+use crate::Result;
+use crate::ratelimit::RateLimiter;
+use redis::aio::ConnectionManager;
+use std::fmt;
+use std::hash::Hash;
+use std::time::Duration;
+use tracing::warn;
+use url::Url;
+
+/// How long an idle bucket without a refill rate is kept, in seconds.
+const UNREFILLED_BUCKET_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Refills the token bucket in the hash at `KEYS[1]` for the time since it was last used, by the
+/// server's clock so every instance agrees on it, then takes `ARGV[3]` tokens from it if it
+/// holds them, going up to `ARGV[4]` tokens into debt. `ARGV[1]` and `ARGV[2]` are the refill
+/// rate and burst, `ARGV[5]` the TTL after which an idle bucket counts as full. Returns whether
+/// the tokens were taken and the tokens left, as a string since Redis truncates Lua numbers.
+const TAKE_TOKENS: &str = r#"
+local rate = tonumber(ARGV[1])
+local burst = tonumber(ARGV[2])
+local tokens = tonumber(ARGV[3])
+local loan = tonumber(ARGV[4])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) + tonumber(time[2]) / 1000000
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'refilled_at')
+local available = tonumber(bucket[1]) or burst
+local refilled_at = tonumber(bucket[2]) or now
+available = math.min(available + math.max(now - refilled_at, 0) * rate, burst)
+local taken = 0
+if available + loan >= tokens then
+  available = available - tokens
+  taken = 1
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(available), 'refilled_at', tostring(now))
+redis.call('EXPIRE', KEYS[1], ARGV[5])
+return {taken, tostring(available)}
+"#;
+
+/// Rate-limit buckets kept on the `[storage]` Redis server, per `shared_rate_limits`, so every
+/// gateway instance sharing it draws a client's requests from the same bucket.
+///
+/// The limits themselves come from each instance's [`RateLimiter`]. While the server can't be
+/// reached, each instance falls back to its own buckets.
+pub struct SharedRateLimits {
+    connection: ConnectionManager,
+    key_prefix: String,
+    script: redis::Script,
+}
+
+impl fmt::Debug for SharedRateLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedRateLimits")
+            .field("key_prefix", &self.key_prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SharedRateLimits {
+    /// Connects to `url`, failing if the server can't be reached. The connection is
+    /// re-established in the background after errors.
+    pub async fn connect(url: &Url, key_prefix: String) -> Result<Self> {
+        let client = redis::Client::open(url.as_str())?;
+        Ok(Self {
+            connection: ConnectionManager::new(client).await?,
+            key_prefix,
+            script: redis::Script::new(TAKE_TOKENS),
+        })
+    }
+
+    /// Like [`RateLimiter::check_with_loan`], against the shared bucket of `key` under `scope`
+    /// (e.g. `ip` or `plan:pro`), with `limiter`'s limits. Returns the whole tokens left.
+    pub async fn check_with_loan<K: Hash + Eq + Clone + fmt::Display>(
+        &self,
+        scope: &str,
+        limiter: &RateLimiter<K>,
+        key: K,
+        tokens: u32,
+        loan: u32,
+    ) -> std::result::Result<u32, Duration> {
+        let (rate, burst) = (limiter.requests_per_sec(), limiter.burst());
+        let ttl_secs = if rate > 0.0 {
+            ((burst + f64::from(loan)) / rate).ceil() as u64 + 1
+        } else {
+            UNREFILLED_BUCKET_TTL_SECS
+        };
+        let result: redis::RedisResult<(u8, String)> = self
+            .script
+            .key(format!("{}ratelimit:{scope}:{key}", self.key_prefix))
+            .arg(rate)
+            .arg(burst)
+            .arg(tokens)
+            .arg(loan)
+            .arg(ttl_secs)
+            .invoke_async(&mut self.connection.clone())
+            .await;
+        match result {
+            Ok((1, available)) => Ok(available.parse::<f64>().unwrap_or(0.0).max(0.0) as u32),
+            Ok((_, available)) => Err(limiter.retry_after(
+                available.parse().unwrap_or(0.0),
+                f64::from(tokens),
+                f64::from(loan),
+            )),
+            Err(e) => {
+                warn!(error = %e, "Shared rate limits unreachable, using this instance's buckets");
+                limiter.check_with_loan(key.clone(), tokens, loan)?;
+                Ok(limiter.remaining(&key))
+            }
+        }
+    }
+}
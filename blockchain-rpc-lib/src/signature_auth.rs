@@ -0,0 +1,236 @@
+use crate::Result;
+use crate::config::AuthConfig;
+use crate::error::Error;
+use crate::store::StateStore;
+use axum::http::{HeaderMap, Method};
+use chrono::Utc;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use sp_core::Pair;
+use sp_core::sr25519::{Pair as Sr25519Pair, Public, Signature};
+use sp_runtime::AccountId32;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::warn;
+
+const ADMIN_NONCES_NAMESPACE: &str = "admin_nonces";
+
+/// SS58 address of the signing account.
+pub const ACCOUNT_HEADER: &str = "x-account";
+/// Unix timestamp (seconds) at which the request was signed.
+pub const TIMESTAMP_HEADER: &str = "x-timestamp";
+/// Client-chosen value that must not repeat within the replay window.
+pub const NONCE_HEADER: &str = "x-nonce";
+/// Hex-encoded sr25519 signature over [`signing_payload`].
+pub const SIGNATURE_HEADER: &str = "x-signature";
+
+/// Authenticates requests signed with an account's sr25519 key.
+///
+/// A request is accepted if its timestamp is within `signature_window_secs` of the gateway's
+/// clock and its nonce hasn't been seen for the same account inside that window. The signature
+/// covers the path with its query string and the request body, so a captured signature can't
+/// be reused with other parameters or another body. Any key can sign a request, so at most
+/// `max_tracked_nonces` nonces are remembered; past that, signed requests are rejected until
+/// older nonces leave the window.
+#[derive(Debug)]
+pub struct SignatureVerifier {
+    window_secs: u64,
+    max_nonces: usize,
+    seen_nonces: Mutex<HashMap<(AccountId32, String), i64>>,
+}
+
+impl SignatureVerifier {
+    pub fn new(config: &AuthConfig) -> Self {
+        SignatureVerifier {
+            window_secs: config.signature_window_secs,
+            max_nonces: config.max_tracked_nonces,
+            seen_nonces: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Verifies the signature headers of a request.
+    ///
+    /// Returns `None` if the request carries no signature, otherwise the authenticated account
+    /// or the reason verification failed. `path_and_query` is the request target as received,
+    /// e.g. `/rpc?chain=1`, and `body` the request body, empty for requests without one.
+    pub fn verify(
+        &self,
+        headers: &HeaderMap,
+        method: &Method,
+        path_and_query: &str,
+        body: &[u8],
+    ) -> Option<Result<AccountId32, Error>> {
+        let signature = header_str(headers, SIGNATURE_HEADER)?;
+        Some(self.verify_signed(headers, signature, method, path_and_query, body))
+    }
+
+    fn verify_signed(
+        &self,
+        headers: &HeaderMap,
+        signature: &str,
+        method: &Method,
+        path_and_query: &str,
+        body: &[u8],
+    ) -> Result<AccountId32, Error> {
+        let account = header_str(headers, ACCOUNT_HEADER)
+            .ok_or_else(|| Error::InvalidSignature(format!("Missing {ACCOUNT_HEADER} header")))?;
+        let account = AccountId32::from_str(account)
+            .map_err(|_| Error::InvalidSignature("Invalid AccountId32 format".to_string()))?;
+        let timestamp: i64 = header_str(headers, TIMESTAMP_HEADER)
+            .and_then(|ts| ts.parse().ok())
+            .ok_or_else(|| {
+                Error::InvalidSignature(format!("Missing or invalid {TIMESTAMP_HEADER} header"))
+            })?;
+        let nonce = header_str(headers, NONCE_HEADER)
+            .ok_or_else(|| Error::InvalidSignature(format!("Missing {NONCE_HEADER} header")))?;
+
+        let now = Utc::now().timestamp();
+        if now.abs_diff(timestamp) > self.window_secs {
+            return Err(Error::InvalidSignature(
+                "Timestamp outside of the accepted window".to_string(),
+            ));
+        }
+
+        let signature: [u8; 64] = hex::decode(signature.trim_start_matches("0x"))
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| {
+                Error::InvalidSignature("Signature must be 64 hex-encoded bytes".to_string())
+            })?;
+        let public = Public::from_raw(*account.as_ref());
+        let payload = signing_payload(timestamp, nonce, method, path_and_query, body);
+        if !Sr25519Pair::verify(&Signature::from_raw(signature), payload.as_bytes(), &public) {
+            return Err(Error::InvalidSignature(
+                "Signature verification failed".to_string(),
+            ));
+        }
+
+        // Only remember nonces of valid signatures, so forged requests can't burn them.
+        let key = (account.clone(), nonce.to_string());
+        let mut seen = self.seen_nonces.lock();
+        if seen.contains_key(&key) {
+            return Err(Error::InvalidSignature("Nonce already used".to_string()));
+        }
+        if seen.len() >= self.max_nonces {
+            let cutoff = self.cutoff();
+            seen.retain(|_, ts| *ts >= cutoff);
+            if seen.len() >= self.max_nonces {
+                warn!(
+                    max = self.max_nonces,
+                    "Rejected signed request: too many nonces in the replay window"
+                );
+                return Err(Error::InvalidSignature(
+                    "Too many signed requests in the replay window, retry later".to_string(),
+                ));
+            }
+        }
+        seen.insert(key, timestamp);
+
+        Ok(account)
+    }
+
+    /// Forgets nonces whose timestamps fell out of the replay window.
+    pub fn prune(&self) {
+        let cutoff = self.cutoff();
+        self.seen_nonces.lock().retain(|_, ts| *ts >= cutoff);
+    }
+
+    /// The oldest timestamp still inside the replay window.
+    fn cutoff(&self) -> i64 {
+        Utc::now()
+            .timestamp()
+            .saturating_sub_unsigned(self.window_secs)
+    }
+}
+
+/// The last nonce each admin account signed an admin API request with.
+///
+/// On top of [`SignatureVerifier`]'s window-based replay cache, signed admin requests must
+/// carry a decimal nonce greater than the account's previous one, e.g. the time in
+/// microseconds. The last nonces are persisted in the state store, so a request captured on a
+/// non-loopback admin listener can't be replayed at all, not even after the replay window or
+/// a restart.
+#[derive(Debug)]
+pub struct AdminNonces {
+    store: Arc<dyn StateStore>,
+    last: Mutex<HashMap<AccountId32, u64>>,
+}
+
+impl AdminNonces {
+    /// Loads the nonces persisted in `store`. Entries that fail to decode are skipped with a
+    /// warning.
+    pub fn load(store: Arc<dyn StateStore>) -> Result<Self> {
+        let mut last = HashMap::new();
+        for (key, value) in store.entries(ADMIN_NONCES_NAMESPACE)? {
+            let account = <[u8; 32]>::try_from(key.as_slice())
+                .ok()
+                .map(AccountId32::new);
+            let nonce = <[u8; 8]>::try_from(value.as_slice())
+                .ok()
+                .map(u64::from_be_bytes);
+            match (account, nonce) {
+                (Some(account), Some(nonce)) => {
+                    last.insert(account, nonce);
+                }
+                _ => warn!(key = ?key, "Skipping undecodable persisted admin nonce"),
+            }
+        }
+        Ok(Self {
+            store,
+            last: Mutex::new(last),
+        })
+    }
+
+    /// The last nonce `account` used, if any.
+    pub fn last(&self, account: &AccountId32) -> Option<u64> {
+        self.last.lock().get(account).copied()
+    }
+
+    /// Accepts the `X-Nonce` of a request `account` signed if it is greater than the account's
+    /// last one, and records it. Call only after the signature was verified and the account
+    /// found to be an admin, so neither forged requests nor arbitrary keys can advance or
+    /// grow the persisted nonces.
+    pub fn advance(&self, account: &AccountId32, headers: &HeaderMap) -> Result<()> {
+        let nonce: u64 = header_str(headers, NONCE_HEADER)
+            .and_then(|nonce| nonce.parse().ok())
+            .ok_or_else(|| {
+                Error::InvalidSignature(format!(
+                    "Admin requests need a decimal {NONCE_HEADER} header"
+                ))
+            })?;
+        let mut last = self.last.lock();
+        match last.get(account) {
+            Some(&previous) if nonce <= previous => {
+                return Err(Error::InvalidSignature(format!(
+                    "Nonce must be greater than the last one used ({previous})"
+                )));
+            }
+            _ => {}
+        }
+        // Persist before accepting, so a restart can't reopen the nonce.
+        let key: &[u8] = account.as_ref();
+        self.store
+            .insert(ADMIN_NONCES_NAMESPACE, key, &nonce.to_be_bytes())?;
+        last.insert(account.clone(), nonce);
+        Ok(())
+    }
+}
+
+/// Builds the message a client signs:
+/// `<timestamp>:<nonce>:<HTTP method>:<path and query>:<hex SHA-256 of the body>`, where the
+/// query string, if any, follows the path as in the request target (`/rpc?chain=1`).
+pub fn signing_payload(
+    timestamp: i64,
+    nonce: &str,
+    method: &Method,
+    path_and_query: &str,
+    body: &[u8],
+) -> String {
+    let body_hash = hex::encode(Sha256::digest(body));
+    format!("{timestamp}:{nonce}:{method}:{path_and_query}:{body_hash}")
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name)?.to_str().ok()
+}
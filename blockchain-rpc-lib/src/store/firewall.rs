@@ -0,0 +1,226 @@
+use super::StateStore;
+use crate::Result;
+use crate::access_windows::AccessWindow;
+use crate::context::TemporaryAccessRecord;
+use chrono::Utc;
+use ipnetwork::IpNetwork;
+use sp_runtime::AccountId32;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::warn;
+
+const ALLOW_IPS: &str = "allow_ips";
+const ALLOW_ACCOUNTS: &str = "allow_accounts";
+const TEMPORARY_ACCESS: &str = "temporary_access";
+const TEMPORARY_IPS: &str = "temporary_ips";
+const DENY_IPS: &str = "deny_ips";
+const DENY_ACCOUNTS: &str = "deny_accounts";
+
+/// Dynamic firewall state restored from the store on startup.
+#[derive(Debug, Default)]
+pub struct PersistedFirewallState {
+    /// Allowed IPs/CIDRs, with the windows they are restricted to (none if always).
+    pub allow_ips: HashMap<IpNetwork, Vec<AccessWindow>>,
+    /// Allowed accounts, with the windows they are restricted to (none if always).
+    pub allow_accounts: HashMap<AccountId32, Vec<AccessWindow>>,
+    pub temporary_access: HashMap<AccountId32, TemporaryAccessRecord>,
+    pub temporary_ips: HashMap<IpNetwork, TemporaryAccessRecord>,
+    pub deny_ips: HashSet<IpNetwork>,
+    pub deny_accounts: HashSet<AccountId32>,
+}
+
+/// The firewall's dynamic rules and temporary grants, one [`StateStore`] namespace per list.
+///
+/// The firewall writes through to this store on every mutation, so paid and job-granted
+/// access survives restarts.
+#[derive(Debug, Clone)]
+pub struct FirewallStore {
+    store: Arc<dyn StateStore>,
+}
+
+impl FirewallStore {
+    pub fn new(store: Arc<dyn StateStore>) -> Self {
+        Self { store }
+    }
+
+    /// Loads all persisted state. Temporary grants that expired while the gateway was down
+    /// are dropped. Entries that fail to decode are skipped with a warning.
+    pub fn load(&self) -> Result<PersistedFirewallState> {
+        let mut state = PersistedFirewallState {
+            allow_ips: self.load_allow_rules(ALLOW_IPS, decode_ip_network)?,
+            allow_accounts: self.load_allow_rules(ALLOW_ACCOUNTS, decode_account)?,
+            deny_ips: self.load_ip_rules(DENY_IPS)?,
+            deny_accounts: self.load_account_rules(DENY_ACCOUNTS)?,
+            ..Default::default()
+        };
+
+        let now = Utc::now();
+        for (key, value) in self.store.entries(TEMPORARY_ACCESS)? {
+            let record = serde_json::from_slice::<TemporaryAccessRecord>(&value);
+            match (decode_account(&key), record) {
+                (Some(account), Ok(record)) if record.expires_at > now => {
+                    state.temporary_access.insert(account, record);
+                }
+                (Some(_), Ok(_)) => {
+                    self.store.remove(TEMPORARY_ACCESS, &key)?;
+                }
+                _ => warn!(key = ?key, "Skipping undecodable persisted temporary grant"),
+            }
+        }
+
+        for (key, value) in self.store.entries(TEMPORARY_IPS)? {
+            let record = serde_json::from_slice::<TemporaryAccessRecord>(&value);
+            match (decode_ip_network(&key), record) {
+                (Some(ip_network), Ok(record)) if record.expires_at > now => {
+                    state.temporary_ips.insert(ip_network, record);
+                }
+                (Some(_), Ok(_)) => {
+                    self.store.remove(TEMPORARY_IPS, &key)?;
+                }
+                _ => warn!(key = ?key, "Skipping undecodable persisted temporary IP grant"),
+            }
+        }
+
+        Ok(state)
+    }
+
+    pub fn insert_ip_rule(&self, ip_network: &IpNetwork, windows: &[AccessWindow]) -> Result<()> {
+        self.store
+            .insert(ALLOW_IPS, &ip_key(ip_network), &encode_windows(windows)?)
+    }
+
+    pub fn insert_account_rule(
+        &self,
+        account: &AccountId32,
+        windows: &[AccessWindow],
+    ) -> Result<()> {
+        self.store.insert(
+            ALLOW_ACCOUNTS,
+            account_key(account),
+            &encode_windows(windows)?,
+        )
+    }
+
+    pub fn remove_ip_rule(&self, ip_network: &IpNetwork) -> Result<()> {
+        self.store.remove(ALLOW_IPS, &ip_key(ip_network))
+    }
+
+    pub fn remove_account_rule(&self, account: &AccountId32) -> Result<()> {
+        self.store.remove(ALLOW_ACCOUNTS, account_key(account))
+    }
+
+    pub fn insert_deny_ip(&self, ip_network: &IpNetwork) -> Result<()> {
+        self.store.insert(DENY_IPS, &ip_key(ip_network), &[])
+    }
+
+    pub fn insert_deny_account(&self, account: &AccountId32) -> Result<()> {
+        self.store.insert(DENY_ACCOUNTS, account_key(account), &[])
+    }
+
+    pub fn insert_temporary_access(
+        &self,
+        account: &AccountId32,
+        record: &TemporaryAccessRecord,
+    ) -> Result<()> {
+        self.store.insert(
+            TEMPORARY_ACCESS,
+            account_key(account),
+            &serde_json::to_vec(record)?,
+        )
+    }
+
+    pub fn remove_temporary_access(&self, account: &AccountId32) -> Result<()> {
+        self.store.remove(TEMPORARY_ACCESS, account_key(account))
+    }
+
+    pub fn insert_temporary_ip(
+        &self,
+        ip_network: &IpNetwork,
+        record: &TemporaryAccessRecord,
+    ) -> Result<()> {
+        self.store.insert(
+            TEMPORARY_IPS,
+            &ip_key(ip_network),
+            &serde_json::to_vec(record)?,
+        )
+    }
+
+    pub fn remove_temporary_ip(&self, ip_network: &IpNetwork) -> Result<()> {
+        self.store.remove(TEMPORARY_IPS, &ip_key(ip_network))
+    }
+
+    fn load_ip_rules(&self, namespace: &str) -> Result<HashSet<IpNetwork>> {
+        let mut rules = HashSet::new();
+        for (key, _) in self.store.entries(namespace)? {
+            match decode_ip_network(&key) {
+                Some(ip_network) => {
+                    rules.insert(ip_network);
+                }
+                None => warn!(key = ?key, "Skipping undecodable persisted IP rule"),
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Loads allow rules, stored with the windows they are restricted to as their value, or an
+    /// empty value for rules that always apply.
+    fn load_allow_rules<K: std::hash::Hash + Eq>(
+        &self,
+        namespace: &str,
+        decode_key: fn(&[u8]) -> Option<K>,
+    ) -> Result<HashMap<K, Vec<AccessWindow>>> {
+        let mut rules = HashMap::new();
+        for (key, value) in self.store.entries(namespace)? {
+            let windows = match value.as_slice() {
+                [] => Ok(Vec::new()),
+                value => serde_json::from_slice(value),
+            };
+            match (decode_key(&key), windows) {
+                (Some(rule), Ok(windows)) => {
+                    rules.insert(rule, windows);
+                }
+                _ => warn!(key = ?key, namespace, "Skipping undecodable persisted allow rule"),
+            }
+        }
+        Ok(rules)
+    }
+
+    fn load_account_rules(&self, namespace: &str) -> Result<HashSet<AccountId32>> {
+        let mut rules = HashSet::new();
+        for (key, _) in self.store.entries(namespace)? {
+            match decode_account(&key) {
+                Some(account) => {
+                    rules.insert(account);
+                }
+                None => warn!(key = ?key, "Skipping undecodable persisted account rule"),
+            }
+        }
+        Ok(rules)
+    }
+}
+
+fn encode_windows(windows: &[AccessWindow]) -> Result<Vec<u8>> {
+    if windows.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::to_vec(windows)?)
+}
+
+fn ip_key(ip_network: &IpNetwork) -> Vec<u8> {
+    ip_network.to_string().into_bytes()
+}
+
+fn decode_ip_network(key: &[u8]) -> Option<IpNetwork> {
+    std::str::from_utf8(key)
+        .ok()
+        .and_then(|s| IpNetwork::from_str(s).ok())
+}
+
+fn account_key(account: &AccountId32) -> &[u8] {
+    account.as_ref()
+}
+
+fn decode_account(key: &[u8]) -> Option<AccountId32> {
+    <[u8; 32]>::try_from(key).ok().map(AccountId32::new)
+}
@@ -0,0 +1,51 @@
+use super::StateStore;
+use crate::Result;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+
+/// Keeps state in memory only, so it's lost on restart. For ephemeral deployments and
+/// development.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    namespaces: Mutex<HashMap<String, HashMap<Vec<u8>, Vec<u8>>>>,
+}
+
+impl StateStore for MemoryStore {
+    fn entries(&self, namespace: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        Ok(self
+            .namespaces
+            .lock()
+            .get(namespace)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    fn get(&self, namespace: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .namespaces
+            .lock()
+            .get(namespace)
+            .and_then(|entries| entries.get(key).cloned()))
+    }
+
+    fn insert(&self, namespace: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        self.namespaces
+            .lock()
+            .entry(namespace.to_string())
+            .or_default()
+            .insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn remove(&self, namespace: &str, key: &[u8]) -> Result<()> {
+        if let Some(entries) = self.namespaces.lock().get_mut(namespace) {
+            entries.remove(key);
+        }
+        Ok(())
+    }
+}
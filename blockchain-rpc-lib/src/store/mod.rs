@@ -0,0 +1,67 @@
+mod firewall;
+mod memory;
+#[cfg(feature = "redis")]
+mod redis;
+mod sled;
+
+pub use self::firewall::{FirewallStore, PersistedFirewallState};
+pub use self::memory::MemoryStore;
+#[cfg(feature = "redis")]
+pub use self::redis::RedisStore;
+pub use self::sled::SledStore;
+
+use crate::Result;
+use crate::config::StorageConfig;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
+
+/// Where gateway state outlives the process: dynamic firewall rules, temporary grants, account
+/// labels and metered usage.
+///
+/// State is kept as opaque values under byte keys, grouped in namespaces (one per rule list,
+/// grant kind or meter), so each feature encodes its own records and every backend can hold
+/// them all. Writes are durable once they return.
+pub trait StateStore: fmt::Debug + Send + Sync {
+    /// Every entry in `namespace`, in no particular order.
+    fn entries(&self, namespace: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>>;
+
+    fn get(&self, namespace: &str, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Inserts or replaces the value at `key`.
+    fn insert(&self, namespace: &str, key: &[u8], value: &[u8]) -> Result<()>;
+
+    /// Removes `key`, if present.
+    fn remove(&self, namespace: &str, key: &[u8]) -> Result<()>;
+}
+
+/// Opens the backend selected by `[storage]`. The sled database lives at
+/// `<data_dir>/firewall.db`, where earlier versions kept the firewall state.
+pub fn open(config: &StorageConfig, data_dir: &Path) -> Result<Arc<dyn StateStore>> {
+    let store: Arc<dyn StateStore> = match config {
+        StorageConfig::Sled => Arc::new(SledStore::open(&data_dir.join("firewall.db"))?),
+        StorageConfig::Memory => Arc::new(MemoryStore::default()),
+        #[cfg(feature = "redis")]
+        StorageConfig::Redis {
+            url,
+            key_prefix,
+            timeout_secs,
+            ..
+        } => Arc::new(RedisStore::open(
+            url,
+            key_prefix.clone(),
+            std::time::Duration::from_secs(*timeout_secs),
+        )?),
+        #[cfg(not(feature = "redis"))]
+        StorageConfig::Redis { .. } => {
+            return Err(crate::error::Error::ConfigError(
+                ::config::ConfigError::Message(
+                    "storage.backend = \"redis\" requires the `redis` feature".to_string(),
+                ),
+            ));
+        }
+    };
+    info!(?store, "Opened state store");
+    Ok(store)
+}
@@ -0,0 +1,121 @@
+use super::StateStore;
+use crate::Result;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+use tokio::runtime::{Handle, RuntimeFlavor};
+use url::Url;
+
+/// Idle connections kept for reuse; calls beyond this many at once open their own.
+const MAX_IDLE_CONNECTIONS: usize = 8;
+
+/// Keeps state in Redis, one hash per namespace at `<key_prefix><namespace>`, so several
+/// gateway instances can share rules, grants and usage.
+///
+/// Commands are blocking, with `timeout` for connecting, sending and receiving each, and run
+/// on connections taken from a small pool, so concurrent calls don't queue behind each other.
+/// A connection that fails is dropped and replaced on a later call. Inside a multi-threaded
+/// tokio runtime, the worker thread is handed over to blocking work for the duration of each
+/// command, so other tasks move to other workers instead of stalling.
+pub struct RedisStore {
+    client: redis::Client,
+    key_prefix: String,
+    timeout: Duration,
+    idle: Mutex<Vec<redis::Connection>>,
+}
+
+impl fmt::Debug for RedisStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The URL may carry a password.
+        f.debug_struct("RedisStore")
+            .field("key_prefix", &self.key_prefix)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RedisStore {
+    /// Connects to `url`, failing if the server can't be reached within `timeout`.
+    pub fn open(url: &Url, key_prefix: String, timeout: Duration) -> Result<Self> {
+        let client = redis::Client::open(url.as_str())?;
+        let store = Self {
+            client,
+            key_prefix,
+            timeout,
+            idle: Mutex::new(Vec::new()),
+        };
+        let connection = blocking(|| store.connect())?;
+        store.idle.lock().push(connection);
+        Ok(store)
+    }
+
+    fn connect(&self) -> Result<redis::Connection> {
+        let connection = self.client.get_connection_with_timeout(self.timeout)?;
+        connection.set_read_timeout(Some(self.timeout))?;
+        connection.set_write_timeout(Some(self.timeout))?;
+        Ok(connection)
+    }
+
+    fn query<T: redis::FromRedisValue>(&self, cmd: &redis::Cmd) -> Result<T> {
+        blocking(|| {
+            let idle = self.idle.lock().pop();
+            let mut conn = match idle {
+                Some(conn) => conn,
+                None => self.connect()?,
+            };
+            let result = cmd.query(&mut conn);
+            // Keep the connection unless it broke or timed out mid-reply.
+            let broken = result
+                .as_ref()
+                .is_err_and(|e| e.is_connection_dropped() || e.is_io_error() || e.is_timeout());
+            if !broken {
+                let mut idle = self.idle.lock();
+                if idle.len() < MAX_IDLE_CONNECTIONS {
+                    idle.push(conn);
+                }
+            }
+            Ok(result?)
+        })
+    }
+
+    fn key(&self, namespace: &str) -> String {
+        format!("{}{namespace}", self.key_prefix)
+    }
+}
+
+/// Runs blocking I/O, first handing the worker thread over to blocking work when called from
+/// a multi-threaded tokio runtime.
+fn blocking<T>(f: impl FnOnce() -> T) -> T {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+impl StateStore for RedisStore {
+    fn entries(&self, namespace: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let entries: HashMap<Vec<u8>, Vec<u8>> =
+            self.query(redis::cmd("HGETALL").arg(self.key(namespace)))?;
+        Ok(entries.into_iter().collect())
+    }
+
+    fn get(&self, namespace: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.query(redis::cmd("HGET").arg(self.key(namespace)).arg(key))
+    }
+
+    fn insert(&self, namespace: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        self.query(
+            redis::cmd("HSET")
+                .arg(self.key(namespace))
+                .arg(key)
+                .arg(value),
+        )
+    }
+
+    fn remove(&self, namespace: &str, key: &[u8]) -> Result<()> {
+        self.query(redis::cmd("HDEL").arg(self.key(namespace)).arg(key))
+    }
+}
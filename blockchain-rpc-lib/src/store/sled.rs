@@ -0,0 +1,52 @@
+use super::StateStore;
+use crate::Result;
+use std::path::Path;
+
+/// Embedded on-disk store, with one sled tree per namespace. Every write is flushed before it
+/// returns.
+#[derive(Debug, Clone)]
+pub struct SledStore {
+    db: sled::Db,
+}
+
+impl SledStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            db: sled::open(path)?,
+        })
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+impl StateStore for SledStore {
+    fn entries(&self, namespace: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut entries = Vec::new();
+        for entry in self.db.open_tree(namespace)?.iter() {
+            let (key, value) = entry?;
+            entries.push((key.to_vec(), value.to_vec()));
+        }
+        Ok(entries)
+    }
+
+    fn get(&self, namespace: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .db
+            .open_tree(namespace)?
+            .get(key)?
+            .map(|value| value.to_vec()))
+    }
+
+    fn insert(&self, namespace: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        self.db.open_tree(namespace)?.insert(key, value)?;
+        self.flush()
+    }
+
+    fn remove(&self, namespace: &str, key: &[u8]) -> Result<()> {
+        self.db.open_tree(namespace)?.remove(key)?;
+        self.flush()
+    }
+}
@@ -0,0 +1,168 @@
+use crate::config::WebSocketConfig;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// JSON-RPC error code returned when a subscription would exceed a limit.
+const SUBSCRIPTION_LIMIT_ERROR_CODE: i64 = -32005;
+
+/// Bookkeeping of the subscriptions open on a single WebSocket connection.
+///
+/// Subscribe calls (`eth_subscribe`, `chain_subscribeNewHeads`, ...) are counted from the moment
+/// they are sent, and become active once the backend answers with a subscription ID. Unsubscribe
+/// calls release the subscription immediately.
+#[derive(Debug)]
+pub struct SubscriptionTracker {
+    max_per_connection: Option<usize>,
+    max_per_account: Option<usize>,
+    /// Request IDs of subscribe calls awaiting the backend's response.
+    pending: HashSet<String>,
+    /// Subscription IDs returned by the backend.
+    active: HashSet<String>,
+}
+
+impl SubscriptionTracker {
+    pub fn new(config: &WebSocketConfig) -> Self {
+        SubscriptionTracker {
+            max_per_connection: config.max_subscriptions_per_connection,
+            max_per_account: config.max_subscriptions_per_account,
+            pending: HashSet::new(),
+            active: HashSet::new(),
+        }
+    }
+
+    /// Number of subscriptions held by this connection, including pending ones.
+    pub fn count(&self) -> usize {
+        self.pending.len() + self.active.len()
+    }
+
+    /// Inspects a client frame before it is forwarded. If its subscribe calls would exceed
+    /// a limit, returns the error payload to send back instead of forwarding the frame.
+    ///
+    /// `account_subscriptions` is the number of subscriptions held by the connection's account
+    /// across all its connections, or `None` for anonymous connections.
+    pub fn check_client_frame(
+        &mut self,
+        payload: &[u8],
+        account_subscriptions: Option<usize>,
+    ) -> Option<String> {
+        let value: Value = serde_json::from_slice(payload).ok()?;
+        let calls: Vec<&Value> = match &value {
+            Value::Array(calls) => calls.iter().collect(),
+            call => vec![call],
+        };
+
+        let subscribes: Vec<&Value> = calls
+            .iter()
+            .copied()
+            .filter(|call| method_kind(call) == Some(MethodKind::Subscribe))
+            .collect();
+        if !subscribes.is_empty() {
+            if let Some(reason) = self.limit_violation(subscribes.len(), account_subscriptions) {
+                return Some(limit_error_payload(&value, &reason));
+            }
+            for call in subscribes {
+                if let Some(id) = call.get("id") {
+                    self.pending.insert(id.to_string());
+                }
+            }
+        }
+
+        for call in calls {
+            if method_kind(call) == Some(MethodKind::Unsubscribe) {
+                let subscription_id = call
+                    .get("params")
+                    .and_then(|params| params.get(0))
+                    .and_then(subscription_key);
+                if let Some(subscription_id) = subscription_id {
+                    self.active.remove(&subscription_id);
+                }
+            }
+        }
+        None
+    }
+
+    /// Inspects a backend frame, activating subscriptions confirmed by the backend.
+    pub fn observe_backend_frame(&mut self, payload: &[u8]) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let Ok(value) = serde_json::from_slice::<Value>(payload) else {
+            return;
+        };
+        let responses: Vec<&Value> = match &value {
+            Value::Array(responses) => responses.iter().collect(),
+            response => vec![response],
+        };
+        for response in responses {
+            let Some(id) = response.get("id") else {
+                continue;
+            };
+            if !self.pending.remove(&id.to_string()) {
+                continue;
+            }
+            // Failed subscribe calls simply release their pending slot.
+            if let Some(subscription_id) = response.get("result").and_then(subscription_key) {
+                self.active.insert(subscription_id);
+            }
+        }
+    }
+
+    fn limit_violation(&self, new: usize, account_subscriptions: Option<usize>) -> Option<String> {
+        if let Some(max) = self.max_per_connection {
+            if self.count() + new > max {
+                return Some(format!("at most {max} subscriptions per connection"));
+            }
+        }
+        if let (Some(max), Some(current)) = (self.max_per_account, account_subscriptions) {
+            if current + new > max {
+                return Some(format!("at most {max} subscriptions per account"));
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MethodKind {
+    Subscribe,
+    Unsubscribe,
+}
+
+/// Classifies `<namespace>_subscribe*` and `<namespace>_unsubscribe*` calls.
+fn method_kind(call: &Value) -> Option<MethodKind> {
+    let method = call.get("method")?.as_str()?;
+    let (_, name) = method.split_once('_')?;
+    if name.starts_with("subscribe") {
+        Some(MethodKind::Subscribe)
+    } else if name.starts_with("unsubscribe") {
+        Some(MethodKind::Unsubscribe)
+    } else {
+        None
+    }
+}
+
+fn subscription_key(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Error responses for every call of a rejected frame.
+fn limit_error_payload(frame: &Value, reason: &str) -> String {
+    let error_for = |call: &Value| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": call.get("id").cloned().unwrap_or(Value::Null),
+            "error": {
+                "code": SUBSCRIPTION_LIMIT_ERROR_CODE,
+                "message": format!("Subscription limit reached: {reason}"),
+            },
+        })
+    };
+    match frame {
+        Value::Array(calls) => Value::Array(calls.iter().map(error_for).collect()).to_string(),
+        call => error_for(call).to_string(),
+    }
+}
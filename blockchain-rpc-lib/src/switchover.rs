@@ -0,0 +1,104 @@
+use crate::Result;
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::runtime_config::RpcOverrides;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+use tracing::info;
+use url::Url;
+
+/// The backends to switch the default route to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwitchUpstreamRequest {
+    /// The new `rpc.proxy_to_url`.
+    pub proxy_to_url: Url,
+    /// The new `rpc.upstreams`, replacing the current ones.
+    #[serde(default)]
+    pub upstreams: Vec<Url>,
+    /// How long WebSocket sessions open to replaced backends may stay on them, in seconds,
+    /// before they are asked to reconnect. `0` asks them right away.
+    #[serde(default)]
+    pub drain_secs: u64,
+}
+
+/// A completed switch of backends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpstreamSwitch {
+    /// The previous `proxy_to_url` and `upstreams`, `proxy_to_url` first.
+    pub old_targets: Vec<Url>,
+    /// The new `proxy_to_url` and `upstreams`, `proxy_to_url` first.
+    pub new_targets: Vec<Url>,
+    pub drain_secs: u64,
+}
+
+/// Switches the default route to other backends at runtime, for blue/green backend upgrades:
+/// new requests and WebSocket connections go to the new backends at once, and sessions open to
+/// a replaced backend are asked to reconnect (close code 1012) once `drain_secs` has passed,
+/// landing on the new ones.
+///
+/// The change is validated and applied like a config reload, persisted as a config patch so it
+/// takes precedence over `config.toml` across reloads and restarts, and reported as an
+/// `UpstreamSwitched` webhook event. Routes other than the default one keep their backends.
+pub async fn switch_upstream(
+    ctx: &SecureRpcContext,
+    request: SwitchUpstreamRequest,
+    switched_by: &str,
+) -> Result<UpstreamSwitch> {
+    let unsupported = std::iter::once(&request.proxy_to_url)
+        .chain(&request.upstreams)
+        .find(|url| {
+            !matches!(url.scheme(), "http" | "https" | "ws" | "wss") || url.host_str().is_none()
+        });
+    if let Some(url) = unsupported {
+        return Err(Error::InvalidJobInput(format!(
+            "Unsupported backend URL {url}"
+        )));
+    }
+
+    let current = ctx.config();
+    let mut updated = (*current).clone();
+    updated.rpc.proxy_to_url = request.proxy_to_url.clone();
+    updated.rpc.upstreams = request.upstreams.clone();
+    if updated.rpc.proxy_to_url == current.rpc.proxy_to_url
+        && updated.rpc.upstreams == current.rpc.upstreams
+    {
+        return Err(Error::InvalidJobInput(
+            "Already using these backends".to_string(),
+        ));
+    }
+    updated.validate()?;
+    updated.validate_reload(&current)?;
+    ctx.apply_config_draining(updated, Duration::from_secs(request.drain_secs))?;
+    ctx.runtime_config.update(RpcOverrides {
+        config_patch: Some(json!({
+            "rpc": {
+                "proxy_to_url": request.proxy_to_url,
+                "upstreams": request.upstreams,
+            }
+        })),
+        ..RpcOverrides::default()
+    })?;
+
+    let switch = UpstreamSwitch {
+        old_targets: std::iter::once(&current.rpc.proxy_to_url)
+            .chain(&current.rpc.upstreams)
+            .cloned()
+            .collect(),
+        new_targets: std::iter::once(request.proxy_to_url)
+            .chain(request.upstreams)
+            .collect(),
+        drain_secs: request.drain_secs,
+    };
+    info!(
+        old_targets = ?switch.old_targets,
+        new_targets = ?switch.new_targets,
+        drain_secs = switch.drain_secs,
+        switched_by,
+        "Switched backends"
+    );
+    ctx.firewall
+        .report_upstream_switched(&switch, switched_by)
+        .await;
+    Ok(switch)
+}
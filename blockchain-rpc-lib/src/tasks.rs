@@ -0,0 +1,73 @@
+use futures::FutureExt;
+use parking_lot::Mutex;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, warn};
+
+/// Owns the gateway's background workers so they can be shut down together.
+///
+/// Every task gets a [`CancellationToken`] that is cancelled on [`TaskRegistry::shutdown`].
+/// Panics are caught and logged with the task's name as soon as they happen.
+#[derive(Debug, Default)]
+pub struct TaskRegistry {
+    shutdown: CancellationToken,
+    tasks: Mutex<JoinSet<()>>,
+}
+
+impl TaskRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Token cancelled when shutdown begins.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Spawns a named background task. `task` receives the shutdown token and should return
+    /// promptly once it is cancelled.
+    pub fn spawn<F, Fut>(&self, name: &'static str, task: F)
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let fut = task(self.shutdown.clone());
+        self.tasks.lock().spawn(async move {
+            debug!(task = name, "Background task started");
+            match AssertUnwindSafe(fut).catch_unwind().await {
+                Ok(()) => debug!(task = name, "Background task finished"),
+                Err(panic) => {
+                    let message = panic
+                        .downcast_ref::<&str>()
+                        .map(|s| s.to_string())
+                        .or_else(|| panic.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "unknown panic".to_string());
+                    error!(task = name, %message, "Background task panicked");
+                }
+            }
+        });
+    }
+
+    /// Signals all tasks to stop and waits up to `timeout` for them to finish.
+    /// Tasks still running after the timeout are aborted.
+    pub async fn shutdown(&self, timeout: Duration) {
+        self.shutdown.cancel();
+        let mut tasks = std::mem::take(&mut *self.tasks.lock());
+
+        let drained = tokio::time::timeout(timeout, async {
+            while tasks.join_next().await.is_some() {}
+        })
+        .await;
+
+        if drained.is_err() {
+            warn!(
+                remaining = tasks.len(),
+                "Background tasks did not stop in time, aborting"
+            );
+            tasks.shutdown().await;
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::Result;
+use crate::config::TelemetryConfig;
+use crate::error::Error;
+use crate::firewall::FirewallStats;
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use opentelemetry::metrics::Gauge;
+use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{KeyValue, global};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, Tracer, TracerProvider};
+use std::time::Duration;
+use tracing::{Span, Subscriber};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Flushes and shuts down the OTLP exporter when dropped.
+#[derive(Debug)]
+pub struct TelemetryGuard {
+    provider: TracerProvider,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to shut down OTLP trace exporter: {e}");
+        }
+    }
+}
+
+/// Flushes and shuts down the OTLP metrics exporter when dropped.
+#[derive(Debug)]
+pub struct MetricsGuard {
+    provider: SdkMeterProvider,
+}
+
+impl Drop for MetricsGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to shut down OTLP metrics exporter: {e}");
+        }
+    }
+}
+
+/// Builds a `tracing` layer exporting spans over OTLP/HTTP, if an endpoint is configured.
+///
+/// Also installs the W3C Trace Context propagator used by [`set_parent_from_headers`]
+/// and [`inject_current_context`]; without it, both are no-ops.
+pub fn otlp_layer<S>(
+    config: &TelemetryConfig,
+) -> Result<Option<(OpenTelemetryLayer<S, Tracer>, TelemetryGuard)>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let Some(endpoint) = &config.otlp_endpoint else {
+        return Ok(None);
+    };
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint.as_str())
+        .build()
+        .map_err(|e| Error::TelemetryError(e.to_string()))?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sample_ratio,
+        ))))
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            config.service_name.clone(),
+        )]))
+        .build();
+
+    let tracer = provider.tracer("secure-rpc-gateway");
+    global::set_tracer_provider(provider.clone());
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    Ok(Some((
+        tracing_opentelemetry::layer().with_tracer(tracer),
+        TelemetryGuard { provider },
+    )))
+}
+
+/// Installs a global meter provider exporting metrics over OTLP/HTTP, if an endpoint is
+/// configured.
+///
+/// Instruments are bound to the provider when created, so this must run before the gateway
+/// context is built.
+pub fn otlp_metrics(config: &TelemetryConfig) -> Result<Option<MetricsGuard>> {
+    let Some(endpoint) = &config.otlp_metrics_endpoint else {
+        return Ok(None);
+    };
+
+    let exporter = opentelemetry_otlp::MetricExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint.as_str())
+        .build()
+        .map_err(|e| Error::TelemetryError(e.to_string()))?;
+    let reader = PeriodicReader::builder(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_interval(Duration::from_secs(config.metrics_interval_secs.max(1)))
+        .build();
+    let provider = SdkMeterProvider::builder()
+        .with_reader(reader)
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            config.service_name.clone(),
+        )]))
+        .build();
+    global::set_meter_provider(provider.clone());
+
+    Ok(Some(MetricsGuard { provider }))
+}
+
+/// Gauges for the size of the firewall's dynamic state, recorded by the firewall whenever its
+/// rules change and on every cleanup pass. No-ops unless [`otlp_metrics`] installed a provider
+/// first.
+#[derive(Debug, Clone)]
+pub struct FirewallGauges {
+    dynamic_rules: Gauge<u64>,
+    temporary_grants: Gauge<u64>,
+    bans: Gauge<u64>,
+    auto_bans: Gauge<u64>,
+    webhooks: Gauge<u64>,
+    cached_decisions: Gauge<u64>,
+}
+
+impl FirewallGauges {
+    pub fn new() -> Self {
+        let meter = global::meter("secure-rpc-gateway");
+        FirewallGauges {
+            dynamic_rules: meter
+                .u64_gauge("firewall.dynamic_rules")
+                .with_description("Allow rules added at runtime, by kind (ip or account)")
+                .build(),
+            temporary_grants: meter
+                .u64_gauge("firewall.temporary_grants")
+                .with_description("Temporary access grants, by kind (ip or account)")
+                .build(),
+            bans: meter
+                .u64_gauge("firewall.bans")
+                .with_description("IP/CIDR and account bans added at runtime")
+                .build(),
+            auto_bans: meter
+                .u64_gauge("firewall.auto_bans")
+                .with_description("Active automatic bans of repeat offenders")
+                .build(),
+            webhooks: meter
+                .u64_gauge("firewall.webhooks")
+                .with_description("Registered webhooks")
+                .build(),
+            cached_decisions: meter
+                .u64_gauge("firewall.decision_cache.entries")
+                .with_description("Cached access decisions")
+                .build(),
+        }
+    }
+
+    pub fn record(&self, stats: &FirewallStats, cached_decisions: usize) {
+        let ip = [KeyValue::new("kind", "ip")];
+        let account = [KeyValue::new("kind", "account")];
+        self.dynamic_rules.record(stats.dynamic_ips as u64, &ip);
+        self.dynamic_rules
+            .record(stats.dynamic_accounts as u64, &account);
+        self.temporary_grants
+            .record(stats.temporary_ip_grants as u64, &ip);
+        self.temporary_grants
+            .record(stats.temporary_grants as u64, &account);
+        self.bans.record(stats.bans as u64, &[]);
+        self.auto_bans.record(stats.auto_bans as u64, &[]);
+        self.webhooks.record(stats.webhooks as u64, &[]);
+        self.cached_decisions.record(cached_decisions as u64, &[]);
+    }
+}
+
+impl Default for FirewallGauges {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Makes the trace context carried by `headers` the parent of `span`.
+pub fn set_parent_from_headers(span: &Span, headers: &HeaderMap) {
+    let parent =
+        global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    span.set_parent(parent);
+}
+
+/// Writes the current span's trace context into outgoing request headers.
+pub fn inject_current_context(headers: &mut HeaderMap) {
+    let context = Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(headers))
+    });
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
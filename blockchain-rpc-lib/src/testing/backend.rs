@@ -0,0 +1,213 @@
+use crate::Result;
+use axum::Router;
+use axum::extract::State;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use axum::routing::any;
+use axum::{Json, body::Bytes};
+use parking_lot::Mutex;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+#[derive(Debug, Default)]
+struct Calls {
+    /// Results by method, returned to every call of it.
+    results: HashMap<String, Value>,
+    /// Methods called, over HTTP and WebSocket, in order.
+    received: Vec<String>,
+    http_requests: usize,
+    /// Headers of the last HTTP request or WebSocket handshake.
+    last_headers: Option<HeaderMap>,
+    /// How long HTTP requests wait before being answered.
+    http_delay: Duration,
+}
+
+#[derive(Debug)]
+struct Shared {
+    calls: Mutex<Calls>,
+    notifications: broadcast::Sender<String>,
+}
+
+/// A JSON-RPC node stand-in, serving HTTP and WebSocket on a loopback port.
+///
+/// Calls to methods given a result with [`MockBackend::respond`] return it, and calls to any
+/// other method fail with JSON-RPC error `-32601`, like a node that doesn't know them. Batches
+/// are answered call by call. Every call is recorded, and [`MockBackend::notify`] pushes a
+/// frame to every open WebSocket, e.g. a subscription notification.
+#[derive(Debug)]
+pub struct MockBackend {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    shutdown: CancellationToken,
+}
+
+impl MockBackend {
+    /// Starts serving. The backend stops when dropped.
+    pub async fn start() -> Result<Self> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            calls: Mutex::new(Calls::default()),
+            notifications: broadcast::channel(64).0,
+        });
+        let shutdown = CancellationToken::new();
+        let router = Router::new()
+            .route("/", any(handle))
+            .route("/*path", any(handle))
+            .with_state(shared.clone());
+        let stopped = shutdown.clone();
+        tokio::spawn(async move {
+            axum::serve(listener, router)
+                .with_graceful_shutdown(stopped.cancelled_owned())
+                .await
+        });
+        Ok(MockBackend {
+            addr,
+            shared,
+            shutdown,
+        })
+    }
+
+    /// The backend's HTTP URL, for `rpc.proxy_to_url`. The gateway dials WebSocket sessions
+    /// at the same address.
+    pub fn url(&self) -> Url {
+        Url::parse(&format!("http://{}/", self.addr)).expect("Loopback URL is valid")
+    }
+
+    /// Answers calls to `method` with `result` from now on.
+    pub fn respond(&self, method: &str, result: Value) {
+        self.shared
+            .calls
+            .lock()
+            .results
+            .insert(method.to_string(), result);
+    }
+
+    /// Holds every HTTP request for `delay` before answering it from now on, e.g. to keep
+    /// calls in flight.
+    pub fn delay_http(&self, delay: Duration) {
+        self.shared.calls.lock().http_delay = delay;
+    }
+
+    /// Methods called so far, in order.
+    pub fn calls(&self) -> Vec<String> {
+        self.shared.calls.lock().received.clone()
+    }
+
+    /// HTTP requests received so far, counting a batch once.
+    pub fn http_requests(&self) -> usize {
+        self.shared.calls.lock().http_requests
+    }
+
+    /// Headers of the last HTTP request or WebSocket handshake received, if any.
+    pub fn last_headers(&self) -> Option<HeaderMap> {
+        self.shared.calls.lock().last_headers.clone()
+    }
+
+    /// Sends `frame` to every open WebSocket. Returns how many sessions it was sent to.
+    pub fn notify(&self, frame: Value) -> usize {
+        self.shared
+            .notifications
+            .send(frame.to_string())
+            .unwrap_or(0)
+    }
+}
+
+impl Drop for MockBackend {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
+
+async fn handle(
+    State(shared): State<Arc<Shared>>,
+    ws: Option<WebSocketUpgrade>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    shared.calls.lock().last_headers = Some(headers);
+    if let Some(ws) = ws {
+        return ws.on_upgrade(move |socket| serve_socket(shared, socket));
+    }
+    let delay = {
+        let mut calls = shared.calls.lock();
+        calls.http_requests += 1;
+        calls.http_delay
+    };
+    if !delay.is_zero() {
+        tokio::time::sleep(delay).await;
+    }
+    match serde_json::from_slice::<Value>(&body) {
+        Ok(request) => Json(answer(&shared, request)).into_response(),
+        Err(_) => Json(error(Value::Null, -32700, "Parse error")).into_response(),
+    }
+}
+
+async fn serve_socket(shared: Arc<Shared>, mut socket: WebSocket) {
+    let mut notifications = shared.notifications.subscribe();
+    loop {
+        tokio::select! {
+            message = socket.recv() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    // Nodes accept JSON-RPC in binary frames too
+                    Some(Ok(Message::Binary(bin))) => match String::from_utf8(bin) {
+                        Ok(text) => text,
+                        Err(_) => continue,
+                    },
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+                let reply = match serde_json::from_str::<Value>(&text) {
+                    Ok(request) => answer(&shared, request),
+                    Err(_) => error(Value::Null, -32700, "Parse error"),
+                };
+                if socket.send(Message::Text(reply.to_string())).await.is_err() {
+                    break;
+                }
+            }
+            Ok(frame) = notifications.recv() => {
+                if socket.send(Message::Text(frame)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Answers a call or a batch of calls.
+fn answer(shared: &Shared, request: Value) -> Value {
+    match request {
+        Value::Array(calls) => Value::Array(
+            calls
+                .into_iter()
+                .map(|call| answer_call(shared, call))
+                .collect(),
+        ),
+        call => answer_call(shared, call),
+    }
+}
+
+fn answer_call(shared: &Shared, call: Value) -> Value {
+    let id = call.get("id").cloned().unwrap_or(Value::Null);
+    let Some(method) = call.get("method").and_then(Value::as_str) else {
+        return error(id, -32600, "Invalid request");
+    };
+    let mut calls = shared.calls.lock();
+    calls.received.push(method.to_string());
+    match calls.results.get(method) {
+        Some(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        None => error(id, -32601, "Method not found"),
+    }
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
@@ -0,0 +1,197 @@
+use crate::Result;
+use crate::access_events::AccessEvents;
+use crate::admin::start_admin_server;
+use crate::config::{
+    AccessEventConfig, FirewallConfig, ServiceConfig, StorageConfig, WebhookRetryConfig,
+};
+use crate::context::SecureRpcContext;
+use crate::error::Error;
+use crate::firewall::Firewall;
+use crate::labels::AccountLabels;
+use crate::listener::start_rpc_gateway;
+use crate::store::{FirewallStore, StateStore};
+use crate::webhooks::{DeadLetterQueue, WebhookDispatcher};
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use serde_json::{Value, json};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::task::JoinHandle;
+use url::Url;
+
+/// How long [`TestGateway::start`] waits for its listeners to accept connections.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A configuration for tests: listening on loopback, proxying to `backend`, allowing requests
+/// from loopback and keeping state in memory. Adjust it before starting a gateway with it.
+pub fn test_config(backend: &Url) -> ServiceConfig {
+    let mut config: ServiceConfig = serde_json::from_value(json!({
+        "rpc": {
+            "listen_addr": "127.0.0.1:0",
+            "proxy_to_url": backend.as_str(),
+        },
+        "firewall": {
+            "allow_ips": ["127.0.0.1", "::1"],
+        },
+    }))
+    .expect("Test configuration is valid");
+    config.storage = StorageConfig::Memory;
+    config
+}
+
+/// Builds a [`SecureRpcContext`] for `config` without connecting to Tangle, with its data
+/// directory in a temporary directory that is removed when the returned guard is dropped.
+///
+/// Everything but the features that need the operator's keystore or a Tangle connection
+/// works: `[payments]`, `[sync]` and `rpc.sign_upstream_requests` must be left unset, and
+/// jobs verifying payments can't be called.
+pub async fn test_context(config: ServiceConfig) -> Result<(SecureRpcContext, TempDir)> {
+    let data_dir = TempDir::new()?;
+    let env = BlueprintEnvironment {
+        data_dir: Some(data_dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let ctx = SecureRpcContext::new(env, config).await?;
+    Ok((ctx, data_dir))
+}
+
+/// Builds a [`Firewall`] with the default `[firewall]` settings on `store`, without webhooks,
+/// an audit log, event history or a daily digest. Firewalls built on the same store share
+/// their rules, grants and labels, as gateway instances sharing a Redis server would.
+pub fn test_firewall(store: &Arc<dyn StateStore>) -> Result<Firewall> {
+    test_firewall_with(store, json!({}))
+}
+
+/// Like [`test_firewall`], with the `[firewall]` section `config` as JSON, e.g.
+/// `json!({"rate_limit": {"requests_per_sec": 1.0, "burst": 2}})`.
+pub fn test_firewall_with(store: &Arc<dyn StateStore>, config: Value) -> Result<Firewall> {
+    let config: FirewallConfig = serde_json::from_value(config)?;
+    let (access_events, _dispatcher) = AccessEvents::new(&AccessEventConfig::default());
+    let dispatcher = WebhookDispatcher::new(
+        &WebhookRetryConfig::default(),
+        DeadLetterQueue::open(store.clone(), TempDir::new()?.path())?,
+        None,
+    )?;
+    Firewall::new(
+        &config,
+        &[],
+        FirewallStore::new(store.clone()),
+        Arc::new(AccountLabels::load(store.clone())?),
+        dispatcher,
+        access_events,
+        None,
+        None,
+        None,
+    )
+}
+
+/// A gateway serving on loopback ports, for end-to-end tests of firewall rules, proxying,
+/// rate limits and WebSocket forwarding. Its context is built by [`test_context`].
+///
+/// The RPC listener, and the admin API if `admin.listen_addr` is set, are moved to free ports.
+/// Rules can be changed through [`TestGateway::ctx`] as jobs would. The gateway shuts down
+/// when dropped.
+pub struct TestGateway {
+    pub ctx: Arc<SecureRpcContext>,
+    addr: SocketAddr,
+    admin_addr: Option<SocketAddr>,
+    gateway: JoinHandle<Result<()>>,
+    admin: JoinHandle<Result<()>>,
+    _data_dir: TempDir,
+}
+
+impl TestGateway {
+    /// Starts a gateway with `config`, returning once it accepts connections.
+    pub async fn start(mut config: ServiceConfig) -> Result<Self> {
+        config.rpc.listen_addr = free_loopback_addr()?;
+        if config.admin.listen_addr.is_some() {
+            config.admin.listen_addr = Some(free_loopback_addr()?);
+        }
+        let (addr, admin_addr) = (config.rpc.listen_addr, config.admin.listen_addr);
+
+        let (ctx, data_dir) = test_context(config).await?;
+        let ctx = Arc::new(ctx);
+        let mut gateway = TestGateway {
+            gateway: tokio::spawn(start_rpc_gateway(ctx.clone())),
+            admin: tokio::spawn(start_admin_server(ctx.clone())),
+            ctx,
+            addr,
+            admin_addr,
+            _data_dir: data_dir,
+        };
+        gateway.wait_until_listening(addr, false).await?;
+        if let Some(admin_addr) = admin_addr {
+            gateway.wait_until_listening(admin_addr, true).await?;
+        }
+        Ok(gateway)
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The gateway's HTTP URL.
+    pub fn url(&self) -> Url {
+        Url::parse(&format!("http://{}/", self.addr)).expect("Loopback URL is valid")
+    }
+
+    /// The gateway's WebSocket URL.
+    pub fn ws_url(&self) -> Url {
+        Url::parse(&format!("ws://{}/", self.addr)).expect("Loopback URL is valid")
+    }
+
+    /// The admin API's URL, if `admin.listen_addr` was set.
+    pub fn admin_url(&self) -> Option<Url> {
+        self.admin_addr
+            .map(|addr| Url::parse(&format!("http://{addr}/")).expect("Loopback URL is valid"))
+    }
+
+    /// Stops the gateway and its background tasks.
+    pub async fn shutdown(self) {
+        self.ctx.shutdown().await;
+    }
+
+    /// Waits for the RPC listener, or the admin API's if `admin`, to accept connections,
+    /// failing with the server's error if it stops instead.
+    async fn wait_until_listening(&mut self, addr: SocketAddr, admin: bool) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            if tokio::net::TcpStream::connect(addr).await.is_ok() {
+                return Ok(());
+            }
+            let server = if admin {
+                &mut self.admin
+            } else {
+                &mut self.gateway
+            };
+            if server.is_finished() {
+                return match server.await {
+                    Ok(Err(e)) => Err(e),
+                    Ok(Ok(())) | Err(_) => Err(startup_error(addr)),
+                };
+            }
+            if tokio::time::Instant::now() > deadline {
+                return Err(startup_error(addr));
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+impl Drop for TestGateway {
+    fn drop(&mut self) {
+        self.ctx.tasks.shutdown_token().cancel();
+    }
+}
+
+fn startup_error(addr: SocketAddr) -> Error {
+    Error::IoError(std::io::Error::other(format!(
+        "Test gateway didn't start listening on {addr}"
+    )))
+}
+
+/// A loopback address with a port free right now.
+fn free_loopback_addr() -> Result<SocketAddr> {
+    Ok(std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?.local_addr()?)
+}
@@ -0,0 +1,18 @@
+//! End-to-end test support, behind the `testing` feature: a mock JSON-RPC backend, a gateway
+//! started on loopback ports in front of it, and a [`SecureRpcContext`] built without a Tangle
+//! connection, so firewall rules, proxying, rate limits and WebSocket forwarding can be tested
+//! in `cargo test` without a live node. [`test_firewall`] and [`test_firewall_with`] build
+//! just the firewall, for tests of its rules and grants, and [`redeem_payment`] redeems
+//! payments without a chain lookup.
+//!
+//! [`SecureRpcContext`]: crate::context::SecureRpcContext
+
+mod backend;
+mod gateway;
+mod payments;
+
+pub use self::backend::MockBackend;
+pub use self::gateway::{
+    TestGateway, test_config, test_context, test_firewall, test_firewall_with,
+};
+pub use self::payments::{redeem_payment, release_payment};
@@ -0,0 +1,27 @@
+use crate::Result;
+use crate::payments::{ChainTransfer, PaymentReference, PaymentVerifier};
+use sp_runtime::AccountId32;
+
+/// Redeems `payment` as if the chain reported it as a transfer of `amount` from `from` to `to`,
+/// for access that `payers` may pay for.
+pub fn redeem_payment(
+    verifier: &PaymentVerifier,
+    payment: &PaymentReference,
+    from: [u8; 32],
+    to: [u8; 32],
+    amount: u128,
+    payers: &[AccountId32],
+    required_amount: u128,
+) -> Result<u128> {
+    let transfer = ChainTransfer {
+        from: AccountId32::new(from),
+        to: AccountId32::new(to),
+        amount,
+    };
+    verifier.redeem(payment, &transfer, payers, required_amount)
+}
+
+/// Releases `payment` as a job does when the access it paid for couldn't be granted.
+pub fn release_payment(verifier: &PaymentVerifier, payment: &PaymentReference) -> Result<()> {
+    verifier.release(payment)
+}
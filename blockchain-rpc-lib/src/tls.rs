@@ -0,0 +1,293 @@
+use crate::Result;
+use crate::client_certs::ClientCertificate;
+use crate::config::{TlsConfig, UpstreamTlsConfig};
+use crate::error::Error;
+use crate::fingerprint::ja3_fingerprint;
+use crate::tasks::TaskRegistry;
+use axum::Extension;
+use axum::middleware::AddExtension;
+use axum_server::accept::Accept;
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use futures::future::BoxFuture;
+use rustls::pki_types::CertificateDer;
+use rustls::server::WebPkiClientVerifier;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+use tokio_rustls::server::TlsStream;
+use tower::Layer;
+use tracing::{debug, info, warn};
+
+/// How long a client gets to send its ClientHello when fingerprinting is enabled.
+const CLIENT_HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest TLS record read ahead of the handshake for fingerprinting.
+const MAX_TLS_RECORD_BYTES: usize = 16 * 1024 + 2048;
+
+/// Loads the listener's certificate chain and private key, and the client CA bundle when
+/// mutual TLS is enabled.
+pub async fn load_rustls_config(config: &TlsConfig) -> Result<RustlsConfig> {
+    Ok(RustlsConfig::from_config(server_config(config)?))
+}
+
+/// Watches the certificate, key and client CA files, swapping them into `rustls_config` when
+/// any changes. New connections use the reloaded files; established ones are unaffected.
+pub fn spawn_certificate_reloader(
+    tasks: &TaskRegistry,
+    config: TlsConfig,
+    rustls_config: RustlsConfig,
+) {
+    let period = Duration::from_secs(config.reload_interval_secs.max(1));
+    tasks.spawn("tls-reload", move |shutdown| async move {
+        let mut last_modified = modified_times(&config);
+        let mut reload_interval = tokio::time::interval(period);
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = reload_interval.tick() => {}
+            }
+            let modified = modified_times(&config);
+            if modified == last_modified {
+                continue;
+            }
+            debug!("TLS certificate, key or client CA changed on disk");
+            match server_config(&config) {
+                Ok(server_config) => {
+                    rustls_config.reload_from_config(server_config);
+                    info!(cert_path = %config.cert_path.display(), "Reloaded TLS certificate");
+                    last_modified = modified;
+                }
+                // Keep serving the previous certificate; a half-written file is retried on the
+                // next tick.
+                Err(e) => warn!(error = %e, "Failed to reload TLS certificate"),
+            }
+        }
+    });
+}
+
+/// Builds the listener's rustls configuration, verifying client certificates against
+/// `client_auth.ca_path` when mutual TLS is enabled.
+fn server_config(config: &TlsConfig) -> Result<Arc<rustls::ServerConfig>> {
+    let certs = read_certs(&config.cert_path)?;
+    let key_pem = std::fs::read(&config.key_path).map_err(|e| {
+        Error::TlsError(format!(
+            "Failed to read key {}: {e}",
+            config.key_path.display()
+        ))
+    })?;
+    let key = rustls_pemfile::private_key(&mut key_pem.as_slice())
+        .map_err(|e| Error::TlsError(format!("Invalid key: {e}")))?
+        .ok_or_else(|| {
+            Error::TlsError(format!(
+                "No private key found in {}",
+                config.key_path.display()
+            ))
+        })?;
+
+    let builder = rustls::ServerConfig::builder();
+    let builder = match &config.client_auth {
+        Some(client_auth) => {
+            let mut roots = rustls::RootCertStore::empty();
+            for cert in read_certs(&client_auth.ca_path)? {
+                roots
+                    .add(cert)
+                    .map_err(|e| Error::TlsError(format!("Invalid client CA certificate: {e}")))?;
+            }
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots));
+            let verifier = if client_auth.required {
+                verifier
+            } else {
+                verifier.allow_unauthenticated()
+            };
+            let verifier = verifier
+                .build()
+                .map_err(|e| Error::TlsError(format!("Invalid client CA bundle: {e}")))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| Error::TlsError(format!("Invalid certificate or key: {e}")))?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(server_config))
+}
+
+/// Builds the client configuration for `wss://` connections to the backend, trusting the
+/// bundled web PKI roots plus any CA certificates in `config.ca_cert_path`.
+pub fn upstream_client_config(config: &UpstreamTlsConfig) -> Result<Arc<rustls::ClientConfig>> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+    if let Some(path) = &config.ca_cert_path {
+        let certs = read_certs(path)?;
+        let added = certs.len();
+        for cert in certs {
+            roots
+                .add(cert)
+                .map_err(|e| Error::TlsError(format!("Invalid CA certificate: {e}")))?;
+        }
+        info!(ca_cert_path = %path.display(), added, "Trusting custom CA for the backend");
+    }
+
+    Ok(Arc::new(
+        rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth(),
+    ))
+}
+
+/// Reads every certificate in a PEM file, failing if there are none.
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let pem = std::fs::read(path)
+        .map_err(|e| Error::TlsError(format!("Failed to read {}: {e}", path.display())))?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| Error::TlsError(format!("Invalid PEM file {}: {e}", path.display())))?;
+    if certs.is_empty() {
+        return Err(Error::TlsError(format!(
+            "No certificates found in {}",
+            path.display()
+        )));
+    }
+    Ok(certs)
+}
+
+fn modified_times(config: &TlsConfig) -> Vec<Option<SystemTime>> {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut times = vec![modified(&config.cert_path), modified(&config.key_path)];
+    if let Some(client_auth) = &config.client_auth {
+        times.push(modified(&client_auth.ca_path));
+    }
+    times
+}
+
+/// What the TLS handshake revealed about the client, attached to every request on the
+/// connection as an extension.
+#[derive(Debug, Clone, Default)]
+pub struct TlsClientInfo {
+    /// The verified client certificate, with mutual TLS.
+    pub certificate: Option<ClientCertificate>,
+    /// JA3 hash of the ClientHello, with client fingerprinting enabled.
+    pub ja3: Option<String>,
+}
+
+/// Wraps the rustls acceptor to attach [`TlsClientInfo`] to every request on a connection.
+#[derive(Clone)]
+pub struct GatewayTlsAcceptor {
+    inner: RustlsAcceptor,
+    fingerprint: bool,
+}
+
+impl GatewayTlsAcceptor {
+    /// With `fingerprint` set, the ClientHello is read ahead of the handshake to compute its
+    /// JA3 hash.
+    pub fn new(inner: RustlsAcceptor, fingerprint: bool) -> Self {
+        Self { inner, fingerprint }
+    }
+}
+
+impl<I, S> Accept<I, S> for GatewayTlsAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = TlsStream<Rewind<I>>;
+    type Service = AddExtension<S, TlsClientInfo>;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, mut stream: I, service: S) -> Self::Future {
+        let inner = self.inner.clone();
+        let fingerprint = self.fingerprint;
+        Box::pin(async move {
+            let (client_hello, ja3) = if fingerprint {
+                let record =
+                    tokio::time::timeout(CLIENT_HELLO_TIMEOUT, read_tls_record(&mut stream))
+                        .await
+                        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+                let ja3 = ja3_fingerprint(&record);
+                (record, ja3)
+            } else {
+                (Vec::new(), None)
+            };
+
+            let (stream, service) = inner
+                .accept(Rewind::new(client_hello, stream), service)
+                .await?;
+            let certificate = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(|cert| ClientCertificate::from_der(cert));
+            let info = TlsClientInfo { certificate, ja3 };
+            Ok((stream, Extension(info).layer(service)))
+        })
+    }
+}
+
+/// Reads the first TLS record (normally the ClientHello) off a fresh connection.
+async fn read_tls_record<I: AsyncRead + Unpin>(stream: &mut I) -> io::Result<Vec<u8>> {
+    let mut record = vec![0u8; 5];
+    stream.read_exact(&mut record).await?;
+    let len = usize::from(u16::from_be_bytes([record[3], record[4]]));
+    record.resize(5 + len.min(MAX_TLS_RECORD_BYTES), 0);
+    stream.read_exact(&mut record[5..]).await?;
+    Ok(record)
+}
+
+/// A stream that replays bytes already read from it before reading further.
+pub struct Rewind<I> {
+    prefix: Vec<u8>,
+    position: usize,
+    inner: I,
+}
+
+impl<I> Rewind<I> {
+    fn new(prefix: Vec<u8>, inner: I) -> Self {
+        Self {
+            prefix,
+            position: 0,
+            inner,
+        }
+    }
+}
+
+impl<I: AsyncRead + Unpin> AsyncRead for Rewind<I> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.position < this.prefix.len() {
+            let n = (this.prefix.len() - this.position).min(buf.remaining());
+            buf.put_slice(&this.prefix[this.position..this.position + n]);
+            this.position += n;
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<I: AsyncWrite + Unpin> AsyncWrite for Rewind<I> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,72 @@
+use axum::http::{HeaderMap, HeaderValue};
+
+/// W3C Trace Context header carrying the trace and parent span IDs.
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+/// W3C Trace Context header carrying vendor-specific trace state.
+pub const TRACESTATE_HEADER: &str = "tracestate";
+
+/// W3C trace context received from a trusted client, forwarded to the upstream.
+#[derive(Debug, Clone)]
+pub struct TraceContext {
+    traceparent: HeaderValue,
+    tracestate: Option<HeaderValue>,
+}
+
+impl TraceContext {
+    /// Extracts a well-formed `traceparent` (and `tracestate`, if present) from request headers.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let traceparent = headers.get(TRACEPARENT_HEADER)?;
+        if !is_valid_traceparent(traceparent.to_str().ok()?) {
+            return None;
+        }
+        Some(TraceContext {
+            traceparent: traceparent.clone(),
+            tracestate: headers.get(TRACESTATE_HEADER).cloned(),
+        })
+    }
+
+    /// The 32-hex-digit trace ID.
+    pub fn trace_id(&self) -> &str {
+        // Validated in `from_headers`: `<version>-<trace-id>-<parent-id>-<flags>`
+        self.traceparent
+            .to_str()
+            .ok()
+            .and_then(|tp| tp.split('-').nth(1))
+            .unwrap_or_default()
+    }
+
+    /// Writes the trace headers into an outgoing request.
+    pub fn inject(&self, headers: &mut HeaderMap) {
+        headers.insert(TRACEPARENT_HEADER, self.traceparent.clone());
+        match &self.tracestate {
+            Some(tracestate) => {
+                headers.insert(TRACESTATE_HEADER, tracestate.clone());
+            }
+            None => {
+                headers.remove(TRACESTATE_HEADER);
+            }
+        }
+    }
+}
+
+/// Removes trace headers from requests of untrusted clients, so they can't inject
+/// themselves into the operator's traces.
+pub fn strip_trace_headers(headers: &mut HeaderMap) {
+    headers.remove(TRACEPARENT_HEADER);
+    headers.remove(TRACESTATE_HEADER);
+}
+
+fn is_valid_traceparent(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    let [version, trace_id, parent_id, flags] = parts.as_slice() else {
+        return false;
+    };
+    let is_hex = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit());
+    is_hex(version, 2)
+        && *version != "ff"
+        && is_hex(trace_id, 32)
+        && trace_id.bytes().any(|b| b != b'0')
+        && is_hex(parent_id, 16)
+        && parent_id.bytes().any(|b| b != b'0')
+        && is_hex(flags, 2)
+}
@@ -0,0 +1,523 @@
+use crate::access_log::AccessDetails;
+use crate::config::TrafficStatsConfig;
+use crate::context::SecureRpcContext;
+use crate::latency::{BATCH_METHOD, Histogram};
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::{DateTime, TimeZone, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// What routes, methods and accounts past `max_keys` are counted under.
+pub const OTHER_KEY: &str = "(other)";
+
+/// Counters of one bucket, overall or for one route, method or account.
+#[derive(Debug, Clone, Copy, Default)]
+struct Counters {
+    requests: u64,
+    errors: u64,
+    latency: Histogram,
+    /// The most WebSocket sessions open at once during the bucket.
+    connections: u64,
+}
+
+impl Counters {
+    fn point(&self) -> TrafficPoint {
+        let quantile = |q| (self.requests > 0).then(|| self.latency.quantile_ms(q));
+        TrafficPoint {
+            requests: self.requests,
+            errors: self.errors,
+            p50_ms: quantile(0.5),
+            p90_ms: quantile(0.9),
+            p99_ms: quantile(0.99),
+            connections: self.connections,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Route,
+    Method,
+    Account,
+}
+
+impl Dimension {
+    const ALL: [Dimension; 3] = [Dimension::Route, Dimension::Method, Dimension::Account];
+
+    fn name(self) -> &'static str {
+        match self {
+            Dimension::Route => "route",
+            Dimension::Method => "method",
+            Dimension::Account => "account",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Dimension::ALL.into_iter().find(|d| d.name() == name)
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    start: i64,
+    totals: Counters,
+    routes: HashMap<String, Counters>,
+    methods: HashMap<String, Counters>,
+    accounts: HashMap<String, Counters>,
+}
+
+impl Bucket {
+    /// A bucket starting at `start`, its connection peaks those of the sessions still open.
+    fn new(start: i64, open: &OpenSessions) -> Self {
+        let seeded = |gauges: &HashMap<String, u64>| {
+            gauges
+                .iter()
+                .map(|(key, open)| {
+                    let counters = Counters {
+                        connections: *open,
+                        ..Counters::default()
+                    };
+                    (key.clone(), counters)
+                })
+                .collect()
+        };
+        Bucket {
+            start,
+            totals: Counters {
+                connections: open.total,
+                ..Counters::default()
+            },
+            routes: seeded(&open.routes),
+            methods: HashMap::new(),
+            accounts: seeded(&open.accounts),
+        }
+    }
+
+    fn series(&self, dimension: Dimension) -> &HashMap<String, Counters> {
+        match dimension {
+            Dimension::Route => &self.routes,
+            Dimension::Method => &self.methods,
+            Dimension::Account => &self.accounts,
+        }
+    }
+
+    fn series_mut(&mut self, dimension: Dimension) -> &mut HashMap<String, Counters> {
+        match dimension {
+            Dimension::Route => &mut self.routes,
+            Dimension::Method => &mut self.methods,
+            Dimension::Account => &mut self.accounts,
+        }
+    }
+
+    fn counters(&self, dimension: Option<Dimension>, key: &str) -> Option<&Counters> {
+        match dimension {
+            None => Some(&self.totals),
+            Some(dimension) => self.series(dimension).get(key),
+        }
+    }
+
+    fn point(&self) -> TrafficBucket {
+        let points = |series: &HashMap<String, Counters>| {
+            series
+                .iter()
+                .map(|(key, counters)| (key.clone(), counters.point()))
+                .collect()
+        };
+        TrafficBucket {
+            start: timestamp(self.start),
+            totals: self.totals.point(),
+            routes: points(&self.routes),
+            methods: points(&self.methods),
+            accounts: points(&self.accounts),
+        }
+    }
+}
+
+/// WebSocket sessions open now, overall and by route and account.
+#[derive(Debug, Default)]
+struct OpenSessions {
+    total: u64,
+    routes: HashMap<String, u64>,
+    accounts: HashMap<String, u64>,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    buckets: VecDeque<Bucket>,
+    open: OpenSessions,
+}
+
+/// One route's, method's or account's traffic over a bucket, or the gateway's overall.
+/// Percentiles are the upper bounds of the latency buckets they fall in, and absent without
+/// requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficPoint {
+    pub requests: u64,
+    /// Requests answered with a 4xx or 5xx status.
+    pub errors: u64,
+    pub p50_ms: Option<u64>,
+    pub p90_ms: Option<u64>,
+    pub p99_ms: Option<u64>,
+    /// The most WebSocket sessions open at once during the bucket.
+    pub connections: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficBucket {
+    pub start: DateTime<Utc>,
+    pub totals: TrafficPoint,
+    pub routes: BTreeMap<String, TrafficPoint>,
+    pub methods: BTreeMap<String, TrafficPoint>,
+    pub accounts: BTreeMap<String, TrafficPoint>,
+}
+
+/// Retained buckets, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficSeries {
+    pub bucket_secs: u64,
+    pub buckets: Vec<TrafficBucket>,
+}
+
+/// A Grafana JSON datasource `/query` request. Fields Grafana sends beyond these are ignored.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GrafanaQuery {
+    pub range: GrafanaRange,
+    pub targets: Vec<GrafanaTarget>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GrafanaRange {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GrafanaTarget {
+    pub target: String,
+}
+
+/// A Grafana JSON datasource `/search` request, narrowing targets to those starting with
+/// `target`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GrafanaSearch {
+    #[serde(default)]
+    pub target: String,
+}
+
+/// A time series answering one query target: `[value, unix milliseconds]` pairs, with no value
+/// for percentiles of buckets without requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrafanaSeries {
+    pub target: String,
+    pub datapoints: Vec<(Option<f64>, i64)>,
+}
+
+const METRICS: [&str; 6] = [
+    "requests",
+    "errors",
+    "p50_ms",
+    "p90_ms",
+    "p99_ms",
+    "connections",
+];
+
+fn metric_value(point: &TrafficPoint, metric: &str) -> Option<f64> {
+    match metric {
+        "requests" => Some(point.requests as f64),
+        "errors" => Some(point.errors as f64),
+        "p50_ms" => point.p50_ms.map(|ms| ms as f64),
+        "p90_ms" => point.p90_ms.map(|ms| ms as f64),
+        "p99_ms" => point.p99_ms.map(|ms| ms as f64),
+        "connections" => Some(point.connections as f64),
+        _ => None,
+    }
+}
+
+fn timestamp(secs: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(secs, 0).single().unwrap_or_default()
+}
+
+/// Requests, errors, latency and WebSocket connections in fixed-length buckets, overall and by
+/// route, method and account, for dashboards on the admin API. Only the last `buckets` buckets
+/// are kept.
+#[derive(Debug)]
+pub struct TrafficStats {
+    bucket_secs: i64,
+    buckets: usize,
+    max_keys: usize,
+    window: Mutex<Window>,
+}
+
+impl TrafficStats {
+    pub fn new(config: &TrafficStatsConfig) -> Self {
+        TrafficStats {
+            bucket_secs: i64::try_from(config.bucket_secs.max(1)).unwrap_or(i64::MAX),
+            buckets: config.buckets.max(1),
+            max_keys: config.max_keys,
+            window: Mutex::new(Window::default()),
+        }
+    }
+
+    /// Records a finished request. Several methods mean a batch, counted as [`BATCH_METHOD`] as
+    /// in the latency stats.
+    pub fn record_request(
+        &self,
+        route: Option<&str>,
+        account: Option<&str>,
+        methods: &[String],
+        status: StatusCode,
+        elapsed: Duration,
+    ) {
+        let elapsed_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+        let error = status.is_client_error() || status.is_server_error();
+        let method = match methods {
+            [] => None,
+            [method] => Some(method.as_str()),
+            _ => Some(BATCH_METHOD),
+        };
+        let record = |counters: &mut Counters| {
+            counters.requests += 1;
+            if error {
+                counters.errors += 1;
+            }
+            counters.latency.record(elapsed_ms, false);
+        };
+
+        let mut window = self.window.lock();
+        let bucket = self.current(&mut window);
+        record(&mut bucket.totals);
+        for (dimension, key) in [
+            (Dimension::Route, route),
+            (Dimension::Method, method),
+            (Dimension::Account, account),
+        ] {
+            if let Some(key) = key {
+                record(self.entry(bucket.series_mut(dimension), key));
+            }
+        }
+    }
+
+    pub fn session_opened(&self, route: &str, account: Option<&str>) {
+        let mut window = self.window.lock();
+        let open = &mut window.open;
+        open.total += 1;
+        let total = open.total;
+        let on_route = *open
+            .routes
+            .entry(route.to_string())
+            .and_modify(|open| *open += 1)
+            .or_insert(1);
+        let on_account = account.map(|account| {
+            *open
+                .accounts
+                .entry(account.to_string())
+                .and_modify(|open| *open += 1)
+                .or_insert(1)
+        });
+
+        let bucket = self.current(&mut window);
+        bucket.totals.connections = bucket.totals.connections.max(total);
+        let counters = self.entry(&mut bucket.routes, route);
+        counters.connections = counters.connections.max(on_route);
+        if let (Some(account), Some(on_account)) = (account, on_account) {
+            let counters = self.entry(&mut bucket.accounts, account);
+            counters.connections = counters.connections.max(on_account);
+        }
+    }
+
+    pub fn session_closed(&self, route: &str, account: Option<&str>) {
+        let mut window = self.window.lock();
+        let open = &mut window.open;
+        open.total = open.total.saturating_sub(1);
+        let close = |gauges: &mut HashMap<String, u64>, key: &str| {
+            if let Some(open) = gauges.get_mut(key) {
+                *open = open.saturating_sub(1);
+                if *open == 0 {
+                    gauges.remove(key);
+                }
+            }
+        };
+        close(&mut open.routes, route);
+        if let Some(account) = account {
+            close(&mut open.accounts, account);
+        }
+    }
+
+    /// Buckets starting from the one holding `since` through the one holding `until`.
+    pub fn series(
+        &self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> TrafficSeries {
+        let mut window = self.window.lock();
+        self.current(&mut window);
+        let buckets = window
+            .buckets
+            .iter()
+            .filter(|bucket| self.in_range(bucket, since, until))
+            .map(Bucket::point)
+            .collect();
+        TrafficSeries {
+            bucket_secs: self.bucket_secs as u64,
+            buckets,
+        }
+    }
+
+    /// Grafana query targets, `<metric>` for the gateway overall and
+    /// `<metric>:<route|method|account>:<key>` for each key seen in the retained buckets, those
+    /// starting with `prefix`.
+    pub fn grafana_targets(&self, prefix: &str) -> Vec<String> {
+        let window = self.window.lock();
+        let mut keys: Vec<_> = Dimension::ALL
+            .into_iter()
+            .flat_map(|dimension| {
+                let mut keys: Vec<_> = window
+                    .buckets
+                    .iter()
+                    .flat_map(|bucket| bucket.series(dimension).keys())
+                    .collect();
+                keys.sort();
+                keys.dedup();
+                keys.into_iter()
+                    .map(move |key| format!(":{}:{key}", dimension.name()))
+            })
+            .collect();
+        keys.insert(0, String::new());
+        METRICS
+            .into_iter()
+            .flat_map(|metric| keys.iter().map(move |key| format!("{metric}{key}")))
+            .filter(|target| target.starts_with(prefix))
+            .collect()
+    }
+
+    /// Answers a Grafana query with one data point per bucket in its range; zero, or no value
+    /// for percentiles, where the target had no traffic.
+    pub fn grafana_query(&self, query: &GrafanaQuery) -> Result<Vec<GrafanaSeries>, String> {
+        let mut window = self.window.lock();
+        self.current(&mut window);
+        query
+            .targets
+            .iter()
+            .map(|target| {
+                let invalid = || format!("Invalid target: {}", target.target);
+                let parts: Vec<&str> = target.target.splitn(3, ':').collect();
+                let (metric, dimension, key) = match parts[..] {
+                    [metric] => (metric, None, ""),
+                    [metric, dimension, key] => (
+                        metric,
+                        Some(Dimension::parse(dimension).ok_or_else(invalid)?),
+                        key,
+                    ),
+                    _ => return Err(invalid()),
+                };
+                if !METRICS.contains(&metric) {
+                    return Err(invalid());
+                }
+                let datapoints = window
+                    .buckets
+                    .iter()
+                    .filter(|bucket| {
+                        self.in_range(bucket, Some(query.range.from), Some(query.range.to))
+                    })
+                    .map(|bucket| {
+                        let point = bucket
+                            .counters(dimension, key)
+                            .copied()
+                            .unwrap_or_default()
+                            .point();
+                        (metric_value(&point, metric), bucket.start * 1000)
+                    })
+                    .collect();
+                Ok(GrafanaSeries {
+                    target: target.target.clone(),
+                    datapoints,
+                })
+            })
+            .collect()
+    }
+
+    fn in_range(
+        &self,
+        bucket: &Bucket,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> bool {
+        since.is_none_or(|since| bucket.start + self.bucket_secs > since.timestamp())
+            && until.is_none_or(|until| bucket.start <= until.timestamp())
+    }
+
+    /// The bucket holding now, opening it and any skipped since the last one, and dropping
+    /// those past retention.
+    fn current<'a>(&self, window: &'a mut Window) -> &'a mut Bucket {
+        let now = Utc::now().timestamp();
+        let start = now - now.rem_euclid(self.bucket_secs);
+        if window.buckets.back().is_none_or(|last| last.start < start) {
+            let retained = start - self.bucket_secs * (self.buckets as i64 - 1);
+            let first = match window.buckets.back() {
+                Some(last) => (last.start + self.bucket_secs).max(retained),
+                None => start,
+            };
+            for bucket_start in (first..=start).step_by(self.bucket_secs as usize) {
+                let bucket = Bucket::new(bucket_start, &window.open);
+                window.buckets.push_back(bucket);
+            }
+        }
+        while window.buckets.len() > self.buckets {
+            window.buckets.pop_front();
+        }
+        window
+            .buckets
+            .back_mut()
+            .expect("the current bucket was just opened")
+    }
+
+    fn entry<'a>(&self, series: &'a mut HashMap<String, Counters>, key: &str) -> &'a mut Counters {
+        let key = if series.contains_key(key) || series.len() < self.max_keys {
+            key
+        } else {
+            OTHER_KEY
+        };
+        series.entry(key.to_string()).or_default()
+    }
+}
+
+/// Records every request in the traffic stats, if `[traffic_stats]` is configured. WebSocket
+/// upgrades are counted as connections by their session instead.
+pub(crate) async fn record_request(
+    State(ctx): State<Arc<SecureRpcContext>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(stats) = ctx.traffic_stats.clone() else {
+        return next.run(req).await;
+    };
+    let started = Instant::now();
+    // Filled in by the handlers; shared with the access log when that is on too.
+    let details = match req.extensions().get::<AccessDetails>() {
+        Some(details) => details.clone(),
+        None => {
+            let details = AccessDetails::default();
+            req.extensions_mut().insert(details.clone());
+            details
+        }
+    };
+
+    let response = next.run(req).await;
+    if response.status() != StatusCode::SWITCHING_PROTOCOLS {
+        stats.record_request(
+            details.route().as_deref(),
+            details.account().as_deref(),
+            &details.rpc_methods(),
+            response.status(),
+            started.elapsed(),
+        );
+    }
+    response
+}
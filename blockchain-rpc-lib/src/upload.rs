@@ -0,0 +1,132 @@
+use axum::body::{Body, Bytes};
+use axum::http::{StatusCode, header::CONNECTION};
+use axum::response::{IntoResponse, Response};
+use hyper::body::{Body as HttpBody, Frame, SizeHint};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+
+/// Why a client's upload was cut off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum UploadError {
+    #[error("request body exceeds the size limit")]
+    TooLarge,
+    #[error("request body stalled")]
+    Stalled,
+}
+
+impl UploadError {
+    /// The response telling the client. It closes the connection, since the rest of the body
+    /// is never read.
+    pub fn response(self) -> Response {
+        let (status, message) = match self {
+            UploadError::TooLarge => (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large"),
+            UploadError::Stalled => (StatusCode::REQUEST_TIMEOUT, "Request body stalled"),
+        };
+        (status, [(CONNECTION, "close")], message).into_response()
+    }
+}
+
+/// Whether a request's body was cut off, shared between the body and the request's
+/// extensions, so a failed read or backend call can be told apart from a failing backend.
+#[derive(Debug, Clone, Default)]
+pub struct UploadStatus(Arc<AtomicU8>);
+
+impl UploadStatus {
+    pub fn failure(&self) -> Option<UploadError> {
+        match self.0.load(Ordering::Relaxed) {
+            1 => Some(UploadError::TooLarge),
+            2 => Some(UploadError::Stalled),
+            _ => None,
+        }
+    }
+
+    fn fail(&self, error: UploadError) -> axum::Error {
+        let code = match error {
+            UploadError::TooLarge => 1,
+            UploadError::Stalled => 2,
+        };
+        self.0.store(code, Ordering::Relaxed);
+        axum::Error::new(error)
+    }
+}
+
+/// A client's request body, checked as it arrives: it fails as soon as more than `max_bytes`
+/// have been received, or when no data arrives for `idle_timeout` once reading started, so
+/// oversized or stalled chunked uploads are rejected early whether the body is buffered or
+/// streamed to the backend.
+pub struct LimitedUpload {
+    inner: Body,
+    received: usize,
+    max_bytes: usize,
+    idle_timeout: Duration,
+    /// Started on the first read, so time spent before the body is needed doesn't count.
+    idle: Option<Pin<Box<Sleep>>>,
+    status: UploadStatus,
+}
+
+impl LimitedUpload {
+    pub fn new(
+        inner: Body,
+        max_bytes: usize,
+        idle_timeout: Duration,
+        status: UploadStatus,
+    ) -> Self {
+        LimitedUpload {
+            inner,
+            received: 0,
+            max_bytes,
+            idle_timeout,
+            idle: None,
+            status,
+        }
+    }
+}
+
+impl HttpBody for LimitedUpload {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        let this = self.get_mut();
+        if let Some(error) = this.status.failure() {
+            return Poll::Ready(Some(Err(axum::Error::new(error))));
+        }
+        let idle_timeout = this.idle_timeout;
+        let idle = this
+            .idle
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(idle_timeout)));
+        match Pin::new(&mut this.inner).poll_frame(cx) {
+            Poll::Pending => match idle.as_mut().poll(cx) {
+                Poll::Ready(()) => Poll::Ready(Some(Err(this.status.fail(UploadError::Stalled)))),
+                Poll::Pending => Poll::Pending,
+            },
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    this.received = this.received.saturating_add(data.len());
+                    if this.received > this.max_bytes {
+                        return Poll::Ready(Some(Err(this.status.fail(UploadError::TooLarge))));
+                    }
+                }
+                idle.as_mut().reset(Instant::now() + idle_timeout);
+                Poll::Ready(Some(Ok(frame)))
+            }
+            other => other,
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
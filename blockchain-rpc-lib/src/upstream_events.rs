@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber before the slowest one starts missing events.
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UpstreamTransport {
+    Http,
+    WebSocket,
+}
+
+/// A change in the gateway's connectivity to the backend node, as opposed to an error in an
+/// individual request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum UpstreamEvent {
+    Connected {
+        transport: UpstreamTransport,
+        target: String,
+    },
+    Disconnected {
+        transport: UpstreamTransport,
+        target: String,
+        duration_secs: u64,
+    },
+    /// TCP connect to the backend failed.
+    ConnectFailed {
+        transport: UpstreamTransport,
+        target: String,
+        error: String,
+    },
+    /// The TLS or WebSocket handshake with the backend failed.
+    HandshakeFailed {
+        transport: UpstreamTransport,
+        target: String,
+        error: String,
+    },
+}
+
+/// Totals of upstream events since startup.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct UpstreamStats {
+    pub connected: u64,
+    pub disconnected: u64,
+    pub connect_failures: u64,
+    pub handshake_failures: u64,
+    /// Backend WebSocket connections currently open.
+    pub open_websockets: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    connected: AtomicU64,
+    disconnected: AtomicU64,
+    connect_failures: AtomicU64,
+    handshake_failures: AtomicU64,
+    open_websockets: AtomicU64,
+}
+
+/// Fans upstream events out to subscribers (webhooks, the admin API) and keeps running
+/// totals of them.
+#[derive(Debug)]
+pub struct UpstreamEvents {
+    sender: broadcast::Sender<UpstreamEvent>,
+    counters: Counters,
+}
+
+impl Default for UpstreamEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UpstreamEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            sender,
+            counters: Counters::default(),
+        }
+    }
+
+    /// Records `event` and delivers it to current subscribers.
+    pub fn emit(&self, event: UpstreamEvent) {
+        let counters = &self.counters;
+        match &event {
+            UpstreamEvent::Connected { transport, .. } => {
+                counters.connected.fetch_add(1, Ordering::Relaxed);
+                if *transport == UpstreamTransport::WebSocket {
+                    counters.open_websockets.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            UpstreamEvent::Disconnected { transport, .. } => {
+                counters.disconnected.fetch_add(1, Ordering::Relaxed);
+                if *transport == UpstreamTransport::WebSocket {
+                    counters.open_websockets.fetch_sub(1, Ordering::Relaxed);
+                }
+            }
+            UpstreamEvent::ConnectFailed { .. } => {
+                counters.connect_failures.fetch_add(1, Ordering::Relaxed);
+            }
+            UpstreamEvent::HandshakeFailed { .. } => {
+                counters.handshake_failures.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        // No subscribers is fine; the counters still reflect the event.
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<UpstreamEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn stats(&self) -> UpstreamStats {
+        let counters = &self.counters;
+        UpstreamStats {
+            connected: counters.connected.load(Ordering::Relaxed),
+            disconnected: counters.disconnected.load(Ordering::Relaxed),
+            connect_failures: counters.connect_failures.load(Ordering::Relaxed),
+            handshake_failures: counters.handshake_failures.load(Ordering::Relaxed),
+            open_websockets: counters.open_websockets.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use crate::Result;
+use crate::config::{RpcConfig, UpstreamProbeConfig};
+use crate::error::Error;
+use crate::method_matcher::MethodMatcher;
+use crate::proxy::outbound::reqwest_proxy;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+use url::Url;
+
+/// How often the backend is re-probed while its chain is unverified.
+const CHAIN_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Whether the backend is on the chain configured in `rpc.expected_chain_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChainStatus {
+    /// Not checked yet, or the backend reconnected and is being checked again.
+    Unverified,
+    Verified,
+    /// The backend reported a different chain.
+    Mismatch {
+        reported: String,
+    },
+}
+
+/// What the backend node reported about itself in the last successful probe.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamCapabilities {
+    /// `web3_clientVersion`, or `system_name`/`system_version` on Substrate nodes.
+    pub client_version: Option<String>,
+    /// `eth_chainId`, or the genesis hash on Substrate nodes.
+    pub chain_id: Option<String>,
+    /// Methods from `rpc_methods`, or `<module>_*` patterns from `rpc_modules`. Absent when the
+    /// node exposes neither, in which case no method is refused.
+    pub methods: Option<Vec<String>>,
+    pub probed_at: DateTime<Utc>,
+}
+
+/// Result of an on-demand check of the backend, made for the admin API without touching the
+/// periodic probe's stored results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamCheck {
+    pub url: String,
+    /// Whether the backend answered the head block query.
+    pub reachable: bool,
+    /// Round trip of the head block query, in milliseconds.
+    pub latency_ms: Option<u64>,
+    /// `eth_blockNumber`, or the number of `chain_getHeader` on Substrate nodes.
+    pub head_block: Option<u64>,
+    pub client_version: Option<String>,
+    pub chain_id: Option<String>,
+    /// Why the head block query failed, if it did.
+    pub error: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct Probed {
+    capabilities: UpstreamCapabilities,
+    methods: Option<MethodMatcher<()>>,
+}
+
+/// Probes the backend node for its version, chain and supported methods, so calls the node
+/// can't answer are refused at the gateway instead of being forwarded.
+#[derive(Debug)]
+pub struct UpstreamProbe {
+    client: reqwest::Client,
+    url: RwLock<Url>,
+    refuse_unsupported: bool,
+    probed: RwLock<Option<Probed>>,
+    expected_chain_id: Option<String>,
+    chain_status: RwLock<ChainStatus>,
+    reprobe: Notify,
+}
+
+impl UpstreamProbe {
+    pub fn new(rpc: &RpcConfig, config: &UpstreamProbeConfig) -> Result<Self> {
+        let (client, url) = probe_client(rpc, Duration::from_secs(config.timeout_secs))?;
+        Ok(UpstreamProbe {
+            client,
+            url: RwLock::new(url),
+            refuse_unsupported: config.refuse_unsupported_methods,
+            probed: RwLock::new(None),
+            expected_chain_id: rpc.expected_chain_id.clone(),
+            chain_status: RwLock::new(ChainStatus::Unverified),
+            reprobe: Notify::new(),
+        })
+    }
+
+    /// Probes now and then every `period`, sooner when [`Self::backend_reconnecting`] asks
+    /// for it, and every few seconds while the chain is unverified.
+    pub async fn run(&self, period: Duration, shutdown: CancellationToken) {
+        loop {
+            self.probe().await;
+            let wait = if self.chain_status() == Some(ChainStatus::Unverified) {
+                CHAIN_RETRY_INTERVAL
+            } else {
+                period
+            };
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = tokio::time::sleep(wait) => {}
+                _ = self.reprobe.notified() => {}
+            }
+        }
+    }
+
+    /// Whether the backend is on the expected chain, or `None` if no chain is expected.
+    pub fn chain_status(&self) -> Option<ChainStatus> {
+        self.expected_chain_id
+            .as_ref()
+            .map(|_| self.chain_status.read().clone())
+    }
+
+    /// Returns true unless an expected chain is configured and the backend hasn't been
+    /// verified to be on it.
+    pub fn is_chain_verified(&self) -> bool {
+        self.chain_status()
+            .is_none_or(|status| status == ChainStatus::Verified)
+    }
+
+    /// Called when connecting to the backend failed: whatever answers once it is reachable
+    /// again has to prove it is on the expected chain before requests are served.
+    pub fn backend_reconnecting(&self) {
+        if self.expected_chain_id.is_none() {
+            return;
+        }
+        let mut status = self.chain_status.write();
+        if *status == ChainStatus::Verified {
+            warn!("Backend connection failed, re-verifying its chain before serving");
+            *status = ChainStatus::Unverified;
+            self.reprobe.notify_one();
+        }
+    }
+
+    /// Switches to probing the backend at `rpc.proxy_to_url` and probes it right away. Its
+    /// chain has to be verified again before requests are served.
+    pub fn retarget(&self, rpc: &RpcConfig) -> Result<()> {
+        *self.url.write() = probe_url(rpc)?;
+        if self.expected_chain_id.is_some() {
+            *self.chain_status.write() = ChainStatus::Unverified;
+        }
+        self.reprobe.notify_one();
+        Ok(())
+    }
+
+    /// The result of the last successful probe, if any.
+    pub fn capabilities(&self) -> Option<UpstreamCapabilities> {
+        self.probed
+            .read()
+            .as_ref()
+            .map(|probed| probed.capabilities.clone())
+    }
+
+    /// Returns true if calls must be inspected for methods the backend doesn't support.
+    pub fn restricts_methods(&self) -> bool {
+        self.refuse_unsupported
+            && self
+                .probed
+                .read()
+                .as_ref()
+                .is_some_and(|probed| probed.methods.is_some())
+    }
+
+    /// Returns the first method in a JSON-RPC payload (single call or batch) that the backend
+    /// didn't list as supported, if refusing those is enabled.
+    pub fn find_unsupported_method(&self, payload: &[u8]) -> Option<String> {
+        if !self.restricts_methods() {
+            return None;
+        }
+        let value: Value = serde_json::from_slice(payload).ok()?;
+        let probed = self.probed.read();
+        let methods = probed.as_ref()?.methods.as_ref()?;
+        let unsupported = |call: &Value| {
+            let method = call.get("method")?.as_str()?;
+            (!methods.matches(method)).then(|| method.to_string())
+        };
+        match value {
+            Value::Array(calls) => calls.iter().find_map(unsupported),
+            call => unsupported(&call),
+        }
+    }
+
+    /// Queries the backend and replaces the stored capabilities. A failed probe keeps the
+    /// previous result.
+    pub async fn probe(&self) {
+        let client_version = match self.call("web3_clientVersion", json!([])).await {
+            Some(Value::String(version)) => Some(version),
+            _ => match (
+                self.call("system_name", json!([])).await,
+                self.call("system_version", json!([])).await,
+            ) {
+                (Some(Value::String(name)), Some(Value::String(version))) => {
+                    Some(format!("{name}/{version}"))
+                }
+                _ => None,
+            },
+        };
+        let chain_id = match self.call("eth_chainId", json!([])).await {
+            Some(Value::String(chain_id)) => Some(chain_id),
+            _ => match self.call("chain_getBlockHash", json!([0])).await {
+                Some(Value::String(genesis)) => Some(genesis),
+                _ => None,
+            },
+        };
+        let mut methods: Option<Vec<String>> =
+            self.call("rpc_methods", json!([]))
+                .await
+                .and_then(|result| {
+                    let methods = result.get("methods")?.as_array()?;
+                    Some(
+                        methods
+                            .iter()
+                            .filter_map(Value::as_str)
+                            .map(str::to_string)
+                            .collect(),
+                    )
+                });
+        if methods.is_none() {
+            methods = self
+                .call("rpc_modules", json!([]))
+                .await
+                .and_then(|modules| {
+                    let modules = modules.as_object()?;
+                    Some(modules.keys().map(|module| format!("{module}_*")).collect())
+                });
+        }
+
+        if client_version.is_none() && chain_id.is_none() && methods.is_none() {
+            warn!(url = %self.url.read(), "Backend capability probe got no answers");
+            return;
+        }
+        self.verify_chain(chain_id.as_deref());
+
+        if let Some(methods) = &mut methods {
+            methods.sort();
+        }
+        let capabilities = UpstreamCapabilities {
+            client_version,
+            chain_id,
+            methods,
+            probed_at: Utc::now(),
+        };
+        info!(
+            client_version = ?capabilities.client_version,
+            chain_id = ?capabilities.chain_id,
+            methods = capabilities.methods.as_ref().map(Vec::len),
+            "Probed backend capabilities"
+        );
+        let methods = capabilities
+            .methods
+            .as_ref()
+            .map(|methods| methods.iter().map(|method| (method, ())).collect());
+        *self.probed.write() = Some(Probed {
+            capabilities,
+            methods,
+        });
+    }
+
+    fn verify_chain(&self, reported: Option<&str>) {
+        let Some(expected) = &self.expected_chain_id else {
+            return;
+        };
+        let status = match reported {
+            Some(reported) if same_chain_id(expected, reported) => ChainStatus::Verified,
+            Some(reported) => ChainStatus::Mismatch {
+                reported: reported.to_string(),
+            },
+            None => ChainStatus::Unverified,
+        };
+        let mut current = self.chain_status.write();
+        if *current == status {
+            return;
+        }
+        match &status {
+            ChainStatus::Verified => info!(chain_id = %expected, "Backend chain verified"),
+            ChainStatus::Mismatch { reported } => error!(
+                expected = %expected,
+                %reported,
+                "Backend is on the wrong chain, refusing to serve requests"
+            ),
+            ChainStatus::Unverified => {
+                warn!("Backend didn't report its chain, refusing to serve requests")
+            }
+        }
+        *current = status;
+    }
+
+    /// Sends a single JSON-RPC call, returning its `result` if the node answered with one.
+    async fn call(&self, method: &str, params: Value) -> Option<Value> {
+        let url = self.url.read().clone();
+        match call(&self.client, &url, method, params).await {
+            Ok(result) => Some(result),
+            Err(e) => {
+                debug!(%method, error = %e, "Backend probe request failed");
+                None
+            }
+        }
+    }
+}
+
+/// Checks the backend live: times a head block query and asks for its version and chain.
+/// Nothing is cached and the periodic probe's results are left alone.
+pub async fn check_backend(rpc: &RpcConfig, timeout: Duration) -> Result<UpstreamCheck> {
+    let (client, url) = probe_client(rpc, timeout)?;
+
+    let started = Instant::now();
+    let (head, latency_ms) = match call(&client, &url, "eth_blockNumber", json!([])).await {
+        Ok(head) => (Ok(head), Some(started.elapsed().as_millis() as u64)),
+        Err(_) => {
+            let started = Instant::now();
+            let head = call(&client, &url, "chain_getHeader", json!([]))
+                .await
+                .map(|header| header.get("number").cloned().unwrap_or(Value::Null));
+            let latency_ms = head.is_ok().then(|| started.elapsed().as_millis() as u64);
+            (head, latency_ms)
+        }
+    };
+    let client_version = match call(&client, &url, "web3_clientVersion", json!([])).await {
+        Ok(Value::String(version)) => Some(version),
+        _ => match (
+            call(&client, &url, "system_name", json!([])).await,
+            call(&client, &url, "system_version", json!([])).await,
+        ) {
+            (Ok(Value::String(name)), Ok(Value::String(version))) => {
+                Some(format!("{name}/{version}"))
+            }
+            _ => None,
+        },
+    };
+    let chain_id = match call(&client, &url, "eth_chainId", json!([])).await {
+        Ok(Value::String(chain_id)) => Some(chain_id),
+        _ => match call(&client, &url, "chain_getBlockHash", json!([0])).await {
+            Ok(Value::String(genesis)) => Some(genesis),
+            _ => None,
+        },
+    };
+
+    Ok(UpstreamCheck {
+        url: url.to_string(),
+        reachable: head.is_ok(),
+        latency_ms,
+        head_block: head
+            .as_ref()
+            .ok()
+            .and_then(Value::as_str)
+            .and_then(parse_chain_number),
+        client_version,
+        chain_id,
+        error: head.err(),
+        checked_at: Utc::now(),
+    })
+}
+
+/// An HTTP client for probing the backend, sending its `[[rpc.upstream_auth]]` credentials,
+/// and the URL to send probes to.
+pub(crate) fn probe_client(rpc: &RpcConfig, timeout: Duration) -> Result<(reqwest::Client, Url)> {
+    let mut client = reqwest::Client::builder().timeout(timeout);
+    if let Some(auth) = rpc
+        .upstream_auth
+        .iter()
+        .find(|auth| auth.backend == rpc.proxy_to_url)
+    {
+        client = client.default_headers(auth.header_map()?);
+    }
+    if let Some(proxy) = &rpc.outbound_proxy {
+        client = client.proxy(reqwest_proxy(proxy)?);
+    }
+    if let Some(ca_cert_path) = &rpc.upstream_tls.ca_cert_path {
+        let pem = std::fs::read(ca_cert_path)?;
+        for cert in reqwest::Certificate::from_pem_bundle(&pem)? {
+            client = client.add_root_certificate(cert);
+        }
+    }
+    Ok((client.build()?, probe_url(rpc)?))
+}
+
+/// The URL probes are sent to. Probes go over HTTP; nodes serving WebSocket JSON-RPC accept
+/// HTTP on the same port.
+fn probe_url(rpc: &RpcConfig) -> Result<Url> {
+    let mut url = rpc.proxy_to_url.clone();
+    let scheme = match url.scheme() {
+        "ws" => Some("http"),
+        "wss" => Some("https"),
+        _ => None,
+    };
+    if let Some(scheme) = scheme {
+        url.set_scheme(scheme).map_err(|()| {
+            Error::AddressParseError(format!("Invalid backend URL {}", rpc.proxy_to_url))
+        })?;
+    }
+    Ok(url)
+}
+
+/// Sends a single JSON-RPC call, returning its `result`, or why there wasn't one.
+pub(crate) async fn call(
+    client: &reqwest::Client,
+    url: &Url,
+    method: &str,
+    params: Value,
+) -> std::result::Result<Value, String> {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": params,
+    });
+    let response = client
+        .post(url.clone())
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut body: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid response: {e}"))?;
+    if let Some(result) = body.get_mut("result") {
+        return Ok(result.take());
+    }
+    Err(match body.get("error") {
+        Some(error) => format!("{method} failed: {error}"),
+        None => format!("{method} returned no result"),
+    })
+}
+
+/// Compares chain IDs numerically when both are numbers (`0x1` and `1` are the same chain),
+/// and case-insensitively otherwise (genesis hashes).
+fn same_chain_id(expected: &str, reported: &str) -> bool {
+    match (parse_chain_number(expected), parse_chain_number(reported)) {
+        (Some(expected), Some(reported)) => expected == reported,
+        _ => expected.trim().eq_ignore_ascii_case(reported.trim()),
+    }
+}
+
+/// A decimal or `0x`-prefixed hex number, as nodes report chain IDs and block numbers.
+pub(crate) fn parse_chain_number(chain_id: &str) -> Option<u64> {
+    let chain_id = chain_id.trim();
+    match chain_id
+        .strip_prefix("0x")
+        .or_else(|| chain_id.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => chain_id.parse().ok(),
+    }
+}
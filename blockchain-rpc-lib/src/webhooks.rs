@@ -0,0 +1,502 @@
+use crate::Result;
+use crate::config::{OutboundProxyConfig, WebhookRetryConfig};
+use crate::firewall::WebhookEvent;
+use crate::labels::Labels;
+use crate::proxy::outbound::reqwest_proxy;
+use crate::store::StateStore;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sp_runtime::AccountId32;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, warn};
+use url::Url;
+
+/// Hex-encoded HMAC-SHA256 over `<timestamp>.<body>`, keyed with the webhook's secret.
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "x-gateway-signature";
+/// Unix timestamp (seconds) at which the delivery was signed. Receivers should reject
+/// deliveries with stale timestamps to prevent replay.
+pub const WEBHOOK_TIMESTAMP_HEADER: &str = "x-gateway-timestamp";
+
+/// Version of the webhook payload schema, sent as `version` in every delivery. Bumped when an
+/// existing event's fields change; new event types don't bump it, so receivers should ignore
+/// events they don't know.
+pub const WEBHOOK_SCHEMA_VERSION: u32 = 2;
+
+const DEAD_LETTERS_NAMESPACE: &str = "webhook_dead_letters";
+const LEGACY_DEAD_LETTERS_FILE: &str = "webhook_dead_letters.jsonl";
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The kinds of firewall events a webhook can subscribe to, named after the
+/// `WebhookEvent` variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WebhookEventType {
+    AccessGranted,
+    AccessDenied,
+    Banned,
+    TemporaryAccessExpired,
+    TemporaryIpAccessExpired,
+    AccessExpiringSoon,
+    RuleAdded,
+    RuleRemoved,
+    WebhookRegistered,
+    WebhookRemoved,
+    RateLimited,
+    RuleLimitExceeded,
+    ConnectionLimitExceeded,
+    WebSocketLimitExceeded,
+    UpstreamConnected,
+    UpstreamDisconnected,
+    UpstreamConnectFailed,
+    UpstreamHandshakeFailed,
+    MonthlyUsageSummary,
+    ConfigUpdated,
+    LatencySummary,
+    AutoBanned,
+    QuotaThresholdReached,
+    MaintenanceStarting,
+    MaintenanceEnded,
+    UpstreamSwitched,
+    DailyDigest,
+}
+
+impl WebhookEventType {
+    pub const ALL: [WebhookEventType; 27] = [
+        WebhookEventType::AccessGranted,
+        WebhookEventType::AccessDenied,
+        WebhookEventType::Banned,
+        WebhookEventType::TemporaryAccessExpired,
+        WebhookEventType::TemporaryIpAccessExpired,
+        WebhookEventType::AccessExpiringSoon,
+        WebhookEventType::RuleAdded,
+        WebhookEventType::RuleRemoved,
+        WebhookEventType::WebhookRegistered,
+        WebhookEventType::WebhookRemoved,
+        WebhookEventType::RateLimited,
+        WebhookEventType::RuleLimitExceeded,
+        WebhookEventType::ConnectionLimitExceeded,
+        WebhookEventType::WebSocketLimitExceeded,
+        WebhookEventType::UpstreamConnected,
+        WebhookEventType::UpstreamDisconnected,
+        WebhookEventType::UpstreamConnectFailed,
+        WebhookEventType::UpstreamHandshakeFailed,
+        WebhookEventType::MonthlyUsageSummary,
+        WebhookEventType::ConfigUpdated,
+        WebhookEventType::LatencySummary,
+        WebhookEventType::AutoBanned,
+        WebhookEventType::QuotaThresholdReached,
+        WebhookEventType::MaintenanceStarting,
+        WebhookEventType::MaintenanceEnded,
+        WebhookEventType::UpstreamSwitched,
+        WebhookEventType::DailyDigest,
+    ];
+
+    /// The fields of this event's payload and their JSON types, as published in the schema.
+    fn fields(self) -> &'static [(&'static str, &'static str)] {
+        const SOURCE: (&str, &str) = ("source", "string");
+        const TRANSPORT: (&str, &str) = ("transport", "\"Http\" | \"WebSocket\"");
+        const TARGET: (&str, &str) = ("target", "string");
+        match self {
+            WebhookEventType::AccessGranted => &[SOURCE, ("access_type", "string")],
+            WebhookEventType::AccessDenied => &[SOURCE, ("country", "string, if geolocated")],
+            WebhookEventType::Banned => &[SOURCE],
+            WebhookEventType::TemporaryAccessExpired => &[("account", "ss58 account")],
+            WebhookEventType::TemporaryIpAccessExpired => &[("ip", "string")],
+            WebhookEventType::AccessExpiringSoon => &[SOURCE, ("expires_at", "rfc3339 datetime")],
+            WebhookEventType::RuleAdded | WebhookEventType::RuleRemoved => {
+                &[("rule_type", "string"), ("value", "string")]
+            }
+            WebhookEventType::WebhookRegistered | WebhookEventType::WebhookRemoved => {
+                &[("url", "url")]
+            }
+            WebhookEventType::RateLimited => &[SOURCE, ("retry_after_secs", "integer")],
+            WebhookEventType::RuleLimitExceeded => &[
+                ("rule_type", "string"),
+                ("count", "integer"),
+                ("warn_at", "integer"),
+            ],
+            WebhookEventType::ConnectionLimitExceeded => {
+                &[SOURCE, ("limit_type", "string"), ("max", "integer")]
+            }
+            WebhookEventType::WebSocketLimitExceeded => {
+                &[SOURCE, ("limit_type", "string"), ("limit", "string")]
+            }
+            WebhookEventType::UpstreamConnected => &[TRANSPORT, TARGET],
+            WebhookEventType::UpstreamDisconnected => {
+                &[TRANSPORT, TARGET, ("duration_secs", "integer")]
+            }
+            WebhookEventType::UpstreamConnectFailed | WebhookEventType::UpstreamHandshakeFailed => {
+                &[TRANSPORT, TARGET, ("error", "string")]
+            }
+            WebhookEventType::MonthlyUsageSummary => &[
+                ("account", "ss58 account"),
+                ("month", "string"),
+                ("usage", "object"),
+                ("top_methods", "array"),
+            ],
+            WebhookEventType::ConfigUpdated => &[("settings", "array of strings")],
+            WebhookEventType::LatencySummary => &[
+                ("period_start", "rfc3339 datetime"),
+                ("period_end", "rfc3339 datetime"),
+                ("methods", "array"),
+            ],
+            WebhookEventType::AutoBanned => &[
+                SOURCE,
+                ("offense", "\"Denied\" | \"RateLimited\" | \"Malformed\""),
+                ("offenses", "integer"),
+                ("ban_secs", "integer"),
+                ("expires_at", "rfc3339 datetime"),
+            ],
+            WebhookEventType::QuotaThresholdReached => &[
+                SOURCE,
+                ("plan", "string"),
+                ("percent", "integer"),
+                ("used", "integer"),
+                ("quota", "integer"),
+            ],
+            WebhookEventType::MaintenanceStarting | WebhookEventType::MaintenanceEnded => &[
+                ("backend", "url"),
+                ("starts_at", "rfc3339 datetime"),
+                ("ends_at", "rfc3339 datetime"),
+                ("reason", "string, if set"),
+            ],
+            WebhookEventType::UpstreamSwitched => &[
+                ("old_targets", "array of urls"),
+                ("new_targets", "array of urls"),
+                ("drain_secs", "integer"),
+                ("switched_by", "string"),
+            ],
+            WebhookEventType::DailyDigest => &[
+                ("day", "string"),
+                ("since", "rfc3339 datetime"),
+                ("until", "rfc3339 datetime"),
+                ("requests", "integer"),
+                ("unique_ips", "integer"),
+                ("top_methods", "array"),
+                ("denials", "integer"),
+                ("bans", "integer"),
+                ("quota_exhaustions", "integer"),
+                ("upstream_availability_percent", "number, if measured"),
+            ],
+        }
+    }
+}
+
+/// The JSON body of a webhook delivery: the event, keyed by its type as before, next to the
+/// schema version, e.g. `{"version": 2, "Banned": {"source": "10.0.0.1"}}`. Events about a
+/// labelled account also carry its `labels`.
+#[derive(Debug, Serialize)]
+pub struct WebhookPayload<'a> {
+    pub version: u32,
+    #[serde(flatten)]
+    pub event: &'a WebhookEvent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<&'a Labels>,
+}
+
+impl<'a> WebhookPayload<'a> {
+    pub fn new(event: &'a WebhookEvent, labels: Option<&'a Labels>) -> Self {
+        WebhookPayload {
+            version: WEBHOOK_SCHEMA_VERSION,
+            event,
+            labels,
+        }
+    }
+}
+
+/// Describes the payloads of the current schema version, served by the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookSchema {
+    pub version: u32,
+    pub events: Vec<WebhookEventSchema>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookEventSchema {
+    pub event_type: WebhookEventType,
+    /// Field names mapped to their JSON types.
+    pub fields: BTreeMap<&'static str, &'static str>,
+}
+
+impl WebhookSchema {
+    pub fn current() -> Self {
+        WebhookSchema {
+            version: WEBHOOK_SCHEMA_VERSION,
+            events: WebhookEventType::ALL
+                .into_iter()
+                .map(|event_type| WebhookEventSchema {
+                    event_type,
+                    fields: event_type.fields().iter().copied().collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A webhook endpoint, with an optional shared secret used to sign deliveries.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WebhookTarget {
+    pub url: Url,
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+    /// Event types delivered to this webhook. Empty means every event.
+    #[serde(default)]
+    pub events: HashSet<WebhookEventType>,
+    /// If set, only events concerning this account are delivered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<AccountId32>,
+}
+
+impl WebhookTarget {
+    pub fn new(
+        url: Url,
+        secret: Option<String>,
+        events: HashSet<WebhookEventType>,
+        account: Option<AccountId32>,
+    ) -> Self {
+        WebhookTarget {
+            url,
+            secret,
+            events,
+            account,
+        }
+    }
+
+    /// Returns true if this webhook subscribes to `event`'s type and, when scoped to an
+    /// account, the event concerns that account.
+    pub fn is_subscribed(&self, event: &WebhookEvent) -> bool {
+        let event_type = event.event_type();
+        (self.events.is_empty() || self.events.contains(&event_type))
+            && self
+                .account
+                .as_ref()
+                .is_none_or(|account| event.concerns_account(account))
+    }
+}
+
+impl fmt::Debug for WebhookTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookTarget")
+            .field("url", &self.url)
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .field("events", &self.events)
+            .field("account", &self.account)
+            .finish()
+    }
+}
+
+/// Signs a webhook body, returning the hex-encoded HMAC-SHA256 of `<timestamp>.<body>`.
+pub fn sign_webhook_payload(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// A webhook delivery that failed every attempt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub url: Url,
+    pub event: serde_json::Value,
+    pub attempts: u32,
+    pub last_error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Failed webhook deliveries, kept in the state store so operators can inspect them and replay
+/// them once the endpoint is back.
+#[derive(Debug)]
+pub struct DeadLetterQueue {
+    store: Arc<dyn StateStore>,
+    lock: Mutex<()>,
+}
+
+impl DeadLetterQueue {
+    /// Opens the queue in `store`, moving in the deliveries earlier versions appended to a file
+    /// under `data_dir`.
+    pub fn open(store: Arc<dyn StateStore>, data_dir: &Path) -> Result<Self> {
+        let queue = DeadLetterQueue {
+            store,
+            lock: Mutex::new(()),
+        };
+        let path = data_dir.join(LEGACY_DEAD_LETTERS_FILE);
+        let file = match std::fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(queue),
+            Err(e) => return Err(e.into()),
+        };
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(letter) => queue.push(&letter)?,
+                Err(e) => warn!(error = %e, "Skipping corrupt dead-letter entry"),
+            }
+        }
+        std::fs::remove_file(&path)?;
+        Ok(queue)
+    }
+
+    /// Adds a failed delivery.
+    pub fn push(&self, letter: &DeadLetter) -> Result<()> {
+        // Ordered by failure time; the random suffix keeps letters of the same instant, or
+        // from other gateways sharing the store, apart
+        let mut key = (letter.failed_at.timestamp_micros().max(0) as u64)
+            .to_be_bytes()
+            .to_vec();
+        key.extend_from_slice(&rand::random::<u64>().to_be_bytes());
+        self.store
+            .insert(DEAD_LETTERS_NAMESPACE, &key, &serde_json::to_vec(letter)?)
+    }
+
+    /// Returns all failed deliveries, oldest first.
+    pub fn list(&self) -> Result<Vec<DeadLetter>> {
+        let _guard = self.lock.lock();
+        Ok(self.read()?.into_iter().map(|(_, letter)| letter).collect())
+    }
+
+    /// Removes and returns all failed deliveries, oldest first.
+    pub fn take_all(&self) -> Result<Vec<DeadLetter>> {
+        let _guard = self.lock.lock();
+        let mut letters = Vec::new();
+        for (key, letter) in self.read()? {
+            self.store.remove(DEAD_LETTERS_NAMESPACE, &key)?;
+            letters.push(letter);
+        }
+        Ok(letters)
+    }
+
+    fn read(&self) -> Result<Vec<(Vec<u8>, DeadLetter)>> {
+        let mut entries = self.store.entries(DEAD_LETTERS_NAMESPACE)?;
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        let mut letters = Vec::new();
+        for (key, value) in entries {
+            match serde_json::from_slice(&value) {
+                Ok(letter) => letters.push((key, letter)),
+                Err(e) => warn!(error = %e, "Skipping corrupt dead-letter entry"),
+            }
+        }
+        Ok(letters)
+    }
+}
+
+/// Delivers webhook events in the background, retrying failures with exponential backoff
+/// and moving deliveries that exhaust their attempts to the dead-letter queue.
+#[derive(Debug, Clone)]
+pub struct WebhookDispatcher {
+    client: reqwest::Client,
+    retry: WebhookRetryConfig,
+    dead_letters: Arc<DeadLetterQueue>,
+}
+
+impl WebhookDispatcher {
+    /// Deliveries go through `proxy` when set.
+    pub fn new(
+        retry: &WebhookRetryConfig,
+        dead_letters: DeadLetterQueue,
+        proxy: Option<&OutboundProxyConfig>,
+    ) -> Result<Self> {
+        let mut client = reqwest::Client::builder();
+        if let Some(proxy) = proxy {
+            client = client.proxy(reqwest_proxy(proxy)?);
+        }
+        Ok(WebhookDispatcher {
+            client: client.build()?,
+            retry: retry.clone(),
+            dead_letters: Arc::new(dead_letters),
+        })
+    }
+
+    pub fn dead_letters(&self) -> &DeadLetterQueue {
+        &self.dead_letters
+    }
+
+    /// Queues `body` for delivery to `target`.
+    pub fn dispatch(&self, target: WebhookTarget, body: Arc<Vec<u8>>) {
+        let dispatcher = self.clone();
+        tokio::spawn(async move { dispatcher.deliver_with_retries(target, body).await });
+    }
+
+    async fn deliver_with_retries(&self, target: WebhookTarget, body: Arc<Vec<u8>>) {
+        let max_attempts = self.retry.max_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            let error = match self.deliver(&target, &body).await {
+                Ok(()) => {
+                    debug!(url = %target.url, attempt, "Webhook notification sent successfully");
+                    return;
+                }
+                Err(error) => error,
+            };
+
+            if attempt >= max_attempts {
+                warn!(
+                    url = %target.url,
+                    attempts = attempt,
+                    %error,
+                    "Webhook notification failed, moving it to the dead-letter queue"
+                );
+                let letter = DeadLetter {
+                    url: target.url.clone(),
+                    event: serde_json::from_slice(&body).unwrap_or_default(),
+                    attempts: attempt,
+                    last_error: error,
+                    failed_at: Utc::now(),
+                };
+                if let Err(e) = self.dead_letters.push(&letter) {
+                    error!(url = %target.url, error = %e, "Failed to persist dead-lettered webhook");
+                }
+                return;
+            }
+
+            let delay = self.backoff(attempt);
+            debug!(url = %target.url, attempt, %error, ?delay, "Webhook notification failed, retrying");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn deliver(&self, target: &WebhookTarget, body: &[u8]) -> Result<(), String> {
+        // Signed per attempt so retries carry a fresh timestamp.
+        let timestamp = Utc::now().timestamp();
+        let mut request = self
+            .client
+            .post(target.url.clone())
+            .timeout(DELIVERY_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(WEBHOOK_TIMESTAMP_HEADER, timestamp.to_string());
+        if let Some(secret) = &target.secret {
+            request = request.header(
+                WEBHOOK_SIGNATURE_HEADER,
+                sign_webhook_payload(secret, timestamp, body),
+            );
+        }
+
+        match request.body(body.to_vec()).send().await {
+            Ok(response) if response.status().is_success() => Ok(()),
+            Ok(response) => Err(format!("HTTP {}", response.status())),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Delay before the attempt following `attempt`: the initial backoff doubled per
+    /// failed attempt, capped at the maximum backoff.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let initial = Duration::from_millis(self.retry.initial_backoff_ms);
+        let max = Duration::from_secs(self.retry.max_backoff_secs);
+        initial
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(max)
+    }
+}
@@ -0,0 +1,142 @@
+use blockchain_rpc_lib::access_windows::{self, AccessWindow};
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use blockchain_rpc_lib::testing::test_firewall;
+use chrono::{DateTime, Duration, Timelike, Utc};
+use serde_json::json;
+use sp_runtime::AccountId32;
+use std::sync::Arc;
+
+fn window(value: serde_json::Value) -> AccessWindow {
+    serde_json::from_value(value).unwrap()
+}
+
+fn at(time: &str) -> DateTime<Utc> {
+    time.parse().unwrap()
+}
+
+/// A window of every day from `from` to `to` after the current time, in UTC.
+fn window_around_now(from: Duration, to: Duration) -> AccessWindow {
+    let now = Utc::now();
+    let time = |offset: Duration| (now + offset).time().with_nanosecond(0).unwrap();
+    window(json!({"start": time(from).to_string(), "end": time(to).to_string()}))
+}
+
+#[test]
+fn windows_open_on_their_days_in_their_offset() {
+    let business_hours = window(json!({
+        "days": ["Mon", "Tue", "Wed", "Thu", "Fri"],
+        "start": "09:00",
+        "end": "17:00",
+        "utc_offset": "+02:00",
+    }));
+    // 2024-06-03 is a Monday.
+    assert!(business_hours.contains(at("2024-06-03T07:00:00Z")));
+    assert!(
+        !business_hours.contains(at("2024-06-03T15:00:00Z")),
+        "17:00 local"
+    );
+    assert!(!business_hours.contains(at("2024-06-03T06:59:59Z")));
+    assert!(
+        !business_hours.contains(at("2024-06-08T10:00:00Z")),
+        "Saturday"
+    );
+
+    let overnight = window(json!({"days": ["Sat"], "start": "22:00", "end": "02:00"}));
+    assert!(overnight.contains(at("2024-06-08T23:00:00Z")));
+    assert!(
+        overnight.contains(at("2024-06-09T01:00:00Z")),
+        "Sunday, opened Saturday"
+    );
+    assert!(
+        !overnight.contains(at("2024-06-08T01:00:00Z")),
+        "opened Friday"
+    );
+
+    assert!(
+        window(json!({"start": "09:00", "end": "09:00"}))
+            .validate()
+            .is_err()
+    );
+    assert!(
+        serde_json::from_value::<AccessWindow>(
+            json!({"start": "09:00", "end": "17:00", "utc_offset": "CEST"})
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn windows_report_when_they_next_open_or_close() {
+    let business_hours = window(json!({
+        "days": ["Mon", "Fri"],
+        "start": "09:00",
+        "end": "17:00",
+        "utc_offset": "+02:00",
+    }));
+    // 2024-06-03 is a Monday.
+    assert_eq!(
+        business_hours.next_change(at("2024-06-03T06:00:00Z")),
+        Some(at("2024-06-03T07:00:00Z"))
+    );
+    assert_eq!(
+        business_hours.next_change(at("2024-06-03T07:00:00Z")),
+        Some(at("2024-06-03T15:00:00Z"))
+    );
+    assert_eq!(
+        business_hours.next_change(at("2024-06-03T16:00:00Z")),
+        Some(at("2024-06-07T07:00:00Z")),
+        "Friday"
+    );
+
+    let overnight = window(json!({"days": ["Sat"], "start": "22:00", "end": "02:00"}));
+    assert_eq!(
+        overnight.next_change(at("2024-06-08T23:00:00Z")),
+        Some(at("2024-06-09T02:00:00Z"))
+    );
+
+    let windows = [business_hours, overnight];
+    assert_eq!(
+        access_windows::next_change(&windows, at("2024-06-07T16:00:00Z")),
+        Some(at("2024-06-08T22:00:00Z"))
+    );
+    assert_eq!(
+        access_windows::next_change(&[], at("2024-06-07T16:00:00Z")),
+        None
+    );
+}
+
+#[tokio::test]
+async fn scheduled_rules_only_allow_within_their_windows() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let firewall = test_firewall(&store).unwrap();
+    let open = AccountId32::new([1; 32]);
+    let closed = AccountId32::new([2; 32]);
+
+    firewall
+        .add_scheduled_account_rule(
+            open.clone(),
+            vec![window_around_now(Duration::hours(-1), Duration::hours(1))],
+        )
+        .await
+        .unwrap();
+    firewall
+        .add_scheduled_account_rule(
+            closed.clone(),
+            vec![window_around_now(Duration::hours(1), Duration::hours(2))],
+        )
+        .await
+        .unwrap();
+    assert!(firewall.is_account_allowed(&open).await);
+    assert!(!firewall.is_account_allowed(&closed).await);
+    assert_eq!(firewall.rules().access_windows.len(), 2);
+
+    // The windows are persisted with the rules.
+    let restored = test_firewall(&store).unwrap();
+    assert!(restored.is_account_allowed(&open).await);
+    assert!(!restored.is_account_allowed(&closed).await);
+
+    // Allowing the account again without windows lifts the restriction.
+    firewall.add_account_rule(closed.clone()).await.unwrap();
+    assert!(firewall.is_account_allowed(&closed).await);
+    assert_eq!(firewall.rules().access_windows.len(), 1);
+}
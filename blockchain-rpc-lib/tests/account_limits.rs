@@ -0,0 +1,68 @@
+use axum::body::{Body, to_bytes};
+use blockchain_rpc_lib::config::WebSocketConfig;
+use blockchain_rpc_lib::in_flight::InFlightRequests;
+use blockchain_rpc_lib::sessions::{SessionLimit, SessionRegistry};
+use sp_runtime::AccountId32;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+
+const CLIENT_IP: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+#[test]
+fn in_flight_requests_are_capped_per_account() {
+    let requests = Arc::new(InFlightRequests::default());
+    let alice = AccountId32::new([1; 32]);
+    let bob = AccountId32::new([2; 32]);
+
+    let first = requests.acquire(&alice, 2).unwrap();
+    let second = requests.acquire(&alice, 2).unwrap();
+    assert!(requests.acquire(&alice, 2).is_none());
+    // Other accounts have slots of their own
+    assert!(requests.acquire(&bob, 2).is_some());
+    assert_eq!(requests.in_flight(&alice), 2);
+
+    drop(first);
+    assert_eq!(requests.in_flight(&alice), 1);
+    let third = requests.acquire(&alice, 2).unwrap();
+    drop((second, third));
+    assert_eq!(requests.in_flight(&alice), 0);
+}
+
+#[tokio::test]
+async fn slots_are_held_until_the_response_is_sent() {
+    let requests = Arc::new(InFlightRequests::default());
+    let alice = AccountId32::new([1; 32]);
+    let slot = requests.acquire(&alice, 1).unwrap();
+    let body = slot.hold_until_sent(Body::from("0x1"));
+    assert_eq!(requests.in_flight(&alice), 1);
+
+    assert_eq!(to_bytes(body, usize::MAX).await.unwrap(), "0x1");
+    assert_eq!(requests.in_flight(&alice), 0);
+}
+
+#[test]
+fn a_plan_cap_replaces_the_account_connection_limit() {
+    let registry = Arc::new(SessionRegistry::new(&WebSocketConfig {
+        max_connections_per_account: Some(1),
+        ..Default::default()
+    }));
+    let alice = AccountId32::new([1; 32]);
+
+    let _first = registry.open(CLIENT_IP, Some(alice.clone()), None).unwrap();
+    assert_eq!(
+        registry
+            .open(CLIENT_IP, Some(alice.clone()), None)
+            .unwrap_err(),
+        SessionLimit::PerAccount(1)
+    );
+    let _second = registry
+        .open(CLIENT_IP, Some(alice.clone()), Some(3))
+        .unwrap();
+    let _third = registry
+        .open(CLIENT_IP, Some(alice.clone()), Some(3))
+        .unwrap();
+    assert_eq!(
+        registry.open(CLIENT_IP, Some(alice), Some(3)).unwrap_err(),
+        SessionLimit::PerAccount(3)
+    );
+}
@@ -0,0 +1,98 @@
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
+use axum::routing::get;
+use axum::{Json, Router};
+use blockchain_rpc_lib::Error;
+use blockchain_rpc_lib::client::{AdminClient, Credentials};
+use blockchain_rpc_lib::config::AuthConfig;
+use blockchain_rpc_lib::signature_auth::{AdminNonces, NONCE_HEADER, SignatureVerifier};
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use sp_core::Pair;
+use sp_core::sr25519::Pair as Sr25519Pair;
+use sp_runtime::AccountId32;
+use std::sync::Arc;
+use url::Url;
+
+fn nonce(value: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(NONCE_HEADER, HeaderValue::from_str(value).unwrap());
+    headers
+}
+
+#[test]
+fn nonces_must_increase_per_account() {
+    let nonces = AdminNonces::load(Arc::new(MemoryStore::default())).unwrap();
+    let alice = AccountId32::new([1; 32]);
+    let bob = AccountId32::new([2; 32]);
+
+    nonces.advance(&alice, &nonce("10")).unwrap();
+    assert!(matches!(
+        nonces.advance(&alice, &nonce("10")),
+        Err(Error::InvalidSignature(_))
+    ));
+    assert!(nonces.advance(&alice, &nonce("9")).is_err());
+    nonces.advance(&alice, &nonce("11")).unwrap();
+    assert_eq!(nonces.last(&alice), Some(11));
+
+    // Accounts count separately
+    nonces.advance(&bob, &nonce("1")).unwrap();
+    assert_eq!(nonces.last(&bob), Some(1));
+}
+
+#[test]
+fn nonces_must_be_decimal() {
+    let nonces = AdminNonces::load(Arc::new(MemoryStore::default())).unwrap();
+    let alice = AccountId32::new([1; 32]);
+    assert!(nonces.advance(&alice, &nonce("a1b2c3")).is_err());
+    assert!(nonces.advance(&alice, &HeaderMap::new()).is_err());
+    assert_eq!(nonces.last(&alice), None);
+}
+
+#[test]
+fn nonces_survive_a_restart() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let alice = AccountId32::new([1; 32]);
+    AdminNonces::load(store.clone())
+        .unwrap()
+        .advance(&alice, &nonce("42"))
+        .unwrap();
+
+    let reloaded = AdminNonces::load(store).unwrap();
+    assert_eq!(reloaded.last(&alice), Some(42));
+    assert!(reloaded.advance(&alice, &nonce("42")).is_err());
+    reloaded.advance(&alice, &nonce("43")).unwrap();
+}
+
+#[tokio::test]
+async fn admin_client_signs_with_increasing_nonces() {
+    let verifier = Arc::new(SignatureVerifier::new(&AuthConfig::default()));
+    let nonces = Arc::new(AdminNonces::load(Arc::new(MemoryStore::default())).unwrap());
+    let router = Router::new().route(
+        "/firewall/auto-bans",
+        get(move |headers: HeaderMap| {
+            let (verifier, nonces) = (verifier.clone(), nonces.clone());
+            async move {
+                let verified = verifier
+                    .verify(&headers, &Method::GET, "/firewall/auto-bans", &[])
+                    .and_then(Result::ok)
+                    .map(|account| nonces.advance(&account, &headers));
+                match verified {
+                    Some(Ok(())) => Ok(Json(Vec::<()>::new())),
+                    _ => Err(StatusCode::UNAUTHORIZED),
+                }
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    let (pair, _) = Sr25519Pair::generate();
+    let client = AdminClient::new(
+        Url::parse(&format!("http://{addr}/")).unwrap(),
+        Credentials::Signed(pair),
+    );
+    // Back-to-back requests within the same microsecond must still increase
+    for _ in 0..5 {
+        assert!(client.auto_bans().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+use blockchain_rpc_lib::Error;
+use blockchain_rpc_lib::api_keys::{ApiKeyStore, hash_key};
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use sp_runtime::AccountId32;
+use std::str::FromStr;
+use std::sync::Arc;
+
+const ALICE: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+const BOB: &str = "5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty";
+
+fn account(ss58: &str) -> AccountId32 {
+    AccountId32::from_str(ss58).unwrap()
+}
+
+#[test]
+fn issued_keys_survive_a_restart() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let keys = ApiKeyStore::load(store.clone()).unwrap();
+    keys.insert(hash_key("alice-key"), account(ALICE), |_| Ok(()))
+        .unwrap();
+    assert_eq!(keys.resolve("alice-key"), Some(account(ALICE)));
+    assert_eq!(keys.resolve("other-key"), None);
+
+    let reloaded = ApiKeyStore::load(store).unwrap();
+    assert_eq!(reloaded.len(), 1);
+    assert_eq!(reloaded.resolve("alice-key"), Some(account(ALICE)));
+}
+
+#[test]
+fn keys_bound_to_another_account_are_only_moved_when_allowed() {
+    let keys = ApiKeyStore::load(Arc::new(MemoryStore::default())).unwrap();
+    let key_hash = hash_key("alice-key");
+    keys.insert(key_hash, account(ALICE), |_| Ok(())).unwrap();
+
+    // Reissuing to the same account doesn't need permission
+    keys.insert(key_hash, account(ALICE), |_| {
+        panic!("no rebinding expected")
+    })
+    .unwrap();
+
+    let denied = keys.insert(key_hash, account(BOB), |owner| {
+        assert_eq!(owner, &account(ALICE));
+        Err(Error::AccessDeniedAdmin("not the owner".to_string()))
+    });
+    assert!(matches!(denied, Err(Error::AccessDeniedAdmin(_))));
+    assert_eq!(keys.owner(&key_hash), Some(account(ALICE)));
+
+    keys.insert(key_hash, account(BOB), |_| Ok(())).unwrap();
+    assert_eq!(keys.owner(&key_hash), Some(account(BOB)));
+}
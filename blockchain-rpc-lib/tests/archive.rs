@@ -0,0 +1,90 @@
+use blockchain_rpc_lib::archive::HistoricalCalls;
+use blockchain_rpc_lib::config::{ArchiveConfig, ArchiveHashRouting};
+use serde_json::{Value, json};
+
+const HASH: &str = "0x91b171bb158e2d3848fa23a9f1c25182fb8e20313b2c1eb49219da7a70ce90c3";
+
+fn calls(block_hashes: ArchiveHashRouting) -> HistoricalCalls {
+    HistoricalCalls::new(&ArchiveConfig {
+        url: "http://archive.internal:8545".parse().unwrap(),
+        recent_blocks: 128,
+        head_poll_secs: 6,
+        block_hashes,
+    })
+}
+
+fn call(method: &str, params: Value) -> Vec<u8> {
+    json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params})
+        .to_string()
+        .into_bytes()
+}
+
+#[test]
+fn dates_block_numbers_against_the_head() {
+    let calls = calls(ArchiveHashRouting::Archive);
+    let head = Some(10_000);
+    let balance = |block: Value| call("eth_getBalance", json!(["0xabc", block]));
+
+    assert!(!calls.is_historical(&balance(json!("latest")), head));
+    assert!(!calls.is_historical(&call("eth_getBalance", json!(["0xabc"])), head));
+    // Within `recent_blocks` of the head
+    assert!(!calls.is_historical(&balance(json!("0x2690")), head));
+    assert!(calls.is_historical(&balance(json!("0x100")), head));
+    assert!(calls.is_historical(&balance(json!(256)), head));
+    assert!(calls.is_historical(&balance(json!("earliest")), head));
+    assert!(calls.is_historical(&balance(json!({"blockNumber": "0x100"})), head));
+    assert!(calls.is_historical(
+        &call("eth_getStorageAt", json!(["0xabc", "0x0", "0x1"])),
+        head
+    ));
+    // Without a head, any number might be old
+    assert!(calls.is_historical(&balance(json!("0x2690")), None));
+    assert!(!calls.is_historical(&balance(json!("latest")), None));
+}
+
+#[test]
+fn log_filters_are_dated_by_their_start() {
+    let calls = calls(ArchiveHashRouting::Archive);
+    let head = Some(10_000);
+    let logs = |filter: Value| call("eth_getLogs", json!([filter]));
+
+    assert!(!calls.is_historical(&logs(json!({"address": "0xabc"})), head));
+    assert!(!calls.is_historical(
+        &logs(json!({"fromBlock": "0x2700", "toBlock": "latest"})),
+        head
+    ));
+    assert!(calls.is_historical(
+        &logs(json!({"fromBlock": "0x1", "toBlock": "0x2700"})),
+        head
+    ));
+    assert!(calls.is_historical(&logs(json!({"blockHash": HASH})), head));
+}
+
+#[test]
+fn block_hashes_follow_the_configured_routing() {
+    let head = Some(10_000);
+    let storage = call("state_getStorage", json!(["0x26aa", HASH]));
+    assert!(calls(ArchiveHashRouting::Archive).is_historical(&storage, head));
+    assert!(!calls(ArchiveHashRouting::Fast).is_historical(&storage, head));
+
+    let at_head = call("state_getStorage", json!(["0x26aa", null]));
+    assert!(!calls(ArchiveHashRouting::Archive).is_historical(&at_head, head));
+    let keys = call("state_getKeysPaged", json!(["0x26aa", 100, null, HASH]));
+    assert!(calls(ArchiveHashRouting::Archive).is_historical(&keys, head));
+}
+
+#[test]
+fn other_methods_and_batches() {
+    let calls = calls(ArchiveHashRouting::Archive);
+    let head = Some(10_000);
+    assert!(!calls.is_historical(&call("eth_chainId", json!([])), head));
+    assert!(!calls.is_historical(&call("eth_sendRawTransaction", json!(["0x1"])), head));
+    assert!(calls.is_historical(&call("state_queryStorage", json!([["0x26aa"], HASH])), head));
+
+    let batch = json!([
+        {"jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber"},
+        {"jsonrpc": "2.0", "id": 2, "method": "eth_call", "params": [{}, "0x10"]},
+    ]);
+    assert!(calls.is_historical(batch.to_string().as_bytes(), head));
+    assert!(!calls.is_historical(b"not json", head));
+}
@@ -0,0 +1,113 @@
+use blockchain_rpc_lib::audit::{AuditLog, AuditRecord};
+use blockchain_rpc_lib::billing::{self, GrantRow};
+use blockchain_rpc_lib::config::{AuditConfig, MeteringConfig};
+use blockchain_rpc_lib::metering::{UsageMeter, UsageSubject};
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use sp_runtime::AccountId32;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+use std::sync::Arc;
+
+#[test]
+fn closed_periods_are_kept_for_export_across_a_restart() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let config: MeteringConfig =
+        serde_json::from_value(json!({"report_interval_secs": 1})).unwrap();
+    let alice = AccountId32::new([1; 32]).to_string();
+    let bob = AccountId32::new([2; 32]);
+    let client = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    let meter = UsageMeter::load(store.clone(), Path::new("/nonexistent"), &config).unwrap();
+    let start = Utc::now();
+    meter.record_calls(
+        &UsageSubject::new(client, Some(&AccountId32::new([1; 32]))),
+        &["eth_call".to_string()],
+    );
+    meter.record_request(&UsageSubject::new(client, None));
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    assert!(meter.close_period_if_due());
+    // Collecting the period for `report_usage` leaves it in the history.
+    assert_eq!(meter.take_reports().len(), 1);
+    meter.record_request(&UsageSubject::new(client, Some(&bob)));
+    meter.persist().unwrap();
+
+    let restarted = UsageMeter::load(store, Path::new("/nonexistent"), &config).unwrap();
+    let periods = restarted.account_history(start - Duration::hours(1), Utc::now());
+    let rows = billing::usage_rows(&periods, None);
+    assert_eq!(rows.len(), 2, "IP usage isn't exported");
+    assert_eq!(rows[0].account, alice);
+    assert_eq!(rows[0].counters.calls, 1);
+    assert_eq!(rows[1].account, bob.to_string());
+    assert_eq!(rows[1].counters.requests, 1);
+    assert!(rows[0].period_end <= rows[1].period_start);
+
+    let alices = billing::usage_rows(&periods, Some(&alice));
+    assert_eq!(alices.len(), 1);
+
+    let before = restarted.account_history(start - Duration::hours(2), start - Duration::hours(1));
+    assert!(before.is_empty());
+
+    let csv = billing::usage_csv(&alices);
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "period_start,period_end,account,requests,calls,compute_units,request_bytes,\
+         response_bytes,ws_messages_in,ws_messages_out"
+    );
+    assert!(
+        lines
+            .next()
+            .unwrap()
+            .ends_with(&format!(",{alice},0,1,1,0,0,0,0"))
+    );
+    assert!(lines.next().is_none());
+}
+
+#[test]
+fn grant_history_comes_from_the_audit_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let config: AuditConfig = serde_json::from_value(json!({})).unwrap();
+    let audit = AuditLog::open(dir.path(), &config).unwrap();
+    let alice = AccountId32::new([1; 32]).to_string();
+
+    let start = Utc::now();
+    audit.record_job_call(1, 7, "grant_temporary", &alice);
+    audit.record_job_call(4, 8, "ban", &alice);
+    audit.record(AuditRecord::Admin {
+        actor: "ops, \"night shift\"".to_string(),
+        action: "revoke".to_string(),
+        target: alice.clone(),
+    });
+    audit.record_job_call(1, 9, "grant_temporary", "10.0.0.0/8");
+
+    let entries = audit
+        .entries_between(
+            start - Duration::seconds(1),
+            Utc::now() + Duration::seconds(1),
+        )
+        .unwrap();
+    assert_eq!(entries.len(), 4);
+    let rows: Vec<GrantRow> = billing::grant_rows(&entries, Some(&alice));
+    assert_eq!(rows.len(), 2, "bans aren't grants");
+    assert_eq!(rows[0].action, "grant_temporary");
+    assert_eq!((rows[0].job_id, rows[0].call_id), (Some(1), Some(7)));
+    assert_eq!(rows[1].action, "revoke");
+    assert_eq!(rows[1].actor.as_deref(), Some("ops, \"night shift\""));
+    assert_eq!(billing::grant_rows(&entries, None).len(), 3);
+
+    let csv = billing::grants_csv(&rows);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "timestamp,action,target,job_id,call_id,actor");
+    assert!(lines[1].ends_with(&format!(",grant_temporary,{alice},1,7,")));
+    assert!(lines[2].ends_with(&format!(",revoke,{alice},,,\"ops, \"\"night shift\"\"\"")));
+
+    let later = audit
+        .entries_between(
+            Utc::now() + Duration::seconds(1),
+            Utc::now() + Duration::hours(1),
+        )
+        .unwrap();
+    assert!(later.is_empty());
+}
@@ -0,0 +1,48 @@
+use blockchain_rpc_lib::circuit_breaker::{CircuitBreaker, CircuitState};
+use blockchain_rpc_lib::config::CircuitBreakerConfig;
+use serde_json::json;
+use std::time::Duration;
+
+fn breaker(open_secs: u64) -> CircuitBreaker {
+    let config: CircuitBreakerConfig = serde_json::from_value(json!({
+        "failure_threshold": 2,
+        "open_secs": open_secs,
+        "half_open_requests": 1,
+    }))
+    .unwrap();
+    CircuitBreaker::new(&config)
+}
+
+#[test]
+fn consecutive_failures_open_the_circuit() {
+    let breaker = breaker(60);
+    breaker.record_failure();
+    // A success in between starts the count over
+    breaker.record_success();
+    breaker.record_failure();
+    assert_eq!(breaker.state(), CircuitState::Closed);
+    assert!(breaker.try_acquire().is_ok());
+
+    breaker.record_failure();
+    assert_eq!(breaker.state(), CircuitState::Open);
+    let retry_after = breaker.try_acquire().unwrap_err();
+    assert!(retry_after <= Duration::from_secs(60), "{retry_after:?}");
+}
+
+#[test]
+fn trial_requests_close_or_reopen_the_circuit() {
+    let breaker = breaker(0);
+    breaker.record_failure();
+    breaker.record_failure();
+    assert_eq!(breaker.state(), CircuitState::Open);
+
+    // Once the open period is over, a trial request is let through
+    assert!(breaker.try_acquire().is_ok());
+    assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    breaker.record_failure();
+    assert_eq!(breaker.state(), CircuitState::Open);
+
+    assert!(breaker.try_acquire().is_ok());
+    breaker.record_success();
+    assert_eq!(breaker.state(), CircuitState::Closed);
+}
@@ -0,0 +1,101 @@
+use axum::body::Bytes;
+use axum::http::{HeaderMap, Method, StatusCode, header::AUTHORIZATION};
+use axum::routing::post;
+use axum::{Json, Router};
+use blockchain_rpc_lib::Error;
+use blockchain_rpc_lib::client::{AdminClient, Credentials, GatewayClient};
+use blockchain_rpc_lib::config::AuthConfig;
+use blockchain_rpc_lib::jobs::allow_access::AccessTarget;
+use blockchain_rpc_lib::signature_auth::SignatureVerifier;
+use serde_json::{Value, json};
+use sp_core::Pair;
+use sp_core::sr25519::Pair as Sr25519Pair;
+use sp_runtime::AccountId32;
+use std::sync::Arc;
+use url::Url;
+
+/// Serves `router` on a local port, returning its base URL.
+async fn serve(router: Router) -> Url {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    Url::parse(&format!("http://{addr}/")).unwrap()
+}
+
+/// A JSON-RPC endpoint answering with the account that signed the request.
+fn signed_echo() -> Router {
+    let verifier = Arc::new(SignatureVerifier::new(&AuthConfig::default()));
+    Router::new().route(
+        "/",
+        post(move |headers: HeaderMap, body: Bytes| {
+            let verifier = verifier.clone();
+            async move {
+                let call: Value = serde_json::from_slice(&body).unwrap();
+                match verifier.verify(&headers, &Method::POST, "/", &body) {
+                    Some(Ok(account)) => Json(json!({
+                        "jsonrpc": "2.0",
+                        "id": call["id"],
+                        "result": account.to_string(),
+                    })),
+                    _ => Json(json!({
+                        "jsonrpc": "2.0",
+                        "id": call["id"],
+                        "error": {"code": -32001, "message": "unauthenticated"},
+                    })),
+                }
+            }
+        }),
+    )
+}
+
+#[tokio::test]
+async fn signed_calls_authenticate_as_the_account() {
+    let url = serve(signed_echo()).await;
+    let (pair, _) = Sr25519Pair::generate();
+    let account = AccountId32::from(pair.public().0).to_string();
+
+    let client = GatewayClient::new(url.clone(), Credentials::Signed(pair));
+    // Each call carries a fresh nonce, so repeated calls aren't rejected as replays
+    for _ in 0..2 {
+        let signer: String = client.call("eth_blockNumber", json!([])).await.unwrap();
+        assert_eq!(signer, account);
+    }
+
+    let anonymous = GatewayClient::new(url, Credentials::Anonymous);
+    let error = anonymous
+        .call::<String>("eth_blockNumber", json!([]))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, Error::JsonRpcError(message) if message.contains("unauthenticated")));
+}
+
+#[tokio::test]
+async fn admin_rule_changes_send_the_token() {
+    let router = Router::new().route(
+        "/firewall/allow",
+        post(|headers: HeaderMap, Json(target): Json<Value>| async move {
+            let authorized = headers
+                .get(AUTHORIZATION)
+                .is_some_and(|value| value == "Bearer change-me");
+            match (authorized, target.get("Ip")) {
+                (false, _) => (StatusCode::UNAUTHORIZED, "Invalid admin token"),
+                (true, Some(_)) => (StatusCode::NO_CONTENT, ""),
+                (true, None) => (StatusCode::BAD_REQUEST, "Expected an IP"),
+            }
+        }),
+    );
+    let url = serve(router).await;
+    let target = AccessTarget::Ip("10.0.0.0/8".to_string());
+
+    let admin = AdminClient::new(url.clone(), Credentials::Bearer("change-me".to_string()));
+    admin.allow(&target).await.unwrap();
+
+    let wrong_token = AdminClient::new(url, Credentials::Bearer("guess".to_string()));
+    match wrong_token.allow(&target).await.unwrap_err() {
+        Error::GatewayRequestFailed { status, message } => {
+            assert_eq!(status, 401);
+            assert_eq!(message, "Invalid admin token");
+        }
+        e => panic!("unexpected error: {e}"),
+    }
+}
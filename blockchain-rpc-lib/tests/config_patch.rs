@@ -0,0 +1,147 @@
+use blockchain_rpc_lib::config::ServiceConfig;
+use blockchain_rpc_lib::config_patch::{
+    ConfigChange, compose_patches, diff_configs, merge_patch, patch_config, remove_from_patch,
+};
+use blockchain_rpc_lib::runtime_config::RpcOverrides;
+use serde_json::json;
+
+fn example_config() -> ServiceConfig {
+    ServiceConfig::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../config.toml")).unwrap()
+}
+
+#[test]
+fn merge_patch_follows_rfc_7396() {
+    let mut target = json!({"a": "b", "c": {"d": "e", "f": "g"}, "list": [1, 2]});
+    merge_patch(
+        &mut target,
+        &json!({"a": "z", "c": {"f": null}, "list": [3], "new": {"x": 1}}),
+    );
+    assert_eq!(
+        target,
+        json!({"a": "z", "c": {"d": "e"}, "list": [3], "new": {"x": 1}})
+    );
+}
+
+#[test]
+fn composed_patches_keep_removals() {
+    let mut first = json!({"rpc": {"request_timeout_secs": 60}, "metering": null});
+    compose_patches(
+        &mut first,
+        &json!({"rpc": {"max_body_size_bytes": 1024, "request_timeout_secs": null}}),
+    );
+    assert_eq!(
+        first,
+        json!({
+            "rpc": {"request_timeout_secs": null, "max_body_size_bytes": 1024},
+            "metering": null
+        })
+    );
+
+    remove_from_patch(&mut first, "rpc.max_body_size_bytes");
+    assert_eq!(
+        first,
+        json!({"rpc": {"request_timeout_secs": null}, "metering": null})
+    );
+}
+
+#[test]
+fn patch_changes_only_the_patched_settings() {
+    let config = example_config();
+    let patched = patch_config(
+        &config,
+        &json!({"firewall": {"rate_limit": {"burst": 250}}, "rpc": {"request_timeout_secs": 45}}),
+    )
+    .unwrap();
+    assert_eq!(patched.firewall.rate_limit.as_ref().unwrap().burst, 250);
+    assert_eq!(patched.rpc.request_timeout_secs, 45);
+    assert_eq!(patched.rpc.proxy_to_url, config.rpc.proxy_to_url);
+
+    let changes = diff_configs(&config, &patched).unwrap();
+    assert_eq!(
+        changes,
+        vec![
+            ConfigChange {
+                path: "firewall.rate_limit.burst".to_string(),
+                old: Some("100".to_string()),
+                new: Some("250".to_string()),
+            },
+            ConfigChange {
+                path: "rpc.request_timeout_secs".to_string(),
+                old: Some(config.rpc.request_timeout_secs.to_string()),
+                new: Some("45".to_string()),
+            },
+        ]
+    );
+    assert_eq!(
+        changes[0].setting(),
+        "firewall.rate_limit.burst: 100 -> 250"
+    );
+}
+
+#[test]
+fn unchanged_config_has_no_diff() {
+    let config = example_config();
+    let patched = patch_config(&config, &json!({})).unwrap();
+    assert!(diff_configs(&config, &patched).unwrap().is_empty());
+}
+
+#[test]
+fn invalid_patches_are_rejected() {
+    let config = example_config();
+    assert!(patch_config(&config, &json!([1, 2])).is_err());
+    assert!(patch_config(&config, &json!({"rpc": {"request_timeout_secs": "soon"}})).is_err());
+    assert!(
+        patch_config(
+            &config,
+            &json!({"firewall": {"allow_countries": ["Germany"]}})
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn secrets_are_redacted_from_the_diff() {
+    let config = example_config();
+    let patched = patch_config(
+        &config,
+        &json!({"webhooks": {"endpoints": [{"url": "https://hooks.example.com/", "secret": "hunter2"}]}}),
+    )
+    .unwrap();
+    let changes = diff_configs(&config, &patched).unwrap();
+    assert!(!changes.is_empty());
+    assert!(changes.iter().all(|change| {
+        !change
+            .new
+            .as_deref()
+            .unwrap_or_default()
+            .contains("hunter2")
+    }));
+}
+
+#[test]
+fn later_changes_replace_earlier_ones() {
+    let mut overrides = RpcOverrides {
+        request_timeout_secs: Some(60),
+        max_body_size_bytes: Some(2048),
+        ..RpcOverrides::default()
+    };
+    // A patch touching a setting set directly takes it over
+    overrides.merge(RpcOverrides {
+        config_patch: Some(json!({"rpc": {"request_timeout_secs": 90}})),
+        ..RpcOverrides::default()
+    });
+    assert_eq!(overrides.request_timeout_secs, None);
+    assert_eq!(overrides.max_body_size_bytes, Some(2048));
+
+    // A setting set directly is dropped from the patch
+    overrides.merge(RpcOverrides {
+        request_timeout_secs: Some(120),
+        ..RpcOverrides::default()
+    });
+    assert_eq!(overrides.config_patch, Some(json!({"rpc": {}})));
+
+    let mut config = example_config();
+    overrides.apply(&mut config);
+    assert_eq!(config.rpc.request_timeout_secs, 120);
+    assert_eq!(config.rpc.max_body_size_bytes, 2048);
+}
@@ -0,0 +1,123 @@
+use blockchain_rpc_lib::Error;
+use blockchain_rpc_lib::config::ServiceConfig;
+
+const EXAMPLE_CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../config.toml");
+
+fn example_config() -> ServiceConfig {
+    ServiceConfig::load(EXAMPLE_CONFIG).unwrap()
+}
+
+#[test]
+fn example_config_has_no_problems() {
+    assert!(example_config().problems().is_empty());
+}
+
+#[test]
+fn every_problem_is_reported_with_its_setting() {
+    let mut config = example_config();
+    assert_eq!(config.rpc.listen_addr.port(), 8545);
+    config.admin.listen_addr = Some("127.0.0.1:8545".parse().unwrap());
+    config.rpc.request_timeout_secs = 0;
+    config.rpc.upstreams = vec![
+        // The gateway itself, and the default backend a second time
+        "http://127.0.0.1:8545".parse().unwrap(),
+        config.rpc.proxy_to_url.clone(),
+        "unix:/run/node.sock".parse().unwrap(),
+    ];
+    config.webhooks.event_urls = vec!["ftp://example.com/hooks".parse().unwrap()];
+
+    let paths: Vec<String> = config
+        .problems()
+        .into_iter()
+        .map(|problem| problem.path)
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            "admin.listen_addr",
+            "rpc.upstreams[0]",
+            "rpc.upstreams[1]",
+            "rpc.upstreams[2]",
+            "rpc.request_timeout_secs",
+            "webhooks.event_urls[0]",
+        ]
+    );
+
+    match config.validate() {
+        Err(Error::InvalidConfig(problems)) => assert_eq!(problems.len(), 6),
+        other => panic!("expected every problem, got {other:?}"),
+    }
+}
+
+#[test]
+fn unauthenticated_admin_apis_must_listen_on_loopback() {
+    let mut config = example_config();
+    config.admin.listen_addr = Some("0.0.0.0:9615".parse().unwrap());
+    config.admin.token = None;
+    config.admin.accounts.clear();
+    let paths: Vec<String> = config
+        .problems()
+        .into_iter()
+        .map(|problem| problem.path)
+        .collect();
+    assert_eq!(paths, ["admin.listen_addr"]);
+
+    config.admin.token = Some("change-me".to_string());
+    assert!(config.problems().is_empty());
+    config.admin.token = None;
+    config.admin.listen_addr = Some("127.0.0.1:9615".parse().unwrap());
+    assert!(config.problems().is_empty());
+}
+
+#[test]
+fn rate_limits_must_refill_at_a_finite_positive_rate() {
+    for requests_per_sec in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        let mut config = example_config();
+        let mut rate_limit = config.firewall.rate_limit.clone().unwrap();
+        rate_limit.requests_per_sec = requests_per_sec;
+        config.firewall.rate_limit = Some(rate_limit.clone());
+        config.websocket.message_rate = Some(rate_limit.clone());
+        config.plans.insert(
+            "basic".to_string(),
+            serde_json::from_value(serde_json::json!({})).unwrap(),
+        );
+        config.plans.get_mut("basic").unwrap().rate_limit = Some(rate_limit);
+
+        let paths: Vec<String> = config
+            .problems()
+            .into_iter()
+            .map(|problem| problem.path)
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "firewall.rate_limit",
+                "websocket.message_rate",
+                "plans.basic.rate_limit",
+            ],
+            "requests_per_sec = {requests_per_sec}"
+        );
+    }
+}
+
+#[test]
+fn values_that_fail_to_parse_are_located() {
+    let example = std::fs::read_to_string(EXAMPLE_CONFIG).unwrap();
+    let typo = example.replacen(
+        "\"127.0.0.1\",\n    \"::1\",\n",
+        "\"127.0.0.1\",\n    \"::1\",\n    \"10.0.0.0/33\",\n",
+        1,
+    );
+    assert_ne!(typo, example);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, typo).unwrap();
+
+    match ServiceConfig::load(&path) {
+        Err(Error::InvalidConfig(problems)) => {
+            assert_eq!(problems.len(), 1);
+            assert_eq!(problems[0].path, "firewall.allow_ips[2]");
+        }
+        other => panic!("expected the invalid network, got {other:?}"),
+    }
+}
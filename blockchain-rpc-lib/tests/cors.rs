@@ -0,0 +1,67 @@
+use blockchain_rpc_lib::config::CorsConfig;
+use blockchain_rpc_lib::cors::OriginPatterns;
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+fn cors(origins: &[&str]) -> CorsConfig {
+    CorsConfig {
+        allowed_origins: strings(origins),
+        allowed_methods: strings(&["GET", "POST"]),
+        allowed_headers: strings(&["content-type", "authorization"]),
+        allow_credentials: false,
+        max_age_secs: 600,
+    }
+}
+
+#[test]
+fn origin_patterns_match_wildcards() {
+    let patterns = OriginPatterns::new(&strings(&[
+        "https://app.example.com",
+        "https://*.example.org",
+        "http://localhost:*",
+    ]));
+    assert!(patterns.matches("https://app.example.com"));
+    assert!(patterns.matches("HTTPS://App.Example.com"));
+    assert!(!patterns.matches("https://app.example.com.evil.net"));
+    assert!(patterns.matches("https://a.example.org"));
+    assert!(patterns.matches("https://a.b.example.org"));
+    assert!(!patterns.matches("https://example.org"));
+    assert!(!patterns.matches("http://a.example.org"));
+    assert!(patterns.matches("http://localhost:3000"));
+    assert!(!patterns.matches("http://localhost.evil.net"));
+
+    assert!(OriginPatterns::new(&strings(&["*"])).matches("https://anything.net"));
+}
+
+#[test]
+fn credentials_need_explicit_lists() {
+    let mut config = cors(&["https://app.example.com"]);
+    config.allow_credentials = true;
+    config.validate().unwrap();
+
+    let mut any_origin = config.clone();
+    any_origin.allowed_origins = strings(&["*"]);
+    assert!(any_origin.validate().is_err());
+    let mut any_header = config.clone();
+    any_header.allowed_headers = strings(&["*"]);
+    assert!(any_header.validate().is_err());
+
+    // Patterns are explicit enough
+    let mut pattern = config;
+    pattern.allowed_origins = strings(&["https://*.example.com"]);
+    pattern.validate().unwrap();
+}
+
+#[test]
+fn invalid_entries_are_rejected() {
+    assert!(cors(&[]).validate().is_err());
+    assert!(cors(&["https://app.example.com\n"]).validate().is_err());
+    let mut config = cors(&["*"]);
+    config.allowed_methods = strings(&["GET POST"]);
+    assert!(config.validate().is_err());
+    let mut config = cors(&["*"]);
+    config.allowed_headers = strings(&["x header"]);
+    assert!(config.validate().is_err());
+}
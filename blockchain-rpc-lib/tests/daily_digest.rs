@@ -0,0 +1,111 @@
+use blockchain_rpc_lib::config::DailyDigestConfig;
+use blockchain_rpc_lib::digest::DailyDigestCollector;
+use blockchain_rpc_lib::firewall::WebhookEvent;
+use chrono::{Duration, NaiveTime, Utc};
+use std::net::IpAddr;
+
+fn config(top_methods: usize, max_unique_ips: usize) -> DailyDigestConfig {
+    DailyDigestConfig {
+        top_methods,
+        max_unique_ips,
+    }
+}
+
+fn ip(ip: &str) -> IpAddr {
+    ip.parse().unwrap()
+}
+
+fn methods(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn closes_once_the_day_is_over() {
+    let collector = DailyDigestCollector::new(&config(10, 1000));
+    collector.record_request(ip("203.0.113.7"));
+    assert!(collector.close_day_if_due(Utc::now()).is_none());
+
+    let tomorrow = Utc::now() + Duration::days(1);
+    let digest = collector.close_day_if_due(tomorrow).unwrap();
+    assert_eq!(digest.day, Utc::now().date_naive());
+    assert_eq!(digest.requests, 1);
+    let midnight = tomorrow.date_naive().and_time(NaiveTime::MIN).and_utc();
+    assert_eq!(digest.until, midnight);
+
+    // The next day starts empty
+    assert!(collector.close_day_if_due(tomorrow).is_none());
+    let next = collector
+        .close_day_if_due(tomorrow + Duration::days(1))
+        .unwrap();
+    assert_eq!(next.since, midnight);
+    assert_eq!(next.requests, 0);
+}
+
+#[test]
+fn aggregates_the_days_traffic() {
+    let collector = DailyDigestCollector::new(&config(2, 1000));
+    collector.record_request(ip("203.0.113.7"));
+    collector.record_request(ip("203.0.113.7"));
+    // The IPv4-mapped form of a known client isn't counted again
+    collector.record_request(ip("::ffff:203.0.113.7"));
+    collector.record_request(ip("198.51.100.1"));
+    collector.record_calls(&methods(&["eth_call", "eth_call", "eth_blockNumber"]));
+    collector.record_calls(&methods(&["eth_getLogs", "eth_call"]));
+    collector.record_denial();
+    collector.record_quota_exhausted();
+    collector.sample_upstream(true);
+    collector.sample_upstream(true);
+    collector.sample_upstream(true);
+    collector.sample_upstream(false);
+
+    let digest = collector
+        .close_day_if_due(Utc::now() + Duration::days(1))
+        .unwrap();
+    assert_eq!(digest.requests, 4);
+    assert_eq!(digest.unique_ips, 2);
+    let top: Vec<(&str, u64)> = digest
+        .top_methods
+        .iter()
+        .map(|count| (count.method.as_str(), count.calls))
+        .collect();
+    assert_eq!(top, [("eth_call", 3), ("eth_blockNumber", 1)]);
+    assert_eq!(digest.denials, 1);
+    assert_eq!(digest.quota_exhaustions, 1);
+    assert_eq!(digest.upstream_availability_percent, Some(75.0));
+}
+
+#[test]
+fn counts_added_bans() {
+    let collector = DailyDigestCollector::new(&config(10, 1000));
+    collector.observe(&WebhookEvent::RuleAdded {
+        rule_type: "DenyIP".to_string(),
+        value: "203.0.113.0/24".to_string(),
+    });
+    collector.observe(&WebhookEvent::RuleAdded {
+        rule_type: "IP".to_string(),
+        value: "198.51.100.1".to_string(),
+    });
+    // Requests blocked by an existing ban aren't new bans
+    collector.observe(&WebhookEvent::Banned {
+        source: "203.0.113.7".to_string(),
+    });
+
+    let digest = collector
+        .close_day_if_due(Utc::now() + Duration::days(1))
+        .unwrap();
+    assert_eq!(digest.bans, 1);
+    assert_eq!(digest.upstream_availability_percent, None);
+}
+
+#[test]
+fn unique_ips_are_capped() {
+    let collector = DailyDigestCollector::new(&config(10, 2));
+    for last in 1..=5 {
+        collector.record_request(ip(&format!("203.0.113.{last}")));
+    }
+    let digest = collector
+        .close_day_if_due(Utc::now() + Duration::days(1))
+        .unwrap();
+    assert_eq!(digest.requests, 5);
+    assert_eq!(digest.unique_ips, 2);
+}
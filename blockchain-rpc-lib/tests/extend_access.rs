@@ -0,0 +1,55 @@
+use blockchain_rpc_lib::config::PlanConfig;
+use blockchain_rpc_lib::plans::{ActivePlan, Plans};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use std::collections::HashMap;
+
+fn plans() -> Plans {
+    let pro: PlanConfig = serde_json::from_value(json!({"request_quota": 10})).unwrap();
+    Plans::new(&HashMap::from([("pro".to_string(), pro)]))
+}
+
+fn active(plans: &Plans, extensions: u32, granted_at: chrono::DateTime<Utc>) -> ActivePlan {
+    ActivePlan {
+        holder: "203.0.113.0/24".to_string(),
+        plan: plans.get("pro").unwrap(),
+        granted_at,
+        expires_at: granted_at + Duration::hours(1),
+        extensions,
+    }
+}
+
+#[test]
+fn extensions_stack_the_quota_and_keep_usage() {
+    let plans = plans();
+    let granted_at = Utc::now();
+    let plan = plans.get("pro").unwrap();
+
+    let grant = active(&plans, 0, granted_at);
+    assert!(!plan.charge_quota(&grant, 8).unwrap().exhausted);
+    assert!(plan.charge_quota(&grant, 3).unwrap().exhausted);
+
+    // Extending the same grant adds the quota once more on top of what's used
+    let extended = active(&plans, 1, granted_at);
+    assert_eq!(plan.quota_remaining(&extended), Some(12));
+    let charge = plan.charge_quota(&extended, 12).unwrap();
+    assert!(!charge.exhausted);
+    assert_eq!((charge.used, charge.quota), (20, 20));
+    assert!(plan.charge_quota(&extended, 1).unwrap().exhausted);
+}
+
+#[test]
+fn a_new_grant_starts_over() {
+    let plans = plans();
+    let plan = plans.get("pro").unwrap();
+    let granted_at = Utc::now();
+    assert!(
+        !plan
+            .charge_quota(&active(&plans, 2, granted_at), 30)
+            .unwrap()
+            .exhausted
+    );
+
+    let renewed = active(&plans, 0, granted_at + Duration::hours(2));
+    assert_eq!(plan.quota_remaining(&renewed), Some(10));
+}
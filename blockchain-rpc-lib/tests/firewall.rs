@@ -0,0 +1,225 @@
+use blockchain_rpc_lib::auto_ban::Offense;
+use blockchain_rpc_lib::context::TemporaryAccessRecord;
+use blockchain_rpc_lib::firewall::{AccessDecision, AccessSurface, Firewall};
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use blockchain_rpc_lib::testing::test_firewall_with;
+use chrono::Utc;
+use ipnetwork::IpNetwork;
+use serde_json::{Value, json};
+use sp_runtime::AccountId32;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A firewall on a fresh in-memory store, with the `[firewall]` section `config`.
+fn firewall(config: Value) -> Firewall {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    test_firewall_with(&store, config).unwrap()
+}
+
+fn ip(ip: &str) -> IpAddr {
+    ip.parse().unwrap()
+}
+
+fn net(net: &str) -> IpNetwork {
+    net.parse().unwrap()
+}
+
+/// A temporary grant from now until `secs` from now.
+fn grant(secs: i64) -> TemporaryAccessRecord {
+    let now = Utc::now();
+    TemporaryAccessRecord {
+        granted_at: now,
+        expires_at: now + chrono::Duration::seconds(secs),
+        plan: None,
+        extensions: 0,
+    }
+}
+
+async fn http_decision(firewall: &Firewall, ip: IpAddr) -> AccessDecision {
+    firewall.check_access(&ip, None, AccessSurface::Http).await
+}
+
+#[test]
+fn rate_limits_refill_token_buckets_per_ip() {
+    let firewall = firewall(json!({"rate_limit": {"requests_per_sec": 1.0, "burst": 2}}));
+    let limiter = firewall.rate_limiter().unwrap();
+    let (client, other) = (ip("203.0.113.1"), ip("203.0.113.2"));
+
+    assert!(limiter.check(client).is_ok());
+    assert!(limiter.check(client).is_ok());
+    let retry_after = limiter.check(client).unwrap_err();
+    assert!(retry_after <= Duration::from_secs(1), "{retry_after:?}");
+    assert_eq!(firewall.rate_limit_remaining(&client), Some(0));
+    // Each IP has a bucket of its own
+    assert!(limiter.check(other).is_ok());
+    // Batches need all their tokens at once, and never fit if larger than the burst
+    assert!(limiter.check_n(other, 2).is_err());
+    assert_eq!(limiter.check_n(other, 3), Err(Duration::MAX));
+
+    std::thread::sleep(Duration::from_millis(1100));
+    assert!(limiter.check(client).is_ok());
+}
+
+#[tokio::test]
+async fn deny_rules_take_precedence_over_every_allow_rule() {
+    let firewall = firewall(json!({
+        "allow_unrestricted_access": true,
+        "allow_ips": ["203.0.113.0/24"],
+        "identity": {"combine": "either"},
+    }));
+    let (banned, allowed) = (ip("203.0.113.7"), ip("203.0.113.8"));
+    let account = AccountId32::new([1; 32]);
+    assert_eq!(
+        http_decision(&firewall, banned).await,
+        AccessDecision::Allowed
+    );
+
+    // What the `ban_target` job applies for an IP/CIDR and an account
+    firewall.ban_ip(net("203.0.113.7/32")).await.unwrap();
+    firewall.add_account_rule(account.clone()).await.unwrap();
+    firewall.ban_account(account.clone()).await.unwrap();
+
+    assert_eq!(
+        http_decision(&firewall, banned).await,
+        AccessDecision::Denied
+    );
+    assert_eq!(
+        http_decision(&firewall, allowed).await,
+        AccessDecision::Allowed
+    );
+    assert!(!firewall.is_account_allowed(&account).await);
+    // Even when the IP alone would be let through
+    assert_eq!(
+        firewall
+            .check_access(&allowed, Some(&account), AccessSurface::Http)
+            .await,
+        AccessDecision::Denied
+    );
+    assert_eq!(firewall.stats().bans, 2);
+}
+
+#[tokio::test]
+async fn temporary_ip_grants_cover_their_network_until_they_expire() {
+    let firewall = firewall(json!({}));
+    let network = net("198.51.100.0/24");
+    firewall
+        .grant_temporary_ip_access(network, grant(3600))
+        .await
+        .unwrap();
+
+    assert!(firewall.is_allowed(&ip("198.51.100.20")).await);
+    assert!(!firewall.is_allowed(&ip("198.51.101.20")).await);
+    let granted = firewall.active_temporary_ip_access(network).unwrap();
+
+    let extended = firewall
+        .extend_temporary_ip_access(network, Duration::from_secs(60), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        extended.expires_at,
+        granted.expires_at + chrono::Duration::seconds(60)
+    );
+    assert_eq!(extended.extensions, 1);
+
+    let expired = net("192.0.2.0/24");
+    firewall
+        .grant_temporary_ip_access(expired, grant(-1))
+        .await
+        .unwrap();
+    assert!(!firewall.is_allowed(&ip("192.0.2.1")).await);
+    assert!(firewall.active_temporary_ip_access(expired).is_none());
+    assert_eq!(firewall.cleanup_expired_access().await.expired, 1);
+    assert_eq!(firewall.stats().temporary_ip_grants, 1);
+}
+
+#[tokio::test]
+async fn cached_decisions_follow_rule_changes_and_grant_expiry() {
+    let firewall = firewall(json!({"decision_cache": {"ttl_ms": 60_000}}));
+    let client = ip("198.51.100.20");
+    assert_eq!(
+        http_decision(&firewall, client).await,
+        AccessDecision::Denied
+    );
+
+    // A rule change invalidates the cached denial
+    firewall
+        .grant_temporary_ip_access(net("198.51.100.0/24"), grant(1))
+        .await
+        .unwrap();
+    assert_eq!(
+        http_decision(&firewall, client).await,
+        AccessDecision::Allowed
+    );
+
+    // The allowance is cached no longer than the grant lasts, well short of the TTL
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(
+        http_decision(&firewall, client).await,
+        AccessDecision::Denied
+    );
+}
+
+#[tokio::test]
+async fn repeat_offenders_are_banned_until_lifted() {
+    let firewall = firewall(json!({
+        "allow_unrestricted_access": true,
+        "auto_ban": {"max_offenses": 3, "ban_secs": 60, "ipv4_prefix": 24},
+    }));
+    let (offender, neighbour) = (ip("203.0.113.1"), ip("203.0.113.2"));
+
+    for _ in 0..2 {
+        firewall.record_offense(&offender, Offense::Denied).await;
+    }
+    assert!(firewall.auto_bans().is_empty());
+    firewall
+        .record_offense(&offender, Offense::RateLimited)
+        .await;
+
+    let bans = firewall.auto_bans();
+    assert_eq!(bans.len(), 1);
+    assert_eq!(bans[0].network, net("203.0.113.0/24"));
+    assert_eq!(bans[0].offense, Offense::RateLimited);
+    assert_eq!(
+        http_decision(&firewall, neighbour).await,
+        AccessDecision::Denied
+    );
+    assert_eq!(
+        http_decision(&firewall, ip("203.0.114.1")).await,
+        AccessDecision::Allowed
+    );
+
+    assert!(firewall.lift_auto_ban(&net("203.0.113.0/24")).await);
+    assert!(!firewall.lift_auto_ban(&net("203.0.113.0/24")).await);
+    assert!(firewall.auto_bans().is_empty());
+    assert_eq!(
+        http_decision(&firewall, offender).await,
+        AccessDecision::Allowed
+    );
+}
+
+#[tokio::test]
+async fn decisions_over_budget_fall_back_to_the_surface_policy() {
+    // No evaluation finishes within a zero budget
+    let firewall = firewall(json!({
+        "allow_ips": ["203.0.113.0/24"],
+        "decision_budget": {"budget_ms": 0, "http": "closed", "session_recheck": "open"},
+    }));
+    let client = ip("203.0.113.1");
+
+    assert_eq!(
+        http_decision(&firewall, client).await,
+        AccessDecision::Unavailable
+    );
+    assert_eq!(
+        firewall
+            .check_access(&client, None, AccessSurface::SessionRecheck)
+            .await,
+        AccessDecision::Allowed
+    );
+    let breaches = firewall.stats().decision_budget_breaches;
+    assert_eq!(
+        (breaches.http, breaches.websocket, breaches.session_recheck),
+        (1, 0, 1)
+    );
+}
@@ -0,0 +1,91 @@
+use blockchain_rpc_lib::context::TemporaryAccessRecord;
+use blockchain_rpc_lib::firewall::AccessSurface;
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use blockchain_rpc_lib::testing::test_firewall_with;
+use chrono::Utc;
+use opentelemetry::{KeyValue, global};
+use opentelemetry_sdk::metrics::data::Gauge;
+use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::runtime;
+use opentelemetry_sdk::testing::metrics::InMemoryMetricExporter;
+use serde_json::json;
+use sp_runtime::AccountId32;
+use std::sync::Arc;
+
+/// The last exported value of the gauge `name` with the given attributes.
+fn gauge(exporter: &InMemoryMetricExporter, name: &str, attributes: &[KeyValue]) -> u64 {
+    let exported = exporter.get_finished_metrics().unwrap();
+    let metric = exported
+        .iter()
+        .flat_map(|resource| &resource.scope_metrics)
+        .flat_map(|scope| &scope.metrics)
+        .filter(|metric| metric.name == name)
+        .next_back()
+        .unwrap_or_else(|| panic!("{name} not exported"));
+    let gauge = metric.data.as_any().downcast_ref::<Gauge<u64>>().unwrap();
+    gauge
+        .data_points
+        .iter()
+        .find(|point| point.attributes == attributes)
+        .unwrap_or_else(|| panic!("{name} has no point for {attributes:?}"))
+        .value
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn firewall_state_is_exported_as_gauges() {
+    let exporter = InMemoryMetricExporter::default();
+    let provider = SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(exporter.clone(), runtime::Tokio).build())
+        .build();
+    global::set_meter_provider(provider.clone());
+
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let firewall =
+        test_firewall_with(&store, json!({"decision_cache": {"ttl_ms": 60_000}})).unwrap();
+    let now = Utc::now();
+    firewall
+        .add_ip_rule("203.0.113.0/24".parse().unwrap())
+        .await
+        .unwrap();
+    firewall
+        .add_account_rule(AccountId32::new([1; 32]))
+        .await
+        .unwrap();
+    firewall
+        .ban_ip("198.51.100.7/32".parse().unwrap())
+        .await
+        .unwrap();
+    firewall
+        .grant_temporary_ip_access(
+            "192.0.2.0/24".parse().unwrap(),
+            TemporaryAccessRecord {
+                granted_at: now,
+                expires_at: now + chrono::Duration::hours(1),
+                plan: None,
+                extensions: 0,
+            },
+        )
+        .await
+        .unwrap();
+    provider.force_flush().unwrap();
+
+    let (ip, account) = (
+        [KeyValue::new("kind", "ip")],
+        [KeyValue::new("kind", "account")],
+    );
+    // Recorded by the rule changes themselves
+    assert_eq!(gauge(&exporter, "firewall.dynamic_rules", &ip), 1);
+    assert_eq!(gauge(&exporter, "firewall.dynamic_rules", &account), 1);
+    assert_eq!(gauge(&exporter, "firewall.temporary_grants", &ip), 1);
+    assert_eq!(gauge(&exporter, "firewall.temporary_grants", &account), 0);
+    assert_eq!(gauge(&exporter, "firewall.bans", &[]), 1);
+    assert_eq!(gauge(&exporter, "firewall.decision_cache.entries", &[]), 0);
+
+    // The cache fills without a rule change, so its size is picked up by the cleanup pass
+    firewall
+        .check_access(&"203.0.113.1".parse().unwrap(), None, AccessSurface::Http)
+        .await;
+    firewall.record_gauges();
+    provider.force_flush().unwrap();
+    assert_eq!(gauge(&exporter, "firewall.decision_cache.entries", &[]), 1);
+}
@@ -0,0 +1,749 @@
+use blockchain_rpc_lib::api_keys::hash_key;
+use blockchain_rpc_lib::config::{
+    ArchiveConfig, ArchiveHashRouting, CoalescingConfig, MirrorConfig, RateLimitConfig,
+    RequestValidationConfig, TrafficStatsConfig, WebSocketBridgeConfig, WebSocketKeepaliveConfig,
+};
+use blockchain_rpc_lib::payload_filters::{FilterContext, RequestFilter, ResponseFilter};
+use blockchain_rpc_lib::switchover::{SwitchUpstreamRequest, switch_upstream};
+use blockchain_rpc_lib::testing::{MockBackend, TestGateway, test_config};
+use blockchain_rpc_lib::traffic_stats::{GrafanaQuery, GrafanaRange, GrafanaTarget, TrafficBucket};
+use futures::{SinkExt, StreamExt};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use sp_runtime::AccountId32;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+async fn post(gateway: &TestGateway, body: Value) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(gateway.url())
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn proxies_allowed_requests() {
+    let backend = MockBackend::start().await.unwrap();
+    backend.respond("eth_blockNumber", json!("0x10"));
+    let gateway = TestGateway::start(test_config(&backend.url()))
+        .await
+        .unwrap();
+
+    let response = post(
+        &gateway,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber"}),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["result"], "0x10");
+    assert_eq!(backend.calls(), ["eth_blockNumber"]);
+
+    gateway.shutdown().await;
+}
+
+#[tokio::test]
+async fn firewall_rules_apply_end_to_end() {
+    let backend = MockBackend::start().await.unwrap();
+    backend.respond("eth_chainId", json!("0x1"));
+    let mut config = test_config(&backend.url());
+    config.firewall.allow_ips = HashSet::new();
+    let gateway = TestGateway::start(config).await.unwrap();
+    let call = json!({"jsonrpc": "2.0", "id": 1, "method": "eth_chainId"});
+
+    assert_eq!(
+        post(&gateway, call.clone()).await.status(),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(backend.http_requests(), 0);
+
+    // As the allow_access job would
+    gateway
+        .ctx
+        .firewall
+        .add_ip_rule("127.0.0.1/32".parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(post(&gateway, call.clone()).await.status(), StatusCode::OK);
+
+    gateway
+        .ctx
+        .firewall
+        .ban_ip("127.0.0.1/32".parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(post(&gateway, call).await.status(), StatusCode::FORBIDDEN);
+    assert_eq!(backend.http_requests(), 1);
+}
+
+#[tokio::test]
+async fn rate_limits_apply_end_to_end() {
+    let backend = MockBackend::start().await.unwrap();
+    backend.respond("eth_chainId", json!("0x1"));
+    let mut config = test_config(&backend.url());
+    config.firewall.rate_limit = Some(RateLimitConfig {
+        requests_per_sec: 0.01,
+        burst: 2,
+    });
+    let gateway = TestGateway::start(config).await.unwrap();
+    let call = json!({"jsonrpc": "2.0", "id": 1, "method": "eth_chainId"});
+
+    assert_eq!(post(&gateway, call.clone()).await.status(), StatusCode::OK);
+    assert_eq!(post(&gateway, call.clone()).await.status(), StatusCode::OK);
+    let limited = post(&gateway, call).await;
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(limited.headers().contains_key("retry-after"));
+    assert_eq!(backend.http_requests(), 2);
+}
+
+#[tokio::test]
+async fn responses_report_rate_limit_state_in_headers() {
+    let backend = MockBackend::start().await.unwrap();
+    backend.respond("eth_chainId", json!("0x1"));
+    let mut config = test_config(&backend.url());
+    config.firewall.rate_limit = Some(RateLimitConfig {
+        requests_per_sec: 0.01,
+        burst: 2,
+    });
+    let gateway = TestGateway::start(config).await.unwrap();
+    let call = json!({"jsonrpc": "2.0", "id": 1, "method": "eth_chainId"});
+    let header = |response: &reqwest::Response, name: &str| -> Option<u64> {
+        response.headers().get(name)?.to_str().ok()?.parse().ok()
+    };
+
+    let first = post(&gateway, call.clone()).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(header(&first, "x-ratelimit-limit"), Some(2));
+    assert_eq!(header(&first, "x-ratelimit-remaining"), Some(1));
+    assert_eq!(header(&first, "x-ratelimit-reset"), Some(100));
+    assert_eq!(header(&first, "x-quota-remaining"), None, "no plan quota");
+
+    let second = post(&gateway, call.clone()).await;
+    assert_eq!(header(&second, "x-ratelimit-remaining"), Some(0));
+
+    let limited = post(&gateway, call).await;
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&limited, "x-ratelimit-limit"), Some(2));
+    assert_eq!(header(&limited, "x-ratelimit-remaining"), Some(0));
+    assert_eq!(
+        header(&limited, "x-ratelimit-reset"),
+        header(&limited, "retry-after")
+    );
+}
+
+#[tokio::test]
+async fn forwards_websocket_frames_both_ways() {
+    let backend = MockBackend::start().await.unwrap();
+    backend.respond("eth_subscribe", json!("0xabc"));
+    let gateway = TestGateway::start(test_config(&backend.url()))
+        .await
+        .unwrap();
+
+    let (mut socket, _) = connect_async(gateway.ws_url().as_str()).await.unwrap();
+    let subscribe =
+        json!({"jsonrpc": "2.0", "id": 1, "method": "eth_subscribe", "params": ["newHeads"]});
+    socket
+        .send(Message::Text(subscribe.to_string().into()))
+        .await
+        .unwrap();
+    let reply: Value =
+        serde_json::from_str(socket.next().await.unwrap().unwrap().to_text().unwrap()).unwrap();
+    assert_eq!(reply["result"], "0xabc");
+
+    let notification = json!({
+        "jsonrpc": "2.0",
+        "method": "eth_subscription",
+        "params": {"subscription": "0xabc", "result": {"number": "0x11"}},
+    });
+    assert_eq!(backend.notify(notification.clone()), 1);
+    let pushed: Value =
+        serde_json::from_str(socket.next().await.unwrap().unwrap().to_text().unwrap()).unwrap();
+    assert_eq!(pushed, notification);
+}
+
+#[tokio::test]
+async fn binary_websocket_frames_are_checked_like_text_frames() {
+    let backend = MockBackend::start().await.unwrap();
+    backend.respond("eth_chainId", json!("0x1"));
+    let mut config = test_config(&backend.url());
+    config.methods.deny = vec!["admin_*".to_string()];
+    let gateway = TestGateway::start(config).await.unwrap();
+
+    let (mut socket, _) = connect_async(gateway.ws_url().as_str()).await.unwrap();
+    let blocked = json!({"jsonrpc": "2.0", "id": 1, "method": "admin_addPeer", "params": []});
+    socket
+        .send(Message::Binary(blocked.to_string().into_bytes().into()))
+        .await
+        .unwrap();
+    let answer: Value =
+        serde_json::from_str(socket.next().await.unwrap().unwrap().to_text().unwrap()).unwrap();
+    assert_eq!(answer["id"], 1);
+    assert!(answer["error"].is_object());
+
+    let allowed = json!({"jsonrpc": "2.0", "id": 2, "method": "eth_chainId"});
+    socket
+        .send(Message::Binary(allowed.to_string().into_bytes().into()))
+        .await
+        .unwrap();
+    let answer: Value =
+        serde_json::from_str(socket.next().await.unwrap().unwrap().to_text().unwrap()).unwrap();
+    assert_eq!(answer["result"], "0x1");
+    assert_eq!(backend.calls(), ["eth_chainId"]);
+
+    // Binary frames that can't be JSON-RPC can't be checked, so the session is closed
+    socket
+        .send(Message::Binary(vec![0xff, 0xfe].into()))
+        .await
+        .unwrap();
+    let close = loop {
+        match socket.next().await.unwrap().unwrap() {
+            Message::Close(close) => break close.unwrap(),
+            other => panic!("Unexpected frame {other:?}"),
+        }
+    };
+    assert_eq!(close.code, CloseCode::Unsupported);
+    assert_eq!(backend.calls(), ["eth_chainId"]);
+}
+
+#[tokio::test]
+async fn closes_websocket_sessions_whose_client_stops_responding() {
+    let backend = MockBackend::start().await.unwrap();
+    let mut config = test_config(&backend.url());
+    config.rpc.websocket_keepalive = Some(WebSocketKeepaliveConfig {
+        ping_interval_secs: 1,
+        idle_timeout_secs: 2,
+    });
+    let gateway = TestGateway::start(config).await.unwrap();
+
+    // Not reading from the socket keeps it from answering the gateway's pings
+    let (mut socket, _) = connect_async(gateway.ws_url().as_str()).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_secs(4)).await;
+
+    let mut pings = 0;
+    let close = loop {
+        match socket.next().await.unwrap().unwrap() {
+            Message::Ping(_) => pings += 1,
+            Message::Close(close) => break close.unwrap(),
+            other => panic!("Unexpected frame {other:?}"),
+        }
+    };
+    assert!(pings > 0);
+    assert_eq!(close.code, CloseCode::Away);
+}
+
+#[test]
+fn keepalive_idle_timeout_must_exceed_the_ping_interval() {
+    let mut config = test_config(&url::Url::parse("http://127.0.0.1:9944/").unwrap());
+    config.rpc.websocket_keepalive = Some(WebSocketKeepaliveConfig {
+        ping_interval_secs: 30,
+        idle_timeout_secs: 30,
+    });
+    assert!(config.validate().is_err());
+    config.rpc.websocket_keepalive = Some(WebSocketKeepaliveConfig {
+        ping_interval_secs: 30,
+        idle_timeout_secs: 90,
+    });
+    config.validate().unwrap();
+}
+
+#[tokio::test]
+async fn routes_historical_queries_to_the_archive_node() {
+    let fast = MockBackend::start().await.unwrap();
+    fast.respond("eth_blockNumber", json!("0x2710"));
+    fast.respond("eth_getBalance", json!("0x1"));
+    let archive = MockBackend::start().await.unwrap();
+    archive.respond("eth_getBalance", json!("0x2"));
+    let mut config = test_config(&fast.url());
+    config.rpc.archive = Some(ArchiveConfig {
+        url: archive.url(),
+        recent_blocks: 128,
+        head_poll_secs: 60,
+        block_hashes: ArchiveHashRouting::Archive,
+    });
+    let gateway = TestGateway::start(config).await.unwrap();
+    // The fast node's head is polled at startup
+    while !fast
+        .calls()
+        .iter()
+        .any(|method| method == "eth_blockNumber")
+    {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+    let balance = |block: &str| json!({"jsonrpc": "2.0", "id": 1, "method": "eth_getBalance", "params": ["0xabc", block]});
+    let recent: Value = post(&gateway, balance("latest"))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(recent["result"], "0x1");
+    let historical: Value = post(&gateway, balance("0x10")).await.json().await.unwrap();
+    assert_eq!(historical["result"], "0x2");
+    assert_eq!(archive.calls(), ["eth_getBalance"]);
+}
+
+#[tokio::test]
+async fn rejects_invalid_requests_at_the_edge() {
+    let backend = MockBackend::start().await.unwrap();
+    backend.respond("eth_chainId", json!("0x1"));
+    let mut config = test_config(&backend.url());
+    config.rpc.validation = Some(RequestValidationConfig {
+        max_method_length: 128,
+        methods: Default::default(),
+    });
+    let gateway = TestGateway::start(config).await.unwrap();
+
+    let response = post(
+        &gateway,
+        json!({"jsonrpc": "1.0", "id": 3, "method": "eth_chainId"}),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["id"], 3);
+    assert_eq!(body["error"]["code"], -32600);
+
+    let (mut socket, _) = connect_async(gateway.ws_url().as_str()).await.unwrap();
+    socket
+        .send(Message::Text("{\"jsonrpc\": ".into()))
+        .await
+        .unwrap();
+    let Message::Text(answer) = socket.next().await.unwrap().unwrap() else {
+        panic!("Expected a text frame");
+    };
+    let answer: Value = serde_json::from_str(&answer).unwrap();
+    assert_eq!(answer["error"]["code"], -32700);
+    assert!(backend.calls().is_empty());
+
+    let response = post(
+        &gateway,
+        json!({"jsonrpc": "2.0", "id": 4, "method": "eth_chainId"}),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(backend.calls(), ["eth_chainId"]);
+
+    gateway.shutdown().await;
+}
+
+/// Renames a deprecated method and refuses a disabled one.
+#[derive(Debug)]
+struct RenameMethods;
+
+impl RequestFilter for RenameMethods {
+    fn filter_request(&self, _: &FilterContext, request: &mut Value) -> Result<(), String> {
+        match request["method"].as_str() {
+            Some("eth_secret") => Err("eth_secret is disabled".to_string()),
+            Some("eth_legacyBlockNumber") => {
+                request["method"] = json!("eth_blockNumber");
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ScrubSecrets;
+
+impl ResponseFilter for ScrubSecrets {
+    fn filter_response(&self, _: &FilterContext, response: &mut Value) {
+        if let Some(result) = response.get_mut("result").and_then(Value::as_object_mut) {
+            result.remove("secret");
+        }
+    }
+}
+
+#[tokio::test]
+async fn payload_filters_apply_to_http_and_websocket() {
+    let backend = MockBackend::start().await.unwrap();
+    backend.respond(
+        "eth_blockNumber",
+        json!({"number": "0x10", "secret": "s3cr3t"}),
+    );
+    let gateway = TestGateway::start(test_config(&backend.url()))
+        .await
+        .unwrap();
+    gateway
+        .ctx
+        .payload_filters
+        .add_request_filter(Arc::new(RenameMethods));
+    gateway
+        .ctx
+        .payload_filters
+        .add_response_filter(Arc::new(ScrubSecrets));
+    let legacy = json!({"jsonrpc": "2.0", "id": 1, "method": "eth_legacyBlockNumber"});
+
+    let response = post(&gateway, legacy.clone()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["result"], json!({"number": "0x10"}));
+    assert_eq!(backend.calls(), ["eth_blockNumber"]);
+
+    let response = post(
+        &gateway,
+        json!({"jsonrpc": "2.0", "id": 2, "method": "eth_secret"}),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body["error"]["message"],
+        "Request rejected: eth_secret is disabled"
+    );
+    assert_eq!(backend.calls(), ["eth_blockNumber"]);
+
+    let (mut socket, _) = connect_async(gateway.ws_url().as_str()).await.unwrap();
+    socket
+        .send(Message::Text(legacy.to_string().into()))
+        .await
+        .unwrap();
+    let reply: Value =
+        serde_json::from_str(socket.next().await.unwrap().unwrap().to_text().unwrap()).unwrap();
+    assert_eq!(reply["result"], json!({"number": "0x10"}));
+
+    gateway.shutdown().await;
+}
+
+#[tokio::test]
+async fn requests_are_mirrored_to_the_shadow_backend() {
+    let backend = MockBackend::start().await.unwrap();
+    backend.respond("eth_blockNumber", json!("0x10"));
+    let shadow = MockBackend::start().await.unwrap();
+    shadow.respond("eth_blockNumber", json!("0x11"));
+    let mut config = test_config(&backend.url());
+    config.rpc.mirror = Some(MirrorConfig {
+        url: shadow.url(),
+        percent: 100,
+        log_differences: true,
+        max_in_flight: 8,
+        timeout_secs: 5,
+    });
+    let gateway = TestGateway::start(config).await.unwrap();
+
+    let response = post(
+        &gateway,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber"}),
+    )
+    .await;
+    // The client gets the primary backend's answer, however the shadow backend answers
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["result"], "0x10");
+    assert_eq!(backend.calls(), ["eth_blockNumber"]);
+
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    while shadow.calls().is_empty() && tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(shadow.calls(), ["eth_blockNumber"]);
+
+    gateway.shutdown().await;
+}
+
+#[tokio::test]
+async fn switching_backends_moves_traffic_and_drains_websocket_sessions() {
+    let blue = MockBackend::start().await.unwrap();
+    blue.respond("eth_blockNumber", json!("0x10"));
+    let green = MockBackend::start().await.unwrap();
+    green.respond("eth_blockNumber", json!("0x20"));
+    let gateway = TestGateway::start(test_config(&blue.url())).await.unwrap();
+    let call = json!({"jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber"});
+
+    let (mut socket, _) = connect_async(gateway.ws_url().as_str()).await.unwrap();
+    socket
+        .send(Message::Text(call.to_string().into()))
+        .await
+        .unwrap();
+    let reply: Value =
+        serde_json::from_str(socket.next().await.unwrap().unwrap().to_text().unwrap()).unwrap();
+    assert_eq!(reply["result"], "0x10");
+
+    // As the switch_upstream job would
+    let request = SwitchUpstreamRequest {
+        proxy_to_url: green.url(),
+        upstreams: Vec::new(),
+        drain_secs: 0,
+    };
+    let switch = switch_upstream(&gateway.ctx, request.clone(), "test")
+        .await
+        .unwrap();
+    assert_eq!(switch.old_targets, [blue.url()]);
+    assert_eq!(switch.new_targets, [green.url()]);
+
+    let body: Value = post(&gateway, call).await.json().await.unwrap();
+    assert_eq!(body["result"], "0x20");
+    assert_eq!(green.http_requests(), 1);
+
+    let close = loop {
+        match socket.next().await.unwrap().unwrap() {
+            Message::Close(close) => break close.unwrap(),
+            Message::Ping(_) | Message::Pong(_) => {}
+            other => panic!("Unexpected frame {other:?}"),
+        }
+    };
+    assert_eq!(close.code, CloseCode::Restart);
+
+    // The switch outlives reloads of config.toml
+    let mut reloaded = test_config(&blue.url());
+    gateway.ctx.runtime_config.apply(&mut reloaded);
+    assert_eq!(reloaded.rpc.proxy_to_url, green.url());
+
+    assert!(
+        switch_upstream(&gateway.ctx, request, "test")
+            .await
+            .is_err(),
+        "switching to the current backends changes nothing"
+    );
+
+    gateway.shutdown().await;
+}
+
+#[tokio::test]
+async fn identical_concurrent_calls_share_one_backend_call() {
+    let backend = MockBackend::start().await.unwrap();
+    backend.respond("eth_call", json!("0x2a"));
+    backend.delay_http(std::time::Duration::from_millis(300));
+    let mut config = test_config(&backend.url());
+    config.rpc.coalescing = Some(CoalescingConfig {
+        methods: vec!["eth_call".to_string()],
+    });
+    let gateway = TestGateway::start(config).await.unwrap();
+
+    let call = |id: u64| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "eth_call",
+            "params": [{"to": "0x0000000000000000000000000000000000000001"}, "latest"],
+        })
+    };
+    let responses = futures::future::join_all((1..=5).map(|id| post(&gateway, call(id)))).await;
+    for (id, response) in (1..=5).zip(responses) {
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = response.json().await.unwrap();
+        // Each client gets the shared answer under its own request id
+        assert_eq!(body["id"], id);
+        assert_eq!(body["result"], "0x2a");
+    }
+    assert_eq!(backend.http_requests(), 1);
+    let stats = gateway.ctx.coalescer.as_ref().unwrap().stats();
+    assert_eq!(stats.upstream_calls, 1);
+    assert_eq!(stats.coalesced, 4);
+
+    // Once answered, the call is made again rather than served from the earlier response
+    post(&gateway, call(6)).await;
+    assert_eq!(backend.http_requests(), 2);
+
+    gateway.shutdown().await;
+}
+
+/// The next frame on `socket`, parsed as JSON.
+async fn next_json<S>(socket: &mut S) -> Value
+where
+    S: futures::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    let frame = socket.next().await.unwrap().unwrap();
+    serde_json::from_str(frame.to_text().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn websocket_sessions_are_bridged_to_http_backends() {
+    let backend = MockBackend::start().await.unwrap();
+    backend.respond("eth_chainId", json!("0x1"));
+    backend.respond(
+        "eth_getBlockByNumber",
+        json!({"hash": "0xaa", "number": "0x10"}),
+    );
+    let mut config = test_config(&backend.url());
+    config.rpc.websocket_bridge = Some(WebSocketBridgeConfig {
+        poll_interval_ms: 50,
+    });
+    let gateway = TestGateway::start(config).await.unwrap();
+    let (mut socket, _) = connect_async(gateway.ws_url().as_str()).await.unwrap();
+    let calls = [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "eth_chainId"}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "eth_subscribe", "params": ["logs"]}),
+        json!({"jsonrpc": "2.0", "id": 3, "method": "eth_subscribe", "params": ["newHeads"]}),
+    ];
+    // Plain calls are POSTed to the backend, while subscriptions are answered by the gateway
+    let mut replies = Vec::new();
+    for call in &calls {
+        socket
+            .send(Message::Text(call.to_string().into()))
+            .await
+            .unwrap();
+        replies.push(next_json(&mut socket).await);
+    }
+    assert_eq!(replies[0]["result"], "0x1");
+    assert_eq!(replies[1]["error"]["code"], -32601);
+    let subscription = replies[2]["result"].as_str().unwrap().to_string();
+    assert_eq!(
+        backend.calls().first().map(String::as_str),
+        Some("eth_chainId")
+    );
+    assert!(
+        backend
+            .calls()
+            .iter()
+            .all(|method| method != "eth_subscribe")
+    );
+
+    // The subscription is sent the current head, then each new one, by polling
+    let notification = next_json(&mut socket).await;
+    assert_eq!(notification["method"], "eth_subscription");
+    assert_eq!(
+        notification["params"]["subscription"],
+        subscription.as_str()
+    );
+    assert_eq!(notification["params"]["result"]["hash"], "0xaa");
+    backend.respond(
+        "eth_getBlockByNumber",
+        json!({"hash": "0xbb", "number": "0x11"}),
+    );
+    let notification = next_json(&mut socket).await;
+    assert_eq!(notification["params"]["result"]["hash"], "0xbb");
+
+    let unsubscribe =
+        json!({"jsonrpc": "2.0", "id": 4, "method": "eth_unsubscribe", "params": [subscription]});
+    socket
+        .send(Message::Text(unsubscribe.to_string().into()))
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut socket).await["result"], true);
+    assert_eq!(backend.calls().len(), backend.http_requests());
+
+    gateway.shutdown().await;
+}
+
+#[tokio::test]
+async fn traffic_stats_break_down_requests_by_route_and_method() {
+    let backend = MockBackend::start().await.unwrap();
+    backend.respond("eth_blockNumber", json!("0x10"));
+    backend.respond("eth_chainId", json!("0x1"));
+    let mut config = test_config(&backend.url());
+    config.traffic_stats = Some(TrafficStatsConfig {
+        bucket_secs: 60,
+        buckets: 5,
+        max_keys: 100,
+    });
+    let gateway = TestGateway::start(config).await.unwrap();
+    let started = chrono::Utc::now();
+
+    for (id, method) in [
+        (1, "eth_blockNumber"),
+        (2, "eth_blockNumber"),
+        (3, "eth_chainId"),
+    ] {
+        let response = post(
+            &gateway,
+            json!({"jsonrpc": "2.0", "id": id, "method": method}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let (mut socket, _) = connect_async(gateway.ws_url().as_str()).await.unwrap();
+    let call = json!({"jsonrpc": "2.0", "id": 4, "method": "eth_chainId"});
+    socket
+        .send(Message::Text(call.to_string().into()))
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut socket).await["result"], "0x1");
+
+    let stats = gateway.ctx.traffic_stats.as_ref().unwrap();
+    // The calls may straddle a bucket boundary, so add up the buckets
+    let series = stats.series(Some(started), None);
+    let sum = |count: fn(&TrafficBucket) -> Option<u64>| -> u64 {
+        series.buckets.iter().filter_map(count).sum()
+    };
+    assert_eq!(sum(|bucket| Some(bucket.totals.requests)), 3);
+    assert_eq!(sum(|bucket| Some(bucket.totals.errors)), 0);
+    assert_eq!(
+        sum(|bucket| bucket.methods.get("eth_blockNumber").map(|p| p.requests)),
+        2
+    );
+    assert_eq!(sum(|bucket| bucket.routes.get("/").map(|p| p.requests)), 3);
+    assert!(
+        series
+            .buckets
+            .iter()
+            .all(|bucket| (bucket.totals.requests > 0) == bucket.totals.p50_ms.is_some())
+    );
+    // The open WebSocket session counts as a connection, not a request
+    assert_eq!(series.buckets.last().unwrap().totals.connections, 1);
+
+    let query = GrafanaQuery {
+        range: GrafanaRange {
+            from: started,
+            to: chrono::Utc::now(),
+        },
+        targets: vec![GrafanaTarget {
+            target: "requests:method:eth_chainId".to_string(),
+        }],
+    };
+    let answer = stats.grafana_query(&query).unwrap();
+    let total: f64 = answer[0]
+        .datapoints
+        .iter()
+        .filter_map(|(value, _)| *value)
+        .sum();
+    assert_eq!(total, 1.0);
+    assert!(
+        stats
+            .grafana_targets("p99_ms:")
+            .contains(&"p99_ms:route:/".to_string())
+    );
+    assert!(
+        stats
+            .grafana_query(&GrafanaQuery {
+                targets: vec![GrafanaTarget {
+                    target: "bogus".to_string(),
+                }],
+                ..query
+            })
+            .is_err()
+    );
+
+    gateway.shutdown().await;
+}
+
+#[tokio::test]
+async fn api_keys_of_admin_accounts_do_not_authorize_admin_requests() {
+    let backend = MockBackend::start().await.unwrap();
+    let alice = AccountId32::from_str("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY").unwrap();
+    let mut config = test_config(&backend.url());
+    config.admin.listen_addr = Some("127.0.0.1:0".parse().unwrap());
+    config.admin.token = Some("admin-token".to_string());
+    config.admin.accounts = HashSet::from([alice.clone()]);
+    let gateway = TestGateway::start(config).await.unwrap();
+    // Anyone can have a key issued for any account on-chain
+    gateway
+        .ctx
+        .api_keys
+        .insert(hash_key("alice-key"), alice, |_| Ok(()))
+        .unwrap();
+
+    let sessions = gateway.admin_url().unwrap().join("sessions").unwrap();
+    let status = |bearer: &'static str| {
+        let sessions = sessions.clone();
+        async move {
+            reqwest::Client::new()
+                .get(sessions)
+                .bearer_auth(bearer)
+                .send()
+                .await
+                .unwrap()
+                .status()
+        }
+    };
+    assert_eq!(status("alice-key").await, StatusCode::UNAUTHORIZED);
+    assert_eq!(status("admin-token").await, StatusCode::OK);
+
+    gateway.shutdown().await;
+}
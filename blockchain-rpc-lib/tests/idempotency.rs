@@ -0,0 +1,38 @@
+use blockchain_rpc_lib::idempotency::IdempotencyStore;
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use std::sync::Arc;
+
+const JOB_ID: u64 = 1;
+
+#[test]
+fn concurrent_duplicates_are_claimed_once() {
+    let keys = Arc::new(IdempotencyStore::load(Arc::new(MemoryStore::default())).unwrap());
+    let claim = keys.claim(JOB_ID, "order-42").unwrap();
+    assert!(keys.claim(JOB_ID, "order-42").is_none());
+    // Keys are scoped per job
+    assert!(keys.claim(JOB_ID + 1, "order-42").is_some());
+
+    claim.complete().unwrap();
+    assert!(keys.is_processed(JOB_ID, "order-42"));
+    assert!(keys.claim(JOB_ID, "order-42").is_none());
+}
+
+#[test]
+fn failed_calls_release_their_key() {
+    let keys = Arc::new(IdempotencyStore::load(Arc::new(MemoryStore::default())).unwrap());
+    drop(keys.claim(JOB_ID, "order-42").unwrap());
+    assert!(!keys.is_processed(JOB_ID, "order-42"));
+    assert!(keys.claim(JOB_ID, "order-42").is_some());
+}
+
+#[test]
+fn processed_keys_survive_a_restart() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let keys = Arc::new(IdempotencyStore::load(store.clone()).unwrap());
+    keys.claim(JOB_ID, "order-42").unwrap().complete().unwrap();
+
+    let reloaded = Arc::new(IdempotencyStore::load(store).unwrap());
+    assert!(reloaded.is_processed(JOB_ID, "order-42"));
+    assert!(reloaded.claim(JOB_ID, "order-42").is_none());
+    assert!(!reloaded.is_processed(JOB_ID, "order-43"));
+}
@@ -0,0 +1,44 @@
+use blockchain_rpc_lib::Error;
+use blockchain_rpc_lib::jobs::{ensure_admin, ensure_admin_or_owner};
+use blockchain_rpc_lib::testing::{MockBackend, test_config, test_context};
+use sp_runtime::AccountId32;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+const ALICE: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+const BOB: &str = "5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty";
+
+fn account(ss58: &str) -> AccountId32 {
+    AccountId32::from_str(ss58).unwrap()
+}
+
+#[tokio::test]
+async fn admin_jobs_are_rejected_while_no_admins_are_configured() {
+    let backend = MockBackend::start().await.unwrap();
+    let config = test_config(&backend.url());
+    assert!(config.jobs.admin_accounts.is_empty());
+    let (ctx, _data_dir) = test_context(config).await.unwrap();
+
+    let alice = account(ALICE);
+    assert!(matches!(
+        ensure_admin(&ctx, "allow_access", &alice),
+        Err(Error::AccessDeniedAdmin(_))
+    ));
+    // Owners may still act on their own account
+    assert!(ensure_admin_or_owner(&ctx, "issue_api_key", &alice, Some(&alice)).is_ok());
+    assert!(ensure_admin_or_owner(&ctx, "issue_api_key", &alice, Some(&account(BOB))).is_err());
+}
+
+#[tokio::test]
+async fn only_configured_admins_may_call_admin_jobs() {
+    let backend = MockBackend::start().await.unwrap();
+    let mut config = test_config(&backend.url());
+    config.jobs.admin_accounts = HashSet::from([account(ALICE)]);
+    let (ctx, _data_dir) = test_context(config).await.unwrap();
+
+    assert!(ensure_admin(&ctx, "ban_target", &account(ALICE)).is_ok());
+    assert!(matches!(
+        ensure_admin(&ctx, "ban_target", &account(BOB)),
+        Err(Error::AccessDeniedAdmin(_))
+    ));
+}
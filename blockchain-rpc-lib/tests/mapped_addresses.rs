@@ -0,0 +1,87 @@
+use axum::http::HeaderMap;
+use blockchain_rpc_lib::firewall::{canonical_ip, canonical_network, network_contains};
+use blockchain_rpc_lib::forwarded::client_addr;
+use ipnetwork::IpNetwork;
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+fn net(s: &str) -> IpNetwork {
+    s.parse().unwrap()
+}
+
+#[test]
+fn mapped_addresses_are_canonicalized() {
+    assert_eq!(canonical_ip(ip("::ffff:1.2.3.4")), ip("1.2.3.4"));
+    assert_eq!(canonical_ip(ip("1.2.3.4")), ip("1.2.3.4"));
+    assert_eq!(canonical_ip(ip("2001:db8::1")), ip("2001:db8::1"));
+    // IPv4-compatible addresses are deprecated and not IPv4 clients
+    assert_eq!(canonical_ip(ip("::1")), ip("::1"));
+}
+
+#[test]
+fn mapped_networks_are_canonicalized() {
+    assert_eq!(
+        canonical_network(net("::ffff:10.0.0.0/104")),
+        net("10.0.0.0/8")
+    );
+    assert_eq!(
+        canonical_network(net("::ffff:1.2.3.4/128")),
+        net("1.2.3.4/32")
+    );
+    assert_eq!(
+        canonical_network(net("::ffff:0.0.0.0/96")),
+        net("0.0.0.0/0")
+    );
+    assert_eq!(canonical_network(net("10.0.0.0/8")), net("10.0.0.0/8"));
+    assert_eq!(
+        canonical_network(net("2001:db8::/32")),
+        net("2001:db8::/32")
+    );
+}
+
+#[test]
+fn ipv4_rules_match_mapped_clients() {
+    let rule = net("10.0.0.0/8");
+    assert!(network_contains(&rule, ip("10.1.2.3")));
+    assert!(network_contains(&rule, ip("::ffff:10.1.2.3")));
+    assert!(!network_contains(&rule, ip("::ffff:11.1.2.3")));
+    assert!(!network_contains(&rule, ip("2001:db8::1")));
+}
+
+#[test]
+fn mapped_rules_match_ipv4_clients() {
+    let rule = net("::ffff:192.0.2.0/120");
+    assert!(network_contains(&rule, ip("192.0.2.7")));
+    assert!(network_contains(&rule, ip("::ffff:192.0.2.7")));
+    assert!(!network_contains(&rule, ip("192.0.3.7")));
+}
+
+#[test]
+fn mixed_rule_sets_match_both_families() {
+    let rules = [net("203.0.113.0/24"), net("2001:db8::/32")];
+    let allowed = |client: &str| rules.iter().any(|rule| network_contains(rule, ip(client)));
+    assert!(allowed("203.0.113.9"));
+    assert!(allowed("::ffff:203.0.113.9"));
+    assert!(allowed("2001:db8::9"));
+    assert!(!allowed("198.51.100.9"));
+    assert!(!allowed("2001:db9::9"));
+}
+
+#[test]
+fn client_addr_resolves_mapped_peers_and_hops() {
+    let peer: SocketAddr = "[::ffff:1.2.3.4]:4000".parse().unwrap();
+    let addr = client_addr(peer, &HeaderMap::new(), &HashSet::new());
+    assert_eq!(addr, "1.2.3.4:4000".parse().unwrap());
+
+    // A trusted IPv4 proxy reached over a dual-stack listener still counts as trusted
+    let proxy: SocketAddr = "[::ffff:10.0.0.1]:4000".parse().unwrap();
+    let trusted = HashSet::from([net("10.0.0.0/8")]);
+    let mut headers = HeaderMap::new();
+    headers.insert("x-forwarded-for", "::ffff:198.51.100.7".parse().unwrap());
+    let addr = client_addr(proxy, &headers, &trusted);
+    assert_eq!(addr.ip(), ip("198.51.100.7"));
+}
@@ -0,0 +1,61 @@
+use blockchain_rpc_lib::config::MethodFilterConfig;
+use blockchain_rpc_lib::method_filter::MethodFilter;
+
+fn filter(allow: &[&str], deny: &[&str]) -> MethodFilter {
+    MethodFilter::new(&MethodFilterConfig {
+        allow: allow.iter().map(|pattern| pattern.to_string()).collect(),
+        deny: deny.iter().map(|pattern| pattern.to_string()).collect(),
+        rewrites: Vec::new(),
+    })
+}
+
+#[test]
+fn the_denylist_takes_precedence_over_the_allowlist() {
+    let filter = filter(&["eth_*", "admin_nodeInfo"], &["eth_sign", "admin_*"]);
+    assert!(filter.is_method_allowed("eth_call"));
+    assert!(!filter.is_method_allowed("eth_sign"));
+    assert!(!filter.is_method_allowed("admin_nodeInfo"));
+    assert!(!filter.is_method_allowed("net_version"));
+    assert!(MethodFilter::default().is_method_allowed("admin_addPeer"));
+}
+
+#[test]
+fn the_first_blocked_call_of_a_batch_is_reported() {
+    let filter = filter(&[], &["admin_*"]);
+    let batch = br#"[
+        {"jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber"},
+        {"jsonrpc": "2.0", "id": 2, "method": "admin_addPeer", "params": []}
+    ]"#;
+    assert_eq!(
+        filter.find_blocked_method(batch),
+        Some("admin_addPeer".to_string())
+    );
+    assert_eq!(
+        filter.find_blocked_method(br#"{"id": 1, "method": "eth_call"}"#),
+        None
+    );
+}
+
+#[test]
+fn payloads_that_cannot_be_read_are_blocked_while_rules_are_set() {
+    let filter = filter(&["eth_*"], &[]);
+    for payload in [
+        &b"not json"[..],
+        br#"{"id": 1}"#,
+        br#"{"id": 1, "method": 7}"#,
+        br#"[{"id": 1, "method": "eth_call"}, "eth_call"]"#,
+        // Go backends would run admin_addPeer for either of these
+        br#"{"id": 1, "METHOD": "admin_addPeer"}"#,
+        br#"{"id": 1, "method": "eth_call", "Method": "admin_addPeer"}"#,
+    ] {
+        assert!(
+            filter.find_blocked_method(payload).is_some(),
+            "{}",
+            String::from_utf8_lossy(payload)
+        );
+    }
+    assert_eq!(
+        MethodFilter::default().find_blocked_method(b"not json"),
+        None
+    );
+}
@@ -0,0 +1,68 @@
+use blockchain_rpc_lib::config::OriginHeatmapConfig;
+use blockchain_rpc_lib::origin_heatmap::{HeatmapOrder, OriginHeatmap, origin_prefix};
+use std::net::IpAddr;
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn clients_are_grouped_by_prefix() {
+    assert_eq!(
+        origin_prefix(ip("203.0.113.77")).to_string(),
+        "203.0.113.0/24"
+    );
+    assert_eq!(
+        origin_prefix(ip("::ffff:203.0.113.77")).to_string(),
+        "203.0.113.0/24"
+    );
+    assert_eq!(
+        origin_prefix(ip("2001:db8:abcd:12::1")).to_string(),
+        "2001:db8:abcd::/48"
+    );
+}
+
+#[test]
+fn report_ranks_prefixes() {
+    let heatmap = OriginHeatmap::new(&OriginHeatmapConfig {
+        enabled: true,
+        ..OriginHeatmapConfig::default()
+    });
+    for host in 1..=3 {
+        heatmap.record(ip(&format!("198.51.100.{host}")));
+    }
+    for _ in 0..2 {
+        heatmap.record(ip("203.0.113.9"));
+        heatmap.record_denial(ip("203.0.113.9"));
+    }
+
+    let by_denials = heatmap.report(HeatmapOrder::Denied, 10);
+    assert_eq!(by_denials.tracked_prefixes, 2);
+    assert_eq!(by_denials.prefixes[0].prefix.to_string(), "203.0.113.0/24");
+    assert_eq!(by_denials.prefixes[0].requests, 2);
+    assert_eq!(by_denials.prefixes[0].denied, 2);
+
+    let by_requests = heatmap.report(HeatmapOrder::Requests, 1);
+    assert_eq!(by_requests.tracked_prefixes, 2);
+    assert_eq!(by_requests.prefixes.len(), 1);
+    assert_eq!(
+        by_requests.prefixes[0].prefix.to_string(),
+        "198.51.100.0/24"
+    );
+    assert_eq!(by_requests.prefixes[0].requests, 3);
+}
+
+#[test]
+fn full_heatmap_ignores_new_prefixes() {
+    let heatmap = OriginHeatmap::new(&OriginHeatmapConfig {
+        enabled: true,
+        window_secs: 3600,
+        max_prefixes: 1,
+    });
+    heatmap.record(ip("198.51.100.1"));
+    heatmap.record(ip("203.0.113.1"));
+    heatmap.record(ip("198.51.100.2"));
+    let report = heatmap.report(HeatmapOrder::Requests, 10);
+    assert_eq!(report.tracked_prefixes, 1);
+    assert_eq!(report.prefixes[0].requests, 2);
+}
@@ -0,0 +1,91 @@
+use blockchain_rpc_lib::config::PaymentVerificationConfig;
+use blockchain_rpc_lib::payments::{PaymentReference, PaymentVerifier};
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use blockchain_rpc_lib::testing::{redeem_payment, release_payment};
+use serde_json::json;
+use sp_runtime::AccountId32;
+use std::sync::Arc;
+
+const OPERATOR: [u8; 32] = [7; 32];
+const PAYER: [u8; 32] = [9; 32];
+
+fn load_verifier(store: &Arc<dyn StateStore>) -> PaymentVerifier {
+    let config: PaymentVerificationConfig =
+        serde_json::from_value(json!({"min_amount": 100, "amount_per_second": 2})).unwrap();
+    PaymentVerifier::load(store.clone(), &config, AccountId32::new(OPERATOR)).unwrap()
+}
+
+fn payment(block_hash: &str) -> PaymentReference {
+    PaymentReference {
+        block_hash: block_hash.to_string(),
+        event_index: 3,
+    }
+}
+
+/// Redeems `payment` as a transfer of `amount` from `PAYER` to the operator.
+fn redeem(verifier: &PaymentVerifier, payment: &PaymentReference, amount: u128) -> bool {
+    let payers = [AccountId32::new(PAYER)];
+    redeem_payment(verifier, payment, PAYER, OPERATOR, amount, &payers, 100).is_ok()
+}
+
+#[test]
+fn required_amount_is_the_duration_price_or_the_minimum() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let verifier = load_verifier(&store);
+    assert_eq!(verifier.required_amount(10), 100);
+    assert_eq!(verifier.required_amount(60), 120);
+    assert_eq!(verifier.required_amount(u64::MAX), 2 * u64::MAX as u128);
+}
+
+#[test]
+fn only_sufficient_transfers_to_the_operator_are_redeemed() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let verifier = load_verifier(&store);
+    let payment = payment("0xab");
+    let payers = [AccountId32::new(PAYER)];
+
+    assert!(redeem_payment(&verifier, &payment, PAYER, [8; 32], 500, &payers, 100).is_err());
+    assert!(!redeem(&verifier, &payment, 99));
+    // Rejected transfers aren't used up
+    assert_eq!(
+        redeem_payment(&verifier, &payment, PAYER, OPERATOR, 150, &payers, 100).unwrap(),
+        150
+    );
+}
+
+#[test]
+fn transfers_from_other_accounts_are_rejected() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let verifier = load_verifier(&store);
+    let payment = payment("0xab");
+    let payers = [AccountId32::new(PAYER), AccountId32::new([10; 32])];
+
+    assert!(redeem_payment(&verifier, &payment, [11; 32], OPERATOR, 150, &payers, 100).is_err());
+    assert!(redeem_payment(&verifier, &payment, [10; 32], OPERATOR, 150, &payers, 100).is_ok());
+}
+
+#[test]
+fn each_transfer_is_redeemed_once_across_restarts() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let verifier = load_verifier(&store);
+    assert!(redeem(&verifier, &payment("0xAB"), 150));
+    assert!(
+        !redeem(&verifier, &payment("ab"), 150),
+        "the same block hash, spelled differently"
+    );
+
+    let restarted = load_verifier(&store);
+    assert!(!redeem(&restarted, &payment("0xab"), 150));
+    assert!(redeem(&restarted, &payment("0xcd"), 150));
+}
+
+#[test]
+fn released_payments_can_be_redeemed_again() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let verifier = load_verifier(&store);
+    assert!(redeem(&verifier, &payment("0xab"), 150));
+    release_payment(&verifier, &payment("0xab")).unwrap();
+
+    let restarted = load_verifier(&store);
+    assert!(redeem(&restarted, &payment("0xab"), 150));
+}
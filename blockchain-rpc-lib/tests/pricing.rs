@@ -0,0 +1,105 @@
+use blockchain_rpc_lib::config::{JobsConfig, PaymentVerificationConfig};
+use blockchain_rpc_lib::pricing::PriceSchedule;
+use chrono::{Duration, Utc};
+use serde_json::json;
+
+const HOUR: u64 = 3600;
+
+fn payments(pricing: serde_json::Value) -> PaymentVerificationConfig {
+    serde_json::from_value(json!({"min_amount": 100, "pricing": pricing})).unwrap()
+}
+
+fn schedule() -> PriceSchedule {
+    let config = payments(json!({
+        "amount_per_hour": 1000,
+        "volume_discounts": [
+            {"min_hours": 720, "percent": 25},
+            {"min_hours": 24, "percent": 10},
+        ],
+        "surge": [
+            {"load_percent": 90, "multiplier_percent": 200},
+            {"load_percent": 75, "multiplier_percent": 150},
+        ],
+    }));
+    config.validate().unwrap();
+    PriceSchedule::new(config.pricing.as_ref().unwrap())
+}
+
+#[test]
+fn surge_follows_the_highest_load_reached() {
+    let schedule = schedule();
+    assert_eq!(schedule.surge_percent(0), 100);
+    assert_eq!(schedule.surge_percent(74), 100);
+    assert_eq!(schedule.surge_percent(75), 150);
+    assert_eq!(schedule.surge_percent(100), 200);
+}
+
+#[test]
+fn longer_grants_get_volume_discounts() {
+    let schedule = schedule();
+    assert_eq!(schedule.price(HOUR, 100), 1000);
+    assert_eq!(schedule.price(23 * HOUR, 100), 23_000);
+    assert_eq!(schedule.price(24 * HOUR, 100), 21_600);
+    assert_eq!(schedule.price(720 * HOUR, 100), 540_000);
+    assert_eq!(schedule.price(HOUR, 150), 1500);
+}
+
+#[test]
+fn duration_follows_from_the_amount_paid() {
+    let schedule = schedule();
+    assert_eq!(schedule.duration_secs(1000, 100), HOUR);
+    assert_eq!(schedule.duration_secs(500, 100), HOUR / 2);
+    // Below a day at the full price, but a day at the discounted one
+    assert_eq!(schedule.duration_secs(21_600, 100), 24 * HOUR);
+    assert_eq!(schedule.duration_secs(540_000, 100), 720 * HOUR);
+    // Half as long while the surge doubles prices
+    assert_eq!(schedule.duration_secs(1000, 200), HOUR / 2);
+    assert_eq!(schedule.duration_secs(0, 100), 0);
+}
+
+#[test]
+fn price_and_duration_agree() {
+    let schedule = schedule();
+    for secs in [60, HOUR, 30 * HOUR, 1000 * HOUR] {
+        for surge in [100, 150, 200] {
+            let price = schedule.price(secs, surge);
+            assert!(schedule.duration_secs(price, surge) >= secs);
+        }
+    }
+}
+
+#[test]
+fn schedules_are_validated() {
+    for pricing in [
+        json!({"amount_per_hour": 0}),
+        json!({"amount_per_hour": 1, "volume_discounts": [{"min_hours": 1, "percent": 100}]}),
+        json!({"amount_per_hour": 1, "surge": [{"load_percent": 0, "multiplier_percent": 150}]}),
+        json!({"amount_per_hour": 1, "surge": [{"load_percent": 80, "multiplier_percent": 50}]}),
+    ] {
+        assert!(
+            payments(pricing.clone()).validate().is_err(),
+            "{pricing} was accepted"
+        );
+    }
+
+    let mut config = payments(json!({"amount_per_hour": 1}));
+    config.validate().unwrap();
+    config.amount_per_second = 1;
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn longest_grant_respects_duration_and_horizon() {
+    let now = Utc::now();
+    let jobs: JobsConfig = serde_json::from_value(json!({
+        "max_access_duration_secs": 10 * HOUR,
+        "max_access_horizon_secs": 24 * HOUR,
+    }))
+    .unwrap();
+    assert_eq!(jobs.max_access_secs(now, now), Some(10 * HOUR));
+    // Extending a grant expiring in 20 hours leaves 4 within the horizon
+    let expiry = now + Duration::hours(20);
+    assert_eq!(jobs.max_access_secs(now, expiry), Some(4 * HOUR));
+
+    assert_eq!(JobsConfig::default().max_access_secs(now, expiry), None);
+}
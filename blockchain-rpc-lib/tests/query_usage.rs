@@ -0,0 +1,69 @@
+use blockchain_rpc_lib::config::{MeteringConfig, PlanConfig};
+use blockchain_rpc_lib::metering::{UsageMeter, UsageSubject};
+use blockchain_rpc_lib::plans::{ActivePlan, Plans};
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use sp_runtime::AccountId32;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+use std::sync::Arc;
+
+fn metering_config() -> MeteringConfig {
+    serde_json::from_value(json!({})).unwrap()
+}
+
+#[test]
+fn usage_totals_survive_a_restart() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let alice = AccountId32::new([1; 32]);
+    let subject = UsageSubject::new(IpAddr::V4(Ipv4Addr::LOCALHOST), Some(&alice));
+
+    let meter =
+        UsageMeter::load(store.clone(), Path::new("/nonexistent"), &metering_config()).unwrap();
+    meter.record_request(&subject);
+    meter.record_request_bytes(&subject, 100);
+    meter.record_calls(
+        &subject,
+        &["eth_call".to_string(), "eth_chainId".to_string()],
+    );
+    meter.persist().unwrap();
+
+    let restarted = UsageMeter::load(store, Path::new("/nonexistent"), &metering_config()).unwrap();
+    let counters = restarted.account_counters(&alice.to_string());
+    assert_eq!(counters.total.requests, 1);
+    assert_eq!(counters.total.request_bytes, 100);
+    assert_eq!(counters.total.calls, 2);
+    assert_eq!(counters.month.calls, 2);
+
+    let bob = AccountId32::new([2; 32]);
+    assert_eq!(
+        restarted.account_counters(&bob.to_string()).total.requests,
+        0
+    );
+}
+
+#[test]
+fn quota_usage_survives_a_restart() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let pro: PlanConfig = serde_json::from_value(json!({"request_quota": 10})).unwrap();
+    let config = HashMap::from([("pro".to_string(), pro)]);
+    let granted_at = Utc::now();
+    let active = |plans: &Plans| ActivePlan {
+        holder: AccountId32::new([1; 32]).to_string(),
+        plan: plans.get("pro").unwrap(),
+        granted_at,
+        expires_at: granted_at + Duration::hours(1),
+        extensions: 0,
+    };
+
+    let plans = Plans::load(store.clone(), &config).unwrap();
+    let grant = active(&plans);
+    grant.plan.charge_quota(&grant, 4).unwrap();
+    plans.persist().unwrap();
+
+    let restarted = Plans::load(store, &config).unwrap();
+    let grant = active(&restarted);
+    assert_eq!(grant.plan.quota_remaining(&grant), Some(6));
+}
@@ -0,0 +1,53 @@
+use blockchain_rpc_lib::read_only::ReadOnlyMode;
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use std::sync::Arc;
+
+fn write_methods() -> Vec<String> {
+    vec![
+        "eth_sendRawTransaction".to_string(),
+        "author_submit*".to_string(),
+    ]
+}
+
+#[test]
+fn read_only_mode_rejects_only_writes() {
+    let mode = ReadOnlyMode::load(Arc::new(MemoryStore::default()), &write_methods()).unwrap();
+    let submit =
+        br#"{"jsonrpc": "2.0", "id": 1, "method": "author_submitExtrinsic", "params": ["0x00"]}"#;
+    let batch = br#"[
+        {"jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber"},
+        {"jsonrpc": "2.0", "id": 2, "method": "eth_sendRawTransaction", "params": ["0x00"]}
+    ]"#;
+    let query = br#"{"jsonrpc": "2.0", "id": 1, "method": "eth_call", "params": []}"#;
+
+    assert!(mode.find_rejected_method(submit).is_none());
+
+    mode.enable(Some("Backend upgrade".to_string()), "token")
+        .unwrap();
+    let (method, status) = mode.find_rejected_method(submit).unwrap();
+    assert_eq!(method, "author_submitExtrinsic");
+    assert_eq!(status.reason.as_deref(), Some("Backend upgrade"));
+    assert_eq!(
+        mode.find_rejected_method(batch).map(|(method, _)| method),
+        Some("eth_sendRawTransaction".to_string())
+    );
+    assert!(mode.find_rejected_method(query).is_none());
+
+    assert!(mode.disable().unwrap());
+    assert!(!mode.disable().unwrap());
+    assert!(mode.find_rejected_method(submit).is_none());
+}
+
+#[test]
+fn read_only_mode_survives_restarts() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let mode = ReadOnlyMode::load(store.clone(), &write_methods()).unwrap();
+    let status = mode.enable(None, "token").unwrap();
+
+    let reloaded = ReadOnlyMode::load(store.clone(), &write_methods()).unwrap();
+    assert_eq!(reloaded.status(), Some(status));
+
+    reloaded.disable().unwrap();
+    let reloaded = ReadOnlyMode::load(store, &write_methods()).unwrap();
+    assert!(!reloaded.is_enabled());
+}
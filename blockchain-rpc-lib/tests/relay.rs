@@ -0,0 +1,113 @@
+use blockchain_rpc_lib::config::RelayConfig;
+use blockchain_rpc_lib::relay::{RelayPolicies, RelayRejection};
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use serde_json::{Value, json};
+use sp_runtime::AccountId32;
+use std::str::FromStr;
+use std::sync::Arc;
+
+const ALICE: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+const BOB: &str = "5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty";
+const CHARLIE: &str = "5FLSigC9HGRKVhB9FiEo4Y3koPsNmBmLJbpXg2mp1hXcS59Y";
+
+fn account(ss58: &str) -> AccountId32 {
+    AccountId32::from_str(ss58).unwrap()
+}
+
+fn relay_config(policies: Value) -> RelayConfig {
+    serde_json::from_value(json!({"signer": BOB, "policies": policies})).unwrap()
+}
+
+fn config() -> RelayConfig {
+    relay_config(json!([{
+        "accounts": [ALICE],
+        "calls": ["Balances.transfer_keep_alive", "System.remark*"],
+        "max_fee": 100,
+        "daily_fee_budget": 250,
+    }]))
+}
+
+#[test]
+fn calls_and_fees_are_checked_against_the_accounts_policy() {
+    let policies = RelayPolicies::new(&config(), Arc::new(MemoryStore::default()));
+    let alice = account(ALICE);
+
+    assert!(
+        policies
+            .check_call(&alice, "Balances.transfer_keep_alive")
+            .is_ok()
+    );
+    assert!(
+        policies
+            .check_call(&alice, "System.remark_with_event")
+            .is_ok()
+    );
+    assert_eq!(
+        policies.check_call(&alice, "Balances.force_transfer"),
+        Err(RelayRejection::CallNotAllowed(
+            "Balances.force_transfer".to_string()
+        ))
+    );
+    // Accounts without a policy can't relay anything
+    assert_eq!(
+        policies.check_call(&account(CHARLIE), "System.remark"),
+        Err(RelayRejection::NoPolicy(account(CHARLIE)))
+    );
+
+    assert!(policies.check_fee(&alice, 100).is_ok());
+    assert_eq!(
+        policies.check_fee(&alice, 101),
+        Err(RelayRejection::FeeTooHigh { fee: 101, max: 100 })
+    );
+}
+
+#[test]
+fn daily_fee_budget_is_kept_across_restarts() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let policies = RelayPolicies::new(&config(), store.clone());
+    let alice = account(ALICE);
+    assert_eq!(policies.record_spend(&alice, 100).unwrap(), 100);
+    assert_eq!(policies.record_spend(&alice, 100).unwrap(), 200);
+
+    let reloaded = RelayPolicies::new(&config(), store);
+    assert_eq!(reloaded.spent_today(&alice), 200);
+    assert!(reloaded.check_fee(&alice, 50).is_ok());
+    let rejection = reloaded.check_fee(&alice, 51).unwrap_err();
+    assert_eq!(
+        rejection,
+        RelayRejection::BudgetExhausted {
+            fee: 51,
+            remaining: 50
+        }
+    );
+    assert_eq!(rejection.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[test]
+fn invalid_relay_configs_are_rejected() {
+    assert!(config().validate().is_ok());
+    assert_eq!(config().path, "/relay");
+
+    let mut root = config();
+    root.path = "/".to_string();
+    assert!(root.validate().is_err());
+
+    let mut http = config();
+    http.submit_url = Some("http://127.0.0.1:9933".parse().unwrap());
+    assert!(http.validate().is_err());
+
+    let no_calls = relay_config(json!([{
+        "accounts": [ALICE],
+        "calls": [],
+        "max_fee": 100,
+        "daily_fee_budget": 250,
+    }]));
+    assert!(no_calls.validate().is_err());
+
+    // An account's limits must be unambiguous
+    let overlapping = relay_config(json!([
+        {"accounts": [ALICE], "calls": ["System.*"], "max_fee": 1, "daily_fee_budget": 1},
+        {"accounts": [ALICE, CHARLIE], "calls": ["Balances.*"], "max_fee": 1, "daily_fee_budget": 1},
+    ]));
+    assert!(overlapping.validate().is_err());
+}
@@ -0,0 +1,71 @@
+use blockchain_rpc_lib::replay::{MAX_CALLS_ABOVE_WATERMARK, ProcessedCalls};
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use std::sync::Arc;
+
+const JOB_ID: u64 = 2;
+
+#[test]
+fn calls_that_failed_stay_eligible_after_later_ones_succeed() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let calls = ProcessedCalls::load(store.clone()).unwrap();
+    // Call 7 failed, say on a transient payment check, and call 8 succeeded
+    calls.mark_processed(JOB_ID, 8).unwrap();
+    assert!(calls.is_processed(JOB_ID, 8));
+    assert!(!calls.is_processed(JOB_ID, 7));
+    assert!(!calls.is_processed(JOB_ID + 1, 8));
+
+    let reloaded = ProcessedCalls::load(store).unwrap();
+    assert!(reloaded.is_processed(JOB_ID, 8));
+    assert!(!reloaded.is_processed(JOB_ID, 7));
+}
+
+#[test]
+fn contiguous_calls_are_compacted_into_the_watermark() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let calls = ProcessedCalls::load(store.clone()).unwrap();
+    for call_id in (0..10_000).filter(|call_id| *call_id != 9_000) {
+        calls.mark_processed(JOB_ID, call_id).unwrap();
+    }
+    let record = store
+        .get("processed_calls", &JOB_ID.to_be_bytes())
+        .unwrap()
+        .unwrap();
+    assert!(record.len() < 16 * 1024, "{} bytes", record.len());
+
+    let reloaded = ProcessedCalls::load(store).unwrap();
+    assert!(reloaded.is_processed(JOB_ID, 0));
+    assert!(reloaded.is_processed(JOB_ID, 8_999));
+    assert!(!reloaded.is_processed(JOB_ID, 9_000));
+    assert!(reloaded.is_processed(JOB_ID, 9_999));
+    assert!(!reloaded.is_processed(JOB_ID, 10_000));
+}
+
+#[test]
+fn the_watermark_passes_calls_that_failed_long_ago() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let calls = ProcessedCalls::load(store).unwrap();
+    // Other jobs' calls leave gaps between this job's, and call 1 failed
+    let later = (1..=MAX_CALLS_ABOVE_WATERMARK as u64 + 1).map(|n| 100 + 2 * n);
+    calls.mark_processed(JOB_ID, 0).unwrap();
+    for call_id in later.clone().take(MAX_CALLS_ABOVE_WATERMARK - 1) {
+        calls.mark_processed(JOB_ID, call_id).unwrap();
+    }
+    assert!(!calls.is_processed(JOB_ID, 1));
+    assert!(!calls.is_processed(JOB_ID, 103));
+
+    for call_id in later.skip(MAX_CALLS_ABOVE_WATERMARK - 1) {
+        calls.mark_processed(JOB_ID, call_id).unwrap();
+    }
+    assert!(calls.is_processed(JOB_ID, 1));
+    assert!(calls.is_processed(JOB_ID, 102));
+    assert!(!calls.is_processed(JOB_ID, 103));
+}
+
+#[test]
+fn corrupt_records_fail_to_load() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    store
+        .insert("processed_calls", &JOB_ID.to_be_bytes(), b"{not json")
+        .unwrap();
+    assert!(ProcessedCalls::load(store).is_err());
+}
@@ -0,0 +1,48 @@
+use axum::http::{HeaderMap, HeaderValue};
+use blockchain_rpc_lib::request_id::{REQUEST_ID_HEADER, RequestId};
+
+fn headers(request_id: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        REQUEST_ID_HEADER,
+        HeaderValue::from_str(request_id).unwrap(),
+    );
+    headers
+}
+
+#[test]
+fn well_formed_client_ids_are_honored() {
+    for id in [
+        "abc-123",
+        "0f8e2c4a-9b1d-4e6f-8a2b-3c4d5e6f7a8b",
+        "req:42/retry=1",
+    ] {
+        let request_id = RequestId::from_headers(&headers(id)).unwrap();
+        assert_eq!(request_id.as_str(), id);
+    }
+}
+
+#[test]
+fn malformed_client_ids_are_replaced() {
+    assert!(RequestId::from_headers(&HeaderMap::new()).is_none());
+    assert!(RequestId::from_headers(&headers("")).is_none());
+    assert!(RequestId::from_headers(&headers("has space")).is_none());
+    assert!(RequestId::from_headers(&headers("quote\"d")).is_none());
+    assert!(RequestId::from_headers(&headers(&"a".repeat(129))).is_none());
+}
+
+#[test]
+fn generated_ids_are_unique_hex() {
+    let first = RequestId::generate();
+    let second = RequestId::generate();
+    assert_eq!(first.as_str().len(), 32);
+    assert!(first.as_str().bytes().all(|b| b.is_ascii_hexdigit()));
+    assert_ne!(first.as_str(), second.as_str());
+
+    let mut forwarded = HeaderMap::new();
+    first.inject(&mut forwarded);
+    assert_eq!(
+        RequestId::from_headers(&forwarded).unwrap().as_str(),
+        first.as_str()
+    );
+}
@@ -0,0 +1,159 @@
+use blockchain_rpc_lib::config::{
+    MethodSchemaConfig, ParamType, ParamsStructure, RequestValidationConfig,
+};
+use blockchain_rpc_lib::request_validation::RequestValidator;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+fn validator(methods: HashMap<String, MethodSchemaConfig>) -> RequestValidator {
+    RequestValidator::new(&RequestValidationConfig {
+        max_method_length: 32,
+        methods,
+    })
+}
+
+fn check(validator: &RequestValidator, payload: Value) -> Result<(), (i64, Value)> {
+    validator
+        .validate(payload.to_string().as_bytes())
+        .map_err(|invalid| (invalid.code, invalid.id))
+}
+
+#[test]
+fn accepts_well_formed_calls_and_notifications() {
+    let validator = validator(HashMap::new());
+    for payload in [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "eth_chainId"}),
+        json!({"jsonrpc": "2.0", "id": "a", "method": "chain_getHeader", "params": []}),
+        json!({"jsonrpc": "2.0", "id": null, "method": "rpc.discover", "params": {}}),
+        json!({"jsonrpc": "2.0", "method": "eth_subscribe", "params": ["newHeads"]}),
+        json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "eth_chainId"},
+            {"jsonrpc": "2.0", "id": 2, "method": "eth_blockNumber"},
+        ]),
+    ] {
+        assert_eq!(check(&validator, payload.clone()), Ok(()), "{payload}");
+    }
+}
+
+#[test]
+fn rejects_malformed_requests() {
+    let validator = validator(HashMap::new());
+    assert_eq!(
+        validator
+            .validate(b"{\"jsonrpc\": \"2.0\",")
+            .unwrap_err()
+            .code,
+        -32700
+    );
+    for payload in [
+        json!([]),
+        json!("eth_chainId"),
+        json!({"id": 1, "method": "eth_chainId"}),
+        json!({"jsonrpc": "1.0", "id": 1, "method": "eth_chainId"}),
+        json!({"jsonrpc": "2.0", "id": 1, "method": 7}),
+        json!({"jsonrpc": "2.0", "id": 1, "method": ""}),
+        json!({"jsonrpc": "2.0", "id": 1, "method": "eth_getBalance; DROP"}),
+        json!({"jsonrpc": "2.0", "id": 1, "method": "a".repeat(33)}),
+        json!({"jsonrpc": "2.0", "id": 1, "method": "eth_call", "params": "0x1"}),
+        json!({"jsonrpc": "2.0", "id": {"nested": 1}, "method": "eth_chainId"}),
+        json!({"jsonrpc": "2.0", "id": 1.5, "method": "eth_chainId"}),
+    ] {
+        assert_eq!(
+            check(&validator, payload.clone()).map_err(|(code, _)| code),
+            Err(-32600),
+            "{payload}"
+        );
+    }
+}
+
+#[test]
+fn errors_carry_the_call_id_outside_batches() {
+    let validator = validator(HashMap::new());
+    let single = json!({"jsonrpc": "1.0", "id": 7, "method": "eth_chainId"});
+    assert_eq!(check(&validator, single.clone()), Err((-32600, json!(7))));
+
+    let batch = json!([{"jsonrpc": "2.0", "id": 1, "method": "eth_chainId"}, single]);
+    let invalid = validator
+        .validate(batch.to_string().as_bytes())
+        .unwrap_err();
+    assert_eq!(invalid.id, Value::Null);
+    assert!(invalid.message.starts_with("Batch call 1: "));
+    let payload: Value = serde_json::from_str(&invalid.payload()).unwrap();
+    assert_eq!(payload["error"]["code"], -32600);
+}
+
+#[test]
+fn checks_params_against_method_schemas() {
+    let validator = validator(HashMap::from([
+        (
+            "eth_getBalance".to_string(),
+            MethodSchemaConfig {
+                params: ParamsStructure::Positional,
+                min_params: 1,
+                max_params: Some(2),
+                param_types: vec![ParamType::Hex, ParamType::String],
+                ..Default::default()
+            },
+        ),
+        (
+            "eth_blockNumber".to_string(),
+            MethodSchemaConfig {
+                params: ParamsStructure::None,
+                ..Default::default()
+            },
+        ),
+        (
+            "custom_*".to_string(),
+            MethodSchemaConfig {
+                params: ParamsStructure::Named,
+                required_keys: vec!["account".to_string()],
+                ..Default::default()
+            },
+        ),
+    ]));
+    let call = |method: &str, params: Value| json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+
+    for valid in [
+        call("eth_getBalance", json!(["0xabc"])),
+        call("eth_getBalance", json!(["0xabc", "latest"])),
+        // Optional params may be null
+        call("eth_getBalance", json!(["0xabc", null])),
+        call("eth_blockNumber", json!([])),
+        call("custom_lookup", json!({"account": "alice", "extra": true})),
+    ] {
+        assert_eq!(check(&validator, valid.clone()), Ok(()), "{valid}");
+    }
+    for invalid in [
+        call("eth_getBalance", json!([])),
+        call("eth_getBalance", json!(["0xabc", "latest", 1])),
+        call("eth_getBalance", json!(["abc"])),
+        call("eth_getBalance", json!([null])),
+        call("eth_getBalance", json!({"address": "0xabc"})),
+        call("eth_blockNumber", json!(["latest"])),
+        call("custom_lookup", json!({"extra": true})),
+        call("custom_lookup", json!(["alice"])),
+        call("custom_lookup", json!([])),
+    ] {
+        assert_eq!(
+            check(&validator, invalid.clone()),
+            Err((-32602, json!(1))),
+            "{invalid}"
+        );
+    }
+}
+
+#[test]
+fn schemas_must_be_satisfiable() {
+    let config = RequestValidationConfig {
+        max_method_length: 128,
+        methods: HashMap::from([(
+            "eth_call".to_string(),
+            MethodSchemaConfig {
+                min_params: 2,
+                max_params: Some(1),
+                ..Default::default()
+            },
+        )]),
+    };
+    assert!(config.validate().is_err());
+}
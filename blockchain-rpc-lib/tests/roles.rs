@@ -0,0 +1,105 @@
+use blockchain_rpc_lib::config::RoleConfig;
+use blockchain_rpc_lib::roles::{AccountRoles, MethodRoles, Roles};
+use blockchain_rpc_lib::store::MemoryStore;
+use sp_runtime::AccountId32;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+fn role_config() -> HashMap<String, RoleConfig> {
+    HashMap::from([
+        (
+            "submitter".to_string(),
+            RoleConfig {
+                methods: vec![
+                    "author_submitExtrinsic".to_string(),
+                    "eth_sendRawTransaction".to_string(),
+                ],
+            },
+        ),
+        (
+            "unsafe".to_string(),
+            RoleConfig {
+                methods: vec!["debug_*".to_string(), "eth_sendRawTransaction".to_string()],
+            },
+        ),
+    ])
+}
+
+fn roles(names: &[&str]) -> Roles {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn privileged_methods_need_a_listing_role() {
+    let methods = MethodRoles::new(&role_config());
+    let submitter = roles(&["submitter"]);
+    let unsafe_role = roles(&["unsafe"]);
+
+    assert!(methods.is_method_allowed("eth_call", None));
+    assert!(!methods.is_method_allowed("author_submitExtrinsic", None));
+    assert!(methods.is_method_allowed("author_submitExtrinsic", Some(&submitter)));
+    assert!(!methods.is_method_allowed("debug_traceTransaction", Some(&submitter)));
+    assert!(methods.is_method_allowed("debug_traceTransaction", Some(&unsafe_role)));
+    // Either role listing a method unlocks it
+    assert!(methods.is_method_allowed("eth_sendRawTransaction", Some(&submitter)));
+    assert!(methods.is_method_allowed("eth_sendRawTransaction", Some(&unsafe_role)));
+}
+
+#[test]
+fn batches_are_checked_call_by_call() {
+    let methods = MethodRoles::new(&role_config());
+    let batch = br#"[
+        {"jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber"},
+        {"jsonrpc": "2.0", "id": 2, "method": "debug_traceCall", "params": []}
+    ]"#;
+    assert_eq!(
+        methods.find_unauthorized_method(batch, Some(&roles(&["submitter"]))),
+        Some("debug_traceCall".to_string())
+    );
+    assert_eq!(
+        methods.find_unauthorized_method(batch, Some(&roles(&["unsafe"]))),
+        None
+    );
+    // Calls that can't be read could hide a restricted method
+    assert!(
+        methods
+            .find_unauthorized_method(b"not json", None)
+            .is_some()
+    );
+    assert!(
+        methods
+            .find_unauthorized_method(br#"{"METHOD": "debug_traceCall"}"#, None)
+            .is_some()
+    );
+}
+
+#[test]
+fn assigned_roles_unlock_methods() {
+    let config = role_config();
+    let account_roles = AccountRoles::load(Arc::new(MemoryStore::default()), &config).unwrap();
+    let account = AccountId32::new([7u8; 32]);
+    let call =
+        br#"{"jsonrpc": "2.0", "id": 1, "method": "author_submitExtrinsic", "params": ["0x00"]}"#;
+
+    assert!(
+        account_roles
+            .find_unauthorized_method(call, Some(&account))
+            .is_some()
+    );
+    account_roles
+        .set(&account, roles(&["submitter"]), &config)
+        .unwrap();
+    assert_eq!(
+        account_roles.find_unauthorized_method(call, Some(&account)),
+        None
+    );
+    assert!(account_roles.find_unauthorized_method(call, None).is_some());
+
+    assert!(
+        account_roles
+            .set(&account, roles(&["operator"]), &config)
+            .is_err()
+    );
+    account_roles.set(&account, Roles::new(), &config).unwrap();
+    assert!(account_roles.all().is_empty());
+}
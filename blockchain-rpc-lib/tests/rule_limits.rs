@@ -0,0 +1,15 @@
+use blockchain_rpc_lib::config::RuleLimit;
+
+#[test]
+fn warnings_fire_once_the_count_passes_the_threshold() {
+    let limit = RuleLimit {
+        warn_at: Some(10),
+        max: None,
+    };
+    assert!(!limit.crossed_warning(9, 10));
+    assert!(limit.crossed_warning(10, 11));
+    // Replacing an entry above the threshold doesn't warn again
+    assert!(!limit.crossed_warning(11, 11));
+    assert!(!limit.crossed_warning(11, 12));
+    assert!(!RuleLimit::default().crossed_warning(10, 11));
+}
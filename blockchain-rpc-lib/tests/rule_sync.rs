@@ -0,0 +1,83 @@
+use blockchain_rpc_lib::config::RuleSyncConfig;
+use blockchain_rpc_lib::jobs::allow_access::AccessTarget;
+use blockchain_rpc_lib::rule_sync::{RuleMutation, RuleSync, SyncRulesInput};
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+fn config(max_batch: usize) -> RuleSyncConfig {
+    RuleSyncConfig {
+        operators: HashSet::new(),
+        submit_interval_secs: 10,
+        max_batch,
+    }
+}
+
+fn ban(ip: &str) -> RuleMutation {
+    RuleMutation::Ban {
+        target: AccessTarget::Ip(ip.to_string()),
+    }
+}
+
+#[test]
+fn batches_changes_in_order() {
+    let sync = RuleSync::load(Arc::new(MemoryStore::default()), &config(2)).unwrap();
+    assert!(sync.next_batch().is_none());
+    for ip in ["203.0.113.1", "203.0.113.2", "203.0.113.3"] {
+        sync.queue(ban(ip)).unwrap();
+    }
+
+    let (last, batch) = sync.next_batch().unwrap();
+    assert_eq!(batch, [ban("203.0.113.1"), ban("203.0.113.2")]);
+    // Until acknowledged, the same changes are offered again
+    assert_eq!(sync.next_batch().unwrap().1, batch);
+
+    sync.acknowledge(last).unwrap();
+    assert_eq!(sync.pending(), 1);
+    let (last, batch) = sync.next_batch().unwrap();
+    assert_eq!(batch, [ban("203.0.113.3")]);
+    sync.acknowledge(last).unwrap();
+    assert!(sync.next_batch().is_none());
+}
+
+#[test]
+fn queued_changes_survive_a_restart() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let sync = RuleSync::load(store.clone(), &config(10)).unwrap();
+    sync.queue(ban("203.0.113.1")).unwrap();
+    sync.queue(RuleMutation::Allow {
+        target: AccessTarget::Account(
+            "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY".to_string(),
+        ),
+    })
+    .unwrap();
+    let (last, _) = sync.next_batch().unwrap();
+    sync.queue(ban("203.0.113.2")).unwrap();
+    sync.acknowledge(last).unwrap();
+
+    // Only the change queued after the submitted batch is left
+    let reloaded = RuleSync::load(store, &config(10)).unwrap();
+    assert_eq!(reloaded.next_batch().unwrap().1, [ban("203.0.113.2")]);
+}
+
+#[test]
+fn sync_input_round_trips() {
+    let input = SyncRulesInput {
+        mutations: vec![
+            ban("203.0.113.0/24"),
+            RuleMutation::GrantTemporary {
+                target: AccessTarget::Ip("198.51.100.7".to_string()),
+                granted_at: 1_700_000_000,
+                expires_at: 1_700_003_600,
+                plan: Some("basic".to_string()),
+            },
+        ],
+    };
+    let json = serde_json::to_value(&input).unwrap();
+    assert_eq!(json["mutations"][0]["action"], "ban");
+    assert_eq!(json["mutations"][1]["action"], "grant_temporary");
+
+    let decoded: SyncRulesInput = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded.mutations, input.mutations);
+    assert_eq!(decoded.mutations[1].action(), "grant_temporary");
+}
@@ -0,0 +1,21 @@
+use blockchain_rpc_lib::jobs::vectors::{JobVector, job_vectors, verify};
+
+const FIXTURE: &str = include_str!("../fixtures/scale_vectors.json");
+
+#[test]
+fn job_vectors_match_fixture() -> color_eyre::Result<()> {
+    let fixture: Vec<JobVector> = serde_json::from_str(FIXTURE)?;
+    assert_eq!(job_vectors(), fixture);
+    for vector in &fixture {
+        verify(&vector.type_name, &vector.hex)
+            .map_err(|e| color_eyre::eyre::eyre!("{} {}: {e}", vector.type_name, vector.name))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn verify_rejects_trailing_bytes() {
+    let fixture: Vec<JobVector> = serde_json::from_str(FIXTURE).unwrap();
+    let padded = format!("{}00", fixture[0].hex);
+    assert!(verify(&fixture[0].type_name, &padded).is_err());
+}
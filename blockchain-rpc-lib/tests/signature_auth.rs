@@ -0,0 +1,163 @@
+use axum::http::{HeaderMap, Method};
+use blockchain_rpc_lib::config::AuthConfig;
+use blockchain_rpc_lib::signature_auth::{
+    ACCOUNT_HEADER, NONCE_HEADER, SIGNATURE_HEADER, SignatureVerifier, TIMESTAMP_HEADER,
+    signing_payload,
+};
+use chrono::Utc;
+use sp_core::Pair;
+use sp_core::sr25519::Pair as Sr25519Pair;
+use sp_runtime::AccountId32;
+
+const BODY: &[u8] = br#"{"jsonrpc":"2.0","id":1,"method":"eth_blockNumber"}"#;
+
+/// Headers of a `POST /` with `body`, signed by `pair` at `timestamp`.
+fn signed(pair: &Sr25519Pair, timestamp: i64, nonce: &str, body: &[u8]) -> HeaderMap {
+    signed_target(pair, timestamp, nonce, "/", body)
+}
+
+/// Headers of a `POST` to `target` with `body`, signed by `pair` at `timestamp`.
+fn signed_target(
+    pair: &Sr25519Pair,
+    timestamp: i64,
+    nonce: &str,
+    target: &str,
+    body: &[u8],
+) -> HeaderMap {
+    let payload = signing_payload(timestamp, nonce, &Method::POST, target, body);
+    let mut headers = HeaderMap::new();
+    let account = AccountId32::from(pair.public().0).to_string();
+    headers.insert(ACCOUNT_HEADER, account.parse().unwrap());
+    headers.insert(TIMESTAMP_HEADER, timestamp.to_string().parse().unwrap());
+    headers.insert(NONCE_HEADER, nonce.parse().unwrap());
+    let signature = hex::encode(pair.sign(payload.as_bytes()).0);
+    headers.insert(SIGNATURE_HEADER, signature.parse().unwrap());
+    headers
+}
+
+#[test]
+fn fresh_signatures_authenticate_their_account() {
+    let verifier = SignatureVerifier::new(&AuthConfig::default());
+    let (pair, _) = Sr25519Pair::generate();
+    let headers = signed(&pair, Utc::now().timestamp(), "1", BODY);
+    let account = verifier.verify(&headers, &Method::POST, "/", BODY);
+    assert_eq!(
+        account.unwrap().unwrap(),
+        AccountId32::from(pair.public().0)
+    );
+    // Unsigned requests are left to other credentials
+    assert!(
+        verifier
+            .verify(&HeaderMap::new(), &Method::POST, "/", BODY)
+            .is_none()
+    );
+}
+
+#[test]
+fn expired_and_extreme_timestamps_are_rejected() {
+    let verifier = SignatureVerifier::new(&AuthConfig::default());
+    let (pair, _) = Sr25519Pair::generate();
+    let now = Utc::now().timestamp();
+    for timestamp in [now - 3600, now + 3600, i64::MIN, i64::MAX] {
+        let headers = signed(&pair, timestamp, "1", BODY);
+        let result = verifier.verify(&headers, &Method::POST, "/", BODY).unwrap();
+        assert!(result.is_err(), "timestamp {timestamp} was accepted");
+    }
+}
+
+#[test]
+fn replayed_nonces_are_rejected() {
+    let verifier = SignatureVerifier::new(&AuthConfig::default());
+    let (pair, _) = Sr25519Pair::generate();
+    let headers = signed(&pair, Utc::now().timestamp(), "1", BODY);
+    assert!(
+        verifier
+            .verify(&headers, &Method::POST, "/", BODY)
+            .unwrap()
+            .is_ok()
+    );
+    assert!(
+        verifier
+            .verify(&headers, &Method::POST, "/", BODY)
+            .unwrap()
+            .is_err()
+    );
+}
+
+#[test]
+fn signatures_do_not_cover_other_bodies_or_paths() {
+    let verifier = SignatureVerifier::new(&AuthConfig::default());
+    let (pair, _) = Sr25519Pair::generate();
+    let headers = signed(&pair, Utc::now().timestamp(), "1", BODY);
+    let tampered = br#"{"jsonrpc":"2.0","id":1,"method":"eth_sendRawTransaction"}"#;
+    assert!(
+        verifier
+            .verify(&headers, &Method::POST, "/", tampered)
+            .unwrap()
+            .is_err()
+    );
+    assert!(
+        verifier
+            .verify(&headers, &Method::POST, "/other", BODY)
+            .unwrap()
+            .is_err()
+    );
+    // Failed attempts don't burn the nonce
+    assert!(
+        verifier
+            .verify(&headers, &Method::POST, "/", BODY)
+            .unwrap()
+            .is_ok()
+    );
+}
+
+#[test]
+fn signatures_cover_the_query_string() {
+    let verifier = SignatureVerifier::new(&AuthConfig::default());
+    let (pair, _) = Sr25519Pair::generate();
+    let headers = signed_target(&pair, Utc::now().timestamp(), "1", "/rpc?chain=1", BODY);
+    for target in ["/rpc", "/rpc?chain=2"] {
+        assert!(
+            verifier
+                .verify(&headers, &Method::POST, target, BODY)
+                .unwrap()
+                .is_err(),
+            "signature accepted for {target}"
+        );
+    }
+    assert!(
+        verifier
+            .verify(&headers, &Method::POST, "/rpc?chain=1", BODY)
+            .unwrap()
+            .is_ok()
+    );
+}
+
+#[test]
+fn remembered_nonces_are_capped() {
+    let config = AuthConfig {
+        max_tracked_nonces: 2,
+        ..AuthConfig::default()
+    };
+    let verifier = SignatureVerifier::new(&config);
+    let now = Utc::now().timestamp();
+    // Any key can sign, so fresh accounts mustn't grow the replay cache without bound
+    for nonce in ["1", "2"] {
+        let (pair, _) = Sr25519Pair::generate();
+        let headers = signed(&pair, now, nonce, BODY);
+        assert!(
+            verifier
+                .verify(&headers, &Method::POST, "/", BODY)
+                .unwrap()
+                .is_ok()
+        );
+    }
+    let (pair, _) = Sr25519Pair::generate();
+    let headers = signed(&pair, now, "3", BODY);
+    assert!(
+        verifier
+            .verify(&headers, &Method::POST, "/", BODY)
+            .unwrap()
+            .is_err()
+    );
+}
@@ -0,0 +1,40 @@
+use blockchain_rpc_lib::context::TemporaryAccessRecord;
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use blockchain_rpc_lib::testing::test_firewall;
+use chrono::{Duration, Utc};
+use sp_runtime::AccountId32;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn instances_sharing_a_store_pick_up_each_others_changes() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let first = test_firewall(&store).unwrap();
+    let second = test_firewall(&store).unwrap();
+    let alice = AccountId32::new([1; 32]);
+    let bob = AccountId32::new([2; 32]);
+
+    let now = Utc::now();
+    first
+        .grant_temporary_access(
+            alice.clone(),
+            TemporaryAccessRecord {
+                granted_at: now,
+                expires_at: now + Duration::hours(1),
+                plan: None,
+                extensions: 0,
+            },
+        )
+        .await
+        .unwrap();
+    first.ban_account(bob.clone()).await.unwrap();
+    assert!(!second.account_status(&alice).allowed);
+    assert!(!second.account_status(&bob).banned);
+
+    assert!(second.sync_dynamic_state().unwrap());
+    assert!(second.account_status(&alice).allowed);
+    assert!(second.account_status(&bob).banned);
+    assert!(
+        !second.sync_dynamic_state().unwrap(),
+        "nothing changed since"
+    );
+}
@@ -0,0 +1,54 @@
+use blockchain_rpc_lib::store::{MemoryStore, StateStore};
+use blockchain_rpc_lib::testing::test_firewall;
+use blockchain_rpc_lib::webhooks::WebhookEventType;
+use sp_runtime::AccountId32;
+use std::collections::HashSet;
+use std::sync::Arc;
+use url::Url;
+
+#[tokio::test]
+async fn registering_a_url_again_updates_it_instead_of_duplicating() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let firewall = test_firewall(&store).unwrap();
+    let url = Url::parse("https://hooks.example.com/gateway").unwrap();
+    let alice = AccountId32::new([1; 32]);
+
+    firewall
+        .add_webhook(url.clone(), None, HashSet::new(), Some(alice.clone()))
+        .await
+        .unwrap();
+    let events = HashSet::from([WebhookEventType::AccessGranted]);
+    firewall
+        .add_webhook(url.clone(), None, events.clone(), Some(alice.clone()))
+        .await
+        .unwrap();
+    let webhooks = firewall.webhooks();
+    assert_eq!(webhooks.len(), 1);
+    assert_eq!(webhooks[0].events, events);
+
+    let other_account = firewall
+        .add_webhook(url.clone(), None, HashSet::new(), None)
+        .await;
+    assert!(other_account.is_err(), "scoped to Alice, not to everyone");
+    assert_eq!(firewall.webhooks()[0].account, Some(alice));
+}
+
+#[tokio::test]
+async fn removed_webhooks_are_unregistered() {
+    let store: Arc<dyn StateStore> = Arc::new(MemoryStore::default());
+    let firewall = test_firewall(&store).unwrap();
+    let dead = Url::parse("https://dead.example.com/hook").unwrap();
+    let live = Url::parse("https://live.example.com/hook").unwrap();
+    for url in [&dead, &live] {
+        firewall
+            .add_webhook(url.clone(), None, HashSet::new(), None)
+            .await
+            .unwrap();
+    }
+
+    let removed = firewall.remove_webhook(&dead).await.unwrap();
+    assert_eq!(removed.url, dead);
+    let urls: Vec<Url> = firewall.webhooks().into_iter().map(|w| w.url).collect();
+    assert_eq!(urls, vec![live]);
+    assert!(firewall.remove_webhook(&dead).await.is_none());
+}
@@ -19,6 +19,25 @@ contract SecureRpcBlueprint is BlueprintServiceManagerBase {
     uint8 constant ALLOW_ACCESS_JOB_ID = 0;
     uint8 constant PAY_FOR_ACCESS_JOB_ID = 1;
     uint8 constant REGISTER_WEBHOOK_JOB_ID = 2;
+    uint8 constant ISSUE_API_KEY_JOB_ID = 3;
+    uint8 constant ACCESS_HISTORY_JOB_ID = 4;
+    uint8 constant REVOKE_ACCESS_JOB_ID = 5;
+    uint8 constant BAN_TARGET_JOB_ID = 6;
+    uint8 constant REPORT_USAGE_JOB_ID = 7;
+    uint8 constant USAGE_SUMMARY_JOB_ID = 8;
+    uint8 constant QUERY_RULES_JOB_ID = 9;
+    uint8 constant UPDATE_CONFIG_JOB_ID = 10;
+    uint8 constant SET_ACCOUNT_LABELS_JOB_ID = 11;
+    uint8 constant PATCH_CONFIG_JOB_ID = 12;
+    uint8 constant SET_ACCOUNT_ROLES_JOB_ID = 13;
+    uint8 constant SET_READ_ONLY_JOB_ID = 14;
+    uint8 constant EXTEND_ACCESS_JOB_ID = 15;
+    uint8 constant SYNC_RULES_JOB_ID = 16;
+    uint8 constant QUERY_USAGE_JOB_ID = 17;
+    uint8 constant PRICE_SCHEDULE_JOB_ID = 18;
+    uint8 constant SWITCH_UPSTREAM_JOB_ID = 19;
+    uint8 constant REMOVE_WEBHOOK_JOB_ID = 20;
+    uint8 constant LIST_WEBHOOKS_JOB_ID = 21;
     
 
     // --- State Variables --- 
@@ -47,7 +66,7 @@ contract SecureRpcBlueprint is BlueprintServiceManagerBase {
         uint64 indexed serviceId,
         uint64 indexed jobCallId,
         address indexed operatorAddress,
-        bytes inputs, // SCALE-encoded PayForAccessInput { beneficiary: AccountId32, duration_secs: u64 }
+        bytes inputs, // SCALE-encoded PayForAccessInput { beneficiary: AccessTarget, duration_secs: u64, idempotency_key: Option<String>, payment: Option<PaymentReference>, plan: Option<String> }
         bytes outputs // Should be empty on success
     );
 
@@ -60,6 +79,177 @@ contract SecureRpcBlueprint is BlueprintServiceManagerBase {
         bytes outputs // Should be empty on success
     );
 
+    /// @dev Emitted when a result for the ISSUE_API_KEY_JOB_ID is processed.
+    event JobIssueApiKeyResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // IssueApiKeyInput { account: String, key_hash: String }
+        bytes outputs // Should be empty on success
+    );
+
+    /// @dev Emitted when a result for the ACCESS_HISTORY_JOB_ID is processed.
+    event JobAccessHistoryResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // AccessHistoryInput { account: String }
+        bytes outputs // The account's access timeline
+    );
+
+    /// @dev Emitted when a result for the REVOKE_ACCESS_JOB_ID is processed.
+    event JobRevokeAccessResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // RevokeAccessInput { target: AccessTarget }
+        bytes outputs // Should be empty on success
+    );
+
+    /// @dev Emitted when a result for the BAN_TARGET_JOB_ID is processed.
+    event JobBanTargetResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // BanTargetInput { target: AccessTarget }
+        bytes outputs // Should be empty on success
+    );
+
+    /// @dev Emitted when a result for the REPORT_USAGE_JOB_ID is processed.
+    event JobReportUsageResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // No inputs
+        bytes outputs // SCALE-encoded Vec<UsageReport>: the usage periods closed since the last call
+    );
+
+    /// @dev Emitted when a result for the USAGE_SUMMARY_JOB_ID is processed.
+    event JobUsageSummaryResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // No inputs
+        bytes outputs // SCALE-encoded Vec<MonthlySummary>: the monthly summaries closed since the last call
+    );
+
+    /// @dev Emitted when a result for the QUERY_RULES_JOB_ID is processed.
+    event JobQueryRulesResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // No inputs
+        bytes outputs // SCALE-encoded FirewallState: rules, temporary grants and webhooks
+    );
+
+    /// @dev Emitted when a result for the UPDATE_CONFIG_JOB_ID is processed.
+    event JobUpdateConfigResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // UpdateConfigInput { proxy_to_url: Option<String>, request_timeout_secs: Option<u64>, max_body_size_bytes: Option<u64>, rate_limit: Option<RateLimitConfig> }
+        bytes outputs // Should be empty on success
+    );
+
+    /// @dev Emitted when a result for the SET_ACCOUNT_LABELS_JOB_ID is processed.
+    event JobSetAccountLabelsResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // SetAccountLabelsInput { account: String, labels: Vec<AccountLabel>, idempotency_key: Option<String> }
+        bytes outputs // Should be empty on success
+    );
+
+    /// @dev Emitted when a result for the PATCH_CONFIG_JOB_ID is processed.
+    event JobPatchConfigResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // PatchConfigInput { patch: String }
+        bytes outputs // SCALE-encoded Vec<ConfigChange>: the settings changed
+    );
+
+    /// @dev Emitted when a result for the SET_ACCOUNT_ROLES_JOB_ID is processed.
+    event JobSetAccountRolesResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // SetAccountRolesInput { account: String, roles: Vec<String>, idempotency_key: Option<String> }
+        bytes outputs // Should be empty on success
+    );
+
+    /// @dev Emitted when a result for the SET_READ_ONLY_JOB_ID is processed.
+    event JobSetReadOnlyResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // SetReadOnlyInput { enabled: bool, reason: Option<String>, idempotency_key: Option<String> }
+        bytes outputs // Should be empty on success
+    );
+
+    /// @dev Emitted when a result for the EXTEND_ACCESS_JOB_ID is processed.
+    event JobExtendAccessResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // PayForAccessInput, as for PAY_FOR_ACCESS_JOB_ID
+        bytes outputs // i64: when the extended grant expires, as a Unix timestamp
+    );
+
+    /// @dev Emitted when a result for the SYNC_RULES_JOB_ID is processed.
+    event JobSyncRulesResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // SyncRulesInput: rule changes made through another operator's admin API
+        bytes outputs // u32: how many changes were applied
+    );
+
+    /// @dev Emitted when a result for the QUERY_USAGE_JOB_ID is processed.
+    event JobQueryUsageResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // QueryUsageInput { account: String }
+        bytes outputs // SCALE-encoded AccountUsage: usage, plan, quota left and grant expiry
+    );
+
+    /// @dev Emitted when a result for the PRICE_SCHEDULE_JOB_ID is processed.
+    event JobPriceScheduleResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // No inputs
+        bytes outputs // SCALE-encoded PriceQuote: the current price of access
+    );
+
+    /// @dev Emitted when a result for the SWITCH_UPSTREAM_JOB_ID is processed.
+    event JobSwitchUpstreamResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // SwitchUpstreamInput { proxy_to_url: String, upstreams: Vec<String>, drain_secs: u64 }
+        bytes outputs // SCALE-encoded Option<UpstreamSwitch>: the backends switched from and to
+    );
+
+    /// @dev Emitted when a result for the REMOVE_WEBHOOK_JOB_ID is processed.
+    event JobRemoveWebhookResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // RemoveWebhookInput { url: String }
+        bytes outputs // Should be empty on success
+    );
+
+    /// @dev Emitted when a result for the LIST_WEBHOOKS_JOB_ID is processed.
+    event JobListWebhooksResult(
+        uint64 indexed serviceId,
+        uint64 indexed jobCallId,
+        address indexed operatorAddress,
+        bytes inputs, // ListWebhooksInput { account: Option<String> }
+        bytes outputs // SCALE-encoded Vec<WebhookTarget>: the registered webhooks
+    );
+
     // --- Lifecycle Hooks (Inherited & Overridden) ---
 
     /// @inheritdoc IBlueprintServiceManager
@@ -83,10 +273,48 @@ contract SecureRpcBlueprint is BlueprintServiceManagerBase {
         if (job == ALLOW_ACCESS_JOB_ID) {
             emit JobAllowAccessResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
         } else if (job == PAY_FOR_ACCESS_JOB_ID) {
-            // Note: inputs here are SCALE-encoded { beneficiary: AccountId32, duration_secs: u64 }
+            // Note: inputs here are SCALE-encoded PayForAccessInput { beneficiary: AccessTarget, duration_secs: u64, idempotency_key: Option<String>, payment: Option<PaymentReference>, plan: Option<String> }
             emit JobPayForAccessResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
         } else if (job == REGISTER_WEBHOOK_JOB_ID) {
             emit JobRegisterWebhookResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == ISSUE_API_KEY_JOB_ID) {
+            emit JobIssueApiKeyResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == ACCESS_HISTORY_JOB_ID) {
+            emit JobAccessHistoryResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == REVOKE_ACCESS_JOB_ID) {
+            emit JobRevokeAccessResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == BAN_TARGET_JOB_ID) {
+            emit JobBanTargetResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == REPORT_USAGE_JOB_ID) {
+            emit JobReportUsageResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == USAGE_SUMMARY_JOB_ID) {
+            emit JobUsageSummaryResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == QUERY_RULES_JOB_ID) {
+            emit JobQueryRulesResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == UPDATE_CONFIG_JOB_ID) {
+            emit JobUpdateConfigResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == SET_ACCOUNT_LABELS_JOB_ID) {
+            emit JobSetAccountLabelsResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == PATCH_CONFIG_JOB_ID) {
+            emit JobPatchConfigResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == SET_ACCOUNT_ROLES_JOB_ID) {
+            emit JobSetAccountRolesResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == SET_READ_ONLY_JOB_ID) {
+            emit JobSetReadOnlyResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == EXTEND_ACCESS_JOB_ID) {
+            emit JobExtendAccessResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == SYNC_RULES_JOB_ID) {
+            emit JobSyncRulesResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == QUERY_USAGE_JOB_ID) {
+            emit JobQueryUsageResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == PRICE_SCHEDULE_JOB_ID) {
+            emit JobPriceScheduleResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == SWITCH_UPSTREAM_JOB_ID) {
+            emit JobSwitchUpstreamResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == REMOVE_WEBHOOK_JOB_ID) {
+            emit JobRemoveWebhookResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
+        } else if (job == LIST_WEBHOOKS_JOB_ID) {
+            emit JobListWebhooksResult(serviceId, jobCallId, operatorAddress, inputs, outputs);
         } else {
             revert("SecureRpcBlueprint: Unknown job ID in result");
         }